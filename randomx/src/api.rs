@@ -0,0 +1,192 @@
+use super::memory::{SeedMemory, VmMemory};
+use super::vm::{new_vm, new_vm_normal_pages, Vm};
+
+use std::fmt;
+use std::ops::BitOr;
+use std::sync::Arc;
+
+/// The flags of librandomx (`randomx_flags`), with the same values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RandomXFlags(u32);
+
+impl RandomXFlags {
+    pub const DEFAULT: RandomXFlags = RandomXFlags(0);
+    /// scratchpad on large pages, normal pages are used if the OS does not grant them
+    pub const LARGE_PAGES: RandomXFlags = RandomXFlags(1);
+    /// accepted, the AES implementation is selected at compile time
+    pub const HARD_AES: RandomXFlags = RandomXFlags(2);
+    /// fast mode, needs a dataset
+    pub const FULL_MEM: RandomXFlags = RandomXFlags(4);
+    /// not supported, the VM is an interpreter
+    pub const JIT: RandomXFlags = RandomXFlags(8);
+    /// accepted, only affects the JIT
+    pub const SECURE: RandomXFlags = RandomXFlags(16);
+
+    /// Unknown bits are kept, `contains` ignores them
+    pub fn from_bits(bits: u32) -> RandomXFlags {
+        RandomXFlags(bits)
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(self, other: RandomXFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for RandomXFlags {
+    type Output = RandomXFlags;
+
+    fn bitor(self, other: RandomXFlags) -> RandomXFlags {
+        RandomXFlags(self.0 | other.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RandomXError {
+    /// neither a cache nor a dataset was given
+    MissingCache,
+    /// `FULL_MEM` without a dataset
+    MissingDataset,
+    /// a flag this implementation does not have
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for RandomXError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RandomXError::MissingCache => write!(f, "the VM needs a cache or a dataset"),
+            RandomXError::MissingDataset => write!(f, "FULL_MEM needs a dataset"),
+            RandomXError::Unsupported(flag) => write!(f, "{} is not supported", flag),
+        }
+    }
+}
+
+impl std::error::Error for RandomXError {}
+
+/// The 256 MB cache of a seed (Argon2 memory and superscalar programs), enough for
+/// light mode VMs and to initialise a dataset
+#[derive(Clone)]
+pub struct RandomXCache {
+    seed_memory: Arc<SeedMemory>,
+}
+
+impl RandomXCache {
+    /// Takes about a second, the key is the seed hash (not hex encoded)
+    pub fn new(key: &[u8]) -> RandomXCache {
+        RandomXCache {
+            seed_memory: Arc::new(SeedMemory::new_initialised(key)),
+        }
+    }
+}
+
+/// The 2 GB dataset of a cache, shared by all fast mode VMs of the seed
+#[cfg(feature = "full")]
+#[derive(Clone)]
+pub struct RandomXDataset {
+    pub(crate) memory: Arc<VmMemory>,
+}
+
+#[cfg(feature = "full")]
+impl RandomXDataset {
+    /// Computes all dataset items with the given number of threads
+    pub fn new(cache: &RandomXCache, threads: usize) -> RandomXDataset {
+        let memory = VmMemory::with_dataset(cache.seed_memory.clone());
+        memory.init_dataset(threads);
+        RandomXDataset {
+            memory: Arc::new(memory),
+        }
+    }
+}
+
+/// A VM for hashing, every thread needs its own
+pub struct RandomXVm {
+    vm: Vm,
+}
+
+#[derive(Default)]
+pub struct RandomXVmBuilder {
+    flags: RandomXFlags,
+    cache: Option<RandomXCache>,
+    #[cfg(feature = "full")]
+    dataset: Option<RandomXDataset>,
+}
+
+impl RandomXVm {
+    pub fn builder() -> RandomXVmBuilder {
+        RandomXVmBuilder::default()
+    }
+
+    /// The 32 byte RandomX hash of the input
+    pub fn hash(&mut self, input: &[u8]) -> [u8; 32] {
+        let hash = self.vm.calculate_hash(input);
+        hash.as_bytes().try_into().expect("32 byte hash")
+    }
+
+    /// False if large pages were not requested or not granted
+    pub fn large_pages(&self) -> bool {
+        self.vm.scratchpad.large_pages()
+    }
+
+    pub fn full_mem(&self) -> bool {
+        self.vm.mem.cache
+    }
+}
+
+impl RandomXVmBuilder {
+    pub fn flags(mut self, flags: RandomXFlags) -> RandomXVmBuilder {
+        self.flags = flags;
+        self
+    }
+
+    /// Light mode, every dataset item is computed from the cache
+    pub fn cache(mut self, cache: &RandomXCache) -> RandomXVmBuilder {
+        self.cache = Some(cache.clone());
+        self
+    }
+
+    /// Fast mode, the dataset is used even without `FULL_MEM`
+    #[cfg(feature = "full")]
+    pub fn dataset(mut self, dataset: &RandomXDataset) -> RandomXVmBuilder {
+        self.dataset = Some(dataset.clone());
+        self
+    }
+
+    pub fn build(self) -> Result<RandomXVm, RandomXError> {
+        if self.flags.contains(RandomXFlags::JIT) {
+            return Err(RandomXError::Unsupported("JIT"));
+        }
+        let memory = self.memory()?;
+        let vm = if self.flags.contains(RandomXFlags::LARGE_PAGES) {
+            new_vm(memory)
+        } else {
+            new_vm_normal_pages(memory)
+        };
+        Ok(RandomXVm { vm })
+    }
+
+    #[cfg(feature = "full")]
+    fn memory(&self) -> Result<Arc<VmMemory>, RandomXError> {
+        if let Some(dataset) = &self.dataset {
+            return Ok(dataset.memory.clone());
+        }
+        self.light_memory()
+    }
+
+    #[cfg(not(feature = "full"))]
+    fn memory(&self) -> Result<Arc<VmMemory>, RandomXError> {
+        self.light_memory()
+    }
+
+    fn light_memory(&self) -> Result<Arc<VmMemory>, RandomXError> {
+        if self.flags.contains(RandomXFlags::FULL_MEM) {
+            return Err(RandomXError::MissingDataset);
+        }
+        match &self.cache {
+            Some(cache) => Ok(Arc::new(VmMemory::with_cache(cache.seed_memory.clone()))),
+            None => Err(RandomXError::MissingCache),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! The parameters that RandomX derivatives change, the defaults are the ones of Monero
+//! (rx/0). The other instruction frequencies are the same for all of them.
+
+/// Bytes of the L1 scratchpad, the same for all derivatives
+pub const SCRATCHPAD_L1_BYTES: usize = 16384;
+/// Bytes of a dataset item, a cache line
+pub const DATASET_ITEM_BYTES: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RandomXConfig {
+    /// lanes of the Argon2d that fills the cache
+    pub argon_lanes: u32,
+    pub argon_salt: Vec<u8>,
+    /// KiB of the Argon2d memory, the size of the cache
+    pub argon_memory_kib: u32,
+    pub argon_iterations: u32,
+    /// superscalar programs of the cache, every dataset item runs all of them
+    pub cache_accesses: usize,
+    pub dataset_base_bytes: usize,
+    /// added to the base size, the VM reads the dataset from a random offset in it
+    pub dataset_extra_bytes: usize,
+    /// instructions of a program
+    pub program_size: usize,
+    /// programs per hash
+    pub program_count: usize,
+    /// how often each program is executed
+    pub program_iterations: usize,
+    pub scratchpad_l2_bytes: usize,
+    /// the size of the whole scratchpad
+    pub scratchpad_l3_bytes: usize,
+    /// the frequencies of IROR_R and IROL_R out of 256, their sum is always 10 as the
+    /// opcode ranges of the other instructions do not move
+    pub freq_iror_r: u8,
+    pub freq_irol_r: u8,
+}
+
+impl RandomXConfig {
+    /// rx/0, Monero
+    pub fn monero() -> RandomXConfig {
+        RandomXConfig {
+            argon_lanes: 1,
+            argon_salt: b"RandomX\x03".to_vec(),
+            argon_memory_kib: 262144,
+            argon_iterations: 3,
+            cache_accesses: 8,
+            dataset_base_bytes: 2147483648,
+            dataset_extra_bytes: 33554368,
+            program_size: 256,
+            program_count: 8,
+            program_iterations: 2048,
+            scratchpad_l2_bytes: 262144,
+            scratchpad_l3_bytes: 2097152,
+            freq_iror_r: 8,
+            freq_irol_r: 2,
+        }
+    }
+
+    /// rx/graft, Graft
+    pub fn graft() -> RandomXConfig {
+        RandomXConfig {
+            argon_lanes: 2,
+            argon_salt: b"RandomX-Graft\x01".to_vec(),
+            program_size: 280,
+            freq_iror_r: 7,
+            freq_irol_r: 3,
+            ..RandomXConfig::monero()
+        }
+    }
+
+    /// rx/keva, Kevacoin
+    pub fn keva() -> RandomXConfig {
+        RandomXConfig {
+            argon_salt: b"RandomKV\x01".to_vec(),
+            scratchpad_l2_bytes: 131072,
+            scratchpad_l3_bytes: 1048576,
+            ..RandomXConfig::monero()
+        }
+    }
+
+    /// The problems of a custom parameter set, the limits of the RandomX specification
+    /// and the fixed opcode ranges of this implementation. A valid set can be hashed
+    /// with, though it is only useful if a chain uses it.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: String| {
+            if !ok {
+                problems.push(problem)
+            }
+        };
+        check(
+            self.argon_salt.len() >= 8,
+            format!(
+                "argon_salt has {} bytes, at least 8 are needed",
+                self.argon_salt.len()
+            ),
+        );
+        check(
+            self.argon_lanes >= 1,
+            "argon_lanes has to be at least 1".to_string(),
+        );
+        check(
+            self.argon_memory_kib.is_power_of_two()
+                && self.argon_memory_kib as u64 >= 8 * self.argon_lanes.max(1) as u64,
+            format!(
+                "argon_memory_kib is {}, it has to be a power of 2 and at least 8 per lane",
+                self.argon_memory_kib
+            ),
+        );
+        check(
+            self.argon_iterations >= 1,
+            "argon_iterations has to be at least 1".to_string(),
+        );
+        check(
+            self.cache_accesses >= 2,
+            "cache_accesses has to be at least 2".to_string(),
+        );
+        check(
+            self.dataset_base_bytes.is_power_of_two()
+                && (DATASET_ITEM_BYTES as u64..=1 << 32)
+                    .contains(&(self.dataset_base_bytes as u64)),
+            format!(
+                "dataset_base_bytes is {}, it has to be a power of 2 between 64 and 4 GiB",
+                self.dataset_base_bytes
+            ),
+        );
+        check(
+            self.dataset_extra_bytes.is_multiple_of(DATASET_ITEM_BYTES),
+            format!(
+                "dataset_extra_bytes is {}, it has to be a multiple of 64",
+                self.dataset_extra_bytes
+            ),
+        );
+        check(
+            self.program_size > 0 && self.program_size.is_multiple_of(8),
+            format!(
+                "program_size is {}, it has to be a positive multiple of 8",
+                self.program_size
+            ),
+        );
+        check(
+            self.program_count >= 1,
+            "program_count has to be at least 1".to_string(),
+        );
+        check(
+            self.program_iterations >= 1,
+            "program_iterations has to be at least 1".to_string(),
+        );
+        check(
+            self.scratchpad_l2_bytes.is_power_of_two()
+                && self.scratchpad_l2_bytes >= SCRATCHPAD_L1_BYTES,
+            format!(
+                "scratchpad_l2_bytes is {}, it has to be a power of 2 and at least {}",
+                self.scratchpad_l2_bytes, SCRATCHPAD_L1_BYTES
+            ),
+        );
+        check(
+            self.scratchpad_l3_bytes.is_power_of_two()
+                && self.scratchpad_l3_bytes >= self.scratchpad_l2_bytes,
+            format!(
+                "scratchpad_l3_bytes is {}, it has to be a power of 2 and at least scratchpad_l2_bytes",
+                self.scratchpad_l3_bytes
+            ),
+        );
+        check(
+            self.freq_iror_r as u32 + self.freq_irol_r as u32 == 10,
+            format!(
+                "freq_iror_r and freq_irol_r are {} and {}, their sum has to be 10",
+                self.freq_iror_r, self.freq_irol_r
+            ),
+        );
+        problems
+    }
+
+    /// Bytes of the Argon2d memory
+    pub fn cache_bytes(&self) -> u64 {
+        self.argon_memory_kib as u64 * 1024
+    }
+
+    pub fn dataset_bytes(&self) -> u64 {
+        (self.dataset_base_bytes + self.dataset_extra_bytes) as u64
+    }
+
+    pub fn dataset_item_count(&self) -> usize {
+        (self.dataset_base_bytes + self.dataset_extra_bytes) / DATASET_ITEM_BYTES
+    }
+
+    pub fn dataset_extra_items(&self) -> u64 {
+        (self.dataset_extra_bytes / DATASET_ITEM_BYTES) as u64
+    }
+
+    /// Aligns a dataset address to an item within the base size
+    pub fn dataset_base_mask(&self) -> u64 {
+        ((self.dataset_base_bytes - 1) & !(DATASET_ITEM_BYTES - 1)) as u64
+    }
+
+    pub fn scratchpad_l2_mask(&self) -> u64 {
+        (self.scratchpad_l2_bytes - 8) as u64
+    }
+
+    pub fn scratchpad_l3_mask(&self) -> u64 {
+        (self.scratchpad_l3_bytes - 8) as u64
+    }
+
+    /// The 64 bit words of the scratchpad
+    pub fn scratchpad_words(&self) -> usize {
+        self.scratchpad_l3_bytes / 8
+    }
+}
+
+impl Default for RandomXConfig {
+    fn default() -> RandomXConfig {
+        RandomXConfig::monero()
+    }
+}
@@ -0,0 +1,178 @@
+//! Cache hits, misses and the latency of `VmMemory::dataset_read`. A hit is a dataset
+//! item that was already computed, in light mode every read is a miss. The reads are
+//! only counted and timed with the dataset-stats feature, to validate changes of the
+//! memory subsystem on real rigs, without it the dataset reads do not touch the stats.
+//! Every thread counts on its own and adds its counts to the totals every `FLUSH_READS`
+//! reads and when it ends, only every `SAMPLE_EVERY`th read is timed.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+#[cfg(feature = "dataset-stats")]
+use std::time::Instant;
+
+/// Bucket `i` holds the latencies of up to 2^i ns, the last one everything above
+pub const LATENCY_BUCKETS: usize = 32;
+#[cfg(feature = "dataset-stats")]
+const SAMPLE_EVERY: u64 = 16;
+#[cfg(feature = "dataset-stats")]
+const FLUSH_READS: u64 = 1 << 14;
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+static LATENCY: [AtomicU64; LATENCY_BUCKETS] = [const { AtomicU64::new(0) }; LATENCY_BUCKETS];
+
+thread_local! {
+    static LOCAL: RefCell<Local> = RefCell::new(Local::default());
+}
+
+/// Counts of the dataset reads
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// sampled read latencies per bucket, see `LATENCY_BUCKETS`
+    pub latency: [u64; LATENCY_BUCKETS],
+}
+
+impl Default for DatasetStats {
+    fn default() -> Self {
+        DatasetStats { hits: 0, misses: 0, latency: [0; LATENCY_BUCKETS] }
+    }
+}
+
+impl DatasetStats {
+    pub fn reads(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    /// Fraction of the reads of an already computed item, None without reads
+    pub fn hit_rate(&self) -> Option<f64> {
+        match self.reads() {
+            0 => None,
+            reads => Some(self.hits as f64 / reads as f64),
+        }
+    }
+
+    /// Upper bound of the bucket with the fraction `p` (0.0 - 1.0) of the sampled
+    /// latencies, so at most twice the latency. None without samples.
+    pub fn latency_percentile(&self, p: f64) -> Option<Duration> {
+        let total: u64 = self.latency.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((p * total as f64).ceil() as u64).clamp(1, total);
+        let mut below = 0;
+        for (bucket, count) in self.latency.iter().enumerate() {
+            below += count;
+            if below >= rank {
+                return Some(Duration::from_nanos(1 << bucket));
+            }
+        }
+        None
+    }
+
+    /// The counts since `earlier`, a snapshot of the same totals
+    pub fn since(&self, earlier: &DatasetStats) -> DatasetStats {
+        let mut latency = [0; LATENCY_BUCKETS];
+        for (bucket, count) in latency.iter_mut().enumerate() {
+            *count = self.latency[bucket].saturating_sub(earlier.latency[bucket]);
+        }
+        DatasetStats {
+            hits: self.hits.saturating_sub(earlier.hits),
+            misses: self.misses.saturating_sub(earlier.misses),
+            latency,
+        }
+    }
+}
+
+/// The bucket of a latency
+pub fn latency_bucket(latency: Duration) -> usize {
+    let nanos = latency.as_nanos().max(1);
+    //the smallest i with nanos <= 2^i
+    let bucket = (u128::BITS - (nanos - 1).leading_zeros()) as usize;
+    bucket.min(LATENCY_BUCKETS - 1)
+}
+
+/// True if the reads are counted and timed, i.e. mithril-randomx was built with the
+/// dataset-stats feature
+pub fn timed() -> bool {
+    cfg!(feature = "dataset-stats")
+}
+
+/// The totals of all threads, without the reads a thread has not added yet
+pub fn snapshot() -> DatasetStats {
+    let mut latency = [0; LATENCY_BUCKETS];
+    for (bucket, count) in latency.iter_mut().enumerate() {
+        *count = LATENCY[bucket].load(Ordering::Relaxed);
+    }
+    DatasetStats { hits: HITS.load(Ordering::Relaxed), misses: MISSES.load(Ordering::Relaxed), latency }
+}
+
+/// Adds the counts of the calling thread to the totals
+pub fn flush() {
+    LOCAL.with(|local| add_to_totals(&mut local.borrow_mut().0));
+}
+
+fn add_to_totals(local: &mut DatasetStats) {
+    HITS.fetch_add(local.hits, Ordering::Relaxed);
+    MISSES.fetch_add(local.misses, Ordering::Relaxed);
+    for (bucket, count) in local.latency.iter().enumerate() {
+        if *count > 0 {
+            LATENCY[bucket].fetch_add(*count, Ordering::Relaxed);
+        }
+    }
+    *local = DatasetStats::default();
+}
+
+/// The counts of a thread, added to the totals when the thread ends
+#[derive(Default)]
+struct Local(DatasetStats);
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        add_to_totals(&mut self.0);
+    }
+}
+
+/// Measures one dataset read
+pub(crate) struct ReadTimer {
+    #[cfg(feature = "dataset-stats")]
+    started: Option<Instant>,
+}
+
+impl ReadTimer {
+    #[inline(always)]
+    pub(crate) fn start() -> ReadTimer {
+        #[cfg(feature = "dataset-stats")]
+        {
+            let sample = LOCAL.with(|local| local.borrow().0.reads() % SAMPLE_EVERY == 0);
+            ReadTimer { started: sample.then(Instant::now) }
+        }
+        #[cfg(not(feature = "dataset-stats"))]
+        ReadTimer {}
+    }
+
+    #[cfg(feature = "dataset-stats")]
+    #[inline(always)]
+    pub(crate) fn finish(self, hit: bool) {
+        LOCAL.with(|local| {
+            let local = &mut local.borrow_mut().0;
+            if hit {
+                local.hits += 1;
+            } else {
+                local.misses += 1;
+            }
+            if let Some(started) = self.started {
+                local.latency[latency_bucket(started.elapsed())] += 1;
+            }
+            if local.reads() >= FLUSH_READS {
+                add_to_totals(local);
+            }
+        });
+    }
+
+    #[cfg(not(feature = "dataset-stats"))]
+    #[inline(always)]
+    pub(crate) fn finish(self, _hit: bool) {}
+}
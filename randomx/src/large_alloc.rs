@@ -0,0 +1,496 @@
+#[cfg(unix)]
+extern crate libc;
+
+use super::pages;
+use super::pages::LargeBuffer;
+
+use std::alloc::{self as heap, Layout};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::{Arc, RwLock};
+use strum::{Display, EnumString};
+
+/// The alignment of all large buffers
+const CACHE_LINE_SIZE: usize = 64;
+
+/// Allocator of the large buffers: the dataset, the cache and the scratchpads. Processes
+/// with their own memory management implement it and pass it to `set_allocators`.
+pub trait LargeAlloc: Send + Sync {
+    /// `bytes` of memory, aligned to at least a cache line (64 bytes)
+    fn alloc(&self, bytes: usize) -> io::Result<NonNull<u8>>;
+
+    /// Frees the memory of an `alloc` call
+    ///
+    /// # Safety
+    ///
+    /// `ptr` was returned by `alloc` of this allocator for the same `bytes` and is not used
+    /// afterwards.
+    unsafe fn free(&self, ptr: NonNull<u8>, bytes: usize);
+
+    /// The memory is on large pages, they are counted in the stats
+    fn large_pages(&self) -> bool {
+        false
+    }
+
+    /// The memory is mapped by other processes too, it is not wiped when it is freed
+    fn shared(&self) -> bool {
+        false
+    }
+
+    /// Used in the log and in errors
+    fn name(&self) -> &str {
+        "custom"
+    }
+}
+
+/// The built-in allocators, selectable by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum AllocKind {
+    Malloc,
+    Mmap,
+    HugeTlb,
+    Shm,
+}
+
+impl AllocKind {
+    pub fn allocator(self) -> Arc<dyn LargeAlloc> {
+        match self {
+            AllocKind::Malloc => Arc::new(Malloc),
+            AllocKind::Mmap => Arc::new(Mmap),
+            AllocKind::HugeTlb => Arc::new(HugeTlb),
+            AllocKind::Shm => Arc::new(Shm),
+        }
+    }
+}
+
+/// The allocator of each kind of large buffer. A buffer is allocated on the heap if its
+/// allocator fails.
+#[derive(Clone)]
+pub struct Allocators {
+    /// the 2 GB dataset of the fast mode
+    pub dataset: Arc<dyn LargeAlloc>,
+    /// the 256 MB Argon2 cache of a seed
+    pub cache: Arc<dyn LargeAlloc>,
+    /// the 2 MB scratchpad of every VM
+    pub scratchpad: Arc<dyn LargeAlloc>,
+}
+
+impl Allocators {
+    /// The same allocator for all buffers
+    pub fn all(alloc: Arc<dyn LargeAlloc>) -> Allocators {
+        Allocators {
+            dataset: alloc.clone(),
+            cache: alloc.clone(),
+            scratchpad: alloc,
+        }
+    }
+}
+
+/// Large pages for the scratchpads, where they save the most TLB misses. The dataset is
+/// advised as transparent huge pages instead, it would need 1040 reserved pages.
+#[cfg(not(feature = "guard-pages"))]
+impl Default for Allocators {
+    fn default() -> Allocators {
+        Allocators {
+            dataset: Arc::new(Malloc),
+            cache: Arc::new(Malloc),
+            scratchpad: Arc::new(HugeTlb),
+        }
+    }
+}
+
+/// Guard pages around all buffers, for debugging the VM
+#[cfg(feature = "guard-pages")]
+impl Default for Allocators {
+    fn default() -> Allocators {
+        Allocators::all(Arc::new(Guarded))
+    }
+}
+
+/// None until `set_allocators` is called, the default allocators are used
+static ALLOCATORS: RwLock<Option<Allocators>> = RwLock::new(None);
+
+/// Sets the allocators of the buffers allocated from now on, the existing buffers are
+/// freed by the allocator they were allocated with
+pub fn set_allocators(allocators: Allocators) {
+    *ALLOCATORS.write().unwrap_or_else(|err| err.into_inner()) = Some(allocators);
+}
+
+/// The allocators of new buffers
+pub fn allocators() -> Allocators {
+    ALLOCATORS
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+/// The global allocator of the process, i.e. malloc
+pub struct Malloc;
+
+impl LargeAlloc for Malloc {
+    fn alloc(&self, bytes: usize) -> io::Result<NonNull<u8>> {
+        let layout = heap_layout(bytes)?;
+        NonNull::new(unsafe { heap::alloc(layout) }).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!("could not allocate {} bytes", bytes),
+            )
+        })
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, bytes: usize) {
+        if let Ok(layout) = heap_layout(bytes) {
+            heap::dealloc(ptr.as_ptr(), layout);
+        }
+    }
+
+    fn name(&self) -> &str {
+        "malloc"
+    }
+}
+
+pub(crate) fn heap_layout(bytes: usize) -> io::Result<Layout> {
+    if bytes == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot allocate 0 bytes",
+        ));
+    }
+    Layout::from_size_align(bytes, CACHE_LINE_SIZE)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+/// Anonymous private mappings, page aligned and returned to the OS when freed
+pub struct Mmap;
+
+impl LargeAlloc for Mmap {
+    fn alloc(&self, bytes: usize) -> io::Result<NonNull<u8>> {
+        map_anonymous(bytes)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, bytes: usize) {
+        unmap(ptr, bytes);
+    }
+
+    fn name(&self) -> &str {
+        "mmap"
+    }
+}
+
+/// The large pages of the OS: hugetlb pages on Linux (reserved with `vm.nr_hugepages`),
+/// superpages on FreeBSD and Intel Macs and large pages on Windows
+pub struct HugeTlb;
+
+impl LargeAlloc for HugeTlb {
+    fn alloc(&self, bytes: usize) -> io::Result<NonNull<u8>> {
+        let ptr = pages::alloc_large(pages::large_page_bytes(bytes))?;
+        NonNull::new(ptr).ok_or_else(|| io::Error::other("the large pages are at address 0"))
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, bytes: usize) {
+        pages::free_large(ptr.as_ptr(), pages::large_page_bytes(bytes));
+    }
+
+    fn large_pages(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> &str {
+        "hugetlb"
+    }
+}
+
+/// POSIX shared memory (`shm_open`), i.e. in `/dev/shm` on Linux, for processes whose
+/// heap is limited or accounted separately. The object is unlinked right away, so it
+/// is freed with the mapping.
+pub struct Shm;
+
+impl LargeAlloc for Shm {
+    fn alloc(&self, bytes: usize) -> io::Result<NonNull<u8>> {
+        map_shared(bytes)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, bytes: usize) {
+        unmap(ptr, bytes);
+    }
+
+    fn name(&self) -> &str {
+        "shm"
+    }
+}
+
+/// Anonymous mappings with an inaccessible page before and after the buffer, an access
+/// out of its bounds faults instead of corrupting other memory. The end of the buffer
+/// (rounded up to a cache line) is the start of the trailing guard page.
+pub struct Guarded;
+
+impl Guarded {
+    /// The offset of the buffer in the mapping and the size of the mapping
+    fn layout(bytes: usize) -> (usize, usize) {
+        let page = page_size();
+        let buffer = bytes.next_multiple_of(CACHE_LINE_SIZE);
+        let pages = buffer.next_multiple_of(page);
+        (page + pages - buffer, pages + 2 * page)
+    }
+}
+
+impl LargeAlloc for Guarded {
+    fn alloc(&self, bytes: usize) -> io::Result<NonNull<u8>> {
+        let (offset, mapped) = Guarded::layout(bytes);
+        let base = map_anonymous(mapped)?;
+        let page = page_size();
+        let guards = [base.as_ptr(), unsafe { base.as_ptr().add(mapped - page) }];
+        if let Err(err) = guards.iter().try_for_each(|guard| protect(*guard, page)) {
+            unmap(base, mapped);
+            return Err(err);
+        }
+        Ok(unsafe { base.add(offset) })
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, bytes: usize) {
+        let (offset, mapped) = Guarded::layout(bytes);
+        unmap(ptr.sub(offset), mapped);
+    }
+
+    fn name(&self) -> &str {
+        "guarded"
+    }
+}
+
+/// A file that other processes map with `map_file`, i.e. in `/dev/shm`. Allocates one
+/// buffer, the file is removed when it is freed. The processes that mapped it keep their
+/// mapping.
+pub struct SharedFile {
+    path: PathBuf,
+}
+
+impl SharedFile {
+    pub fn new(path: impl Into<PathBuf>) -> SharedFile {
+        SharedFile { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl LargeAlloc for SharedFile {
+    fn alloc(&self, bytes: usize) -> io::Result<NonNull<u8>> {
+        map_new_file(&self.path, bytes)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, bytes: usize) {
+        unmap(ptr, bytes);
+        let _ = std::fs::remove_file(&self.path);
+    }
+
+    fn shared(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> &str {
+        "file"
+    }
+}
+
+/// The mapping of `map_file`, it is only freed
+struct FileMapping;
+
+impl LargeAlloc for FileMapping {
+    fn alloc(&self, _bytes: usize) -> io::Result<NonNull<u8>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "file mappings are created by map_file",
+        ))
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, bytes: usize) {
+        unmap(ptr, bytes);
+    }
+
+    fn shared(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> &str {
+        "mapping"
+    }
+}
+
+/// Maps a file of a `SharedFile` copy on write, the pages are shared with the other
+/// processes until they are written. Fails if the file does not have `len` values.
+///
+/// # Safety
+///
+/// The file holds `len` valid values of `T`, written by a process of the same build.
+pub unsafe fn map_file<T>(path: &Path, len: usize) -> io::Result<LargeBuffer<T>> {
+    let bytes = len * std::mem::size_of::<T>();
+    let file = std::fs::File::open(path)?;
+    let file_bytes = file.metadata()?.len();
+    if file_bytes != bytes as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} has {} bytes instead of {}",
+                path.display(),
+                file_bytes,
+                bytes
+            ),
+        ));
+    }
+    if bytes == 0 {
+        return Ok(LargeBuffer::empty());
+    }
+    let ptr = map_private_file(&file, bytes)?;
+    Ok(LargeBuffer::from_raw(ptr, len, Arc::new(FileMapping)))
+}
+
+#[cfg(unix)]
+fn map_new_file(path: &Path, bytes: usize) -> io::Result<NonNull<u8>> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    //readable by the miners of other users, the dataset is derived from a public seed
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o644)
+        .open(path)?;
+    file.set_len(bytes as u64)?;
+    map(bytes, libc::MAP_SHARED, file.as_raw_fd())
+}
+
+#[cfg(unix)]
+fn map_private_file(file: &std::fs::File, bytes: usize) -> io::Result<NonNull<u8>> {
+    use std::os::unix::io::AsRawFd;
+    map(bytes, libc::MAP_PRIVATE, file.as_raw_fd())
+}
+
+#[cfg(unix)]
+fn map_anonymous(bytes: usize) -> io::Result<NonNull<u8>> {
+    map(bytes, libc::MAP_PRIVATE | libc::MAP_ANON, -1)
+}
+
+#[cfg(unix)]
+fn map_shared(bytes: usize) -> io::Result<NonNull<u8>> {
+    use std::ffi::CString;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static OBJECTS: AtomicU64 = AtomicU64::new(0);
+    let name = format!(
+        "/mithril-{}-{}",
+        std::process::id(),
+        OBJECTS.fetch_add(1, Ordering::Relaxed)
+    );
+    let name = CString::new(name).expect("shm name without nul");
+    let flags = libc::O_RDWR | libc::O_CREAT | libc::O_EXCL;
+    //the mode is a variadic argument on macOS
+    #[cfg(target_os = "macos")]
+    let fd = unsafe { libc::shm_open(name.as_ptr(), flags, 0o600 as libc::c_uint) };
+    #[cfg(not(target_os = "macos"))]
+    let fd = unsafe { libc::shm_open(name.as_ptr(), flags, 0o600 as libc::mode_t) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe { libc::shm_unlink(name.as_ptr()) };
+    let result = if unsafe { libc::ftruncate(fd, bytes as libc::off_t) } != 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        map(bytes, libc::MAP_SHARED, fd)
+    };
+    //the mapping keeps the object alive
+    unsafe { libc::close(fd) };
+    result
+}
+
+#[cfg(unix)]
+fn map(bytes: usize, flags: libc::c_int, fd: libc::c_int) -> io::Result<NonNull<u8>> {
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            bytes,
+            libc::PROT_READ | libc::PROT_WRITE,
+            flags,
+            fd,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    NonNull::new(ptr as *mut u8).ok_or_else(|| io::Error::other("mapped at address 0"))
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Makes the page inaccessible
+#[cfg(unix)]
+fn protect(ptr: *mut u8, bytes: usize) -> io::Result<()> {
+    if unsafe { libc::mprotect(ptr as *mut libc::c_void, bytes, libc::PROT_NONE) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn unmap(ptr: NonNull<u8>, bytes: usize) {
+    unsafe {
+        libc::munmap(ptr.as_ptr() as *mut libc::c_void, bytes);
+    }
+}
+
+#[cfg(not(unix))]
+fn map_anonymous(_bytes: usize) -> io::Result<NonNull<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "mmap is only available on Unix",
+    ))
+}
+
+#[cfg(not(unix))]
+fn map_shared(_bytes: usize) -> io::Result<NonNull<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "shm_open is only available on Unix",
+    ))
+}
+
+#[cfg(not(unix))]
+fn map_new_file(_path: &Path, _bytes: usize) -> io::Result<NonNull<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "shared files are only available on Unix",
+    ))
+}
+
+#[cfg(not(unix))]
+fn map_private_file(_file: &std::fs::File, _bytes: usize) -> io::Result<NonNull<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "file mappings are only available on Unix",
+    ))
+}
+
+#[cfg(not(unix))]
+fn page_size() -> usize {
+    4096
+}
+
+#[cfg(not(unix))]
+fn protect(_ptr: *mut u8, _bytes: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "guard pages are only available on Unix",
+    ))
+}
+
+#[cfg(not(unix))]
+fn unmap(_ptr: NonNull<u8>, _bytes: usize) {}
@@ -0,0 +1,47 @@
+//! RandomX hashing of the mithril miner, without the mining parts (stratum, workers,
+//! logging). The `full` feature (default) adds the 2 GB dataset of the fast mode,
+//! without it all VMs run in light mode.
+//!
+//! `RandomXCache`, `RandomXDataset` and `RandomXVm` mirror the API of librandomx. A
+//! light mode VM:
+//!
+//! ```no_run
+//! use mithril_randomx::{RandomXCache, RandomXFlags, RandomXVm};
+//!
+//! let cache = RandomXCache::new(b"seed hash");
+//! let mut vm = RandomXVm::builder()
+//!     .cache(&cache)
+//!     .flags(RandomXFlags::LARGE_PAGES)
+//!     .build()
+//!     .unwrap();
+//! let hash = vm.hash(b"block blob");
+//! ```
+//!
+//! For the fast mode `RandomXDataset::new(&cache, threads)` computes the dataset, it is
+//! passed with `.dataset(&dataset)` and `RandomXFlags::FULL_MEM`.
+
+pub mod api;
+pub mod common;
+pub mod config;
+pub mod dataset_stats;
+pub mod hash;
+pub mod large_alloc;
+pub mod m128;
+pub mod memory;
+pub mod pages;
+pub mod program;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "full")]
+pub mod randomx_rs;
+pub mod rounding;
+pub mod soft_aes;
+pub mod superscalar;
+pub mod vm;
+pub mod wipe;
+
+#[cfg(feature = "full")]
+pub use api::RandomXDataset;
+pub use config::RandomXConfig;
+pub use api::{RandomXCache, RandomXError, RandomXFlags, RandomXVm, RandomXVmBuilder};
+pub use superscalar::{Blake2Generator, ScProgram};
@@ -0,0 +1,436 @@
+#[cfg(not(target_arch = "x86_64"))]
+use super::rounding;
+#[cfg(not(all(target_arch = "aarch64", target_feature = "aes")))]
+#[cfg(not(target_arch = "x86_64"))]
+use super::soft_aes;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::{
+    __m128d, __m128i, _mm_add_pd, _mm_aesdec_si128, _mm_aesenc_si128, _mm_and_pd, _mm_cmpeq_epi32,
+    _mm_cmpeq_pd, _mm_cvtepi32_pd, _mm_div_pd, _mm_extract_epi64, _mm_movemask_epi8,
+    _mm_movemask_pd, _mm_mul_pd, _mm_or_pd, _mm_set_epi32, _mm_set_epi64x, _mm_set_pd,
+    _mm_shuffle_pd, _mm_sqrt_pd, _mm_store_sd, _mm_storeh_pd, _mm_sub_pd, _mm_xor_pd,
+};
+use std::convert::TryInto;
+use std::fmt;
+
+#[allow(nonstandard_style)]
+#[derive(Copy, Clone)]
+#[cfg(target_arch = "x86_64")]
+pub struct m128i(pub __m128i);
+
+/// the low 64 bits first
+#[allow(nonstandard_style)]
+#[derive(Copy, Clone)]
+#[cfg(not(target_arch = "x86_64"))]
+pub struct m128i(pub [u64; 2]);
+
+impl m128i {
+    pub fn zero() -> m128i {
+        m128i::from_i32(0, 0, 0, 0)
+    }
+    pub fn from_u8(bytes: &[u8]) -> m128i {
+        debug_assert_eq!(bytes.len(), 16);
+
+        let u0 = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let u1 = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+
+        m128i::from_u64(u1, u0)
+    }
+
+    pub fn as_m128d(&self) -> m128d {
+        let (i1, i0) = self.as_i64();
+        m128d::from_u64(i1 as u64, i0 as u64)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl m128i {
+    pub fn from_i32(i3: i32, i2: i32, i1: i32, i0: i32) -> m128i {
+        unsafe { m128i(_mm_set_epi32(i3, i2, i1, i0)) }
+    }
+    pub fn from_u64(u1: u64, u0: u64) -> m128i {
+        unsafe { m128i(_mm_set_epi64x(u1 as i64, u0 as i64)) }
+    }
+    pub fn aesdec(&self, key: m128i) -> m128i {
+        unsafe { m128i(_mm_aesdec_si128(self.0, key.0)) }
+    }
+    pub fn aesenc(&self, key: m128i) -> m128i {
+        unsafe { m128i(_mm_aesenc_si128(self.0, key.0)) }
+    }
+    pub fn as_i64(&self) -> (i64, i64) {
+        unsafe {
+            let p1 = _mm_extract_epi64(self.0, 1);
+            let p2 = _mm_extract_epi64(self.0, 0);
+            (p1, p2)
+        }
+    }
+
+    //_mm_cvtepi32_pd
+    pub fn lower_to_m128d(&self) -> m128d {
+        unsafe { m128d(_mm_cvtepi32_pd(self.0)) }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl PartialEq for m128i {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe {
+            let test = _mm_cmpeq_epi32(self.0, other.0);
+            _mm_movemask_epi8(test) == 0xffff
+        }
+    }
+}
+
+impl Eq for m128i {}
+
+fn format_m128i(m: &m128i, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (low, high) = m.as_i64();
+    f.write_fmt(format_args!("({:x},{:x})", high, low))
+}
+
+impl fmt::LowerHex for m128i {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_m128i(self, f)
+    }
+}
+
+impl fmt::Debug for m128i {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_m128i(self, f)
+    }
+}
+
+//==== m128d
+
+#[allow(nonstandard_style)]
+#[derive(Copy, Clone)]
+#[cfg(target_arch = "x86_64")]
+pub struct m128d(pub __m128d);
+
+/// the low f64 first
+#[allow(nonstandard_style)]
+#[derive(Copy, Clone)]
+#[cfg(not(target_arch = "x86_64"))]
+pub struct m128d(pub [f64; 2]);
+
+impl m128d {
+    pub fn zero() -> m128d {
+        m128d::from_f64(0.0, 0.0)
+    }
+    pub fn from_u64(h: u64, l: u64) -> m128d {
+        m128d::from_f64(f64::from_bits(h), f64::from_bits(l))
+    }
+
+    pub fn as_u64(&self) -> (u64, u64) {
+        let (f1, f0) = self.as_f64();
+        (f1.to_bits(), f0.to_bits())
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl m128d {
+    pub fn from_f64(h: f64, l: f64) -> m128d {
+        unsafe { m128d(_mm_set_pd(h, l)) }
+    }
+    pub fn as_f64(&self) -> (f64, f64) {
+        let mut f1: f64 = 0.0;
+        let mut f2: f64 = 0.0;
+        let f1_ptr: *mut f64 = &mut f1;
+        let f2_ptr: *mut f64 = &mut f2;
+        unsafe {
+            _mm_storeh_pd(f1_ptr, self.0);
+            _mm_store_sd(f2_ptr, self.0);
+        }
+        (f1, f2)
+    }
+
+    //_mm_shuffle_pd(a, b, 1)
+    pub fn shuffle_1(&self, other: &m128d) -> m128d {
+        unsafe { m128d(_mm_shuffle_pd(self.0, other.0, 1)) }
+    }
+
+    //_mm_sqrt_pd
+    pub fn sqrt(&self) -> m128d {
+        unsafe { m128d(_mm_sqrt_pd(self.0)) }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl PartialEq for m128d {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe {
+            let test = _mm_cmpeq_pd(self.0, other.0);
+            let mask = _mm_movemask_pd(test);
+            mask == 0b11
+        }
+    }
+}
+impl Eq for m128d {}
+
+#[cfg(target_arch = "x86_64")]
+impl std::ops::Add for m128d {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        unsafe { m128d(_mm_add_pd(self.0, other.0)) }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl std::ops::Sub for m128d {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        unsafe { m128d(_mm_sub_pd(self.0, other.0)) }
+    }
+}
+
+fn format_m128d(m: &m128d, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (low, high) = m.as_f64();
+    f.write_fmt(format_args!("({},{})", low, high))
+}
+
+impl fmt::LowerHex for m128d {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (low, high) = self.as_f64();
+        f.write_fmt(format_args!("({:x},{:x})", high.to_bits(), low.to_bits()))
+    }
+}
+
+impl fmt::Debug for m128d {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_m128d(self, f)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl std::ops::BitXor for m128d {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        unsafe { m128d(_mm_xor_pd(self.0, rhs.0)) }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl std::ops::BitAnd for m128d {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        unsafe { m128d(_mm_and_pd(self.0, rhs.0)) }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl std::ops::BitOr for m128d {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        unsafe { m128d(_mm_or_pd(self.0, rhs.0)) }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl std::ops::Mul for m128d {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        unsafe { m128d(_mm_mul_pd(self.0, rhs.0)) }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl std::ops::Div for m128d {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        unsafe { m128d(_mm_div_pd(self.0, rhs.0)) }
+    }
+}
+
+//==== portable, for CPUs without SSE (aarch64, wasm32)
+
+#[cfg(not(target_arch = "x86_64"))]
+impl m128i {
+    pub fn from_i32(i3: i32, i2: i32, i1: i32, i0: i32) -> m128i {
+        m128i([
+            (i1 as u32 as u64) << 32 | i0 as u32 as u64,
+            (i3 as u32 as u64) << 32 | i2 as u32 as u64,
+        ])
+    }
+    pub fn from_u64(u1: u64, u0: u64) -> m128i {
+        m128i([u0, u1])
+    }
+
+    #[cfg(all(target_arch = "aarch64", target_feature = "aes"))]
+    pub fn aesdec(&self, key: m128i) -> m128i {
+        use std::arch::aarch64::{vaesdq_u8, vaesimcq_u8, vdupq_n_u8, veorq_u8};
+        unsafe {
+            let state = std::mem::transmute::<[u64; 2], _>(self.0);
+            let round = vaesimcq_u8(vaesdq_u8(state, vdupq_n_u8(0)));
+            m128i(std::mem::transmute(veorq_u8(
+                round,
+                std::mem::transmute::<[u64; 2], _>(key.0),
+            )))
+        }
+    }
+    #[cfg(all(target_arch = "aarch64", target_feature = "aes"))]
+    pub fn aesenc(&self, key: m128i) -> m128i {
+        use std::arch::aarch64::{vaeseq_u8, vaesmcq_u8, vdupq_n_u8, veorq_u8};
+        unsafe {
+            let state = std::mem::transmute::<[u64; 2], _>(self.0);
+            let round = vaesmcq_u8(vaeseq_u8(state, vdupq_n_u8(0)));
+            m128i(std::mem::transmute(veorq_u8(
+                round,
+                std::mem::transmute::<[u64; 2], _>(key.0),
+            )))
+        }
+    }
+
+    #[cfg(not(all(target_arch = "aarch64", target_feature = "aes")))]
+    pub fn aesdec(&self, key: m128i) -> m128i {
+        m128i::from_u8(&soft_aes::aesdec(self.to_bytes(), key.to_bytes()))
+    }
+    #[cfg(not(all(target_arch = "aarch64", target_feature = "aes")))]
+    pub fn aesenc(&self, key: m128i) -> m128i {
+        m128i::from_u8(&soft_aes::aesenc(self.to_bytes(), key.to_bytes()))
+    }
+    #[cfg(not(all(target_arch = "aarch64", target_feature = "aes")))]
+    fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&self.0[0].to_le_bytes());
+        bytes[8..].copy_from_slice(&self.0[1].to_le_bytes());
+        bytes
+    }
+
+    pub fn as_i64(&self) -> (i64, i64) {
+        (self.0[1] as i64, self.0[0] as i64)
+    }
+
+    pub fn lower_to_m128d(&self) -> m128d {
+        let low = self.0[0];
+        m128d::from_f64((low >> 32) as u32 as i32 as f64, low as u32 as i32 as f64)
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl PartialEq for m128i {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl m128d {
+    pub fn from_f64(h: f64, l: f64) -> m128d {
+        m128d([l, h])
+    }
+    pub fn as_f64(&self) -> (f64, f64) {
+        (self.0[1], self.0[0])
+    }
+
+    pub fn shuffle_1(&self, other: &m128d) -> m128d {
+        m128d([self.0[1], other.0[0]])
+    }
+
+    pub fn sqrt(&self) -> m128d {
+        let mode = rounding_mode();
+        m128d([
+            rounding::sqrt(self.0[0], mode),
+            rounding::sqrt(self.0[1], mode),
+        ])
+    }
+
+    fn map(self, rhs: m128d, f: impl Fn(f64, f64) -> f64) -> m128d {
+        m128d([f(self.0[0], rhs.0[0]), f(self.0[1], rhs.0[1])])
+    }
+
+    fn map_bits(self, rhs: m128d, f: impl Fn(u64, u64) -> u64) -> m128d {
+        self.map(rhs, |a, b| f64::from_bits(f(a.to_bits(), b.to_bits())))
+    }
+}
+
+//aarch64 rounds in hardware (FPCR), wasm32 has no rounding control
+#[cfg(target_arch = "aarch64")]
+fn rounding_mode() -> u32 {
+    rounding::ROUND_TO_NEAREST
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn rounding_mode() -> u32 {
+    rounding::mode()
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl PartialEq for m128d {
+    fn eq(&self, other: &Self) -> bool {
+        self.0[0] == other.0[0] && self.0[1] == other.0[1]
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl std::ops::Add for m128d {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        let mode = rounding_mode();
+        self.map(rhs, |a, b| rounding::add(a, b, mode))
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl std::ops::Sub for m128d {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        let mode = rounding_mode();
+        self.map(rhs, |a, b| rounding::sub(a, b, mode))
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl std::ops::Mul for m128d {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let mode = rounding_mode();
+        self.map(rhs, |a, b| rounding::mul(a, b, mode))
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl std::ops::Div for m128d {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let mode = rounding_mode();
+        self.map(rhs, |a, b| rounding::div(a, b, mode))
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl std::ops::BitXor for m128d {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self {
+        self.map_bits(rhs, |a, b| a ^ b)
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl std::ops::BitAnd for m128d {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        self.map_bits(rhs, |a, b| a & b)
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+impl std::ops::BitOr for m128d {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.map_bits(rhs, |a, b| a | b)
+    }
+}
@@ -0,0 +1,792 @@
+use argon2::{Algorithm, Argon2, Block, ParamsBuilder, Version};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::{
+    _mm_prefetch,
+    _MM_HINT_NTA
+};
+
+#[cfg(feature = "full")]
+use super::pages;
+use super::config::RandomXConfig;
+use super::dataset_stats::ReadTimer;
+use super::large_alloc::{allocators, LargeAlloc};
+use super::pages::{BufferCount, LargeBuffer, NumaPolicy, PageBacking};
+use super::superscalar::{Blake2Generator, ScProgram};
+use super::wipe::{wipe, wipe_string};
+
+
+const ARGON_BLOCK_SIZE: u32 = 1024;
+
+pub const CACHE_LINE_SIZE: u64 = 64;
+//of rx/0, the memory uses the count of its `RandomXConfig`
+pub const DATASET_ITEM_COUNT: usize = (2147483648 + 33554368) / 64; //34.078.719
+/// the size of a dataset item in memory, with the flag whether it is computed
+const DATASET_SLOT_BYTES: usize = std::mem::size_of::<Option<[u64; 8]>>();
+
+/// The items of a new dataset, how its pages are backed and spread over the NUMA nodes
+#[cfg(feature = "full")]
+type NewDataset = (LargeBuffer<Option<[u64; 8]>>, DatasetPages, DatasetNuma);
+
+/// dataset items computed before taking the write lock in `init_dataset`
+const DATASET_INIT_BATCH: usize = 4096;
+
+/// how often the reaper looks for retired memories the workers no longer use
+const REAPER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// None if threads are not supported, i.e. on wasm32
+static REAPER: OnceLock<Option<Sender<Arc<VmMemory>>>> = OnceLock::new();
+
+/// The datasets allocated from now on are interleaved over the NUMA nodes, see `set_numa_policy`
+static INTERLEAVE_DATASET: AtomicBool = AtomicBool::new(false);
+
+/// odd multiplier of the item checksum (the 64 bit golden ratio)
+const CHECKSUM_MUL: u64 = 0x9e3779b97f4a7c15;
+
+const SUPERSCALAR_MUL_0: u64 = 6364136223846793005;
+const SUPERSCALAR_ADD_1: u64 = 9298411001130361340;
+const SUPERSCALAR_ADD_2: u64 = 12065312585734608966;
+const SUPERSCALAR_ADD_3: u64 = 9306329213124626780;
+const SUPERSCALAR_ADD_4: u64 = 5281919268842080866;
+const SUPERSCALAR_ADD_5: u64 = 10536153434571861004;
+const SUPERSCALAR_ADD_6: u64 = 3398623926847679864;
+const SUPERSCALAR_ADD_7: u64 = 9549104520008361294;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum MemoryError {
+    /// the cache of the seed could not be computed
+    #[error("Argon2 failed: {0}")]
+    Argon2(String),
+    /// neither the allocator nor the heap has the memory, `what` is `cache` or `dataset`
+    #[error("could not allocate the {} MB of the {what}: {reason}", bytes / 1024 / 1024)]
+    Allocation { what: &'static str, bytes: u64, reason: String },
+}
+
+/// An index derived from the VM state is outside of the memory, i.e. because the size of
+/// the dataset does not match the parameters of the variant, or the dataset could not be
+/// computed
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum DatasetError {
+    #[error("dataset item {item} is out of range, the dataset has {items} items")]
+    ItemOutOfRange { item: u64, items: u64 },
+    #[error("cache block {block} is out of range, the cache has {blocks} blocks")]
+    BlockOutOfRange { block: u64, blocks: u64 },
+    /// the dataset init was aborted, see `VmMemory::abort_init`
+    #[error("the dataset init made no progress for {seconds} seconds")]
+    InitStalled { seconds: u64 },
+}
+
+//256MiB, always used, named randomx_cache in the reference implementation
+pub struct SeedMemory {
+    /// from the cache allocator, see `large_alloc::set_allocators`
+    pub blocks: LargeBuffer<Block>,
+    pub programs: Vec<ScProgram<'static>>,
+    /// the parameters the memory was initialised with, the VMs use them too
+    pub config: Arc<RandomXConfig>,
+}
+
+impl SeedMemory {
+    pub fn no_memory() -> SeedMemory {
+        SeedMemory {
+            blocks: LargeBuffer::empty(),
+            programs: Vec::with_capacity(0),
+            config: Arc::new(RandomXConfig::monero()),
+        }
+    }
+
+    /// Creates a new initialised seed memory, panics if Argon2 fails (see
+    /// `try_new_initialised`)
+    pub fn new_initialised(key: &[u8]) -> SeedMemory {
+        SeedMemory::try_new_initialised(key).expect("seed memory")
+    }
+
+    /// Creates a new initialised seed memory.
+    pub fn try_new_initialised(key: &[u8]) -> Result<SeedMemory, MemoryError> {
+        SeedMemory::try_new_initialised_with(key, Arc::new(RandomXConfig::monero()))
+    }
+
+    /// Creates a new initialised seed memory for the parameters of a RandomX derivative.
+    pub fn try_new_initialised_with(key: &[u8], config: Arc<RandomXConfig>) -> Result<SeedMemory, MemoryError> {
+        SeedMemory::try_new_initialised_in(key, config, allocators().cache)
+    }
+
+    /// `try_new_initialised_with` with the cache from `alloc` instead of the cache allocator
+    pub fn try_new_initialised_in(
+        key: &[u8],
+        config: Arc<RandomXConfig>,
+        alloc: Arc<dyn LargeAlloc>,
+    ) -> Result<SeedMemory, MemoryError> {
+        let params = ParamsBuilder::new()
+            .m_cost(config.argon_memory_kib)
+            .t_cost(config.argon_iterations)
+            .p_cost(config.argon_lanes)
+            .build()
+            .map_err(|err| MemoryError::Argon2(format!("invalid parameters, {}", err)))?;
+
+        let argon2 = Argon2::new(Algorithm::Argon2d, Version::V0x13, params);
+
+        let block_count = config.argon_memory_kib as usize;
+        let mut blocks = LargeBuffer::try_allocate(block_count, Block::default(), alloc, |_, _| {}).map_err(|err| MemoryError::Allocation {
+            what: "cache",
+            bytes: (block_count * ARGON_BLOCK_SIZE as usize) as u64,
+            reason: err.to_string(),
+        })?;
+        argon2
+            .fill_memory(key, &config.argon_salt, &mut blocks[..])
+            .map_err(|err| MemoryError::Argon2(err.to_string()))?;
+        Ok(SeedMemory::with_blocks(key, blocks, config))
+    }
+
+    /// The seed memory of a cache that is already computed, i.e. mapped from the dataset
+    /// server. Only the superscalar programs are generated.
+    pub fn with_blocks(key: &[u8], blocks: LargeBuffer<Block>, config: Arc<RandomXConfig>) -> SeedMemory {
+        let mut programs = Vec::with_capacity(config.cache_accesses);
+        let mut gen = Blake2Generator::new(key, 0);
+        for _ in 0..config.cache_accesses {
+            programs.push(ScProgram::generate(&mut gen));
+        }
+        SeedMemory {
+            blocks,
+            programs,
+            config,
+        }
+    }
+}
+
+//the cache is derived from the seed key
+impl Drop for SeedMemory {
+    fn drop(&mut self) {
+        //other processes still use a shared cache
+        if !self.blocks.shared() {
+            wipe(&mut self.blocks);
+        }
+    }
+}
+
+fn mix_block_value(seed_mem: &SeedMemory, reg_value: u64, r: usize) -> Result<u64, DatasetError> {
+    let mask = ((seed_mem.config.argon_memory_kib as u64 * ARGON_BLOCK_SIZE as u64) / CACHE_LINE_SIZE).wrapping_sub(1);
+    let byte_offset = ((reg_value & mask) * CACHE_LINE_SIZE) + (8 * r as u64);
+
+    let block_ix = byte_offset / ARGON_BLOCK_SIZE as u64;
+    let block_v_ix = (byte_offset - (block_ix * ARGON_BLOCK_SIZE as u64)) / 8;
+    let block = seed_mem.blocks.get(block_ix as usize).ok_or(DatasetError::BlockOutOfRange {
+        block: block_ix,
+        blocks: seed_mem.blocks.len() as u64,
+    })?;
+    //block_v_ix is below the 128 values of a block
+    Ok(block.as_ref()[block_v_ix as usize])
+}
+
+/// Computes a dataset item from the cache, fails if the cache is smaller than the
+/// parameters of the seed memory say
+pub fn init_dataset_item(seed_mem: &SeedMemory, item_num: u64) -> Result<[u64; 8], DatasetError> {
+    let mut ds = [0; 8];
+
+    let mut reg_value = item_num;
+    ds[0] = (item_num + 1).wrapping_mul(SUPERSCALAR_MUL_0);
+    ds[1] = ds[0] ^ SUPERSCALAR_ADD_1;
+    ds[2] = ds[0] ^ SUPERSCALAR_ADD_2;
+    ds[3] = ds[0] ^ SUPERSCALAR_ADD_3;
+    ds[4] = ds[0] ^ SUPERSCALAR_ADD_4;
+    ds[5] = ds[0] ^ SUPERSCALAR_ADD_5;
+    ds[6] = ds[0] ^ SUPERSCALAR_ADD_6;
+    ds[7] = ds[0] ^ SUPERSCALAR_ADD_7;
+
+    for prog in &seed_mem.programs {
+        prog.execute(&mut ds);
+
+        for (r, v) in ds.iter_mut().enumerate() {
+            let mix_value = mix_block_value(seed_mem, reg_value, r)?;
+            *v ^= mix_value;
+        }
+        reg_value = ds[prog.address_reg];
+    }
+    Ok(ds)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn prefetch(ptr: *const u64) {
+    unsafe {
+        _mm_prefetch(ptr as *const i8, _MM_HINT_NTA);
+    }
+}
+
+//streaming prefetch, the item is read once like with NTA on x86
+#[cfg(target_arch = "aarch64")]
+fn prefetch(ptr: *const u64) {
+    unsafe {
+        std::arch::asm!("prfm pldl1strm, [{}]", in(reg) ptr, options(nostack, readonly, preserves_flags));
+    }
+}
+
+/// Checksum of a computed dataset item. The dataset checksum is the XOR of the ones
+/// of all cached items, so the items can be added in any order.
+pub fn item_checksum(item_num: u64, item: &[u64; 8]) -> u64 {
+    let mut sum = item_num.wrapping_mul(CHECKSUM_MUL);
+    for word in item {
+        sum = (sum ^ word).wrapping_mul(CHECKSUM_MUL).rotate_left(29);
+    }
+    sum
+}
+
+//no prefetch instruction (wasm32)
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn prefetch(_ptr: *const u64) {}
+
+/// The page size that backs the dataset
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatasetPages {
+    /// transparent huge pages, for `huge_bytes` of the mapping
+    Huge(PageBacking),
+    /// 4 KB pages, with the reason
+    Small(String),
+    /// large pages of the dataset allocator, for this many bytes
+    Reserved(u64),
+}
+
+impl fmt::Display for DatasetPages {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DatasetPages::Huge(backing) => write!(
+                f,
+                "2 MB transparent huge pages for {} of {} MB",
+                backing.huge_bytes / 1024 / 1024,
+                backing.bytes / 1024 / 1024
+            ),
+            DatasetPages::Small(reason) => write!(f, "4 KB pages, {}", reason),
+            DatasetPages::Reserved(bytes) => write!(f, "reserved large pages for {} MB", bytes / 1024 / 1024),
+        }
+    }
+}
+
+/// Where the pages of the dataset are, see `set_numa_policy`
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatasetNuma {
+    /// on the node of the thread that touched them first
+    Local,
+    /// interleaved over this many nodes
+    Interleaved(u32),
+    /// interleaving was configured, but failed for the reason
+    Failed(String),
+}
+
+impl fmt::Display for DatasetNuma {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DatasetNuma::Local => write!(f, "on the nodes that touched them first"),
+            DatasetNuma::Interleaved(nodes) => write!(f, "interleaved over {} NUMA nodes", nodes),
+            DatasetNuma::Failed(reason) => write!(f, "not interleaved, {}", reason),
+        }
+    }
+}
+
+/// Sets the placement of the datasets allocated from now on, one dataset interleaved over
+/// all nodes instead of a dataset per node, which would need 2 GB per node
+pub fn set_numa_policy(policy: NumaPolicy) {
+    INTERLEAVE_DATASET.store(policy == NumaPolicy::Interleave, Ordering::Relaxed);
+}
+
+/// The dataset items of the dataset allocator, backed by transparent huge pages if the
+/// kernel allows it
+#[cfg(feature = "full")]
+fn new_dataset(
+    item_count: usize,
+    alloc: Arc<dyn LargeAlloc>,
+) -> Result<NewDataset, MemoryError> {
+    let bytes = item_count.saturating_mul(DATASET_SLOT_BYTES);
+    let mut advised = Ok(());
+    let mut numa = DatasetNuma::Local;
+    //the policies apply to the pages faulted in afterwards, so they are set before the items
+    let mem = LargeBuffer::try_allocate(item_count, None, alloc, |ptr, bytes| {
+        advised = pages::advise_huge_pages(ptr, bytes);
+        if INTERLEAVE_DATASET.load(Ordering::Relaxed) {
+            numa = match pages::interleave_numa(ptr, bytes) {
+                Ok(nodes) => DatasetNuma::Interleaved(nodes),
+                Err(err) => DatasetNuma::Failed(err.to_string()),
+            };
+        }
+    })
+    .map_err(|err| MemoryError::Allocation { what: "dataset", bytes: bytes as u64, reason: err.to_string() })?;
+    if mem.large_pages() {
+        return Ok((mem, DatasetPages::Reserved(bytes as u64), numa));
+    }
+
+    //the advised part is a mapping of its own, the start of the vec is not 2 MB aligned
+    let middle = unsafe { (mem.as_ptr() as *const u8).add(bytes / 2) };
+    let dataset_pages = match (advised, pages::page_backing(middle)) {
+        (Err(err), _) => DatasetPages::Small(format!("transparent huge pages are not available: {}", err)),
+        (Ok(()), Some(backing)) if backing.huge_bytes > 0 => DatasetPages::Huge(backing),
+        (Ok(()), _) => DatasetPages::Small(format!(
+            "transparent huge pages are set to {}",
+            pages::thp_mode().unwrap_or_else(|| "an unknown mode".to_string())
+        )),
+    };
+    Ok((mem, dataset_pages, numa))
+}
+
+/// The key of a hex encoded seed hash, decoding stops at the first invalid byte
+fn seed_key(seed: &str) -> Vec<u8> {
+    (0..seed.len() / 2)
+        .map_while(|i| seed.get(2 * i..2 * i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct VmMemoryAllocator {
+    pub vm_memory_seed: String,
+    pub vm_memory: Arc<VmMemory>,
+    /// false allocates the light mode memory, i.e. if the memory is limited. Without the
+    /// `full` feature the light mode is always used.
+    pub full: bool,
+    pub config: Arc<RandomXConfig>,
+}
+
+impl VmMemoryAllocator {
+    pub fn initial() -> VmMemoryAllocator {
+        VmMemoryAllocator::with_mode(true)
+    }
+
+    pub fn with_mode(full: bool) -> VmMemoryAllocator {
+        VmMemoryAllocator::with_config(full, Arc::new(RandomXConfig::monero()))
+    }
+
+    /// The allocator of a RandomX derivative
+    pub fn with_config(full: bool, config: Arc<RandomXConfig>) -> VmMemoryAllocator {
+        VmMemoryAllocator {
+            vm_memory_seed: "".to_string(),
+            vm_memory: Arc::new(VmMemory::no_memory()),
+            full,
+            config,
+        }
+    }
+
+    /// Allocates the memory for the hex encoded seed hash, returns the init time or None
+    /// if the seed did not change. On error the memory of the previous seed is kept.
+    pub fn reallocate(&mut self, seed: String) -> Result<Option<Duration>, MemoryError> {
+        if seed == self.vm_memory_seed {
+            return Ok(None);
+        }
+        let mem_init_start = Instant::now();
+        let mut key = seed_key(&seed);
+        let seed_memory = SeedMemory::try_new_initialised_with(&key, self.config.clone());
+        wipe(&mut key);
+        let seed_memory = Arc::new(seed_memory?);
+        #[cfg(feature = "full")]
+        let memory = if self.full { VmMemory::try_with_dataset(seed_memory)? } else { VmMemory::with_cache(seed_memory) };
+        #[cfg(not(feature = "full"))]
+        let memory = VmMemory::with_cache(seed_memory);
+        retire(std::mem::replace(&mut self.vm_memory, Arc::new(memory)));
+        wipe_string(&mut std::mem::replace(&mut self.vm_memory_seed, seed));
+        Ok(Some(mem_init_start.elapsed()))
+    }
+}
+
+/// Hands a replaced memory to the reaper thread, which frees it once the last worker
+/// dropped its reference. Freeing the 2 GB dataset takes hundreds of milliseconds that
+/// would otherwise stall the hashing thread that drops it last.
+pub fn retire(memory: Arc<VmMemory>) {
+    memory.retired.store(true, Ordering::Relaxed);
+    if let Some(reaper) = REAPER.get_or_init(start_reaper) {
+        //the reaper never exits, if it did the memory is dropped right here
+        let _ = reaper.send(memory);
+    }
+}
+
+fn start_reaper() -> Option<Sender<Arc<VmMemory>>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("memory reaper".to_string())
+        .spawn(move || reap(receiver))
+        .ok()
+        .map(|_| sender)
+}
+
+fn reap(receiver: Receiver<Arc<VmMemory>>) {
+    let mut retired: Vec<Arc<VmMemory>> = Vec::new();
+    loop {
+        let received = if retired.is_empty() {
+            receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            receiver.recv_timeout(REAPER_INTERVAL)
+        };
+        match received {
+            Ok(memory) => retired.push(memory),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        //only the reaper holds them, they are dropped on this thread
+        retired.retain(|memory| Arc::strong_count(memory) > 1);
+    }
+}
+
+pub struct VmMemory {
+    /// shared by the memories of the same seed, see `with_cache` and `with_dataset`
+    pub seed_memory: Arc<SeedMemory>,
+    /// locked with `read_dataset` and `write_dataset`, they recover from a poisoned lock
+    pub dataset_memory: RwLock<LargeBuffer<Option<[u64; 8]>>>,
+    pub cache: bool,
+    /// None in light mode
+    pub dataset_pages: Option<DatasetPages>,
+    /// None in light mode
+    pub dataset_numa: Option<DatasetNuma>,
+    /// number of dataset items that are computed and cached
+    dataset_items: AtomicU64,
+    /// XOR of the `item_checksum` of the cached items, changed with the write lock held
+    checksum: AtomicU64,
+    /// the scratchpads of the VMs that use the memory
+    pub scratchpads: BufferCount,
+    /// replaced by the memory of another seed, see `retire`
+    retired: AtomicBool,
+    /// changed by `abort_init`, an init stops once it differs from the one it started with
+    init_generation: AtomicU64,
+    /// dataset items an init got through, the computed ones that were skipped as well
+    init_progress: AtomicU64,
+}
+
+impl VmMemory {
+    //only useful for testing
+    pub fn no_memory() -> VmMemory {
+        VmMemory::with_cache(Arc::new(SeedMemory::no_memory()))
+    }
+
+    pub fn light(key: &[u8]) -> VmMemory {
+        VmMemory::with_cache(Arc::new(SeedMemory::new_initialised(key)))
+    }
+
+    /// Light mode memory of an initialised seed memory, every dataset item is computed
+    /// on access
+    pub fn with_cache(seed_memory: Arc<SeedMemory>) -> VmMemory {
+        VmMemory {
+            seed_memory,
+            cache: false,
+            dataset_memory: RwLock::new(LargeBuffer::empty()),
+            dataset_pages: None,
+            dataset_numa: None,
+            dataset_items: AtomicU64::new(0),
+            checksum: AtomicU64::new(0),
+            scratchpads: BufferCount::default(),
+            retired: AtomicBool::new(false),
+            init_generation: AtomicU64::new(0),
+            init_progress: AtomicU64::new(0),
+        }
+    }
+
+    #[cfg(feature = "full")]
+    pub fn full(key: &[u8]) -> VmMemory {
+        VmMemory::with_dataset(Arc::new(SeedMemory::new_initialised(key)))
+    }
+
+    /// Full mode memory of an initialised seed memory, the dataset items are computed on
+    /// first access or by `init_dataset`. Panics if the dataset cannot be allocated, see
+    /// `try_with_dataset`.
+    #[cfg(feature = "full")]
+    pub fn with_dataset(seed_memory: Arc<SeedMemory>) -> VmMemory {
+        VmMemory::try_with_dataset(seed_memory).expect("dataset allocation")
+    }
+
+    /// `with_dataset`, but an error if the dataset cannot be allocated
+    #[cfg(feature = "full")]
+    pub fn try_with_dataset(seed_memory: Arc<SeedMemory>) -> Result<VmMemory, MemoryError> {
+        VmMemory::try_with_dataset_in(seed_memory, allocators().dataset)
+    }
+
+    /// `with_dataset` with the dataset from `alloc` instead of the dataset allocator
+    #[cfg(feature = "full")]
+    pub fn with_dataset_in(seed_memory: Arc<SeedMemory>, alloc: Arc<dyn LargeAlloc>) -> VmMemory {
+        VmMemory::try_with_dataset_in(seed_memory, alloc).expect("dataset allocation")
+    }
+
+    /// `try_with_dataset` with the dataset from `alloc` instead of the dataset allocator
+    #[cfg(feature = "full")]
+    pub fn try_with_dataset_in(seed_memory: Arc<SeedMemory>, alloc: Arc<dyn LargeAlloc>) -> Result<VmMemory, MemoryError> {
+        let (mem, dataset_pages, dataset_numa) = new_dataset(seed_memory.config.dataset_item_count(), alloc)?;
+        Ok(VmMemory {
+            seed_memory,
+            cache: true,
+            dataset_memory: RwLock::new(mem),
+            dataset_pages: Some(dataset_pages),
+            dataset_numa: Some(dataset_numa),
+            dataset_items: AtomicU64::new(0),
+            checksum: AtomicU64::new(0),
+            scratchpads: BufferCount::default(),
+            retired: AtomicBool::new(false),
+            init_generation: AtomicU64::new(0),
+            init_progress: AtomicU64::new(0),
+        })
+    }
+
+    /// Full mode memory of a dataset that is already computed, i.e. mapped from the dataset
+    /// server, `checksum` is the `checksum` of the memory that computed it
+    #[cfg(feature = "full")]
+    pub fn with_computed_dataset(
+        seed_memory: Arc<SeedMemory>,
+        dataset: LargeBuffer<Option<[u64; 8]>>,
+        checksum: u64,
+    ) -> VmMemory {
+        let items = dataset.iter().filter(|item| item.is_some()).count() as u64;
+        VmMemory {
+            seed_memory,
+            cache: true,
+            dataset_pages: Some(DatasetPages::Small("the dataset is mapped from a file".to_string())),
+            dataset_numa: None,
+            dataset_memory: RwLock::new(dataset),
+            dataset_items: AtomicU64::new(items),
+            checksum: AtomicU64::new(checksum),
+            scratchpads: BufferCount::default(),
+            retired: AtomicBool::new(false),
+            init_generation: AtomicU64::new(0),
+            init_progress: AtomicU64::new(0),
+        }
+    }
+
+    /// The dataset for reading, see `write_dataset` for a poisoned lock
+    pub fn read_dataset(&self) -> RwLockReadGuard<'_, LargeBuffer<Option<[u64; 8]>>> {
+        if let Ok(mem) = self.dataset_memory.read() {
+            return mem;
+        }
+        drop(self.write_dataset());
+        self.dataset_memory.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// The dataset for writing. A thread that panicked with the write lock held may have
+    /// left the item count and the checksum behind the items, the cached items are then
+    /// dropped and computed again on access instead of taking the other threads down.
+    pub fn write_dataset(&self) -> RwLockWriteGuard<'_, LargeBuffer<Option<[u64; 8]>>> {
+        match self.dataset_memory.write() {
+            Ok(mem) => mem,
+            Err(poisoned) => {
+                let mut mem = poisoned.into_inner();
+                self.clear_items(&mut mem);
+                self.dataset_memory.clear_poison();
+                mem
+            }
+        }
+    }
+
+    fn clear_items(&self, mem: &mut [Option<[u64; 8]>]) {
+        mem.iter_mut().for_each(|item| *item = None);
+        self.dataset_items.store(0, Ordering::Relaxed);
+        self.checksum.store(0, Ordering::Relaxed);
+    }
+
+    /// XOR of the `item_checksum` of the computed items
+    pub fn checksum(&self) -> u64 {
+        self.checksum.load(Ordering::Relaxed)
+    }
+
+    /// `full` if the dataset is cached, `light` if every item is computed on access
+    pub fn mode(&self) -> &'static str {
+        if self.cache {
+            "full"
+        } else {
+            "light"
+        }
+    }
+
+    /// Computes all dataset items up front with the given number of threads instead of
+    /// on first access. Does nothing in light mode.
+    pub fn init_dataset(&self, threads: usize) {
+        self.init_dataset_with(threads, 0, || {});
+    }
+
+    /// `init_dataset` with at most `items_per_second` over all threads (0 for no limit),
+    /// `thread_start` is called first on every thread, i.e. to lower its priority. Stops
+    /// once the memory is retired, the remaining items are computed on access.
+    pub fn init_dataset_with(&self, threads: usize, items_per_second: u64, thread_start: impl Fn() + Sync) {
+        if !self.cache {
+            return;
+        }
+        let threads = threads.max(1);
+        let item_count = self.seed_memory.config.dataset_item_count();
+        let chunk = item_count.div_ceil(threads);
+        let thread_items_per_second = (items_per_second as f64 / threads as f64).max(1.0);
+        let thread_start = &thread_start;
+        let generation = self.init_generation.load(Ordering::Relaxed);
+        thread::scope(|scope| {
+            for start in (0..item_count).step_by(chunk) {
+                let end = (start + chunk).min(item_count);
+                scope.spawn(move || {
+                    thread_start();
+                    let started = Instant::now();
+                    for batch_start in (start..end).step_by(DATASET_INIT_BATCH) {
+                        if self.retired.load(Ordering::Relaxed) || self.init_generation.load(Ordering::Relaxed) != generation {
+                            return;
+                        }
+                        let batch_end = (batch_start + DATASET_INIT_BATCH).min(end);
+                        //i.e. by an init that was aborted
+                        if self.read_dataset()[batch_start..batch_end].iter().all(Option::is_some) {
+                            self.init_progress.fetch_add((batch_end - batch_start) as u64, Ordering::Relaxed);
+                            continue;
+                        }
+                        //the workers get the error once they read an item
+                        let items: Vec<[u64; 8]> = match (batch_start..batch_end)
+                            .map(|item_num| init_dataset_item(&self.seed_memory, item_num as u64))
+                            .collect()
+                        {
+                            Ok(items) => items,
+                            Err(_) => return,
+                        };
+                        {
+                            let mut mem = self.write_dataset();
+                            for (item, rl) in (batch_start..batch_end).zip(items) {
+                                if mem[item].is_none() {
+                                    self.dataset_items.fetch_add(1, Ordering::Relaxed);
+                                    self.checksum.fetch_xor(item_checksum(item as u64, &rl), Ordering::Relaxed);
+                                }
+                                mem[item] = Some(rl);
+                            }
+                        }
+                        self.init_progress.fetch_add((batch_end - batch_start) as u64, Ordering::Relaxed);
+                        if items_per_second > 0 {
+                            let due = Duration::from_secs_f64((batch_end - start) as f64 / thread_items_per_second);
+                            if let Some(ahead) = due.checked_sub(started.elapsed()) {
+                                thread::sleep(ahead);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    /// Stops the running `init_dataset`, the threads end after their current batch. A
+    /// thread that is stuck keeps running, the items it computes are still used.
+    pub fn abort_init(&self) {
+        self.init_generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// True once the memory was replaced by the memory of another seed, see `retire`
+    pub fn is_retired(&self) -> bool {
+        self.retired.load(Ordering::Relaxed)
+    }
+
+    /// Dataset items the inits got through so far, grows as long as an init makes progress
+    pub fn init_progress(&self) -> u64 {
+        self.init_progress.load(Ordering::Relaxed)
+    }
+
+    /// Fraction of the dataset items that are cached, 0 in light mode
+    pub fn dataset_fill(&self) -> f64 {
+        self.dataset_items.load(Ordering::Relaxed) as f64 / self.seed_memory.config.dataset_item_count() as f64
+    }
+
+    /// Computes the checksum of the cached items again, false if an item changed since
+    /// it was computed, i.e. because of failing RAM or an unstable overclock. Always
+    /// true in light mode.
+    pub fn verify_checksum(&self) -> bool {
+        let mem = self.read_dataset();
+        let sum = mem
+            .iter()
+            .enumerate()
+            .filter_map(|(item_num, item)| item.as_ref().map(|item| item_checksum(item_num as u64, item)))
+            .fold(0, |sum, checksum| sum ^ checksum);
+        sum == self.checksum.load(Ordering::Relaxed)
+    }
+
+    /// The cached items of `item_nums` that differ from a new computation, items that
+    /// are not computed yet are skipped
+    pub fn verify_items(&self, item_nums: &[u64]) -> Vec<u64> {
+        item_nums
+            .iter()
+            .copied()
+            .filter(|&item_num| {
+                let cached = self.read_dataset().get(item_num as usize).copied().flatten();
+                cached.is_some_and(|item| init_dataset_item(&self.seed_memory, item_num) != Ok(item))
+            })
+            .collect()
+    }
+
+    /// Drops the cached items and computes them again with the given number of threads,
+    /// i.e. after `verify_checksum` found a corrupted item. Does nothing in light mode.
+    pub fn rebuild_dataset(&self, threads: usize) {
+        if !self.cache {
+            return;
+        }
+        {
+            let mut mem = self.write_dataset();
+            self.clear_items(&mut mem);
+        }
+        self.init_dataset(threads);
+    }
+
+    /// Bytes of the Argon2 cache of the seed
+    pub fn cache_bytes(&self) -> u64 {
+        (self.seed_memory.blocks.len() * ARGON_BLOCK_SIZE as usize) as u64
+    }
+
+    /// Bytes allocated for the dataset, 0 in light mode
+    pub fn dataset_bytes(&self) -> u64 {
+        if !self.cache {
+            return 0;
+        }
+        (self.seed_memory.config.dataset_item_count() * DATASET_SLOT_BYTES) as u64
+    }
+
+    /// Bytes of the dataset items that are computed and cached
+    pub fn dataset_filled_bytes(&self) -> u64 {
+        self.dataset_items.load(Ordering::Relaxed) * DATASET_SLOT_BYTES as u64
+    }
+
+    /// A hint only, an item out of range is not prefetched
+    pub fn dataset_prefetch(&self, offset: u64) {
+        let item_num = offset / CACHE_LINE_SIZE;
+        if self.cache {
+            let mem = self.read_dataset();
+            if let Some(Some(rl)) = mem.get(item_num as usize) {
+                prefetch(rl.as_ptr());
+            }
+        }
+    }
+
+    /// XORs the dataset item at `offset` into `reg`, fails if the offset is outside of
+    /// the dataset or the item outside of the cache
+    pub fn dataset_read(&self, offset: u64, reg: &mut [u64; 8]) -> Result<(), DatasetError> {
+        let timer = ReadTimer::start();
+        let hit = self.dataset_read_item(offset / CACHE_LINE_SIZE, reg)?;
+        timer.finish(hit);
+        Ok(())
+    }
+
+    /// True if the item was already computed
+    fn dataset_read_item(&self, item_num: u64, reg: &mut [u64; 8]) -> Result<bool, DatasetError> {
+        if self.cache {
+            let out_of_range = |items: usize| DatasetError::ItemOutOfRange { item: item_num, items: items as u64 };
+            {
+                let mem = self.read_dataset();
+                let rl_cached = mem.get(item_num as usize).ok_or_else(|| out_of_range(mem.len()))?;
+                if let Some(rl) = rl_cached {
+                    for i in 0..8 {
+                        reg[i] ^= rl[i];
+                    }
+                    return Ok(true);
+                }
+            }
+            {
+                let rl = init_dataset_item(&self.seed_memory, item_num)?;
+                let mut mem_mut = self.write_dataset();
+                let items = mem_mut.len();
+                let slot = mem_mut.get_mut(item_num as usize).ok_or_else(|| out_of_range(items))?;
+                if slot.is_none() {
+                    self.dataset_items.fetch_add(1, Ordering::Relaxed);
+                    self.checksum.fetch_xor(item_checksum(item_num, &rl), Ordering::Relaxed);
+                }
+                *slot = Some(rl);
+                for i in 0..8 {
+                    reg[i] ^= rl[i];
+                }
+            }
+        } else {
+            let rl = init_dataset_item(&self.seed_memory, item_num)?;
+            for i in 0..8 {
+                reg[i] ^= rl[i];
+            }
+        }
+        Ok(false)
+    }
+}
@@ -0,0 +1,675 @@
+#[cfg(unix)]
+extern crate libc;
+
+use super::large_alloc::{allocators, heap_layout, LargeAlloc, Malloc};
+
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use strum::{Display, EnumString};
+
+const LARGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// Placement of the dataset pages on a machine with several NUMA nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum NumaPolicy {
+    /// the kernel default, a page is placed on the node of the thread that touches it
+    /// first, so the threads of the other nodes read it remotely
+    #[default]
+    Local,
+    /// the pages are spread round robin over all nodes, every thread reads most of the
+    /// dataset remotely, but the load is even and the memory bandwidth of all nodes is used
+    Interleave,
+}
+
+/// A buffer of a `LargeAlloc`, on the heap if the allocator fails
+pub struct LargeBuffer<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    /// None for an empty buffer
+    alloc: Option<Arc<dyn LargeAlloc>>,
+    /// why the buffer is not on large pages, None if it is
+    small_pages: Option<io::Error>,
+}
+
+/// A scratchpad, backed by large pages if the OS grants them. Large pages avoid TLB misses
+/// on the random accesses of the RandomX scratchpad.
+pub type PageBuffer = LargeBuffer<u64>;
+
+//the memory is owned exclusively by the buffer
+unsafe impl<T: Send> Send for LargeBuffer<T> {}
+unsafe impl<T: Sync> Sync for LargeBuffer<T> {}
+
+impl<T: Copy> LargeBuffer<T> {
+    /// `len` elements set to `value`. `prepare` is called with the memory before it is
+    /// touched, to set the page policy of the range. Aborts the process if neither the
+    /// allocator nor the heap has the memory, see `try_allocate`.
+    pub fn allocate(
+        len: usize,
+        value: T,
+        alloc: Arc<dyn LargeAlloc>,
+        prepare: impl FnOnce(*const u8, usize),
+    ) -> LargeBuffer<T> {
+        LargeBuffer::try_allocate(len, value, alloc, prepare).unwrap_or_else(|_| {
+            std::alloc::handle_alloc_error(heap_layout(len.saturating_mul(std::mem::size_of::<T>())).expect("heap layout"))
+        })
+    }
+
+    /// `allocate`, but an error instead of aborting if the heap fails as well
+    pub fn try_allocate(
+        len: usize,
+        value: T,
+        alloc: Arc<dyn LargeAlloc>,
+        prepare: impl FnOnce(*const u8, usize),
+    ) -> io::Result<LargeBuffer<T>> {
+        debug_assert!(std::mem::align_of::<T>() <= 64);
+        let bytes = len
+            .checked_mul(std::mem::size_of::<T>())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::OutOfMemory, format!("{} elements overflow the address space", len)))?;
+        if bytes == 0 {
+            return Ok(LargeBuffer::empty());
+        }
+        let (ptr, alloc, small_pages) = match alloc.alloc(bytes) {
+            Ok(ptr) => {
+                let reason = small_pages_reason(alloc.as_ref());
+                (ptr, alloc, reason)
+            }
+            Err(err) => {
+                let heap: Arc<dyn LargeAlloc> = Arc::new(Malloc);
+                let ptr = heap
+                    .alloc(bytes)
+                    .map_err(|heap_err| io::Error::new(heap_err.kind(), format!("{}, on the heap: {}", err, heap_err)))?;
+                (ptr, heap, Some(err))
+            }
+        };
+        prepare(ptr.as_ptr(), bytes);
+        let ptr = ptr.cast::<T>();
+        for i in 0..len {
+            unsafe { ptr.as_ptr().add(i).write(value) };
+        }
+        Ok(LargeBuffer {
+            ptr,
+            len,
+            alloc: Some(alloc),
+            small_pages,
+        })
+    }
+}
+
+impl<T> LargeBuffer<T> {
+    /// The buffer of `len` values at `ptr`, freed by `alloc`
+    ///
+    /// # Safety
+    ///
+    /// `ptr` holds `len` valid values and was allocated by `alloc` for their size.
+    pub(crate) unsafe fn from_raw(
+        ptr: NonNull<u8>,
+        len: usize,
+        alloc: Arc<dyn LargeAlloc>,
+    ) -> LargeBuffer<T> {
+        LargeBuffer {
+            ptr: ptr.cast(),
+            len,
+            small_pages: small_pages_reason(alloc.as_ref()),
+            alloc: Some(alloc),
+        }
+    }
+
+    pub fn empty() -> LargeBuffer<T> {
+        LargeBuffer {
+            ptr: NonNull::dangling(),
+            len: 0,
+            alloc: None,
+            small_pages: Some(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the buffer is empty",
+            )),
+        }
+    }
+
+    pub fn large_pages(&self) -> bool {
+        self.small_pages.is_none()
+    }
+
+    /// Why the buffer uses normal pages, None if it is backed by large pages
+    pub fn large_pages_error(&self) -> Option<&io::Error> {
+        self.small_pages.as_ref()
+    }
+
+    /// The memory is mapped by other processes too, see `LargeAlloc::shared`
+    pub fn shared(&self) -> bool {
+        self.alloc.as_ref().is_some_and(|alloc| alloc.shared())
+    }
+}
+
+fn small_pages_reason(alloc: &dyn LargeAlloc) -> Option<io::Error> {
+    if alloc.large_pages() {
+        return None;
+    }
+    Some(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("the {} allocator does not use large pages", alloc.name()),
+    ))
+}
+
+impl PageBuffer {
+    /// A zeroed buffer of the scratchpad allocator, see `large_alloc::set_allocators`
+    pub fn new(len: usize) -> PageBuffer {
+        LargeBuffer::allocate(len, 0, allocators().scratchpad, |_, _| {})
+    }
+
+    /// A zeroed buffer on normal pages, if large pages are not wanted
+    pub fn normal(len: usize) -> PageBuffer {
+        let mut buffer = LargeBuffer::allocate(len, 0, Arc::new(Malloc), |_, _| {});
+        buffer.small_pages = Some(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "large pages were not requested",
+        ));
+        buffer
+    }
+}
+
+/// Counts the live buffers of a memory and how many of them are on large pages, i.e.
+/// the scratchpads of the worker threads
+#[derive(Debug, Default)]
+pub struct BufferCount {
+    buffers: AtomicU64,
+    large_pages: AtomicU64,
+}
+
+impl BufferCount {
+    pub fn add(&self, large_pages: bool) {
+        self.buffers.fetch_add(1, Ordering::Relaxed);
+        if large_pages {
+            self.large_pages.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn remove(&self, large_pages: bool) {
+        self.buffers.fetch_sub(1, Ordering::Relaxed);
+        if large_pages {
+            self.large_pages.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn buffers(&self) -> u64 {
+        self.buffers.load(Ordering::Relaxed)
+    }
+
+    /// The buffers that are backed by large pages
+    pub fn large_pages(&self) -> u64 {
+        self.large_pages.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Deref for LargeBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for LargeBuffer<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for LargeBuffer<T> {
+    fn drop(&mut self) {
+        if let Some(alloc) = &self.alloc {
+            let bytes = self.len * std::mem::size_of::<T>();
+            unsafe { alloc.free(self.ptr.cast(), bytes) };
+        }
+    }
+}
+
+/// The size of a large page allocation, in whole 2 MB pages
+pub(crate) fn large_page_bytes(bytes: usize) -> usize {
+    bytes.div_ceil(LARGE_PAGE_SIZE) * LARGE_PAGE_SIZE
+}
+
+/// Anonymous huge pages, they have to be reserved with `vm.nr_hugepages`
+#[cfg(target_os = "linux")]
+pub(crate) fn alloc_large(bytes: usize) -> io::Result<*mut u8> {
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            bytes,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB,
+            -1,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ptr as *mut u8)
+}
+
+/// Superpage aligned memory, the kernel promotes it to superpages if
+/// `vm.pmap.pg_ps_enabled` is set
+#[cfg(target_os = "freebsd")]
+pub(crate) fn alloc_large(bytes: usize) -> io::Result<*mut u8> {
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            bytes,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_ALIGNED_SUPER,
+            -1,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ptr as *mut u8)
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    all(target_os = "macos", target_arch = "x86_64")
+))]
+pub(crate) fn free_large(ptr: *mut u8, bytes: usize) {
+    unsafe {
+        libc::munmap(ptr as *mut libc::c_void, bytes);
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn alloc_large(bytes: usize) -> io::Result<*mut u8> {
+    windows::alloc_large(bytes)
+}
+
+#[cfg(windows)]
+pub(crate) fn free_large(ptr: *mut u8, _bytes: usize) {
+    windows::free_large(ptr)
+}
+
+/// 2 MB superpages, only Intel Macs have them
+#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+pub(crate) fn alloc_large(bytes: usize) -> io::Result<*mut u8> {
+    //VM_FLAGS_SUPERPAGE_SIZE_2MB, passed as file descriptor of anonymous mappings
+    const SUPERPAGE_SIZE_2MB: libc::c_int = 2 << 16;
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            bytes,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANON,
+            SUPERPAGE_SIZE_2MB,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ptr as *mut u8)
+}
+
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+pub(crate) fn alloc_large(_bytes: usize) -> io::Result<*mut u8> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Apple Silicon has no large pages",
+    ))
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    windows,
+    all(
+        target_os = "macos",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )
+)))]
+pub(crate) fn alloc_large(_bytes: usize) -> io::Result<*mut u8> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "large pages are not supported on this platform",
+    ))
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    windows,
+    all(target_os = "macos", target_arch = "x86_64")
+)))]
+pub(crate) fn free_large(_ptr: *mut u8, _bytes: usize) {}
+
+/// Asks the kernel to back the range with transparent huge pages, a middle ground if
+/// no huge pages are reserved. Only the 2 MB aligned part of the range is advised.
+#[cfg(target_os = "linux")]
+pub fn advise_huge_pages(ptr: *const u8, bytes: usize) -> io::Result<()> {
+    let start = (ptr as usize).next_multiple_of(LARGE_PAGE_SIZE);
+    let end = (ptr as usize + bytes) / LARGE_PAGE_SIZE * LARGE_PAGE_SIZE;
+    if end <= start {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the memory is smaller than a huge page",
+        ));
+    }
+    let result =
+        unsafe { libc::madvise(start as *mut libc::c_void, end - start, libc::MADV_HUGEPAGE) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn advise_huge_pages(_ptr: *const u8, _bytes: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "transparent huge pages are only available on Linux",
+    ))
+}
+
+/// Interleaves the pages of the range over all online NUMA nodes when they are first
+/// touched, like `numactl --interleave=all` does for the whole process. Only the page
+/// aligned part of the range is bound. Returns the number of nodes.
+#[cfg(target_os = "linux")]
+pub fn interleave_numa(ptr: *const u8, bytes: usize) -> io::Result<u32> {
+    const MPOL_INTERLEAVE: libc::c_long = 3;
+    let online = std::fs::read_to_string("/sys/devices/system/node/online")?;
+    let nodes = parse_node_list(&online).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown NUMA node list {}", online.trim()),
+        )
+    })?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let start = (ptr as usize).next_multiple_of(page_size);
+    let end = (ptr as usize + bytes) / page_size * page_size;
+    if end <= start {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the memory is smaller than a page",
+        ));
+    }
+    //the kernel reads one bit less than maxnode
+    let max_node = u64::BITS as libc::c_ulong + 1;
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            start as *mut libc::c_void,
+            end - start,
+            MPOL_INTERLEAVE,
+            &nodes as *const u64,
+            max_node,
+            0 as libc::c_uint,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(nodes.count_ones())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn interleave_numa(_ptr: *const u8, _bytes: usize) -> io::Result<u32> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "NUMA interleaving is only available on Linux",
+    ))
+}
+
+/// The node mask of a sysfs node list, i.e. `0-2,4` is `0b10111`. None if the list is
+/// empty, invalid or has nodes above 63.
+pub fn parse_node_list(list: &str) -> Option<u64> {
+    let mut mask = 0u64;
+    for range in list.trim().split(',') {
+        let (first, last) = match range.split_once('-') {
+            Some((first, last)) => (first.parse::<u32>().ok()?, last.parse::<u32>().ok()?),
+            None => {
+                let node = range.parse::<u32>().ok()?;
+                (node, node)
+            }
+        };
+        if first > last || last >= u64::BITS {
+            return None;
+        }
+        for node in first..=last {
+            mask |= 1 << node;
+        }
+    }
+    Some(mask)
+}
+
+/// Size of a memory mapping and how much of it is backed by transparent huge pages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageBacking {
+    pub bytes: u64,
+    pub huge_bytes: u64,
+}
+
+/// The backing of the mapping that contains `ptr`, from `/proc/self/smaps`
+#[cfg(target_os = "linux")]
+pub fn page_backing(ptr: *const u8) -> Option<PageBacking> {
+    let smaps = std::fs::read_to_string("/proc/self/smaps").ok()?;
+    parse_smaps(&smaps, ptr as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn page_backing(_ptr: *const u8) -> Option<PageBacking> {
+    None
+}
+
+/// Finds the mapping that contains `address` and reads its `Size` and `AnonHugePages`
+pub fn parse_smaps(smaps: &str, address: u64) -> Option<PageBacking> {
+    let mut backing: Option<PageBacking> = None;
+    for line in smaps.lines() {
+        if let Some((start, end)) = mapping_range(line) {
+            if backing.is_some() {
+                break;
+            }
+            if (start..end).contains(&address) {
+                backing = Some(PageBacking {
+                    bytes: end - start,
+                    huge_bytes: 0,
+                });
+            }
+        } else if let (Some(backing), Some(kb)) =
+            (backing.as_mut(), line.strip_prefix("AnonHugePages:"))
+        {
+            let kb = kb.split_whitespace().next()?.parse::<u64>().ok()?;
+            backing.huge_bytes = kb * 1024;
+        }
+    }
+    backing
+}
+
+/// `7f0000000000-7f0000200000 rw-p ...`, the header line of a mapping
+fn mapping_range(line: &str) -> Option<(u64, u64)> {
+    let range = line.split_whitespace().next()?;
+    let (start, end) = range.split_once('-')?;
+    Some((
+        u64::from_str_radix(start, 16).ok()?,
+        u64::from_str_radix(end, 16).ok()?,
+    ))
+}
+
+/// The selected mode of `/sys/kernel/mm/transparent_hugepage/enabled` (always,
+/// madvise or never)
+pub fn thp_mode() -> Option<String> {
+    let enabled = std::fs::read_to_string("/sys/kernel/mm/transparent_hugepage/enabled").ok()?;
+    parse_thp_mode(&enabled).map(|mode| mode.to_string())
+}
+
+/// The mode in brackets, i.e. `madvise` for `always [madvise] never`
+pub fn parse_thp_mode(enabled: &str) -> Option<&str> {
+    let start = enabled.find('[')? + 1;
+    let end = start + enabled[start..].find(']')?;
+    Some(&enabled[start..end])
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::c_void;
+    use std::io;
+    use std::sync::OnceLock;
+
+    type Handle = *mut c_void;
+
+    const MEM_COMMIT: u32 = 0x1000;
+    const MEM_RESERVE: u32 = 0x2000;
+    const MEM_RELEASE: u32 = 0x8000;
+    const MEM_LARGE_PAGES: u32 = 0x2000_0000;
+    const PAGE_READWRITE: u32 = 0x04;
+
+    const TOKEN_ADJUST_PRIVILEGES: u32 = 0x20;
+    const TOKEN_QUERY: u32 = 0x08;
+    const SE_PRIVILEGE_ENABLED: u32 = 0x02;
+    const ERROR_NOT_ALL_ASSIGNED: i32 = 1300;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Luid {
+        low_part: u32,
+        high_part: i32,
+    }
+
+    #[repr(C)]
+    struct LuidAndAttributes {
+        luid: Luid,
+        attributes: u32,
+    }
+
+    #[repr(C)]
+    struct TokenPrivileges {
+        privilege_count: u32,
+        privileges: [LuidAndAttributes; 1],
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> Handle;
+        fn CloseHandle(handle: Handle) -> i32;
+        fn GetLargePageMinimum() -> usize;
+        fn VirtualAlloc(
+            address: *mut c_void,
+            size: usize,
+            alloc_type: u32,
+            protect: u32,
+        ) -> *mut c_void;
+        fn VirtualFree(address: *mut c_void, size: usize, free_type: u32) -> i32;
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn OpenProcessToken(process: Handle, access: u32, token: *mut Handle) -> i32;
+        fn LookupPrivilegeValueW(system: *const u16, name: *const u16, luid: *mut Luid) -> i32;
+        fn AdjustTokenPrivileges(
+            token: Handle,
+            disable_all: i32,
+            new_state: *const TokenPrivileges,
+            buffer_length: u32,
+            previous_state: *mut TokenPrivileges,
+            return_length: *mut u32,
+        ) -> i32;
+    }
+
+    static LOCK_MEMORY_PRIVILEGE: OnceLock<Result<(), String>> = OnceLock::new();
+
+    /// Large pages need SeLockMemoryPrivilege, the user needs the "Lock pages in memory"
+    /// right and the privilege has to be enabled in the process token
+    fn enable_lock_memory_privilege() -> Result<(), String> {
+        let name: Vec<u16> = "SeLockMemoryPrivilege\0".encode_utf16().collect();
+        unsafe {
+            let mut token: Handle = std::ptr::null_mut();
+            if OpenProcessToken(
+                GetCurrentProcess(),
+                TOKEN_ADJUST_PRIVILEGES | TOKEN_QUERY,
+                &mut token,
+            ) == 0
+            {
+                return Err(format!(
+                    "could not open the process token: {}",
+                    io::Error::last_os_error()
+                ));
+            }
+            let mut privileges = TokenPrivileges {
+                privilege_count: 1,
+                privileges: [LuidAndAttributes {
+                    luid: Luid::default(),
+                    attributes: SE_PRIVILEGE_ENABLED,
+                }],
+            };
+            let result = if LookupPrivilegeValueW(
+                std::ptr::null(),
+                name.as_ptr(),
+                &mut privileges.privileges[0].luid,
+            ) == 0
+            {
+                Err(io::Error::last_os_error())
+            } else if AdjustTokenPrivileges(
+                token,
+                0,
+                &privileges,
+                0,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            ) == 0
+            {
+                Err(io::Error::last_os_error())
+            } else {
+                //succeeds without the right, the missing assignment is only in the last error
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(ERROR_NOT_ALL_ASSIGNED) => Err(err),
+                    _ => Ok(()),
+                }
+            };
+            CloseHandle(token);
+            result.map_err(|err| {
+                format!(
+                    "SeLockMemoryPrivilege is missing, grant \"Lock pages in memory\" to the \
+                     user and sign in again ({})",
+                    err
+                )
+            })
+        }
+    }
+
+    pub fn alloc_large(bytes: usize) -> io::Result<*mut u8> {
+        LOCK_MEMORY_PRIVILEGE
+            .get_or_init(enable_lock_memory_privilege)
+            .clone()
+            .map_err(io::Error::other)?;
+        let page_size = unsafe { GetLargePageMinimum() };
+        if page_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the processor has no large pages",
+            ));
+        }
+        let bytes = bytes.div_ceil(page_size) * page_size;
+        let ptr = unsafe {
+            VirtualAlloc(
+                std::ptr::null_mut(),
+                bytes,
+                MEM_RESERVE | MEM_COMMIT | MEM_LARGE_PAGES,
+                PAGE_READWRITE,
+            )
+        };
+        if ptr.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ptr as *mut u8)
+    }
+
+    pub fn free_large(ptr: *mut u8) {
+        unsafe {
+            VirtualFree(ptr as *mut c_void, 0, MEM_RELEASE);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::config::RandomXConfig;
 use super::m128::m128i;
 use super::vm::{is_zero_or_power_of_2, Vm, SCRATCHPAD_L3_MASK};
 use std::fmt;
@@ -207,11 +208,13 @@ impl fmt::Display for Instr {
             Store::A(i) => write!(f, "a{}", i)?,
             _ => write!(f, ", {}", self.src)?,
         }
-        if self.imm.is_some() && !(is_l_cache(&self.dst) || is_l_cache(&self.src)) {
-            if self.unsigned_imm {
-                write!(f, ", {}", self.imm.unwrap() as u32)?
-            } else {
-                write!(f, ", {}", self.imm.unwrap())?
+        if let Some(imm) = self.imm {
+            if !(is_l_cache(&self.dst) || is_l_cache(&self.src)) {
+                if self.unsigned_imm {
+                    write!(f, ", {}", imm as u32)?
+                } else {
+                    write!(f, ", {}", imm)?
+                }
             }
         }
         if self.mode != Mode::None {
@@ -250,6 +253,15 @@ pub struct Program {
 
 impl Program {
     pub fn from_bytes(bytes: Vec<m128i>) -> Program {
+        Program::decode(bytes, Opcode::IROR_R as i64)
+    }
+
+    /// The program with the instruction frequencies of a RandomX derivative
+    pub fn from_bytes_with(bytes: Vec<m128i>, config: &RandomXConfig) -> Program {
+        Program::decode(bytes, Opcode::IXOR_M as i64 + config.freq_iror_r as i64)
+    }
+
+    fn decode(bytes: Vec<m128i>, iror_end: i64) -> Program {
         let mut entropy = Vec::with_capacity(16);
         let mut program = Vec::with_capacity((bytes.len() - 8) * 2);
         let mut register_usage = [-1; MAX_REG];
@@ -262,8 +274,13 @@ impl Program {
 
         for (i, byte) in bytes.iter().enumerate().skip(8) {
             let (op2, op1) = byte.as_i64();
-            let instr1 = decode_instruction(op1, ((i - 8) * 2) as i32, &mut register_usage);
-            let instr2 = decode_instruction(op2, (((i - 8) * 2) + 1) as i32, &mut register_usage);
+            let instr1 = decode(op1, ((i - 8) * 2) as i32, &mut register_usage, iror_end);
+            let instr2 = decode(
+                op2,
+                (((i - 8) * 2) + 1) as i32,
+                &mut register_usage,
+                iror_end,
+            );
             program.push(instr1);
             program.push(instr2);
         }
@@ -285,8 +302,13 @@ impl fmt::Display for Program {
     }
 }
 
-#[allow(overflowing_literals)]
 pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG]) -> Instr {
+    decode(bytes, i, register_usage, Opcode::IROR_R as i64)
+}
+
+//iror_end is the end of the IROR_R opcodes, the derivatives move the border to IROL_R
+#[allow(overflowing_literals)]
+fn decode(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG], iror_end: i64) -> Instr {
     let op = bytes & 0xFF;
     let dst = ((bytes & 0xFF00) >> 8) as usize;
     let src = ((bytes & 0xFF0000) >> 16) as usize;
@@ -294,12 +316,11 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
     let imm = ((bytes & 0xFFFFFFFF00000000) >> 32) as i32;
     if op < Opcode::IADD_RS as i64 {
         let dst_reg = r_reg(dst);
-        let imm_val;
-        if dst_reg == REG_NEEDS_DISPLACEMENT {
-            imm_val = Some(imm);
+        let imm_val = if dst_reg == REG_NEEDS_DISPLACEMENT {
+            Some(imm)
         } else {
-            imm_val = None;
-        }
+            None
+        };
         register_usage[dst % MAX_REG] = i;
         return Instr {
             op: Opcode::IADD_RS,
@@ -434,7 +455,7 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
         register_usage[dst % MAX_REG] = i;
         return new_lcache_instr(Opcode::IXOR_M, r_reg(dst), src, imm, modi, Vm::exec_ixor_m);
     }
-    if op < Opcode::IROR_R as i64 {
+    if op < iror_end {
         register_usage[dst % MAX_REG] = i;
         return new_instr(
             Opcode::IROR_R,
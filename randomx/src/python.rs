@@ -0,0 +1,172 @@
+//! The `mithril_randomx` Python module (feature `python`), built with maturin:
+//!
+//! ```python
+//! import mithril_randomx
+//!
+//! cache = mithril_randomx.Cache(seed_hash)
+//! vm = mithril_randomx.Vm(cache)
+//! assert vm.verify(blob, vm.hash(blob))
+//! ```
+//!
+//! The hashing releases the GIL, a VM per Python thread hashes in parallel.
+
+use super::api;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::sync::Mutex;
+
+/// The cache of a seed hash, takes about a second
+#[pyclass(frozen)]
+pub struct Cache {
+    cache: api::RandomXCache,
+}
+
+#[pymethods]
+impl Cache {
+    #[new]
+    fn new(py: Python<'_>, key: &[u8]) -> Cache {
+        let cache = py.detach(|| api::RandomXCache::new(key));
+        Cache { cache }
+    }
+}
+
+/// The 2 GB dataset of a cache for fast mode VMs, all cores by default
+#[cfg(feature = "full")]
+#[pyclass(frozen)]
+pub struct Dataset {
+    dataset: api::RandomXDataset,
+}
+
+#[cfg(feature = "full")]
+#[pymethods]
+impl Dataset {
+    #[new]
+    #[pyo3(signature = (cache, threads=None))]
+    fn new(py: Python<'_>, cache: &Cache, threads: Option<usize>) -> Dataset {
+        let threads =
+            threads.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+        let dataset = py.detach(|| api::RandomXDataset::new(&cache.cache, threads));
+        Dataset { dataset }
+    }
+}
+
+/// Light mode with a cache, fast mode with a dataset
+#[pyclass(frozen)]
+pub struct Vm {
+    vm: Mutex<api::RandomXVm>,
+}
+
+#[pymethods]
+impl Vm {
+    #[cfg(feature = "full")]
+    #[new]
+    #[pyo3(signature = (cache=None, dataset=None, large_pages=false))]
+    fn new(cache: Option<&Cache>, dataset: Option<&Dataset>, large_pages: bool) -> PyResult<Vm> {
+        let mut builder = api::RandomXVm::builder().flags(flags(large_pages));
+        if let Some(cache) = cache {
+            builder = builder.cache(&cache.cache);
+        }
+        if let Some(dataset) = dataset {
+            builder = builder.dataset(&dataset.dataset);
+        }
+        build(builder)
+    }
+
+    #[cfg(not(feature = "full"))]
+    #[new]
+    #[pyo3(signature = (cache, large_pages=false))]
+    fn new(cache: &Cache, large_pages: bool) -> PyResult<Vm> {
+        build(
+            api::RandomXVm::builder()
+                .flags(flags(large_pages))
+                .cache(&cache.cache),
+        )
+    }
+
+    /// The 32 byte hash of the input
+    fn hash<'py>(&self, py: Python<'py>, input: &[u8]) -> Bound<'py, PyBytes> {
+        let hash = py.detach(|| self.hash_input(input));
+        PyBytes::new(py, &hash)
+    }
+
+    /// True if the input hashes to `expected`, i.e. the result of a submitted share
+    fn verify(&self, py: Python<'_>, input: &[u8], expected: &[u8]) -> bool {
+        py.detach(|| self.hash_input(input)) == expected
+    }
+
+    /// False if large pages were not requested or not granted
+    #[getter]
+    fn large_pages(&self) -> bool {
+        self.lock().large_pages()
+    }
+
+    #[getter]
+    fn full_mem(&self) -> bool {
+        self.lock().full_mem()
+    }
+}
+
+impl Vm {
+    fn hash_input(&self, input: &[u8]) -> [u8; 32] {
+        self.lock().hash(input)
+    }
+
+    //a panic while hashing leaves the VM usable, the next hash starts from scratch
+    fn lock(&self) -> std::sync::MutexGuard<'_, api::RandomXVm> {
+        self.vm
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The light mode hash of the input, computes the cache for every call
+#[pyfunction]
+fn hash<'py>(py: Python<'py>, key: &[u8], input: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let hash = py.detach(|| light_hash(key, input))?;
+    Ok(PyBytes::new(py, &hash))
+}
+
+/// True if the input hashes to `expected` in light mode, computes the cache for every
+/// call. Use a `Vm` to verify more than one hash of a seed.
+#[pyfunction]
+fn verify(py: Python<'_>, key: &[u8], input: &[u8], expected: &[u8]) -> PyResult<bool> {
+    Ok(py.detach(|| light_hash(key, input))? == expected)
+}
+
+#[pymodule]
+fn mithril_randomx(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Cache>()?;
+    #[cfg(feature = "full")]
+    module.add_class::<Dataset>()?;
+    module.add_class::<Vm>()?;
+    module.add_function(wrap_pyfunction!(hash, module)?)?;
+    module.add_function(wrap_pyfunction!(verify, module)?)?;
+    Ok(())
+}
+
+fn light_hash(key: &[u8], input: &[u8]) -> PyResult<[u8; 32]> {
+    let cache = api::RandomXCache::new(key);
+    let mut vm = api::RandomXVm::builder()
+        .cache(&cache)
+        .build()
+        .map_err(value_error)?;
+    Ok(vm.hash(input))
+}
+
+fn flags(large_pages: bool) -> api::RandomXFlags {
+    if large_pages {
+        api::RandomXFlags::LARGE_PAGES
+    } else {
+        api::RandomXFlags::DEFAULT
+    }
+}
+
+fn build(builder: api::RandomXVmBuilder) -> PyResult<Vm> {
+    let vm = builder.build().map_err(value_error)?;
+    Ok(Vm { vm: Mutex::new(vm) })
+}
+
+fn value_error(err: api::RandomXError) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
@@ -0,0 +1,234 @@
+//! The types and signatures of the `randomx-rs` crate (the librandomx bindings), so that
+//! a project can switch with `use mithril_randomx::randomx_rs as randomx_rs;`. Built
+//! on `RandomXCache`, `RandomXDataset` and `RandomXVm` of the `api` module.
+
+use super::api;
+use super::memory::DATASET_ITEM_COUNT;
+
+use std::fmt;
+use std::ops::BitOr;
+use std::sync::Mutex;
+
+/// `randomx_flags`, the constants of randomx-rs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RandomXFlag(u32);
+
+impl RandomXFlag {
+    pub const FLAG_DEFAULT: RandomXFlag = RandomXFlag(0);
+    pub const FLAG_LARGE_PAGES: RandomXFlag = RandomXFlag(1);
+    pub const FLAG_HARD_AES: RandomXFlag = RandomXFlag(2);
+    pub const FLAG_FULL_MEM: RandomXFlag = RandomXFlag(4);
+    pub const FLAG_JIT: RandomXFlag = RandomXFlag(8);
+    pub const FLAG_SECURE: RandomXFlag = RandomXFlag(16);
+    pub const FLAG_ARGON2_SSSE3: RandomXFlag = RandomXFlag(32);
+    pub const FLAG_ARGON2_AVX2: RandomXFlag = RandomXFlag(64);
+    pub const FLAG_ARGON2: RandomXFlag = RandomXFlag(96);
+
+    /// HARD_AES if the CPU has AES-NI, never JIT (the VM is an interpreter)
+    pub fn get_recommended_flags() -> RandomXFlag {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("aes") {
+            return RandomXFlag::FLAG_HARD_AES;
+        }
+        RandomXFlag::FLAG_DEFAULT
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, other: RandomXFlag) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for RandomXFlag {
+    type Output = RandomXFlag;
+
+    fn bitor(self, other: RandomXFlag) -> RandomXFlag {
+        RandomXFlag(self.0 | other.0)
+    }
+}
+
+impl From<RandomXFlag> for api::RandomXFlags {
+    fn from(flag: RandomXFlag) -> api::RandomXFlags {
+        api::RandomXFlags::from_bits(flag.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RandomXError {
+    CreationError(String),
+    FlagConfigError(String),
+    ParameterError(String),
+    Other(String),
+}
+
+impl fmt::Display for RandomXError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RandomXError::CreationError(msg) => {
+                write!(f, "Problem creating the RandomX object: {}", msg)
+            }
+            RandomXError::FlagConfigError(msg) => {
+                write!(f, "Problem with configuration flags: {}", msg)
+            }
+            RandomXError::ParameterError(msg) => {
+                write!(f, "Problem with parameters supplied: {}", msg)
+            }
+            RandomXError::Other(msg) => write!(f, "Failed due to unknown reasons: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RandomXError {}
+
+impl From<api::RandomXError> for RandomXError {
+    fn from(err: api::RandomXError) -> RandomXError {
+        RandomXError::FlagConfigError(err.to_string())
+    }
+}
+
+#[derive(Clone)]
+pub struct RandomXCache {
+    cache: api::RandomXCache,
+}
+
+impl RandomXCache {
+    /// The flags are only checked when the VM is created
+    pub fn new(_flags: RandomXFlag, key: &[u8]) -> Result<RandomXCache, RandomXError> {
+        if key.is_empty() {
+            return Err(RandomXError::ParameterError("key is empty".to_string()));
+        }
+        Ok(RandomXCache {
+            cache: api::RandomXCache::new(key),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct RandomXDataset {
+    dataset: api::RandomXDataset,
+}
+
+impl RandomXDataset {
+    /// Computes the dataset with all cores. The items before `start` are computed too,
+    /// the dataset is the same either way.
+    pub fn new(
+        _flags: RandomXFlag,
+        cache: RandomXCache,
+        start: u32,
+    ) -> Result<RandomXDataset, RandomXError> {
+        if start as usize >= DATASET_ITEM_COUNT {
+            return Err(RandomXError::ParameterError(format!(
+                "start must be below {}",
+                DATASET_ITEM_COUNT
+            )));
+        }
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Ok(RandomXDataset {
+            dataset: api::RandomXDataset::new(&cache.cache, threads),
+        })
+    }
+
+    pub fn count(&self) -> Result<u32, RandomXError> {
+        Ok(DATASET_ITEM_COUNT as u32)
+    }
+
+    /// The dataset items as little endian bytes, 64 per item
+    pub fn get_data(&self) -> Result<Vec<u8>, RandomXError> {
+        let memory = &self.dataset.memory;
+        let mut data = Vec::with_capacity(DATASET_ITEM_COUNT * 64);
+        for offset in 0..DATASET_ITEM_COUNT as u64 {
+            let mut item = [0u64; 8];
+            memory
+                .dataset_read(offset * 64, &mut item)
+                .map_err(|err| RandomXError::Other(err.to_string()))?;
+            data.extend(item.iter().flat_map(|v| v.to_le_bytes()));
+        }
+        Ok(data)
+    }
+}
+
+/// A VM behind a lock, randomx-rs hashes with `&self`
+pub struct RandomXVM {
+    flags: RandomXFlag,
+    cache: Option<RandomXCache>,
+    dataset: Option<RandomXDataset>,
+    vm: Mutex<api::RandomXVm>,
+}
+
+impl RandomXVM {
+    /// Light mode with a cache, fast mode (FLAG_FULL_MEM) with a dataset
+    pub fn new(
+        flags: RandomXFlag,
+        cache: Option<RandomXCache>,
+        dataset: Option<RandomXDataset>,
+    ) -> Result<RandomXVM, RandomXError> {
+        let vm = build_vm(flags, cache.as_ref(), dataset.as_ref())?;
+        Ok(RandomXVM {
+            flags,
+            cache,
+            dataset,
+            vm: Mutex::new(vm),
+        })
+    }
+
+    pub fn calculate_hash(&self, input: &[u8]) -> Result<Vec<u8>, RandomXError> {
+        if input.is_empty() {
+            return Err(RandomXError::ParameterError("input was empty".to_string()));
+        }
+        let mut vm = self
+            .vm
+            .lock()
+            .map_err(|_| RandomXError::Other("a hash calculation panicked".to_string()))?;
+        Ok(vm.hash(input).to_vec())
+    }
+
+    pub fn calculate_hash_set(&self, input: &[&[u8]]) -> Result<Vec<Vec<u8>>, RandomXError> {
+        if input.is_empty() {
+            return Err(RandomXError::ParameterError("input was empty".to_string()));
+        }
+        input.iter().map(|i| self.calculate_hash(i)).collect()
+    }
+
+    /// Light mode only, like librandomx
+    pub fn reinit_cache(&mut self, cache: RandomXCache) -> Result<(), RandomXError> {
+        if self.flags.contains(RandomXFlag::FLAG_FULL_MEM) {
+            return Err(RandomXError::FlagConfigError(
+                "cannot reinit cache with FLAG_FULL_MEM set".to_string(),
+            ));
+        }
+        self.vm = Mutex::new(build_vm(self.flags, Some(&cache), None)?);
+        self.cache = Some(cache);
+        Ok(())
+    }
+
+    /// Fast mode only, like librandomx
+    pub fn reinit_dataset(&mut self, dataset: RandomXDataset) -> Result<(), RandomXError> {
+        if !self.flags.contains(RandomXFlag::FLAG_FULL_MEM) {
+            return Err(RandomXError::FlagConfigError(
+                "cannot reinit dataset without FLAG_FULL_MEM set".to_string(),
+            ));
+        }
+        self.vm = Mutex::new(build_vm(self.flags, self.cache.as_ref(), Some(&dataset))?);
+        self.dataset = Some(dataset);
+        Ok(())
+    }
+}
+
+/// librandomx ignores the dataset without FLAG_FULL_MEM
+fn build_vm(
+    flags: RandomXFlag,
+    cache: Option<&RandomXCache>,
+    dataset: Option<&RandomXDataset>,
+) -> Result<api::RandomXVm, RandomXError> {
+    let mut builder = api::RandomXVm::builder().flags(flags.into());
+    if let Some(cache) = cache {
+        builder = builder.cache(&cache.cache);
+    }
+    if let Some(dataset) = dataset.filter(|_| flags.contains(RandomXFlag::FLAG_FULL_MEM)) {
+        builder = builder.dataset(&dataset.dataset);
+    }
+    Ok(builder.build()?)
+}
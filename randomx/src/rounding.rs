@@ -0,0 +1,102 @@
+//! The RandomX rounding modes (CFROUND) in software, for targets without a rounding
+//! control register like wasm32. An operation is computed rounded to nearest, its exact
+//! error decides if the result moves one step to the next float.
+
+use std::cell::Cell;
+
+pub const ROUND_TO_NEAREST: u32 = 0;
+pub const ROUND_DOWN: u32 = 1;
+pub const ROUND_UP: u32 = 2;
+pub const ROUND_TO_ZERO: u32 = 3;
+
+thread_local! {
+    static MODE: Cell<u32> = const { Cell::new(ROUND_TO_NEAREST) };
+}
+
+/// The mode of this thread, like the control register it is set per thread
+pub fn mode() -> u32 {
+    MODE.with(|mode| mode.get())
+}
+
+pub fn set_mode(mode: u32) {
+    MODE.with(|m| m.set(mode & 3))
+}
+
+pub fn add(a: f64, b: f64, mode: u32) -> f64 {
+    let sum = a + b;
+    if mode == ROUND_TO_NEAREST {
+        return sum;
+    }
+    if !sum.is_finite() {
+        return overflow(sum, a.is_finite() && b.is_finite(), mode);
+    }
+    if sum == 0.0 {
+        //an exact zero is negative when rounding down, unless both operands are +0
+        if mode == ROUND_DOWN && !(a.is_sign_positive() && b.is_sign_positive()) {
+            return -0.0;
+        }
+        return sum;
+    }
+    //two-sum, the exact sum is `sum + error`
+    let b_part = sum - a;
+    let error = (a - (sum - b_part)) + (b - b_part);
+    round(sum, error, mode)
+}
+
+pub fn sub(a: f64, b: f64, mode: u32) -> f64 {
+    add(a, -b, mode)
+}
+
+pub fn mul(a: f64, b: f64, mode: u32) -> f64 {
+    let product = a * b;
+    if mode == ROUND_TO_NEAREST {
+        return product;
+    }
+    if !product.is_finite() {
+        return overflow(product, a.is_finite() && b.is_finite(), mode);
+    }
+    round(product, a.mul_add(b, -product), mode)
+}
+
+pub fn div(a: f64, b: f64, mode: u32) -> f64 {
+    let quotient = a / b;
+    if mode == ROUND_TO_NEAREST {
+        return quotient;
+    }
+    if !quotient.is_finite() {
+        return overflow(quotient, a.is_finite() && b.is_finite() && b != 0.0, mode);
+    }
+    //the remainder `a - quotient * b` is exact, the error has its sign times the sign of b
+    let remainder = (-quotient).mul_add(b, a);
+    round(quotient, remainder * b.signum(), mode)
+}
+
+pub fn sqrt(a: f64, mode: u32) -> f64 {
+    let root = a.sqrt();
+    if mode == ROUND_TO_NEAREST || !root.is_finite() {
+        return root;
+    }
+    round(root, (-root).mul_add(root, a), mode)
+}
+
+/// Moves the nearest result one step towards the exact result if the mode rounds the
+/// other way, `error` has the sign of the exact result minus `nearest`
+fn round(nearest: f64, error: f64, mode: u32) -> f64 {
+    let down = error < 0.0;
+    let up = error > 0.0;
+    match mode {
+        ROUND_DOWN if down => nearest.next_down(),
+        ROUND_UP if up => nearest.next_up(),
+        ROUND_TO_ZERO if down && nearest > 0.0 => nearest.next_down(),
+        ROUND_TO_ZERO if up && nearest < 0.0 => nearest.next_up(),
+        _ => nearest,
+    }
+}
+
+/// An infinity from finite operands, the exact result is below the largest float
+fn overflow(infinity: f64, finite_operands: bool, mode: u32) -> f64 {
+    if !finite_operands || infinity.is_nan() {
+        return infinity;
+    }
+    round(infinity, -infinity, mode)
+}
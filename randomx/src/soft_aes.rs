@@ -0,0 +1,143 @@
+//One AES round in software, for CPUs without AES instructions. Same semantics as
+//the x86 aesenc/aesdec instructions, the state is 16 bytes in column order.
+
+const SBOX: [u8; 256] = sbox();
+const INV_SBOX: [u8; 256] = inv_sbox();
+
+const fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplicative inverse in GF(2^8) followed by the affine transformation
+const fn sbox() -> [u8; 256] {
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut inverse = 0u8;
+        if i != 0 {
+            let mut candidate = 1;
+            while candidate < 256 {
+                if gf_mul(i as u8, candidate as u8) == 1 {
+                    inverse = candidate as u8;
+                    break;
+                }
+                candidate += 1;
+            }
+        }
+        table[i] = inverse
+            ^ inverse.rotate_left(1)
+            ^ inverse.rotate_left(2)
+            ^ inverse.rotate_left(3)
+            ^ inverse.rotate_left(4)
+            ^ 0x63;
+        i += 1;
+    }
+    table
+}
+
+const fn inv_sbox() -> [u8; 256] {
+    let sbox = sbox();
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[sbox[i] as usize] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+/// ShiftRows, SubBytes, MixColumns and the xor with the round key
+pub fn aesenc(state: [u8; 16], key: [u8; 16]) -> [u8; 16] {
+    let mut shifted = [0u8; 16];
+    for column in 0..4 {
+        for row in 0..4 {
+            shifted[4 * column + row] = SBOX[state[4 * ((column + row) % 4) + row] as usize];
+        }
+    }
+    let mut result = [0u8; 16];
+    for column in 0..4 {
+        let a = &shifted[4 * column..4 * column + 4];
+        let mixed = [
+            gf_mul(a[0], 2) ^ gf_mul(a[1], 3) ^ a[2] ^ a[3],
+            a[0] ^ gf_mul(a[1], 2) ^ gf_mul(a[2], 3) ^ a[3],
+            a[0] ^ a[1] ^ gf_mul(a[2], 2) ^ gf_mul(a[3], 3),
+            gf_mul(a[0], 3) ^ a[1] ^ a[2] ^ gf_mul(a[3], 2),
+        ];
+        for row in 0..4 {
+            result[4 * column + row] = mixed[row] ^ key[4 * column + row];
+        }
+    }
+    result
+}
+
+/// InvShiftRows, InvSubBytes, InvMixColumns and the xor with the round key
+pub fn aesdec(state: [u8; 16], key: [u8; 16]) -> [u8; 16] {
+    let mut shifted = [0u8; 16];
+    for column in 0..4 {
+        for row in 0..4 {
+            shifted[4 * column + row] =
+                INV_SBOX[state[4 * ((column + 4 - row) % 4) + row] as usize];
+        }
+    }
+    let mut result = [0u8; 16];
+    for column in 0..4 {
+        let a = &shifted[4 * column..4 * column + 4];
+        let mixed = [
+            gf_mul(a[0], 14) ^ gf_mul(a[1], 11) ^ gf_mul(a[2], 13) ^ gf_mul(a[3], 9),
+            gf_mul(a[0], 9) ^ gf_mul(a[1], 14) ^ gf_mul(a[2], 11) ^ gf_mul(a[3], 13),
+            gf_mul(a[0], 13) ^ gf_mul(a[1], 9) ^ gf_mul(a[2], 14) ^ gf_mul(a[3], 11),
+            gf_mul(a[0], 11) ^ gf_mul(a[1], 13) ^ gf_mul(a[2], 9) ^ gf_mul(a[3], 14),
+        ];
+        for row in 0..4 {
+            result[4 * column + row] = mixed[row] ^ key[4 * column + row];
+        }
+    }
+    result
+}
+
+/// The first `count` round keys of the AES-256 key schedule, CryptoNight encrypts its
+/// scratchpad with 10 of them
+pub fn expand_key_256(key: &[u8; 32], count: usize) -> Vec<[u8; 16]> {
+    let mut words: Vec<[u8; 4]> = key.chunks(4).map(|w| [w[0], w[1], w[2], w[3]]).collect();
+    let mut rcon = 1u8;
+    while words.len() < count * 4 {
+        let i = words.len();
+        let mut word = words[i - 1];
+        if i.is_multiple_of(8) {
+            word = [
+                SBOX[word[1] as usize] ^ rcon,
+                SBOX[word[2] as usize],
+                SBOX[word[3] as usize],
+                SBOX[word[0] as usize],
+            ];
+            rcon = gf_mul(rcon, 2);
+        } else if i % 8 == 4 {
+            word = word.map(|b| SBOX[b as usize]);
+        }
+        let prev = words[i - 8];
+        words.push([0, 1, 2, 3].map(|b| prev[b] ^ word[b]));
+    }
+    words
+        .chunks(4)
+        .take(count)
+        .map(|k| {
+            let mut round_key = [0u8; 16];
+            for (i, word) in k.iter().enumerate() {
+                round_key[4 * i..4 * i + 4].copy_from_slice(word);
+            }
+            round_key
+        })
+        .collect()
+}
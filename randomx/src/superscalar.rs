@@ -1,8 +1,13 @@
 extern crate blake2b_simd;
 
 use self::blake2b_simd::Params;
+#[cfg(feature = "serde")]
+use serde::de::Error;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::TryInto;
 use std::fmt;
+use std::fmt::Write;
 use strum::Display;
 
 use super::common::{mulh, randomx_reciprocal, smulh, u64_from_u32_imm};
@@ -16,6 +21,7 @@ const MAX_THROWAWAY_COUNT: usize = 256;
 
 #[allow(nonstandard_style)]
 #[derive(Copy, Clone, Display, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ScOpcode {
 	INVALID = -1,
 	ISUB_R = 0,
@@ -42,6 +48,18 @@ impl ScOpcode {
 			|| self == ScOpcode::ISMULH_R
 			|| self == ScOpcode::IMUL_RCP
 	}
+
+	fn has_src(self) -> bool {
+		matches!(
+			self,
+			ScOpcode::ISUB_R
+				| ScOpcode::IXOR_R
+				| ScOpcode::IADD_RS
+				| ScOpcode::IMUL_R
+				| ScOpcode::IMULH_R
+				| ScOpcode::ISMULH_R
+		)
+	}
 }
 
 #[derive(Copy, Clone)]
@@ -93,6 +111,21 @@ impl ScInstr<'_> {
 		((self.mod_v >> 2) % 4) as u64
 	}
 
+	/// An instruction as it is executed, without the state of the generator
+	pub fn new(op: ScOpcode, dst: i32, src: i32, mod_v: u8, imm32: u32) -> ScInstr<'static> {
+		ScInstr {
+			info: ScInstrInfo::for_opcode(op),
+			dst,
+			src,
+			mod_v,
+			imm32,
+			op_group: op,
+			op_group_par: -1,
+			can_reuse: false,
+			group_par_is_source: false,
+		}
+	}
+
 	fn select_destination(
 		&mut self,
 		cycle: usize,
@@ -407,6 +440,10 @@ impl ScMacroOp {
 		}
 	}
 
+	pub fn name(&self) -> &'static str {
+		self.name
+	}
+
 	pub fn is_eliminated(&self) -> bool {
 		self.uop1 == ExecutionPort::NULL
 	}
@@ -477,6 +514,27 @@ impl ScInstrInfo {
 	pub fn macro_op(&self, i: usize) -> &'static ScMacroOp {
 		self.macro_ops[i]
 	}
+
+	/// NOP for INVALID and COUNT
+	pub fn for_opcode(op: ScOpcode) -> &'static ScInstrInfo {
+		match op {
+			ScOpcode::ISUB_R => &ISUB_R,
+			ScOpcode::IXOR_R => &IXOR_R,
+			ScOpcode::IADD_RS => &IADD_RS,
+			ScOpcode::IMUL_R => &IMUL_R,
+			ScOpcode::IROR_C => &IROR_C,
+			ScOpcode::IADD_C7 => &IADD_C7,
+			ScOpcode::IXOR_C7 => &IXOR_C7,
+			ScOpcode::IADD_C8 => &IADD_C8,
+			ScOpcode::IXOR_C8 => &IXOR_C8,
+			ScOpcode::IADD_C9 => &IADD_C9,
+			ScOpcode::IXOR_C9 => &IXOR_C9,
+			ScOpcode::IMULH_R => &IMULH_R,
+			ScOpcode::ISMULH_R => &ISMULH_R,
+			ScOpcode::IMUL_RCP => &IMUL_RCP,
+			ScOpcode::INVALID | ScOpcode::COUNT => &NOP,
+		}
+	}
 }
 
 static NOP: ScInstrInfo = ScInstrInfo::new(ScOpcode::INVALID, &[], 0, 0, 0);
@@ -517,6 +575,10 @@ static IMUL_RCP: ScInstrInfo = ScInstrInfo::new(
 );
 
 const BLAKE_GEN_DATA_LEN: usize = 64;
+
+/// The random bytes of the superscalar program generator, Blake2b of the seed and the
+/// nonce. With the `serde` feature the state can be stored and resumed.
+#[derive(Clone)]
 pub struct Blake2Generator {
 	index: usize,
 	data: [u8; BLAKE_GEN_DATA_LEN],
@@ -543,6 +605,16 @@ impl Blake2Generator {
 		}
 	}
 
+	/// The number of bytes of the current Blake2b output that were used
+	pub fn index(&self) -> usize {
+		self.index
+	}
+
+	/// The current Blake2b output, hashed again for the next bytes
+	pub fn data(&self) -> &[u8; BLAKE_GEN_DATA_LEN] {
+		&self.data
+	}
+
 	pub fn get_byte(&mut self) -> u8 {
 		self.check_data(1);
 		let v = self.data[self.index];
@@ -634,6 +706,7 @@ impl DecoderBuffer {
 	}
 }
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ScProgram<'a> {
 	pub prog: Vec<ScInstr<'a>>,
 	pub asic_latencies: Vec<usize>,
@@ -662,7 +735,36 @@ impl fmt::Display for ScProgram<'_> {
 	}
 }
 
+impl fmt::Display for ScInstr<'_> {
+	/// The operands the instruction uses, `IADD_C7 r6, -1234` or `IMUL_R r3, r0`
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let op = self.info.op;
+		match op {
+			ScOpcode::IADD_RS => write!(f, "{} r{}, r{}, shift {}", op, self.dst, self.src, self.mod_shift()),
+			ScOpcode::IROR_C | ScOpcode::IMUL_RCP => write!(f, "{} r{}, {}", op, self.dst, self.imm32),
+			ScOpcode::IADD_C7
+			| ScOpcode::IXOR_C7
+			| ScOpcode::IADD_C8
+			| ScOpcode::IXOR_C8
+			| ScOpcode::IADD_C9
+			| ScOpcode::IXOR_C9 => write!(f, "{} r{}, {}", op, self.dst, self.imm32 as i32),
+			_ if op.has_src() => write!(f, "{} r{}, r{}", op, self.dst, self.src),
+			_ => write!(f, "{}", op),
+		}
+	}
+}
+
 impl ScProgram<'_> {
+	/// The address register and one instruction per line. The form is stable, two
+	/// programs with the same text compute the same.
+	pub fn disassemble(&self) -> String {
+		let mut text = format!("; address register r{}\n", self.address_reg);
+		for instr in &self.prog {
+			let _ = writeln!(text, "{}", instr);
+		}
+		text
+	}
+
 	pub fn generate(gen: &mut Blake2Generator) -> ScProgram<'static> {
 		let mut prog = Vec::with_capacity(SUPERSCALAR_MAX_SIZE);
 
@@ -930,3 +1032,80 @@ fn schedule_uop(
 	}
 	None
 }
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct Blake2GeneratorState {
+	index: usize,
+	data: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Blake2Generator {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		Blake2GeneratorState {
+			index: self.index,
+			data: self.data.to_vec(),
+		}
+		.serialize(serializer)
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Blake2Generator {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Blake2Generator, D::Error> {
+		let state = Blake2GeneratorState::deserialize(deserializer)?;
+		if state.index > BLAKE_GEN_DATA_LEN {
+			return Err(D::Error::custom("index is beyond the data"));
+		}
+		let mut gen = Blake2Generator::new(&[], 0);
+		gen.data = state
+			.data
+			.try_into()
+			.map_err(|_| D::Error::custom("data has to be 64 bytes"))?;
+		gen.index = state.index;
+		Ok(gen)
+	}
+}
+
+/// The fields that are executed, the others are only used while generating
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct ScInstrState {
+	op: ScOpcode,
+	dst: i32,
+	src: i32,
+	mod_v: u8,
+	imm32: u32,
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for ScInstr<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		ScInstrState {
+			op: self.info.op,
+			dst: self.dst,
+			src: self.src,
+			mod_v: self.mod_v,
+			imm32: self.imm32,
+		}
+		.serialize(serializer)
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for ScInstr<'_> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let state = ScInstrState::deserialize(deserializer)?;
+		if state.op == ScOpcode::INVALID || state.op == ScOpcode::COUNT {
+			return Err(D::Error::custom(format!("{} cannot be executed", state.op)));
+		}
+		if !(0..8).contains(&state.dst) {
+			return Err(D::Error::custom("dst has to be a register (0 to 7)"));
+		}
+		if !(-1..8).contains(&state.src) || (state.op.has_src() && state.src < 0) {
+			return Err(D::Error::custom(format!("invalid src for {}", state.op)));
+		}
+		Ok(ScInstr::new(state.op, state.dst, state.src, state.mod_v, state.imm32))
+	}
+}
@@ -1,35 +1,29 @@
 extern crate blake2b_simd;
 
 use self::blake2b_simd::{blake2b, Hash, Params};
+use super::config::RandomXConfig;
 use super::common::{mulh, randomx_reciprocal, smulh, u64_from_i32_imm};
 use super::hash::{fill_aes_1rx4_u64, gen_program_aes_4rx4, hash_aes_1rx4};
 use super::m128::{m128d, m128i};
-use super::memory::{VmMemory, CACHE_LINE_SIZE};
+use super::memory::{DatasetError, VmMemory, CACHE_LINE_SIZE};
 use super::program::{Instr, Mode, Program, Store, MAX_FLOAT_REG, MAX_REG};
-use std::arch::x86_64::{_mm_getcsr, _mm_setcsr};
+use super::pages::PageBuffer;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use std::arch::asm;
 use std::convert::TryInto;
 use std::sync::Arc;
 
 pub const SCRATCHPAD_L1_MASK: u64 = 0x3ff8;
+//the masks of rx/0, the VM uses the ones of its `RandomXConfig`
 pub const SCRATCHPAD_L2_MASK: u64 = 0x3fff8;
 pub const SCRATCHPAD_L3_MASK: u64 = 0x1ffff8;
-const SCRATCHPAD_L3_MASK_U32: u32 = 0x1fffc0;
-
-const SCRATCHPAD_SIZE: usize = 262144;
+#[cfg(target_arch = "x86_64")]
 const MXCSR_DEFAULT: u32 = 0x9FC0;
 const CONDITION_OFFSET: u64 = 8;
 const CONDITION_MASK: u64 = (1 << CONDITION_OFFSET) - 1;
 
-const RANDOMX_PROGRAM_COUNT: usize = 8;
-const RANDOMX_PROGRAM_SIZE: i32 = 256;
-const RANDOMX_PROGRAM_ITERATIONS: usize = 2048;
-const RANDOMX_DATASET_BASE_SIZE: usize = 2147483648;
-const RANDOMX_DATASET_ITEM_SIZE: usize = 64;
-const RANDOMX_DATASET_EXTRA_SIZE: usize = 33554368;
 const RANDOMX_HASH_SIZE: usize = 32;
 
-const DATASET_EXTRA_ITEMS: usize = RANDOMX_DATASET_EXTRA_SIZE / RANDOMX_DATASET_ITEM_SIZE;
-
 const MANTISSA_SIZE: u64 = 52;
 const MANTISSA_MASK: u64 = (1 << MANTISSA_SIZE) - 1;
 const EXPONENT_SIZE: u64 = 11;
@@ -40,27 +34,24 @@ const DYNAMIC_EXPONENT_BITS: u64 = 4;
 const STATIC_EXPONENT_BITS: u64 = 4;
 const DYNAMIC_MANTISSA_MASK: u64 = (1 << (MANTISSA_SIZE + DYNAMIC_EXPONENT_BITS)) - 1;
 
-const CACHE_LINE_ALIGN_MASK: u64 =
-    ((RANDOMX_DATASET_BASE_SIZE - 1) & !(RANDOMX_DATASET_ITEM_SIZE - 1)) as u64;
-
 pub struct MemoryRegister {
     pub mx: usize,
     pub ma: usize,
 }
 
 pub struct Register {
-    pub r: [u64; MAX_REG as usize],
-    pub f: [m128d; MAX_FLOAT_REG as usize],
-    pub e: [m128d; MAX_FLOAT_REG as usize],
-    pub a: [m128d; MAX_FLOAT_REG as usize],
+    pub r: [u64; MAX_REG],
+    pub f: [m128d; MAX_FLOAT_REG],
+    pub e: [m128d; MAX_FLOAT_REG],
+    pub a: [m128d; MAX_FLOAT_REG],
 }
 
 pub fn new_register() -> Register {
     Register {
-        r: [0; MAX_REG as usize],
-        f: [m128d::zero(); MAX_FLOAT_REG as usize],
-        e: [m128d::zero(); MAX_FLOAT_REG as usize],
-        a: [m128d::zero(); MAX_FLOAT_REG as usize],
+        r: [0; MAX_REG],
+        f: [m128d::zero(); MAX_FLOAT_REG],
+        e: [m128d::zero(); MAX_FLOAT_REG],
+        a: [m128d::zero(); MAX_FLOAT_REG],
     }
 }
 
@@ -116,11 +107,17 @@ pub struct VmConfig {
 pub struct Vm {
     pub mem_reg: MemoryRegister,
     pub reg: Register,
-    pub scratchpad: Vec<u64>,
+    pub scratchpad: PageBuffer,
     pub pc: i32,
     pub config: VmConfig,
     pub mem: Arc<VmMemory>,
     pub dataset_offset: u64,
+    /// the parameters of the memory, the masks are copied from it
+    pub rx_config: Arc<RandomXConfig>,
+    pub scratchpad_l2_mask: u64,
+    pub scratchpad_l3_mask: u64,
+    pub dataset_base_mask: u64,
+    pub dataset_extra_items: u64,
 }
 
 impl Vm {
@@ -142,7 +139,7 @@ impl Vm {
             small_positive_float_bit(prog.entropy[6]),
         );
 
-        self.mem_reg.ma = ((prog.entropy[8] & CACHE_LINE_ALIGN_MASK) as u32) as usize;
+        self.mem_reg.ma = ((prog.entropy[8] & self.dataset_base_mask) as u32) as usize;
         self.mem_reg.mx = (prog.entropy[10] as u32) as usize;
 
         let mut address_reg = prog.entropy[12] as usize;
@@ -155,7 +152,7 @@ impl Vm {
         self.config.read_reg[3] = 6 + (address_reg & 1);
 
         self.dataset_offset =
-            (prog.entropy[13] % (DATASET_EXTRA_ITEMS as u64 + 1)) * CACHE_LINE_SIZE;
+            (prog.entropy[13] % (self.dataset_extra_items + 1)) * CACHE_LINE_SIZE;
 
         self.config.e_mask[0] = float_mask(prog.entropy[14]);
         self.config.e_mask[1] = float_mask(prog.entropy[15]);
@@ -169,20 +166,27 @@ impl Vm {
         fill_aes_1rx4_u64(seed, &mut self.scratchpad)
     }
 
+    /// The hash of the input, panics if the memory does not match the parameters of the
+    /// VM (see `try_calculate_hash`)
     pub fn calculate_hash(&mut self, input: &[u8]) -> Hash {
+        self.try_calculate_hash(input).expect("dataset access")
+    }
+
+    /// The hash of the input, fails if the VM reads outside of its memory
+    pub fn try_calculate_hash(&mut self, input: &[u8]) -> Result<Hash, DatasetError> {
         let hash = blake2b(input);
         let seed = hash_to_m128i_array(&hash);
 
         let mut tmp_hash = self.init_scratchpad(&seed);
         self.reset_rounding_mode();
 
-        for _ in 0..(RANDOMX_PROGRAM_COUNT - 1) {
-            self.run(&tmp_hash);
+        for _ in 0..(self.rx_config.program_count - 1) {
+            self.run(&tmp_hash)?;
             let blake_result = blake2b(&self.reg.to_bytes());
             tmp_hash = hash_to_m128i_array(&blake_result);
         }
 
-        self.run(&tmp_hash);
+        self.run(&tmp_hash)?;
         let final_hash = hash_aes_1rx4(&self.scratchpad);
         self.reg.a[0] = final_hash[0].as_m128d();
         self.reg.a[1] = final_hash[1].as_m128d();
@@ -191,26 +195,31 @@ impl Vm {
 
         let mut params = Params::new();
         params.hash_length(RANDOMX_HASH_SIZE);
-        params.hash(&self.reg.to_bytes())
+        Ok(params.hash(&self.reg.to_bytes()))
     }
 
     /// Runs one round
-    pub fn run(&mut self, seed: &[m128i; 4]) {
-        let prog = Program::from_bytes(gen_program_aes_4rx4(seed, 136));
+    pub fn run(&mut self, seed: &[m128i; 4]) -> Result<(), DatasetError> {
+        let prog = Program::from_bytes_with(
+            gen_program_aes_4rx4(seed, 8 + self.rx_config.program_size / 2),
+            &self.rx_config,
+        );
+        let program_size = prog.program.len() as i32;
+        let l3_mask_u32 = (self.scratchpad_l3_mask & !(CACHE_LINE_SIZE - 1)) as u32;
 
         self.init_vm(&prog);
 
         let mut sp_addr_0: u32 = self.mem_reg.mx as u32;
         let mut sp_addr_1: u32 = self.mem_reg.ma as u32;
 
-        for _ in 0..RANDOMX_PROGRAM_ITERATIONS {
+        for _ in 0..self.rx_config.program_iterations {
             let sp_mix = self.reg.r[self.config.read_reg[0]] ^ self.reg.r[self.config.read_reg[1]];
 
             sp_addr_0 ^= sp_mix as u32;
-            sp_addr_0 &= SCRATCHPAD_L3_MASK_U32;
+            sp_addr_0 &= l3_mask_u32;
             sp_addr_0 /= 8;
             sp_addr_1 ^= (sp_mix >> 32) as u32;
-            sp_addr_1 &= SCRATCHPAD_L3_MASK_U32;
+            sp_addr_1 &= l3_mask_u32;
             sp_addr_1 /= 8;
 
             for i in 0..MAX_REG {
@@ -228,7 +237,7 @@ impl Vm {
             }
 
             self.pc = 0;
-            while self.pc < RANDOMX_PROGRAM_SIZE {
+            while self.pc < program_size {
                 let instr = &prog.program[self.pc as usize];
                 instr.execute(self);
                 self.pc += 1;
@@ -236,12 +245,12 @@ impl Vm {
 
             self.mem_reg.mx ^= (self.reg.r[self.config.read_reg[2]]
                 ^ self.reg.r[self.config.read_reg[3]]) as usize;
-            self.mem_reg.mx &= CACHE_LINE_ALIGN_MASK as usize;
+            self.mem_reg.mx &= self.dataset_base_mask as usize;
             self.mem.dataset_prefetch(self.mem_reg.mx as u64);
             self.mem.dataset_read(
                 self.dataset_offset + self.mem_reg.ma as u64,
                 &mut self.reg.r,
-            );
+            )?;
 
             std::mem::swap(&mut self.mem_reg.mx, &mut self.mem_reg.ma);
 
@@ -261,20 +270,19 @@ impl Vm {
             sp_addr_0 = 0;
             sp_addr_1 = 0;
         }
+        Ok(())
     }
 
     pub fn reset_rounding_mode(&mut self) {
-        unsafe {
-            _mm_setcsr(MXCSR_DEFAULT);
-        }
+        write_rounding_mode(0);
     }
 
     pub fn set_rounding_mode(&mut self, mode: u32) {
-        unsafe { _mm_setcsr(MXCSR_DEFAULT | (mode << 13)) }
+        write_rounding_mode(mode)
     }
 
     pub fn get_rounding_mode(&self) -> u32 {
-        unsafe { (_mm_getcsr() >> 13) & 3 }
+        read_rounding_mode()
     }
 
     //f...
@@ -545,8 +553,8 @@ impl Vm {
         let imm = u64_from_i32_imm(instr.imm.unwrap());
         let addr: usize = match &instr.src {
             Store::L1(d) => (self.read_r(d).wrapping_add(imm)) & SCRATCHPAD_L1_MASK,
-            Store::L2(d) => (self.read_r(d).wrapping_add(imm)) & SCRATCHPAD_L2_MASK,
-            Store::L3(_) => imm & SCRATCHPAD_L3_MASK,
+            Store::L2(d) => (self.read_r(d).wrapping_add(imm)) & self.scratchpad_l2_mask,
+            Store::L3(_) => imm & self.scratchpad_l3_mask,
             _ => panic!("illegal read from scratchpad"),
         }
         .try_into()
@@ -558,8 +566,8 @@ impl Vm {
         let imm = u64_from_i32_imm(instr.imm.unwrap());
         let addr: usize = match &instr.dst {
             Store::L1(d) => (self.read_r(d).wrapping_add(imm)) & SCRATCHPAD_L1_MASK,
-            Store::L2(d) => (self.read_r(d).wrapping_add(imm)) & SCRATCHPAD_L2_MASK,
-            Store::L3(d) => (self.read_r(d).wrapping_add(imm)) & SCRATCHPAD_L3_MASK,
+            Store::L2(d) => (self.read_r(d).wrapping_add(imm)) & self.scratchpad_l2_mask,
+            Store::L3(d) => (self.read_r(d).wrapping_add(imm)) & self.scratchpad_l3_mask,
             _ => panic!("illegal read from scratchpad"),
         }
         .try_into()
@@ -574,6 +582,69 @@ impl Vm {
     }
 }
 
+#[cfg(target_arch = "x86_64")]
+fn write_rounding_mode(mode: u32) {
+    set_csr(MXCSR_DEFAULT | (mode << 13))
+}
+
+#[cfg(target_arch = "x86_64")]
+fn read_rounding_mode() -> u32 {
+    (get_csr() >> 13) & 3
+}
+
+#[cfg(target_arch = "x86_64")]
+fn set_csr(csr: u32) {
+    unsafe {
+        asm!("ldmxcsr [{}]", in(reg) &csr, options(nostack, readonly));
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn get_csr() -> u32 {
+    let mut csr: u32 = 0;
+    unsafe {
+        asm!("stmxcsr [{}]", in(reg) &mut csr, options(nostack));
+    }
+    csr
+}
+
+/// The RandomX modes (nearest, down, up, zero) as FPCR.RMode, which orders them
+/// nearest, up, down, zero. The mapping is its own inverse.
+#[cfg(target_arch = "aarch64")]
+const FPCR_RMODE: [u64; 4] = [0, 2, 1, 3];
+#[cfg(target_arch = "aarch64")]
+const FPCR_RMODE_SHIFT: u64 = 22;
+
+#[cfg(target_arch = "aarch64")]
+fn write_rounding_mode(mode: u32) {
+    unsafe {
+        let mut fpcr: u64;
+        asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack));
+        fpcr = (fpcr & !(3 << FPCR_RMODE_SHIFT)) | (FPCR_RMODE[mode as usize] << FPCR_RMODE_SHIFT);
+        asm!("msr fpcr, {}", in(reg) fpcr, options(nomem, nostack));
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn read_rounding_mode() -> u32 {
+    let fpcr: u64;
+    unsafe {
+        asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack));
+    }
+    FPCR_RMODE[((fpcr >> FPCR_RMODE_SHIFT) & 3) as usize] as u32
+}
+
+//no rounding control register (wasm32), the m128d operations round in software
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn write_rounding_mode(mode: u32) {
+    super::rounding::set_mode(mode)
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn read_rounding_mode() -> u32 {
+    super::rounding::mode()
+}
+
 pub fn hash_to_m128i_array(hash: &Hash) -> [m128i; 4] {
     let bytes = hash.as_bytes();
     let i1 = m128i::from_u8(&bytes[0..16]);
@@ -621,11 +692,31 @@ fn static_exponent(entropy: u64) -> u64 {
     exponent << MANTISSA_SIZE
 }
 
+impl Drop for Vm {
+    fn drop(&mut self) {
+        self.mem.scratchpads.remove(self.scratchpad.large_pages());
+    }
+}
+
+/// A VM for the parameters of the seed memory, the scratchpad tries large pages first
 pub fn new_vm(mem: Arc<VmMemory>) -> Vm {
+    let words = mem.seed_memory.config.scratchpad_words();
+    new_vm_with_scratchpad(mem, PageBuffer::new(words))
+}
+
+/// A VM with a scratchpad on normal pages, without trying large pages first
+pub fn new_vm_normal_pages(mem: Arc<VmMemory>) -> Vm {
+    let words = mem.seed_memory.config.scratchpad_words();
+    new_vm_with_scratchpad(mem, PageBuffer::normal(words))
+}
+
+fn new_vm_with_scratchpad(mem: Arc<VmMemory>, scratchpad: PageBuffer) -> Vm {
+    let rx_config = mem.seed_memory.config.clone();
+    mem.scratchpads.add(scratchpad.large_pages());
     Vm {
         mem_reg: MemoryRegister { mx: 0, ma: 0 },
         reg: new_register(),
-        scratchpad: vec![0; SCRATCHPAD_SIZE],
+        scratchpad,
         pc: 0,
         config: VmConfig {
             e_mask: [0; 2],
@@ -633,5 +724,10 @@ pub fn new_vm(mem: Arc<VmMemory>) -> Vm {
         },
         mem,
         dataset_offset: 0,
+        scratchpad_l2_mask: rx_config.scratchpad_l2_mask(),
+        scratchpad_l3_mask: rx_config.scratchpad_l3_mask(),
+        dataset_base_mask: rx_config.dataset_base_mask(),
+        dataset_extra_items: rx_config.dataset_extra_items(),
+        rx_config,
     }
 }
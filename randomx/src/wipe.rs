@@ -0,0 +1,22 @@
+//! Overwrites key material with zeros before the memory is freed or reused, as defense
+//! in depth on shared machines. The writes are volatile, so that the compiler does not
+//! remove stores to memory that is freed right after.
+
+use std::ptr;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+/// Sets every value to its default
+pub fn wipe<T: Copy + Default>(values: &mut [T]) {
+    for value in values.iter_mut() {
+        //a valid, aligned reference, the old value needs no drop because it is Copy
+        unsafe { ptr::write_volatile(value, T::default()) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Zeroes the bytes of the string and empties it
+pub fn wipe_string(string: &mut String) {
+    //zero bytes are valid UTF-8
+    wipe(unsafe { string.as_mut_vec() });
+    string.clear();
+}
@@ -0,0 +1,65 @@
+extern crate mithril_randomx;
+
+use mithril_randomx::{RandomXCache, RandomXError, RandomXFlags, RandomXVm};
+
+#[test]
+fn test_light_vm_hash() {
+    let cache = RandomXCache::new(b"test key 000");
+    let mut vm = RandomXVm::builder().cache(&cache).build().unwrap();
+
+    assert!(!vm.full_mem());
+    assert!(!vm.large_pages());
+    assert_eq!(
+        hex(&vm.hash(b"This is a test")),
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f"
+    );
+
+    //a second VM shares the cache
+    let mut other = RandomXVm::builder()
+        .cache(&cache)
+        .flags(RandomXFlags::LARGE_PAGES | RandomXFlags::HARD_AES)
+        .build()
+        .unwrap();
+    assert_eq!(
+        hex(&other.hash(b"Lorem ipsum dolor sit amet")),
+        "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969"
+    );
+}
+
+#[test]
+fn test_build_errors() {
+    assert_eq!(
+        RandomXVm::builder().build().err(),
+        Some(RandomXError::MissingCache)
+    );
+
+    let cache = RandomXCache::new(b"test key 000");
+    let full_mem = RandomXVm::builder()
+        .cache(&cache)
+        .flags(RandomXFlags::FULL_MEM)
+        .build();
+    assert_eq!(full_mem.err(), Some(RandomXError::MissingDataset));
+
+    let jit = RandomXVm::builder()
+        .cache(&cache)
+        .flags(RandomXFlags::JIT | RandomXFlags::SECURE)
+        .build();
+    assert_eq!(jit.err(), Some(RandomXError::Unsupported("JIT")));
+}
+
+#[test]
+fn test_flags() {
+    let flags = RandomXFlags::FULL_MEM | RandomXFlags::LARGE_PAGES;
+
+    assert_eq!(flags.bits(), 5);
+    assert!(flags.contains(RandomXFlags::FULL_MEM));
+    assert!(!flags.contains(RandomXFlags::JIT));
+    assert!(flags.contains(RandomXFlags::DEFAULT));
+    assert_eq!(RandomXFlags::default(), RandomXFlags::DEFAULT);
+}
+
+//helper
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,95 @@
+extern crate mithril_randomx;
+
+#[cfg(feature = "full")]
+use mithril_randomx::config::RandomXConfig;
+use mithril_randomx::dataset_stats::{self, latency_bucket, DatasetStats, LATENCY_BUCKETS};
+#[cfg(feature = "full")]
+use mithril_randomx::memory::{SeedMemory, VmMemory};
+#[cfg(feature = "full")]
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn test_latency_bucket() {
+    assert_eq!(latency_bucket(Duration::ZERO), 0);
+    assert_eq!(latency_bucket(Duration::from_nanos(1)), 0);
+    assert_eq!(latency_bucket(Duration::from_nanos(2)), 1);
+    assert_eq!(latency_bucket(Duration::from_nanos(3)), 2);
+    assert_eq!(latency_bucket(Duration::from_nanos(64)), 6);
+    assert_eq!(latency_bucket(Duration::from_nanos(65)), 7);
+    assert_eq!(latency_bucket(Duration::from_secs(100)), LATENCY_BUCKETS - 1);
+}
+
+#[test]
+fn test_hit_rate_and_percentiles() {
+    assert_eq!(DatasetStats::default().hit_rate(), None);
+    assert_eq!(DatasetStats::default().latency_percentile(0.5), None);
+
+    let mut stats = DatasetStats {
+        hits: 3,
+        misses: 1,
+        ..DatasetStats::default()
+    };
+    stats.latency[6] = 90;
+    stats.latency[12] = 10;
+    assert_eq!(stats.reads(), 4);
+    assert_eq!(stats.hit_rate(), Some(0.75));
+    assert_eq!(stats.latency_percentile(0.5), Some(Duration::from_nanos(64)));
+    assert_eq!(stats.latency_percentile(0.9), Some(Duration::from_nanos(64)));
+    assert_eq!(stats.latency_percentile(0.99), Some(Duration::from_nanos(4096)));
+}
+
+#[test]
+fn test_since() {
+    let mut earlier = DatasetStats {
+        hits: 10,
+        misses: 5,
+        ..DatasetStats::default()
+    };
+    earlier.latency[3] = 2;
+    let mut later = DatasetStats {
+        hits: 30,
+        misses: 6,
+        ..DatasetStats::default()
+    };
+    later.latency[3] = 7;
+    let window = later.since(&earlier);
+    assert_eq!(window.hits, 20);
+    assert_eq!(window.misses, 1);
+    assert_eq!(window.latency[3], 5);
+}
+
+#[test]
+fn test_timed_with_feature() {
+    assert_eq!(dataset_stats::timed(), cfg!(feature = "dataset-stats"));
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn test_dataset_reads_are_counted() {
+    let config = RandomXConfig {
+        argon_memory_kib: 1024,
+        argon_iterations: 1,
+        dataset_base_bytes: 1 << 20,
+        dataset_extra_bytes: 0,
+        ..RandomXConfig::monero()
+    };
+    let seed_memory = SeedMemory::try_new_initialised_with(b"test key 000", Arc::new(config)).unwrap();
+    let memory = VmMemory::with_dataset(Arc::new(seed_memory));
+    let before = dataset_stats::snapshot();
+    let mut reg = [0; 8];
+    memory.dataset_read(0, &mut reg).unwrap();
+    memory.dataset_read(0, &mut reg).unwrap();
+    memory.dataset_read(64, &mut reg).unwrap();
+    dataset_stats::flush();
+
+    //other tests may read at the same time
+    let window = dataset_stats::snapshot().since(&before);
+    if dataset_stats::timed() {
+        assert!(window.hits >= 1);
+        assert!(window.misses >= 2);
+        assert!(window.latency.iter().sum::<u64>() >= 1);
+    } else {
+        assert_eq!(window, DatasetStats::default());
+    }
+}
@@ -1,7 +1,7 @@
-extern crate mithril;
+extern crate mithril_randomx;
 
-use mithril::randomx::hash::{gen_program_aes_1rx4, gen_program_aes_4rx4};
-use mithril::randomx::m128::m128i;
+use mithril_randomx::hash::{gen_program_aes_1rx4, gen_program_aes_4rx4};
+use mithril_randomx::m128::m128i;
 
 #[test]
 #[allow(overflowing_literals)]
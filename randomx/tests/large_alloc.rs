@@ -0,0 +1,218 @@
+extern crate mithril_randomx;
+
+use mithril_randomx::config::RandomXConfig;
+use mithril_randomx::large_alloc::{
+    self, AllocKind, Allocators, Guarded, HugeTlb, LargeAlloc, Malloc, Mmap, SharedFile, Shm,
+};
+#[cfg(feature = "full")]
+use mithril_randomx::memory::{MemoryError, VmMemory};
+use mithril_randomx::memory::SeedMemory;
+use mithril_randomx::pages::{LargeBuffer, PageBuffer};
+use std::io;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[test]
+fn test_builtin_allocators() {
+    for alloc in [
+        Arc::new(Malloc) as Arc<dyn LargeAlloc>,
+        Arc::new(Mmap),
+        Arc::new(Shm),
+    ] {
+        let mut buffer = LargeBuffer::allocate(1000, 7u64, alloc.clone(), |_, _| {});
+        assert_eq!(buffer.len(), 1000);
+        assert!(buffer.iter().all(|v| *v == 7), "{}", alloc.name());
+        buffer[999] = 1;
+        assert_eq!(buffer[999], 1);
+        assert!(!buffer.large_pages());
+    }
+
+    //hugetlb needs reserved pages, the buffer is on the heap without them
+    let mut buffer = LargeBuffer::allocate(3, 0u64, Arc::new(HugeTlb), |_, _| {});
+    buffer.copy_from_slice(&[1, 2, 3]);
+    assert_eq!(&buffer[..], &[1, 2, 3]);
+    assert_eq!(buffer.large_pages(), buffer.large_pages_error().is_none());
+}
+
+#[test]
+fn test_alloc_kind_names() {
+    assert_eq!("hugetlb".parse::<AllocKind>(), Ok(AllocKind::HugeTlb));
+    assert_eq!("shm".parse::<AllocKind>(), Ok(AllocKind::Shm));
+    assert_eq!(AllocKind::Mmap.allocator().name(), "mmap");
+    assert!("jemalloc".parse::<AllocKind>().is_err());
+}
+
+#[test]
+fn test_failing_allocator_falls_back_to_the_heap() {
+    let mut prepared = 0;
+    let buffer = LargeBuffer::allocate(16, 0u64, Arc::new(Failing), |_, bytes| prepared = bytes);
+    assert_eq!(&buffer[..], &[0; 16]);
+    assert_eq!(prepared, 128);
+    assert_eq!(
+        buffer.large_pages_error().unwrap().to_string(),
+        "out of memory"
+    );
+
+    let empty = LargeBuffer::allocate(0, 0u64, Arc::new(Failing), |_, _| panic!());
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn test_try_allocate_fails_without_the_heap() {
+    //more than the heap can lay out, the failing allocator is asked first
+    let err = LargeBuffer::try_allocate(1 << 60, 0u64, Arc::new(Failing), |_, _| panic!()).err().unwrap();
+    assert!(err.to_string().starts_with("out of memory, on the heap: "), "{}", err);
+
+    let err = LargeBuffer::try_allocate(usize::MAX, 0u64, Arc::new(Failing), |_, _| panic!()).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+    assert!(err.to_string().contains("overflow"), "{}", err);
+
+    let buffer = LargeBuffer::try_allocate(16, 0u64, Arc::new(Failing), |_, _| {}).unwrap();
+    assert_eq!(&buffer[..], &[0; 16]);
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn test_dataset_allocation_error() {
+    let config = RandomXConfig {
+        dataset_base_bytes: 1 << 62,
+        dataset_extra_bytes: 0,
+        ..small_config()
+    };
+    let seed_memory = SeedMemory::try_new_initialised_with(b"key", Arc::new(config)).unwrap();
+    match VmMemory::try_with_dataset_in(Arc::new(seed_memory), Arc::new(Failing)) {
+        Err(err @ MemoryError::Allocation { what: "dataset", .. }) => {
+            assert!(err.to_string().starts_with("could not allocate the "), "{}", err);
+            assert!(err.to_string().contains("on the heap"), "{}", err);
+        }
+        Err(err) => panic!("unexpected error {}", err),
+        Ok(_) => panic!("the dataset was allocated"),
+    }
+}
+
+#[test]
+fn test_embedder_allocator() {
+    let counting = Arc::new(Counting::default());
+    large_alloc::set_allocators(Allocators::all(counting.clone()));
+
+    let scratchpad = PageBuffer::new(1024);
+    assert!(scratchpad.large_pages());
+    let seed_memory =
+        SeedMemory::try_new_initialised_with(b"key", Arc::new(small_config())).unwrap();
+    assert_eq!(
+        counting.allocated.load(Ordering::SeqCst),
+        8192 + 1024 * 1024
+    );
+
+    large_alloc::set_allocators(Allocators::default());
+    //freed by the allocator they are from
+    drop(scratchpad);
+    drop(seed_memory);
+    assert_eq!(counting.allocated.load(Ordering::SeqCst), 0);
+    let normal = PageBuffer::normal(1024);
+    assert!(!normal.large_pages());
+    assert_eq!(counting.allocated.load(Ordering::SeqCst), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_guarded_buffer_ends_at_a_guard_page() {
+    let mut buffer = LargeBuffer::allocate(1000, 7u64, Arc::new(Guarded), |_, _| {});
+    //allocated by the guarded allocator, not the heap fallback
+    assert!(buffer
+        .large_pages_error()
+        .unwrap()
+        .to_string()
+        .contains("guarded"));
+    assert!(buffer.iter().all(|v| *v == 7));
+    buffer[999] = 1;
+    let end = buffer.as_ptr() as usize + 8000;
+    assert_eq!(end % 4096, 0);
+
+    #[cfg(target_os = "linux")]
+    {
+        let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+        let protection = |address: usize| {
+            maps.lines()
+                .find(|line| {
+                    let range = line.split(' ').next().unwrap();
+                    let (start, end) = range.split_once('-').unwrap();
+                    let start = usize::from_str_radix(start, 16).unwrap();
+                    let end = usize::from_str_radix(end, 16).unwrap();
+                    start <= address && address < end
+                })
+                .map(|line| line.split(' ').nth(1).unwrap().to_string())
+        };
+        assert_eq!(protection(end).as_deref(), Some("---p"));
+        assert_eq!(protection(end - 1).as_deref(), Some("rw-p"));
+        let start = buffer.as_ptr() as usize / 4096 * 4096;
+        assert_eq!(protection(start - 1).as_deref(), Some("---p"));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_shared_file_is_mapped_copy_on_write() {
+    let path = std::env::temp_dir().join("mithril_test_shared_file");
+    let mut shared = LargeBuffer::allocate(1000, 7u64, Arc::new(SharedFile::new(&path)), |_, _| {});
+    assert!(shared.shared());
+    shared[3] = 3;
+
+    let mut mapped = unsafe { large_alloc::map_file::<u64>(&path, 1000).unwrap() };
+    assert!(mapped.shared());
+    assert_eq!(mapped[3], 3);
+    assert_eq!(mapped[999], 7);
+    mapped[4] = 4;
+    assert_eq!(shared[4], 7);
+    assert!(unsafe { large_alloc::map_file::<u64>(&path, 999) }.is_err());
+
+    //removed by the owner, the mapping stays valid
+    drop(shared);
+    assert!(!path.exists());
+    assert_eq!(mapped[3], 3);
+}
+
+//helper
+
+struct Failing;
+
+impl LargeAlloc for Failing {
+    fn alloc(&self, _bytes: usize) -> io::Result<NonNull<u8>> {
+        Err(io::Error::new(io::ErrorKind::OutOfMemory, "out of memory"))
+    }
+
+    unsafe fn free(&self, _ptr: NonNull<u8>, _bytes: usize) {
+        panic!("nothing was allocated");
+    }
+}
+
+/// Claims large pages, to tell its buffers apart
+#[derive(Default)]
+struct Counting {
+    allocated: AtomicUsize,
+}
+
+impl LargeAlloc for Counting {
+    fn alloc(&self, bytes: usize) -> io::Result<NonNull<u8>> {
+        self.allocated.fetch_add(bytes, Ordering::SeqCst);
+        Malloc.alloc(bytes)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, bytes: usize) {
+        self.allocated.fetch_sub(bytes, Ordering::SeqCst);
+        Malloc.free(ptr, bytes)
+    }
+
+    fn large_pages(&self) -> bool {
+        true
+    }
+}
+
+fn small_config() -> RandomXConfig {
+    RandomXConfig {
+        argon_memory_kib: 1024,
+        argon_iterations: 1,
+        ..RandomXConfig::monero()
+    }
+}
@@ -1,6 +1,6 @@
-extern crate mithril;
+extern crate mithril_randomx;
 
-use mithril::randomx::m128::{m128d, m128i};
+use mithril_randomx::m128::{m128d, m128i};
 
 #[test]
 #[allow(overflowing_literals)]
@@ -0,0 +1,268 @@
+extern crate lazy_static;
+extern crate mithril_randomx;
+
+use lazy_static::lazy_static;
+use mithril_randomx::config::RandomXConfig;
+#[cfg(feature = "full")]
+use mithril_randomx::memory::DatasetNuma;
+use mithril_randomx::memory::{self, init_dataset_item, DatasetError, SeedMemory, VmMemory, VmMemoryAllocator};
+#[cfg(feature = "full")]
+use mithril_randomx::pages::NumaPolicy;
+use mithril_randomx::vm::new_vm_normal_pages;
+#[cfg(feature = "full")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    static ref TEST_SEED_MEM: SeedMemory = SeedMemory::new_initialised(b"test key 000");
+}
+
+#[test]
+fn test_seed_memory_new_initialised() {
+    assert_eq!(TEST_SEED_MEM.blocks[0].as_ref()[0], 0x191e0e1d23c02186);
+    assert_eq!(TEST_SEED_MEM.blocks[12253].as_ref()[29], 0xf1b62fe6210bf8b1);
+    assert_eq!(
+        TEST_SEED_MEM.blocks[262143].as_ref()[127],
+        0x1f47f056d05cd99b
+    );
+}
+
+#[test]
+fn test_init_dataset_item() {
+    let item = init_dataset_item(&TEST_SEED_MEM, 0).unwrap();
+    assert_eq!(item[0], 0x680588a85ae222db);
+
+    let item = init_dataset_item(&TEST_SEED_MEM, 10000000).unwrap();
+    assert_eq!(item[0], 0x7943a1f6186ffb72);
+
+    let item = init_dataset_item(&TEST_SEED_MEM, 20000000).unwrap();
+    assert_eq!(item[0], 0x9035244d718095e1);
+
+    let item = init_dataset_item(&TEST_SEED_MEM, 30000000).unwrap();
+    assert_eq!(item[0], 0x145a5091f7853099);
+}
+
+#[test]
+fn test_init_dataset_item_with_a_smaller_cache() {
+    //the parameters say 4 MB, the cache has 1 MB
+    let mut seed_memory = small_seed_memory();
+    seed_memory.config = Arc::new(RandomXConfig { argon_memory_kib: 4096, ..small_config() });
+    match init_dataset_item(&seed_memory, 0) {
+        Err(DatasetError::BlockOutOfRange { block, blocks }) => {
+            assert!(block >= 1024);
+            assert_eq!(blocks, 1024);
+        }
+        other => panic!("expected a block out of range, got {:?}", other),
+    }
+
+    let memory = VmMemory::with_cache(Arc::new(seed_memory));
+    let mut reg = [0; 8];
+    assert!(memory.dataset_read(0, &mut reg).is_err());
+}
+
+#[test]
+fn test_light_memory_mode() {
+    let memory = VmMemory::no_memory();
+    assert_eq!(memory.mode(), "light");
+    assert_eq!(memory.dataset_fill(), 0.0);
+}
+
+#[test]
+fn test_memory_usage_light() {
+    let memory = Arc::new(VmMemory::with_cache(Arc::new(small_seed_memory())));
+    assert_eq!(memory.cache_bytes(), 1024 * 1024);
+    assert_eq!(memory.dataset_bytes(), 0);
+    assert_eq!(memory.scratchpads.buffers(), 0);
+
+    let vm = new_vm_normal_pages(memory.clone());
+    let vm2 = new_vm_normal_pages(memory.clone());
+    assert_eq!(memory.scratchpads.buffers(), 2);
+    assert_eq!(memory.scratchpads.large_pages(), 0);
+    drop(vm);
+    drop(vm2);
+    assert_eq!(memory.scratchpads.buffers(), 0);
+}
+
+#[test]
+fn test_retire_frees_after_last_worker() {
+    let memory = Arc::new(VmMemory::no_memory());
+    let weak = Arc::downgrade(&memory);
+    let worker = memory.clone();
+    memory::retire(memory);
+    thread::sleep(Duration::from_millis(300));
+    assert!(weak.upgrade().is_some());
+
+    //the worker only drops its reference, the reaper frees the memory
+    drop(worker);
+    assert!(wait_until_freed(&weak));
+}
+
+#[test]
+fn test_reallocate_retires_the_previous_memory() {
+    let mut allocator = VmMemoryAllocator::with_config(false, Arc::new(small_config()));
+    allocator.reallocate("aa".to_string()).unwrap();
+    let previous = Arc::downgrade(&allocator.vm_memory);
+    allocator.reallocate("bb".to_string()).unwrap();
+    assert!(wait_until_freed(&previous));
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn test_memory_usage_full() {
+    let memory = VmMemory::with_dataset(Arc::new(small_seed_memory()));
+    //every item is stored with the flag whether it is computed
+    assert_eq!(memory.dataset_bytes(), (1 << 20) / 64 * 72);
+    assert_eq!(memory.dataset_filled_bytes(), 0);
+    memory.init_dataset(2);
+    assert_eq!(memory.dataset_filled_bytes(), memory.dataset_bytes());
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn test_dataset_numa_policy() {
+    let memory = VmMemory::with_dataset(Arc::new(small_seed_memory()));
+    assert_eq!(memory.dataset_numa, Some(DatasetNuma::Local));
+
+    memory::set_numa_policy(NumaPolicy::Interleave);
+    let memory = VmMemory::with_dataset(Arc::new(small_seed_memory()));
+    memory::set_numa_policy(NumaPolicy::Local);
+    //fails where the OS has no NUMA support, the dataset is allocated anyway
+    assert_ne!(memory.dataset_numa, Some(DatasetNuma::Local));
+    memory.init_dataset(2);
+    assert!(memory.verify_checksum());
+
+    assert_eq!(VmMemory::no_memory().dataset_numa, None);
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn test_dataset_checksum() {
+    let memory = VmMemory::with_dataset(Arc::new(small_seed_memory()));
+    assert!(memory.verify_checksum());
+    memory.init_dataset(2);
+    assert!(memory.verify_checksum());
+    assert_eq!(memory.verify_items(&[0, 5, 100]), Vec::<u64>::new());
+
+    //a flipped bit, as failing RAM would cause it
+    memory.dataset_memory.write().unwrap()[5].as_mut().unwrap()[3] ^= 1 << 17;
+    assert!(!memory.verify_checksum());
+    assert_eq!(memory.verify_items(&[0, 5, 100]), vec![5]);
+
+    memory.rebuild_dataset(2);
+    assert!(memory.verify_checksum());
+    assert_eq!(memory.verify_items(&[0, 5, 100]), Vec::<u64>::new());
+    assert_eq!(memory.dataset_fill(), 1.0);
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn test_dataset_read_out_of_range() {
+    let memory = VmMemory::with_dataset(Arc::new(small_seed_memory()));
+    let mut reg = [0; 8];
+    assert_eq!(
+        memory.dataset_read(16384 * 64, &mut reg),
+        Err(DatasetError::ItemOutOfRange { item: 16384, items: 16384 })
+    );
+    assert_eq!(reg, [0; 8]);
+    memory.dataset_prefetch(16384 * 64);
+    assert_eq!(memory.dataset_read(16383 * 64, &mut reg), Ok(()));
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn test_dataset_recovers_from_a_poisoned_lock() {
+    let memory = Arc::new(VmMemory::with_dataset(Arc::new(small_seed_memory())));
+    memory.init_dataset(2);
+    let panicking = memory.clone();
+    let result = thread::spawn(move || {
+        let mut mem = panicking.dataset_memory.write().unwrap();
+        mem[5] = None;
+        panic!("hashing thread panicked");
+    })
+    .join();
+    assert!(result.is_err());
+    assert!(memory.dataset_memory.is_poisoned());
+
+    //the items are dropped, the counts match them again
+    assert!(memory.read_dataset().iter().all(|item| item.is_none()));
+    assert!(!memory.dataset_memory.is_poisoned());
+    assert_eq!(memory.dataset_fill(), 0.0);
+    assert!(memory.verify_checksum());
+    let mut reg = [0; 8];
+    assert_eq!(memory.dataset_read(5 * 64, &mut reg), Ok(()));
+    assert_eq!(reg, init_dataset_item(&small_seed_memory(), 5).unwrap());
+    assert!(memory.verify_checksum());
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn test_init_dataset_rate_limit() {
+    let memory = VmMemory::with_dataset(Arc::new(small_seed_memory()));
+    let started_threads = AtomicUsize::new(0);
+    let start = Instant::now();
+    //16384 items in 0.25 seconds
+    memory.init_dataset_with(2, 4 * 16384, || {
+        started_threads.fetch_add(1, Ordering::SeqCst);
+    });
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(started_threads.load(Ordering::SeqCst), 2);
+    assert_eq!(memory.dataset_fill(), 1.0);
+    assert!(memory.verify_checksum());
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn test_init_dataset_stops_when_retired() {
+    let memory = Arc::new(VmMemory::with_dataset(Arc::new(small_seed_memory())));
+    memory::retire(memory.clone());
+    memory.init_dataset(2);
+    assert_eq!(memory.dataset_fill(), 0.0);
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn test_init_dataset_abort_and_progress() {
+    let memory = VmMemory::with_dataset(Arc::new(small_seed_memory()));
+    //every thread aborts the init before its first batch
+    memory.init_dataset_with(2, 0, || memory.abort_init());
+    assert_eq!(memory.dataset_fill(), 0.0);
+    assert_eq!(memory.init_progress(), 0);
+
+    memory.init_dataset(2);
+    assert_eq!(memory.dataset_fill(), 1.0);
+    let progress = memory.init_progress();
+    assert_eq!(progress, 16384);
+    //computed batches are skipped, but still progress
+    memory.init_dataset(2);
+    assert_eq!(memory.init_progress(), progress + 16384);
+    assert!(memory.verify_checksum());
+}
+
+//helper
+
+fn wait_until_freed(weak: &Weak<VmMemory>) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if weak.strong_count() == 0 {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+fn small_config() -> RandomXConfig {
+    RandomXConfig {
+        argon_memory_kib: 1024,
+        argon_iterations: 1,
+        dataset_base_bytes: 1 << 20,
+        dataset_extra_bytes: 0,
+        ..RandomXConfig::monero()
+    }
+}
+
+fn small_seed_memory() -> SeedMemory {
+    SeedMemory::try_new_initialised_with(b"test key 000", Arc::new(small_config())).unwrap()
+}
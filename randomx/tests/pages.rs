@@ -0,0 +1,117 @@
+extern crate mithril_randomx;
+
+use mithril_randomx::pages;
+use mithril_randomx::pages::{NumaPolicy, PageBacking, PageBuffer};
+
+#[test]
+fn test_page_buffer_zeroed_and_writable() {
+    let mut buffer = PageBuffer::new(262144);
+
+    assert_eq!(buffer.len(), 262144);
+    assert!(buffer.iter().all(|v| *v == 0));
+    buffer[0] = 1;
+    buffer[262143] = 2;
+    assert_eq!(buffer[0], 1);
+    assert_eq!(buffer[262143], 2);
+}
+
+#[test]
+fn test_page_buffer_smaller_than_a_page() {
+    let mut buffer = PageBuffer::new(3);
+
+    assert_eq!(&buffer[..], &[0, 0, 0]);
+    buffer.copy_from_slice(&[1, 2, 3]);
+    assert_eq!(&buffer[..], &[1, 2, 3]);
+}
+
+#[test]
+fn test_parse_smaps() {
+    let smaps = "\
+55d0c0000000-55d0c0021000 rw-p 00000000 00:00 0                          [heap]
+Size:                132 kB
+AnonHugePages:         0 kB
+7f0000000000-7f0080000000 rw-p 00000000 00:00 0
+Size:            2097152 kB
+Rss:             2097152 kB
+AnonHugePages:   1048576 kB
+VmFlags: rd wr mr mw me ac sd hg
+7f0080000000-7f0080200000 rw-p 00000000 00:00 0
+Size:               2048 kB
+AnonHugePages:      2048 kB
+";
+    assert_eq!(
+        pages::parse_smaps(smaps, 0x7f0040000000),
+        Some(PageBacking {
+            bytes: 2 * 1024 * 1024 * 1024,
+            huge_bytes: 1024 * 1024 * 1024,
+        })
+    );
+    assert_eq!(
+        pages::parse_smaps(smaps, 0x55d0c0000010),
+        Some(PageBacking {
+            bytes: 0x21000,
+            huge_bytes: 0,
+        })
+    );
+    assert_eq!(pages::parse_smaps(smaps, 0x1000), None);
+}
+
+#[test]
+fn test_parse_thp_mode() {
+    assert_eq!(
+        pages::parse_thp_mode("always [madvise] never\n"),
+        Some("madvise")
+    );
+    assert_eq!(
+        pages::parse_thp_mode("[always] madvise never\n"),
+        Some("always")
+    );
+    assert_eq!(pages::parse_thp_mode(""), None);
+}
+
+#[test]
+fn test_parse_node_list() {
+    assert_eq!(pages::parse_node_list("0\n"), Some(0b1));
+    assert_eq!(pages::parse_node_list("0-3\n"), Some(0b1111));
+    assert_eq!(pages::parse_node_list("0-1,4,6-7"), Some(0b1101_0011));
+    assert_eq!(pages::parse_node_list("63"), Some(1 << 63));
+    assert_eq!(pages::parse_node_list("64"), None);
+    assert_eq!(pages::parse_node_list("3-1"), None);
+    assert_eq!(pages::parse_node_list(""), None);
+}
+
+#[test]
+fn test_numa_policy_names() {
+    assert_eq!("local".parse::<NumaPolicy>(), Ok(NumaPolicy::Local));
+    assert_eq!(
+        "interleave".parse::<NumaPolicy>(),
+        Ok(NumaPolicy::Interleave)
+    );
+    assert!("replicate".parse::<NumaPolicy>().is_err());
+    assert_eq!(NumaPolicy::default(), NumaPolicy::Local);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_page_backing_of_advised_memory() {
+    let bytes = 8 * 1024 * 1024;
+    let mut memory = vec![0u8; bytes];
+    //fails if the kernel was built without transparent huge pages
+    let _ = pages::advise_huge_pages(memory.as_ptr(), bytes);
+    memory.iter_mut().step_by(4096).for_each(|b| *b = 1);
+
+    let backing = pages::page_backing(memory[bytes / 2..].as_ptr()).unwrap();
+
+    assert!(backing.bytes >= 2 * 1024 * 1024);
+    assert!(backing.huge_bytes <= backing.bytes);
+    assert!(pages::advise_huge_pages(memory.as_ptr(), 4096).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_interleave_numa() {
+    let memory = vec![0u8; 4 * 1024 * 1024];
+    //a machine has at least one node, a single node interleaves to itself
+    assert!(pages::interleave_numa(memory.as_ptr(), memory.len()).unwrap() >= 1);
+    assert!(pages::interleave_numa(memory.as_ptr(), 100).is_err());
+}
@@ -1,10 +1,11 @@
-extern crate mithril;
+extern crate mithril_randomx;
 #[macro_use(assert_diff)]
 extern crate difference;
 
-use mithril::randomx::hash::{gen_program_aes_1rx4, gen_program_aes_4rx4};
-use mithril::randomx::m128::m128i;
-use mithril::randomx::program::{decode_instruction, Opcode, Program, MAX_REG};
+use mithril_randomx::hash::{gen_program_aes_1rx4, gen_program_aes_4rx4};
+use mithril_randomx::m128::m128i;
+use mithril_randomx::program::{decode_instruction, Opcode, Program, MAX_REG};
+use mithril_randomx::RandomXConfig;
 
 #[test]
 fn test_decode_instruction_imul_rcp() {
@@ -128,6 +129,24 @@ fn test_decode_program_2_4rx4_and_register_usage() {
     assert_eq!(program.register_usage[7], 254);
 }
 
+#[test]
+fn test_decode_program_graft_rotations() {
+    //the last IROR_R opcode of rx/0 is an IROL_R in rx/graft
+    let mut bytes = vec![m128i::zero(); 8];
+    bytes.push(m128i::from_u64(0x71, 0x70));
+
+    let program = Program::from_bytes(bytes.clone());
+    assert_eq!(program.program[0].op, Opcode::IROR_R);
+    assert_eq!(program.program[1].op, Opcode::IROR_R);
+
+    let program = Program::from_bytes_with(bytes.clone(), &RandomXConfig::graft());
+    assert_eq!(program.program[0].op, Opcode::IROR_R);
+    assert_eq!(program.program[1].op, Opcode::IROL_R);
+
+    let program = Program::from_bytes_with(bytes, &RandomXConfig::monero());
+    assert_eq!(program.program[1].op, Opcode::IROR_R);
+}
+
 //helper
 
 #[allow(overflowing_literals)]
@@ -0,0 +1,79 @@
+#![cfg(feature = "full")]
+
+extern crate mithril_randomx;
+
+use mithril_randomx::randomx_rs::{RandomXCache, RandomXError, RandomXFlag, RandomXVM};
+
+#[test]
+fn test_calculate_hash() {
+    let flags = RandomXFlag::get_recommended_flags();
+    let cache = RandomXCache::new(flags, b"test key 000").unwrap();
+    let vm = RandomXVM::new(flags, Some(cache), None).unwrap();
+
+    let hash = vm.calculate_hash(b"This is a test").unwrap();
+
+    assert_eq!(
+        hex(&hash),
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f"
+    );
+    assert!(matches!(
+        vm.calculate_hash(b""),
+        Err(RandomXError::ParameterError(_))
+    ));
+}
+
+#[test]
+fn test_calculate_hash_set_and_reinit_cache() {
+    let flags = RandomXFlag::FLAG_DEFAULT;
+    let cache = RandomXCache::new(flags, b"test key 000").unwrap();
+    let mut vm = RandomXVM::new(flags, Some(cache), None).unwrap();
+
+    let hashes = vm
+        .calculate_hash_set(&[b"This is a test", b"Lorem ipsum dolor sit amet"])
+        .unwrap();
+    assert_eq!(
+        hashes.iter().map(|h| hex(h)).collect::<Vec<_>>(),
+        vec![
+            "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+            "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969",
+        ]
+    );
+
+    vm.reinit_cache(RandomXCache::new(flags, b"test key 001").unwrap())
+        .unwrap();
+    let hash = vm
+        .calculate_hash(b"sed do eiusmod tempor incididunt ut labore et dolore magna aliqua")
+        .unwrap();
+    assert_eq!(
+        hex(&hash),
+        "e9ff4503201c0c2cca26d285c93ae883f9b1d30c9eb240b820756f2d5a7905fc"
+    );
+}
+
+#[test]
+fn test_flag_errors() {
+    let cache = RandomXCache::new(RandomXFlag::FLAG_DEFAULT, b"test key 000").unwrap();
+
+    let full_mem = RandomXVM::new(RandomXFlag::FLAG_FULL_MEM, Some(cache.clone()), None);
+    assert!(matches!(full_mem, Err(RandomXError::FlagConfigError(_))));
+
+    let jit = RandomXVM::new(RandomXFlag::FLAG_JIT, Some(cache), None);
+    assert!(matches!(jit, Err(RandomXError::FlagConfigError(_))));
+
+    assert!(RandomXCache::new(RandomXFlag::FLAG_DEFAULT, b"").is_err());
+}
+
+#[test]
+fn test_flags() {
+    let flags = RandomXFlag::FLAG_FULL_MEM | RandomXFlag::FLAG_ARGON2;
+    assert_eq!(flags.bits(), 100);
+    assert!(flags.contains(RandomXFlag::FLAG_ARGON2_AVX2));
+    assert!(!flags.contains(RandomXFlag::FLAG_JIT));
+    assert!(!RandomXFlag::get_recommended_flags().contains(RandomXFlag::FLAG_JIT));
+}
+
+//helper
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
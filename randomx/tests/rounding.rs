@@ -0,0 +1,200 @@
+extern crate mithril_randomx;
+
+use mithril_randomx::rounding::{self, ROUND_DOWN, ROUND_TO_NEAREST, ROUND_TO_ZERO, ROUND_UP};
+
+//the operands and results of the fadd_r, fsub_r, fmul_r and fsqrt_r tests of the vm
+
+#[test]
+fn test_add() {
+    let a = f64::from_bits(0x40b8f684057a59e1);
+    let b = f64::from_bits(0xc1ce30b3c4223576);
+    assert_eq!(
+        bits(rounding::add(a, b, ROUND_TO_NEAREST)),
+        0xc1ce30a748e032b9
+    );
+    assert_eq!(bits(rounding::add(a, b, ROUND_DOWN)), 0xc1ce30a748e032b9);
+    assert_eq!(bits(rounding::add(a, b, ROUND_UP)), 0xc1ce30a748e032b8);
+    assert_eq!(bits(rounding::add(a, b, ROUND_TO_ZERO)), 0xc1ce30a748e032b8);
+
+    let a = f64::from_bits(0x402a26a86a60c8fb);
+    let b = f64::from_bits(0x3ffd2c97cc4ef015);
+    assert_eq!(
+        bits(rounding::add(a, b, ROUND_TO_NEAREST)),
+        0x402dcc3b63eaa6fe
+    );
+    assert_eq!(bits(rounding::add(a, b, ROUND_DOWN)), 0x402dcc3b63eaa6fd);
+    assert_eq!(bits(rounding::add(a, b, ROUND_UP)), 0x402dcc3b63eaa6fe);
+    assert_eq!(bits(rounding::add(a, b, ROUND_TO_ZERO)), 0x402dcc3b63eaa6fd);
+}
+
+#[test]
+fn test_sub() {
+    let a = f64::from_bits(0xc1ce30b3c4223576);
+    let b = f64::from_bits(0x40b8f684057a59e1);
+    assert_eq!(
+        bits(rounding::sub(a, b, ROUND_TO_NEAREST)),
+        0xc1ce30c03f643833
+    );
+    assert_eq!(bits(rounding::sub(a, b, ROUND_DOWN)), 0xc1ce30c03f643834);
+    assert_eq!(bits(rounding::sub(a, b, ROUND_UP)), 0xc1ce30c03f643833);
+    assert_eq!(bits(rounding::sub(a, b, ROUND_TO_ZERO)), 0xc1ce30c03f643833);
+}
+
+#[test]
+fn test_mul() {
+    let a = f64::from_bits(0x41dbc35cef248783);
+    let b = f64::from_bits(0x40eba861aa31c7c0);
+    assert_eq!(
+        bits(rounding::mul(a, b, ROUND_TO_NEAREST)),
+        0x42d7feeccd89152f
+    );
+    assert_eq!(bits(rounding::mul(a, b, ROUND_DOWN)), 0x42d7feeccd89152e);
+    assert_eq!(bits(rounding::mul(a, b, ROUND_UP)), 0x42d7feeccd89152f);
+    assert_eq!(bits(rounding::mul(a, b, ROUND_TO_ZERO)), 0x42d7feeccd89152e);
+
+    let a = f64::from_bits(0x40fdfdabb6173d07);
+    let b = f64::from_bits(0x41c4561212ae2d50);
+    assert_eq!(
+        bits(rounding::mul(a, b, ROUND_TO_NEAREST)),
+        0x42d30f35ff7a6969
+    );
+    assert_eq!(bits(rounding::mul(a, b, ROUND_UP)), 0x42d30f35ff7a696a);
+}
+
+#[test]
+fn test_sqrt() {
+    let a = f64::from_bits(0x41b6b21c11affea7);
+    assert_eq!(
+        bits(rounding::sqrt(a, ROUND_TO_NEAREST)),
+        0x40d30e573fa3ba8d
+    );
+    assert_eq!(bits(rounding::sqrt(a, ROUND_DOWN)), 0x40d30e573fa3ba8c);
+    assert_eq!(bits(rounding::sqrt(a, ROUND_UP)), 0x40d30e573fa3ba8d);
+    assert_eq!(bits(rounding::sqrt(a, ROUND_TO_ZERO)), 0x40d30e573fa3ba8c);
+
+    let a = f64::from_bits(0x40526a7e778d9824);
+    assert_eq!(
+        bits(rounding::sqrt(a, ROUND_TO_NEAREST)),
+        0x40212a610b301fe8
+    );
+    assert_eq!(bits(rounding::sqrt(a, ROUND_UP)), 0x40212a610b301fe9);
+}
+
+#[test]
+fn test_div() {
+    assert_eq!(rounding::div(1.0, 3.0, ROUND_DOWN), 1.0 / 3.0);
+    assert_eq!(rounding::div(1.0, 3.0, ROUND_UP), (1.0f64 / 3.0).next_up());
+    assert_eq!(rounding::div(-1.0, 3.0, ROUND_TO_ZERO), -1.0 / 3.0);
+    assert_eq!(
+        rounding::div(-1.0, 3.0, ROUND_DOWN),
+        (-1.0f64 / 3.0).next_down()
+    );
+    assert_eq!(rounding::div(-2.0, 3.0, ROUND_UP), -2.0 / 3.0);
+    assert_eq!(
+        rounding::div(2.0, -3.0, ROUND_DOWN),
+        (-2.0f64 / 3.0).next_down()
+    );
+    assert_eq!(rounding::div(1.0, -4.0, ROUND_UP), -0.25);
+}
+
+#[test]
+fn test_exact_results_are_not_moved() {
+    for mode in 0..4 {
+        assert_eq!(rounding::add(1.5, 2.25, mode), 3.75);
+        assert_eq!(rounding::mul(-1.5, 2.0, mode), -3.0);
+        assert_eq!(rounding::div(3.0, -2.0, mode), -1.5);
+        assert_eq!(rounding::sqrt(6.25, mode), 2.5);
+    }
+}
+
+#[test]
+fn test_zero_sum_sign() {
+    assert!(rounding::add(1.5, -1.5, ROUND_TO_NEAREST).is_sign_positive());
+    assert!(rounding::add(1.5, -1.5, ROUND_DOWN).is_sign_negative());
+    assert!(rounding::sub(1.5, 1.5, ROUND_DOWN).is_sign_negative());
+    assert!(rounding::add(0.0, 0.0, ROUND_DOWN).is_sign_positive());
+    assert!(rounding::add(-0.0, -0.0, ROUND_UP).is_sign_negative());
+}
+
+#[test]
+fn test_overflow() {
+    assert_eq!(
+        rounding::mul(f64::MAX, 2.0, ROUND_TO_NEAREST),
+        f64::INFINITY
+    );
+    assert_eq!(rounding::mul(f64::MAX, 2.0, ROUND_UP), f64::INFINITY);
+    assert_eq!(rounding::mul(f64::MAX, 2.0, ROUND_DOWN), f64::MAX);
+    assert_eq!(rounding::mul(f64::MAX, 2.0, ROUND_TO_ZERO), f64::MAX);
+    assert_eq!(rounding::add(-f64::MAX, -f64::MAX, ROUND_UP), -f64::MAX);
+    assert_eq!(rounding::div(1.0, 0.0, ROUND_DOWN), f64::INFINITY);
+    assert!(rounding::sqrt(-1.0, ROUND_DOWN).is_nan());
+}
+
+#[test]
+fn test_mode() {
+    assert_eq!(rounding::mode(), ROUND_TO_NEAREST);
+    rounding::set_mode(ROUND_TO_ZERO);
+    assert_eq!(rounding::mode(), ROUND_TO_ZERO);
+    rounding::set_mode(ROUND_UP | 4);
+    assert_eq!(rounding::mode(), ROUND_UP);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_same_as_hardware_rounding() {
+    use mithril_randomx::m128::m128d;
+    use mithril_randomx::memory::VmMemory;
+    use mithril_randomx::vm::new_vm_normal_pages;
+    use std::sync::Arc;
+
+    let mut vm = new_vm_normal_pages(Arc::new(VmMemory::no_memory()));
+    let mut seed = 0x9e3779b97f4a7c15u64;
+    for _ in 0..10000 {
+        let a = random_float(&mut seed);
+        let b = random_float(&mut seed);
+        let (ma, mb) = (m128d::from_f64(a, a), m128d::from_f64(b, b));
+        let positive = m128d::from_f64(a.abs(), a.abs());
+        for mode in 0..4 {
+            vm.set_rounding_mode(mode);
+            let hardware = [
+                (ma + mb).as_f64().0,
+                (ma - mb).as_f64().0,
+                (ma * mb).as_f64().0,
+                (ma / mb).as_f64().0,
+                positive.sqrt().as_f64().0,
+            ];
+            vm.reset_rounding_mode();
+            let software = [
+                rounding::add(a, b, mode),
+                rounding::sub(a, b, mode),
+                rounding::mul(a, b, mode),
+                rounding::div(a, b, mode),
+                rounding::sqrt(a.abs(), mode),
+            ];
+            assert_eq!(
+                hardware.map(bits),
+                software.map(bits),
+                "{} {} {}",
+                a,
+                b,
+                mode
+            );
+        }
+    }
+}
+
+//helper
+
+fn bits(f: f64) -> u64 {
+    f.to_bits()
+}
+
+/// Random sign, mantissa and an exponent within +-64 like the RandomX registers
+#[cfg(target_arch = "x86_64")]
+fn random_float(seed: &mut u64) -> f64 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    let exponent = 1023 - 64 + (*seed >> 52) % 128;
+    f64::from_bits((*seed & 0x800f_ffff_ffff_ffff) | (exponent << 52))
+}
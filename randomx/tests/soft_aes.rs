@@ -0,0 +1,83 @@
+extern crate mithril_randomx;
+
+use mithril_randomx::soft_aes::{aesdec, aesenc, expand_key_256};
+
+#[cfg(target_arch = "x86_64")]
+use mithril_randomx::m128::m128i;
+
+#[test]
+fn test_aesenc_fips_197_round() {
+    //round 1 of the AES-128 example of FIPS-197 appendix B
+    let state = [
+        0x19, 0x3d, 0xe3, 0xbe, 0xa0, 0xf4, 0xe2, 0x2b, 0x9a, 0xc6, 0x8d, 0x2a, 0xe9, 0xf8, 0x48,
+        0x08,
+    ];
+    let key = [
+        0xa0, 0xfa, 0xfe, 0x17, 0x88, 0x54, 0x2c, 0xb1, 0x23, 0xa3, 0x39, 0x39, 0x2a, 0x6c, 0x76,
+        0x05,
+    ];
+    let expected = [
+        0xa4, 0x9c, 0x7f, 0xf2, 0x68, 0x9f, 0x35, 0x2b, 0x6b, 0x5b, 0xea, 0x43, 0x02, 0x6a, 0x50,
+        0x49,
+    ];
+    assert_eq!(aesenc(state, key), expected);
+}
+
+#[test]
+fn test_expand_key_256_fips_197() {
+    //the AES-256 key expansion example of FIPS-197 appendix A.3
+    let key = [
+        0x60, 0x3d, 0xeb, 0x10, 0x15, 0xca, 0x71, 0xbe, 0x2b, 0x73, 0xae, 0xf0, 0x85, 0x7d, 0x77,
+        0x81, 0x1f, 0x35, 0x2c, 0x07, 0x3b, 0x61, 0x08, 0xd7, 0x2d, 0x98, 0x10, 0xa3, 0x09, 0x14,
+        0xdf, 0xf4,
+    ];
+    let round_keys = expand_key_256(&key, 15);
+    assert_eq!(round_keys.len(), 15);
+    assert_eq!(round_keys[0], key[..16]);
+    assert_eq!(round_keys[1], key[16..]);
+    assert_eq!(
+        round_keys[2],
+        [
+            0x9b, 0xa3, 0x54, 0x11, 0x8e, 0x69, 0x25, 0xaf, 0xa5, 0x1a, 0x8b, 0x5f, 0x20, 0x67,
+            0xfc, 0xde
+        ]
+    );
+    assert_eq!(
+        round_keys[14],
+        [
+            0xfe, 0x48, 0x90, 0xd1, 0xe6, 0x18, 0x8d, 0x0b, 0x04, 0x6d, 0xf3, 0x44, 0x70, 0x6c,
+            0x63, 0x1e
+        ]
+    );
+    assert_eq!(expand_key_256(&key, 10)[..], round_keys[..10]);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_soft_aes_matches_aes_instructions() {
+    let mut state = [0u8; 16];
+    let mut key = [0u8; 16];
+    for round in 0..64u8 {
+        for i in 0..16 {
+            state[i] = state[i].wrapping_mul(31).wrapping_add(round ^ i as u8);
+            key[i] = key[i]
+                .wrapping_mul(17)
+                .wrapping_add(round.wrapping_add(7 * i as u8));
+        }
+        let m_state = m128i::from_u8(&state);
+        let m_key = m128i::from_u8(&key);
+
+        assert_eq!(
+            m128i::from_u8(&aesenc(state, key)),
+            m_state.aesenc(m_key),
+            "aesenc round {}",
+            round
+        );
+        assert_eq!(
+            m128i::from_u8(&aesdec(state, key)),
+            m_state.aesdec(m_key),
+            "aesdec round {}",
+            round
+        );
+    }
+}
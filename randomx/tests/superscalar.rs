@@ -1,8 +1,8 @@
-extern crate mithril;
+extern crate mithril_randomx;
 #[macro_use(assert_diff)]
 extern crate difference;
 
-use mithril::randomx::superscalar::{Blake2Generator, ScProgram};
+use mithril_randomx::superscalar::{Blake2Generator, ScProgram};
 
 #[test]
 fn test_generate_1() {
@@ -29,6 +29,67 @@ fn test_generate_rage() {
 	assert_diff!(EXPECTED_SUPERSCALAR_PROG_RAGE, &prog.to_string(), "\n", 0);
 }
 
+#[test]
+fn test_disassemble() {
+	let mut gen = Blake2Generator::new(b"test key 000", 0);
+	let prog = ScProgram::generate(&mut gen);
+	let text = prog.disassemble();
+	let lines: Vec<&str> = text.lines().collect();
+	assert_eq!(lines.len(), prog.prog.len() + 1);
+	assert_eq!(
+		&lines[..9],
+		&[
+			"; address register r4",
+			"IMUL_R r3, r0",
+			"IMUL_R r4, r1",
+			"IMUL_R r6, r7",
+			"IROR_C r7, 44",
+			"IADD_RS r2, r1, shift 1",
+			"IXOR_C9 r0, 1759898121",
+			"ISMULH_R r1, r5",
+			"IMUL_RCP r0, 227866060",
+		]
+	);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_program() {
+	let mut gen = Blake2Generator::new(b"666", 0);
+	let prog = ScProgram::generate(&mut gen);
+	let json = serde_json::to_string(&prog).unwrap();
+	let restored: ScProgram = serde_json::from_str(&json).unwrap();
+	assert_eq!(restored.disassemble(), prog.disassemble());
+
+	let mut expected = [1, 2, 3, 4, 5, 6, 7, 8];
+	let mut ds = expected;
+	prog.execute(&mut expected);
+	restored.execute(&mut ds);
+	assert_eq!(ds, expected);
+
+	let invalid = json.replacen("\"dst\":", "\"dst\":8,\"x\":", 1);
+	assert!(serde_json::from_str::<ScProgram>(&invalid).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_generator() {
+	let mut gen = Blake2Generator::new(b"RageAgainstTheMachine", 0);
+	gen.get_u32();
+	let json = serde_json::to_string(&gen).unwrap();
+	let mut restored: Blake2Generator = serde_json::from_str(&json).unwrap();
+	assert_eq!(restored.index(), 4);
+	assert_eq!(
+		ScProgram::generate(&mut restored).disassemble(),
+		ScProgram::generate(&mut gen).disassemble()
+	);
+
+	assert!(serde_json::from_str::<Blake2Generator>("{\"index\":0,\"data\":[1,2]}").is_err());
+	let data = format!("[{}]", vec!["0"; 64].join(","));
+	let beyond = format!("{{\"index\":65,\"data\":{}}}", data);
+	assert!(serde_json::from_str::<Blake2Generator>(&beyond).is_err());
+}
+
 //helper + testdata
 
 const EXPECTED_SUPERSCALAR_PROG_1: &str = r#"op: IMUL_R, src: 0, dst: 3
@@ -1,17 +1,19 @@
 extern crate blake2b_simd;
-extern crate mithril;
+extern crate mithril_randomx;
 
 use self::blake2b_simd::blake2b;
-use mithril::byte_string::{string_to_u8_array, u8_array_to_string};
-use mithril::randomx::common::randomx_reciprocal;
-use mithril::randomx::hash::gen_program_aes_4rx4;
-use mithril::randomx::m128::m128d;
-use mithril::randomx::memory::VmMemory;
-use mithril::randomx::program::{
+use mithril_randomx::common::randomx_reciprocal;
+use mithril_randomx::hash::gen_program_aes_4rx4;
+use mithril_randomx::m128::m128d;
+use mithril_randomx::memory::{SeedMemory, VmMemory};
+use mithril_randomx::program::{
     a_reg, e_reg, f_reg, r_reg, Instr, Mode, Opcode, Program, Store, REG_NEEDS_DISPLACEMENT,
     REG_NEEDS_DISPLACEMENT_IX,
 };
-use mithril::randomx::vm::{hash_to_m128i_array, new_register, new_vm, Vm};
+use mithril_randomx::vm::{
+    hash_to_m128i_array, new_register, new_vm, Vm, SCRATCHPAD_L2_MASK, SCRATCHPAD_L3_MASK,
+};
+use mithril_randomx::RandomXConfig;
 use std::sync::Arc;
 
 #[allow(overflowing_literals)]
@@ -45,14 +47,141 @@ fn test_calculate_hash_1_with_light_memory() {
     );
 }
 
+#[test]
+fn test_calculate_hash_with_derivative_configs() {
+    let rx0_hash = "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f";
+    let mut hashes = Vec::new();
+    for config in [RandomXConfig::graft(), RandomXConfig::keva()] {
+        let seed_memory =
+            SeedMemory::try_new_initialised_with(b"test key 000", Arc::new(config.clone()))
+                .unwrap();
+        let mut vm = new_vm(Arc::new(VmMemory::with_cache(Arc::new(seed_memory))));
+        assert_eq!(vm.scratchpad.len() * 8, config.scratchpad_l3_bytes);
+        assert_eq!(vm.scratchpad_l3_mask, config.scratchpad_l3_mask());
+
+        let hash = u8_array_to_string(vm.calculate_hash(b"This is a test").as_bytes());
+        assert_ne!(hash, rx0_hash);
+        assert_eq!(
+            hash,
+            u8_array_to_string(vm.calculate_hash(b"This is a test").as_bytes())
+        );
+        hashes.push(hash);
+    }
+    assert_ne!(hashes[0], hashes[1]);
+}
+
+#[test]
+fn test_derivative_parameters() {
+    //the published configurations of xmrig, RandomGRAFT and RandomKEVA
+    assert_eq!(
+        RandomXConfig::graft(),
+        RandomXConfig {
+            argon_lanes: 2,
+            argon_salt: b"RandomX-Graft\x01".to_vec(),
+            program_size: 280,
+            freq_iror_r: 7,
+            freq_irol_r: 3,
+            ..RandomXConfig::monero()
+        }
+    );
+    assert_eq!(
+        RandomXConfig::keva(),
+        RandomXConfig {
+            argon_salt: b"RandomKV\x01".to_vec(),
+            scratchpad_l2_bytes: 131072,
+            scratchpad_l3_bytes: 1048576,
+            ..RandomXConfig::monero()
+        }
+    );
+}
+
+#[test]
+fn test_derivative_scratchpad_masks() {
+    assert_eq!(
+        RandomXConfig::monero().scratchpad_l2_mask(),
+        SCRATCHPAD_L2_MASK
+    );
+    assert_eq!(
+        RandomXConfig::monero().scratchpad_l3_mask(),
+        SCRATCHPAD_L3_MASK
+    );
+    assert_eq!(RandomXConfig::keva().scratchpad_l2_mask(), 0x1fff8);
+    assert_eq!(RandomXConfig::keva().scratchpad_l3_mask(), 0xffff8);
+    assert_eq!(RandomXConfig::default(), RandomXConfig::monero());
+
+    let vm = new_test_vm();
+    assert_eq!(vm.scratchpad.len(), 262144);
+    assert_eq!(vm.scratchpad_l2_mask, SCRATCHPAD_L2_MASK);
+}
+
+#[test]
+fn test_config_problems() {
+    for config in [
+        RandomXConfig::monero(),
+        RandomXConfig::graft(),
+        RandomXConfig::keva(),
+        small_config(),
+    ] {
+        assert_eq!(config.problems(), Vec::<String>::new());
+    }
+    assert_eq!(RandomXConfig::monero().dataset_item_count(), 34078719);
+    assert_eq!(RandomXConfig::monero().dataset_base_mask(), 0x7fffffc0);
+
+    let broken = RandomXConfig {
+        argon_salt: b"short".to_vec(),
+        dataset_base_bytes: 3000,
+        program_size: 100,
+        scratchpad_l3_bytes: 65536,
+        freq_iror_r: 9,
+        ..RandomXConfig::monero()
+    };
+    let problems = broken.problems();
+    assert_eq!(problems.len(), 5);
+    assert_eq!(problems[0], "argon_salt has 5 bytes, at least 8 are needed");
+}
+
+#[test]
+fn test_calculate_hash_with_custom_config() {
+    let config = Arc::new(small_config());
+    let seed_memory =
+        Arc::new(SeedMemory::try_new_initialised_with(b"test key 000", config.clone()).unwrap());
+    assert_eq!(seed_memory.blocks.len(), 1024);
+    assert_eq!(seed_memory.programs.len(), 4);
+
+    let mut light_vm = new_vm(Arc::new(VmMemory::with_cache(seed_memory.clone())));
+    assert_eq!(light_vm.dataset_extra_items, 1);
+    let hash = u8_array_to_string(light_vm.calculate_hash(b"This is a test").as_bytes());
+    assert_ne!(
+        hash,
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f"
+    );
+
+    #[cfg(feature = "full")]
+    {
+        let memory = Arc::new(VmMemory::with_dataset(seed_memory));
+        memory.init_dataset(2);
+        assert_eq!(memory.dataset_fill(), 1.0);
+        let mut full_vm = new_vm(memory);
+        assert_eq!(
+            u8_array_to_string(full_vm.calculate_hash(b"This is a test").as_bytes()),
+            hash
+        );
+    }
+}
+
+#[cfg(feature = "full")]
 #[test]
 fn test_calculate_hash_1_with_full_memory() {
-    let mut vm = new_vm(Arc::new(VmMemory::full(b"test key 000")));
+    let memory = Arc::new(VmMemory::full(b"test key 000"));
+    assert_eq!(memory.mode(), "full");
+    assert_eq!(memory.dataset_fill(), 0.0);
+    let mut vm = new_vm(memory.clone());
     let result = vm.calculate_hash(b"This is a test");
     assert_eq!(
         "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
         u8_array_to_string(result.as_bytes())
     );
+    assert!(memory.dataset_fill() > 0.0);
 
     let result = vm.calculate_hash(b"Lorem ipsum dolor sit amet");
     assert_eq!(
@@ -87,6 +216,7 @@ fn test_calculate_hash_2_with_light_memory() {
     );
 }
 
+#[cfg(feature = "full")]
 #[test]
 fn test_calculate_hash_2_with_full_memory() {
     let mut vm = new_vm(Arc::new(VmMemory::full(b"test key 001")));
@@ -107,6 +237,7 @@ fn test_calculate_hash_2_with_full_memory() {
 }
 
 //Bugfix Test
+#[cfg(feature = "full")]
 #[test]
 fn test_calculate_hash_3_with_full_memory() {
     let mut vm = new_vm(Arc::new(VmMemory::full(&string_to_u8_array(
@@ -731,7 +862,7 @@ fn test_exec_iror_r_with_immediate() {
         op: Opcode::IROR_R,
         dst: r_reg(0),
         src: Store::NONE,
-        imm: Some(4569451684712230561 as i32),
+        imm: Some(4569451684712230561_i64 as i32),
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
@@ -773,7 +904,7 @@ fn test_exec_irol_r_with_immediate() {
         op: Opcode::IROL_R,
         dst: r_reg(0),
         src: Store::NONE,
-        imm: Some(4569451684712230561 as i32),
+        imm: Some(4569451684712230561_i64 as i32),
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
@@ -2013,6 +2144,31 @@ fn test_randomx_reciprocal() {
 
 //helper
 
+/// A parameter set that is fast to initialise, 1 MB cache and dataset
+fn small_config() -> RandomXConfig {
+    RandomXConfig {
+        argon_salt: b"RandomX-Test\x01".to_vec(),
+        argon_memory_kib: 1024,
+        argon_iterations: 1,
+        cache_accesses: 4,
+        dataset_base_bytes: 1 << 20,
+        dataset_extra_bytes: 64,
+        program_count: 2,
+        program_iterations: 64,
+        ..RandomXConfig::monero()
+    }
+}
+
 fn new_test_vm() -> Vm {
     new_vm(Arc::new(VmMemory::no_memory()))
 }
+
+fn string_to_u8_array(hex: &str) -> Vec<u8> {
+    (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap())
+        .collect()
+}
+
+fn u8_array_to_string(a: &[u8]) -> String {
+    a.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,25 @@
+extern crate mithril_randomx;
+
+use mithril_randomx::wipe::{wipe, wipe_string};
+
+#[test]
+fn test_wipe() {
+    let mut key = vec![0xab_u8; 32];
+    wipe(&mut key);
+    assert_eq!(key, vec![0; 32]);
+
+    let mut words = [u64::MAX; 4];
+    wipe(&mut words[1..3]);
+    assert_eq!(words, [u64::MAX, 0, 0, u64::MAX]);
+}
+
+#[test]
+fn test_wipe_string() {
+    let mut password = "x:secret".to_string();
+    let bytes = password.as_ptr();
+    wipe_string(&mut password);
+    assert!(password.is_empty());
+    //the buffer is kept and zeroed, not freed with the secret in it
+    assert_eq!(password.as_ptr(), bytes);
+    assert_eq!(unsafe { std::slice::from_raw_parts(bytes, 8) }, &[0; 8]);
+}
@@ -0,0 +1,510 @@
+//! `mithril doctor --analyze`: a short benchmark with the thread count, affinity and memory
+//! mode of the config, compared with the hashrate expected for the CPU. The telemetry
+//! collected before and while hashing points to the likely culprits of a low hashrate,
+//! ordered by the share of the hashrate they cost.
+
+extern crate num_cpus;
+
+use crate::bench::{self, CpuInfo, SEED};
+use crate::cgroup;
+use crate::metric::telemetry::{Telemetry, TelemetrySampler};
+use crate::platform;
+use crate::pow::randomx::MemoryMode;
+use crate::randomx::memory::{SeedMemory, VmMemory};
+use crate::randomx::vm::new_vm;
+use crate::wizard::{self, SCRATCHPAD_CACHE_BYTES};
+use crate::worker::worker_pool::affinity_core;
+
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time the CPU load is measured before the benchmark, mithril is idle then
+#[cfg(target_os = "linux")]
+const BACKGROUND_SAMPLE: Duration = Duration::from_secs(1);
+const TELEMETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// A lower hashrate than this share of the expected one is reported as too low
+const EXPECTED_SHARE: f64 = 0.9;
+/// Clock drop from the first to the last sample that counts as throttling
+const THROTTLE_DROP: f64 = 0.1;
+/// Busy share of the CPU time before the benchmark that counts as background load
+const BACKGROUND_BUSY: f64 = 0.05;
+/// Rough share of the hashrate a scratchpad on 4 KB pages costs
+const SMALL_PAGES_LOSS: f64 = 0.3;
+/// Rough share of the hashrate the light mode costs, it computes every dataset item
+const LIGHT_MODE_LOSS: f64 = 0.8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzeConfig {
+    pub threads: u64,
+    pub affinity: bool,
+    pub memory_mode: MemoryMode,
+    /// hashing time
+    pub duration: Duration,
+}
+
+/// What the benchmark measured and the telemetry around it, the input of `analyze`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evidence {
+    pub cpu: CpuInfo,
+    /// L3 cache of the first core, None if unknown
+    pub l3_cache_bytes: Option<u64>,
+    pub threads: u64,
+    pub hashrate: f64,
+    /// why the benchmark ran in light mode, None in full mode
+    pub light_mode: Option<String>,
+    /// threads whose scratchpad got huge pages
+    pub large_page_threads: u64,
+    /// reserved huge pages, None if unknown
+    pub reserved_huge_pages: Option<u64>,
+    /// average core clock of the first and the last telemetry sample while hashing
+    pub clock_mhz: Option<(f64, f64)>,
+    /// highest package temperature while hashing
+    pub max_temp_celsius: Option<f64>,
+    /// busy share of the CPU time before the benchmark, None if unknown
+    pub background_load: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Culprit {
+    NoHugePages,
+    SmtOversubscription,
+    LightMode,
+    ThermalThrottling,
+    BackgroundLoad,
+}
+
+impl fmt::Display for Culprit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Culprit::NoHugePages => write!(f, "no huge pages"),
+            Culprit::SmtOversubscription => write!(f, "SMT oversubscription"),
+            Culprit::LightMode => write!(f, "light mode"),
+            Culprit::ThermalThrottling => write!(f, "thermal throttling"),
+            Culprit::BackgroundLoad => write!(f, "background load"),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub culprit: Culprit,
+    /// rough share of the hashrate it costs, the findings are ordered by it
+    pub loss: f64,
+    pub evidence: String,
+    pub fix: Option<String>,
+}
+
+/// The hashrate a tuned rig of the CPU generation reaches
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Expectation {
+    pub generation: &'static str,
+    pub hashrate: f64,
+}
+
+/// Serialized for `doctor --analyze --json`, the culprits in snake case
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub threads: u64,
+    pub hashrate: f64,
+    /// None for an unknown CPU
+    pub expected: Option<Expectation>,
+    /// the likely culprits, the most expensive first
+    pub findings: Vec<Finding>,
+}
+
+/// CPU time counters of the `cpu` line of `/proc/stat`, in clock ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuTimes {
+    pub busy: u64,
+    pub total: u64,
+}
+
+/// Measures the background load, prepares the memory like the miner and hashes with the
+/// configured threads while sampling the telemetry
+pub fn run(conf: &AnalyzeConfig) -> Evidence {
+    let background_load = background_load();
+    let (memory, light_mode) = prepare_memory(conf.memory_mode);
+    memory.init_dataset(cgroup::available_cpus());
+
+    let mut sampler = TelemetrySampler::new();
+    let cores = num_cpus::get();
+    let start = Instant::now();
+    let deadline = start + conf.duration;
+    let (threads, samples): (Vec<(u64, bool)>, Vec<Telemetry>) = thread::scope(|scope| {
+        let handles: Vec<_> = (0..conf.threads)
+            .map(|thread| {
+                let memory = memory.clone();
+                let core = conf.affinity.then(|| affinity_core(thread as usize, cores));
+                scope.spawn(move || hash_until(memory, thread, conf.threads, core, deadline))
+            })
+            .collect();
+        let mut samples = Vec::new();
+        while Instant::now() + TELEMETRY_INTERVAL < deadline {
+            thread::sleep(TELEMETRY_INTERVAL);
+            samples.push(sampler.sample());
+        }
+        let threads = handles
+            .into_iter()
+            .map(|handle| handle.join().expect("analyze thread"))
+            .collect();
+        (threads, samples)
+    });
+    let hashes: u64 = threads.iter().map(|(hashes, _)| hashes).sum();
+
+    let clocks: Vec<f64> = samples
+        .iter()
+        .filter_map(|sample| sample.freq_min_avg_max_mhz())
+        .map(|(_, avg, _)| avg)
+        .collect();
+    Evidence {
+        cpu: bench::cpu_info(),
+        l3_cache_bytes: wizard::probe_hardware().l3_cache_bytes,
+        threads: conf.threads,
+        hashrate: hashes as f64 / start.elapsed().as_secs_f64(),
+        light_mode,
+        large_page_threads: threads.iter().filter(|(_, large)| *large).count() as u64,
+        reserved_huge_pages: reserved_huge_pages(),
+        clock_mhz: clocks.first().zip(clocks.last()).map(|(f, l)| (*f, *l)),
+        max_temp_celsius: samples
+            .iter()
+            .filter_map(|sample| sample.package_temp_celsius)
+            .reduce(f64::max),
+        background_load,
+    }
+}
+
+/// The full mode memory unless the config or the container limit select the light mode,
+/// with the light mode fallback of the miner if the dataset cannot be allocated
+fn prepare_memory(mode: MemoryMode) -> (Arc<VmMemory>, Option<String>) {
+    let seed_memory = Arc::new(SeedMemory::new_initialised(SEED));
+    let light = |reason: String| {
+        let memory = VmMemory::with_cache(seed_memory.clone());
+        (Arc::new(memory), Some(reason))
+    };
+    if mode == MemoryMode::Light {
+        return light("memory.mode is light".to_string());
+    }
+    if !mode.full_memory(cgroup::limits().fits_full_memory()) {
+        return light("the container memory limit is below the dataset size".to_string());
+    }
+    match VmMemory::try_with_dataset(seed_memory.clone()) {
+        Ok(memory) => (Arc::new(memory), None),
+        Err(err) => light(format!("the dataset could not be allocated: {}", err)),
+    }
+}
+
+/// Hashes like `bench`, returns the hashes and whether the scratchpad got huge pages
+fn hash_until(
+    memory: Arc<VmMemory>,
+    thread: u64,
+    threads: u64,
+    core: Option<usize>,
+    deadline: Instant,
+) -> (u64, bool) {
+    if let Some(core) = core {
+        if let Err(err) = platform::set_thread_affinity(core) {
+            warn!(
+                "pinning analyze thread {} to core {} failed: {}",
+                thread, core, err
+            );
+        }
+    }
+    let mut vm = new_vm(memory);
+    let mut nonce = thread;
+    let mut hashes = 0;
+    while Instant::now() < deadline {
+        vm.calculate_hash(&bench::nonce_input(nonce));
+        nonce += threads;
+        hashes += 1;
+    }
+    (hashes, vm.scratchpad.large_pages())
+}
+
+/// The generation and the rough per core hashrate of a tuned Linux rig (huge pages, full
+/// mode, one thread per core) for the model name of the CPU. None for an unknown model.
+pub fn cpu_generation(model: &str) -> Option<(&'static str, f64)> {
+    let words: Vec<&str> = model.split_whitespace().collect();
+    //the first word with a 4 or 5 digit model number, i.e. `5950X` or `i7-12700K`
+    let number = |word: &str| -> Option<String> {
+        let digits: String = word.chars().take_while(char::is_ascii_digit).collect();
+        (digits.len() >= 4).then_some(digits)
+    };
+    if words.contains(&"EPYC") {
+        //the last digit of the model number is the generation
+        let digits = words.iter().find_map(|word| number(word))?;
+        return match digits.as_bytes()[3] {
+            b'1' => Some(("Zen", 600.0)),
+            b'2' => Some(("Zen 2", 900.0)),
+            b'3' => Some(("Zen 3", 1100.0)),
+            b'4' => Some(("Zen 4", 1400.0)),
+            b'5' => Some(("Zen 5", 1500.0)),
+            _ => None,
+        };
+    }
+    if words.contains(&"Ryzen") {
+        let digits = words.iter().find_map(|word| number(word))?;
+        return match digits.as_bytes()[0] {
+            b'1' | b'2' => Some(("Zen", 600.0)),
+            b'3' | b'4' => Some(("Zen 2", 900.0)),
+            b'5' | b'6' => Some(("Zen 3", 1100.0)),
+            b'7' | b'8' => Some(("Zen 4", 1400.0)),
+            b'9' => Some(("Zen 5", 1500.0)),
+            _ => None,
+        };
+    }
+    if words.contains(&"Ultra") && model.contains("Core") {
+        return Some(("Core Ultra", 650.0));
+    }
+    let digits = words.iter().find_map(|word| {
+        let (tier, number_part) = word.split_once('-')?;
+        if !matches!(tier, "i3" | "i5" | "i7" | "i9") {
+            return None;
+        }
+        number(number_part)
+    })?;
+    let generation: u32 = if digits.len() == 5 {
+        digits[..2].parse().ok()?
+    } else {
+        digits[..1].parse().ok()?
+    };
+    match generation {
+        2..=5 => Some(("Core (Sandy Bridge to Broadwell)", 350.0)),
+        6..=11 => Some(("Core (Skylake to Rocket Lake)", 500.0)),
+        12..=14 => Some(("Core (Alder Lake to Raptor Lake)", 650.0)),
+        _ => None,
+    }
+}
+
+/// The hashrate the CPU reaches with the threads, SMT threads add nothing to it
+pub fn expected_hashrate(cpu: &CpuInfo, threads: u64) -> Option<Expectation> {
+    let (generation, per_core) = cpu_generation(&cpu.model)?;
+    let cores = threads.min(cpu.physical_cores.max(1) as u64);
+    Some(Expectation {
+        generation,
+        hashrate: per_core * cores as f64,
+    })
+}
+
+/// Compares the evidence with the expectation and finds the culprits
+pub fn analyze(evidence: &Evidence) -> Analysis {
+    let mut findings: Vec<Finding> = [
+        huge_pages_finding(evidence),
+        smt_finding(evidence),
+        light_mode_finding(evidence),
+        throttling_finding(evidence),
+        background_finding(evidence),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    //stable, the order of the culprits above breaks ties
+    findings.sort_by(|a, b| b.loss.total_cmp(&a.loss));
+    Analysis {
+        threads: evidence.threads,
+        hashrate: evidence.hashrate,
+        expected: expected_hashrate(&evidence.cpu, evidence.threads),
+        findings,
+    }
+}
+
+fn huge_pages_finding(evidence: &Evidence) -> Option<Finding> {
+    let small = evidence.threads.saturating_sub(evidence.large_page_threads);
+    if small == 0 {
+        return None;
+    }
+    let mut message = format!(
+        "{} of {} scratchpads are on 4 KB pages",
+        small, evidence.threads
+    );
+    if let Some(reserved) = evidence.reserved_huge_pages {
+        message.push_str(&format!(", {} huge pages are reserved", reserved));
+    }
+    Some(Finding {
+        culprit: Culprit::NoHugePages,
+        loss: SMALL_PAGES_LOSS * small as f64 / evidence.threads as f64,
+        evidence: message,
+        fix: Some(format!(
+            "sudo sysctl -w vm.nr_hugepages={} (add it to /etc/sysctl.conf to keep it)",
+            evidence.threads
+        )),
+    })
+}
+
+fn smt_finding(evidence: &Evidence) -> Option<Finding> {
+    let cores = evidence.cpu.physical_cores.max(1) as u64;
+    if evidence.threads <= cores {
+        return None;
+    }
+    let mut message = format!("{} threads on {} physical cores", evidence.threads, cores);
+    if let Some(l3) = evidence.l3_cache_bytes {
+        let needed = evidence.threads * SCRATCHPAD_CACHE_BYTES;
+        if needed > l3 {
+            message.push_str(&format!(
+                ", their scratchpads need {} MB of L3 cache, the first core has {} MB",
+                needed / 1024 / 1024,
+                l3 / 1024 / 1024
+            ));
+        }
+    }
+    Some(Finding {
+        culprit: Culprit::SmtOversubscription,
+        //the SMT siblings share the L3 and the AES units, they add little
+        loss: 0.5 * (evidence.threads - cores) as f64 / evidence.threads as f64,
+        evidence: message,
+        fix: Some(format!(
+            "num_threads = {} in the [worker] section, or compare with `mithril tune`",
+            cores
+        )),
+    })
+}
+
+fn light_mode_finding(evidence: &Evidence) -> Option<Finding> {
+    let reason = evidence.light_mode.as_ref()?;
+    Some(Finding {
+        culprit: Culprit::LightMode,
+        loss: LIGHT_MODE_LOSS,
+        evidence: format!("every dataset item is computed for each hash, {}", reason),
+        fix: Some(
+            "make 2.3 GB of memory available and set mode = \"auto\" in the [memory] section"
+                .to_string(),
+        ),
+    })
+}
+
+fn throttling_finding(evidence: &Evidence) -> Option<Finding> {
+    let (first, last) = evidence.clock_mhz?;
+    if first <= 0.0 || last >= first * (1.0 - THROTTLE_DROP) {
+        return None;
+    }
+    let mut message = format!(
+        "the average clock fell from {:.0} MHz to {:.0} MHz while hashing",
+        first, last
+    );
+    if let Some(temp) = evidence.max_temp_celsius {
+        message.push_str(&format!(", the package reached {:.0} °C", temp));
+    }
+    Some(Finding {
+        culprit: Culprit::ThermalThrottling,
+        loss: 1.0 - last / first,
+        evidence: message,
+        fix: Some(
+            "improve the cooling, or cap the load with throttle_percent in the [worker] section"
+                .to_string(),
+        ),
+    })
+}
+
+fn background_finding(evidence: &Evidence) -> Option<Finding> {
+    let load = evidence.background_load?;
+    if load < BACKGROUND_BUSY {
+        return None;
+    }
+    Some(Finding {
+        culprit: Culprit::BackgroundLoad,
+        loss: load,
+        evidence: format!(
+            "{:.0}% of the CPU time was busy before the benchmark started",
+            load * 100.0
+        ),
+        fix: Some("stop the other load, `top` shows it".to_string()),
+    })
+}
+
+pub fn format_analysis(analysis: &Analysis) -> String {
+    let mut lines = vec![format!(
+        "hashrate     {:.1} H/s with {} threads",
+        analysis.hashrate, analysis.threads
+    )];
+    let below = match &analysis.expected {
+        Some(expected) => {
+            let share = analysis.hashrate / expected.hashrate;
+            lines.push(format!(
+                "expected     about {:.0} H/s for a {} CPU, {:.0}% reached",
+                expected.hashrate,
+                expected.generation,
+                share * 100.0
+            ));
+            share < EXPECTED_SHARE
+        }
+        None => {
+            lines.push("expected     unknown for this CPU".to_string());
+            false
+        }
+    };
+    lines.push(String::new());
+    if analysis.findings.is_empty() {
+        lines.push(if below {
+            "no known culprit found, single channel memory or missing MSR tuning also lower the hashrate"
+                .to_string()
+        } else {
+            "no culprit found".to_string()
+        });
+        return lines.join("\n");
+    }
+    lines.push("likely culprits:".to_string());
+    for (ix, finding) in analysis.findings.iter().enumerate() {
+        lines.push(format!(
+            "{:>3}. {}: {}",
+            ix + 1,
+            finding.culprit,
+            finding.evidence
+        ));
+        if let Some(fix) = &finding.fix {
+            lines.push(format!("     fix: {}", fix));
+        }
+    }
+    lines.join("\n")
+}
+
+/// The busy and total time of the `cpu` line, the idle and iowait time are not busy
+pub fn parse_cpu_times(stat: &str) -> Option<CpuTimes> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let times: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .map(|time| time.parse().ok())
+        .collect::<Option<_>>()?;
+    if times.len() < 4 {
+        return None;
+    }
+    let total: u64 = times.iter().sum();
+    let idle = times[3] + times.get(4).copied().unwrap_or(0);
+    Some(CpuTimes {
+        busy: total - idle,
+        total,
+    })
+}
+
+/// Busy share of the CPU time between two readings, None if no time passed
+pub fn busy_share(start: CpuTimes, end: CpuTimes) -> Option<f64> {
+    let total = end.total.checked_sub(start.total)?;
+    let busy = end.busy.checked_sub(start.busy)?;
+    (total > 0).then(|| busy as f64 / total as f64)
+}
+
+#[cfg(target_os = "linux")]
+fn background_load() -> Option<f64> {
+    let read = || parse_cpu_times(&std::fs::read_to_string("/proc/stat").ok()?);
+    let start = read()?;
+    thread::sleep(BACKGROUND_SAMPLE);
+    busy_share(start, read()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn background_load() -> Option<f64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn reserved_huge_pages() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    crate::doctor::meminfo_field(&meminfo, "HugePages_Total")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reserved_huge_pages() -> Option<u64> {
+    None
+}
@@ -0,0 +1,541 @@
+extern crate crossbeam_channel;
+
+use self::crossbeam_channel::Sender;
+use crate::console::{steady_hashrate, HASHRATE_WINDOWS};
+use crate::control::ControlCmd;
+use crate::metric::history;
+use crate::metric::history::{HashrateHistory, HashrateLog};
+use crate::metric::stats::MinerStats;
+use crate::pow::PowMemory;
+use crate::profile::MAX_THROTTLE_PERCENT;
+use crate::stratum::stratum_data::PoolConfig;
+use serde_json::{json, Map, Value};
+
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_LINE_BYTES: u64 = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+const ENDPOINTS: [&str; 10] = [
+    "/stats",
+    "/hashrate/history",
+    "/hardware",
+    "/pause",
+    "/resume",
+    "/threads",
+    "/throttle",
+    "/profile",
+    "/pool",
+    "/reload",
+];
+
+#[derive(Debug, Clone)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    /// address the HTTP server listens on, e.g. `127.0.0.1:3380`
+    pub address: String,
+    /// clients have to send `Authorization: Bearer <token>`, empty disables all
+    /// endpoints except `/healthz`
+    pub token: String,
+    pub health: HealthConfig,
+    /// hours of hashrate history for `/hashrate/history`, 0 disables it
+    pub history_hours: u64,
+    pub history_resolution_seconds: u64,
+}
+
+/// Thresholds for `/healthz`
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// unhealthy if no hashes were computed for this long
+    pub hash_stall_seconds: u64,
+    /// unhealthy if the pool is not connected for this long
+    pub disconnect_seconds: u64,
+}
+
+/// The parts of an HTTP request the API looks at
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// without the query string
+    pub path: String,
+    /// the part after `?`, empty without one
+    pub query: String,
+    pub authorization: Option<String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+#[derive(Deserialize)]
+struct ThreadsRequest {
+    num_threads: u64,
+    /// also write the change to the config file
+    #[serde(default)]
+    persist: bool,
+}
+
+#[derive(Deserialize)]
+struct ThrottleRequest {
+    percent: u64,
+    #[serde(default)]
+    persist: bool,
+}
+
+#[derive(Deserialize)]
+struct ProfileRequest {
+    name: String,
+    #[serde(default)]
+    persist: bool,
+}
+
+#[derive(Deserialize)]
+struct PoolRequest {
+    pool_address: String,
+    wallet_address: String,
+    #[serde(default)]
+    pool_password: String,
+    #[serde(default)]
+    persist: bool,
+}
+
+/// Starts the management API thread. Does nothing if the API is disabled.
+pub fn start(conf: ApiConfig, stats: Arc<MinerStats>, sndr: Sender<ControlCmd>) {
+    if !conf.enabled {
+        return;
+    }
+
+    let listener = match TcpListener::bind(&conf.address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!(
+                "management api disabled, could not listen on {}: {}",
+                conf.address, err
+            );
+            return;
+        }
+    };
+    info!("management api listening on {}", conf.address);
+
+    let max_age = HASHRATE_WINDOWS[HASHRATE_WINDOWS.len() - 1]
+        .1
+        .max(Duration::from_secs(conf.health.hash_stall_seconds));
+    let history = history::start_sampling(stats.clone(), max_age);
+    let resolution = Duration::from_secs(conf.history_resolution_seconds);
+    let keep = Duration::from_secs(conf.history_hours * 60 * 60);
+    let hashrate_log = if conf.history_hours > 0 {
+        history::start_logging(stats.clone(), resolution, keep)
+    } else {
+        Arc::new(Mutex::new(HashrateLog::new(resolution, keep)))
+    };
+    let api = Api::new(conf.token, conf.health, stats, history, hashrate_log, sndr);
+    thread::Builder::new()
+        .name("api thread".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|stream| api.serve(stream));
+                if let Err(err) = result {
+                    warn!("management api request failed: {}", err);
+                }
+            }
+        })
+        .expect("api thread handle");
+}
+
+pub struct Api {
+    token: String,
+    health: HealthConfig,
+    started: Instant,
+    stats: Arc<MinerStats>,
+    history: Arc<Mutex<HashrateHistory>>,
+    hashrate_log: Arc<Mutex<HashrateLog>>,
+    sndr: Sender<ControlCmd>,
+}
+
+impl Api {
+    pub fn new(
+        token: String,
+        health: HealthConfig,
+        stats: Arc<MinerStats>,
+        history: Arc<Mutex<HashrateHistory>>,
+        hashrate_log: Arc<Mutex<HashrateLog>>,
+        sndr: Sender<ControlCmd>,
+    ) -> Api {
+        Api {
+            token,
+            health,
+            started: Instant::now(),
+            stats,
+            history,
+            hashrate_log,
+            sndr,
+        }
+    }
+
+    fn serve(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        let response = match read_request(&mut BufReader::new(&stream)) {
+            Ok(request) => self.handle(&request),
+            Err(err) => error_response(400, &err.to_string()),
+        };
+        write_response(&mut &stream, &response)
+    }
+
+    /// Commands are answered with 202 as soon as they are queued for the main loop
+    pub fn handle(&self, request: &Request) -> Response {
+        if request.path == "/healthz" {
+            return match request.method.as_str() {
+                "GET" => self.healthz(),
+                _ => error_response(405, "method not allowed"),
+            };
+        }
+        if !self.authorized(request) {
+            return error_response(401, "missing or invalid token");
+        }
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/stats") => Response {
+                status: 200,
+                body: self.stats_json(),
+            },
+            ("GET", "/hashrate/history") => self.hashrate_history(&request.query),
+            ("GET", "/hardware") => match self.stats.hardware() {
+                Some(hardware) => Response {
+                    status: 200,
+                    body: json!(hardware),
+                },
+                None => error_response(503, "the hardware is not probed yet"),
+            },
+            ("POST", "/pause") => self.send(ControlCmd::Pause),
+            ("POST", "/resume") => self.send(ControlCmd::Resume),
+            ("POST", "/threads") => match serde_json::from_slice::<ThreadsRequest>(&request.body) {
+                Ok(req) if req.num_threads > 0 => {
+                    self.send_change(ControlCmd::SetThreads(req.num_threads), req.persist)
+                }
+                Ok(_) => error_response(400, "num_threads has to be > 0"),
+                Err(err) => error_response(400, &err.to_string()),
+            },
+            ("POST", "/throttle") => match serde_json::from_slice::<ThrottleRequest>(&request.body)
+            {
+                Ok(req) if req.percent <= MAX_THROTTLE_PERCENT => {
+                    self.send_change(ControlCmd::SetThrottle(req.percent), req.persist)
+                }
+                Ok(_) => error_response(
+                    400,
+                    &format!("percent has to be <= {}", MAX_THROTTLE_PERCENT),
+                ),
+                Err(err) => error_response(400, &err.to_string()),
+            },
+            ("POST", "/profile") => match serde_json::from_slice::<ProfileRequest>(&request.body) {
+                Ok(req) if !req.name.is_empty() => {
+                    self.send_change(ControlCmd::SetProfile(req.name), req.persist)
+                }
+                Ok(_) => error_response(400, "name has to be set"),
+                Err(err) => error_response(400, &err.to_string()),
+            },
+            ("POST", "/pool") => match serde_json::from_slice::<PoolRequest>(&request.body) {
+                Ok(req) if !req.pool_address.is_empty() => {
+                    let mut pool_conf = PoolConfig::default();
+                    pool_conf.pool_address = req.pool_address;
+                    pool_conf.wallet_address = req.wallet_address;
+                    pool_conf.pool_password = req.pool_password;
+                    self.send_change(ControlCmd::SwitchPool(Box::new(pool_conf)), req.persist)
+                }
+                Ok(_) => error_response(400, "pool_address has to be set"),
+                Err(err) => error_response(400, &err.to_string()),
+            },
+            ("POST", "/reload") => self.send(ControlCmd::ReloadConfig),
+            (_, path) if ENDPOINTS.contains(&path) => error_response(405, "method not allowed"),
+            _ => error_response(404, "not found"),
+        }
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        let expected = format!("Bearer {}", self.token);
+        match &request.authorization {
+            Some(auth) => {
+                !self.token.is_empty() && constant_time_eq(auth.as_bytes(), expected.as_bytes())
+            }
+            None => false,
+        }
+    }
+
+    fn send(&self, cmd: ControlCmd) -> Response {
+        info!("management api command {:?}", cmd);
+        if self.sndr.send(cmd).is_err() {
+            return error_response(503, "main loop is not running");
+        }
+        Response {
+            status: 202,
+            body: json!({ "status": "accepted" }),
+        }
+    }
+
+    /// Settings changes are written to the config file by the main loop once they are
+    /// applied if `persist` is set
+    fn send_change(&self, cmd: ControlCmd, persist: bool) -> Response {
+        if persist {
+            self.send(ControlCmd::Persist(Box::new(cmd)))
+        } else {
+            self.send(cmd)
+        }
+    }
+
+    /// Not authenticated, so that orchestrators can probe it
+    fn healthz(&self) -> Response {
+        let disconnected_for = match self.stats.connection() {
+            Some(_) => None,
+            None => Some(
+                self.stats
+                    .disconnected_at()
+                    .unwrap_or(self.started)
+                    .elapsed(),
+            ),
+        };
+        let hashrate = self.history.lock().ok().and_then(|history| {
+            history.hashrate(Duration::from_secs(self.health.hash_stall_seconds))
+        });
+        let paused = self.stats.paused();
+        let problems = health_problems(&self.health, paused, hashrate, disconnected_for);
+        let status = if !problems.is_empty() {
+            "unhealthy"
+        } else if paused {
+            "paused"
+        } else {
+            "ok"
+        };
+        Response {
+            status: if problems.is_empty() { 200 } else { 503 },
+            body: json!({ "status": status, "problems": problems }),
+        }
+    }
+
+    /// `since` (unix seconds, default all) and `step` (seconds, default the resolution)
+    /// select the samples, they are `[time, hashrate]` pairs
+    fn hashrate_history(&self, query: &str) -> Response {
+        let param = |name: &str| match query_param(query, name) {
+            Some(value) => value.parse::<u64>().map(Some).map_err(|_| {
+                error_response(400, &format!("{} has to be a number, was {}", name, value))
+            }),
+            None => Ok(None),
+        };
+        let (since, step) = match (param("since"), param("step")) {
+            (Ok(since), Ok(step)) => (since.unwrap_or(0), step),
+            (Err(response), _) | (_, Err(response)) => return response,
+        };
+        let log = match self.hashrate_log.lock() {
+            Ok(log) => log,
+            Err(_) => return error_response(500, "hashrate history is not available"),
+        };
+        let resolution = log.resolution().as_secs();
+        let step = step.unwrap_or(resolution).max(resolution);
+        let samples: Vec<Value> = log
+            .query(since, step)
+            .into_iter()
+            .map(|(time, rate)| json!([time, rate]))
+            .collect();
+        Response {
+            status: 200,
+            body: json!({
+                "resolution_seconds": resolution,
+                "step_seconds": step,
+                "samples": samples,
+            }),
+        }
+    }
+
+    fn stats_json(&self) -> Value {
+        let snapshot = self.stats.snapshot();
+        let connection = self.stats.connection();
+        let mut hashrate = Map::new();
+        let mut steady = None;
+        if let Ok(history) = self.history.lock() {
+            for (name, window) in HASHRATE_WINDOWS.iter() {
+                hashrate.insert(name.to_string(), json!(history.hashrate(*window)));
+            }
+            steady = steady_hashrate(&history);
+        }
+        json!({
+            "paused": self.stats.paused(),
+            "pool": connection.as_ref().map(|(pool, _)| pool.clone()),
+            "connected_seconds": connection.map(|(_, connected_for)| connected_for.as_secs()),
+            "reconnects": self.stats.connects().saturating_sub(1),
+            "hashrate": hashrate,
+            "total_hashes": snapshot.total_hashes,
+            "thread_hashes": self.stats.thread_hashes(),
+            "shares_accepted": snapshot.shares_accepted,
+            "shares_rejected": snapshot.shares_rejected,
+            "difficulty": snapshot.difficulty,
+            "best_share_difficulty": snapshot.best_share_difficulty,
+            "current_effort": snapshot.current_effort(),
+            "average_effort": snapshot.average_effort(),
+            "share_latency_p50_ms": snapshot.share_latency_p50.map(|l| l.as_millis() as u64),
+            "share_latency_p99_ms": snapshot.share_latency_p99.map(|l| l.as_millis() as u64),
+            "dataset_hit_rate": snapshot.dataset_hit_rate,
+            "dataset_read_p50_ns": snapshot.dataset_read_p50.map(|l| l.as_nanos() as u64),
+            "dataset_read_p99_ns": snapshot.dataset_read_p99.map(|l| l.as_nanos() as u64),
+            "earnings": steady.map(|hashrate| self.stats.earnings(hashrate)),
+            "lifetime": self.stats.lifetime(),
+            "memory": self.stats.memory().map(|memory| memory_json(memory.as_ref())),
+            "dataset_errors": self.stats.dataset_errors(),
+            "stratum_errors": self.stats.stratum_errors(),
+        })
+    }
+}
+
+/// The mode and usage of the memory of the current job
+pub fn memory_json(memory: &dyn PowMemory) -> Value {
+    let mut value = json!(memory.usage());
+    value["mode"] = json!(memory.mode());
+    value
+}
+
+/// Reasons why the miner is unhealthy, empty if it is healthy. `hashrate` is the
+/// hashrate over the stall window, None if the miner runs shorter than the window.
+/// A paused miner is healthy, it was stopped on purpose.
+pub fn health_problems(
+    conf: &HealthConfig,
+    paused: bool,
+    hashrate: Option<f64>,
+    disconnected_for: Option<Duration>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    if paused {
+        return problems;
+    }
+    if hashrate == Some(0.0) {
+        problems.push(format!(
+            "no hashes in the last {} seconds",
+            conf.hash_stall_seconds
+        ));
+    }
+    if let Some(disconnected_for) = disconnected_for {
+        if disconnected_for.as_secs() >= conf.disconnect_seconds {
+            problems.push(format!(
+                "pool not connected for {} seconds",
+                disconnected_for.as_secs()
+            ));
+        }
+    }
+    problems
+}
+
+/// Reads one HTTP/1.1 request, bodies are only read if `Content-Length` is set
+pub fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Request> {
+    let request_line = read_line(reader)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return Err(invalid_data("malformed request line")),
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut authorization = None;
+    let mut content_length = 0;
+    let mut headers = 0;
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(invalid_data("too many headers"));
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid_data("malformed header"))?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = value
+                .parse::<usize>()
+                .map_err(|_| invalid_data("invalid content-length"))?;
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Err(invalid_data("body too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        authorization,
+        body,
+    })
+}
+
+/// The value of the parameter in a query string like `since=10&step=60`
+pub fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Reads a line without the line ending
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    reader.take(MAX_LINE_BYTES).read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Err(invalid_data("line too long or connection closed"));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+pub fn write_response<W: Write>(writer: &mut W, response: &Response) -> io::Result<()> {
+    let body = response.body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason_phrase(response.status),
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    }
+}
+
+pub fn error_response(status: u16, message: &str) -> Response {
+    Response {
+        status,
+        body: json!({ "error": message }),
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Compares without an early exit, so the token cannot be guessed byte by byte from
+/// the response time
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
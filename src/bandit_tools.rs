@@ -1,6 +1,5 @@
 extern crate bandit;
 extern crate dirs;
-extern crate num_cpus;
 
 use std::fs::DirBuilder;
 use std::io;
@@ -8,6 +7,7 @@ use std::path::PathBuf;
 
 use self::bandit::softmax::{AnnealingSoftmax, AnnealingSoftmaxConfig};
 use self::bandit::{BanditConfig, Identifiable};
+use crate::cgroup;
 
 const MAX_THREADS_PER_CPU: usize = 4;
 
@@ -23,7 +23,7 @@ impl Identifiable for ThreadArm {
 }
 
 pub fn setup_bandit(log_file: String) -> AnnealingSoftmax<ThreadArm> {
-    let num_arms = num_cpus::get() * MAX_THREADS_PER_CPU;
+    let num_arms = cgroup::available_cpus() * MAX_THREADS_PER_CPU;
     let mut arms = Vec::with_capacity(num_arms);
     for i in 1..num_arms {
         arms.push(ThreadArm {
@@ -0,0 +1,463 @@
+extern crate num_cpus;
+
+use crate::bandit_tools;
+use crate::byte_string;
+use crate::cgroup;
+use crate::metric::telemetry::{self, EnergyCounter, TelemetrySampler};
+use crate::pow::{PowAlgorithm, PowMemory};
+use crate::randomx::memory::{MemoryError, VmMemory};
+use crate::randomx::vm::new_vm;
+use crate::worker::worker_pool::{nonce_hex, with_nonce};
+use serde_json::json;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Key and input of a RandomX test vector, the input is hashed once to verify the build
+pub(crate) const SEED: &[u8] = b"test key 000";
+const VERIFY_INPUT: &[u8] = b"This is a test";
+const VERIFY_HASH: &str = "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f";
+/// hashing blob the nonces are inserted into
+const BLOB: &str = "0606cbe692d005ecfebc7d2249d2b43535c237c02359e888b8b05d2e980c1405779241ac3ab48500000000e62a06e71559c98a37e7b6743465f4f72e42784c5719411c935dc002e347826b05";
+/// block height of the algorithm benchmark, cn/r generates its program from it
+const HEIGHT: u64 = 3_000_000;
+
+#[derive(Debug, PartialEq)]
+pub struct BenchConfig {
+    pub threads: u64,
+    /// hashing time, per algorithm if there are `algorithms`
+    pub duration: Duration,
+    /// benchmarked one after the other and stored in the results file, empty for the
+    /// rx/0 benchmark with the test vector verification
+    pub algorithms: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CpuInfo {
+    pub model: String,
+    pub physical_cores: usize,
+    pub logical_cores: usize,
+}
+
+#[derive(Debug)]
+pub struct BenchReport {
+    pub cpu: CpuInfo,
+    pub memory_mode: &'static str,
+    /// time for the cache and the full dataset
+    pub init_time: Duration,
+    /// measured hashing time, slightly longer than configured
+    pub duration: Duration,
+    pub thread_hashes: Vec<u64>,
+    /// the test vector hash was correct
+    pub verified: bool,
+    /// energy of the CPU packages while hashing, None without RAPL counters
+    pub energy_joules: Option<f64>,
+}
+
+impl BenchReport {
+    pub fn hashrate(&self) -> f64 {
+        self.thread_hashes.iter().sum::<u64>() as f64 / self.duration.as_secs_f64()
+    }
+
+    pub fn joules_per_hash(&self) -> Option<f64> {
+        joules_per_hash(self.energy_joules, &self.thread_hashes)
+    }
+
+    /// H/s/W, the same as hashes per joule
+    pub fn hashrate_per_watt(&self) -> Option<f64> {
+        self.joules_per_hash().map(|joules| 1.0 / joules)
+    }
+}
+
+/// Initializes the full dataset for a fixed seed and hashes for the configured
+/// duration without a pool.
+pub fn run(conf: &BenchConfig) -> BenchReport {
+    let init_start = Instant::now();
+    let memory = Arc::new(VmMemory::full(SEED));
+    memory.init_dataset(cgroup::available_cpus());
+    let init_time = init_start.elapsed();
+
+    let verified = verify_test_vector(memory.clone());
+
+    let energy = EnergyMeter::start();
+    let start = Instant::now();
+    let deadline = start + conf.duration;
+    let thread_hashes = thread::scope(|scope| {
+        let handles: Vec<_> = (0..conf.threads)
+            .map(|thread| {
+                let memory = memory.clone();
+                scope.spawn(move || hash_until(memory, thread, conf.threads, deadline))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("bench thread"))
+            .collect()
+    });
+
+    BenchReport {
+        cpu: cpu_info(),
+        memory_mode: memory.mode(),
+        init_time,
+        duration: start.elapsed(),
+        thread_hashes,
+        verified,
+        energy_joules: energy.joules(),
+    }
+}
+
+/// The hashrate of one algorithm, from `run_algorithm`
+#[derive(Debug)]
+pub struct AlgorithmReport {
+    pub algorithm: &'static str,
+    pub memory_mode: &'static str,
+    /// time for preparing the memory, i.e. the RandomX dataset
+    pub init_time: Duration,
+    pub duration: Duration,
+    pub thread_hashes: Vec<u64>,
+    /// energy of the CPU packages while hashing, None without RAPL counters
+    pub energy_joules: Option<f64>,
+}
+
+impl AlgorithmReport {
+    pub fn hashrate(&self) -> f64 {
+        self.thread_hashes.iter().sum::<u64>() as f64 / self.duration.as_secs_f64()
+    }
+
+    pub fn joules_per_hash(&self) -> Option<f64> {
+        joules_per_hash(self.energy_joules, &self.thread_hashes)
+    }
+
+    /// H/s/W, the same as hashes per joule
+    pub fn hashrate_per_watt(&self) -> Option<f64> {
+        self.joules_per_hash().map(|joules| 1.0 / joules)
+    }
+}
+
+fn joules_per_hash(energy_joules: Option<f64>, thread_hashes: &[u64]) -> Option<f64> {
+    let hashes = thread_hashes.iter().sum::<u64>();
+    energy_joules
+        .filter(|joules| *joules > 0.0 && hashes > 0)
+        .map(|joules| joules / hashes as f64)
+}
+
+/// Reads the RAPL energy counters at the start and the end of the hashing. They count
+/// the energy of the whole packages, so other load on the machine adds to it.
+struct EnergyMeter {
+    sampler: TelemetrySampler,
+    start: Option<Vec<EnergyCounter>>,
+}
+
+impl EnergyMeter {
+    fn start() -> EnergyMeter {
+        let sampler = TelemetrySampler::new();
+        let start = sampler.energy_counters();
+        EnergyMeter { sampler, start }
+    }
+
+    fn joules(&self) -> Option<f64> {
+        let end = self.sampler.energy_counters()?;
+        telemetry::energy_joules(self.start.as_ref()?, &end)
+    }
+}
+
+/// Prepares the memory of the algorithm for a fixed seed and height and hashes for the
+/// configured duration
+pub fn run_algorithm(
+    mut algorithm: Box<dyn PowAlgorithm>,
+    conf: &BenchConfig,
+) -> Result<AlgorithmReport, MemoryError> {
+    let seed_hash = if algorithm.job_hints().seed_hash {
+        byte_string::u8_array_to_string(SEED)
+    } else {
+        String::new()
+    };
+    let init_start = Instant::now();
+    algorithm.prepare(&seed_hash, HEIGHT)?;
+    let init_time = init_start.elapsed();
+    let memory = algorithm.memory();
+
+    let energy = EnergyMeter::start();
+    let start = Instant::now();
+    let deadline = start + conf.duration;
+    let thread_hashes = thread::scope(|scope| {
+        let handles: Vec<_> = (0..conf.threads)
+            .map(|thread| {
+                let memory = memory.clone();
+                scope.spawn(move || hash_memory_until(memory, thread, conf.threads, deadline))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("bench thread"))
+            .collect()
+    });
+
+    Ok(AlgorithmReport {
+        algorithm: algorithm.name(),
+        memory_mode: memory.mode(),
+        init_time,
+        duration: start.elapsed(),
+        thread_hashes,
+        energy_joules: energy.joules(),
+    })
+}
+
+pub(crate) fn verify_test_vector(memory: Arc<VmMemory>) -> bool {
+    new_vm(memory)
+        .calculate_hash(VERIFY_INPUT)
+        .to_hex()
+        .as_str()
+        == VERIFY_HASH
+}
+
+/// The hashing blob with the nonce inserted
+pub(crate) fn nonce_input(nonce: u64) -> Vec<u8> {
+    byte_string::string_to_u8_array(&with_nonce(BLOB, &nonce_hex(nonce as u32)))
+}
+
+/// Hashes the nonces `thread`, `thread + threads`, ... until the deadline
+fn hash_until(memory: Arc<VmMemory>, thread: u64, threads: u64, deadline: Instant) -> u64 {
+    let mut vm = new_vm(memory);
+    let mut nonce = thread;
+    let mut hashes = 0;
+    while Instant::now() < deadline {
+        vm.calculate_hash(&nonce_input(nonce));
+        nonce += threads;
+        hashes += 1;
+    }
+    hashes
+}
+
+/// Like `hash_until`, with the hasher of an algorithm
+fn hash_memory_until(
+    memory: Arc<dyn PowMemory>,
+    thread: u64,
+    threads: u64,
+    deadline: Instant,
+) -> u64 {
+    let mut hasher = memory.hasher();
+    let mut nonce = thread;
+    let mut hashes = 0;
+    while Instant::now() < deadline {
+        hasher.hash(&nonce_input(nonce));
+        nonce += threads;
+        hashes += 1;
+    }
+    hashes
+}
+
+pub fn format_report(report: &BenchReport) -> String {
+    let seconds = report.duration.as_secs_f64();
+    let mut lines = vec![
+        format!("mithril {} benchmark", env!("CARGO_PKG_VERSION")),
+        format!(
+            "cpu          {} ({} cores, {} threads)",
+            report.cpu.model, report.cpu.physical_cores, report.cpu.logical_cores
+        ),
+        format!("memory mode  {}", report.memory_mode),
+        format!("init time    {:.1}s", report.init_time.as_secs_f64()),
+        format!("threads      {}", report.thread_hashes.len()),
+        format!("duration     {:.1}s", seconds),
+        format!("hashrate     {:.1} H/s", report.hashrate()),
+        format!(
+            "efficiency   {}",
+            format_efficiency(report.energy_joules, report.joules_per_hash())
+        ),
+    ];
+    for (ix, hashes) in report.thread_hashes.iter().enumerate() {
+        lines.push(format!(
+            "  thread {:<3}  {:.1} H/s",
+            ix,
+            *hashes as f64 / seconds
+        ));
+    }
+    lines.push(if report.verified {
+        "verification ok".to_string()
+    } else {
+        "verification FAILED, the test vector hash is wrong".to_string()
+    });
+    lines.join("\n")
+}
+
+/// The report of `bench --json`
+pub fn report_json(report: &BenchReport) -> serde_json::Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "cpu": report.cpu,
+        "memory_mode": report.memory_mode,
+        "init_seconds": report.init_time.as_secs_f64(),
+        "duration_seconds": report.duration.as_secs_f64(),
+        "thread_hashes": report.thread_hashes,
+        "hashrate": report.hashrate(),
+        "verified": report.verified,
+        "energy_joules": report.energy_joules,
+        "joules_per_hash": report.joules_per_hash(),
+        "hashrate_per_watt": report.hashrate_per_watt(),
+    })
+}
+
+/// The reports of `bench --algo --json`
+pub fn algorithm_reports_json(cpu: &CpuInfo, reports: &[AlgorithmReport]) -> serde_json::Value {
+    let algorithms: Vec<serde_json::Value> = reports
+        .iter()
+        .map(|report| {
+            json!({
+                "algorithm": report.algorithm,
+                "memory_mode": report.memory_mode,
+                "init_seconds": report.init_time.as_secs_f64(),
+                "duration_seconds": report.duration.as_secs_f64(),
+                "thread_hashes": report.thread_hashes,
+                "hashrate": report.hashrate(),
+                "energy_joules": report.energy_joules,
+                "joules_per_hash": report.joules_per_hash(),
+                "hashrate_per_watt": report.hashrate_per_watt(),
+            })
+        })
+        .collect();
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "cpu": cpu,
+        "algorithms": algorithms,
+    })
+}
+
+pub fn format_algorithm_reports(cpu: &CpuInfo, reports: &[AlgorithmReport]) -> String {
+    let mut lines = vec![
+        format!("mithril {} benchmark", env!("CARGO_PKG_VERSION")),
+        format!(
+            "cpu          {} ({} cores, {} threads)",
+            cpu.model, cpu.physical_cores, cpu.logical_cores
+        ),
+    ];
+    for report in reports {
+        let efficiency = match report.hashrate_per_watt() {
+            Some(hashrate_per_watt) => format!("{:>10.2} H/s/W", hashrate_per_watt),
+            None => format!("{:>16}", "n/a H/s/W"),
+        };
+        lines.push(format!(
+            "{:<16} {:<10} init {:>6.1}s {:>12.1} H/s {}",
+            report.algorithm,
+            report.memory_mode,
+            report.init_time.as_secs_f64(),
+            report.hashrate(),
+            efficiency
+        ));
+    }
+    lines.join("\n")
+}
+
+/// The energy, J/hash and H/s/W of a report
+fn format_efficiency(energy_joules: Option<f64>, joules_per_hash: Option<f64>) -> String {
+    match (energy_joules, joules_per_hash) {
+        (Some(energy), Some(joules)) => format!(
+            "{:.1} J, {:.4} J/hash, {:.2} H/s/W",
+            energy,
+            joules,
+            1.0 / joules
+        ),
+        _ => "n/a, the RAPL energy counters are not readable".to_string(),
+    }
+}
+
+/// The measured hashrate of an algorithm
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlgorithmResult {
+    pub hashrate: f64,
+    pub threads: u64,
+    /// unix time of the benchmark
+    pub time: u64,
+}
+
+/// The hashrates of `mithril bench --algo`, persisted in the mithril folder for the
+/// profit switcher and the `algo-perf` of the login
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct BenchResults {
+    /// by the stratum name of the algorithm
+    pub algorithms: BTreeMap<String, AlgorithmResult>,
+}
+
+impl BenchResults {
+    /// Reads the results, a missing file has none
+    pub fn load(path: &Path) -> io::Result<BenchResults> {
+        if !path.exists() {
+            return Ok(BenchResults::default());
+        }
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(io::Error::other)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        let mut tmp_path = path.to_path_buf().into_os_string();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)
+    }
+
+    /// Replaces the result of the algorithm of the report
+    pub fn add(&mut self, report: &AlgorithmReport) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        self.algorithms.insert(
+            report.algorithm.to_string(),
+            AlgorithmResult {
+                hashrate: report.hashrate(),
+                threads: report.thread_hashes.len() as u64,
+                time,
+            },
+        );
+    }
+
+    pub fn hashrate(&self, algorithm: &str) -> Option<f64> {
+        self.algorithms.get(algorithm).map(|result| result.hashrate)
+    }
+}
+
+pub fn bench_results_file() -> PathBuf {
+    let mut file = bandit_tools::mithril_folder();
+    file.push("bench_results.json");
+    file
+}
+
+/// The stored results, none if the file cannot be read
+pub fn load_bench_results() -> BenchResults {
+    let path = bench_results_file();
+    BenchResults::load(&path).unwrap_or_else(|err| {
+        warn!("could not read {}: {}", path.display(), err);
+        BenchResults::default()
+    })
+}
+
+pub fn cpu_info() -> CpuInfo {
+    #[cfg(target_os = "macos")]
+    let model = crate::platform::sysctl_string("machdep.cpu.brand_string");
+    #[cfg(target_os = "freebsd")]
+    let model = crate::platform::sysctl_string("hw.model");
+    #[cfg(not(any(target_os = "macos", target_os = "freebsd")))]
+    let model = std::fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|cpuinfo| cpu_model(&cpuinfo));
+    let model = model.unwrap_or_else(|| "unknown cpu".to_string());
+    CpuInfo {
+        model,
+        physical_cores: num_cpus::get_physical(),
+        logical_cores: num_cpus::get(),
+    }
+}
+
+/// The model name from the content of `/proc/cpuinfo`
+pub fn cpu_model(cpuinfo: &str) -> Option<String> {
+    cpuinfo
+        .lines()
+        .find(|line| line.starts_with("model name"))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, model)| model.trim().to_string())
+}
@@ -1,41 +1,146 @@
+//! Hex encoding of blobs, hashes, targets and nonces. Decoding is fallible and reports
+//! the position of the first bad char, the hot path of the workers encodes into buffers
+//! it provides instead of allocating.
+
+/// A hex string that could not be decoded or a buffer of the wrong size
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HexError {
+    #[error("odd number of hex chars: {0}")]
+    OddLength(usize),
+    #[error("invalid hex char {found:?} at position {position}")]
+    InvalidChar { position: usize, found: char },
+    #[error("expected {expected} bytes, got {actual}")]
+    Length { expected: usize, actual: usize },
+}
+
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Decodes the hex string, upper and lower case digits are accepted
+pub fn decode(hex: &str) -> Result<Vec<u8>, HexError> {
+    let mut bytes = vec![0; decoded_len(hex)?];
+    decode_into(hex, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Decodes exactly `N` bytes, i.e. a 32 byte hash
+pub fn decode_array<const N: usize>(hex: &str) -> Result<[u8; N], HexError> {
+    let mut bytes = [0; N];
+    decode_into(hex, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Decodes into `out`, which must have the size of the decoded bytes
+pub fn decode_into(hex: &str, out: &mut [u8]) -> Result<(), HexError> {
+    let len = decoded_len(hex)?;
+    if len != out.len() {
+        return Err(HexError::Length {
+            expected: out.len(),
+            actual: len,
+        });
+    }
+    for (i, pair) in hex.as_bytes().chunks_exact(2).enumerate() {
+        out[i] = (nibble(pair[0], 2 * i, hex)? << 4) | nibble(pair[1], 2 * i + 1, hex)?;
+    }
+    Ok(())
+}
+
+fn decoded_len(hex: &str) -> Result<usize, HexError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(HexError::OddLength(hex.len()));
+    }
+    Ok(hex.len() / 2)
+}
+
+fn nibble(c: u8, position: usize, hex: &str) -> Result<u8, HexError> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(HexError::InvalidChar {
+            position,
+            //the char starting at the byte, a multi byte char is not split
+            found: hex
+                .get(position..)
+                .and_then(|rest| rest.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER),
+        }),
+    }
+}
+
+/// Encodes the bytes into `out` as lower case hex without allocating, `out` needs two
+/// bytes per byte
+pub fn encode_into<'a>(bytes: &[u8], out: &'a mut [u8]) -> Result<&'a str, HexError> {
+    let actual = out.len();
+    let out = out.get_mut(..2 * bytes.len()).ok_or(HexError::Length {
+        expected: 2 * bytes.len(),
+        actual,
+    })?;
+    for (i, b) in bytes.iter().enumerate() {
+        out[2 * i] = DIGITS[usize::from(b >> 4)];
+        out[2 * i + 1] = DIGITS[usize::from(b & 0xf)];
+    }
+    Ok(std::str::from_utf8(out).expect("hex digits are ascii"))
+}
+
+/// Compares the bytes in a time that only depends on the length, for hashes and targets
+/// a pool or client sent
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
 //TODO Rename to hex2_u8_array
+/// Decodes up to the first invalid char, which is logged
 pub fn string_to_u8_array(hex: &str) -> Vec<u8> {
-    let mut bytes = Vec::new();
-    for i in 0..(hex.len() / 2) {
-        let res = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16);
-        match res {
-            Ok(v) => bytes.push(v),
-            Err(e) => {
+    let mut bytes = Vec::with_capacity(hex.len() / 2);
+    for (i, pair) in hex.as_bytes().chunks_exact(2).enumerate() {
+        match (nibble(pair[0], 2 * i, hex), nibble(pair[1], 2 * i + 1, hex)) {
+            (Ok(high), Ok(low)) => bytes.push((high << 4) | low),
+            (Err(e), _) | (_, Err(e)) => {
                 error!("Problem with hex: {}", e);
                 return bytes;
             }
-        };
+        }
     }
     bytes
 }
 
+/// Little-endian u32 of the first 8 hex chars (last two chars are most signifiant)
+pub fn decode_u32_le(hex: &str) -> Result<u32, HexError> {
+    let prefix = hex.get(..8).ok_or(HexError::Length {
+        expected: 4,
+        actual: hex.len() / 2,
+    })?;
+    Ok(u32::from_le_bytes(decode_array(prefix)?))
+}
+
+/// Little-endian u64 of up to 16 hex chars
+pub fn decode_u64_le(hex: &str) -> Result<u64, HexError> {
+    let bytes = decode(hex)?;
+    if bytes.len() > 8 {
+        return Err(HexError::Length {
+            expected: 8,
+            actual: bytes.len(),
+        });
+    }
+    let mut le = [0; 8];
+    le[..bytes.len()].copy_from_slice(&bytes);
+    Ok(u64::from_le_bytes(le))
+}
+
 /// Converts the first 8 hex chars of the slice to a u32
 /// number. The hex string is interpreted as litte-endian
 /// (last two chars are most signifiant)
 pub fn hex2_u32_le(hex: &str) -> u32 {
-    let mut result: u32 = 0;
-    for k in (0..8).step_by(2) {
-        let p = u32::from_str_radix(&hex[(8 - k - 2)..(8 - k)], 16).unwrap();
-        result <<= 8;
-        result |= p;
-    }
-    result
+    decode_u32_le(hex).expect("valid hex")
 }
 
-//TODO Write a test
+/// Like `decode_u64_le`, panics on invalid hex
 pub fn hex2_u64_le(hex: &str) -> u64 {
-    let mut result: u64 = 0;
-    for k in (0..hex.len()).step_by(2) {
-        let p = u64::from_str_radix(&hex[(hex.len() - k - 2)..(hex.len() - k)], 16).unwrap();
-        result <<= 8;
-        result |= p;
-    }
-    result
+    decode_u64_le(hex).expect("valid hex")
 }
 
 pub fn hex2_u64_be(hex: &str) -> u64 {
@@ -43,13 +148,12 @@ pub fn hex2_u64_be(hex: &str) -> u64 {
 }
 
 pub fn u8_array_to_string(a: &[u8]) -> String {
-    let mut str = String::new();
-    for a_i in a {
-        str.push_str(&format!("{:02x}", a_i));
-    }
-    str
+    let mut buf = vec![0; 2 * a.len()];
+    encode_into(a, &mut buf)
+        .expect("buffer of two bytes per byte")
+        .to_string()
 }
 
 pub fn u128_to_string(u: u128) -> String {
-    return format!("{:016x}", u);
+    format!("{:016x}", u)
 }
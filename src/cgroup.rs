@@ -0,0 +1,173 @@
+extern crate num_cpus;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Dataset and cache of the full memory mode
+pub const FULL_MEMORY_BYTES: u64 = 2080 * 1024 * 1024 + 256 * 1024 * 1024;
+/// Room for the scratchpads, the binary and the allocator on top of the dataset
+const MEMORY_HEADROOM_BYTES: u64 = 256 * 1024 * 1024;
+/// cgroup v1 reports "no limit" as the largest page aligned i64
+const UNLIMITED_V1_BYTES: u64 = 1 << 62;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+
+/// CPU quota and memory limit of the cgroup mithril runs in (i.e. a container),
+/// None if not limited
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Limits {
+    /// quota divided by period, 1.5 means one and a half cores
+    pub cpus: Option<f64>,
+    pub memory_bytes: Option<u64>,
+}
+
+impl Limits {
+    /// Reads the limits of the cgroup in `/proc/self/cgroup` (cgroup v1 and v2) and of
+    /// all its parents, the lowest limit wins
+    #[cfg(target_os = "linux")]
+    pub fn detect() -> Limits {
+        match fs::read_to_string("/proc/self/cgroup") {
+            Ok(proc_cgroup) => Limits::read(Path::new(CGROUP_ROOT), &proc_cgroup),
+            Err(_) => Limits::default(),
+        }
+    }
+
+    /// Only Linux has cgroups
+    #[cfg(not(target_os = "linux"))]
+    pub fn detect() -> Limits {
+        Limits::default()
+    }
+
+    /// The limits below the cgroup mount `root` for the content of `/proc/self/cgroup`
+    pub fn read(root: &Path, proc_cgroup: &str) -> Limits {
+        let mut limits = Limits::default();
+        for (controllers, path) in proc_cgroup.lines().filter_map(parse_cgroup_line) {
+            if controllers.is_empty() {
+                //v2, one hierarchy for all controllers
+                let cpus = read_min(root, path, |dir| parse_cpu_max(&read(dir, "cpu.max")?));
+                let memory = read_min(root, path, |dir| {
+                    parse_memory_max(&read(dir, "memory.max")?)
+                });
+                limits.cpus = min(limits.cpus, cpus);
+                limits.memory_bytes = min(limits.memory_bytes, memory);
+                continue;
+            }
+            let mount = root.join(controllers.join(","));
+            if controllers.contains(&"cpu") {
+                let cpus = read_min(&mount, path, |dir| {
+                    parse_cfs_quota(
+                        &read(dir, "cpu.cfs_quota_us")?,
+                        &read(dir, "cpu.cfs_period_us")?,
+                    )
+                });
+                limits.cpus = min(limits.cpus, cpus);
+            }
+            if controllers.contains(&"memory") {
+                let memory = read_min(&mount, path, |dir| {
+                    parse_memory_max(&read(dir, "memory.limit_in_bytes")?)
+                });
+                limits.memory_bytes = min(limits.memory_bytes, memory);
+            }
+        }
+        limits
+    }
+
+    /// The cores the workers can use without being throttled, at most `host_cpus`
+    pub fn available_cpus(&self, host_cpus: usize) -> usize {
+        match self.cpus {
+            Some(cpus) => (cpus.ceil() as usize).clamp(1, host_cpus.max(1)),
+            None => host_cpus,
+        }
+    }
+
+    /// False if the full memory mode (the 2 GB dataset) would exceed the memory limit
+    pub fn fits_full_memory(&self) -> bool {
+        self.memory_bytes
+            .is_none_or(|limit| limit >= FULL_MEMORY_BYTES + MEMORY_HEADROOM_BYTES)
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.cpus.is_some() || self.memory_bytes.is_some()
+    }
+}
+
+/// The limits of this process, detected once
+pub fn limits() -> Limits {
+    *LIMITS.get_or_init(Limits::detect)
+}
+
+/// Logical cores minus what the cgroup CPU quota does not allow, use this instead of
+/// `num_cpus::get()` for thread counts
+pub fn available_cpus() -> usize {
+    limits().available_cpus(num_cpus::get())
+}
+
+/// `hierarchy-id:controller,...:path`, v2 has no controllers
+fn parse_cgroup_line(line: &str) -> Option<(Vec<&str>, &str)> {
+    let mut parts = line.splitn(3, ':');
+    let _id = parts.next()?;
+    let controllers = parts.next()?;
+    let path = parts.next()?;
+    let controllers = controllers
+        .split(',')
+        .filter(|c| !c.is_empty())
+        .map(|c| c.trim_start_matches("name="))
+        .collect();
+    Some((controllers, path))
+}
+
+/// Reads the limit of the cgroup directory and all its parents up to `mount`, the
+/// lowest value wins. Inside a container the path of the host is not mounted, then
+/// only the files of `mount` exist.
+fn read_min<T: PartialOrd>(
+    mount: &Path,
+    path: &str,
+    limit: impl Fn(&Path) -> Option<T>,
+) -> Option<T> {
+    let mut dir = PathBuf::from(mount);
+    let mut lowest = limit(&dir);
+    for part in path.split('/').filter(|p| !p.is_empty()) {
+        dir.push(part);
+        lowest = min(lowest, limit(&dir));
+    }
+    lowest
+}
+
+fn read(dir: &Path, file: &str) -> Option<String> {
+    fs::read_to_string(dir.join(file)).ok()
+}
+
+fn min<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b < a { b } else { a }),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// cgroup v2 `cpu.max`: `<quota> <period>` or `max <period>`
+pub fn parse_cpu_max(content: &str) -> Option<f64> {
+    let mut parts = content.split_whitespace();
+    let quota = parts.next()?;
+    let period = parts.next().unwrap_or("100000");
+    parse_cfs_quota(quota, period)
+}
+
+/// cgroup v1 `cpu.cfs_quota_us` and `cpu.cfs_period_us`, a quota of -1 is unlimited
+pub fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota = quota.trim().parse::<i64>().ok().filter(|q| *q > 0)?;
+    let period = period.trim().parse::<i64>().ok().filter(|p| *p > 0)?;
+    Some(quota as f64 / period as f64)
+}
+
+/// cgroup v2 `memory.max` (`max` if unlimited) and v1 `memory.limit_in_bytes`
+pub fn parse_memory_max(content: &str) -> Option<u64> {
+    content
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|bytes| *bytes < UNLIMITED_V1_BYTES)
+}
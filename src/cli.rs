@@ -0,0 +1,471 @@
+extern crate clap;
+
+use self::clap::builder::PossibleValuesParser;
+use self::clap::{value_parser, Args, Parser, Subcommand};
+use crate::bench::{self, BenchConfig};
+use crate::cgroup;
+use crate::cluster::CoordinatorConfig;
+use crate::completions::Shell;
+use crate::console::ConsoleConfig;
+use crate::dataset_server::DatasetServerConfig;
+use crate::hash::HashConfig;
+use crate::mithril_config::{ConfigFormat, MithrilConfig, CONFIG_FILE_NAME};
+use crate::pow;
+use crate::stratum::proxy::ProxyConfig;
+use crate::stratum::stratum_data::DEFAULT_ALGORITHM;
+use crate::stress::StressConfig;
+use crate::tune::{self, TuneConfig};
+use crate::verify_server::VerifyServerConfig;
+
+use log::LevelFilter;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Monero CPU miner. Without a subcommand mithril mines with the config file, the
+/// options override the values of the file.
+#[derive(Debug, Parser)]
+#[command(name = "mithril", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// config file, if it does not exist a config for the hardware is written on the first start
+    #[arg(long, default_value = CONFIG_FILE_NAME)]
+    pub config: PathBuf,
+    /// format of the config file (toml, json), detected from the file extension by default
+    #[arg(long)]
+    pub config_format: Option<ConfigFormat>,
+    /// pool address (host:port)
+    #[arg(short = 'o', long)]
+    pub pool: Option<String>,
+    /// wallet address used as pool login
+    #[arg(short = 'u', long)]
+    pub wallet: Option<String>,
+    /// pool password
+    #[arg(short = 'p', long)]
+    pub password: Option<String>,
+    /// connect to the pool with TLS
+    #[arg(long)]
+    pub tls: bool,
+    /// number of worker threads, disables auto tuning
+    #[arg(long, value_parser = value_parser!(u64).range(1..))]
+    pub threads: Option<u64>,
+    /// worker profile (eco, balanced, max or a [[profile]] of the config), --threads overrides its thread count
+    #[arg(long)]
+    pub profile: Option<String>,
+    /// console log level (off, error, warn, info, debug, trace), overrides RUST_LOG
+    #[arg(long)]
+    pub log_level: Option<LevelFilter>,
+    /// console output without colors, as with the NO_COLOR env var
+    #[arg(long)]
+    pub no_color: bool,
+    /// print only the shares, found blocks and errors on the console
+    #[arg(long)]
+    pub quiet: bool,
+    /// full-screen dashboard instead of the log, needs the tui feature
+    #[arg(long)]
+    pub tui: bool,
+    /// fork to the background and detach from the terminal, the log goes to --log-file (unix only)
+    #[arg(long, conflicts_with = "tui")]
+    pub daemon: bool,
+    /// file the pid is written to, removed again on a clean exit
+    #[arg(long)]
+    pub pidfile: Option<PathBuf>,
+    /// file the log is appended to with --daemon
+    #[arg(long, default_value = "mithril.log")]
+    pub log_file: PathBuf,
+    /// file every job of the pools is written to, for --replay
+    #[arg(long, conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    /// mines the jobs of a --record file offline with their original timing, then exits
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Offline benchmark with a fixed seed, no config or pool needed
+    Bench(BenchArgs),
+    /// Hashes for a long time and verifies the results, for RAM timings and overclocks
+    Stress(StressArgs),
+    /// Hashes a single blob, i.e. to check a rejected share, RandomX in light mode
+    Hash(HashArgs),
+    /// Benchmarks the thread counts, the thread affinity and the huge pages, ranks them and
+    /// optionally writes the fastest settings into the config file
+    Tune(TuneArgs),
+    /// Asks for pool and wallet, probes the hardware and writes a config file
+    Init(InitArgs),
+    /// Checks huge pages, CPU features, memory, limits and the pools, and prints how to
+    /// fix the problems
+    Doctor(DoctorArgs),
+    /// HTTP share validator, `POST /verify` hashes a blob with a nonce and checks it against
+    /// the target
+    VerifyServer(VerifyServerArgs),
+    /// Computes the RandomX datasets in shared memory for the miners of this host that set
+    /// `dataset_server` in the `[memory]` section, they map them instead of 2 GB each
+    DatasetServer(DatasetServerArgs),
+    /// Stratum proxy for a farm, the miners of the LAN share one connection to the pool
+    /// of the config, each one mines its own part of the nonces (nicehash mode)
+    Proxy(ProxyArgs),
+    /// Proxy that also controls its miners, the agents with `[cluster] coordinator`, through
+    /// an HTTP API
+    Coordinator(CoordinatorArgs),
+    /// Prints the completion script of a shell, i.e. `mithril completions bash >
+    /// /etc/bash_completion.d/mithril`
+    Completions(CompletionsArgs),
+    /// Prints the man page in roff, i.e. `mithril manpage > /usr/share/man/man1/mithril.1`
+    Manpage,
+}
+
+#[derive(Debug, Args)]
+pub struct BenchArgs {
+    /// number of hashing threads
+    #[arg(long, default_value_t = cgroup::available_cpus() as u64, value_parser = value_parser!(u64).range(1..))]
+    pub threads: u64,
+    /// hashing time in seconds, per algorithm with --algo
+    #[arg(long, default_value_t = 60, value_parser = value_parser!(u64).range(1..))]
+    pub duration: u64,
+    /// algorithms to benchmark, comma separated or `all`, the hashrates are stored for the
+    /// profit switcher and the pool login
+    #[arg(long, value_delimiter = ',', value_parser = PossibleValuesParser::new(pow::ALGORITHMS.iter().chain(&["all"])))]
+    pub algo: Vec<String>,
+    /// print the report as JSON, the progress goes to stderr
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct StressArgs {
+    /// number of hashing threads
+    #[arg(long, default_value_t = cgroup::available_cpus() as u64, value_parser = value_parser!(u64).range(1..))]
+    pub threads: u64,
+    /// hashing time in seconds
+    #[arg(long, default_value_t = 60 * 60, value_parser = value_parser!(u64).range(1..))]
+    pub duration: u64,
+    /// seconds between the hashrate samples and hash checks
+    #[arg(long, default_value_t = 60, value_parser = value_parser!(u64).range(1..))]
+    pub interval: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct HashArgs {
+    /// the hashing blob of the job, hex encoded
+    pub blob: String,
+    /// algorithm of the job
+    #[arg(long, default_value = DEFAULT_ALGORITHM, value_parser = PossibleValuesParser::new(pow::ALGORITHMS))]
+    pub algo: String,
+    /// seed hash of the job, for RandomX
+    #[arg(long, default_value = "")]
+    pub seed_hash: String,
+    /// block height of the job, for cn/r
+    #[arg(long, default_value_t = 0)]
+    pub height: u64,
+    /// nonce of the share (4 bytes hex encoded), written into the blob
+    #[arg(long)]
+    pub nonce: Option<String>,
+    /// job target (4 or 8 bytes hex encoded), exits with 1 if the hash does not meet it
+    #[arg(long)]
+    pub target: Option<String>,
+    /// print the result as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct TuneArgs {
+    /// thread counts to try, comma separated, by default half the physical cores, the
+    /// physical cores and the logical cores
+    #[arg(long, value_delimiter = ',', value_parser = value_parser!(u64).range(1..))]
+    pub threads: Vec<u64>,
+    /// hashing time in seconds of each combination
+    #[arg(long, default_value_t = 30, value_parser = value_parser!(u64).range(1..))]
+    pub duration: u64,
+    /// write the fastest settings into the --config file
+    #[arg(long)]
+    pub write: bool,
+    /// print the ranked results as JSON, the progress goes to stderr
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct InitArgs {
+    /// file the config is written to
+    #[arg(long, default_value = CONFIG_FILE_NAME)]
+    pub output: PathBuf,
+    /// overwrite an existing file
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DoctorArgs {
+    /// run a short benchmark, compare it with the hashrate expected for the CPU and list
+    /// the likely culprits of a low hashrate
+    #[arg(long)]
+    pub analyze: bool,
+    /// hashing time in seconds of --analyze
+    #[arg(long, default_value_t = 20, value_parser = value_parser!(u64).range(1..), requires = "analyze")]
+    pub duration: u64,
+    /// print the checks (or the analysis) as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyServerArgs {
+    /// address the HTTP server listens on
+    #[arg(long, default_value = "127.0.0.1:9000")]
+    pub listen: String,
+    /// number of requests that are hashed in parallel
+    #[arg(long, default_value_t = cgroup::available_cpus() as u64, value_parser = value_parser!(u64).range(1..))]
+    pub threads: u64,
+    /// number of seed hashes whose cache (256 MB each) is kept
+    #[arg(long, default_value_t = 2, value_parser = value_parser!(u64).range(1..))]
+    pub seeds: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct DatasetServerArgs {
+    /// Unix socket the miners connect to
+    #[arg(long, default_value = "/tmp/mithril-dataset.sock")]
+    pub socket: PathBuf,
+    /// directory of the shared files, a tmpfs
+    #[arg(long, default_value = "/dev/shm")]
+    pub dir: PathBuf,
+    /// number of threads that compute a dataset
+    #[arg(long, default_value_t = cgroup::available_cpus() as u64, value_parser = value_parser!(u64).range(1..))]
+    pub threads: u64,
+    /// number of seed hashes whose dataset (2.3 GB each) is kept
+    #[arg(long, default_value_t = 2, value_parser = value_parser!(u64).range(1..))]
+    pub seeds: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct ProxyArgs {
+    /// address the miners connect to
+    #[arg(long, default_value = "0.0.0.0:3333")]
+    pub listen: String,
+    /// seconds between the stats logs of the miners
+    #[arg(long, default_value_t = 60, value_parser = value_parser!(u64).range(1..))]
+    pub stats_interval: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct CoordinatorArgs {
+    /// address the agents connect to
+    #[arg(long, default_value = "0.0.0.0:3333")]
+    pub listen: String,
+    /// address of the HTTP API, it needs the `[api] token` of the config
+    #[arg(long, default_value = "127.0.0.1:3334")]
+    pub api: String,
+    /// seconds between the stats logs of the agents
+    #[arg(long, default_value_t = 60, value_parser = value_parser!(u64).range(1..))]
+    pub stats_interval: u64,
+    /// TOML config sent to the agents, their config file is read with it on top
+    #[arg(long)]
+    pub fleet_config: Option<PathBuf>,
+}
+
+impl Cli {
+    pub fn config_format(&self) -> ConfigFormat {
+        self.config_format
+            .unwrap_or_else(|| ConfigFormat::detect(&self.config))
+    }
+
+    /// Mining with only command line options, without a config file
+    pub fn has_pool(&self) -> bool {
+        self.pool.is_some() && self.wallet.is_some()
+    }
+
+    /// The config keys that are given on the command line
+    pub fn overridden_keys(&self) -> Vec<&'static str> {
+        let mut keys = Vec::new();
+        if self.pool.is_some() {
+            keys.push("pool.pool_address");
+        }
+        if self.wallet.is_some() {
+            keys.push("pool.wallet_address");
+        }
+        if self.password.is_some() {
+            keys.push("pool.pool_password");
+        }
+        if self.tls {
+            keys.push("pool.tls");
+        }
+        if self.profile.is_some() {
+            keys.push("worker.profile");
+        }
+        if self.threads.is_some() {
+            keys.push("worker.num_threads");
+        }
+        if self.log_level.is_some() {
+            keys.push("log.console_level");
+        }
+        if self.no_color {
+            keys.push("console.color");
+        }
+        if self.quiet {
+            keys.push("console.quiet");
+        }
+        keys
+    }
+
+    /// Replaces the config values that are given on the command line
+    pub fn apply(&self, config: &mut MithrilConfig) {
+        if let Some(pool) = &self.pool {
+            config.pool_conf.pool_address = pool.clone();
+        }
+        if let Some(wallet) = &self.wallet {
+            config.pool_conf.wallet_address = wallet.clone();
+        }
+        if let Some(password) = &self.password {
+            config.pool_conf.pool_password = password.clone();
+        }
+        if self.tls {
+            config.pool_conf.tls = true;
+        }
+        if let Some(profile) = &self.profile {
+            config.select_profile(profile);
+        }
+        if let Some(threads) = self.threads {
+            config.worker_conf.num_threads = threads;
+            config.worker_conf.auto_tune = false;
+        }
+        if let Some(level) = self.log_level {
+            config.log_conf.console_level = Some(level);
+        }
+        self.apply_console(&mut config.console_conf);
+    }
+
+    /// Replaces the console options that are given on the command line, also for the
+    /// subcommands without a config
+    pub fn apply_console(&self, console: &mut ConsoleConfig) {
+        if self.no_color {
+            console.color = false;
+        }
+        if self.quiet {
+            console.quiet = true;
+        }
+    }
+}
+
+impl BenchArgs {
+    pub fn config(&self) -> BenchConfig {
+        let algorithms = if self.algo.iter().any(|algo| algo == "all") {
+            pow::ALGORITHMS
+                .iter()
+                .map(|algo| algo.to_string())
+                .collect()
+        } else {
+            let mut algorithms: Vec<String> = Vec::new();
+            for algo in &self.algo {
+                if !algorithms.contains(algo) {
+                    algorithms.push(algo.clone());
+                }
+            }
+            algorithms
+        };
+        BenchConfig {
+            threads: self.threads,
+            duration: Duration::from_secs(self.duration),
+            algorithms,
+        }
+    }
+}
+
+impl StressArgs {
+    pub fn config(&self) -> StressConfig {
+        StressConfig {
+            threads: self.threads,
+            duration: Duration::from_secs(self.duration),
+            interval: Duration::from_secs(self.interval),
+        }
+    }
+}
+
+impl HashArgs {
+    pub fn config(&self) -> HashConfig {
+        HashConfig {
+            algorithm: self.algo.clone(),
+            blob: self.blob.clone(),
+            nonce: self.nonce.clone(),
+            seed_hash: self.seed_hash.clone(),
+            height: self.height,
+            target: self.target.clone(),
+        }
+    }
+}
+
+impl TuneArgs {
+    pub fn config(&self) -> TuneConfig {
+        let threads = if self.threads.is_empty() {
+            let cpu = bench::cpu_info();
+            tune::default_thread_counts(
+                cpu.physical_cores,
+                cgroup::available_cpus().min(cpu.logical_cores),
+            )
+        } else {
+            let mut threads = self.threads.clone();
+            threads.dedup();
+            threads
+        };
+        TuneConfig {
+            threads,
+            duration: Duration::from_secs(self.duration),
+        }
+    }
+}
+
+impl VerifyServerArgs {
+    pub fn config(&self) -> VerifyServerConfig {
+        VerifyServerConfig {
+            listen: self.listen.clone(),
+            threads: self.threads,
+            seeds: self.seeds as usize,
+        }
+    }
+}
+
+impl DatasetServerArgs {
+    pub fn config(&self) -> DatasetServerConfig {
+        DatasetServerConfig {
+            socket: self.socket.clone(),
+            dir: self.dir.clone(),
+            threads: self.threads,
+            seeds: self.seeds as usize,
+        }
+    }
+}
+
+impl ProxyArgs {
+    /// The proxy of the `[pool]` of the config
+    pub fn config(&self, config: &MithrilConfig) -> ProxyConfig {
+        ProxyConfig {
+            listen: self.listen.clone(),
+            pool: config.pool_conf.clone(),
+            stats_interval: Duration::from_secs(self.stats_interval),
+        }
+    }
+}
+
+impl CoordinatorArgs {
+    /// The coordinator of the `[pool]` of the config, the API uses the `[api]` token
+    pub fn config(&self, config: &MithrilConfig) -> CoordinatorConfig {
+        CoordinatorConfig {
+            proxy: ProxyConfig {
+                listen: self.listen.clone(),
+                pool: config.pool_conf.clone(),
+                stats_interval: Duration::from_secs(self.stats_interval),
+            },
+            api: self.api.clone(),
+            token: config.api_conf.token.clone(),
+            fleet_config: self.fleet_config.clone(),
+        }
+    }
+}
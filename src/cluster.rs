@@ -0,0 +1,286 @@
+use crate::api::{
+    constant_time_eq, error_response, read_request, write_response, Request, Response,
+};
+use crate::mithril_config;
+use crate::stratum::binary;
+use crate::stratum::proxy::{self, MinerStats, Proxy, ProxyConfig, FLEET_EXTENSION};
+use crate::stratum::stratum_data::{ClusterControl, PoolConfig};
+use serde_json::{json, Value};
+
+use std::fs;
+use std::io;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often an agent reports its hashrate
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(30);
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the coordinator looks for changes of the fleet config file
+const FLEET_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The `[cluster]` section of an agent
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ClusterConfig {
+    /// the connection to the coordinator, None if the miner connects to its pools itself
+    pub coordinator: Option<PoolConfig>,
+    /// keys (`section.key` or `section`) the fleet config does not change
+    pub local: Vec<String>,
+    /// the fleet config of the coordinator the config was read with, None for the file only
+    pub fleet_config: Option<String>,
+}
+
+/// The connection of an agent to the coordinator at `address`. The wallet and rig id
+/// of the pool name the agent, the coordinator mines with its own pool login.
+pub fn coordinator_pool(address: &str, pool: &PoolConfig) -> PoolConfig {
+    let mut coordinator = PoolConfig::default();
+    coordinator.pool_address = address.to_string();
+    coordinator.wallet_address = pool.wallet_address.clone();
+    coordinator.rig_id = pool.rig_id.clone();
+    //each agent mines the nonces of its slot
+    coordinator.nicehash = true;
+    coordinator.algorithm = pool.algorithm.clone();
+    coordinator.extensions = vec![binary::EXTENSION.to_string(), FLEET_EXTENSION.to_string()];
+    coordinator
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoordinatorConfig {
+    pub proxy: ProxyConfig,
+    /// address of the HTTP API that lists and controls the agents, empty for none
+    pub api: String,
+    /// clients of the API have to send `Authorization: Bearer <token>`, empty disables it
+    pub token: String,
+    /// the config sent to the agents, re-sent when the file changes
+    pub fleet_config: Option<PathBuf>,
+}
+
+/// The totals of the farm, from the shares the coordinator forwarded and the reports of
+/// the agents
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct Summary {
+    pub agents: usize,
+    /// the agents that sent a report
+    pub reporting: usize,
+    pub paused: usize,
+    /// sum of the reported hashrates
+    pub hashrate: f64,
+    /// sum of the hashrates estimated from the accepted shares
+    pub share_hashrate: f64,
+    pub threads: u64,
+    pub accepted: u64,
+    pub rejected: u64,
+    /// the temperature of the hottest agent, None if no agent reads its temperature
+    pub max_temperature: Option<f64>,
+    /// the name of the hottest agent
+    pub hottest: Option<String>,
+    pub stratum_errors: u64,
+    pub dataset_errors: u64,
+}
+
+impl Summary {
+    pub fn of(agents: &[MinerStats], now: Instant) -> Summary {
+        let mut summary = Summary {
+            agents: agents.len(),
+            ..Summary::default()
+        };
+        for agent in agents {
+            summary.share_hashrate += agent.hashrate(now);
+            summary.accepted += agent.accepted;
+            summary.rejected += agent.rejected;
+            let report = match &agent.report {
+                Some(report) => report,
+                None => continue,
+            };
+            summary.reporting += 1;
+            if report.paused {
+                summary.paused += 1;
+            }
+            summary.hashrate += report.hashrate;
+            summary.threads += report.threads;
+            summary.stratum_errors += report.stratum_errors;
+            summary.dataset_errors += report.dataset_errors;
+            if let Some(temperature) = report.temperature {
+                if summary.max_temperature.is_none_or(|max| temperature > max) {
+                    summary.max_temperature = Some(temperature);
+                    summary.hottest = Some(agent.name.clone());
+                }
+            }
+        }
+        summary
+    }
+}
+
+/// The body of `POST /control`
+#[derive(Deserialize)]
+struct ControlBody {
+    /// the agent, all agents if it is missing
+    #[serde(default)]
+    slot: Option<u8>,
+    #[serde(flatten)]
+    control: ClusterControl,
+}
+
+/// Holds the pool connection for the agents and serves the API until the process is
+/// stopped
+pub fn run(conf: &CoordinatorConfig) -> io::Result<()> {
+    let proxy = Arc::new(Proxy::new());
+    if let Some(path) = &conf.fleet_config {
+        //a broken file at the start is a mistake of the farm admin, later ones are kept out
+        let mut last_modified = modified(path);
+        proxy.set_fleet_config(&read_fleet_config(path)?);
+        let watch_proxy = proxy.clone();
+        let path = path.clone();
+        thread::Builder::new()
+            .name("fleet config thread".to_string())
+            .spawn(move || loop {
+                thread::sleep(FLEET_POLL_INTERVAL);
+                let current = modified(&path);
+                if current == last_modified {
+                    continue;
+                }
+                last_modified = current;
+                match read_fleet_config(&path) {
+                    Ok(config) => watch_proxy.set_fleet_config(&config),
+                    Err(err) => error!(
+                        "fleet config not sent, the agents keep the last one: {}",
+                        err
+                    ),
+                }
+            })?;
+    }
+    if !conf.api.is_empty() {
+        let listener = TcpListener::bind(&conf.api)?;
+        info!("coordinator api listening on {}", conf.api);
+        let api_proxy = proxy.clone();
+        let token = conf.token.clone();
+        thread::Builder::new()
+            .name("coordinator api thread".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let result = stream.and_then(|stream| serve(&api_proxy, &token, stream));
+                    if let Err(err) = result {
+                        warn!("coordinator api request failed: {}", err);
+                    }
+                }
+            })?;
+    }
+    let summary_proxy = proxy.clone();
+    let summary_interval = conf.proxy.stats_interval;
+    thread::Builder::new()
+        .name("coordinator summary thread".to_string())
+        .spawn(move || loop {
+            thread::sleep(summary_interval);
+            log_summary(&Summary::of(&summary_proxy.stats(), Instant::now()));
+        })?;
+    proxy::run_with(&conf.proxy, proxy)
+}
+
+fn log_summary(summary: &Summary) {
+    let temperature = match (&summary.hottest, summary.max_temperature) {
+        (Some(hottest), Some(temperature)) => {
+            format!(", hottest {} at {:.0}°C", hottest, temperature)
+        }
+        _ => String::new(),
+    };
+    info!(
+        "farm: {} agents ({} paused), {:.1} H/s reported on {} threads, {} accepted, {} rejected, {} stratum and {} dataset errors{}",
+        summary.agents,
+        summary.paused,
+        summary.hashrate,
+        summary.threads,
+        summary.accepted,
+        summary.rejected,
+        summary.stratum_errors,
+        summary.dataset_errors,
+        temperature
+    );
+}
+
+/// Reads the fleet config and checks that the agents can use it
+pub fn read_fleet_config(path: &Path) -> io::Result<String> {
+    let config = fs::read_to_string(path)?;
+    mithril_config::check_fleet_config(&config).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid fleet config {}: {}", path.display(), err),
+        )
+    })?;
+    Ok(config)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn serve(proxy: &Proxy, token: &str, stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let response = match read_request(&mut BufReader::new(&stream)) {
+        Ok(request) => handle(proxy, token, &request),
+        Err(err) => error_response(400, &err.to_string()),
+    };
+    write_response(&mut &stream, &response)
+}
+
+/// `GET /agents` lists the agents, `GET /summary` sums them up, `POST /control` sends a
+/// command to one or all of them
+pub fn handle(proxy: &Proxy, token: &str, request: &Request) -> Response {
+    let expected = format!("Bearer {}", token);
+    let authorized = request.authorization.as_ref().is_some_and(|auth| {
+        !token.is_empty() && constant_time_eq(auth.as_bytes(), expected.as_bytes())
+    });
+    if !authorized {
+        return error_response(401, "missing or invalid token");
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/agents") => Response {
+            status: 200,
+            body: agents_json(&proxy.stats(), Instant::now()),
+        },
+        ("GET", "/summary") => Response {
+            status: 200,
+            body: json!(Summary::of(&proxy.stats(), Instant::now())),
+        },
+        ("POST", "/control") => match serde_json::from_slice::<ControlBody>(&request.body) {
+            Ok(body) => match (proxy.control(body.slot, &body.control), body.slot) {
+                (0, Some(slot)) => error_response(404, &format!("no agent in slot {}", slot)),
+                (sent, _) => Response {
+                    status: 200,
+                    body: json!({ "agents": sent }),
+                },
+            },
+            Err(err) => error_response(400, &err.to_string()),
+        },
+        (_, "/agents") | (_, "/summary") | (_, "/control") => {
+            error_response(405, "method not allowed")
+        }
+        _ => error_response(404, "not found"),
+    }
+}
+
+fn agents_json(agents: &[MinerStats], now: Instant) -> Value {
+    agents
+        .iter()
+        .map(|agent| {
+            json!({
+                "slot": agent.slot,
+                "name": agent.name,
+                "address": agent.address,
+                "connected_secs": now.saturating_duration_since(agent.connected).as_secs(),
+                "accepted": agent.accepted,
+                "rejected": agent.rejected,
+                "share_hashrate": agent.hashrate(now),
+                "hashrate": agent.report.as_ref().map(|report| report.hashrate),
+                "threads": agent.report.as_ref().map(|report| report.threads),
+                "paused": agent.report.as_ref().is_some_and(|report| report.paused),
+                "temperature": agent.report.as_ref().and_then(|report| report.temperature),
+                "stratum_errors": agent.report.as_ref().map(|report| report.stratum_errors),
+                "dataset_errors": agent.report.as_ref().map(|report| report.dataset_errors),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,29 @@
+//! Shell completion scripts (`mithril completions <shell>`) and the man page (`mithril
+//! manpage`), generated at runtime from the command line definition so they always
+//! match the binary.
+
+extern crate clap;
+extern crate clap_complete;
+extern crate clap_mangen;
+
+use self::clap::CommandFactory;
+use crate::cli::Cli;
+
+pub use self::clap_complete::Shell;
+
+pub fn completion_script(shell: Shell) -> String {
+    let mut cmd = Cli::command();
+    let name = cmd.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut cmd, name, &mut script);
+    String::from_utf8_lossy(&script).into_owned()
+}
+
+/// The man page in roff
+pub fn manpage() -> String {
+    let mut page = Vec::new();
+    clap_mangen::Man::new(Cli::command())
+        .render(&mut page)
+        .expect("rendering into memory");
+    String::from_utf8_lossy(&page).into_owned()
+}
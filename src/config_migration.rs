@@ -0,0 +1,357 @@
+extern crate config;
+extern crate serde_json;
+
+use self::config::{Config, ConfigError, File};
+use crate::mithril_config::ConfigFormat;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Layout version of the config, stored as top level `config_version`
+pub const CONFIG_VERSION: u64 = 1;
+pub const VERSION_KEY: &str = "config_version";
+
+/// A change of the config layout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    RemoveSection {
+        section: &'static str,
+        reason: &'static str,
+    },
+    RenameSection {
+        from: &'static str,
+        to: &'static str,
+    },
+    RenameKey {
+        section: &'static str,
+        from: &'static str,
+        to: &'static str,
+    },
+}
+
+/// The changes of each version, `MIGRATIONS[0]` migrates version 0 (no
+/// `config_version`) to 1
+pub const MIGRATIONS: &[&[Change]] = &[&[Change::RemoveSection {
+    section: "hardware",
+    reason: "AES support is detected automatically",
+}]];
+
+#[derive(Debug, PartialEq)]
+pub struct Migration {
+    /// the version of the file before the migration
+    pub from: u64,
+    pub content: String,
+    /// what was changed, for the log
+    pub notes: Vec<String>,
+}
+
+/// The `config_version` of the content, 0 if it has none. None if the content cannot
+/// be parsed, reading the config reports that.
+pub fn version(content: &str, format: ConfigFormat) -> Result<Option<u64>, String> {
+    let config = match Config::builder()
+        .add_source(File::from_str(content, format.file_format()))
+        .build()
+    {
+        Ok(config) => config,
+        Err(_) => return Ok(None),
+    };
+    match config.get_int(VERSION_KEY) {
+        Ok(version) if version >= 0 => Ok(Some(version as u64)),
+        Err(ConfigError::NotFound(_)) => Ok(Some(0)),
+        _ => Err(format!(
+            "{} has to be a version number like {}",
+            VERSION_KEY, CONFIG_VERSION
+        )),
+    }
+}
+
+/// Migrates the content to `CONFIG_VERSION`, None if it is current already. Configs of
+/// newer mithril versions are an error.
+pub fn migrate(content: &str, format: ConfigFormat) -> Result<Option<Migration>, String> {
+    let from = match version(content, format)? {
+        Some(version) => version,
+        None => return Ok(None),
+    };
+    if from == CONFIG_VERSION {
+        return Ok(None);
+    }
+    if from > CONFIG_VERSION {
+        return Err(format!(
+            "{} is {}, this mithril only knows version {}, please update mithril",
+            VERSION_KEY, from, CONFIG_VERSION
+        ));
+    }
+
+    let mut content = content.to_string();
+    let mut notes = Vec::new();
+    for changes in &MIGRATIONS[from as usize..] {
+        for change in changes.iter() {
+            let (migrated, changed) = match format {
+                ConfigFormat::Toml => apply_toml(&content, change),
+                ConfigFormat::Json => apply_json(&content, change)?,
+            };
+            if changed {
+                content = migrated;
+                notes.push(describe(change));
+            }
+        }
+    }
+    content = match format {
+        ConfigFormat::Toml => set_toml_version(&content),
+        ConfigFormat::Json => set_json_version(&content)?,
+    };
+    Ok(Some(Migration {
+        from,
+        content,
+        notes,
+    }))
+}
+
+/// `config.toml.v0.bak` for version 0 of `config.toml`
+pub fn backup_path(path: &Path, version: u64) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".v{}.bak", version));
+    PathBuf::from(name)
+}
+
+/// Writes the migrated config to `path`, the original content is kept at
+/// `backup_path`. Returns the backup path.
+pub fn save(path: &Path, original: &str, migration: &Migration) -> Result<PathBuf, String> {
+    let backup = backup_path(path, migration.from);
+    fs::write(&backup, original)
+        .map_err(|err| format!("could not write backup {}: {}", backup.display(), err))?;
+    fs::write(path, &migration.content)
+        .map_err(|err| format!("could not write {}: {}", path.display(), err))?;
+    Ok(backup)
+}
+
+fn describe(change: &Change) -> String {
+    match change {
+        Change::RemoveSection { section, reason } => {
+            format!("removed the [{}] section, {}", section, reason)
+        }
+        Change::RenameSection { from, to } => format!("renamed [{}] to [{}]", from, to),
+        Change::RenameKey { section, from, to } => {
+            format!("renamed {}.{} to {}.{}", section, from, section, to)
+        }
+    }
+}
+
+/// Sets `section.key` to the value, for the settings mithril finds itself. A TOML file
+/// is changed line by line, so that its comments are kept, a missing key is added at the
+/// top of its section and a missing section at the end.
+pub fn set_key(
+    content: &str,
+    format: ConfigFormat,
+    section: &str,
+    key: &str,
+    value: &serde_json::Value,
+) -> Result<String, String> {
+    match format {
+        ConfigFormat::Toml => Ok(set_toml_key(content, section, key, value)),
+        ConfigFormat::Json => {
+            let mut map = parse_json(content)?;
+            let entry = map
+                .entry(section.to_string())
+                .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+            match entry.as_object_mut() {
+                Some(section) => section.insert(key.to_string(), value.clone()),
+                None => return Err(format!("{} of the config is not a section", section)),
+            };
+            Ok(to_json(map))
+        }
+    }
+}
+
+/// Sets the keys in the config file at `path`, see `set_key`
+pub fn write_keys(
+    path: &Path,
+    format: ConfigFormat,
+    values: &[(&str, &str, serde_json::Value)],
+) -> Result<(), String> {
+    let mut content = fs::read_to_string(path)
+        .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+    for (section, key, value) in values {
+        content = set_key(&content, format, section, key, value)?;
+    }
+    fs::write(path, content).map_err(|err| format!("could not write {}: {}", path.display(), err))
+}
+
+fn set_toml_key(content: &str, section: &str, key: &str, value: &serde_json::Value) -> String {
+    //a JSON string, number or bool is a TOML value as well
+    let key_line = format!("{} = {}", key, value);
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut header = None;
+    let mut replaced = false;
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            current = trimmed.trim_matches(['[', ']']).to_string();
+            if is_header(line, section) && header.is_none() {
+                header = Some(lines.len());
+            }
+        }
+        if !replaced && current == section && is_key(line, key) {
+            lines.push(match inline_comment(line) {
+                Some(comment) => format!("{} {}", key_line, comment),
+                None => key_line.clone(),
+            });
+            replaced = true;
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    if !replaced {
+        match header {
+            Some(header) => lines.insert(header + 1, key_line),
+            None => {
+                if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                lines.push(format!("[{}]", section));
+                lines.push(key_line);
+            }
+        }
+    }
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+/// The `# ...` after the value of a TOML line, a `#` in a string is not a comment
+fn inline_comment(line: &str) -> Option<&str> {
+    let mut quote = None;
+    for (ix, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('#', None) => return Some(&line[ix..]),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_header(line: &str, section: &str) -> bool {
+    let line = line.trim();
+    line == format!("[{}]", section) || line == format!("[[{}]]", section)
+}
+
+fn is_key(line: &str, key: &str) -> bool {
+    line.trim_start()
+        .strip_prefix(key)
+        .is_some_and(|rest| rest.trim_start().starts_with('='))
+}
+
+/// Applies the change line by line, so that comments are kept. Returns whether
+/// something changed.
+fn apply_toml(content: &str, change: &Change) -> (String, bool) {
+    let mut lines = Vec::new();
+    let mut changed = false;
+    let mut section = String::new();
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            section = trimmed.trim_matches(['[', ']']).to_string();
+        }
+        match change {
+            Change::RemoveSection { section: name, .. } if section == *name => {
+                changed = true;
+                continue;
+            }
+            Change::RenameSection { from, to } if is_header(line, from) => {
+                changed = true;
+                lines.push(line.replacen(from, to, 1));
+                continue;
+            }
+            Change::RenameKey {
+                section: name,
+                from,
+                to,
+            } if section == *name && is_key(line, from) => {
+                changed = true;
+                lines.push(line.replacen(from, to, 1));
+                continue;
+            }
+            _ => {}
+        }
+        lines.push(line.to_string());
+    }
+    (join_lines(content, lines), changed)
+}
+
+fn set_toml_version(content: &str) -> String {
+    let version_line = format!("{} = {}", VERSION_KEY, CONFIG_VERSION);
+    let mut lines: Vec<String> = Vec::new();
+    let mut replaced = false;
+    let mut top_level = true;
+    for line in content.lines() {
+        if line.trim().starts_with('[') {
+            top_level = false;
+        }
+        if top_level && is_key(line, VERSION_KEY) {
+            lines.push(version_line.clone());
+            replaced = true;
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    if !replaced {
+        lines.insert(0, version_line);
+    }
+    join_lines(content, lines)
+}
+
+fn join_lines(original: &str, lines: Vec<String>) -> String {
+    let mut result = lines.join("\n");
+    if original.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+fn parse_json(content: &str) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    match serde_json::from_str(content) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        Ok(_) => Err("the JSON config has to be an object".to_string()),
+        Err(err) => Err(format!("could not parse the JSON config: {}", err)),
+    }
+}
+
+fn to_json(map: serde_json::Map<String, serde_json::Value>) -> String {
+    serde_json::to_string_pretty(&serde_json::Value::Object(map)).expect("config json") + "\n"
+}
+
+fn apply_json(content: &str, change: &Change) -> Result<(String, bool), String> {
+    let mut map = parse_json(content)?;
+    let changed = match change {
+        Change::RemoveSection { section, .. } => map.remove(*section).is_some(),
+        Change::RenameSection { from, to } => match map.remove(*from) {
+            Some(value) => {
+                map.insert(to.to_string(), value);
+                true
+            }
+            None => false,
+        },
+        Change::RenameKey { section, from, to } => {
+            match map.get_mut(*section).and_then(|s| s.as_object_mut()) {
+                Some(section) => match section.remove(*from) {
+                    Some(value) => {
+                        section.insert(to.to_string(), value);
+                        true
+                    }
+                    None => false,
+                },
+                None => false,
+            }
+        }
+    };
+    Ok((to_json(map), changed))
+}
+
+fn set_json_version(content: &str) -> Result<String, String> {
+    let mut map = parse_json(content)?;
+    map.insert(VERSION_KEY.to_string(), CONFIG_VERSION.into());
+    Ok(to_json(map))
+}
@@ -0,0 +1,705 @@
+extern crate config;
+
+use self::config::{Config, File, Value};
+use crate::cgroup;
+use crate::config_migration;
+use crate::mithril_config::{ConfigFormat, MithrilConfig};
+use crate::pow;
+#[cfg(unix)]
+use crate::privileges;
+use crate::privileges::PrivilegeConfig;
+use crate::profile;
+use crate::stratum::stratum_data::PoolConfig;
+use crate::wallet;
+use crate::wallet::Network;
+
+use std::collections::HashMap;
+use std::fmt;
+use strum::Display;
+
+const POOL_KEYS: &[&str] = &[
+    "pool_address",
+    "wallet_address",
+    "pool_password",
+    "rig_id",
+    "keepalive",
+    "nicehash",
+    "tls",
+    "proxy",
+    "algorithm",
+    "daemon",
+    "zmq",
+    "template_refresh_seconds",
+    "reserve_size",
+    "mempool_refresh",
+    "template_max_age_seconds",
+];
+
+/// All sections and keys that are read from the config file
+pub const KNOWN_KEYS: &[(&str, &[&str])] = &[
+    ("pool", POOL_KEYS),
+    ("backup_pool", POOL_KEYS),
+    (
+        "dual_pool",
+        &[
+            "pool_address",
+            "wallet_address",
+            "pool_password",
+            "rig_id",
+            "keepalive",
+            "nicehash",
+            "tls",
+            "proxy",
+            "algorithm",
+            "num_threads",
+        ],
+    ),
+    (
+        "coin",
+        &[
+            "name",
+            "hashrate",
+            "pool_address",
+            "wallet_address",
+            "pool_password",
+            "rig_id",
+            "keepalive",
+            "nicehash",
+            "tls",
+            "proxy",
+            "algorithm",
+            "daemon",
+            "zmq",
+            "template_refresh_seconds",
+            "reserve_size",
+            "mempool_refresh",
+            "template_max_age_seconds",
+        ],
+    ),
+    ("payout", &["wallet_address", "percentage"]),
+    ("memory", &["mode", "numa", "allocator", "dataset_server"]),
+    (
+        "dataset_init",
+        &[
+            "background",
+            "threads",
+            "priority",
+            "mb_per_second",
+            "stall_seconds",
+            "stall_retries",
+        ],
+    ),
+    (
+        "dataset_check",
+        &["enabled", "interval_minutes", "samples", "rebuild"],
+    ),
+    (
+        "earnings",
+        &[
+            "enabled",
+            "url",
+            "daemon",
+            "interval_minutes",
+            "pool_fee_percent",
+        ],
+    ),
+    (
+        "profit_switch",
+        &["enabled", "url", "interval_minutes", "hysteresis_percent"],
+    ),
+    (
+        "randomx",
+        &[
+            "name",
+            "base",
+            "argon_salt",
+            "argon_lanes",
+            "argon_memory_kib",
+            "argon_iterations",
+            "cache_accesses",
+            "dataset_base_bytes",
+            "dataset_extra_bytes",
+            "program_size",
+            "program_count",
+            "program_iterations",
+            "scratchpad_l2_bytes",
+            "scratchpad_l3_bytes",
+            "freq_iror_r",
+            "freq_irol_r",
+        ],
+    ),
+    (
+        "worker",
+        &[
+            "num_threads",
+            "auto_tune",
+            "auto_tune_interval_minutes",
+            "auto_tune_log",
+            "profile",
+            "affinity",
+            "throttle_percent",
+        ],
+    ),
+    (
+        "profile",
+        &["name", "threads_percent", "priority", "throttle_percent"],
+    ),
+    (
+        "metric",
+        &[
+            "enabled",
+            "resolution",
+            "sample_interval_seconds",
+            "report_file",
+        ],
+    ),
+    (
+        "metric_push",
+        &[
+            "enabled",
+            "format",
+            "address",
+            "interval_seconds",
+            "prefix",
+            "host",
+        ],
+    ),
+    (
+        "otlp",
+        &[
+            "enabled",
+            "endpoint",
+            "interval_seconds",
+            "service_name",
+            "host",
+            "traces",
+        ],
+    ),
+    (
+        "webhook",
+        &[
+            "enabled",
+            "url",
+            "events",
+            "template",
+            "rig_name",
+            "zero_hashrate_minutes",
+            "min_hashrate",
+            "max_reject_percent",
+            "window_minutes",
+            "block_difficulty",
+            "block_command",
+        ],
+    ),
+    (
+        "log",
+        &[
+            "json_file",
+            "json_level",
+            "console_level",
+            "max_size_mb",
+            "rotate_hours",
+            "keep_files",
+            "levels",
+        ],
+    ),
+    ("console", &["timestamps", "color", "hashrate", "quiet"]),
+    (
+        "api",
+        &[
+            "enabled",
+            "address",
+            "token",
+            "healthz_hash_stall_seconds",
+            "healthz_disconnect_seconds",
+            "history_hours",
+            "history_resolution_seconds",
+        ],
+    ),
+    ("cluster", &["coordinator", "local"]),
+    ("share_db", &["enabled", "path", "retention_days"]),
+    (
+        "forensics",
+        &["file", "max_size_mb", "keep_files", "verify"],
+    ),
+    ("privileges", &["user", "group"]),
+    ("update_check", &["enabled", "url", "interval_hours"]),
+    ("donation", &["percentage"]),
+];
+
+/// keys outside of the sections
+const ROOT_KEYS: &[&str] = &[config_migration::VERSION_KEY];
+
+/// sections that are an array of tables (`[[backup_pool]]`)
+const ARRAY_SECTIONS: &[&str] = &["backup_pool", "coin", "profile", "randomx", "payout"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Severity {
+    /// mithril does not start with this config
+    Error,
+    Warning,
+}
+
+/// The config file content the issues refer to
+pub struct ConfigSource<'a> {
+    /// shown in the messages, usually the path
+    pub name: &'a str,
+    pub content: &'a str,
+    pub format: ConfigFormat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: String,
+    /// 1-based
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    /// `section.key` or just the section
+    pub key: String,
+    pub message: String,
+    /// None if the key is not in the file, e.g. it was given on the command line
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} {}", self.severity, self.key, self.message)?;
+        if let Some(location) = &self.location {
+            write!(
+                f,
+                "\n  --> {}:{}: {}",
+                location.file,
+                location.line,
+                location.text.trim()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn has_errors(issues: &[ConfigIssue]) -> bool {
+    issues.iter().any(|i| i.severity == Severity::Error)
+}
+
+/// Unknown sections and keys of the file, checked before the values are read so that
+/// typos of required keys are reported
+pub fn check_keys(source: &ConfigSource) -> Vec<ConfigIssue> {
+    unknown_keys(source)
+        .into_iter()
+        .map(|(key, message)| ConfigIssue {
+            severity: Severity::Error,
+            location: locate(source, &key),
+            key,
+            message,
+        })
+        .collect()
+}
+
+/// Checks for values that cannot work. `overridden` are the keys given on the command
+/// line, they are not looked up in the file.
+pub fn validate(
+    config: &MithrilConfig,
+    source: &ConfigSource,
+    overridden: &[&str],
+) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    let mut issue = |severity: Severity, key: &str, message: String| {
+        let location = if overridden.contains(&key) {
+            None
+        } else {
+            locate(source, key)
+        };
+        issues.push(ConfigIssue {
+            severity,
+            key: key.to_string(),
+            message,
+            location,
+        });
+    };
+
+    let mut algorithms = pow::ALGORITHMS.to_vec();
+    algorithms.extend(config.randomx_variants.iter().map(|v| v.name.as_str()));
+    for (i, pool) in config.pools().into_iter().enumerate() {
+        let section = match i {
+            0 => "pool".to_string(),
+            i => format!("backup_pool[{}]", i - 1),
+        };
+        for (severity, key, problem) in pool_problems(pool, &algorithms) {
+            issue(severity, &format!("{}.{}", section, key), problem);
+        }
+    }
+    if let Some(dual) = &config.dual_pool {
+        for (severity, key, problem) in pool_problems(&dual.pool_conf, &algorithms) {
+            issue(severity, &format!("dual_pool.{}", key), problem);
+        }
+    }
+    for (i, wallet) in config.payout_wallets.iter().enumerate() {
+        if let Some(problem) = wallet_address_problem(&wallet.wallet_address) {
+            issue(
+                Severity::Error,
+                &format!("payout[{}].wallet_address", i),
+                problem,
+            );
+        }
+    }
+    for (i, coin) in config.coins.iter().enumerate() {
+        for (severity, key, problem) in pool_problems(&coin.pool_conf, &algorithms) {
+            issue(severity, &format!("coin[{}].{}", i, key), problem);
+        }
+        if coin.hashrate < 0.0 {
+            issue(
+                Severity::Error,
+                &format!("coin[{}].hashrate", i),
+                format!(
+                    "is {}, has to be the H/s of this miner or 0 for the benchmarked one",
+                    coin.hashrate
+                ),
+            );
+        }
+    }
+    if config.profit_conf.enabled && config.coins.len() < 2 {
+        issue(
+            Severity::Warning,
+            "profit_switch.enabled",
+            format!(
+                "is true with {} [[coin]] entries, there is nothing to switch between",
+                config.coins.len()
+            ),
+        );
+    }
+
+    let profile = &config.worker_conf.profile;
+    if !profile.is_empty() && config.profile(profile).is_none() {
+        let names: Vec<String> = profile::all_profiles(&config.profiles)
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        issue(
+            Severity::Error,
+            "worker.profile",
+            format!("is \"{}\", has to be one of {}", profile, names.join(", ")),
+        );
+    }
+
+    let cores = cgroup::available_cpus() as u64;
+    if !config.worker_conf.auto_tune && config.worker_conf.num_threads > cores {
+        issue(
+            Severity::Warning,
+            "worker.num_threads",
+            format!(
+                "is {}, more threads than the {} usable logical cores slow hashing down",
+                config.worker_conf.num_threads, cores
+            ),
+        );
+    }
+    if let Some(dual) = &config.dual_pool {
+        if !config.worker_conf.auto_tune
+            && config.worker_conf.num_threads + dual.num_threads > cores
+        {
+            issue(
+                Severity::Warning,
+                "dual_pool.num_threads",
+                format!(
+                    "is {}, with the {} threads of worker.num_threads more than the {} usable logical cores",
+                    dual.num_threads, config.worker_conf.num_threads, cores
+                ),
+            );
+        }
+    }
+
+    let percentage = config.donation_conf.percentage;
+    if !(0.0..=100.0).contains(&percentage) {
+        issue(
+            Severity::Error,
+            "donation.percentage",
+            format!("has to be between 0 and 100, was {}", percentage),
+        );
+    }
+
+    if config.api_conf.enabled && config.api_conf.token.is_empty() {
+        issue(
+            Severity::Warning,
+            "api.token",
+            "is empty, the API only serves /healthz".to_string(),
+        );
+    }
+    if config.share_db_conf.enabled && !cfg!(feature = "sqlite") {
+        issue(
+            Severity::Warning,
+            "share_db.enabled",
+            "has no effect, mithril was built without the sqlite feature".to_string(),
+        );
+    }
+
+    for (key, problem) in privilege_problems(&config.privilege_conf) {
+        issue(Severity::Error, key, problem);
+    }
+
+    issues
+}
+
+#[cfg(unix)]
+fn privilege_problems(conf: &PrivilegeConfig) -> Vec<(&'static str, String)> {
+    let mut problems = Vec::new();
+    if conf.user.is_empty() {
+        if !conf.group.is_empty() {
+            problems.push(("privileges.group", "needs privileges.user".to_string()));
+        }
+        return problems;
+    }
+    if let Err(err) = privileges::lookup_user(&conf.user) {
+        problems.push(("privileges.user", err.to_string()));
+    }
+    if !conf.group.is_empty() {
+        if let Err(err) = privileges::lookup_group(&conf.group) {
+            problems.push(("privileges.group", err.to_string()));
+        }
+    }
+    problems
+}
+
+#[cfg(not(unix))]
+fn privilege_problems(conf: &PrivilegeConfig) -> Vec<(&'static str, String)> {
+    if conf.user.is_empty() {
+        Vec::new()
+    } else {
+        vec![("privileges.user", "is only supported on unix".to_string())]
+    }
+}
+
+/// `algorithms` are the built in ones and the `[[randomx]]` entries. The wallet is only
+/// checked as a Monero address for the `MONERO_ALGORITHMS`.
+fn pool_problems(pool: &PoolConfig, algorithms: &[&str]) -> Vec<(Severity, &'static str, String)> {
+    let mut problems = Vec::new();
+    if let Some(problem) = pool_address_problem(&pool.pool_address) {
+        problems.push((Severity::Error, "pool_address", problem));
+    }
+    if pow::MONERO_ALGORITHMS.contains(&pool.algorithm.as_str()) {
+        match wallet::decode(wallet::address_part(&pool.wallet_address)) {
+            Err(err) => problems.push((Severity::Error, "wallet_address", err.to_string())),
+            Ok(address) if address.network != Network::Mainnet => problems.push((
+                Severity::Warning,
+                "wallet_address",
+                format!(
+                    "is a {} address, pools on the main network cannot pay to it",
+                    address.network
+                ),
+            )),
+            Ok(_) => {}
+        }
+    } else if pool.wallet_address.trim().is_empty() {
+        //other coins have their own address formats, only their pools can check them
+        problems.push((
+            Severity::Error,
+            "wallet_address",
+            "is empty, set your wallet address".to_string(),
+        ));
+    }
+    if !pool.proxy.is_empty() {
+        if let Some(problem) = pool_address_problem(&pool.proxy) {
+            problems.push((Severity::Error, "proxy", problem));
+        }
+    }
+    if !algorithms.contains(&pool.algorithm.as_str()) {
+        problems.push((
+            Severity::Error,
+            "algorithm",
+            format!(
+                "has to be one of {}, was \"{}\"",
+                algorithms.join(", "),
+                pool.algorithm
+            ),
+        ));
+    } else if pow::UNVERIFIED_ALGORITHMS.contains(&pool.algorithm.as_str()) {
+        problems.push((
+            Severity::Warning,
+            "algorithm",
+            format!(
+                "{} is not verified against a reference miner yet, the pool may reject the shares",
+                pool.algorithm
+            ),
+        ));
+    }
+    problems
+}
+
+/// None if the address has the form host:port
+pub fn pool_address_problem(address: &str) -> Option<String> {
+    if address.contains("://") {
+        return Some(format!(
+            "has to be host:port without a scheme like stratum+tcp://, was \"{}\"",
+            address
+        ));
+    }
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p > 0) => None,
+        _ => Some(format!("has to be host:port, was \"{}\"", address)),
+    }
+}
+
+/// None if the wallet is a valid Monero address. Pools accept a worker name or fixed
+/// difficulty after the address (`address.worker`, `address+difficulty`), that part is
+/// ignored.
+pub fn wallet_address_problem(wallet: &str) -> Option<String> {
+    wallet::decode(wallet::address_part(wallet))
+        .err()
+        .map(|err| err.to_string())
+}
+
+/// Keys and sections that are not in `KNOWN_KEYS`, with a suggestion for typos
+fn unknown_keys(source: &ConfigSource) -> Vec<(String, String)> {
+    let table: HashMap<String, Value> = match Config::builder()
+        .add_source(File::from_str(source.content, source.format.file_format()))
+        .build()
+        .and_then(|c| c.try_deserialize())
+    {
+        Ok(table) => table,
+        Err(_) => return Vec::new(), //reading the config reports this
+    };
+
+    let mut unknown = Vec::new();
+    let mut sections: Vec<&String> = table.keys().collect();
+    sections.sort();
+    for section in sections
+        .into_iter()
+        .filter(|s| !ROOT_KEYS.contains(&s.as_str()))
+    {
+        let known = match KNOWN_KEYS.iter().find(|(name, _)| name == section) {
+            Some((_, keys)) => *keys,
+            None => {
+                let names: Vec<&str> = KNOWN_KEYS.iter().map(|(name, _)| *name).collect();
+                unknown.push((section.clone(), unknown_message("section", section, &names)));
+                continue;
+            }
+        };
+        let entries = if ARRAY_SECTIONS.contains(&section.as_str()) {
+            match table[section].clone().into_array() {
+                Ok(entries) => entries
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, entry)| (format!("{}[{}]", section, i), entry))
+                    .collect(),
+                Err(_) => {
+                    unknown.push((section.clone(), "has to be a list of sections".to_string()));
+                    continue;
+                }
+            }
+        } else {
+            vec![(section.clone(), table[section].clone())]
+        };
+        for (name, entry) in entries {
+            let mut keys: Vec<String> = match entry.into_table() {
+                Ok(values) => values.into_keys().collect(),
+                Err(_) => {
+                    unknown.push((name, "has to be a section".to_string()));
+                    continue;
+                }
+            };
+            keys.sort();
+            for key in keys.iter().filter(|k| !known.contains(&k.as_str())) {
+                unknown.push((
+                    format!("{}.{}", name, key),
+                    unknown_message("key", key, known),
+                ));
+            }
+        }
+    }
+    unknown
+}
+
+/// Suggests the closest known name if at most a third of the characters differ
+fn unknown_message(kind: &str, name: &str, known: &[&str]) -> String {
+    let closest = known
+        .iter()
+        .map(|k| (k, edit_distance(name, k)))
+        .min_by_key(|(_, distance)| *distance)
+        .filter(|(_, distance)| *distance <= (name.len() / 3).max(1))
+        .map(|(k, _)| k);
+    match closest {
+        Some(suggestion) => format!("is an unknown {}, did you mean {}?", kind, suggestion),
+        None => format!("is an unknown {}", kind),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            current.push(substitution.min(prev[j + 1] + 1).min(current[j] + 1));
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+/// The line of `section.key`, or of the section header for a bare section. Entries of
+/// array sections are given as `section[index]`.
+fn locate(source: &ConfigSource, key: &str) -> Option<Location> {
+    let (section, key) = match key.split_once('.') {
+        Some((section, key)) => (section, Some(key)),
+        None => (key, None),
+    };
+    let (section, index) = match section.strip_suffix(']').and_then(|s| s.split_once('[')) {
+        Some((section, index)) => (section, index.parse::<usize>().ok()?),
+        None => (section, 0),
+    };
+    let lines: Vec<&str> = source.content.lines().collect();
+    let is_section = |line: &str| match source.format {
+        ConfigFormat::Toml => {
+            line.trim() == format!("[{}]", section) || line.trim() == format!("[[{}]]", section)
+        }
+        ConfigFormat::Json => line.contains(&format!("\"{}\"", section)),
+    };
+    let is_key = |line: &str, key: &str| match source.format {
+        ConfigFormat::Toml => line
+            .trim_start()
+            .strip_prefix(key)
+            .is_some_and(|rest| rest.trim_start().starts_with('=')),
+        ConfigFormat::Json => line.contains(&format!("\"{}\"", key)),
+    };
+
+    // the TOML entries have their own headers, in JSON the nth occurrence of the key
+    // after the section is used
+    let (header, key_index) = match source.format {
+        ConfigFormat::Toml => (index, 0),
+        ConfigFormat::Json => (0, index),
+    };
+    let section_ix = (0..lines.len())
+        .filter(|ix| is_section(lines[*ix]))
+        .nth(header)?;
+    let ix = match key {
+        None => section_ix,
+        // TOML sections end at the next header, a JSON section and its keys can be on
+        // the same line
+        Some(key) => (section_ix..lines.len())
+            .take_while(|ix| {
+                *ix == section_ix
+                    || source.format == ConfigFormat::Json
+                    || !lines[*ix].trim_start().starts_with('[')
+            })
+            .filter(|ix| {
+                let line = match source.format {
+                    ConfigFormat::Json if *ix == section_ix => lines[*ix]
+                        .split_once(&format!("\"{}\"", section))
+                        .map_or("", |(_, rest)| rest),
+                    _ => lines[*ix],
+                };
+                is_key(line, key)
+            })
+            .nth(key_index)?,
+    };
+    Some(Location {
+        file: source.name.to_string(),
+        line: ix + 1,
+        text: lines[ix].to_string(),
+    })
+}
@@ -0,0 +1,372 @@
+extern crate crossbeam_channel;
+#[cfg(unix)]
+extern crate libc;
+
+use self::crossbeam_channel::Sender;
+use crate::control::ControlCmd;
+use crate::earnings::format_estimate;
+use crate::metric::history;
+use crate::metric::history::{HashrateHistory, ThreadHistory};
+use crate::metric::stats::{MinerStats, DATASET_WINDOW};
+
+use std::io;
+use std::io::{IsTerminal, Read};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use strum::EnumString;
+
+/// How the log lines and reports are printed on the console
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleConfig {
+    pub timestamps: TimestampFormat,
+    /// colored levels on a terminal, off with `--no-color` or `NO_COLOR`
+    pub color: bool,
+    /// the hashrate report of the `h` key
+    pub hashrate: HashrateFormat,
+    /// only the shares and errors, also `--quiet`
+    pub quiet: bool,
+}
+
+impl Default for ConsoleConfig {
+    fn default() -> Self {
+        ConsoleConfig {
+            timestamps: TimestampFormat::Seconds,
+            color: true,
+            hashrate: HashrateFormat::Verbose,
+            quiet: false,
+        }
+    }
+}
+
+/// The timestamp of a log line, RFC 3339 in UTC
+#[derive(Debug, Clone, Copy, PartialEq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum TimestampFormat {
+    /// none, e.g. for journald which adds its own
+    Off,
+    Seconds,
+    Millis,
+    Micros,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum HashrateFormat {
+    /// `hashrate 1205.3/1198.0/n/a H/s` for 10s, 60s and 15m
+    Compact,
+    /// `hashrate 10s 1205.3 H/s, 60s 1198.0 H/s, 15m n/a`
+    Verbose,
+}
+
+/// Windows of the hashrate report
+pub const HASHRATE_WINDOWS: [(&str, Duration); 3] = [
+    ("10s", Duration::from_secs(10)),
+    ("60s", Duration::from_secs(60)),
+    ("15m", Duration::from_secs(15 * 60)),
+];
+
+/// Commands that are entered as single keys on the console
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleCmd {
+    /// `h`, print the hashrate over the last 10s, 60s and 15m
+    HashrateReport,
+    /// `s`, print the share and connection statistics
+    ShareStats,
+    /// `p`, stop or restart hashing
+    TogglePause,
+    /// `r`, reconnect to the pool
+    Reconnect,
+    /// `t`, draw a new auto tuning arm now
+    Retune,
+    /// `c`, switch to the next profile
+    NextProfile,
+}
+
+pub fn parse_key(key: u8) -> Option<ConsoleCmd> {
+    match key.to_ascii_lowercase() {
+        b'h' => Some(ConsoleCmd::HashrateReport),
+        b's' => Some(ConsoleCmd::ShareStats),
+        b'p' => Some(ConsoleCmd::TogglePause),
+        b'r' => Some(ConsoleCmd::Reconnect),
+        b't' => Some(ConsoleCmd::Retune),
+        b'c' => Some(ConsoleCmd::NextProfile),
+        _ => None,
+    }
+}
+
+/// Starts reading hotkeys from stdin if it is a terminal. The reports (`h`, `s`) are
+/// printed directly, all other commands are sent to the main loop. `Retune` is only
+/// sent if auto tuning is enabled.
+pub fn start(
+    stats: Arc<MinerStats>,
+    auto_tune: bool,
+    hashrate: HashrateFormat,
+    sndr: Sender<ControlCmd>,
+) {
+    if !io::stdin().is_terminal() {
+        return;
+    }
+
+    enable_raw_mode();
+    println!(
+        "hotkeys: h hashrate, s shares, p pause/resume, r reconnect, t re-tune, c next profile"
+    );
+
+    let history = history::start_sampling(
+        stats.clone(),
+        HASHRATE_WINDOWS[HASHRATE_WINDOWS.len() - 1].1,
+    );
+    thread::Builder::new()
+        .name("console thread".to_string())
+        .spawn(move || read_keys(&sndr, &stats, &history, auto_tune, hashrate))
+        .expect("console thread handle");
+}
+
+/// The command for the main loop, None for the reports
+pub fn control_cmd(cmd: ConsoleCmd) -> Option<ControlCmd> {
+    match cmd {
+        ConsoleCmd::TogglePause => Some(ControlCmd::TogglePause),
+        ConsoleCmd::Reconnect => Some(ControlCmd::Reconnect),
+        ConsoleCmd::Retune => Some(ControlCmd::Retune),
+        ConsoleCmd::NextProfile => Some(ControlCmd::NextProfile),
+        ConsoleCmd::HashrateReport | ConsoleCmd::ShareStats => None,
+    }
+}
+
+fn read_keys(
+    sndr: &Sender<ControlCmd>,
+    stats: &MinerStats,
+    history: &Mutex<HashrateHistory>,
+    auto_tune: bool,
+    hashrate: HashrateFormat,
+) {
+    for byte in io::stdin().lock().bytes() {
+        let key = match byte {
+            Ok(key) => key,
+            Err(err) => {
+                error!("reading console input failed, hotkeys disabled: {}", err);
+                return;
+            }
+        };
+        let cmd = match parse_key(key) {
+            Some(cmd) => cmd,
+            None => continue,
+        };
+        match cmd {
+            ConsoleCmd::HashrateReport => {
+                if let Ok(history) = history.lock() {
+                    println!("{}", hashrate_report(&history, hashrate));
+                }
+            }
+            ConsoleCmd::ShareStats => {
+                let hashrate = history.lock().ok().and_then(|h| steady_hashrate(&h));
+                println!("{}", stats_report(stats, hashrate));
+            }
+            ConsoleCmd::Retune if !auto_tune => {
+                println!("auto_tune is disabled");
+                continue;
+            }
+            ConsoleCmd::TogglePause if stats.paused() => println!("resuming"),
+            ConsoleCmd::TogglePause => println!("paused, press p to resume"),
+            ConsoleCmd::Reconnect => println!("reconnecting"),
+            ConsoleCmd::Retune => println!("drawing new auto tuning arm"),
+            ConsoleCmd::NextProfile => println!("switching to the next profile"),
+        }
+        if let Some(control) = control_cmd(cmd) {
+            if sndr.send(control).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+pub fn hashrate_report(history: &HashrateHistory, format: HashrateFormat) -> String {
+    match format {
+        HashrateFormat::Compact => {
+            let rates: Vec<String> = HASHRATE_WINDOWS
+                .iter()
+                .map(|(_, window)| match history.hashrate(*window) {
+                    Some(h) => format!("{:.1}", h),
+                    None => "n/a".to_string(),
+                })
+                .collect();
+            format!("hashrate {} H/s", rates.join("/"))
+        }
+        HashrateFormat::Verbose => {
+            let rates: Vec<String> = HASHRATE_WINDOWS
+                .iter()
+                .map(|(name, window)| {
+                    format!("{} {}", name, format_hashrate(history.hashrate(*window)))
+                })
+                .collect();
+            format!("hashrate {}", rates.join(", "))
+        }
+    }
+}
+
+/// The hashrate of the longest window with a value, for the earnings estimate
+pub fn steady_hashrate(history: &HashrateHistory) -> Option<f64> {
+    HASHRATE_WINDOWS
+        .iter()
+        .rev()
+        .find_map(|(_, window)| history.hashrate(*window))
+}
+
+/// `hashrate` is the one the earnings are estimated for, no estimate without
+pub fn stats_report(stats: &MinerStats, hashrate: Option<f64>) -> String {
+    let snapshot = stats.snapshot();
+    let connection = match stats.connection() {
+        Some((pool, connected_for)) => format!(
+            "connected to {} for {}",
+            pool,
+            format_duration(connected_for)
+        ),
+        None => "not connected".to_string(),
+    };
+    let reconnects = stats.connects().saturating_sub(1);
+
+    let shares = snapshot.shares_accepted + snapshot.shares_rejected;
+    let rejected_percent = if shares == 0 {
+        0.0
+    } else {
+        snapshot.shares_rejected as f64 * 100.0 / shares as f64
+    };
+    let mut report = format!(
+        "{}, {} reconnects\nshares {} accepted, {} rejected ({:.1}%), difficulty {}, effort {}, average effort {}\nlatency p50 {}, p99 {}",
+        connection,
+        reconnects,
+        snapshot.shares_accepted,
+        snapshot.shares_rejected,
+        rejected_percent,
+        snapshot.difficulty,
+        format_percentage(snapshot.current_effort()),
+        format_percentage(snapshot.average_effort()),
+        format_latency(snapshot.share_latency_p50),
+        format_latency(snapshot.share_latency_p99)
+    );
+    if let Some(hit_rate) = snapshot.dataset_hit_rate {
+        report.push_str(&format!(
+            "\ndataset hit rate {:.1}% over the last {}s",
+            hit_rate * 100.0,
+            DATASET_WINDOW.as_secs()
+        ));
+    }
+    let estimate = hashrate.map(|hashrate| stats.earnings(hashrate));
+    if let Some(estimate) =
+        estimate.filter(|e| e.shares_per_day.is_some() || e.xmr_per_day.is_some())
+    {
+        report.push_str(&format!("\n{}", format_estimate(&estimate)));
+    }
+    if let Some(lifetime) = stats.lifetime() {
+        report.push_str(&format!(
+            "\nlifetime {} hashes, shares {} accepted, {} rejected, best share {}, uptime {}",
+            lifetime.hashes,
+            lifetime.shares_accepted,
+            lifetime.shares_rejected,
+            lifetime.best_share_difficulty,
+            format_duration(Duration::from_secs(lifetime.uptime_seconds))
+        ));
+    }
+    report
+}
+
+/// Everything known about the miner, for headless rigs
+pub fn stats_dump(
+    stats: &MinerStats,
+    history: &HashrateHistory,
+    threads: &ThreadHistory,
+) -> String {
+    let mut dump = vec![
+        "statistics dump".to_string(),
+        hashrate_report(history, HashrateFormat::Verbose),
+    ];
+    for (ix, (hashes, rate)) in threads.hashrates().iter().enumerate() {
+        dump.push(format!(
+            "thread {} {}, {} hashes",
+            ix,
+            format_hashrate(*rate),
+            hashes
+        ));
+    }
+    dump.push(stats_report(stats, steady_hashrate(history)));
+    dump.push(match stats.memory() {
+        Some(memory) => format!(
+            "memory {}, dataset {:.1}% cached",
+            memory.mode(),
+            memory.fill() * 100.0
+        ),
+        None => "memory not allocated yet".to_string(),
+    });
+    dump.join("\n")
+}
+
+pub fn format_hashrate(hashrate: Option<f64>) -> String {
+    match hashrate {
+        Some(h) => format!("{:.1} H/s", h),
+        None => "n/a".to_string(),
+    }
+}
+
+pub fn format_latency(latency: Option<Duration>) -> String {
+    match latency {
+        Some(l) => format!("{}ms", l.as_millis()),
+        None => "n/a".to_string(),
+    }
+}
+
+pub fn format_percentage(v: Option<f64>) -> String {
+    match v {
+        Some(v) => format!("{:.1}%", v * 100.0),
+        None => "n/a".to_string(),
+    }
+}
+
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!(
+        "{}h {:02}m {:02}s",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(unix)]
+static ORIGINAL_TERMIOS: std::sync::OnceLock<libc::termios> = std::sync::OnceLock::new();
+
+/// Switches the terminal to unbuffered input without echo, so single key presses can
+/// be read. Ctrl-C still works, `restore_terminal` has to be called before exiting.
+#[cfg(unix)]
+fn enable_raw_mode() {
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+            return;
+        }
+        if ORIGINAL_TERMIOS.set(termios).is_err() {
+            return; //already enabled
+        }
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+    }
+}
+
+/// Restores the terminal mode from before the hotkeys were enabled
+#[cfg(unix)]
+pub fn restore_terminal() {
+    if let Some(termios) = ORIGINAL_TERMIOS.get() {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
+        }
+    }
+}
+
+/// On other platforms the keys have to be confirmed with enter
+#[cfg(not(unix))]
+fn enable_raw_mode() {}
+
+#[cfg(not(unix))]
+pub fn restore_terminal() {}
@@ -0,0 +1,63 @@
+use crate::stratum::stratum_data::PoolConfig;
+use serde_json::Value;
+
+/// Commands that change the state of the running miner. They are sent by the console,
+/// the dashboard and the management API and executed by the main loop.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCmd {
+    TogglePause,
+    Pause,
+    Resume,
+    Reconnect,
+    /// draw a new auto tuning arm now
+    Retune,
+    /// restart the workers with this number of threads, disables auto tuning
+    SetThreads(u64),
+    /// restart the workers with this share of the time sleeping between hashes
+    SetThrottle(u64),
+    /// restart the workers with the settings of this profile, disables auto tuning
+    SetProfile(String),
+    /// switch to the profile after the current one
+    NextProfile,
+    /// connect to another pool, boxed as the pool config is large
+    SwitchPool(Box<PoolConfig>),
+    /// re-read the pool and worker settings from the config file
+    ReloadConfig,
+    /// re-read the config file with this fleet config of the coordinator on top
+    ApplyFleetConfig(String),
+    /// apply the command and write its settings to the config file
+    Persist(Box<ControlCmd>),
+    /// stop the workers and exit
+    Shutdown,
+}
+
+/// The config keys a command changes, empty for commands that do not change settings
+pub fn config_values(cmd: &ControlCmd) -> Vec<(&'static str, &'static str, Value)> {
+    match cmd {
+        ControlCmd::SetThreads(num_threads) => vec![
+            ("worker", "num_threads", (*num_threads).into()),
+            ("worker", "auto_tune", false.into()),
+        ],
+        ControlCmd::SetThrottle(percent) => vec![("worker", "throttle_percent", (*percent).into())],
+        ControlCmd::SetProfile(name) => vec![("worker", "profile", name.as_str().into())],
+        ControlCmd::SwitchPool(pool_conf) => vec![
+            (
+                "pool",
+                "pool_address",
+                pool_conf.pool_address.as_str().into(),
+            ),
+            (
+                "pool",
+                "wallet_address",
+                pool_conf.wallet_address.as_str().into(),
+            ),
+            (
+                "pool",
+                "pool_password",
+                pool_conf.pool_password.as_str().into(),
+            ),
+        ],
+        ControlCmd::Persist(cmd) => config_values(cmd),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,133 @@
+#[cfg(unix)]
+extern crate libc;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The pid of the running mithril, removes the file when dropped
+pub struct Pidfile {
+    path: PathBuf,
+}
+
+impl Pidfile {
+    /// Writes the pid of this process. Fails if the file names another process that
+    /// is still alive, a stale file is overwritten.
+    pub fn create(path: &Path) -> io::Result<Pidfile> {
+        check_not_running(path)?;
+        fs::write(path, format!("{}\n", std::process::id()))?;
+        Ok(Pidfile {
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Pidfile {
+    fn drop(&mut self) {
+        //only remove the file if it was not taken over by another process
+        if read_pid(&self.path) == Some(std::process::id()) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// The pid in the file if that process is alive
+pub fn running_pid(path: &Path) -> Option<u32> {
+    read_pid(path).filter(|pid| is_alive(*pid))
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn check_not_running(path: &Path) -> io::Result<()> {
+    match running_pid(path) {
+        Some(pid) if pid != std::process::id() => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "mithril is already running with pid {} ({})",
+                pid,
+                path.display()
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Forks to the background and detaches from the terminal. stdin reads from
+/// `/dev/null`, stdout and stderr (and with them the log) are appended to `log_file`.
+/// Has to be called before any thread is started, only the calling thread survives
+/// the fork. The working directory is kept, relative paths of the config stay valid.
+#[cfg(unix)]
+pub fn daemonize(log_file: &Path, pidfile: Option<&Path>) -> io::Result<Option<Pidfile>> {
+    use std::fs::OpenOptions;
+    use std::os::unix::io::AsRawFd;
+
+    //fail while the errors can still be seen on the terminal
+    if let Some(path) = pidfile {
+        check_not_running(path)?;
+    }
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)?;
+    let null = OpenOptions::new().read(true).open("/dev/null")?;
+
+    fork_and_exit_parent()?;
+    //new session without a controlling terminal
+    if unsafe { libc::setsid() } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    //the session leader could acquire a terminal again, its child can not
+    fork_and_exit_parent()?;
+
+    for (from, to) in [
+        (null.as_raw_fd(), libc::STDIN_FILENO),
+        (log.as_raw_fd(), libc::STDOUT_FILENO),
+        (log.as_raw_fd(), libc::STDERR_FILENO),
+    ] {
+        if unsafe { libc::dup2(from, to) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    pidfile.map(Pidfile::create).transpose()
+}
+
+#[cfg(not(unix))]
+pub fn daemonize(_log_file: &Path, _pidfile: Option<&Path>) -> io::Result<Option<Pidfile>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--daemon needs fork, use a service instead",
+    ))
+}
+
+#[cfg(unix)]
+fn fork_and_exit_parent() -> io::Result<()> {
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(()),
+        //the parent must not run destructors or flush buffers that the child shares
+        _ => unsafe { libc::_exit(0) },
+    }
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    if pid == 0 || pid > i32::MAX as u32 {
+        return false;
+    }
+    //signal 0 only checks the process, EPERM means it exists but belongs to another user
+    let sent = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    sent || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a cheap liveness check every pidfile is treated as stale
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    false
+}
@@ -0,0 +1,330 @@
+use crate::byte_string;
+use crate::pow::randomx::Variant;
+use crate::randomx::large_alloc::{self, SharedFile};
+use crate::randomx::memory::{SeedMemory, VmMemory};
+use crate::randomx::wipe::wipe;
+
+use argon2::Block;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The server and its clients have to be the same build, the files hold Rust values
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The shared files start with it, the ones of a previous server are removed at start
+const FILE_PREFIX: &str = "mithril-dataset-";
+/// The server answers when the dataset is computed, which takes minutes on few cores
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetServerConfig {
+    /// path of the Unix socket the miners connect to
+    pub socket: PathBuf,
+    /// directory of the shared files, a tmpfs like `/dev/shm`
+    pub dir: PathBuf,
+    /// number of threads that compute a dataset
+    pub threads: u64,
+    /// number of seed hashes whose dataset is kept
+    pub seeds: usize,
+}
+
+/// The line a miner sends
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetRequest {
+    /// the RandomX variant, i.e. `rx/0`
+    pub algorithm: String,
+    pub seed_hash: String,
+}
+
+/// The line the server answers with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasetReply {
+    Ready(DatasetFiles),
+    Error(String),
+}
+
+/// The computed cache and dataset of a seed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetFiles {
+    /// mithril version of the server
+    pub version: String,
+    pub cache: PathBuf,
+    pub dataset: PathBuf,
+    /// the `VmMemory::checksum` of the dataset
+    pub checksum: u64,
+}
+
+/// Computes the datasets in shared files, the memory of a dataset is freed and its files
+/// are removed when more than `seeds` seed hashes were requested after it
+pub struct DatasetServer {
+    dir: PathBuf,
+    threads: usize,
+    seeds: usize,
+    /// the least recently requested first
+    datasets: Mutex<Vec<SharedDataset>>,
+}
+
+struct SharedDataset {
+    algorithm: String,
+    seed_hash: String,
+    files: DatasetFiles,
+    //frees the files when dropped
+    _memory: VmMemory,
+}
+
+impl DatasetServer {
+    pub fn new(dir: PathBuf, threads: usize, seeds: usize) -> DatasetServer {
+        DatasetServer {
+            dir,
+            threads: threads.max(1),
+            seeds: seeds.max(1),
+            datasets: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The files of the dataset, computed first if no miner requested it before. Requests
+    /// wait for the computation of another seed.
+    pub fn dataset(&self, request: &DatasetRequest) -> Result<DatasetFiles, String> {
+        let variant = Variant::from_name(&request.algorithm)
+            .ok_or_else(|| format!("{} is not a RandomX algorithm", request.algorithm))?;
+        if request.seed_hash.len() != 64
+            || !request.seed_hash.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            return Err(format!("invalid seed_hash {}", request.seed_hash));
+        }
+        let mut datasets = self.datasets.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(i) = datasets.iter().position(|dataset| {
+            dataset.algorithm == request.algorithm && dataset.seed_hash == request.seed_hash
+        }) {
+            let dataset = datasets.remove(i);
+            let files = dataset.files.clone();
+            datasets.push(dataset);
+            return Ok(files);
+        }
+
+        let start = Instant::now();
+        let dataset = self.compute(variant, &request.seed_hash)?;
+        info!(
+            "computed the dataset of seed_hash {} for {} in {:?}",
+            request.seed_hash,
+            request.algorithm,
+            start.elapsed()
+        );
+        let files = dataset.files.clone();
+        datasets.push(dataset);
+        if datasets.len() > self.seeds {
+            let old = datasets.remove(0);
+            info!("removed the dataset of seed_hash {}", old.seed_hash);
+        }
+        Ok(files)
+    }
+
+    fn compute(&self, variant: Variant, seed_hash: &str) -> Result<SharedDataset, String> {
+        let name: String = variant
+            .name()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let cache_path = self
+            .dir
+            .join(format!("{}{}-{}.cache", FILE_PREFIX, name, seed_hash));
+        let dataset_path = self
+            .dir
+            .join(format!("{}{}-{}.dataset", FILE_PREFIX, name, seed_hash));
+
+        let mut key = byte_string::string_to_u8_array(seed_hash);
+        let seed_memory = SeedMemory::try_new_initialised_in(
+            &key,
+            Arc::new(variant.config()),
+            Arc::new(SharedFile::new(&cache_path)),
+        );
+        wipe(&mut key);
+        let seed_memory = seed_memory.map_err(|err| err.to_string())?;
+        //the buffers fall back to the heap if the file could not be created
+        if !seed_memory.blocks.shared() {
+            return Err(file_error(
+                &cache_path,
+                seed_memory.blocks.large_pages_error(),
+            ));
+        }
+        let memory = VmMemory::try_with_dataset_in(
+            Arc::new(seed_memory),
+            Arc::new(SharedFile::new(&dataset_path)),
+        )
+        .map_err(|err| err.to_string())?;
+        {
+            let dataset = memory.read_dataset();
+            if !dataset.shared() {
+                return Err(file_error(&dataset_path, dataset.large_pages_error()));
+            }
+        }
+        memory.init_dataset(self.threads);
+        Ok(SharedDataset {
+            algorithm: variant.name().to_string(),
+            seed_hash: seed_hash.to_string(),
+            files: DatasetFiles {
+                version: VERSION.to_string(),
+                cache: cache_path,
+                dataset: dataset_path,
+                checksum: memory.checksum(),
+            },
+            _memory: memory,
+        })
+    }
+
+    /// Answers the request line of a miner
+    pub fn reply(&self, line: &str) -> DatasetReply {
+        match serde_json::from_str::<DatasetRequest>(line) {
+            Ok(request) => match self.dataset(&request) {
+                Ok(files) => DatasetReply::Ready(files),
+                Err(err) => {
+                    warn!("dataset request failed: {}", err);
+                    DatasetReply::Error(err)
+                }
+            },
+            Err(err) => DatasetReply::Error(format!("invalid request: {}", err)),
+        }
+    }
+}
+
+fn file_error(path: &Path, err: Option<&io::Error>) -> String {
+    match err {
+        Some(err) => format!("could not create {}: {}", path.display(), err),
+        None => format!("could not create {}", path.display()),
+    }
+}
+
+/// Serves the miners on the socket until the process is stopped
+#[cfg(unix)]
+pub fn run(conf: &DatasetServerConfig) -> io::Result<()> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    remove_stale_files(&conf.dir)?;
+    //a socket of a previous server, other files are not replaced
+    if std::fs::symlink_metadata(&conf.socket).is_ok_and(|meta| meta.file_type().is_socket()) {
+        std::fs::remove_file(&conf.socket)?;
+    }
+    let listener = UnixListener::bind(&conf.socket)?;
+    //miners of other users connect too
+    std::fs::set_permissions(&conf.socket, std::fs::Permissions::from_mode(0o666))?;
+    info!(
+        "dataset server listening on {}, files in {}",
+        conf.socket.display(),
+        conf.dir.display()
+    );
+
+    let server = Arc::new(DatasetServer::new(
+        conf.dir.clone(),
+        conf.threads as usize,
+        conf.seeds,
+    ));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("dataset server connection failed: {}", err);
+                continue;
+            }
+        };
+        let server = server.clone();
+        thread::Builder::new()
+            .name("dataset server thread".to_string())
+            .spawn(move || {
+                if let Err(err) = serve(&server, stream) {
+                    warn!("dataset server connection failed: {}", err);
+                }
+            })?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn run(_conf: &DatasetServerConfig) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the dataset server needs Unix sockets",
+    ))
+}
+
+#[cfg(unix)]
+fn serve(server: &DatasetServer, stream: std::os::unix::net::UnixStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let reply = serde_json::to_string(&server.reply(&line)).map_err(io::Error::other)?;
+    (&stream).write_all(format!("{}\n", reply).as_bytes())
+}
+
+/// The files of a server that did not remove them, i.e. because it was killed
+fn remove_stale_files(dir: &Path) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with(FILE_PREFIX) {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Maps the cache and dataset of the seed from the server on `socket`, the memory is
+/// shared with the server and its other miners
+#[cfg(unix)]
+pub fn request(socket: &Path, variant: Variant, seed_hash: &str) -> io::Result<VmMemory> {
+    let stream = std::os::unix::net::UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    let request = DatasetRequest {
+        algorithm: variant.name().to_string(),
+        seed_hash: seed_hash.to_string(),
+    };
+    let line = serde_json::to_string(&request).map_err(io::Error::other)?;
+    (&stream).write_all(format!("{}\n", line).as_bytes())?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let files = match serde_json::from_str::<DatasetReply>(&line).map_err(io::Error::other)? {
+        DatasetReply::Ready(files) => files,
+        DatasetReply::Error(err) => return Err(io::Error::other(err)),
+    };
+    map_files(&files, variant, seed_hash)
+}
+
+#[cfg(not(unix))]
+pub fn request(_socket: &Path, _variant: Variant, _seed_hash: &str) -> io::Result<VmMemory> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the dataset server needs Unix sockets",
+    ))
+}
+
+/// The memory of the files of a server of the same version
+pub fn map_files(files: &DatasetFiles, variant: Variant, seed_hash: &str) -> io::Result<VmMemory> {
+    if files.version != VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the dataset server runs mithril {}, this is {}",
+                files.version, VERSION
+            ),
+        ));
+    }
+    let config = variant.config();
+    //the files are complete, the server answers after computing them, and the sizes are checked
+    let blocks =
+        unsafe { large_alloc::map_file::<Block>(&files.cache, config.argon_memory_kib as usize)? };
+    let dataset = unsafe {
+        large_alloc::map_file::<Option<[u64; 8]>>(&files.dataset, config.dataset_item_count())?
+    };
+    let mut key = byte_string::string_to_u8_array(seed_hash);
+    let seed_memory = SeedMemory::with_blocks(&key, blocks, Arc::new(config));
+    wipe(&mut key);
+    Ok(VmMemory::with_computed_dataset(
+        Arc::new(seed_memory),
+        dataset,
+        files.checksum,
+    ))
+}
@@ -0,0 +1,93 @@
+//! Conversions between targets and difficulties. A pool sends a compact 4 byte target,
+//! solo mining an 8 byte one, both as little endian hex. The workers compare the last 8
+//! bytes of a hash with the 64 bit target, a block is checked on all 256 bits of the
+//! hash like monerod does.
+
+use crate::byte_string;
+
+/// The 64 bit target of a compact 4 byte target, 0 stays 0
+pub fn compact_target(target: u32) -> u64 {
+    if target == 0 {
+        return 0;
+    }
+    u64::MAX / (u64::from(u32::MAX) / u64::from(target))
+}
+
+/// The 64 bit target of a 4 byte (of a pool) or 8 byte (of solo mining) hex target
+pub fn target_value(hex: &str) -> u64 {
+    if hex.len() == 16 {
+        return byte_string::hex2_u64_le(hex);
+    }
+    compact_target(byte_string::hex2_u32_le(hex))
+}
+
+/// The 8 byte hex target, i.e. of solo mining, the 4 bytes do not reach the network
+/// difficulty
+pub fn format_target(target: u64) -> String {
+    target
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Expected number of hashes to find a share for the target, the target 0 is met by no
+/// hash and has the highest difficulty
+pub fn target_difficulty(target: u64) -> u64 {
+    u64::MAX.checked_div(target).unwrap_or(u64::MAX)
+}
+
+/// The target of the difficulty, 0 is handled as 1
+pub fn difficulty_target(difficulty: u64) -> u64 {
+    u64::MAX / difficulty.max(1)
+}
+
+/// Expected number of hashes to find a share for the hex target of a job
+pub fn job_difficulty(hex: &str) -> u64 {
+    target_difficulty(target_value(hex))
+}
+
+/// The last 8 bytes of the hex hash, compared with the 64 bit target
+pub fn hash_value(hash: &str) -> u64 {
+    byte_string::hex2_u64_le(&hash[48..])
+}
+
+/// `hash_value` of the hash bytes, without encoding them
+pub fn hash_bytes_value(hash: &[u8; 32]) -> u64 {
+    u64::from_le_bytes(hash[24..].try_into().expect("8 byte limb"))
+}
+
+/// Difficulty the hex hash would have satisfied, see `max_difficulty`. 0 if it is not a
+/// 32 byte hex hash.
+pub fn hash_difficulty(hash: &str) -> u64 {
+    match byte_string::decode_array(hash) {
+        Ok(bytes) => max_difficulty(&bytes),
+        Err(_) => 0,
+    }
+}
+
+/// True if `hash * difficulty` fits in 256 bits, the check of monerod for a block
+pub fn meets_difficulty(hash: &[u8; 32], difficulty: u64) -> bool {
+    let mut carry: u128 = 0;
+    for limb in hash.chunks(8) {
+        let limb = u64::from_le_bytes(limb.try_into().expect("8 byte limb"));
+        carry = (u128::from(limb) * u128::from(difficulty) + carry) >> 64;
+    }
+    carry == 0
+}
+
+/// The highest difficulty the hash meets, `(2^256 - 1) / hash` on all 256 bits, at most
+/// `u64::MAX`
+pub fn max_difficulty(hash: &[u8; 32]) -> u64 {
+    //meets_difficulty holds up to the quotient, which is found by bisection
+    let (mut low, mut high) = (0, u64::MAX);
+    while low < high {
+        let mid = low + (high - low) / 2 + 1;
+        if meets_difficulty(hash, mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
@@ -0,0 +1,419 @@
+#[cfg(unix)]
+extern crate libc;
+
+use crate::cgroup;
+use crate::cgroup::FULL_MEMORY_BYTES;
+#[cfg(target_os = "linux")]
+use crate::hardware;
+use crate::randomx::pages;
+use crate::stratum::socks;
+use crate::stratum::stratum_data::PoolConfig;
+use serde_json::json;
+
+use std::fmt;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+const POOL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    /// mining works, but slower than it could
+    Warn,
+    /// mining does not work (or crashes) until this is fixed
+    Fail,
+}
+
+/// One line of the `mithril doctor` report
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub message: String,
+    /// command or setting that fixes a warning or failure
+    pub fix: Option<String>,
+}
+
+/// Resource limits of the process in bytes, None if unlimited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ulimits {
+    pub virtual_bytes: Option<u64>,
+    pub data_bytes: Option<u64>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: String) -> Check {
+        Check {
+            name,
+            status: Status::Pass,
+            message,
+            fix: None,
+        }
+    }
+
+    fn problem(name: &'static str, status: Status, message: String, fix: Option<String>) -> Check {
+        Check {
+            name,
+            status,
+            message,
+            fix,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Status::Pass => write!(f, "PASS"),
+            Status::Warn => write!(f, "WARN"),
+            Status::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// The hardware and OS checks for `threads` worker threads
+pub fn system_checks(threads: u64) -> Vec<Check> {
+    let mut checks = cpu_feature_checks();
+    checks.push(huge_pages_check(threads));
+    checks.push(check_memory(
+        available_memory(),
+        cgroup::limits().memory_bytes,
+    ));
+    #[cfg(target_os = "linux")]
+    {
+        let msr = std::path::Path::new("/dev/cpu/0/msr");
+        let writable = std::fs::OpenOptions::new().write(true).open(msr).is_ok();
+        checks.push(check_msr(msr.exists(), writable));
+        checks.push(check_numa(hardware::numa_nodes()));
+    }
+    #[cfg(unix)]
+    checks.push(check_ulimits(&ulimits()));
+    checks
+}
+
+/// Connects to the pool (through the proxy if one is set), but does not log in
+pub fn pool_check(conf: &PoolConfig) -> Check {
+    check_pool(&conf.pool_address, probe_pool(conf))
+}
+
+/// The config could not be read, so the pools are unknown
+pub fn config_check(err: &str) -> Check {
+    Check::problem(
+        "pool",
+        Status::Fail,
+        format!("no pool to check, {}", err),
+        Some("mithril init".to_string()),
+    )
+}
+
+pub fn has_failures(checks: &[Check]) -> bool {
+    checks.iter().any(|c| c.status == Status::Fail)
+}
+
+pub fn format_report(checks: &[Check]) -> String {
+    let mut lines = Vec::new();
+    for check in checks {
+        lines.push(format!(
+            "{}  {}: {}",
+            check.status, check.name, check.message
+        ));
+        if let Some(fix) = &check.fix {
+            lines.push(format!("      fix: {}", fix));
+        }
+    }
+    let problems = checks.iter().filter(|c| c.status != Status::Pass).count();
+    lines.push(String::new());
+    lines.push(match problems {
+        0 => "all checks passed".to_string(),
+        1 => "1 problem found".to_string(),
+        n => format!("{} problems found", n),
+    });
+    lines.join("\n")
+}
+
+/// The report of `doctor --json`, the status is `pass`, `warn` or `fail`
+pub fn report_json(checks: &[Check]) -> serde_json::Value {
+    json!({
+        "checks": checks,
+        "problems": checks.iter().filter(|c| c.status != Status::Pass).count(),
+        "failed": has_failures(checks),
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+fn cpu_feature_checks() -> Vec<Check> {
+    check_cpu_features(
+        is_x86_feature_detected!("aes"),
+        is_x86_feature_detected!("avx2"),
+    )
+}
+
+/// The other architectures use the software AES (or the aarch64 crypto extension)
+#[cfg(not(target_arch = "x86_64"))]
+fn cpu_feature_checks() -> Vec<Check> {
+    Vec::new()
+}
+
+/// mithril uses the AES-NI instructions unconditionally on x86_64, AVX2 is a hint for
+/// a CPU that is too old to mine efficiently
+pub fn check_cpu_features(aes: bool, avx2: bool) -> Vec<Check> {
+    let aes = if aes {
+        Check::pass("AES-NI", "supported".to_string())
+    } else {
+        Check::problem(
+            "AES-NI",
+            Status::Fail,
+            "not supported, mithril stops with an illegal instruction".to_string(),
+            Some(
+                "enable AES in the BIOS or the hypervisor (i.e. `-cpu host` for QEMU)".to_string(),
+            ),
+        )
+    };
+    let avx2 = if avx2 {
+        Check::pass("AVX2", "supported".to_string())
+    } else {
+        Check::problem(
+            "AVX2",
+            Status::Warn,
+            "not supported, the CPU is older than Haswell or Zen, expect a low hashrate"
+                .to_string(),
+            None,
+        )
+    };
+    vec![aes, avx2]
+}
+
+#[cfg(target_os = "linux")]
+fn huge_pages_check(threads: u64) -> Check {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").unwrap_or_default();
+    check_huge_pages(
+        meminfo_field(&meminfo, "HugePages_Total").unwrap_or(0),
+        threads,
+        pages::thp_mode().as_deref(),
+    )
+}
+
+/// Without a reservation to read, a scratchpad sized large page allocation is tried
+#[cfg(not(target_os = "linux"))]
+fn huge_pages_check(_threads: u64) -> Check {
+    if pages::PageBuffer::new(2 * 1024 * 1024 / 8).large_pages() {
+        Check::pass("huge pages", "large pages are granted".to_string())
+    } else {
+        Check::problem(
+            "huge pages",
+            Status::Warn,
+            "large pages are not granted, the scratchpads use normal pages".to_string(),
+            Some("see the Large Pages section of the README for your OS".to_string()),
+        )
+    }
+}
+
+/// One reserved 2 MB page per scratchpad, the dataset uses transparent huge pages
+pub fn check_huge_pages(reserved: u64, threads: u64, thp_mode: Option<&str>) -> Check {
+    let mut check = if reserved >= threads {
+        Check::pass(
+            "huge pages",
+            format!(
+                "{} reserved, {} needed for the scratchpads",
+                reserved, threads
+            ),
+        )
+    } else {
+        Check::problem(
+            "huge pages",
+            Status::Warn,
+            format!(
+                "{} reserved, {} needed for the scratchpads, the rest uses 4 KB pages",
+                reserved, threads
+            ),
+            Some(format!(
+                "sudo sysctl -w vm.nr_hugepages={} (add it to /etc/sysctl.conf to keep it)",
+                threads
+            )),
+        )
+    };
+    if thp_mode == Some("never") {
+        check
+            .message
+            .push_str(", transparent huge pages for the dataset are disabled");
+        check.status = Status::Warn;
+        let thp_fix = "echo madvise | sudo tee /sys/kernel/mm/transparent_hugepage/enabled";
+        check.fix = Some(match check.fix {
+            Some(fix) => format!("{} and {}", fix, thp_fix),
+            None => thp_fix.to_string(),
+        });
+    }
+    check
+}
+
+/// The smaller of the available memory and the container limit has to hold the dataset
+pub fn check_memory(available_bytes: Option<u64>, limit_bytes: Option<u64>) -> Check {
+    let memory = match (available_bytes, limit_bytes) {
+        (Some(available), Some(limit)) => available.min(limit),
+        (available, limit) => match available.or(limit) {
+            Some(memory) => memory,
+            None => return Check::pass("memory", "free memory is unknown".to_string()),
+        },
+    };
+    let message = format!(
+        "{} MB available, {} MB needed for the dataset",
+        memory / 1024 / 1024,
+        FULL_MEMORY_BYTES / 1024 / 1024
+    );
+    if memory >= FULL_MEMORY_BYTES {
+        return Check::pass("memory", message);
+    }
+    let fix = if limit_bytes == Some(memory) {
+        "raise the container memory limit, i.e. docker run --memory 3g"
+    } else {
+        "close other programs, mithril falls back to the slow light mode"
+    };
+    Check::problem("memory", Status::Warn, message, Some(fix.to_string()))
+}
+
+/// The msr device is needed to tune the CPU (i.e. disable the hardware prefetchers)
+/// with wrmsr, worth up to 15% on Ryzen
+pub fn check_msr(exists: bool, writable: bool) -> Check {
+    match (exists, writable) {
+        (true, true) => Check::pass("MSR", "/dev/cpu/0/msr is writable".to_string()),
+        (true, false) => Check::problem(
+            "MSR",
+            Status::Warn,
+            "/dev/cpu/0/msr is not writable".to_string(),
+            Some("run the MSR tuning as root".to_string()),
+        ),
+        (false, _) => Check::problem(
+            "MSR",
+            Status::Warn,
+            "the msr kernel module is not loaded".to_string(),
+            Some("sudo modprobe msr".to_string()),
+        ),
+    }
+}
+
+/// The dataset is allocated once, on multi socket systems the workers of the other
+/// nodes read it over the interconnect
+pub fn check_numa(nodes: usize) -> Check {
+    if nodes <= 1 {
+        return Check::pass("NUMA", "single node".to_string());
+    }
+    Check::problem(
+        "NUMA",
+        Status::Warn,
+        format!("{} nodes, the dataset is only on one of them", nodes),
+        Some("set numa = \"interleave\" in the [memory] section".to_string()),
+    )
+}
+
+/// Limits below the dataset size make the allocation fail
+pub fn check_ulimits(limits: &Ulimits) -> Check {
+    let too_low = |limit: Option<u64>| limit.is_some_and(|bytes| bytes < FULL_MEMORY_BYTES);
+    let mut fixes = Vec::new();
+    if too_low(limits.virtual_bytes) {
+        fixes.push("ulimit -v unlimited");
+    }
+    if too_low(limits.data_bytes) {
+        fixes.push("ulimit -d unlimited");
+    }
+    if fixes.is_empty() {
+        return Check::pass(
+            "ulimits",
+            "no memory limit below the dataset size".to_string(),
+        );
+    }
+    Check::problem(
+        "ulimits",
+        Status::Fail,
+        "the virtual memory or data segment limit is below the dataset size".to_string(),
+        Some(format!(
+            "{} (LimitAS=infinity and LimitDATA=infinity for systemd)",
+            fixes.join(" and ")
+        )),
+    )
+}
+
+pub fn check_pool(address: &str, connected: io::Result<Duration>) -> Check {
+    match connected {
+        Ok(time) => Check::pass(
+            "pool",
+            format!("{} reachable in {} ms", address, time.as_millis()),
+        ),
+        Err(err) => Check::problem(
+            "pool",
+            Status::Fail,
+            format!("{} not reachable: {}", address, err),
+            Some(format!(
+                "check the address and port, the firewall and the DNS with: nc -vz {}",
+                address.replace(':', " ")
+            )),
+        ),
+    }
+}
+
+/// The value of a `/proc/meminfo` field, in kB for the sizes
+pub fn meminfo_field(meminfo: &str, key: &str) -> Option<u64> {
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+        .and_then(|value| value.split_whitespace().next()?.parse().ok())
+}
+
+fn probe_pool(conf: &PoolConfig) -> io::Result<Duration> {
+    let start = Instant::now();
+    if !conf.proxy.is_empty() {
+        socks::connect(&conf.proxy, &conf.pool_address)?;
+        return Ok(start.elapsed());
+    }
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no address found");
+    for addr in conf.pool_address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, POOL_TIMEOUT) {
+            Ok(_) => return Ok(start.elapsed()),
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+#[cfg(target_os = "linux")]
+fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    meminfo_field(&meminfo, "MemAvailable").map(|kb| kb * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn available_memory() -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn ulimits() -> Ulimits {
+    Ulimits {
+        virtual_bytes: rlimit(libc::RLIMIT_AS),
+        data_bytes: rlimit(libc::RLIMIT_DATA),
+    }
+}
+
+//glibc declares the resources as an enum
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type Resource = libc::c_int;
+
+//rlim_t is signed on the BSDs
+#[allow(clippy::unnecessary_cast)]
+#[cfg(unix)]
+fn rlimit(resource: Resource) -> Option<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(resource, &mut limit) } != 0
+        || limit.rlim_cur == libc::RLIM_INFINITY
+    {
+        return None;
+    }
+    Some(limit.rlim_cur as u64)
+}
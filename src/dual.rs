@@ -0,0 +1,298 @@
+extern crate crossbeam_channel;
+
+use self::crossbeam_channel::{select, unbounded, Receiver, RecvTimeoutError, Sender};
+use crate::difficulty;
+use crate::error::{MithrilError, Recovery};
+use crate::metric;
+use crate::metric::stats::MinerStats;
+use crate::metric::MetricConfig;
+use crate::pow::{self, PowAlgorithm};
+use crate::randomx::memory::VmMemoryAllocator;
+use crate::signals;
+use crate::stratum::stratum_data::{Job, PoolConfig};
+use crate::stratum::{StratumAction, StratumClient, StratumError};
+use crate::worker::worker_pool;
+use crate::worker::worker_pool::WorkerConfig;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Wait before the dual pool is connected again after an error
+const RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// A second pool that is mined at the same time as the primary one, with threads of
+/// its own, i.e. for a secondary chain that is profitable for a while
+#[derive(Debug, Clone, PartialEq)]
+pub struct DualConfig {
+    pub pool_conf: PoolConfig,
+    /// started in addition to the threads of the primary pool
+    pub num_threads: u64,
+}
+
+enum DualCmd {
+    Pause,
+    Resume,
+    Stop,
+}
+
+/// The thread that mines the dual pool, it reconnects by itself until it is stopped
+pub struct DualMiner {
+    cmd_sndr: Sender<DualCmd>,
+    hnd: thread::JoinHandle<()>,
+    /// shares and hashes of the dual pool, the primary pool counts its own
+    pub stats: Arc<MinerStats>,
+}
+
+/// Starts mining the dual pool. Its memory is shared with the primary pool if both
+/// mine the same RandomX variant with the same seed.
+pub fn start(
+    conf: DualConfig,
+    worker_conf: &WorkerConfig,
+    metric_resolution: u64,
+    allocator: fn() -> VmMemoryAllocator,
+) -> DualMiner {
+    let (cmd_sndr, cmd_rcvr) = unbounded();
+    let stats = Arc::new(MinerStats::new());
+    let thread_stats = stats.clone();
+    //the primary pool pins its threads to the first cores
+    let worker_conf = WorkerConfig {
+        num_threads: conf.num_threads,
+        affinity: false,
+        ..worker_conf.clone()
+    };
+    let hnd = thread::Builder::new()
+        .name("dual pool thread".to_string())
+        .spawn(move || {
+            run(
+                &conf,
+                &worker_conf,
+                metric_resolution,
+                allocator,
+                &cmd_rcvr,
+                &thread_stats,
+            )
+        })
+        .expect("dual pool thread handle");
+    DualMiner {
+        cmd_sndr,
+        hnd,
+        stats,
+    }
+}
+
+impl DualMiner {
+    /// Stops hashing until `resume`, i.e. while the primary pool is paused
+    pub fn pause(&self) {
+        let _ = self.cmd_sndr.send(DualCmd::Pause);
+    }
+
+    pub fn resume(&self) {
+        let _ = self.cmd_sndr.send(DualCmd::Resume);
+    }
+
+    /// Stops the workers and waits for the thread
+    pub fn stop(self) {
+        let _ = self.cmd_sndr.send(DualCmd::Stop);
+        if self.hnd.join().is_err() {
+            error!("dual pool thread panicked");
+        }
+    }
+}
+
+/// How a session of the dual pool ended
+enum SessionEnd {
+    Pause,
+    Stop,
+}
+
+fn run(
+    conf: &DualConfig,
+    worker_conf: &WorkerConfig,
+    metric_resolution: u64,
+    allocator: fn() -> VmMemoryAllocator,
+    cmd_rcvr: &Receiver<DualCmd>,
+    stats: &Arc<MinerStats>,
+) {
+    let pool_address = &conf.pool_conf.pool_address;
+    let mut algorithm = match pow::new_algorithm(&conf.pool_conf.algorithm, allocator) {
+        Some(algorithm) => algorithm,
+        None => {
+            error!(
+                "algorithm {} of the dual pool is not supported, not mining {}",
+                conf.pool_conf.algorithm, pool_address
+            );
+            return;
+        }
+    };
+    info!(
+        "dual mining {} with {} threads on {}",
+        algorithm.name(),
+        worker_conf.num_threads,
+        pool_address
+    );
+
+    loop {
+        if signals::shutdown_requested() {
+            return;
+        }
+        let result = session(
+            conf,
+            worker_conf,
+            metric_resolution,
+            &mut algorithm,
+            allocator,
+            cmd_rcvr,
+            stats,
+        );
+        stats.disconnected();
+        let delay = match result {
+            Ok(SessionEnd::Stop) => return,
+            Ok(SessionEnd::Pause) => {
+                stats.set_paused(true);
+                let resumed = await_resume(cmd_rcvr);
+                stats.set_paused(false);
+                if !resumed {
+                    return;
+                }
+                continue;
+            }
+            Err(err) => match err.recovery(algorithm.full_memory()) {
+                Recovery::Retry => {
+                    error!("dual pool {}: {}, reconnecting", pool_address, err);
+                    RECONNECT_DELAY
+                }
+                Recovery::LightMode => {
+                    error!(
+                        "dual pool {}: {}, continuing in the slow light mode",
+                        pool_address, err
+                    );
+                    algorithm.light_mode();
+                    Duration::ZERO
+                }
+                Recovery::Exit => {
+                    error!(
+                        "dual pool {}: {}, stopping the dual pool",
+                        pool_address, err
+                    );
+                    return;
+                }
+            },
+        };
+        match cmd_rcvr.recv_timeout(delay) {
+            Ok(DualCmd::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+            Ok(DualCmd::Pause) => {
+                if !await_resume(cmd_rcvr) {
+                    return;
+                }
+            }
+            Ok(DualCmd::Resume) | Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+}
+
+/// One connection to the dual pool, the memory of the algorithm is kept for the next one
+fn session(
+    conf: &DualConfig,
+    worker_conf: &WorkerConfig,
+    metric_resolution: u64,
+    algorithm: &mut Box<dyn PowAlgorithm>,
+    allocator: fn() -> VmMemoryAllocator,
+    cmd_rcvr: &Receiver<DualCmd>,
+    stats: &Arc<MinerStats>,
+) -> Result<SessionEnd, MithrilError> {
+    let pool_address = conf.pool_conf.pool_address.clone();
+    let (stratum_sndr, stratum_rcvr) = unbounded();
+    let (client_err_sndr, client_err_rcvr) = unbounded();
+    let client = StratumClient::login(conf.pool_conf.clone(), client_err_sndr, stratum_sndr)?;
+    stats.connected(&pool_address);
+
+    let (metric_sndr, metric_rcvr) = unbounded();
+    //the metric file belongs to the primary pool
+    let metric = metric::start(MetricConfig::disabled(), metric_rcvr, stats.clone());
+    stats.set_thread_count(worker_conf.num_threads as usize);
+    let mut pool = worker_pool::start(
+        worker_conf.num_threads,
+        worker_conf,
+        &client.new_cmd_channel(),
+        metric_resolution,
+        &metric_sndr,
+        algorithm.clone_box(),
+        allocator,
+    );
+
+    let worker_errors = pool.errors().clone();
+    let init_stalls = pow::randomx::init_stalls();
+    let result = loop {
+        select! {
+            recv(stratum_rcvr) -> stratum_msg => match stratum_msg {
+                Err(_) => break Err(StratumError::ChannelClosed("action").into()),
+                Ok(StratumAction::Job { miner_id, seed_hash, height, blob, job_id, target, algo }) => {
+                    stats.set_difficulty(difficulty::job_difficulty(&target));
+                    let job = Job { seed_hash, height, blob, job_id, target, algo };
+                    if let Err(err) = pool.job_change(&miner_id, &job, conf.pool_conf.nicehash) {
+                        break Err(err);
+                    }
+                    stats.set_memory(pool.algorithm.memory());
+                }
+                Ok(StratumAction::ShareAccepted { share, latency }) => {
+                    stats.share_accepted(share.difficulty);
+                    stats.share_latency(latency);
+                    let snapshot = stats.snapshot();
+                    info!(
+                        "dual pool {}: share accepted (job {}, nonce {}), {} accepted, {} rejected",
+                        pool_address, share.job_id, share.nonce, snapshot.shares_accepted, snapshot.shares_rejected
+                    );
+                }
+                Ok(StratumAction::ShareRejected { share, err, latency }) => {
+                    stats.share_rejected();
+                    stats.share_latency(latency);
+                    warn!(
+                        "dual pool {}: share rejected (job {}, nonce {}): {}",
+                        pool_address, share.job_id, share.nonce, err
+                    );
+                }
+                Ok(StratumAction::Error { err }) => {
+                    error!("dual pool {}: stratum error: {}", pool_address, err)
+                }
+                //the dual pool is not a cluster coordinator
+                Ok(StratumAction::Ok) | Ok(StratumAction::KeepAliveOk) | Ok(StratumAction::Control { .. }) | Ok(StratumAction::FleetConfig { .. }) => {}
+            },
+            recv(cmd_rcvr) -> cmd => match cmd {
+                Ok(DualCmd::Pause) => break Ok(SessionEnd::Pause),
+                Ok(DualCmd::Resume) => {} //not paused
+                Ok(DualCmd::Stop) | Err(_) => break Ok(SessionEnd::Stop),
+            },
+            recv(client_err_rcvr) -> client_err => {
+                break Err(client_err.map_or(StratumError::ChannelClosed("error"), |err| err).into());
+            },
+            recv(worker_errors) -> worker_err => {
+                break Err(worker_err.map_or(MithrilError::ChannelClosed("worker error"), |err| err.into()));
+            },
+            recv(init_stalls) -> stall => {
+                break Err(stall.map_or(MithrilError::ChannelClosed("dataset init"), |err| err.into()));
+            },
+        }
+    };
+
+    *algorithm = pool.algorithm.clone_box();
+    pool.stop();
+    if let Err(err) = client.stop() {
+        warn!("dual pool stratum client did not stop cleanly: {}", err);
+    }
+    pool.join();
+    metric.stop();
+    metric.join();
+    result
+}
+
+/// Waits for the resume command, false if the dual pool is stopped instead
+fn await_resume(cmd_rcvr: &Receiver<DualCmd>) -> bool {
+    loop {
+        match cmd_rcvr.recv() {
+            Ok(DualCmd::Resume) => return true,
+            Ok(DualCmd::Pause) => {}
+            Ok(DualCmd::Stop) | Err(_) => return false,
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Estimated earnings of the current hashrate. The shares per day follow from the pool
+//! difficulty, the XMR per day need the network difficulty and the block reward, polled
+//! from a monerod (`get_info` and `get_last_block_header`) or an API.
+
+extern crate ureq;
+
+use crate::metric::stats::MinerStats;
+use crate::profit::{daily_revenue, CoinMarket};
+use crate::signals;
+use serde_json::{json, Value};
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const SECONDS_PER_DAY: f64 = 86400.0;
+/// atomic units per XMR
+const ATOMIC_UNITS: f64 = 1e12;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EarningsConfig {
+    pub enabled: bool,
+    /// returns the `NetworkStats` as JSON, used if no daemon is set
+    pub url: String,
+    /// RPC address of a monerod, i.e. `http://127.0.0.1:18081`
+    pub daemon: String,
+    pub interval_minutes: u64,
+    /// the fee the pool keeps of the rewards
+    pub pool_fee_percent: f64,
+}
+
+/// The network values the earnings depend on
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NetworkStats {
+    /// expected number of hashes per block
+    pub difficulty: f64,
+    /// XMR per block
+    pub reward: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Estimate {
+    /// None without pool difficulty
+    pub shares_per_day: Option<f64>,
+    /// after the pool fee, None without network stats
+    pub xmr_per_day: Option<f64>,
+}
+
+pub fn estimate(
+    hashrate: f64,
+    pool_difficulty: u64,
+    network: Option<&NetworkStats>,
+    pool_fee_percent: f64,
+) -> Estimate {
+    let shares_per_day =
+        (pool_difficulty > 0).then(|| hashrate * SECONDS_PER_DAY / pool_difficulty as f64);
+    let xmr_per_day = network.map(|network| {
+        let market = CoinMarket {
+            price: 1.0,
+            difficulty: network.difficulty,
+            reward: network.reward,
+        };
+        daily_revenue(hashrate, &market) * (1.0 - pool_fee_percent / 100.0)
+    });
+    Estimate {
+        shares_per_day,
+        xmr_per_day,
+    }
+}
+
+pub fn format_estimate(estimate: &Estimate) -> String {
+    let shares = match estimate.shares_per_day {
+        Some(shares) => format!("{:.1} shares/day", shares),
+        None => "n/a shares/day".to_string(),
+    };
+    match estimate.xmr_per_day {
+        Some(xmr) => format!("estimated {:.6} XMR/day, {}", xmr, shares),
+        None => format!("estimated {}", shares),
+    }
+}
+
+/// Polls the network stats into `stats`. Does nothing if the estimator is disabled.
+pub fn start(conf: EarningsConfig, stats: Arc<MinerStats>) {
+    if !conf.enabled {
+        return;
+    }
+    let source = if conf.daemon.is_empty() {
+        conf.url.clone()
+    } else {
+        conf.daemon.clone()
+    };
+    info!(
+        "estimating the earnings with the network stats of {}",
+        source
+    );
+    thread::Builder::new()
+        .name("earnings thread".to_string())
+        .spawn(move || {
+            let agent = ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build();
+            while !signals::shutdown_requested() {
+                let network = if conf.daemon.is_empty() {
+                    fetch_url(&agent, &conf.url)
+                } else {
+                    fetch_daemon(&agent, &conf.daemon)
+                };
+                match network {
+                    Ok(network) => stats.set_network_stats(network, conf.pool_fee_percent),
+                    Err(err) => warn!("earnings estimate: {}", err),
+                }
+                thread::sleep(Duration::from_secs(conf.interval_minutes * 60));
+            }
+        })
+        .expect("earnings thread handle");
+}
+
+fn fetch_url(agent: &ureq::Agent, url: &str) -> Result<NetworkStats, String> {
+    let body = agent
+        .get(url)
+        .call()
+        .map_err(|err| format!("fetching {} failed: {}", url, err))?
+        .into_string()
+        .map_err(|err| format!("reading {} failed: {}", url, err))?;
+    parse_network_stats(&body)
+}
+
+fn fetch_daemon(agent: &ureq::Agent, daemon: &str) -> Result<NetworkStats, String> {
+    let url = format!("{}/json_rpc", daemon.trim_end_matches('/'));
+    let call = |method: &str| {
+        agent
+            .post(&url)
+            .set("Content-Type", "application/json")
+            .send_string(&json!({"jsonrpc": "2.0", "id": "0", "method": method}).to_string())
+            .map_err(|err| format!("{} of {} failed: {}", method, url, err))?
+            .into_string()
+            .map_err(|err| format!("reading {} of {} failed: {}", method, url, err))
+    };
+    Ok(NetworkStats {
+        difficulty: parse_daemon_difficulty(&call("get_info")?)?,
+        reward: parse_daemon_reward(&call("get_last_block_header")?)?,
+    })
+}
+
+/// The API response, i.e. `{"difficulty": 3.2e11, "reward": 0.6}`
+pub fn parse_network_stats(body: &str) -> Result<NetworkStats, String> {
+    serde_json::from_str(body).map_err(|err| format!("invalid response: {}", err))
+}
+
+/// `result.difficulty` of the `get_info` response
+pub fn parse_daemon_difficulty(body: &str) -> Result<f64, String> {
+    rpc_result(body)?["difficulty"]
+        .as_f64()
+        .ok_or_else(|| "get_info without difficulty".to_string())
+}
+
+/// `result.block_header.reward` of the `get_last_block_header` response, in XMR
+pub fn parse_daemon_reward(body: &str) -> Result<f64, String> {
+    rpc_result(body)?["block_header"]["reward"]
+        .as_f64()
+        .map(|reward| reward / ATOMIC_UNITS)
+        .ok_or_else(|| "get_last_block_header without reward".to_string())
+}
+
+fn rpc_result(body: &str) -> Result<Value, String> {
+    let mut response: Value =
+        serde_json::from_str(body).map_err(|err| format!("invalid response: {}", err))?;
+    if let Some(message) = response["error"]["message"].as_str() {
+        return Err(format!("daemon error: {}", message));
+    }
+    Ok(response["result"].take())
+}
@@ -0,0 +1,53 @@
+use crate::randomx::memory::{DatasetError, MemoryError};
+use crate::stratum::StratumError;
+
+use std::io;
+use thiserror::Error;
+
+/// Why a mining session ended, `recovery` decides how the miner continues
+#[derive(Debug, Error)]
+pub enum MithrilError {
+    /// the config file or the command line options are invalid, the problems are
+    /// printable as they are
+    #[error("{0}")]
+    Config(String),
+    #[error("pool connection failed: {0}")]
+    Stratum(#[from] StratumError),
+    #[error("memory init failed: {0}")]
+    Memory(#[from] MemoryError),
+    /// a worker thread read outside of the memory of the job
+    #[error("hashing failed: {0}")]
+    Dataset(#[from] DatasetError),
+    /// a thread of the miner ended without stopping the session
+    #[error("the {0} channel is closed")]
+    ChannelClosed(&'static str),
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// reconnect, to the next backup pool
+    Retry,
+    /// reconnect with the light mode memory
+    LightMode,
+    /// stop mining, the error is not going away by itself
+    Exit,
+}
+
+impl MithrilError {
+    /// The memory errors are retried once in light mode, it needs 2 GB less and has no
+    /// dataset that can mismatch the parameters
+    pub fn recovery(&self, full_memory: bool) -> Recovery {
+        match self {
+            MithrilError::Config(_) => Recovery::Exit,
+            MithrilError::Memory(_) | MithrilError::Dataset(_) if full_memory => {
+                Recovery::LightMode
+            }
+            MithrilError::Memory(_) | MithrilError::Dataset(_) => Recovery::Exit,
+            MithrilError::Stratum(_) | MithrilError::ChannelClosed(_) | MithrilError::Io(_) => {
+                Recovery::Retry
+            }
+        }
+    }
+}
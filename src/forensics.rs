@@ -0,0 +1,260 @@
+//! Captures the full context of the shares the pool rejects: the job with its blob and
+//! target, the nonce, the hash and the timestamps. The hash is computed again with the
+//! memory of the job, so a reject can be told apart as stale, too low a difficulty or
+//! a computation bug. The records are JSON lines in a rotating file.
+
+extern crate crossbeam_channel;
+
+use self::crossbeam_channel::{unbounded, Sender};
+use crate::byte_string;
+use crate::difficulty;
+use crate::logging::{format_timestamp, RotatingFile};
+use crate::pow::PowMemory;
+use crate::stratum::stratum_data::Share;
+use crate::worker::worker_pool;
+use serde_json::json;
+use strum::Display;
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Mutex, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Jobs a rejected share can belong to, older ones are forgotten
+const KEEP_JOBS: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct ForensicsConfig {
+    /// path of the JSON lines file, empty disables the capture
+    pub file: String,
+    pub max_size_mb: u64,
+    pub keep_files: usize,
+    /// compute the hash of a rejected share again
+    pub verify: bool,
+}
+
+/// Why the pool most likely rejected the share
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum RejectCause {
+    /// the hash computed again differs, the miner computed a wrong hash
+    HashMismatch,
+    /// the hash does not meet the target, of the job or of the pool that changed it
+    LowDifficulty,
+    /// the job was replaced before the pool got the share
+    Stale,
+    Duplicate,
+    Unknown,
+}
+
+/// A job as the pool sent it
+#[derive(Clone)]
+pub struct JobContext {
+    pub job_id: String,
+    pub blob: String,
+    pub target: String,
+    pub seed_hash: String,
+    pub height: u64,
+    pub received: SystemTime,
+    /// the memory the job was hashed with, gone once the seed changed
+    pub memory: Weak<dyn PowMemory>,
+}
+
+/// The context of the recent jobs, the rejects are written on a thread of its own
+pub struct Forensics {
+    jobs: Mutex<VecDeque<JobContext>>,
+    sndr: Option<Sender<Reject>>,
+}
+
+struct Reject {
+    job: Option<JobContext>,
+    current: bool,
+    share: Share,
+    err: String,
+    latency: Duration,
+    pool: String,
+    rejected: SystemTime,
+}
+
+impl Forensics {
+    /// Does not write anything if the capture is disabled
+    pub fn disabled() -> Forensics {
+        Forensics {
+            jobs: Mutex::new(VecDeque::new()),
+            sndr: None,
+        }
+    }
+
+    pub fn job(&self, job: JobContext) {
+        if self.sndr.is_none() {
+            return;
+        }
+        if let Ok(mut jobs) = self.jobs.lock() {
+            if jobs.len() == KEEP_JOBS {
+                jobs.pop_front();
+            }
+            jobs.push_back(job);
+        }
+    }
+
+    pub fn share_rejected(&self, share: &Share, err: &str, latency: Duration, pool: &str) {
+        let sndr = match &self.sndr {
+            Some(sndr) => sndr,
+            None => return,
+        };
+        let (job, current) = match self.jobs.lock() {
+            Ok(jobs) => (
+                jobs.iter()
+                    .rev()
+                    .find(|job| job.job_id == share.job_id)
+                    .cloned(),
+                jobs.back().is_some_and(|job| job.job_id == share.job_id),
+            ),
+            Err(_) => (None, false),
+        };
+        //the writer may be gone, the reject is still in the log
+        let _ = sndr.send(Reject {
+            job,
+            current,
+            share: share.clone(),
+            err: err.to_string(),
+            latency,
+            pool: pool.to_string(),
+            rejected: SystemTime::now(),
+        });
+    }
+}
+
+/// Starts the writer thread. Does nothing if the capture is disabled.
+pub fn start(conf: ForensicsConfig) -> Forensics {
+    if conf.file.is_empty() {
+        return Forensics::disabled();
+    }
+    let mut file = match RotatingFile::open(
+        Path::new(&conf.file),
+        conf.max_size_mb * 1024 * 1024,
+        None,
+        conf.keep_files,
+    ) {
+        Ok(file) => file,
+        Err(err) => {
+            error!(
+                "reject forensics disabled, could not open {}: {}",
+                conf.file, err
+            );
+            return Forensics::disabled();
+        }
+    };
+    info!("capturing rejected shares to {}", conf.file);
+
+    let (sndr, rcvr) = unbounded::<Reject>();
+    thread::Builder::new()
+        .name("forensics thread".to_string())
+        .spawn(move || {
+            for reject in rcvr.iter() {
+                let recomputed = match &reject.job {
+                    Some(job) if conf.verify => recompute(job, &reject.share.nonce),
+                    _ => None,
+                };
+                let line = record(&reject, recomputed.as_deref());
+                if let Err(err) = file.write_line(&line) {
+                    error!("writing the reject forensics failed: {}", err);
+                }
+            }
+        })
+        .expect("forensics thread handle");
+    Forensics {
+        jobs: Mutex::new(VecDeque::new()),
+        sndr: Some(sndr),
+    }
+}
+
+/// The hash of the job blob with the nonce, None if the memory of the job is gone
+fn recompute(job: &JobContext, nonce: &str) -> Option<String> {
+    let memory = job.memory.upgrade()?;
+    let input = byte_string::string_to_u8_array(&worker_pool::with_nonce(&job.blob, nonce));
+    Some(byte_string::u8_array_to_string(
+        &memory.hasher().hash(&input),
+    ))
+}
+
+/// The most likely cause. A different hash computed again is a bug of the miner, a hash
+/// that does not meet the job target as well, otherwise the error of the pool decides.
+pub fn classify(
+    err: &str,
+    current_job: bool,
+    hash_difficulty: u64,
+    job_difficulty: u64,
+    hash_matches: Option<bool>,
+) -> RejectCause {
+    let err = err.to_lowercase();
+    if hash_matches == Some(false) {
+        RejectCause::HashMismatch
+    } else if hash_difficulty < job_difficulty {
+        RejectCause::LowDifficulty
+    } else if [
+        "stale",
+        "expired",
+        "job not found",
+        "unknown job",
+        "invalid job",
+    ]
+    .iter()
+    .any(|s| err.contains(s))
+        || !current_job
+    {
+        RejectCause::Stale
+    } else if err.contains("duplicate") {
+        RejectCause::Duplicate
+    } else if err.contains("low difficulty") || err.contains("low diff") {
+        RejectCause::LowDifficulty
+    } else {
+        RejectCause::Unknown
+    }
+}
+
+fn record(reject: &Reject, recomputed: Option<&str>) -> String {
+    let share = &reject.share;
+    let hash_difficulty = difficulty::hash_difficulty(&share.hash);
+    let job_difficulty = reject.job.as_ref().map_or(share.difficulty, |job| {
+        difficulty::job_difficulty(&job.target)
+    });
+    let cause = classify(
+        &reject.err,
+        reject.current,
+        hash_difficulty,
+        job_difficulty,
+        recomputed.map(|hash| byte_string::ct_eq(hash.as_bytes(), share.hash.as_bytes())),
+    );
+    warn!(
+        "share {} of job {} was most likely rejected as {}",
+        share.nonce, share.job_id, cause
+    );
+    let submitted = reject.rejected.checked_sub(reject.latency);
+    json!({
+        "rejected": timestamp(reject.rejected),
+        "submitted": submitted.map(timestamp),
+        "job_received": reject.job.as_ref().map(|job| timestamp(job.received)),
+        "pool": reject.pool,
+        "error": reject.err,
+        "cause": cause.to_string(),
+        "job_id": share.job_id,
+        "current_job": reject.current,
+        "blob": reject.job.as_ref().map(|job| job.blob.as_str()),
+        "target": reject.job.as_ref().map(|job| job.target.as_str()),
+        "seed_hash": reject.job.as_ref().map(|job| job.seed_hash.as_str()),
+        "height": reject.job.as_ref().map(|job| job.height),
+        "nonce": share.nonce,
+        "hash": share.hash,
+        "recomputed_hash": recomputed,
+        "hash_difficulty": hash_difficulty,
+        "job_difficulty": job_difficulty,
+        "latency_ms": reject.latency.as_millis() as u64,
+    })
+    .to_string()
+}
+
+fn timestamp(time: SystemTime) -> String {
+    format_timestamp(time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO))
+}
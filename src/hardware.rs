@@ -0,0 +1,238 @@
+//! The hardware summary logged once at startup and served by `/hardware` of the
+//! management API: CPU, caches, NUMA nodes, huge pages, CPU features, the memory mode
+//! and the cores of the worker threads.
+
+use crate::bench::cpu_info;
+#[cfg(target_os = "linux")]
+use crate::doctor::meminfo_field;
+#[cfg(target_os = "linux")]
+use crate::randomx::pages;
+use crate::worker::worker_pool;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HardwareSummary {
+    pub cpu_model: String,
+    pub physical_cores: usize,
+    pub logical_cores: usize,
+    /// name (`L1d`, `L1i`, `L2`, `L3`) and size of the caches of the first core
+    pub caches: Vec<(String, u64)>,
+    pub numa_nodes: usize,
+    /// reserved and free huge pages, None if unknown
+    pub huge_pages: Option<(u64, u64)>,
+    /// i.e. `madvise`, None if unknown
+    pub transparent_huge_pages: Option<String>,
+    /// the supported ones of the features RandomX benefits from
+    pub cpu_features: Vec<&'static str>,
+    /// `full` or `light`
+    pub memory_mode: String,
+    /// the core each worker thread is pinned to, None without `worker.affinity`
+    pub thread_cores: Vec<Option<usize>>,
+}
+
+/// Reads the summary of this machine
+pub fn probe(memory_mode: &str, threads: u64, affinity: bool) -> HardwareSummary {
+    let cpu = cpu_info();
+    HardwareSummary {
+        thread_cores: thread_cores(threads, affinity, cpu.logical_cores),
+        cpu_model: cpu.model,
+        physical_cores: cpu.physical_cores,
+        logical_cores: cpu.logical_cores,
+        caches: caches(),
+        numa_nodes: numa_nodes(),
+        huge_pages: huge_pages(),
+        transparent_huge_pages: transparent_huge_pages(),
+        cpu_features: cpu_features(),
+        memory_mode: memory_mode.to_string(),
+    }
+}
+
+/// The cores the worker pool pins the threads to
+pub fn thread_cores(threads: u64, affinity: bool, cores: usize) -> Vec<Option<usize>> {
+    (0..threads as usize)
+        .map(|thread| affinity.then(|| worker_pool::affinity_core(thread, cores)))
+        .collect()
+}
+
+/// One line per topic, for the log
+pub fn format_summary(summary: &HardwareSummary) -> Vec<String> {
+    let mut lines = vec![format!(
+        "cpu: {} ({} cores, {} threads)",
+        summary.cpu_model, summary.physical_cores, summary.logical_cores
+    )];
+    if !summary.caches.is_empty() {
+        let caches: Vec<String> = summary
+            .caches
+            .iter()
+            .map(|(name, bytes)| format!("{} {}", name, format_size(*bytes)))
+            .collect();
+        lines.push(format!("caches: {}", caches.join(", ")));
+    }
+    lines.push(format!("numa nodes: {}", summary.numa_nodes));
+    let mut huge_pages = match summary.huge_pages {
+        Some((reserved, free)) => format!("{} reserved, {} free", reserved, free),
+        None => "unknown".to_string(),
+    };
+    if let Some(thp) = &summary.transparent_huge_pages {
+        huge_pages.push_str(&format!(", transparent huge pages {}", thp));
+    }
+    lines.push(format!("huge pages: {}", huge_pages));
+    lines.push(format!(
+        "cpu features: {}",
+        if summary.cpu_features.is_empty() {
+            "none".to_string()
+        } else {
+            summary.cpu_features.join(", ")
+        }
+    ));
+    lines.push(format!("memory mode: {}", summary.memory_mode));
+    let cores: Vec<String> = summary
+        .thread_cores
+        .iter()
+        .enumerate()
+        .map(|(thread, core)| match core {
+            Some(core) => format!("{}->{}", thread, core),
+            None => format!("{}->any", thread),
+        })
+        .collect();
+    lines.push(format!("thread affinity: {}", cores.join(" ")));
+    lines
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 && bytes.is_multiple_of(1024 * 1024) {
+        format!("{} MB", bytes / 1024 / 1024)
+    } else {
+        format!("{} KB", bytes / 1024)
+    }
+}
+
+/// The name of a cache of the sysfs `level` and `type`, i.e. `L1d` for 1 and `Data`
+pub fn cache_name(level: &str, kind: &str) -> String {
+    let suffix = match kind.trim() {
+        "Data" => "d",
+        "Instruction" => "i",
+        _ => "",
+    };
+    format!("L{}{}", level.trim(), suffix)
+}
+
+#[cfg(target_os = "linux")]
+fn caches() -> Vec<(String, u64)> {
+    use crate::wizard::parse_cache_size;
+    let dir = "/sys/devices/system/cpu/cpu0/cache";
+    let read = |index: usize, file: &str| {
+        std::fs::read_to_string(format!("{}/index{}/{}", dir, index, file))
+    };
+    (0..)
+        .map_while(|index| {
+            let level = read(index, "level").ok()?;
+            let kind = read(index, "type").unwrap_or_default();
+            let size = read(index, "size")
+                .ok()
+                .and_then(|size| parse_cache_size(&size));
+            Some(size.map(|size| (cache_name(&level, &kind), size)))
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn caches() -> Vec<(String, u64)> {
+    use crate::platform::sysctl_u64;
+    [
+        ("L1d", "hw.l1dcachesize"),
+        ("L1i", "hw.l1icachesize"),
+        ("L2", "hw.l2cachesize"),
+        ("L3", "hw.l3cachesize"),
+    ]
+    .iter()
+    .filter_map(|(name, key)| {
+        sysctl_u64(key)
+            .filter(|size| *size > 0)
+            .map(|size| (name.to_string(), size))
+    })
+    .collect()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn caches() -> Vec<(String, u64)> {
+    Vec::new()
+}
+
+#[cfg(target_os = "linux")]
+pub fn numa_nodes() -> usize {
+    std::fs::read_dir("/sys/devices/system/node")
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    let name = entry.file_name();
+                    let name = name.to_string_lossy();
+                    name.strip_prefix("node")
+                        .is_some_and(|n| n.parse::<u32>().is_ok())
+                })
+                .count()
+        })
+        .unwrap_or(1)
+        .max(1)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn numa_nodes() -> usize {
+    1
+}
+
+#[cfg(target_os = "linux")]
+fn huge_pages() -> Option<(u64, u64)> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    Some((
+        meminfo_field(&meminfo, "HugePages_Total")?,
+        meminfo_field(&meminfo, "HugePages_Free")?,
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn huge_pages() -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn transparent_huge_pages() -> Option<String> {
+    pages::thp_mode()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn transparent_huge_pages() -> Option<String> {
+    None
+}
+
+#[cfg(target_arch = "x86_64")]
+fn cpu_features() -> Vec<&'static str> {
+    [
+        ("aes", is_x86_feature_detected!("aes")),
+        ("sse4.1", is_x86_feature_detected!("sse4.1")),
+        ("avx", is_x86_feature_detected!("avx")),
+        ("avx2", is_x86_feature_detected!("avx2")),
+        ("avx512f", is_x86_feature_detected!("avx512f")),
+    ]
+    .iter()
+    .filter(|(_, detected)| *detected)
+    .map(|(name, _)| *name)
+    .collect()
+}
+
+#[cfg(target_arch = "aarch64")]
+fn cpu_features() -> Vec<&'static str> {
+    [
+        ("aes", std::arch::is_aarch64_feature_detected!("aes")),
+        ("neon", std::arch::is_aarch64_feature_detected!("neon")),
+    ]
+    .iter()
+    .filter(|(_, detected)| *detected)
+    .map(|(name, _)| *name)
+    .collect()
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn cpu_features() -> Vec<&'static str> {
+    Vec::new()
+}
@@ -0,0 +1,136 @@
+//! `mithril hash`, the hash of a single blob, i.e. to check a share that a pool rejected
+//! or the test vector of another miner. RandomX hashes in light mode, only the cache of
+//! the seed is computed.
+
+use crate::byte_string;
+use crate::difficulty::{hash_difficulty, hash_value, target_value};
+use crate::pow;
+use crate::randomx::memory::VmMemoryAllocator;
+use crate::verify_server::{check_hex, MIN_BLOB_HEX_LEN};
+use crate::worker::worker_pool::with_nonce;
+use serde_json::json;
+
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashConfig {
+    pub algorithm: String,
+    /// the hashing blob, hex encoded
+    pub blob: String,
+    /// replaces the nonce bytes of the blob, 4 bytes hex encoded
+    pub nonce: Option<String>,
+    /// the seed hash of the job, empty for the algorithms without one
+    pub seed_hash: String,
+    /// the block height, for cn/r
+    pub height: u64,
+    /// the job target (4 or 8 bytes) the hash is checked against
+    pub target: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashReport {
+    pub algorithm: &'static str,
+    pub hash: String,
+    /// difficulty the hash would have satisfied
+    pub difficulty: u64,
+    /// the hash meets the target, None without a target
+    pub valid: Option<bool>,
+    /// the memory init, i.e. the RandomX cache of the seed
+    pub init_time: Duration,
+    pub hash_time: Duration,
+}
+
+/// Hashes the blob of the config, an error if the algorithm is unknown or a hex field
+/// is invalid
+pub fn run(conf: &HashConfig) -> Result<HashReport, String> {
+    let mut algorithm = pow::new_algorithm(&conf.algorithm, || VmMemoryAllocator::with_mode(false))
+        .ok_or_else(|| format!("unknown algorithm {}", conf.algorithm))?;
+    check_hex("blob", &conf.blob)?;
+    if let Some(nonce) = &conf.nonce {
+        check_hex("nonce", nonce)?;
+        if nonce.len() != 8 {
+            return Err("nonce has to be 4 bytes".to_string());
+        }
+        if conf.blob.len() < MIN_BLOB_HEX_LEN {
+            return Err(format!(
+                "blob has to be at least {} bytes for a nonce",
+                MIN_BLOB_HEX_LEN / 2
+            ));
+        }
+    }
+    if algorithm.job_hints().seed_hash {
+        check_hex("seed_hash", &conf.seed_hash)?;
+        if conf.seed_hash.len() != 64 {
+            return Err(format!(
+                "{} needs a seed_hash of 32 bytes",
+                algorithm.name()
+            ));
+        }
+    }
+    if let Some(target) = &conf.target {
+        check_hex("target", target)?;
+        if target.len() != 8 && target.len() != 16 {
+            return Err("target has to be 4 or 8 bytes".to_string());
+        }
+    }
+
+    let start = Instant::now();
+    algorithm
+        .prepare(&conf.seed_hash, conf.height)
+        .map_err(|err| err.to_string())?;
+    let init_time = start.elapsed();
+
+    let blob = match &conf.nonce {
+        Some(nonce) => with_nonce(&conf.blob, nonce),
+        None => conf.blob.clone(),
+    };
+    let mut hasher = algorithm.memory().hasher();
+    let start = Instant::now();
+    let hash = hasher.hash(&byte_string::string_to_u8_array(&blob));
+    let hash_time = start.elapsed();
+
+    let hash = byte_string::u8_array_to_string(&hash);
+    Ok(HashReport {
+        algorithm: algorithm.name(),
+        difficulty: hash_difficulty(&hash),
+        valid: conf
+            .target
+            .as_ref()
+            .map(|target| hash_value(&hash) < target_value(target)),
+        hash,
+        init_time,
+        hash_time,
+    })
+}
+
+pub fn format_report(report: &HashReport) -> String {
+    let mut lines = vec![
+        format!("algorithm   {}", report.algorithm),
+        format!("hash        {}", report.hash),
+        format!("difficulty  {}", report.difficulty),
+    ];
+    if let Some(valid) = report.valid {
+        lines.push(format!(
+            "target      {}",
+            if valid { "met" } else { "NOT met" }
+        ));
+    }
+    lines.push(format!(
+        "init time   {:.1}s",
+        report.init_time.as_secs_f64()
+    ));
+    lines.join("\n")
+}
+
+/// The report of `hash --json`, `valid` is null without a target
+pub fn report_json(report: &HashReport) -> serde_json::Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "algorithm": report.algorithm,
+        "hash": report.hash,
+        "difficulty": report.difficulty,
+        "valid": report.valid,
+        "init_seconds": report.init_time.as_secs_f64(),
+        "hash_seconds": report.hash_time.as_secs_f64(),
+    })
+}
@@ -0,0 +1,85 @@
+use crate::cgroup;
+use crate::metric::stats::MinerStats;
+use crate::pow::PowMemory;
+use crate::signals;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegrityConfig {
+    pub enabled: bool,
+    pub interval_minutes: u64,
+    /// dataset items that are computed again on every check
+    pub samples: u64,
+    /// computes the dataset again if it is corrupted
+    pub rebuild: bool,
+}
+
+/// Checks the RandomX dataset of the current job every `interval_minutes`, corrupted
+/// items mean failing RAM or an unstable overclock
+pub fn start(conf: IntegrityConfig, stats: Arc<MinerStats>) {
+    if !conf.enabled {
+        return;
+    }
+    thread::Builder::new()
+        .name("dataset check thread".to_string())
+        .spawn(move || {
+            while !signals::shutdown_requested() {
+                thread::sleep(Duration::from_secs(conf.interval_minutes * 60));
+                if let Some(memory) = stats.memory() {
+                    check_memory(memory.as_ref(), &conf, &stats, seed());
+                }
+            }
+        })
+        .expect("dataset check thread handle");
+}
+
+/// Verifies the memory, counts and logs the errors and rebuilds the dataset if
+/// configured. Returns the number of errors.
+pub fn check_memory(
+    memory: &dyn PowMemory,
+    conf: &IntegrityConfig,
+    stats: &MinerStats,
+    seed: u64,
+) -> u64 {
+    let check = match memory.verify(conf.samples, seed) {
+        Some(check) => check,
+        None => return 0,
+    };
+    let errors = check.errors();
+    if errors == 0 {
+        debug!("dataset check passed, {} items verified", check.samples);
+        return 0;
+    }
+    stats.add_dataset_errors(errors);
+    warn!(
+        "dataset corrupted (checksum {}, {} of {} sampled items differ{}), the RAM may be \
+         failing or the overclock unstable",
+        if check.checksum_matches {
+            "matches"
+        } else {
+            "differs"
+        },
+        check.corrupt.len(),
+        check.samples,
+        match check.corrupt.first() {
+            Some(item) => format!(", i.e. item {}", item),
+            None => "".to_string(),
+        }
+    );
+    if conf.rebuild {
+        info!("rebuilding the dataset");
+        memory.rebuild(cgroup::available_cpus());
+    }
+    errors
+}
+
+/// Another selection of samples for every check
+fn seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos() as u64)
+        .unwrap_or(1)
+}
@@ -1,6 +1,5 @@
 #![crate_name = "mithril"]
 #![crate_type = "lib"]
-#![feature(repr_simd)]
 
 #[macro_use]
 extern crate serde_derive;
@@ -8,11 +7,56 @@ extern crate serde_derive;
 extern crate log;
 extern crate strum;
 
+pub mod analyze;
+pub mod api;
 pub mod bandit_tools;
+pub mod bench;
 pub mod byte_string;
+pub mod cgroup;
+pub mod cli;
+pub mod cluster;
+pub mod completions;
+pub mod config_migration;
+pub mod config_validation;
+pub mod console;
+pub mod control;
+pub mod daemon;
+pub mod dataset_server;
+pub mod difficulty;
+pub mod doctor;
+pub mod dual;
+pub mod earnings;
+pub mod error;
+pub mod forensics;
+pub mod hardware;
+pub mod hash;
+pub mod integrity;
+pub mod logging;
 pub mod metric;
 pub mod mithril_config;
-pub mod randomx;
+pub mod payout;
+pub mod platform;
+pub mod pow;
+pub mod privileges;
+pub mod profile;
+pub mod profit;
+pub mod replay;
+pub mod share_db;
+pub mod signals;
 pub mod stratum;
+pub mod stress;
+pub mod systemd;
 pub mod timer;
+pub mod trace;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod tune;
+pub mod update_check;
+pub mod verify_server;
+pub mod webhook;
+pub mod wallet;
+pub mod wizard;
 pub mod worker;
+
+/// The RandomX implementation is a crate of its own, it can be used without the miner
+pub use mithril_randomx as randomx;
@@ -0,0 +1,404 @@
+extern crate env_logger;
+
+use self::env_logger::filter::{Builder as FilterBuilder, Filter};
+use self::env_logger::fmt::{TimestampPrecision, WriteStyle};
+use crate::console::{ConsoleConfig, TimestampFormat};
+use crate::trace;
+use log::kv::{Error as KvError, Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::{Map, Number, Value as JsonValue};
+
+use std::env;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    /// path of the JSON log file, empty disables JSON logging
+    pub json_file: String,
+    pub json_level: LevelFilter,
+    /// console log level, None uses `RUST_LOG`
+    pub console_level: Option<LevelFilter>,
+    /// the file is rotated when it would grow beyond this size
+    pub max_size_mb: u64,
+    /// the file is rotated after this many hours, 0 disables time based rotation
+    pub rotate_hours: u64,
+    /// number of rotated files that are kept
+    pub keep_files: usize,
+    /// levels of single modules (`stratum`, `randomx`), for the console and the JSON log
+    pub levels: Vec<(String, LevelFilter)>,
+}
+
+/// The events a quiet console prints besides the errors
+pub const QUIET_EVENTS: [&str; 3] = ["share_accepted", "share_rejected", "block_found"];
+
+/// The module levels and the highest level of the console and JSON log without them.
+/// The config reload replaces the module levels.
+static LEVELS: RwLock<Levels> = RwLock::new(Levels {
+    base: LevelFilter::Off,
+    modules: Vec::new(),
+});
+
+struct Levels {
+    base: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+/// Logs to the console as configured with `RUST_LOG` or `console_level` and, if
+/// enabled, additionally as JSON lines to a rotating file. The module levels override
+/// both.
+struct MithrilLogger {
+    /// None if the console is used by the dashboard. The logger writes, the filter
+    /// decides.
+    console: Option<(env_logger::Logger, Filter)>,
+    quiet: bool,
+    json: Option<(LevelFilter, Mutex<RotatingFile>)>,
+}
+
+/// Sets up the global logger. Falls back to console only logging if the JSON log
+/// file cannot be opened. Without `console` only the JSON log is written.
+pub fn init(conf: &LogConfig, console: Option<&ConsoleConfig>) {
+    let mut max_level = LevelFilter::Off;
+    let quiet = console.is_some_and(|console| console.quiet);
+    let console = if let Some(console_conf) = console {
+        let mut filter = FilterBuilder::new();
+        if let Ok(filters) = env::var("RUST_LOG") {
+            filter.parse(&filters);
+        }
+        if let Some(level) = conf.console_level {
+            filter.filter_level(level);
+        }
+        let filter = filter.build();
+        max_level = filter.filter();
+
+        let mut builder = env_logger::Builder::new();
+        if !console_conf.color || env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            builder.write_style(WriteStyle::Never);
+        } else if let Ok(style) = env::var("RUST_LOG_STYLE") {
+            builder.parse_write_style(&style);
+        }
+        builder.format_timestamp(match console_conf.timestamps {
+            TimestampFormat::Off => None,
+            TimestampFormat::Seconds => Some(TimestampPrecision::Seconds),
+            TimestampFormat::Millis => Some(TimestampPrecision::Millis),
+            TimestampFormat::Micros => Some(TimestampPrecision::Micros),
+        });
+        builder.filter_level(LevelFilter::Trace);
+        Some((builder.build(), filter))
+    } else {
+        None
+    };
+
+    let mut json = None;
+    let mut open_err = None;
+    if !conf.json_file.is_empty() {
+        match RotatingFile::open(
+            Path::new(&conf.json_file),
+            conf.max_size_mb * 1024 * 1024,
+            rotate_interval(conf.rotate_hours),
+            conf.keep_files,
+        ) {
+            Ok(file) => {
+                max_level = max_level.max(conf.json_level);
+                json = Some((conf.json_level, Mutex::new(file)));
+            }
+            Err(err) => open_err = Some(err),
+        }
+    }
+
+    log::set_boxed_logger(Box::new(MithrilLogger {
+        console,
+        quiet,
+        json,
+    }))
+    .expect("logger setup");
+    if let Ok(mut levels) = LEVELS.write() {
+        levels.base = max_level;
+    }
+    set_module_levels(&conf.levels);
+    //the pipeline spans are logged as debug records of mithril::span
+    trace::init();
+
+    if let Some(err) = open_err {
+        error!(
+            "JSON logging disabled, could not open {}: {}",
+            conf.json_file, err
+        );
+    }
+}
+
+/// Replaces the levels of the modules, also while logging
+pub fn set_module_levels(modules: &[(String, LevelFilter)]) {
+    if let Ok(mut levels) = LEVELS.write() {
+        levels.modules = modules.to_vec();
+        let modules_max = modules.iter().map(|(_, level)| *level).max();
+        log::set_max_level(modules_max.map_or(levels.base, |max| max.max(levels.base)));
+    }
+}
+
+/// The level of the longest module that names the target. `stratum` names the target
+/// `mithril::stratum::solo`, `randomx` the target `mithril_randomx::vm`.
+pub fn module_level(modules: &[(String, LevelFilter)], target: &str) -> Option<LevelFilter> {
+    let path = target
+        .strip_prefix("mithril::")
+        .or_else(|| target.strip_prefix("mithril_"))
+        .unwrap_or(target);
+    modules
+        .iter()
+        .filter(|(module, _)| {
+            [target, path].iter().any(|t| {
+                t.strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+        })
+        .max_by_key(|(module, _)| module.len())
+        .map(|(_, level)| *level)
+}
+
+/// A quiet console prints only the errors and the share and block events
+pub fn quiet_allows(record: &Record) -> bool {
+    record.level() == log::Level::Error
+        || record
+            .key_values()
+            .get(Key::from_str("event"))
+            .is_some_and(|event| QUIET_EVENTS.iter().any(|e| event.to_string() == *e))
+}
+
+fn configured_level(target: &str) -> Option<LevelFilter> {
+    LEVELS
+        .read()
+        .ok()
+        .and_then(|levels| module_level(&levels.modules, target))
+}
+
+fn rotate_interval(hours: u64) -> Option<Duration> {
+    if hours == 0 {
+        None
+    } else {
+        Some(Duration::from_secs(hours * 60 * 60))
+    }
+}
+
+impl Log for MithrilLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        if let Some(level) = configured_level(metadata.target()) {
+            return metadata.level() <= level && (self.console.is_some() || self.json.is_some());
+        }
+        self.console
+            .as_ref()
+            .map(|(_, filter)| filter.enabled(metadata))
+            .unwrap_or(false)
+            || self
+                .json
+                .as_ref()
+                .map(|(level, _)| metadata.level() <= *level)
+                .unwrap_or(false)
+    }
+
+    fn log(&self, record: &Record) {
+        let module = configured_level(record.target());
+        if let Some((console, filter)) = &self.console {
+            let enabled =
+                module.map_or_else(|| filter.matches(record), |level| record.level() <= level);
+            if enabled && (!self.quiet || quiet_allows(record)) {
+                console.log(record);
+            }
+        }
+        if let Some((level, file)) = &self.json {
+            if record.level() <= module.unwrap_or(*level) {
+                let line = json_line(record, unix_time());
+                if let Ok(mut file) = file.lock() {
+                    //there is nowhere left to report a failing log write
+                    let _ = file.write_line(&line);
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some((console, _)) = &self.console {
+            console.flush();
+        }
+    }
+}
+
+/// Formats the record as one JSON object. The `event` key-value (`log` if missing)
+/// names the event type, all other key-values are added as fields.
+pub fn json_line(record: &Record, timestamp: Duration) -> String {
+    let mut fields = Map::new();
+    fields.insert(
+        "timestamp".to_string(),
+        JsonValue::String(format_timestamp(timestamp)),
+    );
+    fields.insert(
+        "level".to_string(),
+        JsonValue::String(record.level().to_string()),
+    );
+    fields.insert(
+        "target".to_string(),
+        JsonValue::String(record.target().to_string()),
+    );
+    if let Some(name) = thread::current().name() {
+        fields.insert("thread".to_string(), JsonValue::String(name.to_string()));
+    }
+    fields.insert("event".to_string(), JsonValue::String("log".to_string()));
+    fields.insert(
+        "message".to_string(),
+        JsonValue::String(record.args().to_string()),
+    );
+
+    let mut visitor = JsonVisitor {
+        fields: &mut fields,
+    };
+    //the visitor never fails
+    let _ = record.key_values().visit(&mut visitor);
+
+    JsonValue::Object(fields).to_string()
+}
+
+struct JsonVisitor<'a> {
+    fields: &'a mut Map<String, JsonValue>,
+}
+
+impl<'kvs> VisitSource<'kvs> for JsonVisitor<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), KvError> {
+        self.fields
+            .insert(key.as_str().to_string(), to_json_value(&value));
+        Ok(())
+    }
+}
+
+fn to_json_value(value: &Value) -> JsonValue {
+    if let Some(v) = value.to_bool() {
+        return JsonValue::Bool(v);
+    }
+    if let Some(v) = value.to_u64() {
+        return JsonValue::Number(v.into());
+    }
+    if let Some(v) = value.to_i64() {
+        return JsonValue::Number(v.into());
+    }
+    if let Some(v) = value.to_f64().and_then(Number::from_f64) {
+        return JsonValue::Number(v);
+    }
+    JsonValue::String(value.to_string())
+}
+
+/// RFC 3339 UTC timestamp with milliseconds
+pub fn format_timestamp(unix: Duration) -> String {
+    let secs = unix.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let rem = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        unix.subsec_millis()
+    )
+}
+
+/// Converts days since the unix epoch to year, month and day (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn unix_time() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+}
+
+/// Append-only file that is rotated by size and age. Rotated files get the suffixes
+/// `.1` (newest) to `.<keep_files>` (oldest), older ones are deleted.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    rotate_interval: Option<Duration>,
+    keep_files: usize,
+    file: File,
+    written: u64,
+    opened_at: Instant,
+}
+
+impl RotatingFile {
+    pub fn open(
+        path: &Path,
+        max_bytes: u64,
+        rotate_interval: Option<Duration>,
+        keep_files: usize,
+    ) -> io::Result<RotatingFile> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            max_bytes,
+            rotate_interval,
+            keep_files,
+            file,
+            written,
+            opened_at: Instant::now(),
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        let too_big = self.written > 0 && self.written + len > self.max_bytes;
+        let too_old = self
+            .rotate_interval
+            .map(|interval| self.opened_at.elapsed() >= interval)
+            .unwrap_or(false);
+        if too_big || too_old {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.written += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated_path(self.keep_files));
+            for n in (1..self.keep_files).rev() {
+                let from = self.rotated_path(n);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+}
@@ -2,41 +2,216 @@
 extern crate log;
 
 extern crate bandit;
+extern crate clap;
 extern crate crossbeam_channel;
-extern crate env_logger;
 extern crate mithril;
 
-use self::crossbeam_channel::{select, unbounded, Receiver};
+use self::clap::Parser;
+use self::crossbeam_channel::{never, select, tick, unbounded, Receiver, Sender};
+use log::LevelFilter;
+use mithril::analyze::{self, AnalyzeConfig};
+use mithril::api;
 use mithril::bandit_tools;
+use mithril::bench;
+use mithril::bench::{BenchConfig, BenchResults};
+use mithril::cgroup;
+use mithril::cluster;
+use mithril::completions;
+use mithril::cli::{
+    BenchArgs, Cli, Command, CompletionsArgs, CoordinatorArgs, DatasetServerArgs, DoctorArgs,
+    HashArgs, InitArgs, ProxyArgs, StressArgs, TuneArgs, VerifyServerArgs,
+};
+use mithril::config_migration;
+use mithril::config_validation;
+use mithril::config_validation::{ConfigIssue, ConfigSource, Severity};
+use mithril::console::{self, ConsoleConfig};
+use mithril::control::{self, ControlCmd};
+use mithril::daemon;
+use mithril::daemon::Pidfile;
+use mithril::dataset_server;
+use mithril::difficulty;
+use mithril::doctor;
+use mithril::dual;
+use mithril::earnings;
+use mithril::error::{MithrilError, Recovery};
+use mithril::forensics::{self, Forensics, JobContext};
+use mithril::hardware;
+use mithril::hash;
+use mithril::integrity;
+use mithril::logging;
 use mithril::metric;
+use mithril::metric::lifetime;
+use mithril::metric::otlp;
+use mithril::metric::push;
+use mithril::metric::stats::{MinerStats, ShareOutcome, ShareRecord};
+use mithril::metric::telemetry::TelemetrySampler;
 use mithril::mithril_config;
+use mithril::mithril_config::{ConfigFormat, MithrilConfig};
+use mithril::payout::{self, PayoutSplit};
+use mithril::pow::randomx::{self, MemoryMode, RandomX};
+use mithril::pow::{self, PowAlgorithm, PowMemory};
+use mithril::privileges;
+use mithril::profile;
+use mithril::profit;
+use mithril::replay::{self, JobRecorder};
 use mithril::randomx::memory::VmMemoryAllocator;
-use mithril::stratum::{StratumAction, StratumClient};
+use mithril::share_db;
+use mithril::share_db::{ConnectionEvent, HistoryEvent, ShareDb};
+use mithril::signals;
+use mithril::stratum::proxy;
+use mithril::stratum;
+use mithril::stratum::stratum_data::{ClusterControl, HashrateReport, Job, PoolConfig, Share};
+use mithril::stratum::{PoolClient, StratumAction, StratumCmd, StratumError};
+use mithril::stress;
+use mithril::systemd::Notifier;
 use mithril::timer;
+use mithril::tune;
+use mithril::update_check;
+use mithril::verify_server;
+use mithril::webhook;
+use mithril::webhook::{AlertEvent, Alerter};
+use mithril::wizard;
 use mithril::worker::worker_pool;
 use mithril::worker::worker_pool::WorkerPool;
+use std::fs;
 use std::io;
-use std::io::Error;
+use std::io::IsTerminal;
 use std::path::Path;
+use std::process;
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Upper bound for the number of hashes a worker accumulates before reporting them, so
+/// that the statistics stay current even if the metric file is disabled.
+const STATS_HASH_RESOLUTION: u64 = 100;
+/// How often waits outside of the main loop check for a shutdown
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the primary pool is probed while a backup pool is mined
+const FAIL_BACK_INTERVAL: Duration = Duration::from_secs(60);
+/// How often the main loop looks if the dataset init is done, for the ready notification
+const READY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How long `--replay` hashes the last job of the recording
+const REPLAY_TAIL: Duration = Duration::from_secs(10);
 
 use bandit::MultiArmedBandit;
 
+type Bandit = bandit::softmax::AnnealingSoftmax<bandit_tools::ThreadArm>;
+
 #[derive(Debug, PartialEq)]
 enum MainLoopExit {
     DrawNewBanditArm,
     DonationHashing,
+    Pause,
+    Reconnect,
+    Retune,
+    /// the payout split moved on to another wallet, the pool is logged in with it
+    RotatePayout,
+    /// a management command changed the pool or worker config
+    Reconfigure(ControlCmd),
+    Shutdown,
+}
+
+/// Receivers of the pool and share events of the main event loop
+struct Reporters<'a> {
+    stats: &'a MinerStats,
+    alerter: &'a Alerter,
+    share_db: &'a ShareDb,
+    forensics: &'a Forensics,
+    notifier: &'a Notifier,
+    /// the hashrate reports of an agent, None if there is no coordinator
+    coordinator: Option<&'a Sender<StratumCmd>>,
+    /// the fleet config the config was read with, another one of the coordinator is applied
+    fleet_config: Option<&'a str>,
+    /// the primary pool while a backup pool is mined, the session ends once it is
+    /// reachable again
+    fail_back: Option<&'a PoolConfig>,
+    /// the pool is a monerod, each accepted share is a block
+    solo: bool,
+    /// the payout split the pool login uses, None if it mines another wallet
+    payout: Option<&'a PayoutSplit>,
+    /// writes the jobs for `--replay`, None without `--record`
+    recorder: Option<&'a JobRecorder>,
 }
 
 #[allow(clippy::unnecessary_unwrap)]
 fn main() {
-    env_logger::init();
+    let cli = Cli::parse();
+    match &cli.command {
+        Some(Command::Bench(args)) => return run_bench(args),
+        Some(Command::Stress(args)) => return run_stress(args),
+        Some(Command::Hash(args)) => return run_hash(args),
+        Some(Command::Tune(args)) => return run_tune(&cli, args),
+        Some(Command::Init(args)) => return run_init(args),
+        Some(Command::Doctor(args)) => return run_doctor(&cli, args),
+        Some(Command::VerifyServer(args)) => return run_verify_server(&cli, args),
+        Some(Command::DatasetServer(args)) => return run_dataset_server(&cli, args),
+        Some(Command::Proxy(args)) => return run_proxy(&cli, args),
+        Some(Command::Coordinator(args)) => return run_coordinator(&cli, args),
+        Some(Command::Completions(args)) => return run_completions(args),
+        Some(Command::Manpage) => return run_manpage(),
+        None => {}
+    }
+    if let Some(path) = &cli.replay {
+        return run_replay(&cli, path);
+    }
+    if !cli.config.exists() {
+        write_first_config(&cli);
+    }
 
     //Read config
-    let cwd_path = &format!("{}{}", "./", mithril_config::CONFIG_FILE_NAME);
-    let config =
-        mithril_config::read_config(Path::new(cwd_path), mithril_config::CONFIG_FILE_NAME).unwrap();
+    let (mut config, config_warnings) = match read_config(&cli, None) {
+        Ok(config) => config,
+        Err(problems) => {
+            eprintln!("{}", problems);
+            process::exit(1);
+        }
+    };
+
+    //before any thread is started, the fork only keeps the calling thread
+    let _pidfile = match start_daemon(&cli) {
+        Ok(pidfile) => pidfile,
+        Err(err) => {
+            eprintln!("could not start mithril: {}", err);
+            process::exit(1);
+        }
+    };
+
+    let tui = cli.tui;
+    let console = (!tui).then_some(&config.console_conf);
+    logging::init(&config.log_conf, console);
+    if cli.daemon {
+        info!("running as daemon with pid {}", process::id());
+    }
+    for warning in &config_warnings {
+        warn!("{}", warning);
+    }
+    let recorder = cli.record.as_ref().map(|path| match JobRecorder::create(path) {
+        Ok(recorder) => {
+            info!("recording the jobs to {}", path.display());
+            recorder
+        }
+        Err(err) => {
+            error!("could not create {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    });
+
+    //after the pidfile and the log files are opened, before any connection or thread
+    match privileges::drop_privileges(&config.privilege_conf) {
+        Ok(Some(account)) => info!(
+            "dropped root privileges, running as {} (uid {}, gid {})",
+            account.user, account.uid, account.gid
+        ),
+        Ok(None) if privileges::is_root() => {
+            warn!("running as root, set privileges.user to mine as an unprivileged user")
+        }
+        Ok(None) => {}
+        Err(err) => {
+            error!("could not drop root privileges: {}", err);
+            process::exit(1);
+        }
+    }
 
     if config.donation_conf.percentage > 0.0 {
         print_donation_hint(config.donation_conf.percentage);
@@ -50,64 +225,240 @@ fn main() {
         None
     };
 
-    let timer_rcvr = timer::setup(&config.worker_conf, &config.donation_conf);
+    let stats = Arc::new(MinerStats::new());
+    let full_memory = randomx::memory_mode().full_memory(cgroup::limits().fits_full_memory());
+    let hardware = hardware::probe(
+        if full_memory { "full" } else { "light" },
+        config.worker_conf.num_threads,
+        config.worker_conf.affinity,
+    );
+    for line in hardware::format_summary(&hardware) {
+        info!("{}", line);
+    }
+    stats.set_hardware(hardware);
+    if let Err(err) = bandit_tools::ensure_mithril_folder_exists() {
+        error!("could not create folder for state file {:?}", err);
+    }
+    lifetime::start(stats.clone(), lifetime::lifetime_stats_file());
+    push::start(config.push_conf.clone(), stats.clone());
+    otlp::start(config.otlp_conf.clone(), stats.clone());
+    let (control_sndr, control_rcvr) = unbounded();
+    signals::start(stats.clone(), control_sndr.clone());
+    let alerter = webhook::start(config.webhook_conf.clone(), stats.clone());
+    let share_db = share_db::start(config.share_db_conf.clone());
+    let forensics = forensics::start(config.forensics_conf.clone());
+    if tui {
+        start_tui(
+            stats.clone(),
+            config.worker_conf.auto_tune,
+            control_sndr.clone(),
+        );
+    } else {
+        console::start(
+            stats.clone(),
+            config.worker_conf.auto_tune,
+            config.console_conf.hashrate,
+            control_sndr.clone(),
+        );
+    }
+    profit::start(
+        config.profit_conf.clone(),
+        config.coins.clone(),
+        &config.pool_conf.pool_address,
+        control_sndr.clone(),
+    );
+    earnings::start(config.earnings_conf.clone(), stats.clone());
+    update_check::start(config.update_check_conf.clone());
+    integrity::start(config.integrity_conf.clone(), stats.clone());
+    api::start(config.api_conf.clone(), stats.clone(), control_sndr);
+
+    let notifier = Notifier::from_env();
+    if let Some(interval) = notifier.watchdog_interval() {
+        info!(
+            "notifying the systemd watchdog every {}s",
+            interval.as_secs_f64()
+        );
+    }
+
+    let mut timer_rcvr = timer::setup(&config.worker_conf, &config.donation_conf);
     let mut donation_hashing = false;
-    let mut vm_memory_allocator = VmMemoryAllocator::initial();
+    let mut payout = PayoutSplit::new(config.payout_wallets.clone());
+    //the memory of the algorithm is kept while the pools use the same one
+    let mut algorithm: Box<dyn PowAlgorithm> =
+        pow::new_algorithm(&config.pools()[0].algorithm, memory_allocator)
+            .unwrap_or_else(|| Box::new(RandomX::new(memory_allocator())));
+    //the dual pool keeps its own connection, the primary pool only pauses it
+    let dual_miner = config.dual_pool.clone().map(|dual_conf| {
+        dual::start(
+            dual_conf,
+            &config.worker_conf,
+            config.metric_conf.resolution.min(STATS_HASH_RESOLUTION),
+            memory_allocator,
+        )
+    });
+    //index into config.pools(), 0 is the primary pool
+    let mut pool_ix = 0;
+    let mut exit_code = 0;
 
     loop {
+        if signals::shutdown_requested() {
+            break;
+        }
+
         //Stratum start
         let (stratum_sndr, stratum_rcvr) = unbounded();
         let (client_err_sndr, client_err_rcvr) = unbounded();
 
-        let conf = if donation_hashing {
+        let mut conf = if donation_hashing {
             mithril_config::donation_conf()
         } else {
-            config.pool_conf.clone()
+            config.pools()[pool_ix].clone()
         };
+        //the pools that mine the wallet of [pool], the wallet of an agent only names it
+        let payout_split = payout.as_ref().filter(|_| {
+            !donation_hashing
+                && config.cluster_conf.coordinator.is_none()
+                && conf.wallet_address == config.pool_conf.wallet_address
+        });
+        if let Some(split) = payout_split {
+            conf.wallet_address = split.current().to_string();
+        }
 
-        let login_result = StratumClient::login(conf, client_err_sndr, stratum_sndr);
-        if login_result.is_err() {
-            error!("stratum login failed {:?}", login_result.err());
-            await_timeout();
-            continue;
+        if algorithm.name() != conf.algorithm {
+            match pow::new_algorithm(&conf.algorithm, memory_allocator) {
+                Some(pool_algorithm) => {
+                    info!("switching from {} to {}", algorithm.name(), conf.algorithm);
+                    algorithm = pool_algorithm;
+                }
+                None => error!(
+                    "algorithm {} is not supported, mining {}",
+                    conf.algorithm,
+                    algorithm.name()
+                ),
+            }
         }
-        let client = login_result.expect("stratum client");
+        let pool_address = conf.pool_address.clone();
+        let nicehash = conf.nicehash;
+        let solo = conf.daemon;
+        notifier.status(&format!("connecting to {}", pool_address));
+        let client = match PoolClient::login(conf, client_err_sndr, stratum_sndr) {
+            Ok(client) => client,
+            Err(err) => {
+                error!(event = "pool_login_failed"; "stratum login failed: {}", err);
+                alerter.fire(
+                    AlertEvent::PoolDisconnect,
+                    format!("stratum login failed: {}", err),
+                );
+                share_db.record(HistoryEvent::Connection {
+                    time: SystemTime::now(),
+                    event: ConnectionEvent::LoginFailed,
+                    pool: pool_address,
+                    detail: err.to_string(),
+                });
+                fail_over(&mut pool_ix, &config, donation_hashing, &notifier);
+                continue;
+            }
+        };
+        stats.connected(&pool_address);
+        share_db.record(HistoryEvent::Connection {
+            time: SystemTime::now(),
+            event: ConnectionEvent::Connected,
+            pool: pool_address.clone(),
+            detail: String::new(),
+        });
         let share_sndr = client.new_cmd_channel();
         let (arm, num_threads) = if bandit.is_some() {
             let selected_arm = bandit.as_ref().unwrap().select_arm();
-            info!("trying arm with {} #threads", selected_arm.num_threads);
+            info!(
+                event = "auto_tune_arm", num_threads = selected_arm.num_threads;
+                "trying arm with {} #threads", selected_arm.num_threads
+            );
             (Some(selected_arm), selected_arm.num_threads)
         } else {
             (None, config.worker_conf.num_threads)
         };
 
         let (metric_sndr, metric_rcvr) = unbounded();
-        let metric = metric::start(config.metric_conf.clone(), metric_rcvr);
+        let metric = metric::start(config.metric_conf.clone(), metric_rcvr, stats.clone());
+        stats.set_thread_count(num_threads as usize);
 
         //worker pool start
         let mut pool = worker_pool::start(
             num_threads,
+            &config.worker_conf,
             &share_sndr,
-            config.metric_conf.resolution,
+            config.metric_conf.resolution.min(STATS_HASH_RESOLUTION),
             &metric_sndr.clone(),
-            vm_memory_allocator,
+            algorithm,
+            memory_allocator,
         );
 
-        let term_result =
-            start_main_event_loop(&mut pool, &client_err_rcvr, &stratum_rcvr, &timer_rcvr);
+        let term_result = start_main_event_loop(
+            &mut pool,
+            &client_err_rcvr,
+            &stratum_rcvr,
+            &timer_rcvr,
+            &control_rcvr,
+            nicehash,
+            &Reporters {
+                stats: &stats,
+                alerter: &alerter,
+                share_db: &share_db,
+                forensics: &forensics,
+                notifier: &notifier,
+                coordinator: config
+                    .cluster_conf
+                    .coordinator
+                    .as_ref()
+                    .map(|_| &share_sndr),
+                fleet_config: config.cluster_conf.fleet_config.as_deref(),
+                fail_back: (pool_ix != 0 && !donation_hashing).then(|| config.pools()[0]),
+                solo,
+                payout: payout_split,
+                recorder: recorder.as_ref(),
+            },
+        );
 
-        vm_memory_allocator = pool.vm_memory_allocator.clone();
+        algorithm = pool.algorithm.clone_box();
         pool.stop();
-        client.stop();
+        if let Err(err) = client.stop() {
+            warn!("stratum client did not stop cleanly: {}", err);
+        }
+        stats.disconnected();
+        share_db.record(HistoryEvent::Connection {
+            time: SystemTime::now(),
+            event: ConnectionEvent::Disconnected,
+            pool: pool_address,
+            detail: match &term_result {
+                Err(err) => err.to_string(),
+                Ok(ex) => format!("{:?}", ex),
+            },
+        });
 
         match term_result {
-            Err(err) => {
-                error!(
-                    "error received, restarting connection after 60 seconds. err was {}",
-                    err
-                );
-                await_timeout();
-            }
+            Err(err) => match err.recovery(algorithm.full_memory()) {
+                Recovery::Retry => {
+                    error!(
+                        event = "pool_disconnect";
+                        "error received, reconnecting. err was {}",
+                        err
+                    );
+                    alerter.fire(
+                        AlertEvent::PoolDisconnect,
+                        format!("connection lost: {}", err),
+                    );
+                    fail_over(&mut pool_ix, &config, donation_hashing, &notifier);
+                }
+                Recovery::LightMode => {
+                    error!("{}, continuing in the slow light mode", err);
+                    algorithm.light_mode();
+                }
+                Recovery::Exit => {
+                    error!("{}, stopping", err);
+                    exit_code = 1;
+                    break;
+                }
+            },
             Ok(ex) => {
                 info!("main loop exit, next loop {:?}", ex);
                 pool.join();
@@ -116,28 +467,837 @@ fn main() {
                 let hashes = metric.hash_count();
                 metric.join();
 
-                if arm.is_some() && bandit.is_some() && !donation_hashing {
+                let arm_completed =
+                    ex == MainLoopExit::DrawNewBanditArm || ex == MainLoopExit::DonationHashing;
+                if arm.is_some() && bandit.is_some() && !donation_hashing && arm_completed {
                     //do not save reward for donation hashing, it probably only runs for a short period
                     let bandit_ref = bandit.as_mut().unwrap();
                     let reward = (hashes as f64
                         / (config.worker_conf.auto_tune_interval_minutes as f64 * 60.0))
                         / 1000.0; /*kH/s*/
-                    info!("adding reward {:?} for arm {:?}", reward, arm);
+                    info!(
+                        event = "auto_tune_reward", reward = reward;
+                        "adding reward {:?} for arm {:?}", reward, arm
+                    );
                     bandit_ref.update(arm.unwrap(), reward);
                     save_bandit_state(bandit_ref);
                 }
 
-                donation_hashing = ex == MainLoopExit::DonationHashing;
+                let timing = timer_settings(&config);
+                match ex {
+                    MainLoopExit::DrawNewBanditArm | MainLoopExit::Retune => {
+                        donation_hashing = false
+                    }
+                    MainLoopExit::DonationHashing => donation_hashing = true,
+                    MainLoopExit::Pause => {
+                        if let Some(dual_miner) = &dual_miner {
+                            dual_miner.pause();
+                        }
+                        let resumed = await_resume(
+                            &control_rcvr,
+                            &stats,
+                            &notifier,
+                            &cli,
+                            &mut config,
+                            &mut bandit,
+                        );
+                        if !resumed {
+                            break;
+                        }
+                        if let Some(dual_miner) = &dual_miner {
+                            dual_miner.resume();
+                        }
+                        pool_ix = 0;
+                    }
+                    MainLoopExit::Reconnect => pool_ix = 0,
+                    MainLoopExit::RotatePayout => {}
+                    MainLoopExit::Reconfigure(cmd) => {
+                        reconfigure(cmd, &cli, &mut config, &mut bandit);
+                        pool_ix = 0;
+                    }
+                    MainLoopExit::Shutdown => break,
+                }
+                let wallets = payout.as_ref().map_or(&[][..], |split| split.wallets());
+                if wallets != config.payout_wallets.as_slice() {
+                    //a reloaded config starts the split over
+                    payout = PayoutSplit::new(config.payout_wallets.clone());
+                }
+                if timer_settings(&config) != timing {
+                    //the old clock stops with its next tick
+                    timer_rcvr = timer::setup(&config.worker_conf, &config.donation_conf);
+                }
             }
         }
     }
+
+    //the workers are stopped, a signal or the dashboard asked to exit
+    notifier.stopping();
+    if let Some(dual_miner) = dual_miner {
+        dual_miner.stop();
+    }
+    lifetime::save(&stats, &lifetime::lifetime_stats_file());
+    if tui {
+        stop_tui();
+    }
+    console::restore_terminal();
+    info!("shut down");
+    if exit_code != 0 {
+        process::exit(exit_code);
+    }
 }
 
-fn await_timeout() {
-    thread::sleep(Duration::from_secs(60))
+/// Offline benchmark, runs without a config and a pool
+fn run_bench(args: &BenchArgs) {
+    let conf = args.config();
+    if !conf.algorithms.is_empty() {
+        return run_algorithm_bench(&conf, args.json);
+    }
+    progress(
+        args.json,
+        &format!(
+            "initializing dataset, then hashing with {} threads for {}s",
+            conf.threads,
+            conf.duration.as_secs()
+        ),
+    );
+    let report = bench::run(&conf);
+    if args.json {
+        println!("{}", bench::report_json(&report));
+    } else {
+        println!("{}", bench::format_report(&report));
+    }
+    if !report.verified {
+        process::exit(1);
+    }
 }
 
-fn save_bandit_state(bandit: &mut bandit::softmax::AnnealingSoftmax<bandit_tools::ThreadArm>) {
+/// Benchmarks every algorithm of the config and stores the hashrates
+fn run_algorithm_bench(conf: &BenchConfig, json: bool) {
+    let path = bench::bench_results_file();
+    let mut results = match BenchResults::load(&path) {
+        Ok(results) => results,
+        Err(err) => {
+            eprintln!("could not read {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    };
+    let mut reports = Vec::new();
+    for name in &conf.algorithms {
+        let algorithm = match pow::new_algorithm(name, memory_allocator) {
+            Some(algorithm) => algorithm,
+            None => continue,
+        };
+        progress(
+            json,
+            &format!(
+                "benchmarking {} with {} threads for {}s",
+                name,
+                conf.threads,
+                conf.duration.as_secs()
+            ),
+        );
+        match bench::run_algorithm(algorithm, conf) {
+            Ok(report) => {
+                results.add(&report);
+                reports.push(report);
+            }
+            Err(err) => eprintln!("benchmarking {} failed: {}", name, err),
+        }
+    }
+    if json {
+        println!("{}", bench::algorithm_reports_json(&bench::cpu_info(), &reports));
+    } else {
+        println!("{}", bench::format_algorithm_reports(&bench::cpu_info(), &reports));
+    }
+    let saved = bandit_tools::ensure_mithril_folder_exists().and_then(|_| results.save(&path));
+    match saved {
+        Ok(()) => progress(json, &format!("hashrates saved to {}", path.display())),
+        Err(err) => {
+            eprintln!("could not save the hashrates to {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Offline stress test that verifies the hashes, for checking RAM and overclocking settings
+fn run_stress(args: &StressArgs) {
+    let conf = args.config();
+    println!(
+        "initializing dataset, then hashing with {} threads for {}s, checking every {}s",
+        conf.threads,
+        conf.duration.as_secs(),
+        conf.interval.as_secs()
+    );
+    let report = stress::run(&conf);
+    println!("{}", stress::format_report(&report));
+    if !report.passed() {
+        process::exit(1);
+    }
+}
+
+/// Ranks the thread counts, affinity and huge page settings by their hashrate
+fn run_tune(cli: &Cli, args: &TuneArgs) {
+    let conf = args.config();
+    let candidates = tune::candidates(&conf.threads).len() as u64;
+    progress(
+        args.json,
+        &format!(
+            "initializing dataset, then hashing {} combinations for {}s each (about {} minutes)",
+            candidates,
+            conf.duration.as_secs(),
+            (candidates * conf.duration.as_secs()).div_ceil(60)
+        ),
+    );
+    let results = tune::run(&conf, |result| progress(args.json, &tune::format_result(result)));
+    let written = match results.first() {
+        Some(best) if args.write => {
+            match tune::write_config(&cli.config, cli.config_format(), &best.candidate) {
+                Ok(()) => Some(cli.config.as_path()),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    process::exit(1);
+                }
+            }
+        }
+        _ => None,
+    };
+    if args.json {
+        println!("{}", tune::results_json(&results, written));
+        return;
+    }
+    println!("{}", tune::format_table(&results));
+    if written.is_some() {
+        println!("fastest settings written to {}", cli.config.display());
+    } else if !results.is_empty() {
+        println!("run with --write to save the fastest settings to {}", cli.config.display());
+    }
+}
+
+/// A progress line, on stderr if stdout is reserved for the JSON output
+fn progress(json: bool, line: &str) {
+    if json {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// Hashes one blob, runs without a config and a pool
+fn run_hash(args: &HashArgs) {
+    let report = match hash::run(&args.config()) {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+    if args.json {
+        println!("{}", hash::report_json(&report));
+    } else {
+        println!("{}", hash::format_report(&report));
+    }
+    if report.valid == Some(false) {
+        process::exit(1);
+    }
+}
+
+/// Mines the jobs of a `--record` file with the workers of the config, without a pool
+fn run_replay(cli: &Cli, path: &Path) {
+    let records = match replay::read_records(path) {
+        Ok(records) => records,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+    let config = match read_config(cli, None) {
+        Ok((config, _)) => config,
+        Err(problems) => {
+            eprintln!("{}", problems);
+            process::exit(1);
+        }
+    };
+    logging::init(&config.log_conf, Some(&config.console_conf));
+    info!("replaying {} jobs of {}", records.len(), path.display());
+    let algorithm = pow::new_algorithm(&config.pools()[0].algorithm, memory_allocator)
+        .unwrap_or_else(|| Box::new(RandomX::new(memory_allocator())));
+    let (share_sndr, share_rcvr) = unbounded();
+    let (metric_sndr, _metric_rcvr) = unbounded();
+    let mut pool = worker_pool::start(
+        config.worker_conf.num_threads,
+        &config.worker_conf,
+        &share_sndr,
+        config.metric_conf.resolution.min(STATS_HASH_RESOLUTION),
+        &metric_sndr,
+        algorithm,
+        memory_allocator,
+    );
+    let result = replay::replay(&records, &mut pool, &share_rcvr, REPLAY_TAIL);
+    pool.stop();
+    pool.join();
+    match result {
+        Ok(summary) => println!("{}", replay::format_summary(&summary)),
+        Err(err) => {
+            eprintln!("replay failed: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Preflight checks for a low hashrate or a miner that does not start, with `--analyze` a
+/// short benchmark and the likely culprits of a low hashrate
+fn run_doctor(cli: &Cli, args: &DoctorArgs) {
+    let config = read_config(cli, None);
+    let threads = match &config {
+        Ok((config, _)) if !config.worker_conf.auto_tune => config.worker_conf.num_threads,
+        _ => cgroup::available_cpus() as u64,
+    };
+    if args.analyze {
+        let conf = match &config {
+            Ok((config, _)) => AnalyzeConfig {
+                threads,
+                affinity: config.worker_conf.affinity,
+                memory_mode: config.memory_conf.mode,
+                duration: Duration::from_secs(args.duration),
+            },
+            Err(_) => AnalyzeConfig {
+                threads,
+                affinity: false,
+                memory_mode: MemoryMode::Auto,
+                duration: Duration::from_secs(args.duration),
+            },
+        };
+        progress(
+            args.json,
+            &format!(
+                "analyzing {} threads for {}s after the dataset init",
+                conf.threads, args.duration
+            ),
+        );
+        let analysis = analyze::analyze(&analyze::run(&conf));
+        if args.json {
+            println!("{}", serde_json::to_string(&analysis).expect("analysis json"));
+        } else {
+            println!("{}", analyze::format_analysis(&analysis));
+        }
+        return;
+    }
+    let mut checks = doctor::system_checks(threads);
+    match &config {
+        Ok((config, _)) => checks.extend(config.pools().into_iter().map(doctor::pool_check)),
+        Err(err) => checks.push(doctor::config_check(&err.to_string())),
+    }
+    if args.json {
+        println!("{}", doctor::report_json(&checks));
+    } else {
+        println!("{}", doctor::format_report(&checks));
+    }
+    if doctor::has_failures(&checks) {
+        process::exit(1);
+    }
+}
+
+fn run_completions(args: &CompletionsArgs) {
+    print!("{}", completions::completion_script(args.shell));
+}
+
+fn run_manpage() {
+    print!("{}", completions::manpage());
+}
+
+/// The console of the subcommands that run without a config
+fn console_config(cli: &Cli) -> ConsoleConfig {
+    let mut console = ConsoleConfig::default();
+    cli.apply_console(&mut console);
+    console
+}
+
+/// Share validator for pool backends, runs without a config and a pool
+fn run_verify_server(cli: &Cli, args: &VerifyServerArgs) {
+    logging::init(
+        &logging::LogConfig {
+            json_file: String::new(),
+            json_level: LevelFilter::Off,
+            console_level: Some(cli.log_level.unwrap_or(LevelFilter::Info)),
+            max_size_mb: 0,
+            rotate_hours: 0,
+            keep_files: 0,
+            levels: Vec::new(),
+        },
+        Some(&console_config(cli)),
+    );
+    if let Err(err) = verify_server::run(&args.config()) {
+        error!("verify server stopped: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run_dataset_server(cli: &Cli, args: &DatasetServerArgs) {
+    logging::init(
+        &logging::LogConfig {
+            json_file: String::new(),
+            json_level: LevelFilter::Off,
+            console_level: Some(cli.log_level.unwrap_or(LevelFilter::Info)),
+            max_size_mb: 0,
+            rotate_hours: 0,
+            keep_files: 0,
+            levels: Vec::new(),
+        },
+        Some(&console_config(cli)),
+    );
+    //registers the [[randomx]] parameter sets of the miners, if there is a config
+    if let Err(err) = read_config(cli, None) {
+        info!("no custom RandomX parameters, {}", err);
+    }
+    if let Err(err) = dataset_server::run(&args.config()) {
+        error!("dataset server stopped: {}", err);
+        process::exit(1);
+    }
+}
+
+/// Stratum proxy for the `[pool]` of the config, the miners of a farm connect to it
+fn run_proxy(cli: &Cli, args: &ProxyArgs) {
+    let (config, config_warnings) = match read_config(cli, None) {
+        Ok(config) => config,
+        Err(problems) => {
+            eprintln!("{}", problems);
+            process::exit(1);
+        }
+    };
+    logging::init(&config.log_conf, Some(&config.console_conf));
+    for warning in &config_warnings {
+        warn!("{}", warning);
+    }
+    if let Err(err) = proxy::run(&args.config(&config)) {
+        error!("proxy stopped: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run_coordinator(cli: &Cli, args: &CoordinatorArgs) {
+    let (config, config_warnings) = match read_config(cli, None) {
+        Ok(config) => config,
+        Err(problems) => {
+            eprintln!("{}", problems);
+            process::exit(1);
+        }
+    };
+    logging::init(&config.log_conf, Some(&config.console_conf));
+    for warning in &config_warnings {
+        warn!("{}", warning);
+    }
+    if config.api_conf.token.is_empty() {
+        warn!("[api] token is empty, the coordinator api refuses all requests");
+    }
+    if let Err(err) = cluster::run(&args.config(&config)) {
+        error!("coordinator stopped: {}", err);
+        process::exit(1);
+    }
+}
+
+/// Config wizard for first-time users
+fn run_init(args: &InitArgs) {
+    if args.output.exists() && !args.force {
+        eprintln!(
+            "{} already exists, use --force to overwrite it",
+            args.output.display()
+        );
+        process::exit(1);
+    }
+    let hardware = wizard::probe_hardware();
+    println!("{}\n", wizard::hardware_summary(&hardware));
+
+    let answers = match wizard::ask(&mut io::stdin().lock(), &mut io::stdout()) {
+        Ok(answers) => answers,
+        Err(err) => {
+            eprintln!("\nno config written: {}", err);
+            process::exit(1);
+        }
+    };
+    let config = wizard::render_config(&answers, &hardware);
+    if let Err(err) = fs::write(&args.output, config) {
+        eprintln!("could not write {}: {}", args.output.display(), err);
+        process::exit(1);
+    }
+    println!(
+        "wrote {} with {} threads, start mining with: mithril --config {}",
+        args.output.display(),
+        wizard::recommended_threads(&hardware, answers.rig),
+        args.output.display()
+    );
+}
+
+/// Writes a config for the hardware on the first start without a config file, with the
+/// pool and wallet of the command line or, on a terminal, of the questions of the wizard.
+/// If it cannot be written, the defaults are used for this run.
+fn write_first_config(cli: &Cli) {
+    if cli.config_format() != ConfigFormat::Toml {
+        return;
+    }
+    let hardware = wizard::probe_hardware();
+    let answers = if cli.has_pool() {
+        match wizard::answers_from_cli(cli.pool.as_deref(), cli.wallet.as_deref()) {
+            Some(answers) => answers,
+            None => return,
+        }
+    } else if io::stdin().is_terminal() && !cli.daemon {
+        println!(
+            "no config file {} found, answer a few questions to create one\n",
+            cli.config.display()
+        );
+        println!("{}\n", wizard::hardware_summary(&hardware));
+        match wizard::ask(&mut io::stdin().lock(), &mut io::stdout()) {
+            Ok(answers) => answers,
+            Err(err) => {
+                eprintln!("\nno config written: {}", err);
+                process::exit(1);
+            }
+        }
+    } else {
+        return;
+    };
+    let config = wizard::render_config(&answers, &hardware);
+    match fs::write(&cli.config, config) {
+        Ok(()) => println!(
+            "wrote {} with {} threads for this hardware",
+            cli.config.display(),
+            wizard::recommended_threads(&hardware, answers.rig)
+        ),
+        Err(err) => eprintln!(
+            "could not write {}, mining with the defaults: {}",
+            cli.config.display(),
+            err
+        ),
+    }
+}
+
+fn start_daemon(cli: &Cli) -> io::Result<Option<Pidfile>> {
+    if cli.daemon {
+        daemon::daemonize(&cli.log_file, cli.pidfile.as_deref())
+    } else {
+        cli.pidfile.as_deref().map(Pidfile::create).transpose()
+    }
+}
+
+/// Reads the config file, applies the command line options and validates the result.
+/// Without a config file the defaults are used if the pool is given on the command line.
+/// The fleet config of a cluster coordinator is read on top of the file.
+/// Returns the config with the warnings, or all problems as printable message.
+fn read_config(
+    cli: &Cli,
+    fleet_config: Option<&str>,
+) -> Result<(MithrilConfig, Vec<ConfigIssue>), MithrilError> {
+    let name = cli.config.to_string_lossy();
+    let defaults = !cli.config.exists() && cli.has_pool();
+    let (mut content, format) = if defaults {
+        (
+            mithril_config::DEFAULT_CONFIG.to_string(),
+            ConfigFormat::Toml,
+        )
+    } else {
+        let content = fs::read_to_string(&cli.config).map_err(|err| {
+            MithrilError::Config(format!("could not read config {}: {}", name, err))
+        })?;
+        (content, cli.config_format())
+    };
+
+    let mut issues = Vec::new();
+    match config_migration::migrate(&content, format) {
+        Ok(Some(migration)) => {
+            let saved = match config_migration::save(&cli.config, &content, &migration) {
+                Ok(backup) => format!("the original is kept as {}", backup.display()),
+                Err(err) => format!("only for this run, {}", err),
+            };
+            issues.push(ConfigIssue {
+                severity: Severity::Warning,
+                key: config_migration::VERSION_KEY.to_string(),
+                message: format!(
+                    "was {}, the config was migrated to version {}, {} ({})",
+                    migration.from,
+                    config_migration::CONFIG_VERSION,
+                    saved,
+                    migration.notes.join(", ")
+                ),
+                location: None,
+            });
+            content = migration.content;
+        }
+        Ok(None) => {}
+        Err(err) => return Err(MithrilError::Config(format!("error: {}", err))),
+    }
+
+    let source = ConfigSource {
+        name: &name,
+        content: &content,
+        format,
+    };
+    issues.extend(config_validation::check_keys(&source));
+    let read = match fleet_config {
+        Some(fleet_config) => {
+            mithril_config::read_config_with_fleet(&content, format, fleet_config)
+        }
+        None => mithril_config::read_config_content(&content, format),
+    };
+    let mut config = match read {
+        Ok(config) => config,
+        Err(err) => {
+            let mut problems: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+            problems.push(format!("error: could not read config {}: {}", name, err));
+            return Err(MithrilError::Config(problems.join("\n")));
+        }
+    };
+    cli.apply(&mut config);
+
+    issues.extend(config_validation::validate(
+        &config,
+        &source,
+        &cli.overridden_keys(),
+    ));
+    if config_validation::has_errors(&issues) {
+        let problems: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+        return Err(MithrilError::Config(problems.join("\n")));
+    }
+    randomx::register_custom(&config.randomx_variants);
+    randomx::set_memory(config.memory_conf.clone());
+    randomx::set_dataset_init(config.dataset_init_conf);
+    Ok((config, issues))
+}
+
+#[cfg(feature = "tui")]
+fn start_tui(stats: Arc<MinerStats>, auto_tune: bool, control_sndr: Sender<ControlCmd>) {
+    mithril::tui::start(stats, auto_tune, control_sndr)
+}
+
+#[cfg(feature = "tui")]
+fn stop_tui() {
+    mithril::tui::stop()
+}
+
+#[cfg(not(feature = "tui"))]
+fn stop_tui() {}
+
+#[cfg(not(feature = "tui"))]
+fn start_tui(_stats: Arc<MinerStats>, _auto_tune: bool, _control_sndr: Sender<ControlCmd>) {
+    eprintln!("mithril was built without the tui feature (cargo build --features tui)");
+    std::process::exit(1);
+}
+
+/// Full memory mode unless the memory limit of the container is too low for the dataset
+fn memory_allocator() -> VmMemoryAllocator {
+    let limits = cgroup::limits();
+    if let Some(cpus) = limits.cpus {
+        info!(
+            "cgroup cpu limit of {} cores, profiles and auto tuning use at most {} threads",
+            cpus,
+            cgroup::available_cpus()
+        );
+    }
+    if let Some(limit) = limits.memory_bytes {
+        info!("cgroup memory limit of {} MB", limit / 1024 / 1024);
+    }
+    let mode = randomx::memory_mode();
+    match mode {
+        MemoryMode::Light => info!("memory.mode is light, the dataset is not allocated"),
+        MemoryMode::Fast if !limits.fits_full_memory() => warn!(
+            "the memory limit is too low for the {} MB of the full memory mode, memory.mode = fast uses it anyway",
+            cgroup::FULL_MEMORY_BYTES / 1024 / 1024
+        ),
+        MemoryMode::Auto if !limits.fits_full_memory() => warn!(
+            "the memory limit is too low for the {} MB of the full memory mode, using the slow light mode",
+            cgroup::FULL_MEMORY_BYTES / 1024 / 1024
+        ),
+        _ => {}
+    }
+    VmMemoryAllocator::with_mode(mode.full_memory(limits.fits_full_memory()))
+}
+
+/// Waits a minute, the systemd watchdog is kept notified. Returns early on shutdown.
+fn await_timeout(notifier: &Notifier) {
+    let end = Instant::now() + Duration::from_secs(60);
+    let mut last_watchdog = Instant::now();
+    while let Some(remaining) = end.checked_duration_since(Instant::now()) {
+        if signals::shutdown_requested() {
+            return;
+        }
+        thread::sleep(remaining.min(SHUTDOWN_POLL_INTERVAL));
+        if let Some(interval) = notifier.watchdog_interval() {
+            if last_watchdog.elapsed() >= interval {
+                notifier.watchdog();
+                last_watchdog = Instant::now();
+            }
+        }
+    }
+}
+
+/// Moves on to the next backup pool after a connection problem. Once all pools failed
+/// it waits before starting over with the primary pool.
+fn fail_over(
+    pool_ix: &mut usize,
+    config: &MithrilConfig,
+    donation_hashing: bool,
+    notifier: &Notifier,
+) {
+    if !donation_hashing {
+        let pools = config.pools();
+        *pool_ix = (*pool_ix + 1) % pools.len();
+        if *pool_ix != 0 {
+            info!("trying backup pool {}", pools[*pool_ix].pool_address);
+            return;
+        }
+    }
+    info!("retrying in 60 seconds");
+    notifier.status("all pools failed, retrying in 60 seconds");
+    await_timeout(notifier);
+}
+
+/// Waits for the resume command, config changes are applied while paused. Returns
+/// false if a shutdown was requested instead.
+fn await_resume(
+    control_rcvr: &Receiver<ControlCmd>,
+    stats: &MinerStats,
+    notifier: &Notifier,
+    cli: &Cli,
+    config: &mut MithrilConfig,
+    bandit: &mut Option<Bandit>,
+) -> bool {
+    info!("paused");
+    stats.set_paused(true);
+    notifier.status("paused");
+    let watchdog = notifier.watchdog_interval().map_or(never(), tick);
+    loop {
+        select! {
+            recv(control_rcvr) -> cmd => match cmd {
+                Ok(ControlCmd::TogglePause) | Ok(ControlCmd::Resume) | Err(_) => break,
+                Ok(ControlCmd::Shutdown) => return false,
+                Ok(ControlCmd::Pause) | Ok(ControlCmd::Reconnect) | Ok(ControlCmd::Retune) => {}
+                Ok(cmd) => reconfigure(cmd, cli, config, bandit),
+            },
+            recv(watchdog) -> _ => notifier.watchdog(),
+        }
+    }
+    info!("resuming");
+    stats.set_paused(false);
+    true
+}
+
+/// Applies a thread count, pool or config change. Auto tuning is switched on or off
+/// to follow `worker_conf.auto_tune`.
+fn reconfigure(
+    cmd: ControlCmd,
+    cli: &Cli,
+    config: &mut MithrilConfig,
+    bandit: &mut Option<Bandit>,
+) {
+    match cmd {
+        ControlCmd::SetThreads(num_threads) => {
+            info!("switching to {} threads", num_threads);
+            config.worker_conf.num_threads = num_threads;
+            config.worker_conf.auto_tune = false;
+        }
+        ControlCmd::SetThrottle(percent) => {
+            info!("throttling the workers by {}%", percent);
+            config.worker_conf.throttle_percent = percent;
+        }
+        ControlCmd::SetProfile(name) => {
+            if config.select_profile(&name) {
+                info!(
+                    "switching to profile {} with {} threads",
+                    name, config.worker_conf.num_threads
+                );
+            } else {
+                error!("unknown profile {}, keeping the current settings", name);
+            }
+        }
+        ControlCmd::NextProfile => {
+            let next = profile::next(&config.worker_conf.profile, &config.profiles);
+            config.select_profile(&next.name);
+            info!(
+                "switching to profile {} with {} threads",
+                next.name, config.worker_conf.num_threads
+            );
+        }
+        ControlCmd::SwitchPool(pool_conf) => {
+            info!("switching to pool {}", pool_conf.pool_address);
+            config.pool_conf = *pool_conf;
+        }
+        ControlCmd::ReloadConfig => {
+            let fleet_config = config.cluster_conf.fleet_config.clone();
+            reload_config(cli, fleet_config.as_deref(), config, "config reloaded");
+        }
+        ControlCmd::ApplyFleetConfig(fleet_config) => reload_config(
+            cli,
+            Some(&fleet_config),
+            config,
+            "fleet config of the coordinator applied",
+        ),
+        ControlCmd::Persist(cmd) => {
+            let values = control::config_values(&cmd);
+            let unknown_profile = match cmd.as_ref() {
+                ControlCmd::SetProfile(name) => config.profile(name).is_none(),
+                _ => false,
+            };
+            reconfigure(*cmd, cli, config, bandit);
+            if !unknown_profile {
+                persist_settings(cli, &values);
+            }
+        }
+        _ => {}
+    }
+
+    if config.worker_conf.auto_tune && bandit.is_none() {
+        info!("auto tuning enabled");
+        *bandit = Some(bandit_tools::setup_bandit(
+            config.worker_conf.auto_tune_log.clone(),
+        ));
+    } else if !config.worker_conf.auto_tune && bandit.is_some() {
+        info!("auto tuning disabled");
+        *bandit = None;
+    }
+}
+
+/// Writes settings changed at runtime into the config file, its comments are kept
+fn persist_settings(cli: &Cli, values: &[(&str, &str, serde_json::Value)]) {
+    match config_migration::write_keys(&cli.config, cli.config_format(), values) {
+        Ok(()) => info!("settings written to {}", cli.config.display()),
+        Err(err) => error!("could not write the settings to the config file: {}", err),
+    }
+}
+
+/// The settings of the auto tuning and donation clock
+fn timer_settings(config: &MithrilConfig) -> (bool, u64, f64) {
+    (
+        config.worker_conf.auto_tune,
+        config.worker_conf.auto_tune_interval_minutes,
+        config.donation_conf.percentage,
+    )
+}
+
+/// Replaces the pool, worker and donation settings with the ones of the config file
+fn reload_config(cli: &Cli, fleet_config: Option<&str>, config: &mut MithrilConfig, done: &str) {
+    match read_config(cli, fleet_config) {
+        Ok((new_config, warnings)) => {
+            for warning in warnings {
+                warn!("{}", warning);
+            }
+            info!("{}", done);
+            config.pool_conf = new_config.pool_conf;
+            config.backup_pools = new_config.backup_pools;
+            config.payout_wallets = new_config.payout_wallets;
+            logging::set_module_levels(&new_config.log_conf.levels);
+            config.log_conf.levels = new_config.log_conf.levels;
+            config.worker_conf = new_config.worker_conf;
+            config.profiles = new_config.profiles;
+            config.donation_conf = new_config.donation_conf;
+            config.cluster_conf = new_config.cluster_conf;
+        }
+        Err(problems) => error!(
+            "config reload failed, keeping the current config:\n{}",
+            problems
+        ),
+    }
+}
+
+fn save_bandit_state(bandit: &mut Bandit) {
     let res = bandit_tools::ensure_mithril_folder_exists();
     if res.is_err() {
         error!("could not create folder for state file {:?}", res.err());
@@ -152,54 +1312,296 @@ fn save_bandit_state(bandit: &mut bandit::softmax::AnnealingSoftmax<bandit_tools
 /// This function terminates if a non-recoverable error was detected (i.e. connection lost)
 fn start_main_event_loop(
     pool: &mut WorkerPool,
-    client_err_rcvr: &Receiver<Error>,
+    client_err_rcvr: &Receiver<StratumError>,
     stratum_rcvr: &Receiver<StratumAction>,
     timer_rcvr: &Receiver<timer::TickAction>,
-) -> io::Result<MainLoopExit> {
+    control_rcvr: &Receiver<ControlCmd>,
+    nicehash: bool,
+    reporters: &Reporters,
+) -> Result<MainLoopExit, MithrilError> {
+    let stats = reporters.stats;
+    let notifier = reporters.notifier;
+    let watchdog = notifier.watchdog_interval().map_or(never(), tick);
+    let report_tick = reporters.coordinator.map_or(never(), |_| tick(cluster::REPORT_INTERVAL));
+    let mut last_report = (Instant::now(), stats.snapshot().total_hashes);
+    let mut telemetry = TelemetrySampler::new();
+    let fail_back_tick = reporters.fail_back.map_or(never(), |_| tick(FAIL_BACK_INTERVAL));
+    let payout_tick = reporters.payout.map_or(never(), |_| tick(payout::ROTATION_INTERVAL));
+    let (reachable_sndr, reachable_rcvr) = unbounded();
+    let worker_errors = pool.errors().clone();
+    let init_stalls = randomx::init_stalls();
+    let mut job_height = 0;
+    //the memory of the job while its dataset is computed in the background
+    let mut initialising: Option<Arc<dyn PowMemory>> = None;
+    let mut ready_check = never();
+    //the coordinator stopped the workers, the connection stays open for its resume
+    let mut paused = false;
     loop {
         select! {
             recv(stratum_rcvr) -> stratum_msg => {
                 if stratum_msg.is_err() {
-                    return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "received error"));
+                    return Err(StratumError::ChannelClosed("action").into());
                 }
                 match stratum_msg.unwrap() {
-                    StratumAction::Job{miner_id, seed_hash, blob, job_id, target} => {
-                        pool.job_change(&miner_id, &seed_hash, &blob, &job_id, &target);
+                    StratumAction::Job{job_id, ..} if paused => {
+                        info!("paused by the coordinator, job {} ignored", job_id);
+                    },
+                    StratumAction::Job{miner_id, seed_hash, height, blob, job_id, target, algo} => {
+                        let difficulty = difficulty::job_difficulty(&target);
+                        stats.set_difficulty(difficulty);
+                        reporters.share_db.record(HistoryEvent::Job {
+                            time: SystemTime::now(),
+                            job_id: job_id.clone(),
+                            difficulty,
+                        });
+                        job_height = height;
+                        let job = Job{seed_hash, height, blob, job_id, target, algo};
+                        if let Some(recorder) = reporters.recorder {
+                            recorder.record(&miner_id, &job, nicehash);
+                        }
+                        pool.job_change(&miner_id, &job, nicehash)?;
+                        let memory = pool.algorithm.memory();
+                        reporters.forensics.job(JobContext {
+                            job_id: job.job_id,
+                            blob: job.blob,
+                            target: job.target,
+                            seed_hash: job.seed_hash,
+                            height,
+                            received: SystemTime::now(),
+                            memory: Arc::downgrade(&memory),
+                        });
+                        stats.set_memory(memory.clone());
+                        //ready once connected and the dataset of the seed is initialised,
+                        //a background init is waited for
+                        if memory.initialising() {
+                            notifier.status("initialising the dataset");
+                            initialising = Some(memory);
+                            ready_check = tick(READY_CHECK_INTERVAL);
+                        } else {
+                            initialising = None;
+                            ready_check = never();
+                            if let Some((pool_address, _)) = stats.connection() {
+                                notifier.ready(&format!("mining on {}", pool_address));
+                            }
+                        }
                     },
                     StratumAction::Error{err} => {
-                        error!("Received stratum error: {}", err);
+                        stats.stratum_error();
+                        error!(event = "stratum_error", error = err.as_str(); "Received stratum error: {}", err);
                     },
                     StratumAction::Ok => {
                         info!("Received stratum ok");
                     },
                     StratumAction::KeepAliveOk => {
                         info!("Received keep alive ok");
+                    },
+                    StratumAction::Control{control} => {
+                        info!("command from the coordinator: {:?}", control);
+                        let exit = match control {
+                            ClusterControl::Pause => {
+                                if !paused {
+                                    pool.stop();
+                                    stats.set_paused(true);
+                                    paused = true;
+                                }
+                                None
+                            },
+                            //new workers for the next job of the coordinator
+                            ClusterControl::Resume => paused.then_some(MainLoopExit::Reconnect),
+                            ClusterControl::Reconnect => Some(MainLoopExit::Reconnect),
+                            ClusterControl::Retune => Some(MainLoopExit::Retune),
+                            ClusterControl::Threads{threads} => Some(MainLoopExit::Reconfigure(ControlCmd::SetThreads(threads))),
+                            ClusterControl::Profile{profile} => Some(MainLoopExit::Reconfigure(ControlCmd::SetProfile(profile))),
+                        };
+                        if let Some(exit) = exit {
+                            stats.set_paused(false);
+                            return Ok(exit);
+                        }
+                    },
+                    //sent after each login, only a changed one restarts the workers
+                    StratumAction::FleetConfig{config} if reporters.fleet_config == Some(config.as_str()) => {},
+                    StratumAction::FleetConfig{config} => {
+                        info!("new fleet config from the coordinator");
+                        stats.set_paused(false);
+                        return Ok(MainLoopExit::Reconfigure(ControlCmd::ApplyFleetConfig(config)));
+                    },
+                    StratumAction::ShareAccepted{share, latency} => {
+                        let effort = stats.share_accepted(share.difficulty);
+                        stats.share_latency(latency);
+                        let hash_difficulty = difficulty::hash_difficulty(&share.hash);
+                        stats.best_share(hash_difficulty);
+                        stats.record_share(ShareRecord {
+                            time: SystemTime::now(),
+                            job_id: share.job_id.clone(),
+                            difficulty: share.difficulty,
+                            outcome: ShareOutcome::Accepted { effort },
+                        });
+                        let pool_address = stats.connection().map(|(pool_address, _)| pool_address).unwrap_or_default();
+                        reporters.alerter.share_accepted(&share, job_height, &pool_address, reporters.solo);
+                        if let Some(split) = reporters.payout {
+                            split.share_accepted(share.difficulty);
+                        }
+                        reporters.share_db.record(share_event(&share, hash_difficulty, latency, None));
+                        let average_effort = stats.snapshot().average_effort();
+                        info!(
+                            event = "share_accepted",
+                            job_id = share.job_id.as_str(),
+                            nonce = share.nonce.as_str(),
+                            difficulty = share.difficulty,
+                            effort = effort.unwrap_or(0.0),
+                            latency_ms = latency.as_millis() as u64;
+                            "Share accepted (job {}, nonce {}), effort {}, average effort {}, latency {}ms",
+                            share.job_id,
+                            share.nonce,
+                            format_percentage(effort),
+                            format_percentage(average_effort),
+                            latency.as_millis()
+                        );
+                    },
+                    StratumAction::ShareRejected{share, err, latency} => {
+                        stats.share_rejected();
+                        stats.share_latency(latency);
+                        stats.record_share(ShareRecord {
+                            time: SystemTime::now(),
+                            job_id: share.job_id.clone(),
+                            difficulty: share.difficulty,
+                            outcome: ShareOutcome::Rejected { err: err.clone() },
+                        });
+                        let hash_difficulty = difficulty::hash_difficulty(&share.hash);
+                        reporters.share_db.record(share_event(&share, hash_difficulty, latency, Some(err.clone())));
+                        let pool_address = stats.connection().map(|(pool, _)| pool).unwrap_or_default();
+                        reporters.forensics.share_rejected(&share, &err, latency, &pool_address);
+                        warn!(
+                            event = "share_rejected",
+                            job_id = share.job_id.as_str(),
+                            nonce = share.nonce.as_str(),
+                            error = err.as_str(),
+                            latency_ms = latency.as_millis() as u64;
+                            "Share rejected (job {}, nonce {}): {}", share.job_id, share.nonce, err
+                        );
                     }
                 }
             },
             recv(timer_rcvr) -> timer_msg => {
-                if timer_msg.is_err() {
-                    return Err(io::Error::new(io::ErrorKind::Other, format!("error received {:?}", timer_msg)));
-                } else {
-                    let tick_action = timer_msg.expect("tickAction");
-                    match tick_action {
-                        timer::TickAction::ArmChange => {
-                            info!("bandit clock signal received - time for new arm");
-                            return Ok(MainLoopExit::DrawNewBanditArm)
-                        },
-                        timer::TickAction::DonationHashing => {
-                            return Ok(MainLoopExit::DonationHashing)
-                        }
+                match timer_msg {
+                    Err(_) => return Err(MithrilError::ChannelClosed("timer")),
+                    Ok(timer::TickAction::ArmChange) => {
+                        info!("bandit clock signal received - time for new arm");
+                        return Ok(MainLoopExit::DrawNewBanditArm)
+                    },
+                    Ok(timer::TickAction::DonationHashing) => {
+                        return Ok(MainLoopExit::DonationHashing)
                     }
                 }
             },
+            recv(control_rcvr) -> control_msg => {
+                match control_msg {
+                    Ok(ControlCmd::TogglePause) | Ok(ControlCmd::Pause) => return Ok(MainLoopExit::Pause),
+                    Ok(ControlCmd::Resume) => {}, //not paused
+                    Ok(ControlCmd::Reconnect) => return Ok(MainLoopExit::Reconnect),
+                    Ok(ControlCmd::Retune) => return Ok(MainLoopExit::Retune),
+                    Ok(ControlCmd::Shutdown) => return Ok(MainLoopExit::Shutdown),
+                    Ok(cmd) => return Ok(MainLoopExit::Reconfigure(cmd)),
+                    Err(_) => return Err(MithrilError::ChannelClosed("control")),
+                }
+            },
             recv(client_err_rcvr) -> client_err_msg => {
-                return Err(io::Error::new(io::ErrorKind::Other, format!("error received {:?}", client_err_msg)));
-            }
+                return Err(client_err_msg.map_or(StratumError::ChannelClosed("error"), |err| err).into());
+            },
+            recv(worker_errors) -> worker_err => {
+                return Err(worker_err.map_or(MithrilError::ChannelClosed("worker error"), |err| err.into()));
+            },
+            recv(init_stalls) -> stall => {
+                return Err(stall.map_or(MithrilError::ChannelClosed("dataset init"), |err| err.into()));
+            },
+            recv(report_tick) -> _ => {
+                if let Some(coordinator) = reporters.coordinator {
+                    let (now, total_hashes) = (Instant::now(), stats.snapshot().total_hashes);
+                    let elapsed = now.duration_since(last_report.0).as_secs_f64();
+                    let report = HashrateReport {
+                        hashrate: total_hashes.saturating_sub(last_report.1) as f64 / elapsed,
+                        threads: if paused { 0 } else { stats.thread_hashes().len() as u64 },
+                        paused,
+                        temperature: telemetry.sample().package_temp_celsius,
+                        stratum_errors: stats.stratum_errors(),
+                        dataset_errors: stats.dataset_errors(),
+                    };
+                    last_report = (now, total_hashes);
+                    coordinator.send(StratumCmd::Report{report}).map_err(|_| StratumError::ChannelClosed("report"))?;
+                }
+            },
+            recv(fail_back_tick) -> _ => {
+                if let Some(primary) = reporters.fail_back {
+                    let primary = primary.clone();
+                    let reachable_sndr = reachable_sndr.clone();
+                    //the probe waits for the connection, the events are handled meanwhile
+                    let probe = thread::Builder::new()
+                        .name("fail back probe thread".to_string())
+                        .spawn(move || {
+                            if stratum::reachable(&primary) {
+                                let _ = reachable_sndr.send(primary.pool_address.clone());
+                            }
+                        });
+                    if let Err(err) = probe {
+                        warn!("could not probe the primary pool: {}", err);
+                    }
+                }
+            },
+            recv(reachable_rcvr) -> pool_address => {
+                if let Ok(pool_address) = pool_address {
+                    info!("primary pool {} is reachable again, switching back", pool_address);
+                }
+                return Ok(MainLoopExit::Reconnect);
+            },
+            recv(payout_tick) -> _ => {
+                if let Some(split) = reporters.payout {
+                    let percentages: Vec<String> = split.split().iter().map(|p| format!("{:.1}%", p)).collect();
+                    info!("payout split so far {}", percentages.join(" / "));
+                    if split.rotate() {
+                        info!("paying out to wallet {}", split.current());
+                        return Ok(MainLoopExit::RotatePayout);
+                    }
+                }
+            },
+            recv(ready_check) -> _ => {
+                if !initialising.as_ref().is_some_and(|memory| memory.initialising()) {
+                    initialising = None;
+                    ready_check = never();
+                    if let Some((pool_address, _)) = stats.connection() {
+                        notifier.ready(&format!("mining on {}", pool_address));
+                    }
+                }
+            },
+            recv(watchdog) -> _ => notifier.watchdog(),
         }
     }
 }
 
+fn share_event(
+    share: &Share,
+    hash_difficulty: u64,
+    latency: Duration,
+    error: Option<String>,
+) -> HistoryEvent {
+    let now = SystemTime::now();
+    HistoryEvent::Share {
+        submitted: now.checked_sub(latency).unwrap_or(now),
+        job_id: share.job_id.clone(),
+        nonce: share.nonce.clone(),
+        difficulty: share.difficulty,
+        hash_difficulty,
+        latency,
+        error,
+    }
+}
+
+fn format_percentage(v: Option<f64>) -> String {
+    match v {
+        Some(v) => format!("{:.1}%", v * 100.0),
+        None => "n/a".to_string(),
+    }
+}
+
 fn print_donation_hint(percentage: f64) {
     println!("-------------------------------------------------------------------");
     println!("Donation Hashing enabled with {}%.", percentage);
@@ -0,0 +1,210 @@
+use super::stats::MinerStats;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Total hash counts sampled over time, for computing the hashrate over different
+/// windows (e.g. 10s, 60s and 15m).
+pub struct HashrateHistory {
+    max_age: Duration,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl HashrateHistory {
+    pub fn new(max_age: Duration) -> HashrateHistory {
+        HashrateHistory {
+            max_age,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Adds the total hash count at the given time, samples older than `max_age`
+    /// are dropped.
+    pub fn record(&mut self, at: Instant, total_hashes: u64) {
+        self.samples.push_back((at, total_hashes));
+        while let Some((oldest, _)) = self.samples.front() {
+            if at.duration_since(*oldest) > self.max_age {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Hashrate over the window ending at the latest sample. None if the history does
+    /// not cover the full window yet.
+    pub fn hashrate(&self, window: Duration) -> Option<f64> {
+        let (latest_at, latest_hashes) = *self.samples.back()?;
+        let (start_at, start_hashes) = *self
+            .samples
+            .iter()
+            .rev()
+            .find(|(at, _)| latest_at.duration_since(*at) >= window)?;
+        let seconds = latest_at.duration_since(start_at).as_secs_f64();
+        if seconds <= 0.0 {
+            return None;
+        }
+        Some(latest_hashes.saturating_sub(start_hashes) as f64 / seconds)
+    }
+}
+
+/// Per thread hash counters sampled over a fixed window, for the hashrate per
+/// worker thread
+pub struct ThreadHistory {
+    window: Duration,
+    samples: VecDeque<(Instant, Vec<u64>)>,
+}
+
+impl ThreadHistory {
+    pub fn new(window: Duration) -> ThreadHistory {
+        ThreadHistory {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, at: Instant, thread_hashes: Vec<u64>) {
+        self.samples.push_back((at, thread_hashes));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(oldest, _)| at.duration_since(*oldest) > self.window)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The latest hash count and the hashrate over the window per thread. The rate is
+    /// None until two samples are recorded and after the worker pool was restarted.
+    pub fn hashrates(&self) -> Vec<(u64, Option<f64>)> {
+        let (latest_at, latest) = match self.samples.back() {
+            Some(latest) => latest,
+            None => return Vec::new(),
+        };
+        let (first_at, first) = &self.samples[0];
+        let seconds = latest_at.duration_since(*first_at).as_secs_f64();
+        latest
+            .iter()
+            .enumerate()
+            .map(|(ix, hashes)| {
+                // the counters are reset when the worker pool changes
+                let rate = first
+                    .get(ix)
+                    .filter(|start| seconds > 0.0 && *start <= hashes)
+                    .map(|start| (hashes - start) as f64 / seconds);
+                (*hashes, rate)
+            })
+            .collect()
+    }
+}
+
+/// Starts a thread that records the total hash count once per second
+pub fn start_sampling(stats: Arc<MinerStats>, max_age: Duration) -> Arc<Mutex<HashrateHistory>> {
+    let history = Arc::new(Mutex::new(HashrateHistory::new(max_age)));
+    let sample_history = history.clone();
+    thread::Builder::new()
+        .name("hashrate sample thread".to_string())
+        .spawn(move || loop {
+            let total_hashes = stats.snapshot().total_hashes;
+            if let Ok(mut history) = sample_history.lock() {
+                history.record(Instant::now(), total_hashes);
+            }
+            thread::sleep(SAMPLE_INTERVAL);
+        })
+        .expect("hashrate sample thread handle");
+    history
+}
+
+/// The hashrate over the last hours at a fixed resolution, e.g. 24h of 10s samples, for
+/// drawing the history without an external database. The oldest samples are dropped.
+pub struct HashrateLog {
+    resolution: Duration,
+    capacity: usize,
+    /// unix seconds and hashrate
+    samples: VecDeque<(u64, f64)>,
+    last: Option<(Instant, u64)>,
+}
+
+impl HashrateLog {
+    pub fn new(resolution: Duration, keep: Duration) -> HashrateLog {
+        let capacity = (keep.as_secs() / resolution.as_secs().max(1)) as usize;
+        HashrateLog {
+            resolution,
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+            last: None,
+        }
+    }
+
+    pub fn resolution(&self) -> Duration {
+        self.resolution
+    }
+
+    /// Adds the hashrate since the previous total hash count, the first count is only
+    /// the base
+    pub fn record(&mut self, at: Instant, unix_time: u64, total_hashes: u64) {
+        if let Some((last_at, last_hashes)) = self.last {
+            let seconds = at.duration_since(last_at).as_secs_f64();
+            if seconds > 0.0 && self.capacity > 0 {
+                if self.samples.len() == self.capacity {
+                    self.samples.pop_front();
+                }
+                let rate = total_hashes.saturating_sub(last_hashes) as f64 / seconds;
+                self.samples.push_back((unix_time, rate));
+            }
+        }
+        self.last = Some((at, total_hashes));
+    }
+
+    /// The samples from `since` (unix seconds) on. With a `step` above the resolution
+    /// the samples are averaged over `step` seconds, the time is the start of the step.
+    pub fn query(&self, since: u64, step: u64) -> Vec<(u64, f64)> {
+        let samples = self.samples.iter().filter(|(time, _)| *time >= since);
+        if step <= self.resolution.as_secs() {
+            return samples.copied().collect();
+        }
+        let mut buckets: Vec<(u64, f64, u32)> = Vec::new();
+        for (time, rate) in samples {
+            let start = time - time % step;
+            match buckets.last_mut() {
+                Some((bucket, sum, n)) if *bucket == start => {
+                    *sum += rate;
+                    *n += 1;
+                }
+                _ => buckets.push((start, *rate, 1)),
+            }
+        }
+        buckets
+            .into_iter()
+            .map(|(start, sum, n)| (start, sum / n as f64))
+            .collect()
+    }
+}
+
+/// Starts a thread that logs the hashrate every `resolution`
+pub fn start_logging(
+    stats: Arc<MinerStats>,
+    resolution: Duration,
+    keep: Duration,
+) -> Arc<Mutex<HashrateLog>> {
+    let log = Arc::new(Mutex::new(HashrateLog::new(resolution, keep)));
+    let sample_log = log.clone();
+    thread::Builder::new()
+        .name("hashrate log thread".to_string())
+        .spawn(move || loop {
+            let total_hashes = stats.snapshot().total_hashes;
+            let unix_time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs());
+            if let Ok(mut log) = sample_log.lock() {
+                log.record(Instant::now(), unix_time, total_hashes);
+            }
+            thread::sleep(resolution);
+        })
+        .expect("hashrate log thread handle");
+    log
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+/// Upper bound of the first bucket
+const FIRST_BOUND_MS: f64 = 1.0;
+/// Each bucket is this factor wider than the previous one, so percentiles are
+/// estimated with an error of at most 25%
+const GROWTH: f64 = 1.25;
+/// Buckets up to ~70s, everything above goes to an overflow bucket
+const BUCKETS: usize = 50;
+
+/// Histogram of share round-trip times with exponentially growing buckets
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> LatencyHistogram {
+        LatencyHistogram {
+            counts: vec![0; BUCKETS + 1],
+            total: 0,
+        }
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        let ms = latency.as_secs_f64() * 1000.0;
+        let bucket = if ms <= FIRST_BOUND_MS {
+            0
+        } else {
+            ((ms / FIRST_BOUND_MS).ln() / GROWTH.ln()).ceil() as usize
+        };
+        self.counts[bucket.min(BUCKETS)] += 1;
+        self.total += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    /// Estimated latency below which the fraction `p` (0.0 - 1.0) of the samples are,
+    /// interpolated within the bucket
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.total == 0 {
+            return None;
+        }
+        let rank = ((p * self.total as f64).ceil() as u64).clamp(1, self.total);
+        let mut below = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            if below + count >= rank {
+                let lower = if bucket == 0 {
+                    0.0
+                } else {
+                    bucket_bound_ms(bucket - 1)
+                };
+                if bucket == BUCKETS {
+                    return Some(Duration::from_secs_f64(lower / 1000.0));
+                }
+                let upper = bucket_bound_ms(bucket);
+                let fraction = (rank - below) as f64 / *count as f64;
+                return Some(Duration::from_secs_f64(
+                    (lower + (upper - lower) * fraction) / 1000.0,
+                ));
+            }
+            below += count;
+        }
+        None
+    }
+
+    /// Upper bound in ms and count of the non empty buckets, the overflow bucket has
+    /// an infinite bound
+    pub fn buckets(&self) -> Vec<(f64, u64)> {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(bucket, count)| {
+                let bound = if bucket == BUCKETS {
+                    f64::INFINITY
+                } else {
+                    bucket_bound_ms(bucket)
+                };
+                (bound, *count)
+            })
+            .collect()
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram::new()
+    }
+}
+
+fn bucket_bound_ms(bucket: usize) -> f64 {
+    FIRST_BOUND_MS * GROWTH.powi(bucket as i32)
+}
@@ -0,0 +1,111 @@
+use super::stats::{MinerStats, StatsSnapshot};
+use crate::bandit_tools;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Totals over all runs of the miner, persisted in the mithril folder
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub hashes: u64,
+    pub shares_accepted: u64,
+    pub shares_rejected: u64,
+    pub uptime_seconds: u64,
+    pub best_share_difficulty: u64,
+    /// unix time of the first run
+    pub since: u64,
+}
+
+impl LifetimeStats {
+    /// Reads the stats, a missing file counts as the first run
+    pub fn load(path: &Path) -> io::Result<LifetimeStats> {
+        if !path.exists() {
+            return Ok(LifetimeStats {
+                since: unix_time(),
+                ..LifetimeStats::default()
+            });
+        }
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(io::Error::other)
+    }
+
+    /// Writes the stats to a temporary file first, so an interrupted write does not
+    /// lose the totals
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        let mut tmp_path = path.to_path_buf().into_os_string();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)
+    }
+
+    /// These totals plus the stats of the current run
+    pub fn add(&self, run: &StatsSnapshot, uptime: Duration) -> LifetimeStats {
+        LifetimeStats {
+            hashes: self.hashes + run.total_hashes,
+            shares_accepted: self.shares_accepted + run.shares_accepted,
+            shares_rejected: self.shares_rejected + run.shares_rejected,
+            uptime_seconds: self.uptime_seconds + uptime.as_secs(),
+            best_share_difficulty: self.best_share_difficulty.max(run.best_share_difficulty),
+            since: self.since,
+        }
+    }
+}
+
+pub fn lifetime_stats_file() -> PathBuf {
+    let mut file = bandit_tools::mithril_folder();
+    file.push("lifetime_stats.json");
+    file
+}
+
+/// Loads the lifetime stats into `stats` and starts the thread that saves them
+/// periodically. Does nothing if the file cannot be read, to not overwrite it.
+pub fn start(stats: Arc<MinerStats>, path: PathBuf) {
+    let base = match LifetimeStats::load(&path) {
+        Ok(base) => base,
+        Err(err) => {
+            error!(
+                "lifetime stats disabled, could not read {}: {}",
+                path.display(),
+                err
+            );
+            return;
+        }
+    };
+    info!(
+        "lifetime stats: {} hashes, {} shares accepted, {} rejected, uptime {}s",
+        base.hashes, base.shares_accepted, base.shares_rejected, base.uptime_seconds
+    );
+    stats.set_lifetime_base(base);
+
+    thread::Builder::new()
+        .name("lifetime stats thread".to_string())
+        .spawn(move || loop {
+            thread::sleep(SAVE_INTERVAL);
+            save(&stats, &path);
+        })
+        .expect("lifetime stats thread handle");
+}
+
+/// Saves the totals including this run, does nothing if `start` did not load them
+pub fn save(stats: &MinerStats, path: &Path) {
+    if let Some(lifetime) = stats.lifetime() {
+        if let Err(err) = lifetime.save(path) {
+            error!("saving lifetime stats failed: {}", err);
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -1,3 +1,11 @@
+pub mod history;
+pub mod latency;
+pub mod lifetime;
+pub mod otlp;
+pub mod push;
+pub mod stats;
+pub mod telemetry;
+
 extern crate crossbeam_channel;
 
 use self::crossbeam_channel::{select, unbounded, Receiver, RecvTimeoutError, Sender};
@@ -8,6 +16,9 @@ use std::sync::Arc;
 use std::thread;
 use std::time;
 
+use self::stats::MinerStats;
+use crate::randomx::dataset_stats;
+
 #[derive(Clone)]
 pub struct MetricConfig {
     pub enabled: bool,
@@ -16,6 +27,18 @@ pub struct MetricConfig {
     pub report_file: String,
 }
 
+impl MetricConfig {
+    /// No metric file, the hashes are still counted
+    pub fn disabled() -> MetricConfig {
+        MetricConfig {
+            enabled: false,
+            resolution: u32::MAX as u64,
+            sample_interval_seconds: u32::MAX as u64,
+            report_file: "/dev/null".to_string(),
+        }
+    }
+}
+
 pub struct Metric {
     /// This is the total hash count since the construction of the
     /// metric struct.
@@ -26,7 +49,12 @@ pub struct Metric {
     stop_cnt_sndr: Sender<()>,
 }
 
-pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
+/// `hash_cnt_rcvr` receives the worker thread index and its hash count
+pub fn start(
+    conf: MetricConfig,
+    hash_cnt_rcvr: Receiver<(usize, u64)>,
+    stats: Arc<MinerStats>,
+) -> Metric {
     let log_count = Arc::new(AtomicU64::new(0));
     let total_count = Arc::new(AtomicU64::new(0));
 
@@ -43,9 +71,10 @@ pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
                     break 'select_loop;
                 },
                 recv(hash_cnt_rcvr) -> cnt_msg => {
-                    let cnt = cnt_msg.expect("Hash channel unexpectedly closed");
+                    let (thread, cnt) = cnt_msg.expect("Hash channel unexpectedly closed");
                     thread_log_count.fetch_add(cnt, Ordering::SeqCst);
                     thread_total_count.fetch_add(cnt, Ordering::SeqCst);
+                    stats.add_thread_hashes(thread, cnt);
                 }
             }
         })
@@ -56,6 +85,7 @@ pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
     let tick_hnd = thread::Builder::new()
         .name("metric sample thread".to_string())
         .spawn(move || {
+            let mut dataset = dataset_stats::snapshot();
             loop {
                 let recv_result = stop_tick_rcvr
                     .recv_timeout(time::Duration::from_secs(conf.sample_interval_seconds));
@@ -68,6 +98,9 @@ pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
                 }
 
                 let sample_cnt = log_count.swap(0, Ordering::SeqCst);
+                let dataset_totals = dataset_stats::snapshot();
+                let hit_rate = dataset_totals.since(&dataset).hit_rate();
+                dataset = dataset_totals;
 
                 let timestamp_result = time::SystemTime::now().duration_since(time::UNIX_EPOCH);
                 if timestamp_result.is_err() {
@@ -82,7 +115,8 @@ pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
                     .append(true)
                     .open(conf.report_file.clone());
                 if let Ok(mut file) = file_result {
-                    let write_result = writeln!(file, "{};{}", millis, sample_cnt);
+                    let write_result =
+                        writeln!(file, "{}", report_line(millis, sample_cnt, hit_rate));
                     if write_result.is_err() {
                         error!("could not write metric file");
                     }
@@ -105,6 +139,13 @@ pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
     }
 }
 
+/// `<unix-millis>;<hashes>;<dataset hit rate>`, the hit rate of the sample interval is
+/// empty without dataset reads
+pub fn report_line(millis: u64, hashes: u64, dataset_hit_rate: Option<f64>) -> String {
+    let hit_rate = dataset_hit_rate.map_or(String::new(), |rate| format!("{:.4}", rate));
+    format!("{};{};{}", millis, hashes, hit_rate)
+}
+
 impl Metric {
     pub fn hash_count(&self) -> u64 {
         self.total_hashes.load(Ordering::SeqCst)
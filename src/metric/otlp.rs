@@ -0,0 +1,264 @@
+//! Exports the miner metrics and the pipeline spans via OTLP/HTTP with the JSON
+//! encoding, to an OpenTelemetry collector or any backend that accepts OTLP. Needs a
+//! build with the otlp feature.
+
+#[cfg(feature = "otlp")]
+extern crate crossbeam_channel;
+#[cfg(feature = "otlp")]
+extern crate ureq;
+
+#[cfg(feature = "otlp")]
+use self::crossbeam_channel::{tick, unbounded, Receiver};
+#[cfg(feature = "otlp")]
+use super::push::hashrate;
+use super::push::optional_values;
+use super::stats::{MinerStats, StatsSnapshot};
+use super::telemetry::Telemetry;
+#[cfg(feature = "otlp")]
+use super::telemetry::TelemetrySampler;
+#[cfg(feature = "otlp")]
+use crate::trace;
+use crate::trace::{Field, SpanRecord};
+use serde_json::{json, Value};
+
+use std::sync::Arc;
+#[cfg(feature = "otlp")]
+use std::thread;
+#[cfg(feature = "otlp")]
+use std::time::Instant;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Spans beyond this many per export are dropped
+pub const MAX_SPANS: usize = 10_000;
+
+#[cfg(feature = "otlp")]
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct OtlpConfig {
+    pub enabled: bool,
+    /// base URL of the collector, `/v1/metrics` and `/v1/traces` are appended
+    pub endpoint: String,
+    pub interval_seconds: u64,
+    /// the `service.name` resource attribute
+    pub service_name: String,
+    /// the `host.name` resource attribute if not empty
+    pub host: String,
+    /// also export the spans of the job, dataset init, hashing and share submits
+    pub traces: bool,
+}
+
+/// Starts the export thread. Does nothing if the export is disabled.
+pub fn start(conf: OtlpConfig, stats: Arc<MinerStats>) {
+    if !conf.enabled {
+        return;
+    }
+    start_exporter(conf, stats);
+}
+
+#[cfg(not(feature = "otlp"))]
+fn start_exporter(_conf: OtlpConfig, _stats: Arc<MinerStats>) {
+    error!("OTLP export disabled: mithril was built without the otlp feature (cargo build --features otlp)");
+}
+
+#[cfg(feature = "otlp")]
+fn start_exporter(conf: OtlpConfig, stats: Arc<MinerStats>) {
+    let spans = if conf.traces {
+        let (sndr, rcvr) = unbounded();
+        trace::export_to(sndr);
+        Some(rcvr)
+    } else {
+        None
+    };
+    info!(
+        "exporting OTLP {} to {} every {}s",
+        if conf.traces {
+            "metrics and traces"
+        } else {
+            "metrics"
+        },
+        conf.endpoint,
+        conf.interval_seconds
+    );
+
+    thread::Builder::new()
+        .name("otlp export thread".to_string())
+        .spawn(move || export(&conf, &stats, spans.as_ref()))
+        .expect("otlp export thread handle");
+}
+
+#[cfg(feature = "otlp")]
+fn export(conf: &OtlpConfig, stats: &MinerStats, spans: Option<&Receiver<SpanRecord>>) {
+    let agent = ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build();
+    let endpoint = conf.endpoint.trim_end_matches('/');
+    let start = SystemTime::now();
+    let ticker = tick(Duration::from_secs(conf.interval_seconds));
+    let mut last_snapshot = stats.snapshot();
+    let mut last_export = Instant::now();
+    let mut sampler = TelemetrySampler::new();
+    sampler.sample(); //first sample is the base for the power computation
+    let mut pending = Vec::new();
+    let mut dropped = 0;
+    loop {
+        if ticker.recv().is_err() {
+            return;
+        }
+        let snapshot = stats.snapshot();
+        let elapsed = last_export.elapsed().as_secs_f64();
+        last_export = Instant::now();
+        let body = metrics_json(
+            conf,
+            &snapshot,
+            hashrate(&last_snapshot, &snapshot, elapsed),
+            &sampler.sample(),
+            start,
+            SystemTime::now(),
+        );
+        last_snapshot = snapshot;
+        post(&agent, &format!("{}/v1/metrics", endpoint), &body);
+
+        if let Some(spans) = spans {
+            for span in spans.try_iter() {
+                if pending.len() < MAX_SPANS {
+                    pending.push(span);
+                } else {
+                    dropped += 1;
+                }
+            }
+            if dropped > 0 {
+                warn!("OTLP export dropped {} spans", dropped);
+                dropped = 0;
+            }
+            if !pending.is_empty() {
+                let body = traces_json(conf, &pending);
+                post(&agent, &format!("{}/v1/traces", endpoint), &body);
+                pending.clear();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn post(agent: &ureq::Agent, url: &str, body: &str) {
+    let result = agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(body);
+    if let Err(err) = result {
+        error!("OTLP export to {} failed: {}", url, err);
+    }
+}
+
+/// The `ExportMetricsServiceRequest` with the hashrate, difficulty and the optional
+/// values as gauges and the hash and share counts as cumulative sums since `start`
+pub fn metrics_json(
+    conf: &OtlpConfig,
+    snapshot: &StatsSnapshot,
+    hashrate: f64,
+    telemetry: &Telemetry,
+    start: SystemTime,
+    now: SystemTime,
+) -> String {
+    let now = unix_nanos(now).to_string();
+    let start = unix_nanos(start).to_string();
+    let gauge = |name: &str, unit: &str, value: f64| {
+        json!({
+            "name": format!("mithril.{}", name),
+            "unit": unit,
+            "gauge": {"dataPoints": [{"timeUnixNano": now, "asDouble": value}]},
+        })
+    };
+    let sum = |name: &str, value: u64| {
+        json!({
+            "name": format!("mithril.{}", name),
+            "unit": "1",
+            "sum": {
+                "aggregationTemporality": 2,
+                "isMonotonic": true,
+                "dataPoints": [{
+                    "startTimeUnixNano": start,
+                    "timeUnixNano": now,
+                    "asInt": value.to_string(),
+                }],
+            },
+        })
+    };
+    let mut metrics = vec![
+        gauge("hashrate", "H/s", hashrate),
+        gauge("difficulty", "1", snapshot.difficulty as f64),
+        sum("hashes", snapshot.total_hashes),
+        sum("shares_accepted", snapshot.shares_accepted),
+        sum("shares_rejected", snapshot.shares_rejected),
+    ];
+    for (name, value) in optional_values(snapshot, telemetry) {
+        metrics.push(gauge(name, "1", value));
+    }
+    json!({
+        "resourceMetrics": [{
+            "resource": resource(conf),
+            "scopeMetrics": [{"scope": {"name": "mithril"}, "metrics": metrics}],
+        }]
+    })
+    .to_string()
+}
+
+/// The `ExportTraceServiceRequest` of the spans
+pub fn traces_json(conf: &OtlpConfig, spans: &[SpanRecord]) -> String {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let start = unix_nanos(span.start);
+            let mut value = json!({
+                "traceId": format!("{:032x}", span.trace_id),
+                "spanId": format!("{:016x}", span.span_id),
+                "name": span.name,
+                "kind": 1,
+                "startTimeUnixNano": start.to_string(),
+                "endTimeUnixNano": (start + span.duration.as_nanos()).to_string(),
+                "attributes": span
+                    .fields
+                    .iter()
+                    .map(|(key, value)| attribute(key, value))
+                    .collect::<Vec<_>>(),
+            });
+            if let Some(parent_id) = span.parent_id {
+                value["parentSpanId"] = json!(format!("{:016x}", parent_id));
+            }
+            value
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": resource(conf),
+            "scopeSpans": [{"scope": {"name": "mithril"}, "spans": spans}],
+        }]
+    })
+    .to_string()
+}
+
+fn resource(conf: &OtlpConfig) -> Value {
+    let mut attributes = vec![attribute(
+        "service.name",
+        &Field::Str(conf.service_name.clone()),
+    )];
+    if !conf.host.is_empty() {
+        attributes.push(attribute("host.name", &Field::Str(conf.host.clone())));
+    }
+    json!({ "attributes": attributes })
+}
+
+fn attribute(key: &str, value: &Field) -> Value {
+    let value = match value {
+        Field::Str(value) => json!({ "stringValue": value }),
+        //64 bit integers are strings in the JSON encoding
+        Field::U64(value) => json!({ "intValue": value.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+/// The timestamps are strings of nanoseconds in the JSON encoding
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_nanos()
+}
@@ -0,0 +1,284 @@
+extern crate ureq;
+
+use super::stats::{MinerStats, StatsSnapshot};
+use super::telemetry::{Telemetry, TelemetrySampler};
+
+use std::io;
+use std::net::UdpSocket;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use strum::EnumString;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wire format used for pushing the metrics
+#[derive(Debug, Clone, Copy, PartialEq, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum PushFormat {
+    /// InfluxDB line protocol, via UDP or the HTTP write endpoint
+    Influx,
+    /// statsd gauges and counters via UDP
+    Statsd,
+}
+
+#[derive(Debug, Clone)]
+pub struct PushConfig {
+    pub enabled: bool,
+    pub format: PushFormat,
+    /// `udp://host:port` or (influx only) `http://host:port/write?db=...`
+    pub address: String,
+    pub interval_seconds: u64,
+    /// influx measurement name or statsd metric prefix
+    pub prefix: String,
+    /// added as `host` tag to influx lines if not empty
+    pub host: String,
+}
+
+/// Where the metric lines are sent to, derived from `PushConfig::address`
+#[derive(Debug, PartialEq)]
+pub enum PushTarget {
+    Udp { addr: String },
+    Http { addr: String, path: String },
+}
+
+pub fn parse_address(address: &str) -> Result<PushTarget, String> {
+    if let Some(addr) = address.strip_prefix("udp://") {
+        return Ok(PushTarget::Udp {
+            addr: addr.to_string(),
+        });
+    }
+    if let Some(rest) = address.strip_prefix("http://") {
+        let (addr, path) = match rest.find('/') {
+            Some(ix) => (&rest[..ix], &rest[ix..]),
+            None => (rest, "/"),
+        };
+        return Ok(PushTarget::Http {
+            addr: addr.to_string(),
+            path: path.to_string(),
+        });
+    }
+    Err(format!(
+        "unsupported push address {}, expected udp://host:port or http://host:port/path",
+        address
+    ))
+}
+
+/// Starts the push thread. Does nothing if pushing is disabled.
+pub fn start(conf: PushConfig, stats: Arc<MinerStats>) {
+    if !conf.enabled {
+        return;
+    }
+
+    let target = match parse_address(&conf.address) {
+        Ok(target) => target,
+        Err(err) => {
+            error!("metric push disabled: {}", err);
+            return;
+        }
+    };
+    if let (PushFormat::Statsd, PushTarget::Http { .. }) = (conf.format, &target) {
+        error!("metric push disabled: statsd only supports udp:// addresses");
+        return;
+    }
+
+    info!(
+        "pushing metrics as {:?} to {} every {}s",
+        conf.format, conf.address, conf.interval_seconds
+    );
+
+    thread::Builder::new()
+        .name("metric push thread".to_string())
+        .spawn(move || {
+            let mut last_snapshot = stats.snapshot();
+            let mut last_push = Instant::now();
+            let mut sampler = TelemetrySampler::new();
+            sampler.sample(); //first sample is the base for the power computation
+            loop {
+                thread::sleep(Duration::from_secs(conf.interval_seconds));
+
+                let snapshot = stats.snapshot();
+                let elapsed = last_push.elapsed().as_secs_f64();
+                last_push = Instant::now();
+                let hashrate = hashrate(&last_snapshot, &snapshot, elapsed);
+                let telemetry = sampler.sample();
+
+                let payload = match conf.format {
+                    PushFormat::Influx => {
+                        influx_lines(&conf, &snapshot, hashrate, &telemetry, unix_time())
+                    }
+                    PushFormat::Statsd => {
+                        statsd_lines(&conf, &last_snapshot, &snapshot, hashrate, &telemetry)
+                    }
+                };
+                if let Err(err) = send(&target, &payload) {
+                    error!("metric push to {} failed: {}", conf.address, err);
+                }
+                last_snapshot = snapshot;
+            }
+        })
+        .expect("metric push thread handle");
+}
+
+pub fn hashrate(previous: &StatsSnapshot, current: &StatsSnapshot, elapsed_seconds: f64) -> f64 {
+    if elapsed_seconds <= 0.0 {
+        return 0.0;
+    }
+    current.total_hashes.saturating_sub(previous.total_hashes) as f64 / elapsed_seconds
+}
+
+/// Formats one InfluxDB line protocol line with all miner values as fields, followed
+/// by one `<prefix>_cpu` line per core with its frequency
+pub fn influx_lines(
+    conf: &PushConfig,
+    snapshot: &StatsSnapshot,
+    hashrate: f64,
+    telemetry: &Telemetry,
+    timestamp: Duration,
+) -> String {
+    let mut host_tag = String::new();
+    if !conf.host.is_empty() {
+        host_tag = format!(",host={}", escape_influx(&conf.host));
+    }
+    let mut line = format!("{}{}", escape_influx(&conf.prefix), host_tag);
+    line.push_str(&format!(
+        " hashrate={:.2},hashes={}i,shares_accepted={}i,shares_rejected={}i,difficulty={}i",
+        hashrate,
+        snapshot.total_hashes,
+        snapshot.shares_accepted,
+        snapshot.shares_rejected,
+        snapshot.difficulty
+    ));
+    for (field, value) in optional_values(snapshot, telemetry) {
+        line.push_str(&format!(",{}={:.4}", field, value));
+    }
+    line.push_str(&format!(" {}", timestamp.as_nanos()));
+
+    let mut lines = vec![line];
+    for (core, freq) in telemetry.core_freq_mhz.iter().enumerate() {
+        if let Some(freq) = freq {
+            lines.push(format!(
+                "{}_cpu{},core={} freq_mhz={:.1} {}",
+                escape_influx(&conf.prefix),
+                host_tag,
+                core,
+                freq,
+                timestamp.as_nanos()
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Formats the hashrate as gauge and the hash and share counts since the last push as
+/// counters, one metric per line
+pub fn statsd_lines(
+    conf: &PushConfig,
+    previous: &StatsSnapshot,
+    current: &StatsSnapshot,
+    hashrate: f64,
+    telemetry: &Telemetry,
+) -> String {
+    let prefix = &conf.prefix;
+    let mut lines = vec![
+        format!("{}.hashrate:{:.2}|g", prefix, hashrate),
+        format!(
+            "{}.hashes:{}|c",
+            prefix,
+            current.total_hashes.saturating_sub(previous.total_hashes)
+        ),
+        format!(
+            "{}.shares_accepted:{}|c",
+            prefix,
+            current
+                .shares_accepted
+                .saturating_sub(previous.shares_accepted)
+        ),
+        format!(
+            "{}.shares_rejected:{}|c",
+            prefix,
+            current
+                .shares_rejected
+                .saturating_sub(previous.shares_rejected)
+        ),
+    ];
+    for (field, value) in optional_values(current, telemetry) {
+        lines.push(format!("{}.{}:{:.4}|g", prefix, field, value));
+    }
+    for (core, freq) in telemetry.core_freq_mhz.iter().enumerate() {
+        if let Some(freq) = freq {
+            lines.push(format!("{}.cpu{}.freq_mhz:{:.1}|g", prefix, core, freq));
+        }
+    }
+    lines.join("\n")
+}
+
+/// The effort, luck, share latency, dataset and telemetry values that are known
+pub fn optional_values(
+    snapshot: &StatsSnapshot,
+    telemetry: &Telemetry,
+) -> Vec<(&'static str, f64)> {
+    let freqs = telemetry.freq_min_avg_max_mhz();
+    [
+        ("effort", snapshot.current_effort()),
+        ("average_effort", snapshot.average_effort()),
+        ("luck", snapshot.luck()),
+        (
+            "share_latency_p50_ms",
+            snapshot.share_latency_p50.map(|l| l.as_secs_f64() * 1000.0),
+        ),
+        (
+            "share_latency_p99_ms",
+            snapshot.share_latency_p99.map(|l| l.as_secs_f64() * 1000.0),
+        ),
+        ("dataset_hit_rate", snapshot.dataset_hit_rate),
+        (
+            "dataset_read_p50_ns",
+            snapshot.dataset_read_p50.map(|l| l.as_nanos() as f64),
+        ),
+        (
+            "dataset_read_p99_ns",
+            snapshot.dataset_read_p99.map(|l| l.as_nanos() as f64),
+        ),
+        ("cpu_freq_min_mhz", freqs.map(|f| f.0)),
+        ("cpu_freq_avg_mhz", freqs.map(|f| f.1)),
+        ("cpu_freq_max_mhz", freqs.map(|f| f.2)),
+        ("package_temp_celsius", telemetry.package_temp_celsius),
+        ("power_watts", telemetry.power_watts),
+    ]
+    .iter()
+    .filter_map(|(field, value)| value.map(|v| (*field, v)))
+    .collect()
+}
+
+fn escape_influx(s: &str) -> String {
+    s.replace(',', "\\,")
+        .replace(' ', "\\ ")
+        .replace('=', "\\=")
+}
+
+fn unix_time() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+}
+
+pub fn send(target: &PushTarget, payload: &str) -> io::Result<()> {
+    match target {
+        PushTarget::Udp { addr } => {
+            let socket = UdpSocket::bind("0.0.0.0:0")?;
+            socket.send_to(payload.as_bytes(), addr)?;
+            Ok(())
+        }
+        PushTarget::Http { addr, path } => http_post(addr, path, payload),
+    }
+}
+
+fn http_post(addr: &str, path: &str, body: &str) -> io::Result<()> {
+    ureq::post(&format!("http://{}{}", addr, path))
+        .timeout(HTTP_TIMEOUT)
+        .set("Content-Type", "text/plain")
+        .send_string(body)
+        .map(|_| ())
+        .map_err(|err| io::Error::other(err.to_string()))
+}
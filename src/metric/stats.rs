@@ -0,0 +1,372 @@
+use super::latency::LatencyHistogram;
+use super::lifetime::LifetimeStats;
+use crate::earnings::{self, Estimate, NetworkStats};
+use crate::hardware::HardwareSummary;
+use crate::pow::PowMemory;
+use crate::randomx::dataset_stats::{self, DatasetStats};
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Number of shares kept for `recent_shares`
+const RECENT_SHARES: usize = 20;
+/// Length of the windows of the dataset hit rate
+pub const DATASET_WINDOW: Duration = Duration::from_secs(60);
+
+/// Counters that live for the whole process. In contrast to `Metric`, which is
+/// restarted with every main loop iteration, these are never reset.
+#[derive(Default)]
+pub struct MinerStats {
+    total_hashes: AtomicU64,
+    shares_accepted: AtomicU64,
+    shares_rejected: AtomicU64,
+    /// sum of the pool difficulty of all accepted shares
+    accepted_difficulty: AtomicU64,
+    /// total_hashes at the time the last share was accepted
+    last_share_hashes: AtomicU64,
+    /// pool difficulty of the current job
+    difficulty: AtomicU64,
+    /// highest difficulty an accepted share hash reached
+    best_share_difficulty: AtomicU64,
+    /// pool address and time of the current connection
+    connection: Mutex<Option<(String, Instant)>>,
+    connects: AtomicU64,
+    /// end of the last connection
+    disconnected_at: Mutex<Option<Instant>>,
+    /// hashing was stopped via the console or the management API
+    paused: AtomicBool,
+    /// hashes per worker thread of the current worker pool
+    thread_hashes: Mutex<Vec<u64>>,
+    recent_shares: Mutex<VecDeque<ShareRecord>>,
+    share_latency: Mutex<LatencyHistogram>,
+    /// memory of the current job, i.e. the RandomX dataset
+    memory: Mutex<Option<Arc<dyn PowMemory>>>,
+    /// corrupted dataset items and checksums the dataset checks found
+    dataset_errors: AtomicU64,
+    /// errors the pools sent outside of share results
+    stratum_errors: AtomicU64,
+    /// totals of previous runs and the start of this run
+    lifetime_base: Mutex<Option<(LifetimeStats, Instant)>>,
+    dataset_window: Mutex<DatasetWindow>,
+    /// probed at startup
+    hardware: Mutex<Option<HardwareSummary>>,
+    /// polled by the earnings estimator, with the pool fee in percent
+    network: Mutex<Option<(NetworkStats, f64)>>,
+}
+
+/// The dataset hit rate of the last full window. In the lazy modes it shows how far
+/// the dataset has warmed up, a rate that stays low means a too small memory budget.
+#[derive(Debug, Default)]
+pub struct DatasetWindow {
+    /// start of the current window and the dataset reads then
+    start: Option<(Instant, DatasetStats)>,
+    hit_rate: Option<f64>,
+}
+
+impl DatasetWindow {
+    /// Ends the window if it is older than `length`, returns the hit rate of the last
+    /// full window. `totals` are the dataset reads since the start.
+    pub fn update(&mut self, now: Instant, totals: &DatasetStats, length: Duration) -> Option<f64> {
+        match &self.start {
+            None => self.start = Some((now, totals.clone())),
+            Some((start, start_totals)) if now.duration_since(*start) >= length => {
+                self.hit_rate = totals.since(start_totals).hit_rate();
+                self.start = Some((now, totals.clone()));
+            }
+            Some(_) => {}
+        }
+        self.hit_rate
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShareOutcome {
+    Accepted { effort: Option<f64> },
+    Rejected { err: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareRecord {
+    pub time: SystemTime,
+    pub job_id: String,
+    pub difficulty: u64,
+    pub outcome: ShareOutcome,
+}
+
+/// A consistent-enough copy of the counters for reporting
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatsSnapshot {
+    pub total_hashes: u64,
+    pub shares_accepted: u64,
+    pub shares_rejected: u64,
+    pub accepted_difficulty: u64,
+    pub last_share_hashes: u64,
+    pub difficulty: u64,
+    pub best_share_difficulty: u64,
+    /// median and 99th percentile of the share round-trip time
+    pub share_latency_p50: Option<Duration>,
+    pub share_latency_p99: Option<Duration>,
+    /// see `DatasetWindow`, None without dataset reads
+    pub dataset_hit_rate: Option<f64>,
+    /// dataset read latency since the start, None without the dataset-stats feature
+    pub dataset_read_p50: Option<Duration>,
+    pub dataset_read_p99: Option<Duration>,
+}
+
+impl MinerStats {
+    pub fn new() -> MinerStats {
+        MinerStats::default()
+    }
+
+    pub fn add_hashes(&self, cnt: u64) {
+        self.total_hashes.fetch_add(cnt, Ordering::SeqCst);
+    }
+
+    pub fn set_difficulty(&self, difficulty: u64) {
+        self.difficulty.store(difficulty, Ordering::SeqCst);
+    }
+
+    /// Counts the share and returns the effort it took to find it.
+    pub fn share_accepted(&self, difficulty: u64) -> Option<f64> {
+        let total_hashes = self.total_hashes.load(Ordering::SeqCst);
+        let last_share_hashes = self.last_share_hashes.swap(total_hashes, Ordering::SeqCst);
+        self.shares_accepted.fetch_add(1, Ordering::SeqCst);
+        self.accepted_difficulty
+            .fetch_add(difficulty, Ordering::SeqCst);
+        effort(total_hashes.saturating_sub(last_share_hashes), difficulty)
+    }
+
+    /// `difficulty` is the difficulty the share hash reached, not the pool difficulty
+    pub fn best_share(&self, difficulty: u64) {
+        self.best_share_difficulty
+            .fetch_max(difficulty, Ordering::SeqCst);
+    }
+
+    /// Time between submitting a share and the pool response
+    pub fn share_latency(&self, latency: Duration) {
+        if let Ok(mut histogram) = self.share_latency.lock() {
+            histogram.record(latency);
+        }
+    }
+
+    pub fn latency_histogram(&self) -> LatencyHistogram {
+        self.share_latency
+            .lock()
+            .map(|h| h.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn share_rejected(&self) {
+        self.shares_rejected.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn connected(&self, pool_address: &str) {
+        self.connects.fetch_add(1, Ordering::SeqCst);
+        if let Ok(mut connection) = self.connection.lock() {
+            *connection = Some((pool_address.to_string(), Instant::now()));
+        }
+    }
+
+    pub fn disconnected(&self) {
+        if let Ok(mut connection) = self.connection.lock() {
+            *connection = None;
+        }
+        if let Ok(mut disconnected_at) = self.disconnected_at.lock() {
+            *disconnected_at = Some(Instant::now());
+        }
+    }
+
+    /// When the last connection ended, None if there was none yet
+    pub fn disconnected_at(&self) -> Option<Instant> {
+        self.disconnected_at.lock().ok().and_then(|at| *at)
+    }
+
+    /// The pool address and how long the miner is connected to it
+    pub fn connection(&self) -> Option<(String, Duration)> {
+        let connection = self.connection.lock().ok()?;
+        connection
+            .as_ref()
+            .map(|(pool, since)| (pool.clone(), since.elapsed()))
+    }
+
+    /// Number of successful pool logins, including the first one
+    pub fn connects(&self) -> u64 {
+        self.connects.load(Ordering::SeqCst)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Resets the per thread counters for a new worker pool
+    pub fn set_thread_count(&self, num_threads: usize) {
+        if let Ok(mut thread_hashes) = self.thread_hashes.lock() {
+            *thread_hashes = vec![0; num_threads];
+        }
+    }
+
+    /// Adds the hashes to the total and to the counter of the worker thread
+    pub fn add_thread_hashes(&self, thread: usize, cnt: u64) {
+        self.add_hashes(cnt);
+        if let Ok(mut thread_hashes) = self.thread_hashes.lock() {
+            if thread_hashes.len() <= thread {
+                thread_hashes.resize(thread + 1, 0);
+            }
+            thread_hashes[thread] += cnt;
+        }
+    }
+
+    pub fn thread_hashes(&self) -> Vec<u64> {
+        self.thread_hashes
+            .lock()
+            .map(|t| t.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_memory(&self, memory: Arc<dyn PowMemory>) {
+        if let Ok(mut current) = self.memory.lock() {
+            *current = Some(memory);
+        }
+    }
+
+    /// None until the first job was received
+    pub fn memory(&self) -> Option<Arc<dyn PowMemory>> {
+        self.memory.lock().ok().and_then(|m| m.clone())
+    }
+
+    pub fn set_hardware(&self, hardware: HardwareSummary) {
+        if let Ok(mut current) = self.hardware.lock() {
+            *current = Some(hardware);
+        }
+    }
+
+    pub fn hardware(&self) -> Option<HardwareSummary> {
+        self.hardware.lock().ok().and_then(|h| h.clone())
+    }
+
+    pub fn set_network_stats(&self, network: NetworkStats, pool_fee_percent: f64) {
+        if let Ok(mut current) = self.network.lock() {
+            *current = Some((network, pool_fee_percent));
+        }
+    }
+
+    /// The earnings of the hashrate at the current pool difficulty
+    pub fn earnings(&self, hashrate: f64) -> Estimate {
+        let network = self.network.lock().ok().and_then(|n| *n);
+        earnings::estimate(
+            hashrate,
+            self.difficulty.load(Ordering::SeqCst),
+            network.as_ref().map(|(network, _)| network),
+            network.map_or(0.0, |(_, fee)| fee),
+        )
+    }
+
+    pub fn add_dataset_errors(&self, errors: u64) {
+        self.dataset_errors.fetch_add(errors, Ordering::SeqCst);
+    }
+
+    pub fn dataset_errors(&self) -> u64 {
+        self.dataset_errors.load(Ordering::SeqCst)
+    }
+
+    pub fn stratum_error(&self) {
+        self.stratum_errors.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn stratum_errors(&self) -> u64 {
+        self.stratum_errors.load(Ordering::SeqCst)
+    }
+
+    /// Keeps the last shares for display
+    pub fn record_share(&self, record: ShareRecord) {
+        if let Ok(mut recent_shares) = self.recent_shares.lock() {
+            if recent_shares.len() == RECENT_SHARES {
+                recent_shares.pop_front();
+            }
+            recent_shares.push_back(record);
+        }
+    }
+
+    /// The last shares, the newest first
+    pub fn recent_shares(&self) -> Vec<ShareRecord> {
+        self.recent_shares
+            .lock()
+            .map(|r| r.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let latency = self.latency_histogram();
+        let dataset = dataset_stats::snapshot();
+        StatsSnapshot {
+            total_hashes: self.total_hashes.load(Ordering::SeqCst),
+            shares_accepted: self.shares_accepted.load(Ordering::SeqCst),
+            shares_rejected: self.shares_rejected.load(Ordering::SeqCst),
+            accepted_difficulty: self.accepted_difficulty.load(Ordering::SeqCst),
+            last_share_hashes: self.last_share_hashes.load(Ordering::SeqCst),
+            difficulty: self.difficulty.load(Ordering::SeqCst),
+            best_share_difficulty: self.best_share_difficulty.load(Ordering::SeqCst),
+            share_latency_p50: latency.percentile(0.5),
+            share_latency_p99: latency.percentile(0.99),
+            dataset_hit_rate: self
+                .dataset_window
+                .lock()
+                .ok()
+                .and_then(|mut window| window.update(Instant::now(), &dataset, DATASET_WINDOW)),
+            dataset_read_p50: dataset.latency_percentile(0.5),
+            dataset_read_p99: dataset.latency_percentile(0.99),
+        }
+    }
+
+    /// Sets the totals of the previous runs, the uptime is counted from now on
+    pub fn set_lifetime_base(&self, base: LifetimeStats) {
+        if let Ok(mut lifetime_base) = self.lifetime_base.lock() {
+            *lifetime_base = Some((base, Instant::now()));
+        }
+    }
+
+    /// The lifetime totals including this run, None if no base was set
+    pub fn lifetime(&self) -> Option<LifetimeStats> {
+        let (base, started) = self.lifetime_base.lock().ok()?.clone()?;
+        Some(base.add(&self.snapshot(), started.elapsed()))
+    }
+}
+
+impl StatsSnapshot {
+    /// Hashes done since the last accepted share relative to the current pool difficulty.
+    /// 1.0 means exactly the expected number of hashes was needed so far.
+    pub fn current_effort(&self) -> Option<f64> {
+        effort(
+            self.total_hashes.saturating_sub(self.last_share_hashes),
+            self.difficulty,
+        )
+    }
+
+    /// Hashes needed for all accepted shares relative to their summed difficulty.
+    pub fn average_effort(&self) -> Option<f64> {
+        effort(self.last_share_hashes, self.accepted_difficulty)
+    }
+
+    /// Expected hashes (the summed difficulty of accepted shares) relative to all
+    /// hashes done, including the current round. > 1.0 means more shares than
+    /// expected were found.
+    pub fn luck(&self) -> Option<f64> {
+        if self.total_hashes == 0 {
+            return None;
+        }
+        Some(self.accepted_difficulty as f64 / self.total_hashes as f64)
+    }
+}
+
+fn effort(hashes: u64, difficulty: u64) -> Option<f64> {
+    if difficulty == 0 {
+        return None;
+    }
+    Some(hashes as f64 / difficulty as f64)
+}
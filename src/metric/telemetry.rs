@@ -0,0 +1,220 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// CPU frequency, temperature and power readings. Values that are not available on
+/// the platform (or are not readable by the user) are missing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Telemetry {
+    /// current frequency per core, indexed by cpu number
+    pub core_freq_mhz: Vec<Option<f64>>,
+    /// highest package temperature over all packages
+    pub package_temp_celsius: Option<f64>,
+    /// average power of all packages since the last sample
+    pub power_watts: Option<f64>,
+}
+
+impl Telemetry {
+    pub fn freq_min_avg_max_mhz(&self) -> Option<(f64, f64, f64)> {
+        let freqs: Vec<f64> = self.core_freq_mhz.iter().filter_map(|f| *f).collect();
+        if freqs.is_empty() {
+            return None;
+        }
+        let min = freqs.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = freqs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let avg = freqs.iter().sum::<f64>() / freqs.len() as f64;
+        Some((min, avg, max))
+    }
+}
+
+/// The energy counter of one RAPL package zone
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnergyCounter {
+    pub energy_uj: u64,
+    /// the counter wraps to 0 after this value, None if unknown
+    pub max_energy_range_uj: Option<u64>,
+}
+
+/// The energy in joules between two readings of the same counters, a wrapped counter is
+/// counted up to its range. None if the zones differ or a wrapped counter has no range.
+pub fn energy_joules(start: &[EnergyCounter], end: &[EnergyCounter]) -> Option<f64> {
+    if start.is_empty() || start.len() != end.len() {
+        return None;
+    }
+    let mut total_uj = 0;
+    for (start, end) in start.iter().zip(end) {
+        total_uj += if end.energy_uj >= start.energy_uj {
+            end.energy_uj - start.energy_uj
+        } else {
+            end.max_energy_range_uj?.checked_sub(start.energy_uj)? + end.energy_uj
+        };
+    }
+    Some(total_uj as f64 / 1_000_000.0)
+}
+
+struct RaplReading {
+    energy_uj: u64,
+    at: Instant,
+}
+
+/// Samples the telemetry from sysfs (Linux only). The power draw is computed from the
+/// RAPL energy counters, so it is only available from the second sample on.
+pub struct TelemetrySampler {
+    sys_root: PathBuf,
+    last_rapl: Option<RaplReading>,
+}
+
+impl TelemetrySampler {
+    pub fn new() -> TelemetrySampler {
+        TelemetrySampler::with_sys_root(Path::new("/sys"))
+    }
+
+    /// For testing, reads from a fake sysfs tree
+    pub fn with_sys_root(sys_root: &Path) -> TelemetrySampler {
+        TelemetrySampler {
+            sys_root: sys_root.to_path_buf(),
+            last_rapl: None,
+        }
+    }
+
+    pub fn sample(&mut self) -> Telemetry {
+        Telemetry {
+            core_freq_mhz: self.core_freqs(),
+            package_temp_celsius: self.package_temp(),
+            power_watts: self.power(),
+        }
+    }
+
+    fn core_freqs(&self) -> Vec<Option<f64>> {
+        let cpu_dir = self.sys_root.join("devices/system/cpu");
+        let mut cpus: Vec<usize> = dir_names(&cpu_dir)
+            .iter()
+            .filter_map(|name| name.strip_prefix("cpu")?.parse().ok())
+            .collect();
+        cpus.sort_unstable();
+
+        let mut freqs = Vec::new();
+        for cpu in cpus {
+            let path = cpu_dir.join(format!("cpu{}/cpufreq/scaling_cur_freq", cpu));
+            if freqs.len() <= cpu {
+                freqs.resize(cpu + 1, None);
+            }
+            freqs[cpu] = read_u64(&path).map(|khz| khz as f64 / 1000.0);
+        }
+        freqs
+    }
+
+    fn package_temp(&self) -> Option<f64> {
+        let mut temps = Vec::new();
+
+        let hwmon_dir = self.sys_root.join("class/hwmon");
+        for hwmon in dir_names(&hwmon_dir) {
+            let dir = hwmon_dir.join(hwmon);
+            let package_label: fn(&str) -> bool = match read_string(&dir.join("name")).as_deref() {
+                Some("coretemp") => |l| l.starts_with("Package id"),
+                Some("k10temp") | Some("zenpower") => |l| l == "Tctl" || l == "Tdie",
+                _ => continue,
+            };
+            for file in dir_names(&dir) {
+                if let Some(prefix) = file.strip_suffix("_label") {
+                    let label = read_string(&dir.join(&file)).unwrap_or_default();
+                    if package_label(&label) {
+                        if let Some(milli) = read_u64(&dir.join(format!("{}_input", prefix))) {
+                            temps.push(milli as f64 / 1000.0);
+                        }
+                    }
+                }
+            }
+        }
+
+        if temps.is_empty() {
+            let thermal_dir = self.sys_root.join("class/thermal");
+            for zone in dir_names(&thermal_dir) {
+                let dir = thermal_dir.join(zone);
+                if read_string(&dir.join("type")).as_deref() == Some("x86_pkg_temp") {
+                    if let Some(milli) = read_u64(&dir.join("temp")) {
+                        temps.push(milli as f64 / 1000.0);
+                    }
+                }
+            }
+        }
+
+        temps.into_iter().reduce(f64::max)
+    }
+
+    fn power(&mut self) -> Option<f64> {
+        let energy_uj = self.rapl_energy_uj()?;
+        let now = Instant::now();
+        let power = self.last_rapl.as_ref().and_then(|last| {
+            let seconds = now.duration_since(last.at).as_secs_f64();
+            if seconds <= 0.0 || energy_uj < last.energy_uj {
+                return None; //counter wrapped
+            }
+            Some((energy_uj - last.energy_uj) as f64 / 1_000_000.0 / seconds)
+        });
+        self.last_rapl = Some(RaplReading { energy_uj, at: now });
+        power
+    }
+
+    /// Sum of the energy counters of all packages (the top level RAPL zones)
+    fn rapl_energy_uj(&self) -> Option<u64> {
+        Some(
+            self.energy_counters()?
+                .iter()
+                .map(|counter| counter.energy_uj)
+                .sum(),
+        )
+    }
+
+    /// The energy counters of all packages, sorted by zone. None if there are none or
+    /// one is not readable, which needs root on recent kernels.
+    pub fn energy_counters(&self) -> Option<Vec<EnergyCounter>> {
+        let powercap_dir = self.sys_root.join("class/powercap");
+        let mut zones: Vec<String> = dir_names(&powercap_dir)
+            .into_iter()
+            .filter(|name| {
+                name.strip_prefix("intel-rapl:")
+                    .map(|n| !n.contains(':'))
+                    .unwrap_or(false)
+            })
+            .collect();
+        if zones.is_empty() {
+            return None;
+        }
+        zones.sort();
+        zones
+            .iter()
+            .map(|zone| {
+                let dir = powercap_dir.join(zone);
+                Some(EnergyCounter {
+                    energy_uj: read_u64(&dir.join("energy_uj"))?,
+                    max_energy_range_uj: read_u64(&dir.join("max_energy_range_uj")),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for TelemetrySampler {
+    fn default() -> Self {
+        TelemetrySampler::new()
+    }
+}
+
+fn dir_names(dir: &Path) -> Vec<String> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.file_name().into_string().ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn read_string(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    read_string(path)?.parse().ok()
+}
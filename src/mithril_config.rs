@@ -1,43 +1,276 @@
 extern crate config;
 
+use crate::api::{ApiConfig, HealthConfig};
+use crate::cgroup;
+use crate::cluster::{self, ClusterConfig};
+use crate::console::{ConsoleConfig, HashrateFormat, TimestampFormat};
+use crate::dual::DualConfig;
+use crate::earnings::EarningsConfig;
+use crate::forensics::ForensicsConfig;
+use crate::integrity::IntegrityConfig;
+use crate::logging::LogConfig;
+use crate::metric::otlp::OtlpConfig;
+use crate::metric::push::{PushConfig, PushFormat};
 use crate::metric::MetricConfig;
-use crate::stratum::stratum_data::PoolConfig;
+use crate::payout::PayoutWallet;
+use crate::pow;
+use crate::pow::randomx::{CustomVariant, DatasetInitConfig, MemoryConfig, MemoryMode, Variant};
+use crate::privileges::PrivilegeConfig;
+use crate::profile;
+use crate::profile::{Priority, Profile, MAX_THROTTLE_PERCENT};
+use crate::profit::{CoinConfig, ProfitConfig};
+use crate::randomx::large_alloc::AllocKind;
+use crate::randomx::pages::NumaPolicy;
+use crate::randomx::RandomXConfig;
+use crate::share_db::ShareDbConfig;
+use crate::stratum::stratum_data::{PoolConfig, SoloConfig, DEFAULT_ALGORITHM};
+use crate::update_check::{self, UpdateCheckConfig};
+use crate::webhook::{AlertEvent, WebhookConfig, ALL_EVENTS, DEFAULT_TEMPLATE};
 use crate::worker::worker_pool::WorkerConfig;
 
 use self::config::{Config, ConfigError, File, FileFormat};
+use log::LevelFilter;
 use std;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use strum::{Display, EnumString};
 
 pub const CONFIG_FILE_NAME: &str = "config.toml";
+/// used if there is no config file but the pool is given on the command line
+pub const DEFAULT_CONFIG: &str = include_str!("../default_config.toml");
+
+/// Format of the config file, both use the same sections and keys
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// JSON for `.json` files, TOML otherwise
+    pub fn detect(conf_file: &Path) -> ConfigFormat {
+        match conf_file.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    pub(crate) fn file_format(self) -> FileFormat {
+        match self {
+            ConfigFormat::Toml => FileFormat::Toml,
+            ConfigFormat::Json => FileFormat::Json,
+        }
+    }
+}
 
 /// contains all configurations for mithril
 #[derive(Clone)]
 pub struct MithrilConfig {
+    /// the primary pool
     pub pool_conf: PoolConfig,
+    /// tried in order if the primary pool is not reachable
+    pub backup_pools: Vec<PoolConfig>,
+    /// mined at the same time as the primary pool, None without a `[dual_pool]`
+    pub dual_pool: Option<DualConfig>,
+    /// the `[[payout]]` wallets the pool wallet is split between, empty for none
+    pub payout_wallets: Vec<PayoutWallet>,
+    pub profit_conf: ProfitConfig,
+    /// the `[[coin]]` entries the profit switcher chooses from
+    pub coins: Vec<CoinConfig>,
+    pub earnings_conf: EarningsConfig,
+    /// the `[[randomx]]` parameter sets, usable as `algorithm` once registered
+    pub randomx_variants: Vec<CustomVariant>,
     pub worker_conf: WorkerConfig,
+    /// the `[[profile]]` entries, the built-in profiles are not included
+    pub profiles: Vec<Profile>,
+    pub memory_conf: MemoryConfig,
+    pub dataset_init_conf: DatasetInitConfig,
+    pub integrity_conf: IntegrityConfig,
     pub metric_conf: MetricConfig,
+    pub push_conf: PushConfig,
+    pub otlp_conf: OtlpConfig,
+    pub webhook_conf: WebhookConfig,
+    pub log_conf: LogConfig,
+    pub console_conf: ConsoleConfig,
+    pub api_conf: ApiConfig,
+    pub cluster_conf: ClusterConfig,
+    pub share_db_conf: ShareDbConfig,
+    pub forensics_conf: ForensicsConfig,
+    pub privilege_conf: PrivilegeConfig,
+    pub update_check_conf: UpdateCheckConfig,
     pub donation_conf: DonationConfig,
 }
 
+impl MithrilConfig {
+    /// The primary pool followed by the backup pools, only the coordinator for a cluster
+    /// agent
+    pub fn pools(&self) -> Vec<&PoolConfig> {
+        if let Some(coordinator) = &self.cluster_conf.coordinator {
+            return vec![coordinator];
+        }
+        std::iter::once(&self.pool_conf)
+            .chain(self.backup_pools.iter())
+            .collect()
+    }
+
+    /// A user or built-in profile
+    pub fn profile(&self, name: &str) -> Option<Profile> {
+        profile::find(name, &self.profiles)
+    }
+
+    /// Applies the profile to the worker config, false if there is no profile with
+    /// this name. The name is kept in `worker_conf.profile` either way, so that the
+    /// validation reports it.
+    pub fn select_profile(&mut self, name: &str) -> bool {
+        match self.profile(name) {
+            Some(profile) => {
+                profile.apply(&mut self.worker_conf, cgroup::available_cpus() as u64);
+                true
+            }
+            None => {
+                self.worker_conf.profile = name.to_string();
+                false
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct DonationConfig {
     pub percentage: f64,
 }
 
+/// Reads the config file, the format is detected from the file extension
 pub fn read_config(conf_file: &Path, filename: &str) -> Result<MithrilConfig, config::ConfigError> {
-    let config = parse_conf(conf_file, filename)?;
+    read_config_format(conf_file, filename, ConfigFormat::detect(conf_file))
+}
 
-    let pool_conf = pool_config(&config)?;
-    let worker_conf = worker_config(&config)?;
-    let metric_conf = metric_config(&config)?;
-    let donation_conf = donation_config(&config)?;
+pub fn read_config_format(
+    conf_file: &Path,
+    filename: &str,
+    format: ConfigFormat,
+) -> Result<MithrilConfig, config::ConfigError> {
+    mithril_config(&parse_conf(conf_file, filename, format)?)
+}
+
+/// The config of `default_config.toml`
+pub fn default_config() -> Result<MithrilConfig, ConfigError> {
+    read_config_content(DEFAULT_CONFIG, ConfigFormat::Toml)
+}
 
-    Ok(MithrilConfig {
+/// Reads the config from a string instead of a file
+pub fn read_config_content(
+    content: &str,
+    format: ConfigFormat,
+) -> Result<MithrilConfig, ConfigError> {
+    let config = Config::builder()
+        .add_source(File::from_str(content, format.file_format()))
+        .build()?;
+    mithril_config(&config)
+}
+
+/// Reads the config with the fleet config of the coordinator on top. The `[cluster]`
+/// section and the keys in `cluster.local` keep the values of the file.
+pub fn read_config_with_fleet(
+    content: &str,
+    format: ConfigFormat,
+    fleet_config: &str,
+) -> Result<MithrilConfig, ConfigError> {
+    let local = Config::builder()
+        .add_source(File::from_str(content, format.file_format()))
+        .build()?;
+    let mut builder = Config::builder()
+        .add_source(File::from_str(content, format.file_format()))
+        .add_source(File::from_str(fleet_config, FileFormat::Toml));
+    let local_keys = mithril_config(&local)?.cluster_conf.local;
+    for key in std::iter::once("cluster".to_string()).chain(local_keys) {
+        match local.get::<config::Value>(&key) {
+            Ok(value) => builder = builder.set_override(key, value)?,
+            Err(ConfigError::NotFound(_)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    let mut config = mithril_config(&builder.build()?)?;
+    config.cluster_conf.fleet_config = Some(fleet_config.to_string());
+    Ok(config)
+}
+
+/// Checks a fleet config on top of the default config, it must not change the
+/// `[cluster]` section of the agents
+pub fn check_fleet_config(fleet_config: &str) -> Result<(), ConfigError> {
+    let fleet = Config::builder()
+        .add_source(File::from_str(fleet_config, FileFormat::Toml))
+        .build()?;
+    if fleet.get::<config::Value>("cluster").is_ok() {
+        return Err(ConfigError::Message(
+            "the fleet config cannot set the [cluster] section of the agents".to_string(),
+        ));
+    }
+    read_config_with_fleet(DEFAULT_CONFIG, ConfigFormat::Toml, fleet_config).map(|_| ())
+}
+
+fn mithril_config(config: &Config) -> Result<MithrilConfig, ConfigError> {
+    let pool_conf = pool_config(config, "pool", None)?;
+    let backup_pools = backup_pools(config, &pool_conf)?;
+    let dual_pool = dual_pool(config, &pool_conf)?;
+    let payout_wallets = payout_wallets(config)?;
+    let profit_conf = profit_config(config)?;
+    let coins = coins(config, &pool_conf)?;
+    let earnings_conf = earnings_config(config)?;
+    let randomx_variants = randomx_variants(config)?;
+    let worker_conf = worker_config(config)?;
+    let profiles = profiles(config)?;
+    let memory_conf = memory_config(config)?;
+    let dataset_init_conf = dataset_init_config(config)?;
+    let integrity_conf = integrity_config(config)?;
+    let metric_conf = metric_config(config)?;
+    let push_conf = push_config(config)?;
+    let otlp_conf = otlp_config(config)?;
+    let webhook_conf = webhook_config(config)?;
+    let log_conf = log_config(config)?;
+    let console_conf = console_config(config)?;
+    let api_conf = api_config(config)?;
+    let cluster_conf = cluster_config(config, &pool_conf)?;
+    let share_db_conf = share_db_config(config)?;
+    let forensics_conf = forensics_config(config)?;
+    let privilege_conf = privilege_config(config)?;
+    let update_check_conf = update_check_config(config)?;
+    let donation_conf = donation_config(config)?;
+
+    let mut mithril_conf = MithrilConfig {
         pool_conf,
+        backup_pools,
+        dual_pool,
+        payout_wallets,
+        profit_conf,
+        coins,
+        earnings_conf,
+        randomx_variants,
         worker_conf,
+        profiles,
+        memory_conf,
+        dataset_init_conf,
+        integrity_conf,
         metric_conf,
+        push_conf,
+        otlp_conf,
+        webhook_conf,
+        log_conf,
+        console_conf,
+        api_conf,
+        cluster_conf,
+        share_db_conf,
+        forensics_conf,
+        privilege_conf,
+        update_check_conf,
         donation_conf,
-    })
+    };
+    //the profile overrides the thread settings of [worker]
+    let profile = mithril_conf.worker_conf.profile.clone();
+    if !profile.is_empty() {
+        mithril_conf.select_profile(&profile);
+    }
+    Ok(mithril_conf)
 }
 
 fn donation_config(conf: &Config) -> Result<DonationConfig, ConfigError> {
@@ -45,14 +278,281 @@ fn donation_config(conf: &Config) -> Result<DonationConfig, ConfigError> {
     Ok(DonationConfig { percentage })
 }
 
-fn pool_config(conf: &Config) -> Result<PoolConfig, ConfigError> {
-    let pool_address = conf.get_string("pool.pool_address")?;
-    let wallet_address = conf.get_string("pool.wallet_address")?;
-    let pool_password = conf.get_string("pool.pool_password")?;
+/// The `[[backup_pool]]` entries, wallet and password default to the primary pool
+fn backup_pools(conf: &Config, primary: &PoolConfig) -> Result<Vec<PoolConfig>, ConfigError> {
+    let count = match conf.get_array("backup_pool") {
+        Err(ConfigError::NotFound(_)) => 0,
+        other => other?.len(),
+    };
+    (0..count)
+        .map(|i| pool_config(conf, &format!("backup_pool[{}]", i), Some(primary)))
+        .collect()
+}
+
+/// The `[dual_pool]`, wallet and password default to the primary pool
+fn dual_pool(conf: &Config, primary: &PoolConfig) -> Result<Option<DualConfig>, ConfigError> {
+    if let Err(ConfigError::NotFound(_)) = conf.get_table("dual_pool") {
+        return Ok(None);
+    }
+    let pool_conf = pool_config(conf, "dual_pool", Some(primary))?;
+    if pool_conf.daemon {
+        return Err(ConfigError::Message(
+            "dual_pool: the dual pool cannot be a solo daemon".to_string(),
+        ));
+    }
+    let num_threads = or_default(get_u64_no_zero(conf, "dual_pool.num_threads"), 1)?;
+    Ok(Some(DualConfig {
+        pool_conf,
+        num_threads,
+    }))
+}
+
+/// The `[[payout]]` entries, their percentages add up to 100
+fn payout_wallets(conf: &Config) -> Result<Vec<PayoutWallet>, ConfigError> {
+    let count = match conf.get_array("payout") {
+        Err(ConfigError::NotFound(_)) => 0,
+        other => other?.len(),
+    };
+    let wallets = (0..count)
+        .map(|i| {
+            let section = format!("payout[{}]", i);
+            let wallet_address = conf.get_string(&format!("{}.wallet_address", section))?;
+            let percentage = conf.get_float(&format!("{}.percentage", section))?;
+            if percentage <= 0.0 {
+                return Err(ConfigError::Message(format!(
+                    "{}.percentage has to be > 0, was {}",
+                    section, percentage
+                )));
+            }
+            Ok(PayoutWallet {
+                wallet_address,
+                percentage,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let total: f64 = wallets.iter().map(|w| w.percentage).sum();
+    if !wallets.is_empty() && (total - 100.0).abs() > 0.01 {
+        return Err(ConfigError::Message(format!(
+            "the payout percentages have to add up to 100, were {}",
+            total
+        )));
+    }
+    Ok(wallets)
+}
+
+fn profit_config(conf: &Config) -> Result<ProfitConfig, ConfigError> {
+    let enabled = or_default(conf.get_bool("profit_switch.enabled"), false)?;
+    let url = or_default(conf.get_string("profit_switch.url"), "".to_string())?;
+    if enabled && url.is_empty() {
+        return Err(ConfigError::Message(
+            "profit_switch.url has to be set if profit switching is enabled".to_string(),
+        ));
+    }
+    let interval_minutes = or_default(get_u64_no_zero(conf, "profit_switch.interval_minutes"), 10)?;
+    let hysteresis_percent = or_default(conf.get_float("profit_switch.hysteresis_percent"), 5.0)?;
+    Ok(ProfitConfig {
+        enabled,
+        url,
+        interval_minutes,
+        hysteresis_percent,
+    })
+}
+
+/// The `[[coin]]` entries, wallet and password default to the primary pool
+fn coins(conf: &Config, primary: &PoolConfig) -> Result<Vec<CoinConfig>, ConfigError> {
+    let count = match conf.get_array("coin") {
+        Err(ConfigError::NotFound(_)) => 0,
+        other => other?.len(),
+    };
+    (0..count)
+        .map(|i| {
+            let section = format!("coin[{}]", i);
+            Ok(CoinConfig {
+                name: conf.get_string(&format!("{}.name", section))?,
+                hashrate: or_default(conf.get_float(&format!("{}.hashrate", section)), 0.0)?,
+                pool_conf: pool_config(conf, &section, Some(primary))?,
+            })
+        })
+        .collect()
+}
+
+/// The `[[randomx]]` entries, the keys that are not set are the ones of the `base`
+fn randomx_variants(conf: &Config) -> Result<Vec<CustomVariant>, ConfigError> {
+    let count = match conf.get_array("randomx") {
+        Err(ConfigError::NotFound(_)) => 0,
+        other => other?.len(),
+    };
+    let mut variants: Vec<CustomVariant> = Vec::with_capacity(count);
+    for i in 0..count {
+        let section = format!("randomx[{}]", i);
+        let key = |name: &str| format!("{}.{}", section, name);
+        let name = conf.get_string(&key("name"))?;
+        if name.is_empty() {
+            return Err(ConfigError::Message(format!("{} is empty", key("name"))));
+        }
+        if pow::ALGORITHMS.contains(&name.as_str()) || variants.iter().any(|v| v.name == name) {
+            return Err(ConfigError::Message(format!(
+                "{} is {}, which is already an algorithm",
+                key("name"),
+                name
+            )));
+        }
+        let base_name = or_default(conf.get_string(&key("base")), "rx/0".to_string())?;
+        let base = match Variant::ALL.into_iter().find(|v| v.name() == base_name) {
+            Some(base) => base.config(),
+            None => {
+                return Err(ConfigError::Message(format!(
+                    "{} is {}, it has to be one of rx/0, rx/graft or rx/keva",
+                    key("base"),
+                    base_name
+                )))
+            }
+        };
+        let config = RandomXConfig {
+            argon_lanes: get_uint(conf, &key("argon_lanes"), base.argon_lanes)?,
+            argon_salt: match conf.get_string(&key("argon_salt")) {
+                Err(ConfigError::NotFound(_)) => base.argon_salt.clone(),
+                salt => salt?.into_bytes(),
+            },
+            argon_memory_kib: get_uint(conf, &key("argon_memory_kib"), base.argon_memory_kib)?,
+            argon_iterations: get_uint(conf, &key("argon_iterations"), base.argon_iterations)?,
+            cache_accesses: get_uint(conf, &key("cache_accesses"), base.cache_accesses)?,
+            dataset_base_bytes: get_uint(
+                conf,
+                &key("dataset_base_bytes"),
+                base.dataset_base_bytes,
+            )?,
+            dataset_extra_bytes: get_uint(
+                conf,
+                &key("dataset_extra_bytes"),
+                base.dataset_extra_bytes,
+            )?,
+            program_size: get_uint(conf, &key("program_size"), base.program_size)?,
+            program_count: get_uint(conf, &key("program_count"), base.program_count)?,
+            program_iterations: get_uint(
+                conf,
+                &key("program_iterations"),
+                base.program_iterations,
+            )?,
+            scratchpad_l2_bytes: get_uint(
+                conf,
+                &key("scratchpad_l2_bytes"),
+                base.scratchpad_l2_bytes,
+            )?,
+            scratchpad_l3_bytes: get_uint(
+                conf,
+                &key("scratchpad_l3_bytes"),
+                base.scratchpad_l3_bytes,
+            )?,
+            freq_iror_r: get_uint(conf, &key("freq_iror_r"), base.freq_iror_r)?,
+            freq_irol_r: get_uint(conf, &key("freq_irol_r"), base.freq_irol_r)?,
+        };
+        //invalid parameters would panic or hash garbage
+        let problems = config.problems();
+        if !problems.is_empty() {
+            return Err(ConfigError::Message(format!(
+                "{} {}: {}",
+                section,
+                name,
+                problems.join(", ")
+            )));
+        }
+        variants.push(CustomVariant { name, config });
+    }
+    Ok(variants)
+}
+
+fn pool_config(
+    conf: &Config,
+    section: &str,
+    primary: Option<&PoolConfig>,
+) -> Result<PoolConfig, ConfigError> {
+    let key = |name: &str| format!("{}.{}", section, name);
+    let pool_address = conf.get_string(&key("pool_address"))?;
+    let (wallet_address, pool_password) = match primary {
+        None => (
+            conf.get_string(&key("wallet_address"))?,
+            conf.get_string(&key("pool_password"))?,
+        ),
+        Some(primary) => (
+            or_default(
+                conf.get_string(&key("wallet_address")),
+                primary.wallet_address.clone(),
+            )?,
+            or_default(
+                conf.get_string(&key("pool_password")),
+                primary.pool_password.clone(),
+            )?,
+        ),
+    };
+    let rig_id = or_default(conf.get_string(&key("rig_id")), "".to_string())?;
+    let keepalive = or_default(conf.get_bool(&key("keepalive")), true)?;
+    let nicehash = or_default(conf.get_bool(&key("nicehash")), false)?;
+    let tls = or_default(conf.get_bool(&key("tls")), false)?;
+    let proxy = or_default(conf.get_string(&key("proxy")), "".to_string())?;
+    let algorithm = or_default(
+        conf.get_string(&key("algorithm")),
+        DEFAULT_ALGORITHM.to_string(),
+    )?;
+    let daemon = or_default(conf.get_bool(&key("daemon")), false)?;
+    let zmq = or_default(conf.get_string(&key("zmq")), "".to_string())?;
+    if !daemon && !zmq.is_empty() {
+        return Err(ConfigError::Message(format!(
+            "{}: zmq needs daemon = true",
+            section
+        )));
+    }
+    if daemon && !proxy.is_empty() {
+        return Err(ConfigError::Message(format!(
+            "{}: the daemon of a solo pool cannot be reached through a proxy",
+            section
+        )));
+    }
+    let solo = solo_config(conf, section)?;
     Ok(PoolConfig {
         pool_address,
         wallet_address,
         pool_password,
+        rig_id,
+        keepalive,
+        nicehash,
+        tls,
+        proxy,
+        algorithm,
+        daemon,
+        zmq,
+        solo,
+        extensions: Vec::new(),
+    })
+}
+
+fn solo_config(conf: &Config, section: &str) -> Result<SoloConfig, ConfigError> {
+    let key = |name: &str| format!("{}.{}", section, name);
+    let default = SoloConfig::default();
+    let template_refresh_seconds = or_default(
+        get_u64_no_zero(conf, &key("template_refresh_seconds")),
+        default.template_refresh_seconds,
+    )?;
+    let reserve_size = or_default(get_u64(conf, &key("reserve_size")), default.reserve_size)?;
+    if reserve_size > 255 {
+        return Err(ConfigError::Message(format!(
+            "{}: reserve_size has to be at most 255",
+            section
+        )));
+    }
+    let mempool_refresh = or_default(
+        conf.get_bool(&key("mempool_refresh")),
+        default.mempool_refresh,
+    )?;
+    let template_max_age_seconds = or_default(
+        get_u64(conf, &key("template_max_age_seconds")),
+        default.template_max_age_seconds,
+    )?;
+    Ok(SoloConfig {
+        template_refresh_seconds,
+        reserve_size,
+        mempool_refresh,
+        template_max_age_seconds,
     })
 }
 
@@ -74,15 +574,71 @@ fn worker_config(conf: &Config) -> Result<WorkerConfig, ConfigError> {
     }
 
     let auto_tune_log = conf.get_string("worker.auto_tune_log")?;
+    let profile = or_default(conf.get_string("worker.profile"), "".to_string())?;
+    let affinity = or_default(conf.get_bool("worker.affinity"), false)?;
+    let throttle_percent = or_default(get_u64(conf, "worker.throttle_percent"), 0)?;
+    if throttle_percent > MAX_THROTTLE_PERCENT {
+        return Err(ConfigError::Message(format!(
+            "throttle_percent has to be <= {}",
+            MAX_THROTTLE_PERCENT
+        )));
+    }
 
     Ok(WorkerConfig {
         num_threads: num_threads as u64,
         auto_tune,
         auto_tune_interval_minutes: auto_tune_interval_minutes as u64,
         auto_tune_log,
+        profile,
+        priority: Priority::Normal,
+        throttle_percent,
+        affinity,
     })
 }
 
+/// The `[[profile]]` entries
+fn profiles(conf: &Config) -> Result<Vec<Profile>, ConfigError> {
+    let count = match conf.get_array("profile") {
+        Err(ConfigError::NotFound(_)) => 0,
+        other => other?.len(),
+    };
+    (0..count)
+        .map(|i| {
+            let key = |name: &str| format!("profile[{}].{}", i, name);
+            let name = conf.get_string(&key("name"))?;
+            let threads_percent = or_default(get_u64_no_zero(conf, &key("threads_percent")), 100)?;
+            if threads_percent > 100 {
+                return Err(ConfigError::Message(format!(
+                    "{} has to be <= 100",
+                    key("threads_percent")
+                )));
+            }
+            let priority_str = or_default(conf.get_string(&key("priority")), "normal".to_string())?;
+            let priority = priority_str.parse::<Priority>().map_err(|_| {
+                ConfigError::Message(format!(
+                    "{} has to be normal, low or idle, was {}",
+                    key("priority"),
+                    priority_str
+                ))
+            })?;
+            let throttle_percent = or_default(get_u64(conf, &key("throttle_percent")), 0)?;
+            if throttle_percent > MAX_THROTTLE_PERCENT {
+                return Err(ConfigError::Message(format!(
+                    "{} has to be <= {}",
+                    key("throttle_percent"),
+                    MAX_THROTTLE_PERCENT
+                )));
+            }
+            Ok(Profile {
+                name,
+                threads_percent,
+                priority,
+                throttle_percent,
+            })
+        })
+        .collect()
+}
+
 fn metric_config(conf: &Config) -> Result<MetricConfig, ConfigError> {
     let enabled = conf.get_bool("metric.enabled")?;
     if enabled {
@@ -96,12 +652,420 @@ fn metric_config(conf: &Config) -> Result<MetricConfig, ConfigError> {
             report_file,
         })
     } else {
-        Ok(MetricConfig {
-            enabled: false,
-            resolution: std::u32::MAX as u64,
-            sample_interval_seconds: std::u32::MAX as u64,
-            report_file: "/dev/null".to_string(),
-        })
+        Ok(MetricConfig::disabled())
+    }
+}
+
+fn push_config(conf: &Config) -> Result<PushConfig, ConfigError> {
+    let enabled = or_default(conf.get_bool("metric_push.enabled"), false)?;
+    let format_str = or_default(conf.get_string("metric_push.format"), "influx".to_string())?;
+    let format = format_str.parse::<PushFormat>().map_err(|_| {
+        ConfigError::Message(format!(
+            "metric_push.format has to be influx or statsd, was {}",
+            format_str
+        ))
+    })?;
+    let address = or_default(
+        conf.get_string("metric_push.address"),
+        "udp://127.0.0.1:8089".to_string(),
+    )?;
+    let interval_seconds = or_default(get_u64_no_zero(conf, "metric_push.interval_seconds"), 10)?;
+    let prefix = or_default(conf.get_string("metric_push.prefix"), "mithril".to_string())?;
+    let host = or_default(conf.get_string("metric_push.host"), "".to_string())?;
+
+    Ok(PushConfig {
+        enabled,
+        format,
+        address,
+        interval_seconds,
+        prefix,
+        host,
+    })
+}
+
+fn otlp_config(conf: &Config) -> Result<OtlpConfig, ConfigError> {
+    let enabled = or_default(conf.get_bool("otlp.enabled"), false)?;
+    let endpoint = or_default(
+        conf.get_string("otlp.endpoint"),
+        "http://127.0.0.1:4318".to_string(),
+    )?;
+    if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+        return Err(ConfigError::Message(format!(
+            "otlp.endpoint has to be an http:// or https:// URL, was {}",
+            endpoint
+        )));
+    }
+    let interval_seconds = or_default(get_u64_no_zero(conf, "otlp.interval_seconds"), 10)?;
+    let service_name = or_default(conf.get_string("otlp.service_name"), "mithril".to_string())?;
+    let host = or_default(conf.get_string("otlp.host"), "".to_string())?;
+    let traces = or_default(conf.get_bool("otlp.traces"), true)?;
+
+    Ok(OtlpConfig {
+        enabled,
+        endpoint,
+        interval_seconds,
+        service_name,
+        host,
+        traces,
+    })
+}
+
+fn memory_config(conf: &Config) -> Result<MemoryConfig, ConfigError> {
+    let mode_str = or_default(conf.get_string("memory.mode"), "auto".to_string())?;
+    let mode = mode_str.parse::<MemoryMode>().map_err(|_| {
+        ConfigError::Message(format!(
+            "memory.mode has to be auto, fast or light, was {}",
+            mode_str
+        ))
+    })?;
+    let numa_str = or_default(conf.get_string("memory.numa"), "local".to_string())?;
+    let numa = numa_str.parse::<NumaPolicy>().map_err(|_| {
+        ConfigError::Message(format!(
+            "memory.numa has to be local or interleave, was {}",
+            numa_str
+        ))
+    })?;
+    let allocator_str = or_default(conf.get_string("memory.allocator"), "default".to_string())?;
+    let allocator = match allocator_str.as_str() {
+        "default" => None,
+        name => Some(name.parse::<AllocKind>().map_err(|_| {
+            ConfigError::Message(format!(
+                "memory.allocator has to be default, malloc, mmap, hugetlb or shm, was {}",
+                allocator_str
+            ))
+        })?),
+    };
+    let dataset_server = or_default(conf.get_string("memory.dataset_server"), "".to_string())?;
+    Ok(MemoryConfig {
+        mode,
+        numa,
+        allocator,
+        dataset_server: match dataset_server.as_str() {
+            "" => None,
+            socket => Some(PathBuf::from(socket)),
+        },
+    })
+}
+
+fn cluster_config(conf: &Config, pool_conf: &PoolConfig) -> Result<ClusterConfig, ConfigError> {
+    let coordinator = or_default(conf.get_string("cluster.coordinator"), "".to_string())?;
+    let local = match conf.get_array("cluster.local") {
+        Err(ConfigError::NotFound(_)) => Vec::new(),
+        Err(err) => return Err(err),
+        Ok(values) => values
+            .into_iter()
+            .map(|value| value.into_string())
+            .collect::<Result<_, _>>()?,
+    };
+    Ok(ClusterConfig {
+        coordinator: match coordinator.as_str() {
+            "" => None,
+            address => Some(cluster::coordinator_pool(address, pool_conf)),
+        },
+        local,
+        fleet_config: None,
+    })
+}
+
+fn dataset_init_config(conf: &Config) -> Result<DatasetInitConfig, ConfigError> {
+    let background = or_default(conf.get_bool("dataset_init.background"), true)?;
+    let threads = or_default(get_u64(conf, "dataset_init.threads"), 0)?;
+    let priority_str = or_default(conf.get_string("dataset_init.priority"), "idle".to_string())?;
+    let priority = priority_str.parse::<Priority>().map_err(|_| {
+        ConfigError::Message(format!(
+            "dataset_init.priority has to be normal, low or idle, was {}",
+            priority_str
+        ))
+    })?;
+    let mb_per_second = or_default(get_u64(conf, "dataset_init.mb_per_second"), 0)?;
+    let stall_seconds = or_default(get_u64(conf, "dataset_init.stall_seconds"), 120)?;
+    let stall_retries = or_default(get_u64(conf, "dataset_init.stall_retries"), 1)?;
+    Ok(DatasetInitConfig {
+        background,
+        threads,
+        priority,
+        mb_per_second,
+        stall_seconds,
+        stall_retries,
+    })
+}
+
+fn integrity_config(conf: &Config) -> Result<IntegrityConfig, ConfigError> {
+    let enabled = or_default(conf.get_bool("dataset_check.enabled"), false)?;
+    let interval_minutes = or_default(get_u64_no_zero(conf, "dataset_check.interval_minutes"), 30)?;
+    let samples = or_default(get_u64_no_zero(conf, "dataset_check.samples"), 1024)?;
+    let rebuild = or_default(conf.get_bool("dataset_check.rebuild"), false)?;
+    Ok(IntegrityConfig {
+        enabled,
+        interval_minutes,
+        samples,
+        rebuild,
+    })
+}
+
+fn webhook_config(conf: &Config) -> Result<WebhookConfig, ConfigError> {
+    let enabled = or_default(conf.get_bool("webhook.enabled"), false)?;
+    let url = or_default(conf.get_string("webhook.url"), "".to_string())?;
+    if enabled && url.is_empty() {
+        return Err(ConfigError::Message(
+            "webhook.url has to be set if webhooks are enabled".to_string(),
+        ));
+    }
+
+    let events = match conf.get_array("webhook.events") {
+        Err(ConfigError::NotFound(_)) => ALL_EVENTS.to_vec(),
+        Err(err) => return Err(err),
+        Ok(values) => {
+            let mut events = Vec::with_capacity(values.len());
+            for value in values {
+                let name = value.into_string()?;
+                let event = name
+                    .parse::<AlertEvent>()
+                    .map_err(|_| ConfigError::Message(format!("unknown webhook event {}", name)))?;
+                events.push(event);
+            }
+            events
+        }
+    };
+
+    let template = or_default(
+        conf.get_string("webhook.template"),
+        DEFAULT_TEMPLATE.to_string(),
+    )?;
+    let rig_name = or_default(conf.get_string("webhook.rig_name"), "".to_string())?;
+    let zero_hashrate_minutes = or_default(get_u64(conf, "webhook.zero_hashrate_minutes"), 10)?;
+    let min_hashrate = or_default(conf.get_float("webhook.min_hashrate"), 0.0)?;
+    let max_reject_percent = or_default(conf.get_float("webhook.max_reject_percent"), 10.0)?;
+    let window_minutes = or_default(get_u64_no_zero(conf, "webhook.window_minutes"), 15)?;
+    let block_difficulty = or_default(get_u64(conf, "webhook.block_difficulty"), 0)?;
+    let block_command = or_default(conf.get_string("webhook.block_command"), "".to_string())?;
+
+    Ok(WebhookConfig {
+        enabled,
+        url,
+        events,
+        template,
+        rig_name,
+        zero_hashrate_minutes,
+        min_hashrate,
+        max_reject_percent,
+        window_minutes,
+        block_difficulty,
+        block_command,
+    })
+}
+
+fn log_config(conf: &Config) -> Result<LogConfig, ConfigError> {
+    let json_file = or_default(conf.get_string("log.json_file"), "".to_string())?;
+    let level_str = or_default(conf.get_string("log.json_level"), "info".to_string())?;
+    let json_level = level_str.parse::<LevelFilter>().map_err(|_| {
+        ConfigError::Message(format!(
+            "log.json_level has to be one of off, error, warn, info, debug, trace, was {}",
+            level_str
+        ))
+    })?;
+    let console_level = match or_default(conf.get_string("log.console_level"), "".to_string())? {
+        level_str if level_str.is_empty() => None,
+        level_str => Some(level_str.parse::<LevelFilter>().map_err(|_| {
+            ConfigError::Message(format!(
+                "log.console_level has to be one of off, error, warn, info, debug, trace, was {}",
+                level_str
+            ))
+        })?),
+    };
+    let max_size_mb = or_default(get_u64_no_zero(conf, "log.max_size_mb"), 10)?;
+    let rotate_hours = or_default(get_u64(conf, "log.rotate_hours"), 24)?;
+    let keep_files = or_default(get_u64(conf, "log.keep_files"), 5)?;
+    let mut levels = match conf.get_table("log.levels") {
+        Err(ConfigError::NotFound(_)) => Vec::new(),
+        Err(err) => return Err(err),
+        Ok(table) => table
+            .into_iter()
+            .map(|(module, value)| {
+                let level_str = value.into_string()?;
+                let level = level_str.parse::<LevelFilter>().map_err(|_| {
+                    ConfigError::Message(format!(
+                        "log.levels.{} has to be one of off, error, warn, info, debug, trace, was {}",
+                        module, level_str
+                    ))
+                })?;
+                Ok((module, level))
+            })
+            .collect::<Result<Vec<_>, ConfigError>>()?,
+    };
+    levels.sort();
+
+    Ok(LogConfig {
+        json_file,
+        json_level,
+        console_level,
+        max_size_mb,
+        rotate_hours,
+        keep_files: keep_files as usize,
+        levels,
+    })
+}
+
+fn console_config(conf: &Config) -> Result<ConsoleConfig, ConfigError> {
+    let timestamps_str = or_default(conf.get_string("console.timestamps"), "seconds".to_string())?;
+    let timestamps = timestamps_str.parse::<TimestampFormat>().map_err(|_| {
+        ConfigError::Message(format!(
+            "console.timestamps has to be off, seconds, millis or micros, was {}",
+            timestamps_str
+        ))
+    })?;
+    let color = or_default(conf.get_bool("console.color"), true)?;
+    let hashrate_str = or_default(conf.get_string("console.hashrate"), "verbose".to_string())?;
+    let hashrate = hashrate_str.parse::<HashrateFormat>().map_err(|_| {
+        ConfigError::Message(format!(
+            "console.hashrate has to be compact or verbose, was {}",
+            hashrate_str
+        ))
+    })?;
+    let quiet = or_default(conf.get_bool("console.quiet"), false)?;
+
+    Ok(ConsoleConfig {
+        timestamps,
+        color,
+        hashrate,
+        quiet,
+    })
+}
+
+fn api_config(conf: &Config) -> Result<ApiConfig, ConfigError> {
+    let enabled = or_default(conf.get_bool("api.enabled"), false)?;
+    let address = or_default(conf.get_string("api.address"), "127.0.0.1:3380".to_string())?;
+    let token = or_default(conf.get_string("api.token"), "".to_string())?;
+    let hash_stall_seconds =
+        or_default(get_u64_no_zero(conf, "api.healthz_hash_stall_seconds"), 300)?;
+    let disconnect_seconds =
+        or_default(get_u64_no_zero(conf, "api.healthz_disconnect_seconds"), 300)?;
+    let history_hours = or_default(get_u64(conf, "api.history_hours"), 24)?;
+    let history_resolution_seconds =
+        or_default(get_u64_no_zero(conf, "api.history_resolution_seconds"), 10)?;
+
+    Ok(ApiConfig {
+        enabled,
+        address,
+        token,
+        health: HealthConfig {
+            hash_stall_seconds,
+            disconnect_seconds,
+        },
+        history_hours,
+        history_resolution_seconds,
+    })
+}
+
+fn share_db_config(conf: &Config) -> Result<ShareDbConfig, ConfigError> {
+    let enabled = or_default(conf.get_bool("share_db.enabled"), false)?;
+    let path = or_default(
+        conf.get_string("share_db.path"),
+        "./mithril_history.db".to_string(),
+    )?;
+    let retention_days = or_default(get_u64(conf, "share_db.retention_days"), 30)?;
+
+    Ok(ShareDbConfig {
+        enabled,
+        path,
+        retention_days,
+    })
+}
+
+fn forensics_config(conf: &Config) -> Result<ForensicsConfig, ConfigError> {
+    let file = or_default(conf.get_string("forensics.file"), "".to_string())?;
+    let max_size_mb = or_default(get_u64_no_zero(conf, "forensics.max_size_mb"), 10)?;
+    let keep_files = or_default(get_u64(conf, "forensics.keep_files"), 3)?;
+    let verify = or_default(conf.get_bool("forensics.verify"), true)?;
+
+    Ok(ForensicsConfig {
+        file,
+        max_size_mb,
+        keep_files: keep_files as usize,
+        verify,
+    })
+}
+
+fn earnings_config(conf: &Config) -> Result<EarningsConfig, ConfigError> {
+    let enabled = or_default(conf.get_bool("earnings.enabled"), false)?;
+    let url = or_default(conf.get_string("earnings.url"), "".to_string())?;
+    let daemon = or_default(conf.get_string("earnings.daemon"), "".to_string())?;
+    if enabled && url.is_empty() && daemon.is_empty() {
+        return Err(ConfigError::Message(
+            "earnings.url or earnings.daemon has to be set if the estimator is enabled"
+                .to_string(),
+        ));
+    }
+    let interval_minutes = or_default(get_u64_no_zero(conf, "earnings.interval_minutes"), 10)?;
+    let pool_fee_percent = or_default(conf.get_float("earnings.pool_fee_percent"), 0.0)?;
+    if !(0.0..=100.0).contains(&pool_fee_percent) {
+        return Err(ConfigError::Message(
+            "earnings.pool_fee_percent has to be between 0 and 100".to_string(),
+        ));
+    }
+
+    Ok(EarningsConfig {
+        enabled,
+        url,
+        daemon,
+        interval_minutes,
+        pool_fee_percent,
+    })
+}
+
+fn privilege_config(conf: &Config) -> Result<PrivilegeConfig, ConfigError> {
+    let user = or_default(conf.get_string("privileges.user"), "".to_string())?;
+    let group = or_default(conf.get_string("privileges.group"), "".to_string())?;
+
+    Ok(PrivilegeConfig { user, group })
+}
+
+fn update_check_config(conf: &Config) -> Result<UpdateCheckConfig, ConfigError> {
+    let enabled = or_default(conf.get_bool("update_check.enabled"), false)?;
+    let url = or_default(
+        conf.get_string("update_check.url"),
+        update_check::DEFAULT_URL.to_string(),
+    )?;
+    if !url.starts_with("https://") {
+        return Err(ConfigError::Message(format!(
+            "update_check.url has to be an https:// URL, was {}",
+            url
+        )));
+    }
+    let interval_hours = or_default(get_u64_no_zero(conf, "update_check.interval_hours"), 24)?;
+
+    Ok(UpdateCheckConfig {
+        enabled,
+        url,
+        interval_hours,
+    })
+}
+
+/// Falls back to the default for optional fields that are not present in the config file.
+fn or_default<T>(result: Result<T, ConfigError>, default: T) -> Result<T, ConfigError> {
+    match result {
+        Err(ConfigError::NotFound(_)) => Ok(default),
+        other => other,
+    }
+}
+
+fn get_u64(conf: &Config, field: &str) -> Result<u64, ConfigError> {
+    let val = conf.get_int(field)?;
+    if val < 0 {
+        return Err(ConfigError::Message(format!("{} has to be >= 0", field)));
+    }
+    Ok(val as u64)
+}
+
+/// An integer that has to fit into `T`, the default if the key is not set
+fn get_uint<T: TryFrom<u64>>(conf: &Config, field: &str, default: T) -> Result<T, ConfigError> {
+    match get_u64(conf, field) {
+        Err(ConfigError::NotFound(_)) => Ok(default),
+        other => {
+            let val = other?;
+            T::try_from(val)
+                .map_err(|_| ConfigError::Message(format!("{} is {}, it is too large", field, val)))
+        }
     }
 }
 
@@ -113,19 +1077,23 @@ fn get_u64_no_zero(conf: &Config, field: &str) -> Result<u64, ConfigError> {
     Ok(val as u64)
 }
 
-fn parse_conf(conf_file: &Path, filename: &str) -> Result<Config, ConfigError> {
+fn parse_conf(
+    conf_file: &Path,
+    filename: &str,
+    format: ConfigFormat,
+) -> Result<Config, ConfigError> {
     if conf_file.exists() {
         return Config::builder()
-            .add_source(File::new(filename, FileFormat::Toml))
+            .add_source(File::new(filename, format.file_format()))
             .build();
     }
     Err(ConfigError::Message("config file not found".to_string()))
 }
 
 pub fn donation_conf() -> PoolConfig {
-    PoolConfig {
-        pool_address: "xmrpool.eu:3333".to_string(),
-        pool_password: "x".to_string(),
-        wallet_address: "48y3RCT5SzSS4jumHm9rRL91eWWzd6xcVGSCF1KUZGWYJ6npqwFxHee4xkLLNUqY4NjiswdJhxFALeRqzncHoToeJMg2bhL".to_string()
-    }
+    let mut donation = PoolConfig::default();
+    donation.pool_address = "xmrpool.eu:3333".to_string();
+    donation.pool_password = "x".to_string();
+    donation.wallet_address = "48y3RCT5SzSS4jumHm9rRL91eWWzd6xcVGSCF1KUZGWYJ6npqwFxHee4xkLLNUqY4NjiswdJhxFALeRqzncHoToeJMg2bhL".to_string();
+    donation
 }
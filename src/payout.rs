@@ -0,0 +1,103 @@
+//! Splits the payouts of the pool between several wallets (`[[payout]]`). The pool
+//! login rotates between the wallets, the difficulty of the accepted shares is counted
+//! per wallet and the one that is furthest behind its percentage is mined next.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often the wallet may change, each change is a new pool login
+pub const ROTATION_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayoutWallet {
+    pub wallet_address: String,
+    /// share of the accepted share difficulty, all wallets add up to 100
+    pub percentage: f64,
+}
+
+/// The wallet that is mined and the accepted share difficulty of each one
+pub struct PayoutSplit {
+    wallets: Vec<PayoutWallet>,
+    state: Mutex<SplitState>,
+}
+
+struct SplitState {
+    current: usize,
+    difficulty: Vec<u64>,
+}
+
+impl PayoutSplit {
+    /// None without wallets, the pool wallet is mined then
+    pub fn new(wallets: Vec<PayoutWallet>) -> Option<PayoutSplit> {
+        if wallets.is_empty() {
+            return None;
+        }
+        let state = SplitState {
+            current: 0,
+            difficulty: vec![0; wallets.len()],
+        };
+        Some(PayoutSplit {
+            wallets,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn wallets(&self) -> &[PayoutWallet] {
+        &self.wallets
+    }
+
+    /// The address the pool login uses
+    pub fn current(&self) -> &str {
+        let current = self.state.lock().map_or(0, |state| state.current);
+        &self.wallets[current].wallet_address
+    }
+
+    /// Counts the share for the current wallet
+    pub fn share_accepted(&self, difficulty: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            let current = state.current;
+            state.difficulty[current] += difficulty;
+        }
+    }
+
+    /// Moves on to the wallet that is furthest behind its percentage, the first one of
+    /// equal ones. True if the wallet changed.
+    pub fn rotate(&self) -> bool {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return false,
+        };
+        let total: u64 = state.difficulty.iter().sum();
+        let mut next = 0;
+        let mut max_deficit = f64::MIN;
+        for (i, wallet) in self.wallets.iter().enumerate() {
+            let deficit = total as f64 * wallet.percentage / 100.0 - state.difficulty[i] as f64;
+            if deficit > max_deficit {
+                next = i;
+                max_deficit = deficit;
+            }
+        }
+        let changed = next != state.current;
+        state.current = next;
+        changed
+    }
+
+    /// The percentage of the accepted share difficulty each wallet got so far
+    pub fn split(&self) -> Vec<f64> {
+        let difficulty = match self.state.lock() {
+            Ok(state) => state.difficulty.clone(),
+            Err(_) => return vec![0.0; self.wallets.len()],
+        };
+        let total: u64 = difficulty.iter().sum();
+        difficulty
+            .iter()
+            .map(|d| {
+                if total == 0 {
+                    0.0
+                } else {
+                    *d as f64 * 100.0 / total as f64
+                }
+            })
+            .collect()
+    }
+}
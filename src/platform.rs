@@ -0,0 +1,223 @@
+#[cfg(unix)]
+extern crate libc;
+
+use crate::profile::Priority;
+
+use std::io;
+
+/// Pins the calling thread to a logical core
+#[cfg(target_os = "linux")]
+pub fn set_thread_affinity(core: usize) -> io::Result<()> {
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "freebsd")]
+pub fn set_thread_affinity(core: usize) -> io::Result<()> {
+    let result = unsafe {
+        let mut set: libc::cpuset_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        //id -1 is the calling thread
+        libc::cpuset_setaffinity(
+            libc::CPU_LEVEL_WHICH,
+            libc::CPU_WHICH_TID,
+            -1,
+            std::mem::size_of::<libc::cpuset_t>(),
+            &set,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn set_thread_affinity(core: usize) -> io::Result<()> {
+    windows::set_thread_affinity(core)
+}
+
+/// macOS only takes affinity hints, and none on Apple Silicon
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", windows)))]
+pub fn set_thread_affinity(_core: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "pinning threads is not supported on this platform",
+    ))
+}
+
+/// Lowers the nice value of the calling thread, on Linux it is per thread
+#[cfg(target_os = "linux")]
+pub fn set_thread_priority(priority: Priority) -> io::Result<()> {
+    if priority == Priority::Normal {
+        return Ok(());
+    }
+    let result = unsafe {
+        let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        libc::setpriority(libc::PRIO_PROCESS, tid, priority.nice())
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn set_thread_priority(priority: Priority) -> io::Result<()> {
+    windows::set_thread_priority(priority)
+}
+
+/// The quality of service class of the calling thread. On Apple Silicon the utility
+/// and background classes prefer or only use the efficiency cores.
+#[cfg(target_os = "macos")]
+pub fn set_thread_priority(priority: Priority) -> io::Result<()> {
+    const QOS_CLASS_UTILITY: u32 = 0x11;
+    const QOS_CLASS_BACKGROUND: u32 = 0x09;
+    extern "C" {
+        fn pthread_set_qos_class_self_np(
+            qos_class: u32,
+            relative_priority: libc::c_int,
+        ) -> libc::c_int;
+    }
+
+    let qos_class = match priority {
+        Priority::Normal => return Ok(()),
+        Priority::Low => QOS_CLASS_UTILITY,
+        Priority::Idle => QOS_CLASS_BACKGROUND,
+    };
+    match unsafe { pthread_set_qos_class_self_np(qos_class, 0) } {
+        0 => Ok(()),
+        err => Err(io::Error::from_raw_os_error(err)),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn set_thread_priority(priority: Priority) -> io::Result<()> {
+    if priority == Priority::Normal {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "thread priorities are not supported on this platform",
+    ))
+}
+
+/// The priority class of the process, only Windows has one. Thread priorities are
+/// relative to it.
+#[cfg(windows)]
+pub fn set_process_priority(priority: Priority) -> io::Result<()> {
+    windows::set_process_priority(priority)
+}
+
+#[cfg(not(windows))]
+pub fn set_process_priority(_priority: Priority) -> io::Result<()> {
+    Ok(())
+}
+
+/// A string value of `sysctl`, e.g. `machdep.cpu.brand_string`
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+pub fn sysctl_string(name: &str) -> Option<String> {
+    let mut value = vec![0u8; 256];
+    let len = sysctl(name, &mut value)?;
+    value.truncate(len);
+    let value = std::ffi::CStr::from_bytes_until_nul(&value).ok()?;
+    Some(value.to_string_lossy().trim().to_string())
+}
+
+/// A numeric value of `sysctl`, e.g. `hw.memsize`
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+pub fn sysctl_u64(name: &str) -> Option<u64> {
+    let mut value = [0u8; 8];
+    match sysctl(name, &mut value)? {
+        4 => Some(u32::from_ne_bytes(value[..4].try_into().ok()?) as u64),
+        8 => Some(u64::from_ne_bytes(value)),
+        _ => None,
+    }
+}
+
+/// Reads `name` into `value`, returns the length of the value
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn sysctl(name: &str, value: &mut [u8]) -> Option<usize> {
+    let name = std::ffi::CString::new(name).ok()?;
+    let mut len = value.len();
+    let result = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr(),
+            value.as_mut_ptr() as *mut libc::c_void,
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    (result == 0).then_some(len)
+}
+
+#[cfg(windows)]
+mod windows {
+    use crate::profile::Priority;
+
+    use std::ffi::c_void;
+    use std::io;
+
+    type Handle = *mut c_void;
+
+    const NORMAL_PRIORITY_CLASS: u32 = 0x20;
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x4000;
+    const IDLE_PRIORITY_CLASS: u32 = 0x40;
+    const THREAD_PRIORITY_NORMAL: i32 = 0;
+    const THREAD_PRIORITY_BELOW_NORMAL: i32 = -1;
+    const THREAD_PRIORITY_IDLE: i32 = -15;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> Handle;
+        fn GetCurrentThread() -> Handle;
+        fn SetThreadAffinityMask(thread: Handle, mask: usize) -> usize;
+        fn SetThreadPriority(thread: Handle, priority: i32) -> i32;
+        fn SetPriorityClass(process: Handle, class: u32) -> i32;
+    }
+
+    pub fn set_thread_affinity(core: usize) -> io::Result<()> {
+        if core >= usize::BITS as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("core {} is outside of the processor group", core),
+            ));
+        }
+        if unsafe { SetThreadAffinityMask(GetCurrentThread(), 1 << core) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn set_thread_priority(priority: Priority) -> io::Result<()> {
+        let thread_priority = match priority {
+            Priority::Normal => THREAD_PRIORITY_NORMAL,
+            Priority::Low => THREAD_PRIORITY_BELOW_NORMAL,
+            Priority::Idle => THREAD_PRIORITY_IDLE,
+        };
+        if unsafe { SetThreadPriority(GetCurrentThread(), thread_priority) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn set_process_priority(priority: Priority) -> io::Result<()> {
+        let class = match priority {
+            Priority::Normal => NORMAL_PRIORITY_CLASS,
+            Priority::Low => BELOW_NORMAL_PRIORITY_CLASS,
+            Priority::Idle => IDLE_PRIORITY_CLASS,
+        };
+        if unsafe { SetPriorityClass(GetCurrentProcess(), class) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
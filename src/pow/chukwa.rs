@@ -0,0 +1,181 @@
+use super::{
+    IntegrityCheck, JobHints, MemoryRequirements, MemoryUsage, PowAlgorithm, PowHasher, PowMemory,
+};
+use crate::randomx::memory::MemoryError;
+use crate::randomx::pages::BufferCount;
+
+use argon2::{Algorithm, Argon2, Block, Params, Version};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Bytes of the input that are the salt
+const SALT_BYTES: usize = 16;
+
+/// Argon2id with the parameters of the TurtleCoin family, the block is the password and
+/// its first 16 bytes the salt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// 512 KB, 3 iterations
+    Chukwa,
+    /// 1 MB, 4 iterations
+    ChukwaV2,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 2] = [Variant::Chukwa, Variant::ChukwaV2];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Chukwa => "argon2/chukwa",
+            Variant::ChukwaV2 => "argon2/chukwav2",
+        }
+    }
+
+    /// The variant with the stratum name
+    pub fn from_name(name: &str) -> Option<Variant> {
+        Variant::ALL.into_iter().find(|v| v.name() == name)
+    }
+
+    pub fn memory_kib(&self) -> u32 {
+        match self {
+            Variant::Chukwa => 512,
+            Variant::ChukwaV2 => 1024,
+        }
+    }
+
+    fn iterations(&self) -> u32 {
+        match self {
+            Variant::Chukwa => 3,
+            Variant::ChukwaV2 => 4,
+        }
+    }
+}
+
+/// Chukwa, every thread has its own Argon2 memory
+#[derive(Clone)]
+pub struct Chukwa {
+    memory: Arc<ChukwaMemory>,
+}
+
+impl Chukwa {
+    pub fn new(variant: Variant) -> Chukwa {
+        Chukwa {
+            memory: Arc::new(ChukwaMemory {
+                variant,
+                scratchpads: BufferCount::default(),
+            }),
+        }
+    }
+}
+
+impl PowAlgorithm for Chukwa {
+    fn name(&self) -> &'static str {
+        self.memory.variant.name()
+    }
+
+    fn job_hints(&self) -> JobHints {
+        JobHints {
+            seed_hash: false,
+            height: false,
+        }
+    }
+
+    fn memory_requirements(&self) -> MemoryRequirements {
+        MemoryRequirements {
+            shared_bytes: 0,
+            thread_bytes: u64::from(self.memory.variant.memory_kib()) * 1024,
+        }
+    }
+
+    fn prepare(&mut self, _seed_hash: &str, _height: u64) -> Result<Option<Duration>, MemoryError> {
+        Ok(None)
+    }
+
+    fn memory(&self) -> Arc<dyn PowMemory> {
+        self.memory.clone()
+    }
+
+    fn full_memory(&self) -> bool {
+        false
+    }
+
+    fn light_mode(&mut self) {}
+
+    fn clone_box(&self) -> Box<dyn PowAlgorithm> {
+        Box::new(self.clone())
+    }
+}
+
+/// Nothing is shared, only the variant
+pub struct ChukwaMemory {
+    variant: Variant,
+    /// the Argon2 blocks of the hashers, always on normal pages
+    scratchpads: BufferCount,
+}
+
+impl PowMemory for ChukwaMemory {
+    fn hasher(self: Arc<Self>) -> Box<dyn PowHasher> {
+        let variant = self.variant;
+        //the parameters are constants within the bounds of Argon2
+        let params = Params::new(variant.memory_kib(), variant.iterations(), 1, Some(32))
+            .expect("chukwa parameters");
+        self.scratchpads.add(false);
+        Box::new(ChukwaHasher {
+            argon2: Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+            blocks: vec![Block::default(); variant.memory_kib() as usize],
+            memory: self,
+        })
+    }
+
+    fn mode(&self) -> &'static str {
+        "scratchpad"
+    }
+
+    fn fill(&self) -> f64 {
+        0.0
+    }
+
+    fn usage(&self) -> MemoryUsage {
+        let scratchpads = self.scratchpads.buffers();
+        MemoryUsage {
+            scratchpads,
+            scratchpad_bytes: scratchpads * self.variant.memory_kib() as u64 * 1024,
+            ..MemoryUsage::default()
+        }
+    }
+
+    fn verify(&self, _samples: u64, _seed: u64) -> Option<IntegrityCheck> {
+        None
+    }
+
+    fn rebuild(&self, _threads: usize) {}
+}
+
+pub struct ChukwaHasher {
+    argon2: Argon2<'static>,
+    /// 1 KB Argon2 blocks, reused for every hash
+    blocks: Vec<Block>,
+    memory: Arc<ChukwaMemory>,
+}
+
+impl Drop for ChukwaHasher {
+    fn drop(&mut self) {
+        self.memory.scratchpads.remove(false);
+    }
+}
+
+impl PowHasher for ChukwaHasher {
+    /// Inputs shorter than the 8 bytes Argon2 needs as salt hash to all ones, that
+    /// meets no target
+    fn hash(&mut self, input: &[u8]) -> [u8; 32] {
+        let salt = &input[..input.len().min(SALT_BYTES)];
+        let mut hash = [0; 32];
+        match self
+            .argon2
+            .hash_password_into_with_memory(input, salt, &mut hash, &mut self.blocks)
+        {
+            Ok(()) => hash,
+            Err(_) => [0xff; 32],
+        }
+    }
+}
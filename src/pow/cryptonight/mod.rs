@@ -0,0 +1,526 @@
+pub mod random_math;
+
+use self::random_math::Instr;
+use super::{
+    IntegrityCheck, JobHints, MemoryRequirements, MemoryUsage, PowAlgorithm, PowHasher, PowMemory,
+};
+use crate::randomx::m128::m128i;
+use crate::randomx::memory::MemoryError;
+use crate::randomx::pages::{BufferCount, PageBuffer};
+use crate::randomx::soft_aes::expand_key_256;
+
+use blake_hash::Blake256;
+use groestl::Groestl256;
+use jh::Jh256;
+use skein::consts::U32;
+use skein::{Digest, Skein512};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tiny_keccak::keccakf;
+
+/// Bytes of the keccak state that are encrypted into the scratchpad
+const TEXT: std::ops::Range<usize> = 64..192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// the original CryptoNight, for the test vectors of the reference implementation
+    Cn0,
+    /// CryptoNight variant 4, with a random program for every block height
+    CnR,
+    /// 4 MB scratchpad, with a division in the main loop
+    CnHeavy0,
+    /// cn-heavy of Haven, the division result is mixed in differently
+    CnHeavyXhv,
+    /// variant 2 with a 256 KB scratchpad, TurtleCoin
+    CnPico,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 5] = [
+        Variant::Cn0,
+        Variant::CnR,
+        Variant::CnHeavy0,
+        Variant::CnHeavyXhv,
+        Variant::CnPico,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Cn0 => "cn/0",
+            Variant::CnR => "cn/r",
+            Variant::CnHeavy0 => "cn-heavy/0",
+            Variant::CnHeavyXhv => "cn-heavy/xhv",
+            Variant::CnPico => "cn-pico",
+        }
+    }
+
+    /// The variant with the stratum name
+    pub fn from_name(name: &str) -> Option<Variant> {
+        Variant::ALL.into_iter().find(|v| v.name() == name)
+    }
+
+    pub fn scratchpad_bytes(&self) -> u64 {
+        match self {
+            Variant::Cn0 | Variant::CnR => 2 * 1024 * 1024,
+            Variant::CnHeavy0 | Variant::CnHeavyXhv => 4 * 1024 * 1024,
+            Variant::CnPico => 256 * 1024,
+        }
+    }
+
+    /// Rounds of the main loop, every round makes two memory accesses
+    fn iterations(&self) -> usize {
+        match self {
+            Variant::Cn0 | Variant::CnR => 0x80000,
+            Variant::CnHeavy0 | Variant::CnHeavyXhv => 0x40000,
+            Variant::CnPico => 0x10000,
+        }
+    }
+
+    /// Mask of the byte addresses of the main loop, cn-pico only uses the first half
+    /// of its scratchpad
+    fn address_mask(&self) -> u64 {
+        match self {
+            Variant::CnPico => 0x1fff0,
+            _ => (self.scratchpad_bytes() - 1) & !0xf,
+        }
+    }
+
+    /// The CryptoNight variant the main loop is based on: 0, 2 (integer math and
+    /// shuffle) or 4 (random math)
+    fn base(&self) -> u8 {
+        match self {
+            Variant::Cn0 | Variant::CnHeavy0 | Variant::CnHeavyXhv => 0,
+            Variant::CnPico => 2,
+            Variant::CnR => 4,
+        }
+    }
+
+    fn is_heavy(&self) -> bool {
+        matches!(self, Variant::CnHeavy0 | Variant::CnHeavyXhv)
+    }
+}
+
+/// CryptoNight, every thread has its own scratchpad and there is no shared memory
+#[derive(Clone)]
+pub struct CryptoNight {
+    memory: Arc<CnMemory>,
+}
+
+impl CryptoNight {
+    pub fn new(variant: Variant) -> CryptoNight {
+        CryptoNight {
+            memory: Arc::new(CnMemory::new(variant, 0)),
+        }
+    }
+}
+
+impl PowAlgorithm for CryptoNight {
+    fn name(&self) -> &'static str {
+        self.memory.variant.name()
+    }
+
+    fn job_hints(&self) -> JobHints {
+        JobHints {
+            seed_hash: false,
+            height: self.memory.variant == Variant::CnR,
+        }
+    }
+
+    fn memory_requirements(&self) -> MemoryRequirements {
+        MemoryRequirements {
+            shared_bytes: 0,
+            thread_bytes: self.memory.variant.scratchpad_bytes(),
+        }
+    }
+
+    /// Generates the program of the height for cn/r
+    fn prepare(&mut self, _seed_hash: &str, height: u64) -> Result<Option<Duration>, MemoryError> {
+        let variant = self.memory.variant;
+        if variant != Variant::CnR || height == self.memory.height {
+            return Ok(None);
+        }
+        let start = Instant::now();
+        self.memory = Arc::new(CnMemory::new(variant, height));
+        Ok(Some(start.elapsed()))
+    }
+
+    fn memory(&self) -> Arc<dyn PowMemory> {
+        self.memory.clone()
+    }
+
+    fn full_memory(&self) -> bool {
+        false
+    }
+
+    fn light_mode(&mut self) {}
+
+    fn clone_box(&self) -> Box<dyn PowAlgorithm> {
+        Box::new(self.clone())
+    }
+}
+
+/// What the threads of a job share, the random program of cn/r
+pub struct CnMemory {
+    variant: Variant,
+    height: u64,
+    program: Vec<Instr>,
+    scratchpads: BufferCount,
+}
+
+impl CnMemory {
+    fn new(variant: Variant, height: u64) -> CnMemory {
+        let program = match variant {
+            Variant::CnR => random_math::generate(height),
+            _ => Vec::new(),
+        };
+        CnMemory {
+            variant,
+            height,
+            program,
+            scratchpads: BufferCount::default(),
+        }
+    }
+}
+
+impl PowMemory for CnMemory {
+    fn hasher(self: Arc<Self>) -> Box<dyn PowHasher> {
+        let scratchpad = PageBuffer::new(self.variant.scratchpad_bytes() as usize / 8);
+        self.scratchpads.add(scratchpad.large_pages());
+        Box::new(CnHasher {
+            memory: self,
+            scratchpad,
+        })
+    }
+
+    fn mode(&self) -> &'static str {
+        "scratchpad"
+    }
+
+    fn fill(&self) -> f64 {
+        0.0
+    }
+
+    fn usage(&self) -> MemoryUsage {
+        let scratchpads = self.scratchpads.buffers();
+        MemoryUsage {
+            scratchpads,
+            scratchpad_bytes: scratchpads * self.variant.scratchpad_bytes(),
+            large_page_scratchpads: self.scratchpads.large_pages(),
+            ..MemoryUsage::default()
+        }
+    }
+
+    fn verify(&self, _samples: u64, _seed: u64) -> Option<IntegrityCheck> {
+        None
+    }
+
+    fn rebuild(&self, _threads: usize) {}
+}
+
+pub struct CnHasher {
+    memory: Arc<CnMemory>,
+    scratchpad: PageBuffer,
+}
+
+impl Drop for CnHasher {
+    fn drop(&mut self) {
+        self.memory
+            .scratchpads
+            .remove(self.scratchpad.large_pages());
+    }
+}
+
+impl PowHasher for CnHasher {
+    fn hash(&mut self, input: &[u8]) -> [u8; 32] {
+        let mut state = keccak1600(input);
+        let mut bytes = state_bytes(&state);
+        self.explode(&bytes);
+        self.main_loop(&state);
+        self.implode(&mut bytes);
+
+        for (word, chunk) in state.iter_mut().zip(bytes.chunks(8)) {
+            *word = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        keccakf(&mut state);
+        let bytes = state_bytes(&state);
+        let mut hash = [0; 32];
+        match bytes[0] & 3 {
+            0 => hash.copy_from_slice(&<Blake256 as blake_hash::Digest>::digest(&bytes)),
+            1 => hash.copy_from_slice(&Groestl256::digest(bytes)),
+            2 => hash.copy_from_slice(&Jh256::digest(bytes)),
+            _ => hash.copy_from_slice(&Skein512::<U32>::digest(bytes)),
+        }
+        hash
+    }
+}
+
+impl CnHasher {
+    /// Fills the scratchpad with the text of the state, encrypted again for every
+    /// 128 bytes
+    fn explode(&mut self, state: &[u8; 200]) {
+        let heavy = self.memory.variant.is_heavy();
+        let keys = round_keys(&state[..32]);
+        let mut text = text_blocks(state);
+        if heavy {
+            for _ in 0..16 {
+                encrypt(&mut text, &keys);
+                mix_and_propagate(&mut text);
+            }
+        }
+        for chunk in self.scratchpad.chunks_mut(16) {
+            encrypt(&mut text, &keys);
+            for (block, words) in text.iter().zip(chunk.chunks_mut(2)) {
+                let (hi, lo) = block.as_i64();
+                words[0] = lo as u64;
+                words[1] = hi as u64;
+            }
+        }
+    }
+
+    fn main_loop(&mut self, state: &[u64; 25]) {
+        let variant = self.memory.variant;
+        let base = variant.base();
+        let program = &self.memory.program;
+        let sp = &mut self.scratchpad[..];
+        let mask = variant.address_mask();
+
+        let mut a = [state[0] ^ state[4], state[1] ^ state[5]];
+        let mut b = [state[2] ^ state[6], state[3] ^ state[7]];
+        let mut b1 = [state[8] ^ state[10], state[9] ^ state[11]];
+        //the address of the first access, cn-heavy does not use `a` for it
+        let mut idx = a[0];
+        let mut r = [0u32; 9];
+        r[0] = state[12] as u32;
+        r[1] = (state[12] >> 32) as u32;
+        r[2] = state[13] as u32;
+        r[3] = (state[13] >> 32) as u32;
+        let mut division_result = state[12];
+        let mut sqrt_result = state[13];
+
+        for _ in 0..variant.iterations() {
+            let j = index(idx, mask);
+            let block = read(sp, j);
+            let mut c1 = aes_round(block, a);
+            if base >= 2 {
+                let chunks = shuffle_add(sp, j, a, b, b1);
+                if base == 4 {
+                    xor(&mut c1, chunks);
+                }
+            }
+            write(sp, j, [c1[0] ^ b[0], c1[1] ^ b[1]]);
+
+            let j = index(c1[0], mask);
+            let mut c2 = read(sp, j);
+            let mut a1 = a;
+            if base == 2 {
+                c2[0] ^= division_result ^ (sqrt_result << 32);
+                let divisor = (c1[0].wrapping_add(sqrt_result << 1) as u32 | 0x80000001) as u64;
+                division_result = (c1[1] / divisor) as u32 as u64 | (c1[1] % divisor) << 32;
+                sqrt_result = int_sqrt_v2(c1[0].wrapping_add(division_result));
+            }
+            if base == 4 {
+                c2[0] ^=
+                    u64::from(r[0].wrapping_add(r[1])) | u64::from(r[2].wrapping_add(r[3])) << 32;
+                r[4] = a[0] as u32;
+                r[5] = a[1] as u32;
+                r[6] = b[0] as u32;
+                r[7] = b1[0] as u32;
+                r[8] = b1[1] as u32;
+                random_math::execute(program, &mut r);
+                a1[0] ^= u64::from(r[2]) | u64::from(r[3]) << 32;
+                a1[1] ^= u64::from(r[0]) | u64::from(r[1]) << 32;
+            }
+            let product = u128::from(c1[0]) * u128::from(c2[0]);
+            let mut d = [(product >> 64) as u64, product as u64];
+            if base == 2 {
+                let mut chunk1 = read(sp, j ^ 2);
+                xor(&mut chunk1, d);
+                write(sp, j ^ 2, chunk1);
+                xor(&mut d, read(sp, j ^ 4));
+            }
+            if base >= 2 {
+                let chunks = shuffle_add(sp, j, a, b, b1);
+                if base == 4 {
+                    xor(&mut c1, chunks);
+                }
+            }
+            a1 = add(a1, d);
+            write(sp, j, a1);
+            a = [a1[0] ^ c2[0], a1[1] ^ c2[1]];
+            idx = a[0];
+
+            if variant.is_heavy() {
+                let j = index(idx, mask);
+                let n = sp[j] as i64;
+                let mut d = sp[j + 1] as i32;
+                let q = n.wrapping_div(i64::from(d | 0x5));
+                sp[j] = (n ^ q) as u64;
+                if variant == Variant::CnHeavyXhv {
+                    d = !d;
+                }
+                idx = (i64::from(d) ^ q) as u64;
+            }
+            b1 = b;
+            b = c1;
+        }
+    }
+
+    /// Encrypts the scratchpad into the text of the state
+    fn implode(&self, state: &mut [u8; 200]) {
+        let heavy = self.memory.variant.is_heavy();
+        let keys = round_keys(&state[32..64]);
+        let mut text = text_blocks(state);
+        //cn-heavy encrypts the scratchpad twice and then the text on its own
+        let passes = if heavy { 2 } else { 1 };
+        for _ in 0..passes {
+            for chunk in self.scratchpad.chunks(16) {
+                for (block, words) in text.iter_mut().zip(chunk.chunks(2)) {
+                    let (hi, lo) = block.as_i64();
+                    *block = m128i::from_u64(hi as u64 ^ words[1], lo as u64 ^ words[0]);
+                }
+                encrypt(&mut text, &keys);
+                if heavy {
+                    mix_and_propagate(&mut text);
+                }
+            }
+        }
+        if heavy {
+            for _ in 0..16 {
+                encrypt(&mut text, &keys);
+                mix_and_propagate(&mut text);
+            }
+        }
+        for (block, bytes) in text.iter().zip(state[TEXT].chunks_mut(16)) {
+            let (hi, lo) = block.as_i64();
+            bytes[..8].copy_from_slice(&lo.to_le_bytes());
+            bytes[8..].copy_from_slice(&hi.to_le_bytes());
+        }
+    }
+}
+
+/// Keccak with a rate of 136 bytes, the whole 200 byte state is the result
+fn keccak1600(input: &[u8]) -> [u64; 25] {
+    const RATE: usize = 136;
+    let mut state = [0u64; 25];
+    let mut blocks = input.chunks_exact(RATE);
+    for block in &mut blocks {
+        absorb(&mut state, block);
+    }
+    let rest = blocks.remainder();
+    let mut last = [0u8; RATE];
+    last[..rest.len()].copy_from_slice(rest);
+    last[rest.len()] = 1;
+    last[RATE - 1] |= 0x80;
+    absorb(&mut state, &last);
+    state
+}
+
+fn absorb(state: &mut [u64; 25], block: &[u8]) {
+    for (word, chunk) in state.iter_mut().zip(block.chunks(8)) {
+        *word ^= u64::from_le_bytes(chunk.try_into().unwrap());
+    }
+    keccakf(state);
+}
+
+fn state_bytes(state: &[u64; 25]) -> [u8; 200] {
+    let mut bytes = [0u8; 200];
+    for (chunk, word) in bytes.chunks_mut(8).zip(state.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    bytes
+}
+
+/// The first 10 round keys of the AES-256 key
+fn round_keys(key: &[u8]) -> Vec<m128i> {
+    expand_key_256(key.try_into().unwrap(), 10)
+        .iter()
+        .map(|key| m128i::from_u8(key))
+        .collect()
+}
+
+fn text_blocks(state: &[u8; 200]) -> Vec<m128i> {
+    state[TEXT].chunks(16).map(m128i::from_u8).collect()
+}
+
+/// Every block of the text gets the 10 AES rounds
+fn encrypt(text: &mut [m128i], keys: &[m128i]) {
+    for block in text.iter_mut() {
+        *block = keys.iter().fold(*block, |block, key| block.aesenc(*key));
+    }
+}
+
+/// cn-heavy: every block of the text is xored with the next one
+fn mix_and_propagate(text: &mut [m128i]) {
+    let first = text[0];
+    for i in 0..text.len() {
+        let next = if i + 1 < text.len() {
+            text[i + 1]
+        } else {
+            first
+        };
+        let (hi, lo) = text[i].as_i64();
+        let (next_hi, next_lo) = next.as_i64();
+        text[i] = m128i::from_u64((hi ^ next_hi) as u64, (lo ^ next_lo) as u64);
+    }
+}
+
+/// The integer square root of variant 2, computed with a double
+fn int_sqrt_v2(n: u64) -> u64 {
+    let x = f64::from_bits((n >> 12) + (1023 << 52));
+    let mut r = x.sqrt().to_bits();
+    let s = r >> 20;
+    r >>= 19;
+    let x2 = s
+        .wrapping_sub(1022 << 32)
+        .wrapping_mul(r.wrapping_sub(s).wrapping_sub(1022 << 32).wrapping_add(1));
+    if x2 < n {
+        r += 1;
+    }
+    r
+}
+
+/// Word index of the 16 byte block the low word of `a` points to
+fn index(a: u64, mask: u64) -> usize {
+    (a & mask) as usize / 8
+}
+
+fn read(sp: &[u64], ix: usize) -> [u64; 2] {
+    [sp[ix], sp[ix + 1]]
+}
+
+fn write(sp: &mut [u64], ix: usize, block: [u64; 2]) {
+    sp[ix] = block[0];
+    sp[ix + 1] = block[1];
+}
+
+/// One AES round with `key` as round key, the blocks are [low, high] words
+fn aes_round(block: [u64; 2], key: [u64; 2]) -> [u64; 2] {
+    let (hi, lo) = m128i::from_u64(block[1], block[0])
+        .aesenc(m128i::from_u64(key[1], key[0]))
+        .as_i64();
+    [lo as u64, hi as u64]
+}
+
+/// Variant 2 and later: the other three blocks of the 64 byte line are added to each
+/// other. Returns the xor of the three blocks before, variant 4 mixes it in.
+fn shuffle_add(sp: &mut [u64], ix: usize, a: [u64; 2], b: [u64; 2], b1: [u64; 2]) -> [u64; 2] {
+    let chunk1 = read(sp, ix ^ 2);
+    let chunk2 = read(sp, ix ^ 4);
+    let chunk3 = read(sp, ix ^ 6);
+    write(sp, ix ^ 2, add(chunk3, b1));
+    write(sp, ix ^ 6, add(chunk2, a));
+    write(sp, ix ^ 4, add(chunk1, b));
+    [
+        chunk1[0] ^ chunk2[0] ^ chunk3[0],
+        chunk1[1] ^ chunk2[1] ^ chunk3[1],
+    ]
+}
+
+fn xor(x: &mut [u64; 2], y: [u64; 2]) {
+    x[0] ^= y[0];
+    x[1] ^= y[1];
+}
+
+fn add(x: [u64; 2], y: [u64; 2]) -> [u64; 2] {
+    [x[0].wrapping_add(y[0]), x[1].wrapping_add(y[1])]
+}
@@ -0,0 +1,256 @@
+//The random math of cn/r (CryptoNight variant 4). Every block height has its own
+//program of 60 to 70 integer instructions, generated so that its latency is the same
+//on all CPUs. It runs once in every iteration of the main loop.
+
+use blake_hash::{Blake256, Digest};
+
+/// Cycles of the longest dependency chain, equivalent to 15 multiplications
+const TOTAL_LATENCY: usize = 15 * 3;
+const NUM_INSTRUCTIONS_MIN: usize = 60;
+const NUM_INSTRUCTIONS_MAX: usize = 70;
+/// ALUs of the abstract CPU the latency is computed for, only one can multiply
+const ALU_COUNT_MUL: usize = 1;
+const ALU_COUNT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Mul,
+    /// dst + src + c
+    Add,
+    Sub,
+    Ror,
+    Rol,
+    Xor,
+}
+
+impl Opcode {
+    fn latency(self) -> usize {
+        match self {
+            Opcode::Mul => 3,
+            Opcode::Add | Opcode::Ror | Opcode::Rol => 2,
+            Opcode::Sub | Opcode::Xor => 1,
+        }
+    }
+
+    fn asic_latency(self) -> usize {
+        match self {
+            Opcode::Mul => 3,
+            _ => 1,
+        }
+    }
+
+    fn alus(self) -> usize {
+        match self {
+            Opcode::Mul => ALU_COUNT_MUL,
+            _ => ALU_COUNT,
+        }
+    }
+
+    fn is_rotation(self) -> bool {
+        matches!(self, Opcode::Ror | Opcode::Rol)
+    }
+
+    fn index(self) -> u32 {
+        self as u32
+    }
+}
+
+/// An instruction on the 9 registers, r0 to r3 are variable, r4 to r8 are loaded from
+/// the main loop before the program runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instr {
+    pub op: Opcode,
+    pub dst: usize,
+    pub src: usize,
+    pub c: u32,
+}
+
+/// The program of a block height
+pub fn generate(height: u64) -> Vec<Instr> {
+    let mut data = [0u8; 32];
+    data[..8].copy_from_slice(&height.to_le_bytes());
+    data[20] = 0xda;
+    let mut random = RandomBytes {
+        data,
+        //past the end, the data is hashed before the first byte is used
+        index: 32,
+    };
+
+    loop {
+        let code = try_generate(&mut random);
+        if let Some(code) = code {
+            return code;
+        }
+    }
+}
+
+/// Runs the program, the registers are 32 bit
+pub fn execute(code: &[Instr], r: &mut [u32; 9]) {
+    for instr in code {
+        let src = r[instr.src];
+        let dst = &mut r[instr.dst];
+        *dst = match instr.op {
+            Opcode::Mul => dst.wrapping_mul(src),
+            Opcode::Add => dst.wrapping_add(src).wrapping_add(instr.c),
+            Opcode::Sub => dst.wrapping_sub(src),
+            Opcode::Ror => dst.rotate_right(src % 32),
+            Opcode::Rol => dst.rotate_left(src % 32),
+            Opcode::Xor => *dst ^ src,
+        };
+    }
+}
+
+/// The random bytes of the generator, hashed with Blake-256 when they run out
+struct RandomBytes {
+    data: [u8; 32],
+    index: usize,
+}
+
+impl RandomBytes {
+    fn next(&mut self, count: usize) -> &[u8] {
+        if self.index + count > self.data.len() {
+            let hash = Blake256::digest(&self.data);
+            self.data.copy_from_slice(&hash);
+            self.index = 0;
+        }
+        self.index += count;
+        &self.data[self.index - count..self.index]
+    }
+}
+
+/// One attempt, None if r8 is not used or the instruction count is out of bounds.
+/// That happens for less than 2% of the heights, the next attempt continues with the
+/// following random bytes.
+fn try_generate(random: &mut RandomBytes) -> Option<Vec<Instr>> {
+    let mut code = Vec::with_capacity(NUM_INSTRUCTIONS_MAX);
+    let mut latency = [0usize; 9];
+    let mut asic_latency = [0usize; 9];
+    //the last instruction and source value of every register, r4 to r8 are constant and
+    //treated as the same value
+    let mut inst_data: [u32; 9] = [0, 1, 2, 3, 0xffffff, 0xffffff, 0xffffff, 0xffffff, 0xffffff];
+    let mut alu_busy = [[false; ALU_COUNT]; TOTAL_LATENCY + 1];
+    let mut rotated = [false; 4];
+    let mut rotate_count = 0;
+    let mut num_retries = 0;
+    let mut total_iterations = 0;
+    let mut r8_used = false;
+
+    while latency[..4].iter().any(|l| *l < TOTAL_LATENCY) && num_retries < 64 {
+        total_iterations += 1;
+        if total_iterations > 256 {
+            break;
+        }
+
+        let c = random.next(1)[0];
+        let op = match c & 7 {
+            0..=2 => Opcode::Mul,
+            3 => Opcode::Add,
+            4 => Opcode::Sub,
+            5 if (random.next(1)[0] as i8) >= 0 => Opcode::Ror,
+            5 => Opcode::Rol,
+            _ => Opcode::Xor,
+        };
+        let dst = ((c >> 3) & 3) as usize;
+        let mut src = ((c >> 5) & 7) as usize;
+
+        //ADD, SUB and XOR with the same register use r8 instead
+        if matches!(op, Opcode::Add | Opcode::Sub | Opcode::Xor) && dst == src {
+            src = 8;
+        }
+        //two rotations in a row are one rotation
+        if op.is_rotation() && rotated[dst] {
+            continue;
+        }
+        //the same instruction with the same source twice can be optimized into one,
+        //except for MUL
+        if op != Opcode::Mul
+            && (inst_data[dst] & 0xffff00) == (op.index() << 8) + ((inst_data[src] & 255) << 16)
+        {
+            continue;
+        }
+
+        //the first cycle with a free ALU for the instruction
+        let mut next_latency = latency[dst].max(latency[src]);
+        let mut alu = None;
+        while next_latency < TOTAL_LATENCY {
+            alu = (0..op.alus()).rev().find(|i| {
+                !alu_busy[next_latency][*i]
+                    && !(op == Opcode::Add && alu_busy[next_latency + 1][*i])
+                    && !(op.is_rotation() && next_latency < rotate_count * op.latency())
+            });
+            if alu.is_some() {
+                break;
+            }
+            next_latency += 1;
+        }
+
+        //no register stays unchanged for more than 7 cycles
+        if next_latency > latency[dst] + 7 {
+            continue;
+        }
+        next_latency += op.latency();
+
+        if next_latency <= TOTAL_LATENCY {
+            //next_latency < TOTAL_LATENCY before the instruction latency was added, so
+            //there was a free ALU
+            let alu = alu.unwrap_or(0);
+            if op.is_rotation() {
+                rotate_count += 1;
+            }
+            alu_busy[next_latency - op.latency()][alu] = true;
+            latency[dst] = next_latency;
+            asic_latency[dst] = asic_latency[dst].max(asic_latency[src]) + op.asic_latency();
+            rotated[dst] = op.is_rotation();
+            inst_data[dst] = code.len() as u32 + (op.index() << 8) + ((inst_data[src] & 255) << 16);
+            if src == 8 {
+                r8_used = true;
+            }
+
+            let mut c = 0;
+            if op == Opcode::Add {
+                //ADD is two instructions on a real CPU
+                alu_busy[next_latency - op.latency() + 1][alu] = true;
+                let bytes = random.next(4);
+                c = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+            code.push(Instr { op, dst, src, c });
+
+            if code.len() >= NUM_INSTRUCTIONS_MIN {
+                break;
+            }
+        } else {
+            num_retries += 1;
+        }
+    }
+
+    //an ASIC runs all independent instructions in parallel, MUL and ROR are added until
+    //one register has the latency there too
+    let prev_len = code.len();
+    while code.len() < NUM_INSTRUCTIONS_MAX && asic_latency[..4].iter().all(|l| *l < TOTAL_LATENCY)
+    {
+        let mut min_ix = 0;
+        let mut max_ix = 0;
+        for i in 1..4 {
+            if asic_latency[i] < asic_latency[min_ix] {
+                min_ix = i;
+            }
+            if asic_latency[i] > asic_latency[max_ix] {
+                max_ix = i;
+            }
+        }
+        let op = [Opcode::Ror, Opcode::Mul, Opcode::Mul][(code.len() - prev_len) % 3];
+        asic_latency[min_ix] = asic_latency[max_ix] + op.asic_latency();
+        code.push(Instr {
+            op,
+            dst: min_ix,
+            src: max_ix,
+            c: 0,
+        });
+    }
+
+    if r8_used && (NUM_INSTRUCTIONS_MIN..=NUM_INSTRUCTIONS_MAX).contains(&code.len()) {
+        Some(code)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,192 @@
+pub mod chukwa;
+pub mod cryptonight;
+pub mod randomx;
+
+use self::chukwa::Chukwa;
+use self::cryptonight::CryptoNight;
+use self::randomx::RandomX;
+use crate::randomx::memory::{DatasetError, MemoryError, VmMemoryAllocator};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The stratum names of the algorithms mithril can mine
+pub const ALGORITHMS: &[&str] = &[
+    "rx/0",
+    "rx/graft",
+    "rx/keva",
+    "cn/r",
+    "cn-heavy/0",
+    "cn-heavy/xhv",
+    "cn-pico",
+    "argon2/chukwa",
+    "argon2/chukwav2",
+];
+
+/// The algorithms of coins that use Monero addresses, the wallets of their pools are
+/// checked as Monero addresses
+pub const MONERO_ALGORITHMS: &[&str] = &["rx/0", "cn/r"];
+
+/// The algorithms without known-answer vectors of a reference miner, their hashes are
+/// only checked against this implementation and pools may reject all shares
+pub const UNVERIFIED_ALGORITHMS: &[&str] = &["rx/graft", "rx/keva"];
+
+/// The built in algorithms and the RandomX parameter sets of the config
+pub fn algorithm_names() -> Vec<&'static str> {
+    let mut names = ALGORITHMS.to_vec();
+    names.extend(randomx::custom_names());
+    names
+}
+
+/// The algorithm with the stratum name, None if it is not supported. The allocator is
+/// only created for RandomX and its derivatives.
+pub fn new_algorithm(
+    name: &str,
+    allocator: impl FnOnce() -> VmMemoryAllocator,
+) -> Option<Box<dyn PowAlgorithm>> {
+    if let Some(variant) = randomx::Variant::from_name(name) {
+        return Some(Box::new(RandomX::with_variant(variant, allocator())));
+    }
+    if !ALGORITHMS.contains(&name) {
+        return None;
+    }
+    if let Some(variant) = chukwa::Variant::from_name(name) {
+        return Some(Box::new(Chukwa::new(variant)));
+    }
+    cryptonight::Variant::from_name(name)
+        .map(|variant| Box::new(CryptoNight::new(variant)) as Box<dyn PowAlgorithm>)
+}
+
+/// What the stratum and worker layer read from the jobs of an algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobHints {
+    /// the jobs have a `seed_hash` that selects the shared memory, jobs without one are
+    /// ignored
+    pub seed_hash: bool,
+    /// the jobs have the block `height`, i.e. for the per block programs of cn/r
+    pub height: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRequirements {
+    /// memory shared by all threads, i.e. the RandomX dataset and cache
+    pub shared_bytes: u64,
+    /// memory of every worker thread, i.e. the scratchpad
+    pub thread_bytes: u64,
+}
+
+impl MemoryRequirements {
+    pub fn total_bytes(&self, threads: u64) -> u64 {
+        self.shared_bytes + self.thread_bytes * threads
+    }
+}
+
+/// The memory an algorithm holds, for the statistics. Farms check it to see that a rig
+/// really mines in full mode on large pages.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// the Argon2 cache of the RandomX seed
+    pub cache_bytes: u64,
+    /// allocated for the dataset, 0 in light mode
+    pub dataset_bytes: u64,
+    /// the computed dataset items
+    pub dataset_filled_bytes: u64,
+    /// the part of the dataset on transparent huge pages
+    pub dataset_huge_page_bytes: u64,
+    /// the page size of the dataset and why, None without a dataset
+    pub dataset_pages: Option<String>,
+    /// the NUMA placement of the dataset pages, None without a dataset
+    pub dataset_numa: Option<String>,
+    /// the scratchpads of the worker threads
+    pub scratchpads: u64,
+    pub scratchpad_bytes: u64,
+    pub large_page_scratchpads: u64,
+}
+
+/// The result of `PowMemory::verify`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityCheck {
+    /// the checksum of the initialised memory is unchanged
+    pub checksum_matches: bool,
+    /// number of sampled parts that were computed again
+    pub samples: u64,
+    /// the sampled parts that differ from a new computation, i.e. dataset item numbers
+    pub corrupt: Vec<u64>,
+}
+
+impl IntegrityCheck {
+    /// A checksum mismatch counts as one error, every corrupt sample as another
+    pub fn errors(&self) -> u64 {
+        self.corrupt.len() as u64 + u64::from(!self.checksum_matches)
+    }
+}
+
+/// The hashing state of a worker thread, i.e. a VM with its scratchpad
+pub trait PowHasher {
+    fn hash(&mut self, input: &[u8]) -> [u8; 32];
+
+    /// `hash` for the worker threads, fails instead of panicking if the hasher reads
+    /// outside of its memory
+    fn try_hash(&mut self, input: &[u8]) -> Result<[u8; 32], DatasetError> {
+        Ok(self.hash(input))
+    }
+}
+
+/// The memory that the threads of a job share, i.e. the RandomX dataset of the seed
+pub trait PowMemory: Send + Sync {
+    /// A hasher of a worker thread, created for every job
+    fn hasher(self: Arc<Self>) -> Box<dyn PowHasher>;
+
+    /// `full` or `light`, `scratchpad` without shared memory, for the statistics
+    fn mode(&self) -> &'static str;
+
+    /// Fraction of the shared memory that is initialised, 0 if it is computed on access
+    fn fill(&self) -> f64;
+
+    /// True while the shared memory is computed in the background, the hashes are
+    /// slower until it is done
+    fn initialising(&self) -> bool {
+        false
+    }
+
+    /// The shared memory and the scratchpads of the hashers that are alive
+    fn usage(&self) -> MemoryUsage;
+
+    /// Checks the initialised shared memory for corruption by failing RAM, `samples`
+    /// random parts selected by `seed` are computed again. None if nothing is shared.
+    fn verify(&self, samples: u64, seed: u64) -> Option<IntegrityCheck>;
+
+    /// Computes the shared memory again with the given number of threads, i.e. after
+    /// `verify` found corruption
+    fn rebuild(&self, threads: usize);
+}
+
+/// A proof-of-work algorithm, it owns the memory the worker threads share. The worker
+/// pool prepares it for every job and hands the memory to the threads.
+pub trait PowAlgorithm: Send {
+    /// The name of the algorithm in stratum, i.e. `rx/0`
+    fn name(&self) -> &'static str;
+
+    fn job_hints(&self) -> JobHints;
+
+    /// The memory of the current mode
+    fn memory_requirements(&self) -> MemoryRequirements;
+
+    /// Prepares the memory for the seed hash and height of a job, the seed hash is empty
+    /// and the height 0 if the jobs have none. Returns the init time, or None if the
+    /// memory did not change.
+    fn prepare(&mut self, seed_hash: &str, height: u64) -> Result<Option<Duration>, MemoryError>;
+
+    /// The memory of the last prepared job
+    fn memory(&self) -> Arc<dyn PowMemory>;
+
+    /// False if the algorithm has no mode with less memory, or already uses it
+    fn full_memory(&self) -> bool;
+
+    /// Switches to the mode with less memory after a memory error, the memory is
+    /// prepared again for the next job
+    fn light_mode(&mut self);
+
+    /// The algorithm with the same memory, it is kept from one pool connection to the next
+    fn clone_box(&self) -> Box<dyn PowAlgorithm>;
+}
@@ -0,0 +1,656 @@
+extern crate crossbeam_channel;
+
+use self::crossbeam_channel::{unbounded, Receiver, Sender};
+use super::{
+    IntegrityCheck, JobHints, MemoryRequirements, MemoryUsage, PowAlgorithm, PowHasher, PowMemory,
+};
+use crate::cgroup;
+use crate::dataset_server;
+use crate::platform;
+use crate::profile::Priority;
+use crate::randomx::large_alloc::{self, AllocKind, Allocators};
+use crate::randomx::memory::{
+    self, DatasetError, DatasetNuma, DatasetPages, MemoryError, VmMemory, VmMemoryAllocator,
+    CACHE_LINE_SIZE,
+};
+use crate::randomx::pages::NumaPolicy;
+use crate::randomx::vm::{new_vm, Vm};
+use crate::randomx::wipe::wipe_string;
+use crate::randomx::RandomXConfig;
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, Once, OnceLock, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+use strum::{Display, EnumString};
+
+/// The 256 MB cache, enough for the light mode
+pub const LIGHT_MEMORY_BYTES: u64 = 256 * 1024 * 1024;
+/// The scratchpad of rx/0
+pub const SCRATCHPAD_BYTES: u64 = 2 * 1024 * 1024;
+
+static LARGE_PAGES_UNAVAILABLE: Once = Once::new();
+
+/// How often the watchdog of the dataset init looks at its progress
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// See `init_stalls`
+static INIT_STALLS: OnceLock<(Sender<DatasetError>, Receiver<DatasetError>)> = OnceLock::new();
+
+/// The memories whose dataset is computed by `start_dataset_init`, by address
+static BACKGROUND_INITS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// The memories of all RandomX instances by seed hash, with dual mining both pools use
+/// the same dataset if the seed, the parameters and the mode are the same
+static SHARED_MEMORY: Mutex<Vec<(String, Weak<VmMemory>)>> = Mutex::new(Vec::new());
+
+/// The `[[randomx]]` parameter sets of the config. They are leaked so that `Variant`
+/// stays `Copy`, only a reload with changed parameters leaks more.
+static CUSTOM_VARIANTS: Mutex<Vec<&'static CustomVariant>> = Mutex::new(Vec::new());
+
+/// A RandomX parameter set of the config, for forks that are not built in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomVariant {
+    /// the stratum name, i.e. the `algorithm` of the pools
+    pub name: String,
+    pub config: RandomXConfig,
+}
+
+/// The parameter sets of RandomX, the published ones use the dataset of Monero
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// Monero
+    Rx0,
+    /// Graft, two Argon2 lanes and longer programs
+    Graft,
+    /// Kevacoin, 1 MB scratchpad
+    Keva,
+    /// registered with `register_custom`
+    Custom(&'static CustomVariant),
+}
+
+impl Variant {
+    pub const ALL: [Variant; 3] = [Variant::Rx0, Variant::Graft, Variant::Keva];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Rx0 => "rx/0",
+            Variant::Graft => "rx/graft",
+            Variant::Keva => "rx/keva",
+            Variant::Custom(custom) => &custom.name,
+        }
+    }
+
+    /// The variant with the stratum name, built in or registered
+    pub fn from_name(name: &str) -> Option<Variant> {
+        Variant::ALL
+            .into_iter()
+            .find(|v| v.name() == name)
+            .or_else(|| {
+                lock_custom_variants()
+                    .iter()
+                    .find(|custom| custom.name == name)
+                    .map(|custom| Variant::Custom(custom))
+            })
+    }
+
+    pub fn config(&self) -> RandomXConfig {
+        match self {
+            Variant::Rx0 => RandomXConfig::monero(),
+            Variant::Graft => RandomXConfig::graft(),
+            Variant::Keva => RandomXConfig::keva(),
+            Variant::Custom(custom) => custom.config.clone(),
+        }
+    }
+}
+
+/// Makes the parameter sets known to `Variant::from_name` and `pow::new_algorithm`, the
+/// ones of an earlier config are replaced
+pub fn register_custom(variants: &[CustomVariant]) {
+    let mut registered = lock_custom_variants();
+    let custom = variants
+        .iter()
+        .map(
+            |variant| match registered.iter().find(|r| ***r == *variant) {
+                Some(same) => *same,
+                None => &*Box::leak(Box::new(variant.clone())),
+            },
+        )
+        .collect();
+    *registered = custom;
+}
+
+/// The names of the registered parameter sets
+pub fn custom_names() -> Vec<&'static str> {
+    lock_custom_variants()
+        .iter()
+        .map(|custom| custom.name.as_str())
+        .collect()
+}
+
+fn lock_custom_variants() -> MutexGuard<'static, Vec<&'static CustomVariant>> {
+    CUSTOM_VARIANTS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+/// How the dataset of a new seed is computed in the background, see `set_dataset_init`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DatasetInitConfig {
+    /// compute the dataset up front, otherwise the workers compute the items on access
+    pub background: bool,
+    /// 0 uses all available cores
+    pub threads: u64,
+    pub priority: Priority,
+    /// 0 for no limit
+    pub mb_per_second: u64,
+    /// the init is aborted after this long without progress, 0 never aborts it
+    pub stall_seconds: u64,
+    /// inits after a stall, then the miner falls back to the light mode
+    pub stall_retries: u64,
+}
+
+/// None until the config is read, i.e. for the benchmark, no background init
+static DATASET_INIT: Mutex<Option<DatasetInitConfig>> = Mutex::new(None);
+
+/// Sets how the dataset of the next seeds is computed
+pub fn set_dataset_init(conf: DatasetInitConfig) {
+    *DATASET_INIT.lock().unwrap_or_else(|err| err.into_inner()) = Some(conf);
+}
+
+/// Whether the 2 GB dataset is used, `memory.mode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum MemoryMode {
+    /// the full mode unless the memory limit of the cgroup is too low
+    #[default]
+    Auto,
+    /// the full mode even if the memory limit looks too low
+    Fast,
+    /// only the 256 MB cache, the dataset items are computed for each hash
+    Light,
+}
+
+impl MemoryMode {
+    /// True for the full mode, `fits_full_memory` is the check of the cgroup limit
+    pub fn full_memory(self, fits_full_memory: bool) -> bool {
+        match self {
+            MemoryMode::Auto => fits_full_memory,
+            MemoryMode::Fast => true,
+            MemoryMode::Light => false,
+        }
+    }
+}
+
+/// The `[memory]` section
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MemoryConfig {
+    /// full or light mode, a memory error still falls back to the light mode
+    pub mode: MemoryMode,
+    /// placement of the dataset on the NUMA nodes
+    pub numa: NumaPolicy,
+    /// the allocator of the dataset, cache and scratchpads, None for the default ones
+    pub allocator: Option<AllocKind>,
+    /// socket of a `mithril dataset-server`, the full mode maps its datasets
+    pub dataset_server: Option<PathBuf>,
+}
+
+/// None if the datasets are computed by the miner, see `set_memory`
+static DATASET_SERVER: Mutex<Option<PathBuf>> = Mutex::new(None);
+static MEMORY_MODE: Mutex<MemoryMode> = Mutex::new(MemoryMode::Auto);
+
+/// Sets how the memory of the next seeds is allocated
+pub fn set_memory(conf: MemoryConfig) {
+    memory::set_numa_policy(conf.numa);
+    if let Some(kind) = conf.allocator {
+        large_alloc::set_allocators(Allocators::all(kind.allocator()));
+    }
+    *DATASET_SERVER.lock().unwrap_or_else(|err| err.into_inner()) = conf.dataset_server;
+    *MEMORY_MODE.lock().unwrap_or_else(|err| err.into_inner()) = conf.mode;
+}
+
+/// The configured mode, auto until the config is read
+pub fn memory_mode() -> MemoryMode {
+    *MEMORY_MODE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Computes the dataset on threads of its own, so that an epoch change on a desktop
+/// does not freeze it. The workers compute the items they need before the init does.
+/// An init that stalls is retried, after the retries the stall is sent to `init_stalls`.
+fn start_dataset_init(memory: Arc<VmMemory>) {
+    let conf = *DATASET_INIT.lock().unwrap_or_else(|err| err.into_inner());
+    let conf = match conf {
+        Some(conf) if conf.background && memory.cache => conf,
+        _ => return,
+    };
+    let threads = match conf.threads {
+        0 => cgroup::available_cpus(),
+        threads => threads as usize,
+    };
+    let items_per_second = conf.mb_per_second * 1024 * 1024 / CACHE_LINE_SIZE;
+    let background = BackgroundInit::start(&memory);
+    let spawned = thread::Builder::new()
+        .name("dataset init watchdog".to_string())
+        .spawn(move || {
+            let _background = background;
+            let start = Instant::now();
+            let stall = Duration::from_secs(conf.stall_seconds);
+            for attempt in 0..=conf.stall_retries {
+                let outcome = watch_dataset_init(
+                    &memory,
+                    threads,
+                    items_per_second,
+                    move || {
+                        if let Err(err) = platform::set_thread_priority(conf.priority) {
+                            debug!("dataset init keeps the normal priority: {}", err);
+                        }
+                    },
+                    stall,
+                    WATCHDOG_INTERVAL,
+                );
+                if outcome == InitOutcome::Finished {
+                    if memory.dataset_fill() >= 1.0 {
+                        info!(
+                            "dataset initialised in {:?} with {} threads",
+                            start.elapsed(),
+                            threads
+                        );
+                    }
+                    return;
+                }
+                warn!("{}", stall_report(&memory, threads, stall, start.elapsed()));
+                if attempt < conf.stall_retries {
+                    warn!("dataset init aborted, starting it again");
+                }
+            }
+            if memory.is_retired() {
+                return;
+            }
+            error!(
+                "the dataset init stalled {} times, falling back to the light mode",
+                conf.stall_retries + 1
+            );
+            let _ = init_stall_channel().0.send(DatasetError::InitStalled {
+                seconds: conf.stall_seconds,
+            });
+        });
+    if let Err(err) = spawned {
+        warn!("dataset init thread could not be started: {}", err);
+    }
+}
+
+/// Registers a memory in `BACKGROUND_INITS` until it is dropped, i.e. when the watchdog
+/// of its init is done
+struct BackgroundInit(usize);
+
+impl BackgroundInit {
+    fn start(memory: &Arc<VmMemory>) -> BackgroundInit {
+        let address = Arc::as_ptr(memory) as usize;
+        lock_background_inits().push(address);
+        BackgroundInit(address)
+    }
+}
+
+impl Drop for BackgroundInit {
+    fn drop(&mut self) {
+        lock_background_inits().retain(|&address| address != self.0);
+    }
+}
+
+fn lock_background_inits() -> MutexGuard<'static, Vec<usize>> {
+    BACKGROUND_INITS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+/// How a dataset init watched by `watch_dataset_init` ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitOutcome {
+    /// all items are computed or the memory was retired
+    Finished,
+    /// no progress for the stall time, the init was aborted
+    Stalled,
+}
+
+/// Runs `VmMemory::init_dataset_with` on a thread of its own and looks at its progress
+/// every `interval`. After `stall` without progress the init is aborted, a zero `stall`
+/// waits for it however long it takes.
+pub fn watch_dataset_init(
+    memory: &Arc<VmMemory>,
+    threads: usize,
+    items_per_second: u64,
+    thread_start: impl Fn() + Send + Sync + 'static,
+    stall: Duration,
+    interval: Duration,
+) -> InitOutcome {
+    let init_memory = memory.clone();
+    let spawned = thread::Builder::new()
+        .name("dataset init thread".to_string())
+        .spawn(move || init_memory.init_dataset_with(threads, items_per_second, thread_start));
+    let init = match spawned {
+        Ok(init) => init,
+        Err(err) => {
+            warn!("dataset init thread could not be started: {}", err);
+            return InitOutcome::Finished;
+        }
+    };
+    let mut progress = memory.init_progress();
+    let mut progressed = Instant::now();
+    loop {
+        thread::sleep(interval);
+        if init.is_finished() {
+            return InitOutcome::Finished;
+        }
+        let current = memory.init_progress();
+        if current != progress {
+            progress = current;
+            progressed = Instant::now();
+        } else if !stall.is_zero() && progressed.elapsed() >= stall {
+            memory.abort_init();
+            return InitOutcome::Stalled;
+        }
+    }
+}
+
+/// What is known about a stalled init, for the log
+pub fn stall_report(
+    memory: &VmMemory,
+    threads: usize,
+    stall: Duration,
+    elapsed: Duration,
+) -> String {
+    let items = memory.seed_memory.config.dataset_item_count();
+    let mut report = format!(
+        "the dataset init made no progress for {}s, {:.1}% of the {} items computed in {}s with {} threads",
+        stall.as_secs(),
+        memory.dataset_fill() * 100.0,
+        items,
+        elapsed.as_secs(),
+        threads
+    );
+    if let Some(pages) = &memory.dataset_pages {
+        report.push_str(&format!(", the dataset uses {}", pages));
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(available) = std::fs::read_to_string("/proc/meminfo")
+        .ok()
+        .and_then(|meminfo| crate::doctor::meminfo_field(&meminfo, "MemAvailable"))
+    {
+        report.push_str(&format!(", {} MB of memory available", available / 1024));
+    }
+    report
+}
+
+fn init_stall_channel() -> &'static (Sender<DatasetError>, Receiver<DatasetError>) {
+    INIT_STALLS.get_or_init(unbounded)
+}
+
+/// The dataset inits that stalled after all retries, the main loop falls back to the
+/// light mode
+pub fn init_stalls() -> Receiver<DatasetError> {
+    init_stall_channel().1.clone()
+}
+
+/// RandomX or one of its derivatives, the memory is allocated per seed hash
+#[derive(Clone)]
+pub struct RandomX {
+    variant: Variant,
+    allocator: VmMemoryAllocator,
+}
+
+impl RandomX {
+    /// rx/0 with the memory of the allocator
+    /// The dataset of the dataset server in full mode, None if there is no server or it
+    /// failed, the dataset is computed by the miner then
+    fn server_memory(&self, seed_hash: &str) -> Option<VmMemory> {
+        if !self.allocator.full || *self.allocator.config != self.variant.config() {
+            return None;
+        }
+        let socket = DATASET_SERVER
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()?;
+        match dataset_server::request(&socket, self.variant, seed_hash) {
+            Ok(memory) => {
+                info!(
+                    "mapped the dataset of seed_hash {} from the dataset server",
+                    seed_hash
+                );
+                Some(memory)
+            }
+            Err(err) => {
+                warn!(
+                    "dataset server {} failed, computing the dataset: {}",
+                    socket.display(),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    pub fn new(allocator: VmMemoryAllocator) -> RandomX {
+        RandomX::with_variant(Variant::Rx0, allocator)
+    }
+
+    /// The variant in the mode of the allocator, memory of other parameters is dropped
+    pub fn with_variant(variant: Variant, allocator: VmMemoryAllocator) -> RandomX {
+        let config = variant.config();
+        let allocator = if *allocator.config == config {
+            allocator
+        } else {
+            VmMemoryAllocator::with_config(allocator.full, Arc::new(config))
+        };
+        RandomX { variant, allocator }
+    }
+}
+
+impl PowAlgorithm for RandomX {
+    fn name(&self) -> &'static str {
+        self.variant.name()
+    }
+
+    fn job_hints(&self) -> JobHints {
+        JobHints {
+            seed_hash: true,
+            height: false,
+        }
+    }
+
+    fn memory_requirements(&self) -> MemoryRequirements {
+        MemoryRequirements {
+            shared_bytes: if self.allocator.full {
+                self.allocator.config.dataset_bytes() + self.allocator.config.cache_bytes()
+            } else {
+                self.allocator.config.cache_bytes()
+            },
+            thread_bytes: self.allocator.config.scratchpad_l3_bytes as u64,
+        }
+    }
+
+    fn prepare(&mut self, seed_hash: &str, _height: u64) -> Result<Option<Duration>, MemoryError> {
+        if seed_hash == self.allocator.vm_memory_seed {
+            return Ok(None);
+        }
+        //locked during the init, an instance with the same seed waits and shares it
+        let mut shared = SHARED_MEMORY.lock().unwrap_or_else(|err| err.into_inner());
+        shared.retain(|(_, memory)| memory.strong_count() > 0);
+        let same_memory = shared.iter().find_map(|(seed, memory)| {
+            memory.upgrade().filter(|memory| {
+                seed == seed_hash
+                    && memory.cache == self.allocator.full
+                    && *memory.seed_memory.config == *self.allocator.config
+            })
+        });
+        if let Some(memory) = same_memory {
+            info!("sharing the memory of seed_hash {}", seed_hash);
+            memory::retire(std::mem::replace(&mut self.allocator.vm_memory, memory));
+            wipe_string(&mut std::mem::replace(
+                &mut self.allocator.vm_memory_seed,
+                seed_hash.to_string(),
+            ));
+            return Ok(Some(Duration::ZERO));
+        }
+        let start = Instant::now();
+        if let Some(memory) = self.server_memory(seed_hash) {
+            memory::retire(std::mem::replace(
+                &mut self.allocator.vm_memory,
+                Arc::new(memory),
+            ));
+            wipe_string(&mut std::mem::replace(
+                &mut self.allocator.vm_memory_seed,
+                seed_hash.to_string(),
+            ));
+            shared.push((
+                seed_hash.to_string(),
+                Arc::downgrade(&self.allocator.vm_memory),
+            ));
+            return Ok(Some(start.elapsed()));
+        }
+        let init_time = match self.allocator.reallocate(seed_hash.to_string()) {
+            Err(MemoryError::Allocation {
+                what: "dataset",
+                bytes,
+                reason,
+            }) => {
+                warn_dataset_allocation(bytes, &reason);
+                self.light_mode();
+                self.allocator.reallocate(seed_hash.to_string())?
+            }
+            init_time => init_time?,
+        };
+        shared.push((
+            seed_hash.to_string(),
+            Arc::downgrade(&self.allocator.vm_memory),
+        ));
+        if init_time.is_some() {
+            if let Some(pages) = &self.allocator.vm_memory.dataset_pages {
+                info!("dataset uses {}", pages);
+            }
+            match &self.allocator.vm_memory.dataset_numa {
+                Some(numa @ DatasetNuma::Failed(_)) => warn!("dataset pages are {}", numa),
+                Some(numa @ DatasetNuma::Interleaved(_)) => info!("dataset pages are {}", numa),
+                _ => {}
+            }
+            start_dataset_init(self.allocator.vm_memory.clone());
+        }
+        Ok(init_time)
+    }
+
+    fn memory(&self) -> Arc<dyn PowMemory> {
+        self.allocator.vm_memory.clone()
+    }
+
+    fn full_memory(&self) -> bool {
+        self.allocator.full
+    }
+
+    fn light_mode(&mut self) {
+        let light = VmMemoryAllocator::with_config(false, self.allocator.config.clone());
+        let mut replaced = std::mem::replace(&mut self.allocator, light);
+        wipe_string(&mut replaced.vm_memory_seed);
+        memory::retire(replaced.vm_memory);
+    }
+
+    fn clone_box(&self) -> Box<dyn PowAlgorithm> {
+        Box::new(self.clone())
+    }
+}
+
+/// Logs the fallback to the light mode after the dataset could not be allocated, several
+/// lines so it is not missed between the job logs
+fn warn_dataset_allocation(bytes: u64, reason: &str) {
+    warn!("**********************************************************************");
+    warn!(
+        "the {} MB dataset could not be allocated: {}",
+        bytes / 1024 / 1024,
+        reason
+    );
+    warn!("continuing in the light mode, with about a fifth of the hashrate");
+    warn!("free some memory and restart, or set mode = \"light\" in [memory]");
+    warn!("**********************************************************************");
+}
+
+impl PowMemory for VmMemory {
+    fn hasher(self: Arc<Self>) -> Box<dyn PowHasher> {
+        let vm = new_vm(self);
+        if let Some(err) = vm.scratchpad.large_pages_error() {
+            LARGE_PAGES_UNAVAILABLE.call_once(|| {
+                info!("large pages are not available, using normal pages: {}", err);
+            });
+        }
+        Box::new(vm)
+    }
+
+    fn mode(&self) -> &'static str {
+        VmMemory::mode(self)
+    }
+
+    fn fill(&self) -> f64 {
+        self.dataset_fill()
+    }
+
+    fn initialising(&self) -> bool {
+        let address = self as *const VmMemory as usize;
+        lock_background_inits().contains(&address)
+    }
+
+    fn usage(&self) -> MemoryUsage {
+        let scratchpads = self.scratchpads.buffers();
+        MemoryUsage {
+            cache_bytes: self.cache_bytes(),
+            dataset_bytes: self.dataset_bytes(),
+            dataset_filled_bytes: self.dataset_filled_bytes(),
+            dataset_huge_page_bytes: match &self.dataset_pages {
+                Some(DatasetPages::Huge(backing)) => backing.huge_bytes,
+                Some(DatasetPages::Reserved(bytes)) => *bytes,
+                _ => 0,
+            },
+            dataset_pages: self.dataset_pages.as_ref().map(|pages| pages.to_string()),
+            dataset_numa: self.dataset_numa.as_ref().map(|numa| numa.to_string()),
+            scratchpads,
+            scratchpad_bytes: scratchpads * self.seed_memory.config.scratchpad_l3_bytes as u64,
+            large_page_scratchpads: self.scratchpads.large_pages(),
+        }
+    }
+
+    fn verify(&self, samples: u64, seed: u64) -> Option<IntegrityCheck> {
+        if !self.cache {
+            return None;
+        }
+        let item_count = self.seed_memory.config.dataset_item_count() as u64;
+        let mut state = seed | 1;
+        let item_nums: Vec<u64> = (0..samples)
+            .map(|_| {
+                //xorshift64, good enough to spread the samples
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state % item_count
+            })
+            .collect();
+        Some(IntegrityCheck {
+            checksum_matches: self.verify_checksum(),
+            samples,
+            corrupt: self.verify_items(&item_nums),
+        })
+    }
+
+    fn rebuild(&self, threads: usize) {
+        self.rebuild_dataset(threads);
+    }
+}
+
+impl PowHasher for Vm {
+    fn hash(&mut self, input: &[u8]) -> [u8; 32] {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(self.calculate_hash(input).as_bytes());
+        hash
+    }
+
+    fn try_hash(&mut self, input: &[u8]) -> Result<[u8; 32], DatasetError> {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(self.try_calculate_hash(input)?.as_bytes());
+        Ok(hash)
+    }
+}
@@ -0,0 +1,152 @@
+#[cfg(unix)]
+extern crate libc;
+
+use std::io;
+
+#[derive(Debug, Clone)]
+pub struct PrivilegeConfig {
+    /// user that mithril switches to when started as root, empty keeps root
+    pub user: String,
+    /// group of the user, empty uses the primary group of `user`
+    pub group: String,
+}
+
+/// The unprivileged account from the passwd and group databases
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub user: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+}
+
+/// Switches to the configured user and group if running as root: clears the
+/// supplementary groups, then sets the group and the user id. Returns the account or
+/// None if there was nothing to drop. Has to be called before any thread is started,
+/// `HOME` is set to the home of the user.
+#[cfg(unix)]
+pub fn drop_privileges(conf: &PrivilegeConfig) -> io::Result<Option<Account>> {
+    if conf.user.is_empty() || !is_root() {
+        return Ok(None);
+    }
+    let mut account = lookup_user(&conf.user)?;
+    if !conf.group.is_empty() {
+        account.gid = lookup_group(&conf.group)?;
+    }
+
+    unsafe {
+        if libc::setgroups(0, std::ptr::null()) != 0 {
+            return Err(last_error("clearing the supplementary groups"));
+        }
+        if libc::setgid(account.gid as libc::gid_t) != 0 {
+            return Err(last_error("setgid"));
+        }
+        if libc::setuid(account.uid as libc::uid_t) != 0 {
+            return Err(last_error("setuid"));
+        }
+        //a saved root id would allow to switch back
+        if account.uid != 0 && libc::setuid(0) == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "root privileges could be regained after setuid",
+            ));
+        }
+    }
+    //the mithril folder (~/.mithril) is looked up by HOME
+    std::env::set_var("HOME", &account.home);
+    Ok(Some(account))
+}
+
+#[cfg(not(unix))]
+pub fn drop_privileges(conf: &PrivilegeConfig) -> io::Result<Option<Account>> {
+    if conf.user.is_empty() {
+        return Ok(None);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "privileges.user is only supported on unix",
+    ))
+}
+
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
+
+#[cfg(unix)]
+pub fn lookup_user(name: &str) -> io::Result<Account> {
+    let c_name = c_string(name)?;
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let mut buf = vec![0 as libc::c_char; 16384];
+    let err = unsafe {
+        libc::getpwnam_r(
+            c_name.as_ptr(),
+            &mut passwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if err != 0 {
+        return Err(io::Error::from_raw_os_error(err));
+    }
+    if result.is_null() {
+        return Err(not_found("user", name));
+    }
+    let home = unsafe { std::ffi::CStr::from_ptr(passwd.pw_dir) };
+    Ok(Account {
+        user: name.to_string(),
+        uid: passwd.pw_uid as u32,
+        gid: passwd.pw_gid as u32,
+        home: home.to_string_lossy().to_string(),
+    })
+}
+
+#[cfg(unix)]
+pub fn lookup_group(name: &str) -> io::Result<u32> {
+    let c_name = c_string(name)?;
+    let mut group: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+    let mut buf = vec![0 as libc::c_char; 16384];
+    let err = unsafe {
+        libc::getgrnam_r(
+            c_name.as_ptr(),
+            &mut group,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if err != 0 {
+        return Err(io::Error::from_raw_os_error(err));
+    }
+    if result.is_null() {
+        return Err(not_found("group", name));
+    }
+    Ok(group.gr_gid as u32)
+}
+
+#[cfg(unix)]
+fn c_string(name: &str) -> io::Result<std::ffi::CString> {
+    std::ffi::CString::new(name).map_err(|_| not_found("name", name))
+}
+
+#[cfg(unix)]
+fn not_found(kind: &str, name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("unknown {} {}", kind, name),
+    )
+}
+
+#[cfg(unix)]
+fn last_error(action: &str) -> io::Error {
+    let err = io::Error::last_os_error();
+    io::Error::new(err.kind(), format!("{} failed: {}", action, err))
+}
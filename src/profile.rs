@@ -0,0 +1,105 @@
+use crate::worker::worker_pool::WorkerConfig;
+
+use strum::{Display, EnumString};
+
+/// Scheduling priority of the worker threads
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Priority {
+    /// the priority mithril was started with
+    Normal,
+    Low,
+    /// only runs if nothing else wants the CPU
+    Idle,
+}
+
+impl Priority {
+    /// The nice value of the worker threads
+    pub fn nice(self) -> i32 {
+        match self {
+            Priority::Normal => 0,
+            Priority::Low => 10,
+            Priority::Idle => 19,
+        }
+    }
+}
+
+/// A preset for the worker threads, selected with `worker.profile`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    /// share of the logical cores that get a worker thread
+    pub threads_percent: u64,
+    pub priority: Priority,
+    /// share of the time the workers sleep between hashes
+    pub throttle_percent: u64,
+}
+
+pub const MAX_THROTTLE_PERCENT: u64 = 90;
+
+impl Profile {
+    /// At least one thread
+    pub fn num_threads(&self, logical_cores: u64) -> u64 {
+        (logical_cores * self.threads_percent / 100).max(1)
+    }
+
+    /// Sets the threads, priority and throttling, auto tuning is disabled because the
+    /// profile fixes the thread count
+    pub fn apply(&self, worker_conf: &mut WorkerConfig, logical_cores: u64) {
+        worker_conf.profile = self.name.clone();
+        worker_conf.num_threads = self.num_threads(logical_cores);
+        worker_conf.auto_tune = false;
+        worker_conf.priority = self.priority;
+        worker_conf.throttle_percent = self.throttle_percent;
+    }
+}
+
+/// `eco`, `balanced` and `max`
+pub fn builtin_profiles() -> Vec<Profile> {
+    vec![
+        Profile {
+            name: "eco".to_string(),
+            threads_percent: 25,
+            priority: Priority::Idle,
+            throttle_percent: 50,
+        },
+        Profile {
+            name: "balanced".to_string(),
+            threads_percent: 50,
+            priority: Priority::Low,
+            throttle_percent: 0,
+        },
+        Profile {
+            name: "max".to_string(),
+            threads_percent: 100,
+            priority: Priority::Normal,
+            throttle_percent: 0,
+        },
+    ]
+}
+
+/// The built-in profiles followed by the user profiles, a user profile replaces a
+/// built-in one with the same name
+pub fn all_profiles(user: &[Profile]) -> Vec<Profile> {
+    let mut profiles: Vec<Profile> = builtin_profiles()
+        .into_iter()
+        .filter(|b| !user.iter().any(|u| u.name == b.name))
+        .collect();
+    profiles.extend(user.iter().cloned());
+    profiles
+}
+
+pub fn find(name: &str, user: &[Profile]) -> Option<Profile> {
+    all_profiles(user).into_iter().find(|p| p.name == name)
+}
+
+/// The profile after `current` (the first one if `current` is no profile), for cycling
+/// through the profiles with a hotkey
+pub fn next(current: &str, user: &[Profile]) -> Profile {
+    let profiles = all_profiles(user);
+    let ix = profiles
+        .iter()
+        .position(|p| p.name == current)
+        .map_or(0, |ix| (ix + 1) % profiles.len());
+    profiles[ix].clone()
+}
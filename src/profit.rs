@@ -0,0 +1,192 @@
+extern crate crossbeam_channel;
+extern crate ureq;
+
+use self::crossbeam_channel::Sender;
+use crate::bench::{self, BenchResults};
+use crate::control::ControlCmd;
+use crate::signals;
+use crate::stratum::stratum_data::PoolConfig;
+
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfitConfig {
+    pub enabled: bool,
+    /// returns the `CoinMarket` of every coin as JSON object by coin name
+    pub url: String,
+    pub interval_minutes: u64,
+    /// a coin has to earn this much more than the current one before the pool is
+    /// switched, so that small price changes do not switch back and forth
+    pub hysteresis_percent: f64,
+}
+
+/// A `[[coin]]` of the profit switcher
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoinConfig {
+    /// the key of the coin in the API response
+    pub name: String,
+    /// H/s of this miner for the algorithm of the coin, 0 to use the one of
+    /// `mithril bench --algo`
+    pub hashrate: f64,
+    pub pool_conf: PoolConfig,
+}
+
+/// The market data of a coin in the API response
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct CoinMarket {
+    /// of one coin, in any currency that is the same for all coins
+    pub price: f64,
+    /// network difficulty, the expected number of hashes per block
+    pub difficulty: f64,
+    /// coins per block
+    pub reward: f64,
+}
+
+/// Expected earnings per day of the hashrate, in the currency of the price
+pub fn daily_revenue(hashrate: f64, market: &CoinMarket) -> f64 {
+    if market.difficulty <= 0.0 {
+        return 0.0;
+    }
+    hashrate * SECONDS_PER_DAY / market.difficulty * market.reward * market.price
+}
+
+/// The coin to switch to, None to stay with the current one. A coin without revenue,
+/// i.e. missing in the response, is never selected.
+pub fn select_coin<'a>(
+    current: Option<&str>,
+    revenues: &'a [(String, f64)],
+    hysteresis_percent: f64,
+) -> Option<&'a str> {
+    let (best, best_revenue) = revenues
+        .iter()
+        .filter(|(_, revenue)| *revenue > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    if Some(best.as_str()) == current {
+        return None;
+    }
+    let current_revenue = revenues
+        .iter()
+        .find(|(name, _)| Some(name.as_str()) == current)
+        .map_or(0.0, |(_, revenue)| *revenue);
+    if *best_revenue > current_revenue * (1.0 + hysteresis_percent / 100.0) {
+        Some(best)
+    } else {
+        None
+    }
+}
+
+/// The revenue of every coin, 0 for coins that are missing in the response
+pub fn revenues(coins: &[CoinConfig], markets: &HashMap<String, CoinMarket>) -> Vec<(String, f64)> {
+    coins
+        .iter()
+        .map(|coin| {
+            let revenue = markets
+                .get(&coin.name)
+                .map_or(0.0, |market| daily_revenue(coin.hashrate, market));
+            (coin.name.clone(), revenue)
+        })
+        .collect()
+}
+
+/// The coins with the benchmarked hashrate of their algorithm where none is configured
+pub fn with_bench_hashrates(coins: Vec<CoinConfig>, results: &BenchResults) -> Vec<CoinConfig> {
+    coins
+        .into_iter()
+        .map(|coin| {
+            if coin.hashrate > 0.0 {
+                return coin;
+            }
+            let algorithm = &coin.pool_conf.algorithm;
+            match results.hashrate(algorithm) {
+                Some(hashrate) => CoinConfig { hashrate, ..coin },
+                None => {
+                    warn!(
+                        "{} has no hashrate and {} was not benchmarked (mithril bench --algo {}), it is not mined",
+                        coin.name, algorithm, algorithm
+                    );
+                    coin
+                }
+            }
+        })
+        .collect()
+}
+
+/// Polls the API and switches the pool via `control_sndr` when another coin earns
+/// more. `current_pool` is the pool the miner starts with.
+pub fn start(
+    conf: ProfitConfig,
+    coins: Vec<CoinConfig>,
+    current_pool: &str,
+    control_sndr: Sender<ControlCmd>,
+) {
+    if !conf.enabled || coins.is_empty() {
+        return;
+    }
+    let coins = with_bench_hashrates(coins, &bench::load_bench_results());
+    let mut current = coins
+        .iter()
+        .find(|coin| coin.pool_conf.pool_address == current_pool)
+        .map(|coin| coin.name.clone());
+    info!(
+        "profit switching between {}",
+        coins
+            .iter()
+            .map(|coin| coin.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    thread::Builder::new()
+        .name("profit switch thread".to_string())
+        .spawn(move || {
+            let agent = ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build();
+            while !signals::shutdown_requested() {
+                match fetch_markets(&agent, &conf.url) {
+                    Ok(markets) => {
+                        let revenues = revenues(&coins, &markets);
+                        for (name, revenue) in &revenues {
+                            info!("profit of {}: {:.6} per day", name, revenue);
+                        }
+                        if let Some(name) =
+                            select_coin(current.as_deref(), &revenues, conf.hysteresis_percent)
+                        {
+                            let coin = coins.iter().find(|coin| coin.name == name);
+                            if let Some(coin) = coin {
+                                info!("switching to the more profitable {}", coin.name);
+                                if control_sndr
+                                    .send(ControlCmd::SwitchPool(Box::new(coin.pool_conf.clone())))
+                                    .is_err()
+                                {
+                                    return;
+                                }
+                                current = Some(coin.name.clone());
+                            }
+                        }
+                    }
+                    Err(err) => warn!("profit switching: {}, keeping the current coin", err),
+                }
+                thread::sleep(Duration::from_secs(conf.interval_minutes * 60));
+            }
+        })
+        .expect("profit switch thread handle");
+}
+
+fn fetch_markets(agent: &ureq::Agent, url: &str) -> Result<HashMap<String, CoinMarket>, String> {
+    let body = agent
+        .get(url)
+        .call()
+        .map_err(|err| format!("fetching {} failed: {}", url, err))?
+        .into_string()
+        .map_err(|err| format!("reading {} failed: {}", url, err))?;
+    parse_markets(&body)
+}
+
+/// The API response, i.e. `{"monero": {"price": 160.0, "difficulty": 3.2e11, "reward": 0.6}}`
+pub fn parse_markets(body: &str) -> Result<HashMap<String, CoinMarket>, String> {
+    serde_json::from_str(body).map_err(|err| format!("invalid response: {}", err))
+}
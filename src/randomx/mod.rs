@@ -1,7 +0,0 @@
-pub mod common;
-pub mod hash;
-pub mod m128;
-pub mod memory;
-pub mod program;
-pub mod superscalar;
-pub mod vm;
@@ -0,0 +1,184 @@
+//! Record and replay of the pool jobs. `--record` appends every job the workers get to a
+//! file, one JSON line each with the milliseconds since the start of the recording.
+//! `--replay` feeds the jobs of such a file to a worker pool with the same timing, without
+//! a pool, so the job switches and seed changes that showed a race happen again.
+
+extern crate crossbeam_channel;
+
+use self::crossbeam_channel::{after, select, Receiver};
+use crate::error::MithrilError;
+use crate::pow::randomx;
+use crate::stratum::stratum_data::Job;
+use crate::stratum::StratumCmd;
+use crate::worker::worker_pool::WorkerPool;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// One job of a recording
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JobRecord {
+    /// milliseconds since the start of the recording
+    pub ms: u64,
+    pub miner_id: String,
+    pub nicehash: bool,
+    pub job: Job,
+}
+
+/// Appends the jobs to the recording file
+pub struct JobRecorder {
+    start: Instant,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl JobRecorder {
+    /// Truncates the file, the recording starts now
+    pub fn create(path: &Path) -> io::Result<JobRecorder> {
+        Ok(JobRecorder {
+            start: Instant::now(),
+            file: Mutex::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    /// Writes the job, flushed right away to keep it if the miner crashes
+    pub fn record(&self, miner_id: &str, job: &Job, nicehash: bool) {
+        let record = JobRecord {
+            ms: self.start.elapsed().as_millis() as u64,
+            miner_id: miner_id.to_string(),
+            nicehash,
+            job: job.clone(),
+        };
+        let line = serde_json::to_string(&record).expect("job record json");
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+        if let Err(err) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+            warn!("recording job {} failed: {}", job.job_id, err);
+        }
+    }
+}
+
+/// The records of a recording, empty lines are skipped
+pub fn parse_records(reader: impl BufRead) -> Result<Vec<JobRecord>, String> {
+    let mut records = Vec::new();
+    for (ix, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| err.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|err| format!("line {}: invalid job record: {}", ix + 1, err))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+pub fn read_records(path: &Path) -> Result<Vec<JobRecord>, String> {
+    let file =
+        File::open(path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+    parse_records(BufReader::new(file)).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplaySummary {
+    pub jobs: u64,
+    /// jobs with another seed hash than the job before
+    pub seed_changes: u64,
+    pub shares: u64,
+    /// shares of another job than the current one, found before the switch reached the
+    /// worker
+    pub stale_shares: u64,
+    pub duration: Duration,
+}
+
+/// Feeds the records to the pool at their time, the last job is hashed for `tail`. Fails
+/// like the main loop on a memory or dataset error of the workers.
+pub fn replay(
+    records: &[JobRecord],
+    pool: &mut WorkerPool,
+    share_rcvr: &Receiver<StratumCmd>,
+    tail: Duration,
+) -> Result<ReplaySummary, MithrilError> {
+    let start = Instant::now();
+    let mut summary = ReplaySummary::default();
+    let mut current: Option<&Job> = None;
+    for record in records {
+        let at = start + Duration::from_millis(record.ms);
+        collect_shares(pool, share_rcvr, current, at, &mut summary)?;
+        let job = &record.job;
+        info!(
+            "replaying job {} at {}ms, seed_hash {}",
+            job.job_id, record.ms, job.seed_hash
+        );
+        if current.is_some_and(|current| current.seed_hash != job.seed_hash) {
+            summary.seed_changes += 1;
+        }
+        pool.job_change(&record.miner_id, job, record.nicehash)?;
+        summary.jobs += 1;
+        current = Some(job);
+    }
+    collect_shares(
+        pool,
+        share_rcvr,
+        current,
+        Instant::now() + tail,
+        &mut summary,
+    )?;
+    summary.duration = start.elapsed();
+    Ok(summary)
+}
+
+fn collect_shares(
+    pool: &WorkerPool,
+    share_rcvr: &Receiver<StratumCmd>,
+    current: Option<&Job>,
+    until: Instant,
+    summary: &mut ReplaySummary,
+) -> Result<(), MithrilError> {
+    let init_stalls = randomx::init_stalls();
+    let deadline = after(until.saturating_duration_since(Instant::now()));
+    loop {
+        select! {
+            recv(share_rcvr) -> cmd => match cmd {
+                Ok(StratumCmd::SubmitShare { share }) => {
+                    summary.shares += 1;
+                    match current {
+                        Some(job) if job.job_id != share.job_id => {
+                            summary.stale_shares += 1;
+                            warn!(
+                                "share of job {} found while job {} is current",
+                                share.job_id, job.job_id
+                            );
+                        }
+                        _ => debug!("share of job {} with nonce {}", share.job_id, share.nonce),
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => return Ok(()),
+            },
+            recv(pool.errors()) -> err => {
+                if let Ok(err) = err {
+                    return Err(err.into());
+                }
+            },
+            recv(init_stalls) -> err => {
+                if let Ok(err) = err {
+                    return Err(err.into());
+                }
+            },
+            recv(deadline) -> _ => return Ok(()),
+        }
+    }
+}
+
+pub fn format_summary(summary: &ReplaySummary) -> String {
+    format!(
+        "replayed {} jobs with {} seed changes in {:.1}s, {} shares, {} of them stale",
+        summary.jobs,
+        summary.seed_changes,
+        summary.duration.as_secs_f64(),
+        summary.shares,
+        summary.stale_shares
+    )
+}
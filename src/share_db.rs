@@ -0,0 +1,289 @@
+extern crate crossbeam_channel;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+
+use self::crossbeam_channel::Sender;
+#[cfg(feature = "sqlite")]
+use self::crossbeam_channel::{unbounded, Receiver, RecvTimeoutError};
+#[cfg(feature = "sqlite")]
+use self::rusqlite::{params, Connection};
+
+#[cfg(feature = "sqlite")]
+use std::path::Path;
+#[cfg(feature = "sqlite")]
+use std::thread;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "sqlite")]
+use std::time::{Instant, UNIX_EPOCH};
+use strum::Display;
+
+/// How often rows older than the retention period are deleted
+#[cfg(feature = "sqlite")]
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS shares (
+    submitted_ms INTEGER NOT NULL,
+    pool TEXT NOT NULL,
+    job_id TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    difficulty INTEGER NOT NULL,
+    hash_difficulty INTEGER NOT NULL,
+    latency_ms INTEGER NOT NULL,
+    accepted INTEGER NOT NULL,
+    error TEXT
+);
+CREATE INDEX IF NOT EXISTS shares_submitted ON shares (submitted_ms);
+CREATE TABLE IF NOT EXISTS jobs (
+    time_ms INTEGER NOT NULL,
+    pool TEXT NOT NULL,
+    job_id TEXT NOT NULL,
+    difficulty INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS jobs_time ON jobs (time_ms);
+CREATE TABLE IF NOT EXISTS connections (
+    time_ms INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    pool TEXT NOT NULL,
+    detail TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS connections_time ON connections (time_ms);
+";
+
+#[derive(Debug, Clone)]
+pub struct ShareDbConfig {
+    pub enabled: bool,
+    /// path of the SQLite database file
+    pub path: String,
+    /// rows older than this are deleted, 0 keeps everything
+    pub retention_days: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum ConnectionEvent {
+    Connected,
+    Disconnected,
+    LoginFailed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryEvent {
+    /// a submitted share and the answer of the pool, `error` is set for rejected shares
+    Share {
+        submitted: SystemTime,
+        job_id: String,
+        nonce: String,
+        difficulty: u64,
+        hash_difficulty: u64,
+        latency: Duration,
+        error: Option<String>,
+    },
+    Job {
+        time: SystemTime,
+        job_id: String,
+        difficulty: u64,
+    },
+    Connection {
+        time: SystemTime,
+        event: ConnectionEvent,
+        pool: String,
+        detail: String,
+    },
+}
+
+/// Handle for recording events. The events are written asynchronously by the share db
+/// thread, nothing is recorded if the history is disabled.
+#[derive(Clone)]
+pub struct ShareDb {
+    sndr: Option<Sender<HistoryEvent>>,
+}
+
+impl ShareDb {
+    pub fn disabled() -> ShareDb {
+        ShareDb { sndr: None }
+    }
+
+    pub fn record(&self, event: HistoryEvent) {
+        if let Some(sndr) = &self.sndr {
+            if sndr.send(event).is_err() {
+                error!("share db thread is gone, event dropped");
+            }
+        }
+    }
+}
+
+/// Opens the database and starts the writer thread. Returns a disabled `ShareDb` if
+/// the history is not enabled or the database cannot be opened.
+pub fn start(conf: ShareDbConfig) -> ShareDb {
+    if !conf.enabled {
+        return ShareDb::disabled();
+    }
+    start_writer(conf)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn start_writer(_conf: ShareDbConfig) -> ShareDb {
+    error!("share history disabled: mithril was built without the sqlite feature (cargo build --features sqlite)");
+    ShareDb::disabled()
+}
+
+#[cfg(feature = "sqlite")]
+fn start_writer(conf: ShareDbConfig) -> ShareDb {
+    let db = match Database::open(Path::new(&conf.path)) {
+        Ok(db) => db,
+        Err(err) => {
+            error!(
+                "share history disabled, could not open {}: {}",
+                conf.path, err
+            );
+            return ShareDb::disabled();
+        }
+    };
+    info!("recording share history to {}", conf.path);
+
+    let (sndr, rcvr) = unbounded();
+    thread::Builder::new()
+        .name("share db thread".to_string())
+        .spawn(move || write_events(db, &rcvr, conf.retention_days))
+        .expect("share db thread handle");
+    ShareDb { sndr: Some(sndr) }
+}
+
+#[cfg(feature = "sqlite")]
+fn write_events(mut db: Database, rcvr: &Receiver<HistoryEvent>, retention_days: u64) {
+    let mut last_prune: Option<Instant> = None;
+    loop {
+        if retention_days > 0 && last_prune.is_none_or(|at| at.elapsed() >= PRUNE_INTERVAL) {
+            let before = SystemTime::now() - Duration::from_secs(retention_days * 24 * 60 * 60);
+            match db.prune(before) {
+                Ok(0) => {}
+                Ok(deleted) => info!(
+                    "deleted {} share history rows older than {} days",
+                    deleted, retention_days
+                ),
+                Err(err) => error!("pruning the share history failed: {}", err),
+            }
+            last_prune = Some(Instant::now());
+        }
+
+        match rcvr.recv_timeout(PRUNE_INTERVAL) {
+            Ok(event) => {
+                if let Err(err) = db.insert(&event) {
+                    error!("writing the share history failed: {}", err);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// The share history database. Shares and jobs are stored with the pool of the last
+/// `Connected` event.
+#[cfg(feature = "sqlite")]
+pub struct Database {
+    conn: Connection,
+    pool: String,
+}
+
+#[cfg(feature = "sqlite")]
+impl Database {
+    /// Opens or creates the database file
+    pub fn open(path: &Path) -> rusqlite::Result<Database> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Database {
+            conn,
+            pool: String::new(),
+        })
+    }
+
+    pub fn insert(&mut self, event: &HistoryEvent) -> rusqlite::Result<()> {
+        match event {
+            HistoryEvent::Share {
+                submitted,
+                job_id,
+                nonce,
+                difficulty,
+                hash_difficulty,
+                latency,
+                error,
+            } => {
+                self.conn.execute(
+                    "INSERT INTO shares (submitted_ms, pool, job_id, nonce, difficulty, hash_difficulty, latency_ms, accepted, error)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        unix_millis(*submitted),
+                        self.pool,
+                        job_id,
+                        nonce,
+                        sql_int(*difficulty),
+                        sql_int(*hash_difficulty),
+                        sql_int(latency.as_millis() as u64),
+                        error.is_none(),
+                        error
+                    ],
+                )?;
+            }
+            HistoryEvent::Job {
+                time,
+                job_id,
+                difficulty,
+            } => {
+                self.conn.execute(
+                    "INSERT INTO jobs (time_ms, pool, job_id, difficulty) VALUES (?1, ?2, ?3, ?4)",
+                    params![unix_millis(*time), self.pool, job_id, sql_int(*difficulty)],
+                )?;
+            }
+            HistoryEvent::Connection {
+                time,
+                event,
+                pool,
+                detail,
+            } => {
+                if *event == ConnectionEvent::Connected {
+                    self.pool = pool.clone();
+                }
+                self.conn.execute(
+                    "INSERT INTO connections (time_ms, event, pool, detail) VALUES (?1, ?2, ?3, ?4)",
+                    params![unix_millis(*time), event.to_string(), pool, detail],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes all rows older than `before`, returns the number of deleted rows
+    pub fn prune(&self, before: SystemTime) -> rusqlite::Result<usize> {
+        let before = unix_millis(before);
+        Ok(self
+            .conn
+            .execute("DELETE FROM shares WHERE submitted_ms < ?1", [before])?
+            + self
+                .conn
+                .execute("DELETE FROM jobs WHERE time_ms < ?1", [before])?
+            + self
+                .conn
+                .execute("DELETE FROM connections WHERE time_ms < ?1", [before])?)
+    }
+
+    /// For analysis queries
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// SQLite integers are signed, larger values are capped
+#[cfg(feature = "sqlite")]
+fn sql_int(v: u64) -> i64 {
+    i64::try_from(v).unwrap_or(i64::MAX)
+}
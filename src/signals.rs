@@ -0,0 +1,168 @@
+extern crate crossbeam_channel;
+#[cfg(unix)]
+extern crate libc;
+
+use self::crossbeam_channel::Sender;
+use crate::console;
+use crate::control::ControlCmd;
+use crate::metric::history::{HashrateHistory, ThreadHistory};
+use crate::metric::stats::MinerStats;
+
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the signal thread looks for received signals
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const THREAD_HASHRATE_WINDOW: Duration = Duration::from_secs(10);
+/// Time the main loop gets to stop the workers before the process is exited anyway,
+/// i.e. while a pool login is still blocking
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One bit per signal number, set by the signal handler. The signal thread does the
+/// actual work.
+static PENDING: AtomicU32 = AtomicU32::new(0);
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// What a received signal does
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignalAction {
+    /// stop the workers and exit, a second signal exits right away
+    Shutdown,
+    /// log a statistics dump (see `console::stats_dump`)
+    StatsDump,
+    /// sent to the main loop like a console or API command
+    Control(ControlCmd),
+}
+
+/// SIGTERM and SIGINT shut down, SIGHUP reloads the config, SIGUSR1 logs a statistics
+/// dump and SIGUSR2 toggles pause
+#[cfg(unix)]
+pub fn action(signal: i32) -> Option<SignalAction> {
+    match signal {
+        libc::SIGTERM | libc::SIGINT => Some(SignalAction::Shutdown),
+        libc::SIGHUP => Some(SignalAction::Control(ControlCmd::ReloadConfig)),
+        libc::SIGUSR1 => Some(SignalAction::StatsDump),
+        libc::SIGUSR2 => Some(SignalAction::Control(ControlCmd::TogglePause)),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+const HANDLED_SIGNALS: [libc::c_int; 5] = [
+    libc::SIGTERM,
+    libc::SIGINT,
+    libc::SIGHUP,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+];
+
+/// Installs the signal handlers and starts the thread that executes the `action` of
+/// the received signals. The commands go through the same channel as the console and
+/// API commands, so they are executed once the main loop is back from a job change
+/// (dataset initialisation) or a pause.
+pub fn start(stats: Arc<MinerStats>, sndr: Sender<ControlCmd>) {
+    if !install_handlers() {
+        return;
+    }
+
+    thread::Builder::new()
+        .name("signal thread".to_string())
+        .spawn(move || {
+            let mut history = HashrateHistory::new(
+                console::HASHRATE_WINDOWS[console::HASHRATE_WINDOWS.len() - 1].1,
+            );
+            let mut threads = ThreadHistory::new(THREAD_HASHRATE_WINDOW);
+            let mut last_sample: Option<Instant> = None;
+            loop {
+                let now = Instant::now();
+                if last_sample.is_none_or(|at| now - at >= SAMPLE_INTERVAL) {
+                    history.record(now, stats.snapshot().total_hashes);
+                    threads.record(now, stats.thread_hashes());
+                    last_sample = Some(now);
+                }
+                for action in take_pending() {
+                    match action {
+                        SignalAction::Shutdown => request_shutdown(&sndr),
+                        SignalAction::StatsDump => info!(
+                            event = "stats_dump";
+                            "{}", console::stats_dump(&stats, &history, &threads)
+                        ),
+                        SignalAction::Control(cmd) => {
+                            info!("signal received, {:?}", cmd);
+                            let _ = sndr.send(cmd);
+                        }
+                    }
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        })
+        .expect("signal thread handle");
+}
+
+/// Asks the main loop to stop the workers and exit. A second request, or a main loop
+/// that is not done after `SHUTDOWN_TIMEOUT`, exits the process right away.
+pub fn request_shutdown(sndr: &Sender<ControlCmd>) {
+    if SHUTDOWN_REQUESTED.swap(true, Ordering::SeqCst) {
+        force_exit("shutdown requested again, exiting now");
+    }
+    info!("shutting down");
+    let _ = sndr.send(ControlCmd::Shutdown);
+    thread::Builder::new()
+        .name("shutdown timeout thread".to_string())
+        .spawn(|| {
+            thread::sleep(SHUTDOWN_TIMEOUT);
+            force_exit("workers did not stop in time, exiting now");
+        })
+        .expect("shutdown timeout thread handle");
+}
+
+/// For the waits outside of the main loop `select!`, i.e. between reconnects
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+fn force_exit(reason: &str) -> ! {
+    warn!("{}", reason);
+    console::restore_terminal();
+    #[cfg(feature = "tui")]
+    crate::tui::stop();
+    process::exit(1);
+}
+
+#[cfg(unix)]
+fn take_pending() -> Vec<SignalAction> {
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    HANDLED_SIGNALS
+        .iter()
+        .filter(|signal| pending & (1 << **signal) != 0)
+        .filter_map(|signal| action(*signal))
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn take_pending() -> Vec<SignalAction> {
+    Vec::new()
+}
+
+#[cfg(unix)]
+fn install_handlers() -> bool {
+    let handler = record_signal as extern "C" fn(libc::c_int);
+    HANDLED_SIGNALS.iter().all(|signal| unsafe {
+        libc::signal(*signal, handler as libc::sighandler_t) != libc::SIG_ERR
+    })
+}
+
+/// There are no unix signals on other platforms
+#[cfg(not(unix))]
+fn install_handlers() -> bool {
+    false
+}
+
+#[cfg(unix)]
+extern "C" fn record_signal(signal: libc::c_int) {
+    PENDING.fetch_or(1 << signal, Ordering::SeqCst);
+}
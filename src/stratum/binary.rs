@@ -0,0 +1,329 @@
+//! The binary protocol of mithril proxies and coordinators. A miner asks for it with the
+//! `binary` login extension, after the login reply both ends send length prefixed frames
+//! instead of JSON lines. Jobs, submits and submit results get a compact encoding, the
+//! other messages are sent as their JSON line in a frame.
+
+use serde_json::{json, Map, Value};
+
+use std::io;
+use std::io::{Read, Write};
+
+/// The name of the login extension
+pub const EXTENSION: &str = "binary";
+/// Larger frames are a broken or foreign peer
+const MAX_FRAME_BYTES: usize = 64 * 1024;
+
+const KIND_JSON: u8 = 0;
+const KIND_JOB: u8 = 1;
+const KIND_SUBMIT: u8 = 2;
+const KIND_RESULT: u8 = 3;
+
+/// A message of the stratum connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// any message without a compact encoding, as its JSON line
+    Json(String),
+    Job {
+        job_id: String,
+        blob: Vec<u8>,
+        target: Vec<u8>,
+        height: u64,
+        /// empty if the job has none
+        seed_hash: Vec<u8>,
+        /// empty if the job has none
+        algo: String,
+    },
+    Submit {
+        id: u32,
+        miner_id: String,
+        job_id: String,
+        nonce: [u8; 4],
+        result: [u8; 32],
+    },
+    /// the reply to a submit, None if the share was accepted
+    Result { id: u32, error: Option<String> },
+}
+
+impl Message {
+    /// The compact form of the JSON line, `Json` if it has none
+    pub fn from_line(line: &str) -> Message {
+        let line = line.trim_end();
+        serde_json::from_str::<Value>(line)
+            .ok()
+            .and_then(|value| compact(&value))
+            .unwrap_or_else(|| Message::Json(line.to_string()))
+    }
+
+    /// The JSON line of the message, without the newline
+    pub fn to_line(&self) -> String {
+        match self {
+            Message::Json(line) => line.clone(),
+            Message::Job {
+                job_id,
+                blob,
+                target,
+                height,
+                seed_hash,
+                algo,
+            } => {
+                let mut params = Map::new();
+                params.insert("blob".to_string(), json!(to_hex(blob)));
+                params.insert("job_id".to_string(), json!(job_id));
+                params.insert("target".to_string(), json!(to_hex(target)));
+                params.insert("height".to_string(), json!(height));
+                if !seed_hash.is_empty() {
+                    params.insert("seed_hash".to_string(), json!(to_hex(seed_hash)));
+                }
+                if !algo.is_empty() {
+                    params.insert("algo".to_string(), json!(algo));
+                }
+                json!({ "jsonrpc": "2.0", "method": "job", "params": params }).to_string()
+            }
+            Message::Submit {
+                id,
+                miner_id,
+                job_id,
+                nonce,
+                result,
+            } => json!({
+                "id": id,
+                "method": "submit",
+                "params": {
+                    "id": miner_id,
+                    "job_id": job_id,
+                    "nonce": to_hex(nonce),
+                    "result": to_hex(result),
+                },
+            })
+            .to_string(),
+            Message::Result { id, error: None } => {
+                json!({ "id": id, "jsonrpc": "2.0", "error": null, "result": { "status": "OK" } })
+                    .to_string()
+            }
+            Message::Result {
+                id,
+                error: Some(message),
+            } => json!({ "id": id, "jsonrpc": "2.0", "error": { "code": -1, "message": message } })
+                .to_string(),
+        }
+    }
+}
+
+fn compact(value: &Value) -> Option<Message> {
+    let id = || -> Option<u32> { value.get("id")?.as_u64()?.try_into().ok() };
+    match str_of(value, "method") {
+        Some("job") => {
+            let params = value.get("params")?;
+            Some(Message::Job {
+                job_id: str_of(params, "job_id")?.to_string(),
+                blob: from_hex(str_of(params, "blob")?)?,
+                target: from_hex(str_of(params, "target")?)?,
+                height: params.get("height").map_or(Some(0), Value::as_u64)?,
+                seed_hash: from_hex(str_of(params, "seed_hash").unwrap_or(""))?,
+                algo: str_of(params, "algo").unwrap_or("").to_string(),
+            })
+        }
+        Some("submit") => {
+            let params = value.get("params")?;
+            Some(Message::Submit {
+                id: id()?,
+                miner_id: str_of(params, "id")?.to_string(),
+                job_id: str_of(params, "job_id")?.to_string(),
+                nonce: from_hex(str_of(params, "nonce")?)?.try_into().ok()?,
+                result: from_hex(str_of(params, "result")?)?.try_into().ok()?,
+            })
+        }
+        Some(_) => None,
+        //a reply, only the plain OK and the errors are the ones of submits
+        None => match (value.get("error"), value.get("result")) {
+            (Some(Value::Object(error)), _) => Some(Message::Result {
+                id: id()?,
+                error: Some(error.get("message")?.as_str()?.to_string()),
+            }),
+            (_, Some(result)) if *result == json!({ "status": "OK" }) => Some(Message::Result {
+                id: id()?,
+                error: None,
+            }),
+            _ => None,
+        },
+    }
+}
+
+/// Whether the login request asks for the binary protocol
+pub fn asks_binary(line: &str) -> bool {
+    serde_json::from_str::<Value>(line).is_ok_and(|request| {
+        request["method"] == "login" && has_extension(&request["params"], EXTENSION)
+    })
+}
+
+/// Whether the login reply switches the connection to the binary protocol
+pub fn switches_to_binary(line: &str) -> bool {
+    serde_json::from_str::<Value>(line).is_ok_and(|reply| {
+        reply["result"]["status"] == "OK" && has_extension(&reply["result"], EXTENSION)
+    })
+}
+
+/// Whether the `extensions` of the login params or result contain the extension
+pub(crate) fn has_extension(value: &Value, extension: &str) -> bool {
+    value["extensions"]
+        .as_array()
+        .is_some_and(|extensions| extensions.iter().any(|ext| ext == extension))
+}
+
+/// Writes the message as one frame: the length of the rest as u32, the kind byte and
+/// the fields, little endian
+pub fn write_message(writer: &mut impl Write, message: &Message) -> io::Result<()> {
+    let mut frame = vec![0; 4];
+    match message {
+        Message::Json(line) => {
+            frame.push(KIND_JSON);
+            frame.extend_from_slice(line.as_bytes());
+        }
+        Message::Job {
+            job_id,
+            blob,
+            target,
+            height,
+            seed_hash,
+            algo,
+        } => {
+            frame.push(KIND_JOB);
+            put_bytes(&mut frame, job_id.as_bytes())?;
+            put_bytes(&mut frame, blob)?;
+            put_bytes(&mut frame, target)?;
+            frame.extend_from_slice(&height.to_le_bytes());
+            put_bytes(&mut frame, seed_hash)?;
+            put_bytes(&mut frame, algo.as_bytes())?;
+        }
+        Message::Submit {
+            id,
+            miner_id,
+            job_id,
+            nonce,
+            result,
+        } => {
+            frame.push(KIND_SUBMIT);
+            frame.extend_from_slice(&id.to_le_bytes());
+            put_bytes(&mut frame, miner_id.as_bytes())?;
+            put_bytes(&mut frame, job_id.as_bytes())?;
+            frame.extend_from_slice(nonce);
+            frame.extend_from_slice(result);
+        }
+        Message::Result { id, error } => {
+            frame.push(KIND_RESULT);
+            frame.extend_from_slice(&id.to_le_bytes());
+            if let Some(error) = error {
+                put_bytes(&mut frame, error.as_bytes())?;
+            }
+        }
+    }
+    let len = frame.len() - 4;
+    if len > MAX_FRAME_BYTES {
+        return Err(invalid(format!("the message has {} bytes", len)));
+    }
+    frame[..4].copy_from_slice(&(len as u32).to_le_bytes());
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+/// Reads the next frame, an `UnexpectedEof` error means the connection was closed
+pub fn read_message(reader: &mut impl Read) -> io::Result<Message> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len == 0 || len > MAX_FRAME_BYTES {
+        return Err(invalid(format!("invalid frame length {}", len)));
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    let (kind, mut fields) = (frame[0], &frame[1..]);
+    let message = match kind {
+        KIND_JSON => Message::Json(string(fields)?),
+        KIND_JOB => Message::Job {
+            job_id: string(take_bytes(&mut fields)?)?,
+            blob: take_bytes(&mut fields)?.to_vec(),
+            target: take_bytes(&mut fields)?.to_vec(),
+            height: u64::from_le_bytes(take(&mut fields)?),
+            seed_hash: take_bytes(&mut fields)?.to_vec(),
+            algo: string(take_bytes(&mut fields)?)?,
+        },
+        KIND_SUBMIT => Message::Submit {
+            id: u32::from_le_bytes(take(&mut fields)?),
+            miner_id: string(take_bytes(&mut fields)?)?,
+            job_id: string(take_bytes(&mut fields)?)?,
+            nonce: take(&mut fields)?,
+            result: take(&mut fields)?,
+        },
+        KIND_RESULT => {
+            let id = u32::from_le_bytes(take(&mut fields)?);
+            let error = if fields.is_empty() {
+                None
+            } else {
+                Some(string(take_bytes(&mut fields)?)?)
+            };
+            Message::Result { id, error }
+        }
+        kind => return Err(invalid(format!("unknown frame kind {}", kind))),
+    };
+    if kind != KIND_JSON && !fields.is_empty() {
+        return Err(invalid(format!(
+            "{} extra bytes in the frame",
+            fields.len()
+        )));
+    }
+    Ok(message)
+}
+
+/// A u16 length and the bytes
+fn put_bytes(frame: &mut Vec<u8>, bytes: &[u8]) -> io::Result<()> {
+    let len = u16::try_from(bytes.len()).map_err(|_| invalid("a field is too long"))?;
+    frame.extend_from_slice(&len.to_le_bytes());
+    frame.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn take<const N: usize>(fields: &mut &[u8]) -> io::Result<[u8; N]> {
+    if fields.len() < N {
+        return Err(invalid("truncated frame"));
+    }
+    let (bytes, rest) = fields.split_at(N);
+    *fields = rest;
+    Ok(bytes.try_into().expect("split at N"))
+}
+
+fn take_bytes<'a>(fields: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let len = u16::from_le_bytes(take(fields)?) as usize;
+    if fields.len() < len {
+        return Err(invalid("truncated frame"));
+    }
+    let (bytes, rest) = fields.split_at(len);
+    *fields = rest;
+    Ok(bytes)
+}
+
+fn string(bytes: &[u8]) -> io::Result<String> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| invalid("a string is not UTF-8"))
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// None for an odd length or a non hex digit, the JSON line is sent as it is then
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn str_of<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str)
+}
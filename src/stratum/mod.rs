@@ -1,16 +1,30 @@
+pub mod binary;
+pub mod proxy;
+pub mod socks;
+pub mod solo;
+pub mod zmq;
 pub mod stratum_data;
+pub mod tls;
 
 extern crate crossbeam_channel;
 extern crate serde;
 extern crate serde_json;
+extern crate tracing;
 
 use self::crossbeam_channel::{unbounded, Receiver, SendError, Sender};
+use self::tracing::instrument;
+use crate::bench::{self, BenchResults};
+use crate::pow;
+use crate::randomx::wipe::{wipe, wipe_string};
+use std::collections::{BTreeMap, HashMap};
 use std::io;
-use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Write};
-use std::net::{Shutdown, TcpStream};
-use std::sync::{Arc, Mutex};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use thiserror::Error;
 
 /// command send to the stratum server
 #[derive(Debug)]
@@ -18,6 +32,8 @@ pub enum StratumCmd {
     Login {},
     SubmitShare { share: stratum_data::Share },
     KeepAlive { miner_id: String },
+    /// the hashrate for the coordinator of a cluster
+    Report { report: stratum_data::HashrateReport },
     Shutdown {},
 }
 
@@ -27,18 +43,79 @@ pub enum StratumAction {
     Job {
         miner_id: String,
         seed_hash: String,
+        height: u64,
         blob: String,
         job_id: String,
         target: String,
+        /// empty if the pool does not send it
+        algo: String,
     },
     Error {
         err: String,
     },
     Ok,
     KeepAliveOk,
+    ShareAccepted {
+        share: stratum_data::Share,
+        /// time between submitting the share and the response
+        latency: Duration,
+    },
+    ShareRejected {
+        share: stratum_data::Share,
+        err: String,
+        latency: Duration,
+    },
+    /// a command of the coordinator of a cluster
+    Control {
+        control: stratum_data::ClusterControl,
+    },
+    /// the fleet config of the coordinator of a cluster, TOML
+    FleetConfig {
+        config: String,
+    },
+}
+
+/// Why the client stopped, sent to the error channel given to `login`
+#[derive(Debug, Error)]
+pub enum StratumError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("could not encode the {0} request: {1}")]
+    Json(&'static str, serde_json::Error),
+    /// the other end of a channel of the client ended
+    #[error("the {0} channel is closed")]
+    ChannelClosed(&'static str),
+    #[error("the {0} thread panicked")]
+    ThreadPanicked(&'static str),
+    /// the daemon of a solo pool is not reachable or has no block template
+    #[error("daemon: {0}")]
+    Daemon(String),
 }
 
-pub enum StratumError {}
+/// Shares that were sent to the pool but not answered yet, keyed by the request id
+/// of the submit.
+pub type PendingShares = Arc<Mutex<HashMap<u32, PendingShare>>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingShare {
+    pub share: stratum_data::Share,
+    pub submitted: Instant,
+}
+
+/// Submit requests get their own ids (starting here), so that the pool response can be
+/// matched to the share. Login and keep alive always use id 1.
+const FIRST_SUBMIT_ID: u32 = 2;
+
+/// The connection to the pool, TCP or TLS
+type PoolReader = BufReader<Box<dyn Read + Send>>;
+type PoolWriter = BufWriter<Box<dyn Write + Send>>;
+
+/// How long `reachable` waits for the connection
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// large enough that encoding the login request does not reallocate and leave copies
+/// of the credentials behind
+const LOGIN_BUFFER_BYTES: usize = 4096;
 
 pub struct StratumClient {
     command_sender: Sender<StratumCmd>,
@@ -49,38 +126,107 @@ pub struct StratumClient {
     tick_tx: Sender<()>,
 }
 
+/// The connection to a pool, a stratum server or the daemon of a solo pool
+pub enum PoolClient {
+    Stratum(StratumClient),
+    Solo(solo::SoloClient),
+}
+
+impl PoolClient {
+    pub fn login(
+        pool_conf: stratum_data::PoolConfig,
+        err_receiver: Sender<StratumError>,
+        action_rcv: Sender<StratumAction>,
+    ) -> Result<PoolClient, StratumError> {
+        if pool_conf.daemon {
+            solo::SoloClient::login(pool_conf, err_receiver, action_rcv).map(PoolClient::Solo)
+        } else {
+            StratumClient::login(pool_conf, err_receiver, action_rcv).map(PoolClient::Stratum)
+        }
+    }
+
+    pub fn new_cmd_channel(&self) -> Sender<StratumCmd> {
+        match self {
+            PoolClient::Stratum(client) => client.new_cmd_channel(),
+            PoolClient::Solo(client) => client.new_cmd_channel(),
+        }
+    }
+
+    pub fn stop(self) -> Result<(), StratumError> {
+        match self {
+            PoolClient::Stratum(client) => client.stop(),
+            PoolClient::Solo(client) => client.stop(),
+        }
+    }
+}
+
+/// Whether a connection to the pool would work now, the daemon of a solo pool also has
+/// to be synchronized
+pub fn reachable(pool_conf: &stratum_data::PoolConfig) -> bool {
+    if pool_conf.daemon {
+        return solo::Daemon::new(pool_conf).ready();
+    }
+    if pool_conf.proxy.is_empty() {
+        pool_conf
+            .pool_address
+            .to_socket_addrs()
+            .map(|mut addrs| {
+                addrs.any(|addr| TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).is_ok())
+            })
+            .unwrap_or(false)
+    } else {
+        socks::connect(&pool_conf.proxy, &pool_conf.pool_address).is_ok()
+    }
+}
+
 /// All operation in the client are async
 impl StratumClient {
     pub fn login(
         pool_conf: stratum_data::PoolConfig,
-        err_receiver: Sender<Error>,
+        err_receiver: Sender<StratumError>,
         action_rcv: Sender<StratumAction>,
-    ) -> io::Result<StratumClient> {
-        info!("connecting to address: {}", pool_conf.pool_address);
+    ) -> Result<StratumClient, StratumError> {
+        info!(
+            event = "pool_connect", pool = pool_conf.pool_address.as_str();
+            "connecting to address: {}", pool_conf.pool_address
+        );
 
-        let (tcp_stream_hnd, reader, writer) = StratumClient::connect_tcp(&pool_conf.pool_address)?;
+        let (tcp_stream_hnd, reader, writer) = StratumClient::connect(&pool_conf)?;
+        let keepalive = pool_conf.keepalive;
 
         let miner_id = Arc::new(Mutex::new(Option::None));
+        let pending_shares = Arc::new(Mutex::new(HashMap::new()));
         let (command_sender, command_receiver) = unbounded();
+        //set by the receive thread when the login reply accepts the binary protocol
+        let binary_mode = Arc::new(AtomicBool::new(false));
+        let asks_binary = pool_conf
+            .extensions
+            .iter()
+            .any(|extension| extension == binary::EXTENSION)
+            .then(|| binary_mode.clone());
 
         let send_thread = StratumClient::start_send_thread(
             writer,
             command_receiver,
             pool_conf,
+            pending_shares.clone(),
             err_receiver.clone(),
+            binary_mode,
         )?;
         let rcv_thread = StratumClient::start_receive_thread(
             reader,
             action_rcv,
             miner_id.clone(),
+            pending_shares,
             err_receiver,
+            asks_binary,
         )?;
         let (keep_alive_thread, tick_tx) =
-            StratumClient::start_keep_alive_thread(command_sender.clone(), miner_id)?;
+            StratumClient::start_keep_alive_thread(command_sender.clone(), miner_id, keepalive)?;
 
         command_sender
             .send(StratumCmd::Login {})
-            .expect("login command send");
+            .map_err(|_| StratumError::ChannelClosed("command"))?;
 
         Ok(StratumClient {
             command_sender,
@@ -92,52 +238,78 @@ impl StratumClient {
         })
     }
 
-    fn connect_tcp(
-        pool_address: &str,
-    ) -> io::Result<(TcpStream, BufReader<TcpStream>, BufWriter<TcpStream>)> {
-        let stream = TcpStream::connect(pool_address)?;
+    fn connect(
+        pool_conf: &stratum_data::PoolConfig,
+    ) -> io::Result<(TcpStream, PoolReader, PoolWriter)> {
+        let stream = if pool_conf.proxy.is_empty() {
+            TcpStream::connect(&pool_conf.pool_address)?
+        } else {
+            info!("connecting via SOCKS5 proxy {}", pool_conf.proxy);
+            socks::connect(&pool_conf.proxy, &pool_conf.pool_address)?
+        };
         stream.set_read_timeout(None)?;
         stream.set_write_timeout(Some(Duration::from_secs(10)))?;
 
-        let reader = BufReader::new(stream.try_clone()?);
-        let writer = BufWriter::new(stream.try_clone()?);
+        let (reader, writer): (Box<dyn Read + Send>, Box<dyn Write + Send>) = if pool_conf.tls {
+            let tls = tls::connect(
+                stream.try_clone()?,
+                &pool_conf.pool_address,
+                tls::client_config(),
+            )?;
+            info!("TLS connection established");
+            (Box::new(tls.try_clone()?), Box::new(tls))
+        } else {
+            (Box::new(stream.try_clone()?), Box::new(stream.try_clone()?))
+        };
 
-        Ok((stream, reader, writer))
+        Ok((stream, BufReader::new(reader), BufWriter::new(writer)))
     }
 
     fn start_send_thread(
-        writer: BufWriter<TcpStream>,
+        writer: PoolWriter,
         command_rcv: Receiver<StratumCmd>,
         pool_conf: stratum_data::PoolConfig,
-        err_receiver: Sender<Error>,
+        pending_shares: PendingShares,
+        err_receiver: Sender<StratumError>,
+        binary_mode: Arc<AtomicBool>,
     ) -> io::Result<thread::JoinHandle<()>> {
         thread::Builder::new()
             .name("Stratum send thread".to_string())
             .spawn(move || {
-                let result = handle_stratum_send(&command_rcv, writer, &pool_conf);
-                if result.is_err() {
-                    err_receiver
-                        .send(result.err().expect("result error send thread"))
-                        .expect("sending error in send thread");
+                let result = handle_stratum_send(
+                    &command_rcv,
+                    writer,
+                    &pool_conf,
+                    &pending_shares,
+                    &binary_mode,
+                );
+                if let Err(err) = result {
+                    report_error(&err_receiver, err);
                 }
                 info!("stratum send thread ended");
             })
     }
 
     fn start_receive_thread(
-        reader: BufReader<TcpStream>,
+        reader: PoolReader,
         action_rcv: Sender<StratumAction>,
         miner_id: Arc<Mutex<Option<String>>>,
-        err_receiver: Sender<Error>,
+        pending_shares: PendingShares,
+        err_receiver: Sender<StratumError>,
+        asks_binary: Option<Arc<AtomicBool>>,
     ) -> io::Result<thread::JoinHandle<()>> {
         thread::Builder::new()
             .name("Stratum receive thread".to_string())
             .spawn(move || {
-                let result = handle_stratum_receive(reader, &action_rcv, &miner_id);
-                if result.is_err() {
-                    err_receiver
-                        .send(result.err().expect("result error recv thread"))
-                        .expect("sending error in recv thread");
+                let result = handle_stratum_receive(
+                    reader,
+                    &action_rcv,
+                    &miner_id,
+                    &pending_shares,
+                    asks_binary.as_deref(),
+                );
+                if let Err(err) = result {
+                    report_error(&err_receiver, err);
                 }
                 info!("stratum receive thread ended");
             })
@@ -146,26 +318,26 @@ impl StratumClient {
     fn start_keep_alive_thread(
         cmd_alive: Sender<StratumCmd>,
         alive_miner_id: Arc<Mutex<Option<String>>>,
+        keepalive: bool,
     ) -> io::Result<(thread::JoinHandle<()>, Sender<()>)> {
         let (stop_sndr, stop_rcvr) = unbounded();
 
-        let (tick_rcv, _) = start_tick_thread(Duration::from_secs(60), stop_rcvr);
+        let (tick_rcv, _) = start_tick_thread(Duration::from_secs(60), stop_rcvr)?;
         Ok((
             thread::Builder::new()
                 .name("keep alive thread".to_string())
                 .spawn(move || {
-                    loop {
-                        let tick_result = tick_rcv.recv();
-                        if tick_result.is_err() || tick_result.expect("tick result") == Tick::Stop {
-                            break;
-                        } //else: normal tick, loop around
-
-                        let miner_id_guard = &*alive_miner_id.lock().expect("miner_id lock");
-                        if miner_id_guard.is_some() {
-                            let miner_id = miner_id_guard.clone().expect("miner_id clone");
-                            cmd_alive
-                                .send(StratumCmd::KeepAlive { miner_id })
-                                .expect("KeepAlive send failed");
+                    while let Ok(Tick::Tick) = tick_rcv.recv() {
+                        if !keepalive {
+                            continue;
+                        }
+
+                        let miner_id = lock(&alive_miner_id).clone();
+                        if let Some(miner_id) = miner_id {
+                            if cmd_alive.send(StratumCmd::KeepAlive { miner_id }).is_err() {
+                                info!("send thread ended, stopping keep alive");
+                                break;
+                            }
                         }
                     }
                     info!("keep alive thread ended");
@@ -179,14 +351,13 @@ impl StratumClient {
         self.command_sender.clone()
     }
 
-    /// Stops the StratumClient, ending all communication with the server end.
-    pub fn stop(self) {
+    /// Stops the StratumClient, ending all communication with the server end. An error
+    /// means a thread of the client ended early, everything is stopped anyway.
+    pub fn stop(self) -> Result<(), StratumError> {
         info!("stopping stratum client");
 
-        //stop send thread
-        self.command_sender
-            .send(StratumCmd::Shutdown {})
-            .expect("shutdown command send");
+        //stop send thread (it is already ended if the connection failed)
+        let _ = self.command_sender.send(StratumCmd::Shutdown {});
 
         //stop receive thread
         let shutdown_result = self.tcp_stream_hnd.shutdown(Shutdown::Both);
@@ -197,13 +368,32 @@ impl StratumClient {
         }
 
         //stop keep alive thread (via stopping tick thread)
-        self.tick_tx.send(()).expect("ending tick thread");
-        self.send_thread.join().expect("join send thread");
-        self.rcv_thread.join().expect("join rcv thread");
-        self.keep_alive_thread.join().expect("keep alive thread");
+        let _ = self.tick_tx.send(());
+        let send = self.send_thread.join();
+        let rcv = self.rcv_thread.join();
+        let keep_alive = self.keep_alive_thread.join();
+        send.map_err(|_| StratumError::ThreadPanicked("send"))?;
+        rcv.map_err(|_| StratumError::ThreadPanicked("receive"))?;
+        keep_alive.map_err(|_| StratumError::ThreadPanicked("keep alive"))?;
+        Ok(())
     }
 }
 
+/// The error channel is dropped when the miner already moved on
+fn report_error(err_receiver: &Sender<StratumError>, err: StratumError) {
+    if let Err(SendError(err)) = err_receiver.send(err) {
+        info!("stratum error after the client was stopped: {}", err);
+    }
+}
+
+/// The miner id and the pending shares stay usable if a thread panicked while holding
+/// the lock, the values are replaced as a whole
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Tick {
     Tick,
@@ -213,7 +403,7 @@ pub enum Tick {
 pub fn start_tick_thread(
     interval: Duration,
     stop_rcv: Receiver<()>,
-) -> (Receiver<Tick>, thread::JoinHandle<()>) {
+) -> io::Result<(Receiver<Tick>, thread::JoinHandle<()>)> {
     let (sndr, rcvr) = unbounded();
     let hnd = thread::Builder::new()
         .name("tick thread".to_string())
@@ -235,29 +425,50 @@ pub fn start_tick_thread(
                     break;
                 }
             }
-        })
-        .expect("tick thread handle");
-    (rcvr, hnd)
+        })?;
+    Ok((rcvr, hnd))
 }
 
 pub fn submit_share(
     tx: &Sender<StratumCmd>,
     share: stratum_data::Share,
 ) -> Result<(), SendError<StratumCmd>> {
-    info!("submitting share: {:?}", share);
+    info!(
+        event = "share_submit", job_id = share.job_id.as_str(), nonce = share.nonce.as_str();
+        "submitting share: {:?}", share
+    );
     tx.send(StratumCmd::SubmitShare { share })
 }
 
 fn handle_stratum_send(
     rx: &Receiver<StratumCmd>,
-    mut writer: BufWriter<TcpStream>,
+    mut writer: PoolWriter,
     pool_conf: &stratum_data::PoolConfig,
-) -> Result<(), Error> {
+    pending_shares: &PendingShares,
+    binary_mode: &AtomicBool,
+) -> Result<(), StratumError> {
+    let mut submit_id = FIRST_SUBMIT_ID;
     loop {
-        match rx.recv().expect("stratum receiver") {
+        let cmd = rx
+            .recv()
+            .map_err(|_| StratumError::ChannelClosed("command"))?;
+        match cmd {
             StratumCmd::Login {} => do_stratum_login(&mut writer, pool_conf)?,
-            StratumCmd::SubmitShare { share } => do_stratum_submit_share(&mut writer, share)?,
-            StratumCmd::KeepAlive { miner_id } => do_stratum_keep_alive(&mut writer, miner_id)?,
+            StratumCmd::SubmitShare { share } => {
+                lock(pending_shares).insert(
+                    submit_id,
+                    PendingShare {
+                        share: share.clone(),
+                        submitted: Instant::now(),
+                    },
+                );
+                do_stratum_submit_share(&mut writer, submit_id, share, binary_mode)?;
+                submit_id = submit_id.checked_add(1).unwrap_or(FIRST_SUBMIT_ID);
+            }
+            StratumCmd::KeepAlive { miner_id } => {
+                do_stratum_keep_alive(&mut writer, miner_id, binary_mode)?
+            }
+            StratumCmd::Report { report } => do_stratum_report(&mut writer, report, binary_mode)?,
             StratumCmd::Shutdown {} => {
                 info!("stopping stratum send thread");
                 break;
@@ -267,25 +478,51 @@ fn handle_stratum_send(
     Ok(())
 }
 
-fn do_stratum_keep_alive(writer: &mut BufWriter<TcpStream>, miner_id: String) -> Result<(), Error> {
+fn do_stratum_keep_alive(
+    writer: &mut PoolWriter,
+    miner_id: String,
+    binary_mode: &AtomicBool,
+) -> Result<(), StratumError> {
     let keep_alive_req = stratum_data::KeepAliveRequest {
         id: 1,
         method: "keepalived".to_string(),
         params: stratum_data::KeepAliveParams { id: miner_id },
     };
 
-    let json = serde_json::to_string(&keep_alive_req).expect("marshaling keep alive json");
-    writeln!(writer, "{}", json)?;
-    writer.flush()?;
+    let json = serde_json::to_string(&keep_alive_req)
+        .map_err(|err| StratumError::Json("keep alive", err))?;
+    write_request(writer, &json, binary_mode)?;
+    Ok(())
+}
+
+fn do_stratum_report(
+    writer: &mut PoolWriter,
+    report: stratum_data::HashrateReport,
+    binary_mode: &AtomicBool,
+) -> Result<(), StratumError> {
+    let report_req = stratum_data::ReportRequest {
+        method: "report".to_string(),
+        params: report,
+    };
+    let json =
+        serde_json::to_string(&report_req).map_err(|err| StratumError::Json("report", err))?;
+    write_request(writer, &json, binary_mode)?;
     Ok(())
 }
 
+#[instrument(
+    name = "share_submit",
+    skip_all,
+    fields(job_id = share.job_id.as_str(), nonce = share.nonce.as_str(), submit_id = id)
+)]
 fn do_stratum_submit_share(
-    writer: &mut BufWriter<TcpStream>,
+    writer: &mut PoolWriter,
+    id: u32,
     share: stratum_data::Share,
-) -> Result<(), Error> {
+    binary_mode: &AtomicBool,
+) -> Result<(), StratumError> {
     let submit_req = stratum_data::SubmitRequest {
-        id: 1,
+        id,
         method: "submit".to_string(),
         params: stratum_data::SubmitParams {
             id: share.miner_id,
@@ -294,183 +531,254 @@ fn do_stratum_submit_share(
             result: share.hash,
         },
     };
-    let json = serde_json::to_string(&submit_req).expect("marshaling submit json");
-    writeln!(writer, "{}", json)?;
-    writer.flush()?;
+    let json =
+        serde_json::to_string(&submit_req).map_err(|err| StratumError::Json("submit", err))?;
+    write_request(writer, &json, binary_mode)?;
     Ok(())
 }
 
+/// Sends the request as a JSON line, or as a frame after the switch to the binary protocol
+fn write_request(
+    writer: &mut PoolWriter,
+    json: &str,
+    binary_mode: &AtomicBool,
+) -> io::Result<()> {
+    if binary_mode.load(Ordering::SeqCst) {
+        binary::write_message(writer, &binary::Message::from_line(json))
+    } else {
+        writeln!(writer, "{}", json)?;
+        writer.flush()
+    }
+}
+
 fn do_stratum_login(
-    writer: &mut BufWriter<TcpStream>,
+    writer: &mut PoolWriter,
     pool_conf: &stratum_data::PoolConfig,
-) -> Result<(), Error> {
-    let login_req = stratum_data::LoginRequest {
+) -> Result<(), StratumError> {
+    let mut login_req = stratum_data::LoginRequest {
         id: 1,
         method: "login".to_string(),
         params: stratum_data::LoginParams {
             login: pool_conf.wallet_address.clone(),
             pass: pool_conf.pool_password.clone(),
+            rigid: pool_conf.rig_id.clone(),
+            algo: login_algorithms(&pool_conf.algorithm),
+            algo_perf: login_algo_perf(&bench::load_bench_results()),
+            extensions: pool_conf.extensions.clone(),
         },
     };
-    let json = serde_json::to_string(&login_req).expect("marshaling login json");
-    writeln!(writer, "{}", json)?;
-    writer.flush()?;
-    Ok(())
+    let mut json = Vec::with_capacity(LOGIN_BUFFER_BYTES);
+    let encoded = serde_json::to_writer(&mut json, &login_req);
+    wipe_string(&mut login_req.params.login);
+    wipe_string(&mut login_req.params.pass);
+    json.push(b'\n');
+    //past the buffer of the writer, it would keep the credentials after the flush
+    //(a TLS stream still copies them into rustls buffers, which are not wiped)
+    let result = match encoded {
+        Ok(()) => writer
+            .flush()
+            .and_then(|_| writer.get_mut().write_all(&json))
+            .map_err(StratumError::Io),
+        Err(err) => Err(StratumError::Json("login", err)),
+    };
+    wipe(&mut json);
+    result
+}
+
+/// The algorithms sent on login, the configured one first as the preferred one
+pub fn login_algorithms(algorithm: &str) -> Vec<String> {
+    let mut algorithms = vec![algorithm.to_string()];
+    algorithms.extend(
+        pow::algorithm_names()
+            .iter()
+            .filter(|name| **name != algorithm)
+            .map(|name| name.to_string()),
+    );
+    algorithms
+}
+
+/// The benchmarked hashrates of the supported algorithms
+pub fn login_algo_perf(results: &BenchResults) -> BTreeMap<String, f64> {
+    let supported = pow::algorithm_names();
+    results
+        .algorithms
+        .iter()
+        .filter(|(name, _)| supported.contains(&name.as_str()))
+        .map(|(name, result)| (name.clone(), result.hashrate))
+        .collect()
 }
 
 fn handle_stratum_receive(
-    mut reader: BufReader<TcpStream>,
+    mut reader: PoolReader,
     rcv: &Sender<StratumAction>,
     miner_id: &Arc<Mutex<Option<String>>>,
-) -> Result<(), Error> {
+    pending_shares: &PendingShares,
+    asks_binary: Option<&AtomicBool>,
+) -> Result<(), StratumError> {
     loop {
         let mut line = String::new();
-        match reader.read_line(&mut line) {
+        let read = match asks_binary {
+            Some(binary_mode) if binary_mode.load(Ordering::SeqCst) => {
+                binary::read_message(&mut reader).map(|message| {
+                    line = message.to_line();
+                    line.len()
+                })
+            }
+            _ => reader.read_line(&mut line),
+        };
+        match read {
             Ok(n) => {
                 if n == 0 {
                     //that means EOF in the TCPStream was reached
-                    return Err(Error::new(ErrorKind::Other, "connection terminated"));
+                    return Err(io::Error::other("connection terminated").into());
+                }
+                if let Some(binary_mode) = asks_binary {
+                    //before the login reply sets the miner id, keep alives are only sent after that
+                    if !binary_mode.load(Ordering::SeqCst) && binary::switches_to_binary(&line) {
+                        info!("switching to the binary protocol");
+                        binary_mode.store(true, Ordering::SeqCst);
+                    }
+                }
+                if let Some(action) = parse_submit_response(&line, pending_shares, Instant::now()) {
+                    if rcv.send(action).is_err() {
+                        info!("sending share result to receiver failed (receiver probably already terminated)");
+                    }
+                } else {
+                    parse_line_dispatch_result(&line, rcv, miner_id);
                 }
-                parse_line_dispatch_result(&line, rcv, miner_id);
             }
             Err(e) => {
                 //read_line fails (maybe connection lost, dispatch err to channel)
                 //=> Terminate loop
-                return Err(e);
+                return Err(e.into());
             }
         };
     }
 }
 
-fn is_known_ok(
-    result: Result<stratum_data::OkResponse, serde_json::Error>,
+/// Checks whether the line is the answer to a submitted share. Returns None if the line
+/// is not a response to any pending share submit.
+pub fn parse_submit_response(
+    line: &str,
+    pending_shares: &PendingShares,
+    received: Instant,
 ) -> Option<StratumAction> {
-    if result.is_ok() {
-        let unwrapped = result.expect("result unwrap");
-        if unwrapped.result.status == "OK" && unwrapped.result.id.is_none() {
-            return Some(StratumAction::Ok);
-        } else if unwrapped.result.status == "KEEPALIVED" && unwrapped.result.id.is_none() {
-            return Some(StratumAction::KeepAliveOk);
+    let message = stratum_data::PoolMessage::parse(line).ok()?;
+    let id = match &message {
+        stratum_data::PoolMessage::Error { id: Some(id), .. }
+        | stratum_data::PoolMessage::Status { id: Some(id), .. }
+        | stratum_data::PoolMessage::Login { id: Some(id), .. } => *id,
+        _ => return None,
+    };
+    let PendingShare { share, submitted } = lock(pending_shares).remove(&id)?;
+    let latency = received.saturating_duration_since(submitted);
+
+    match message {
+        stratum_data::PoolMessage::Error { error, .. } => Some(StratumAction::ShareRejected {
+            share,
+            err: format!("{} (code {})", error.message, error.code),
+            latency,
+        }),
+        stratum_data::PoolMessage::Status { status, .. } if status == "OK" => {
+            Some(StratumAction::ShareAccepted { share, latency })
         }
+        _ => Some(StratumAction::ShareRejected {
+            share,
+            err: format!("unexpected submit response: {}", line.trim()),
+            latency,
+        }),
     }
-    None
 }
 
-//TODO Refactor this method (it is very ugly) - its probably better to use generic value parsing and not using struct for every case
+/// Parses a line of the pool that is not a submit response and sends the action of it
 pub fn parse_line_dispatch_result(
     line: &str,
     rcv: &Sender<StratumAction>,
     miner_id_mutx: &Arc<Mutex<Option<String>>>,
 ) {
-    let action;
+    let action = match stratum_data::PoolMessage::parse(line) {
+        Ok(message) => message_action(message, line, miner_id_mutx),
+        Err(err) => StratumAction::Error {
+            err: format!("{}, json received {}", err, line),
+        },
+    };
 
-    let error: Result<stratum_data::ErrorResult, serde_json::Error> = serde_json::from_str(line);
-    if error.is_ok() {
-        let stratum_data::ErrorResult { error: err_details } = error.expect("error unwrap");
-        action = StratumAction::Error {
+    let send_result = rcv.send(action);
+    if send_result.is_err() {
+        info!("sending action to receiver failed (receiver probably already terminated), trying next receiver");
+    }
+}
+
+fn message_action(
+    message: stratum_data::PoolMessage,
+    line: &str,
+    miner_id_mutx: &Arc<Mutex<Option<String>>>,
+) -> StratumAction {
+    match message {
+        stratum_data::PoolMessage::Error { error, .. } => StratumAction::Error {
             err: format!(
                 "error received: {} (code {}, raw json {})",
-                err_details.message, err_details.code, line
+                error.message, error.code, line
             ),
-        };
-    } else {
-        let ok_result: Result<stratum_data::OkResponse, serde_json::Error> =
-            serde_json::from_str(line);
-        let known_ok = is_known_ok(ok_result);
-        if known_ok.is_some() {
-            action = known_ok.expect("known_ok unwrap");
-        } else {
-            let result: Result<stratum_data::Method, serde_json::Error> =
-                serde_json::from_str(line);
-            if result.is_ok() {
-                let stratum_data::Method { method } = result.expect("result unwrap");
-                match method.as_ref() {
-                    "job" => action = parse_job(line, miner_id_mutx),
-                    _ => {
-                        action = StratumAction::Error {
-                            err: format!("unknown method received: {}", method),
-                        }
-                    }
+        },
+        stratum_data::PoolMessage::Status { status, .. } if status == "OK" => StratumAction::Ok,
+        stratum_data::PoolMessage::Status { status, .. } if status == "KEEPALIVED" => {
+            StratumAction::KeepAliveOk
+        }
+        stratum_data::PoolMessage::Status { status, .. } => StratumAction::Error {
+            err: format!("unexpected status {}, json received {}", status, line),
+        },
+        stratum_data::PoolMessage::Login {
+            miner_id,
+            status,
+            job,
+            ..
+        } => {
+            if status != "OK" {
+                return StratumAction::Error {
+                    err: format!("Not OK initial job received, status was {}", status),
                 };
-            } else {
-                //try parsing intial job
-                let initial: Result<stratum_data::LoginResponse, serde_json::Error> =
-                    serde_json::from_str(line);
-                match initial {
-                    Ok(stratum_data::LoginResponse {
-                        result:
-                            stratum_data::LoginResult {
-                                status,
-                                job:
-                                    stratum_data::Job {
-                                        seed_hash,
-                                        blob,
-                                        job_id,
-                                        target,
-                                    },
-                                id: miner_id,
-                            },
-                        ..
-                    }) => {
-                        if status == "OK" {
-                            action = StratumAction::Job {
-                                miner_id: miner_id.clone(),
-                                seed_hash,
-                                blob,
-                                job_id,
-                                target,
-                            };
-                            let mut miner_id_guard = miner_id_mutx.lock().expect("miner_id lock");
-                            *miner_id_guard = Option::Some(miner_id);
-                        } else {
-                            action = StratumAction::Error {
-                                err: format!("Not OK initial job received, status was {}", status),
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        action = StratumAction::Error {
-                            err: format!("{:?}, json received {}", e, line),
-                        }
-                    }
-                }
             }
+            *lock(miner_id_mutx) = Some(miner_id.clone());
+            job_action(miner_id, job)
         }
-    }
-
-    let send_result = rcv.send(action);
-    if send_result.is_err() {
-        info!("sending action to receiver failed (receiver probably already terminated), trying next receiver");
+        stratum_data::PoolMessage::Job(job) => match lock(miner_id_mutx).clone() {
+            Some(miner_id) => job_action(miner_id, job),
+            None => StratumAction::Error{err: "miner_id not available for first mining job (login failed previously, this is a bug)".to_string()},
+        },
+        stratum_data::PoolMessage::Control(control) => StratumAction::Control { control },
+        stratum_data::PoolMessage::FleetConfig(params) => StratumAction::FleetConfig {
+            config: params.config,
+        },
+        stratum_data::PoolMessage::UnknownMethod(method) => StratumAction::Error {
+            err: format!("unknown method received: {}", method),
+        },
     }
 }
 
-fn parse_job(line: &str, miner_id_mutx: &Arc<Mutex<Option<String>>>) -> StratumAction {
-    let result: Result<stratum_data::JobResponse, serde_json::Error> = serde_json::from_str(line);
-    let miner_id_guard = &*miner_id_mutx.lock().expect("miner_id lock");
-
-    if miner_id_guard.is_none() {
-        return StratumAction::Error{err: "miner_id not available for first mining job (login failed previously, this is a bug)".to_string()};
+/// The job for the workers, an error for an invalid job so that they keep mining the
+/// previous one. The error is logged by the receiver of the action.
+fn job_action(miner_id: String, job: stratum_data::Job) -> StratumAction {
+    if let Err(err) = job.validate() {
+        return StratumAction::Error {
+            err: format!("invalid job {}: {}", job.job_id, err),
+        };
     }
-    let miner_id = miner_id_guard.clone().expect("miner_id clone");
-
-    match result {
-        Ok(stratum_data::JobResponse {
-            params:
-                stratum_data::Job {
-                    seed_hash,
-                    blob,
-                    job_id,
-                    target,
-                },
-        }) => StratumAction::Job {
-            miner_id,
-            seed_hash,
-            blob,
-            job_id,
-            target,
-        },
-        _ => StratumAction::Error {
-            err: "Error parsing job response".to_string(),
-        },
+    let stratum_data::Job {
+        seed_hash,
+        height,
+        blob,
+        job_id,
+        target,
+        algo,
+    } = job;
+    StratumAction::Job {
+        miner_id,
+        seed_hash,
+        height,
+        blob,
+        job_id,
+        target,
+        algo,
     }
 }
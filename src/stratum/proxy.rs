@@ -0,0 +1,727 @@
+extern crate crossbeam_channel;
+
+use self::crossbeam_channel::{select, unbounded, Sender};
+use super::binary::{self, Message};
+use super::stratum_data::{self, ClusterControl, HashrateReport, PoolConfig, SubmitParams};
+use super::{lock, StratumAction, StratumClient, StratumCmd};
+use crate::difficulty::job_difficulty;
+use serde_json::{json, Map, Value};
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Shares for the jobs before the current one are still forwarded, pools accept them
+/// for a while after a new job
+const RECENT_JOBS: usize = 4;
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The hex of the last nonce byte in the blob (byte 42), the slot of a miner
+const SLOT_START: usize = 84;
+const SLOT_END: usize = 86;
+/// The login extension of the miners that take the fleet config of a coordinator
+pub const FLEET_EXTENSION: &str = "fleet";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyConfig {
+    /// address the miners connect to, e.g. `0.0.0.0:3333`
+    pub listen: String,
+    /// the upstream pool, it must not reserve a nonce byte itself (`nicehash`)
+    pub pool: PoolConfig,
+    /// time between the stats logs of the miners
+    pub stats_interval: Duration,
+}
+
+/// A miner connected to the proxy
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinerStats {
+    /// the last nonce byte of the miner
+    pub slot: u8,
+    pub address: String,
+    /// the rig id of the login, or the login if it has none
+    pub name: String,
+    pub connected: Instant,
+    pub accepted: u64,
+    pub rejected: u64,
+    /// sum of the difficulties of the accepted shares
+    pub hashes: u64,
+    /// the last report of a cluster agent, None for other miners
+    pub report: Option<HashrateReport>,
+}
+
+impl MinerStats {
+    /// H/s since the miner connected, estimated from the accepted shares
+    pub fn hashrate(&self, now: Instant) -> f64 {
+        let secs = now.saturating_duration_since(self.connected).as_secs_f64();
+        if secs > 0.0 {
+            self.hashes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct UpstreamJob {
+    miner_id: String,
+    seed_hash: String,
+    height: u64,
+    blob: String,
+    job_id: String,
+    target: String,
+    algo: String,
+}
+
+struct Miner {
+    stats: MinerStats,
+    /// the lines sent to the miner
+    lines: Sender<String>,
+    /// the miner asked for the fleet config
+    fleet: bool,
+}
+
+struct PendingSubmit {
+    slot: u8,
+    request_id: Value,
+}
+
+#[derive(Default)]
+struct ProxyState {
+    job: Option<UpstreamJob>,
+    /// ids and difficulties of the last jobs, the newest last
+    recent_jobs: VecDeque<(String, u64)>,
+    miners: BTreeMap<u8, Miner>,
+    /// the submits the pool did not answer yet, by job id and nonce
+    pending: HashMap<(String, String), PendingSubmit>,
+    upstream: Option<Sender<StratumCmd>>,
+    /// the config line sent to the agents, None without a fleet config
+    fleet_config: Option<String>,
+}
+
+impl ProxyState {
+    fn send(&self, slot: u8, line: String) {
+        if let Some(miner) = self.miners.get(&slot) {
+            //the connection thread removes the miner when it ends
+            let _ = miner.lines.send(line);
+        }
+    }
+
+    fn rejected(&mut self, slot: u8) {
+        if let Some(miner) = self.miners.get_mut(&slot) {
+            miner.stats.rejected += 1;
+        }
+    }
+}
+
+/// A request line of a miner
+#[derive(Deserialize)]
+struct MinerRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Shares one pool connection between the miners of a farm. Each miner gets the jobs
+/// with its own last nonce byte, like NiceHash does, so they never hash the same nonces.
+#[derive(Default)]
+pub struct Proxy {
+    state: Mutex<ProxyState>,
+}
+
+impl Proxy {
+    pub fn new() -> Proxy {
+        Proxy::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, ProxyState> {
+        lock(&self.state)
+    }
+
+    /// The command channel of the pool client, None when the connection is lost. The
+    /// submits the pool did not answer fail then.
+    pub fn set_upstream(&self, upstream: Option<Sender<StratumCmd>>) {
+        let mut state = self.state();
+        if upstream.is_none() {
+            state.job = None;
+            state.recent_jobs.clear();
+            let pending: Vec<PendingSubmit> = state.pending.drain().map(|(_, p)| p).collect();
+            for submit in pending {
+                state.rejected(submit.slot);
+                let line = error_line(&submit.request_id, "the pool connection was lost");
+                state.send(submit.slot, line);
+            }
+        }
+        state.upstream = upstream;
+    }
+
+    /// Handles a line of the miner on `address`, the replies are sent to `lines`. `slot`
+    /// is set by the login and kept by the connection.
+    pub fn request(
+        &self,
+        slot: &mut Option<u8>,
+        address: &str,
+        lines: &Sender<String>,
+        line: &str,
+    ) {
+        let request = match serde_json::from_str::<MinerRequest>(line) {
+            Ok(request) => request,
+            Err(err) => {
+                let _ = lines.send(error_line(
+                    &Value::Null,
+                    &format!("invalid request: {}", err),
+                ));
+                return;
+            }
+        };
+        let reply = match (request.method.as_str(), *slot) {
+            ("login", None) => self.login(slot, address, lines, &request),
+            ("login", Some(_)) => Some(error_line(&request.id, "already logged in")),
+            ("submit", Some(slot)) => self.submit(slot, &request),
+            ("keepalived", _) => Some(ok_line(&request.id, "KEEPALIVED")),
+            //a notification, not answered
+            ("report", slot) => {
+                self.report(slot, &request);
+                None
+            }
+            ("submit", None) => Some(error_line(&request.id, "not logged in")),
+            (method, _) => Some(error_line(
+                &request.id,
+                &format!("unknown method {}", method),
+            )),
+        };
+        if let Some(reply) = reply {
+            let _ = lines.send(reply);
+        }
+    }
+
+    fn login(
+        &self,
+        slot: &mut Option<u8>,
+        address: &str,
+        lines: &Sender<String>,
+        request: &MinerRequest,
+    ) -> Option<String> {
+        let mut state = self.state();
+        let job = match &state.job {
+            Some(job) => job.clone(),
+            None => return Some(error_line(&request.id, "no job from the pool yet")),
+        };
+        let free = (0..=u8::MAX).find(|slot| !state.miners.contains_key(slot));
+        let free = match free {
+            Some(free) => free,
+            None => return Some(error_line(&request.id, "the proxy has 256 miners already")),
+        };
+        let param = |key: &str| {
+            request
+                .params
+                .get(key)
+                .and_then(Value::as_str)
+                .unwrap_or("")
+        };
+        let name = [param("rigid"), param("login"), address]
+            .into_iter()
+            .find(|name| !name.is_empty())
+            .unwrap_or(address)
+            .to_string();
+        let mut extensions = vec!["nicehash", "keepalive"];
+        if binary::has_extension(&request.params, binary::EXTENSION) {
+            extensions.push(binary::EXTENSION);
+        }
+        let fleet = binary::has_extension(&request.params, FLEET_EXTENSION);
+        if fleet {
+            extensions.push(FLEET_EXTENSION);
+        }
+        info!("miner {} ({}) connected, slot {}", name, address, free);
+        state.miners.insert(
+            free,
+            Miner {
+                stats: MinerStats {
+                    slot: free,
+                    address: address.to_string(),
+                    name,
+                    connected: Instant::now(),
+                    accepted: 0,
+                    rejected: 0,
+                    hashes: 0,
+                    report: None,
+                },
+                lines: lines.clone(),
+                fleet,
+            },
+        );
+        *slot = Some(free);
+        let reply = json!({
+            "id": request.id,
+            "jsonrpc": "2.0",
+            "error": null,
+            "result": {
+                "id": free.to_string(),
+                "job": job_json(&job, free),
+                "status": "OK",
+                "extensions": extensions,
+            },
+        });
+        let _ = lines.send(reply.to_string());
+        //after the reply, the agent only knows the extension then
+        if let Some(config_line) = state.fleet_config.as_ref().filter(|_| fleet) {
+            let _ = lines.send(config_line.clone());
+        }
+        None
+    }
+
+    fn submit(&self, slot: u8, request: &MinerRequest) -> Option<String> {
+        let mut state = self.state();
+        let error = match serde_json::from_value::<SubmitParams>(request.params.clone()) {
+            Ok(params) => match check_submit(&state, slot, &params) {
+                Ok(difficulty) => {
+                    let (upstream, job) = match (&state.upstream, &state.job) {
+                        (Some(upstream), Some(job)) => (upstream.clone(), job.clone()),
+                        _ => return Some(error_line(&request.id, "no pool connection")),
+                    };
+                    let share = stratum_data::Share {
+                        miner_id: job.miner_id,
+                        job_id: params.job_id.clone(),
+                        nonce: params.nonce.clone(),
+                        hash: params.result,
+                        difficulty,
+                    };
+                    if super::submit_share(&upstream, share).is_err() {
+                        "no pool connection".to_string()
+                    } else {
+                        state.pending.insert(
+                            (params.job_id, params.nonce),
+                            PendingSubmit {
+                                slot,
+                                request_id: request.id.clone(),
+                            },
+                        );
+                        //answered when the pool answers
+                        return None;
+                    }
+                }
+                Err(err) => err,
+            },
+            Err(err) => format!("invalid submit: {}", err),
+        };
+        state.rejected(slot);
+        Some(error_line(&request.id, &error))
+    }
+
+    fn report(&self, slot: Option<u8>, request: &MinerRequest) {
+        let report = serde_json::from_value::<HashrateReport>(request.params.clone());
+        let mut state = self.state();
+        match (slot.and_then(|slot| state.miners.get_mut(&slot)), report) {
+            (Some(miner), Ok(report)) => miner.stats.report = Some(report),
+            (Some(miner), Err(err)) => {
+                warn!("invalid report of miner {}: {}", miner.stats.name, err)
+            }
+            (None, _) => {}
+        }
+    }
+
+    /// Sends the command to the miner of the slot, or to all miners if it is None.
+    /// Returns the number of miners it was sent to.
+    pub fn control(&self, slot: Option<u8>, control: &ClusterControl) -> usize {
+        let state = self.state();
+        let line = json!({
+            "jsonrpc": "2.0",
+            "method": "control",
+            "params": control,
+        })
+        .to_string();
+        let mut sent = 0;
+        for (miner_slot, miner) in &state.miners {
+            if slot.is_none_or(|slot| slot == *miner_slot) && miner.lines.send(line.clone()).is_ok()
+            {
+                sent += 1;
+            }
+        }
+        info!("sent {:?} to {} miners", control, sent);
+        sent
+    }
+
+    /// Sends the fleet config to the agents that asked for it, and to the ones that log
+    /// in later
+    pub fn set_fleet_config(&self, config: &str) {
+        let mut state = self.state();
+        let line = json!({
+            "jsonrpc": "2.0",
+            "method": "config",
+            "params": { "config": config },
+        })
+        .to_string();
+        let mut sent = 0;
+        for miner in state.miners.values().filter(|miner| miner.fleet) {
+            if miner.lines.send(line.clone()).is_ok() {
+                sent += 1;
+            }
+        }
+        info!("fleet config sent to {} agents", sent);
+        state.fleet_config = Some(line);
+    }
+
+    /// The miner of the slot disconnected
+    pub fn disconnect(&self, slot: u8) {
+        let mut state = self.state();
+        if let Some(miner) = state.miners.remove(&slot) {
+            let stats = miner.stats;
+            info!(
+                "miner {} ({}) disconnected, {} accepted and {} rejected shares",
+                stats.name, stats.address, stats.accepted, stats.rejected
+            );
+        }
+        state.pending.retain(|_, submit| submit.slot != slot);
+    }
+
+    /// Handles a message of the pool
+    pub fn upstream_action(&self, action: StratumAction) {
+        let mut state = self.state();
+        match action {
+            StratumAction::Job {
+                miner_id,
+                seed_hash,
+                height,
+                blob,
+                job_id,
+                target,
+                algo,
+            } => {
+                if blob.len() < SLOT_END || !blob.is_char_boundary(SLOT_END) {
+                    warn!("job {} of the pool has a too short blob", job_id);
+                    return;
+                }
+                let job = UpstreamJob {
+                    miner_id,
+                    seed_hash,
+                    height,
+                    blob,
+                    job_id,
+                    target,
+                    algo,
+                };
+                state
+                    .recent_jobs
+                    .push_back((job.job_id.clone(), job_difficulty(&job.target)));
+                if state.recent_jobs.len() > RECENT_JOBS {
+                    state.recent_jobs.pop_front();
+                }
+                for (slot, miner) in &state.miners {
+                    let line = json!({
+                        "jsonrpc": "2.0",
+                        "method": "job",
+                        "params": job_json(&job, *slot),
+                    });
+                    let _ = miner.lines.send(line.to_string());
+                }
+                info!("job {} sent to {} miners", job.job_id, state.miners.len());
+                state.job = Some(job);
+            }
+            StratumAction::ShareAccepted { share, .. } => {
+                if let Some(submit) = state.pending.remove(&(share.job_id, share.nonce)) {
+                    if let Some(miner) = state.miners.get_mut(&submit.slot) {
+                        miner.stats.accepted += 1;
+                        miner.stats.hashes += share.difficulty;
+                    }
+                    state.send(submit.slot, ok_line(&submit.request_id, "OK"));
+                }
+            }
+            StratumAction::ShareRejected { share, err, .. } => {
+                if let Some(submit) = state.pending.remove(&(share.job_id, share.nonce)) {
+                    state.rejected(submit.slot);
+                    state.send(submit.slot, error_line(&submit.request_id, &err));
+                }
+            }
+            StratumAction::Error { err } => warn!("pool error: {}", err),
+            StratumAction::Ok
+            | StratumAction::KeepAliveOk
+            | StratumAction::Control { .. }
+            | StratumAction::FleetConfig { .. } => {}
+        }
+    }
+
+    /// The connected miners by slot
+    pub fn stats(&self) -> Vec<MinerStats> {
+        self.state()
+            .miners
+            .values()
+            .map(|miner| miner.stats.clone())
+            .collect()
+    }
+
+    fn log_stats(&self) {
+        let now = Instant::now();
+        let stats = self.stats();
+        let hashrate: f64 = stats.iter().map(|miner| miner.hashrate(now)).sum();
+        info!(
+            "proxy: {} miners, {:.1} H/s from the accepted shares",
+            stats.len(),
+            hashrate
+        );
+        for miner in stats {
+            let reported = match &miner.report {
+                Some(report) if report.paused => ", paused".to_string(),
+                Some(report) => format!(
+                    ", reports {:.1} H/s on {} threads{}",
+                    report.hashrate,
+                    report.threads,
+                    report
+                        .temperature
+                        .map_or(String::new(), |temperature| format!(
+                            " at {:.0}°C",
+                            temperature
+                        ))
+                ),
+                None => String::new(),
+            };
+            info!(
+                "miner {} ({}, slot {}): {} accepted, {} rejected, {:.1} H/s{}",
+                miner.name,
+                miner.address,
+                miner.slot,
+                miner.accepted,
+                miner.rejected,
+                miner.hashrate(now),
+                reported
+            );
+        }
+    }
+}
+
+/// The difficulty of the job of the share, or why it is not forwarded
+fn check_submit(state: &ProxyState, slot: u8, params: &SubmitParams) -> Result<u64, String> {
+    let difficulty = state
+        .recent_jobs
+        .iter()
+        .find(|(job_id, _)| *job_id == params.job_id)
+        .map(|(_, difficulty)| *difficulty)
+        .ok_or_else(|| format!("unknown job {}", params.job_id))?;
+    if params.nonce.len() != 8 || !params.nonce.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("invalid nonce {}", params.nonce));
+    }
+    if !params.nonce[6..].eq_ignore_ascii_case(&format!("{:02x}", slot)) {
+        return Err(format!(
+            "nonce {} is not in the nonces of the miner, is it in nicehash mode?",
+            params.nonce
+        ));
+    }
+    if params.result.len() != 64 || !params.result.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("invalid result".to_string());
+    }
+    if state
+        .pending
+        .contains_key(&(params.job_id.clone(), params.nonce.clone()))
+    {
+        return Err("duplicate share".to_string());
+    }
+    Ok(difficulty)
+}
+
+/// The job with the slot as the last nonce byte
+fn job_json(job: &UpstreamJob, slot: u8) -> Value {
+    let blob = format!(
+        "{}{:02x}{}",
+        &job.blob[..SLOT_START],
+        slot,
+        &job.blob[SLOT_END..]
+    );
+    let mut params = Map::new();
+    params.insert("blob".to_string(), json!(blob));
+    params.insert("job_id".to_string(), json!(job.job_id));
+    params.insert("target".to_string(), json!(job.target));
+    params.insert("height".to_string(), json!(job.height));
+    if !job.seed_hash.is_empty() {
+        params.insert("seed_hash".to_string(), json!(job.seed_hash));
+    }
+    if !job.algo.is_empty() {
+        params.insert("algo".to_string(), json!(job.algo));
+    }
+    Value::Object(params)
+}
+
+fn ok_line(id: &Value, status: &str) -> String {
+    json!({ "id": id, "jsonrpc": "2.0", "error": null, "result": { "status": status } }).to_string()
+}
+
+fn error_line(id: &Value, message: &str) -> String {
+    json!({ "id": id, "jsonrpc": "2.0", "error": { "code": -1, "message": message } }).to_string()
+}
+
+/// Accepts miners on `listen` and connects to the pool until the process is stopped, a
+/// lost pool connection is reconnected
+pub fn run(conf: &ProxyConfig) -> io::Result<()> {
+    run_with(conf, Arc::new(Proxy::new()))
+}
+
+/// Like `run`, the caller keeps the proxy to control its miners
+pub fn run_with(conf: &ProxyConfig, proxy: Arc<Proxy>) -> io::Result<()> {
+    if conf.pool.nicehash {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the proxy splits the nonces of the pool between the miners, it cannot use a pool with nicehash = true",
+        ));
+    }
+    if conf.pool.daemon {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the proxy needs a stratum pool, it cannot mine solo on a daemon",
+        ));
+    }
+    let listener = TcpListener::bind(&conf.listen)?;
+    info!(
+        "stratum proxy listening on {} for {}",
+        conf.listen, conf.pool.pool_address
+    );
+
+    let accept_proxy = proxy.clone();
+    thread::Builder::new()
+        .name("proxy accept thread".to_string())
+        .spawn(move || accept(&accept_proxy, listener))?;
+    let stats_proxy = proxy.clone();
+    let stats_interval = conf.stats_interval;
+    thread::Builder::new()
+        .name("proxy stats thread".to_string())
+        .spawn(move || loop {
+            thread::sleep(stats_interval);
+            stats_proxy.log_stats();
+        })?;
+
+    loop {
+        connect_upstream(&proxy, &conf.pool);
+        thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+/// Forwards the messages of the pool until the connection is lost
+fn connect_upstream(proxy: &Proxy, pool: &PoolConfig) {
+    let (err_sndr, err_rcvr) = unbounded();
+    let (action_sndr, action_rcvr) = unbounded();
+    let client = match StratumClient::login(pool.clone(), err_sndr, action_sndr) {
+        Ok(client) => client,
+        Err(err) => {
+            error!("pool connection failed: {}", err);
+            return;
+        }
+    };
+    proxy.set_upstream(Some(client.new_cmd_channel()));
+    loop {
+        select! {
+            recv(action_rcvr) -> action => match action {
+                Ok(action) => proxy.upstream_action(action),
+                Err(_) => break,
+            },
+            recv(err_rcvr) -> err => {
+                if let Ok(err) = err {
+                    error!("pool connection lost, reconnecting: {}", err);
+                }
+                break;
+            },
+        }
+    }
+    proxy.set_upstream(None);
+    if let Err(err) = client.stop() {
+        warn!("stratum client did not stop cleanly: {}", err);
+    }
+}
+
+fn accept(proxy: &Arc<Proxy>, listener: TcpListener) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("proxy accept failed: {}", err);
+                continue;
+            }
+        };
+        let proxy = proxy.clone();
+        let spawned = thread::Builder::new()
+            .name("proxy miner thread".to_string())
+            .spawn(move || {
+                if let Err(err) = serve(&proxy, stream) {
+                    info!("miner connection ended: {}", err);
+                }
+            });
+        if let Err(err) = spawned {
+            warn!("proxy miner thread could not be started: {}", err);
+        }
+    }
+}
+
+fn serve(proxy: &Proxy, stream: TcpStream) -> io::Result<()> {
+    let address = stream.peer_addr()?.to_string();
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let (lines, lines_rcvr) = unbounded::<String>();
+    //the miner asked for the binary protocol, the writer switches after the login reply
+    let asks_binary = Arc::new(AtomicBool::new(false));
+    let writer_asks_binary = asks_binary.clone();
+    let mut writer = stream.try_clone()?;
+    let write_thread = thread::Builder::new()
+        .name("proxy write thread".to_string())
+        .spawn(move || {
+            let mut binary_mode = false;
+            for line in lines_rcvr.iter() {
+                let written = if binary_mode {
+                    binary::write_message(&mut writer, &Message::from_line(&line))
+                } else {
+                    binary_mode = writer_asks_binary.load(Ordering::SeqCst)
+                        && binary::switches_to_binary(&line);
+                    writeln!(writer, "{}", line)
+                };
+                if written.is_err() {
+                    //ends the read loop too
+                    let _ = writer.shutdown(Shutdown::Both);
+                    break;
+                }
+            }
+        })?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut slot = None;
+    let mut binary_mode = false;
+    let result = loop {
+        let line = match read_line(&mut reader, binary_mode) {
+            Ok(Some(line)) => line,
+            Ok(None) => break Ok(()),
+            Err(err) => break Err(err),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let asks = slot.is_none() && binary::asks_binary(&line);
+        if asks {
+            asks_binary.store(true, Ordering::SeqCst);
+        }
+        proxy.request(&mut slot, &address, &lines, line.trim_end());
+        //the login succeeded, the miner sends frames after the reply
+        binary_mode = binary_mode || (asks && slot.is_some());
+    };
+    if let Some(slot) = slot {
+        proxy.disconnect(slot);
+    }
+    drop(lines);
+    let _ = write_thread.join();
+    result
+}
+
+/// The next request of the miner as a JSON line, None when the miner disconnected
+fn read_line(reader: &mut BufReader<&TcpStream>, binary_mode: bool) -> io::Result<Option<String>> {
+    if binary_mode {
+        match binary::read_message(reader) {
+            Ok(message) => Ok(Some(message.to_line())),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err),
+        }
+    } else {
+        let mut line = String::new();
+        match reader.read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line)),
+        }
+    }
+}
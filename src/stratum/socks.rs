@@ -0,0 +1,102 @@
+use std::io;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const CONNECT: u8 = 1;
+const DOMAIN_NAME: u8 = 3;
+
+/// Connects to `target` (host:port) through the SOCKS5 proxy at `proxy`. The proxy
+/// resolves the host name, no authentication is supported.
+pub fn connect(proxy: &str, target: &str) -> io::Result<TcpStream> {
+    let request = connect_request(target)?;
+
+    let mut stream = TcpStream::connect(proxy)?;
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+
+    stream.write_all(&[VERSION, 1, NO_AUTHENTICATION])?;
+    let mut method = [0u8; 2];
+    stream.read_exact(&mut method)?;
+    if method != [VERSION, NO_AUTHENTICATION] {
+        return Err(io::Error::other(format!(
+            "proxy {} is no SOCKS5 proxy or requires authentication",
+            proxy
+        )));
+    }
+
+    stream.write_all(&request)?;
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != VERSION {
+        return Err(io::Error::other(format!(
+            "proxy {} is no SOCKS5 proxy",
+            proxy
+        )));
+    }
+    if reply[1] != 0 {
+        return Err(io::Error::other(format!(
+            "proxy {} could not connect to {}: {}",
+            proxy,
+            target,
+            reply_message(reply[1])
+        )));
+    }
+
+    //the address the proxy bound for the connection is not needed
+    let address_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        DOMAIN_NAME => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        other => {
+            return Err(io::Error::other(format!(
+                "proxy {} replied with unknown address type {}",
+                proxy, other
+            )))
+        }
+    };
+    let mut bound = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound)?;
+    Ok(stream)
+}
+
+/// The CONNECT request for `target`, the host is sent as domain name
+pub fn connect_request(target: &str) -> io::Result<Vec<u8>> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} has to be host:port", target),
+        )
+    };
+    let (host, port) = target.rsplit_once(':').ok_or_else(invalid)?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = port.parse::<u16>().map_err(|_| invalid())?;
+    if host.is_empty() || host.len() > u8::MAX as usize {
+        return Err(invalid());
+    }
+
+    let mut request = vec![VERSION, CONNECT, 0, DOMAIN_NAME, host.len() as u8];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    Ok(request)
+}
+
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
@@ -0,0 +1,403 @@
+//! Solo mining against the JSON-RPC of a monerod. The block templates of the daemon
+//! are handed to the workers as jobs with the network difficulty as target, so every
+//! share is a block and is submitted with `submit_block`. With the ZMQ notifications of
+//! the daemon a new block is mined at once, the polling only catches lost ones. How often
+//! the templates are renewed is tuned by the `SoloConfig` of the pool.
+
+extern crate crossbeam_channel;
+extern crate tracing;
+extern crate ureq;
+
+use self::crossbeam_channel::{never, select, tick, unbounded, Receiver, Sender};
+use self::tracing::instrument;
+use super::stratum_data::{PoolConfig, Share, SoloConfig};
+use super::zmq::{self, Subscriber};
+use super::{StratumAction, StratumCmd, StratumError};
+use crate::byte_string;
+use crate::difficulty;
+use crate::worker::worker_pool;
+use serde_json::{json, Value};
+
+use std::collections::VecDeque;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// The daemon is asked at most this often while its notifications arrive
+const NOTIFIED_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// The templates kept for the shares of the previous jobs
+const RECENT_TEMPLATES: usize = 4;
+/// The miner id of the jobs, the daemon has no login
+const MINER_ID: &str = "solo";
+
+/// The part of the `get_block_template` result the miner uses
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BlockTemplate {
+    pub blocktemplate_blob: String,
+    pub blockhashing_blob: String,
+    pub difficulty: u64,
+    pub height: u64,
+    pub prev_hash: String,
+    #[serde(default)]
+    pub seed_hash: String,
+    /// block reward with the fees of the transactions, changes with the mempool
+    #[serde(default)]
+    pub expected_reward: u64,
+}
+
+/// The JSON-RPC of a monerod
+pub struct Daemon {
+    agent: ureq::Agent,
+    url: String,
+    wallet_address: String,
+    reserve_size: u64,
+}
+
+impl Daemon {
+    pub fn new(pool_conf: &PoolConfig) -> Daemon {
+        let scheme = if pool_conf.tls { "https" } else { "http" };
+        Daemon {
+            agent: ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build(),
+            url: format!("{}://{}/json_rpc", scheme, pool_conf.pool_address),
+            wallet_address: pool_conf.wallet_address.clone(),
+            reserve_size: pool_conf.solo.reserve_size,
+        }
+    }
+
+    /// The result of the call, Ok(Err) with the message if the daemon answered with an
+    /// error, Err if the daemon was not reachable
+    fn call(&self, method: &str, params: Value) -> Result<Result<Value, String>, StratumError> {
+        let request = json!({ "jsonrpc": "2.0", "id": "0", "method": method, "params": params });
+        let body = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&request.to_string())
+            .map_err(|err| StratumError::Daemon(err.to_string()))?
+            .into_string()?;
+        let response: Value = serde_json::from_str(&body)
+            .map_err(|err| StratumError::Daemon(format!("invalid {} response: {}", method, err)))?;
+        if let Some(message) = response["error"]["message"].as_str() {
+            return Ok(Err(message.to_string()));
+        }
+        match response.get("result") {
+            Some(result) if result["status"] == "OK" => Ok(Ok(result.clone())),
+            Some(result) => Ok(Err(format!("status {}", result["status"]))),
+            None => Err(StratumError::Daemon(format!(
+                "{} returned no result",
+                method
+            ))),
+        }
+    }
+
+    pub fn block_template(&self) -> Result<BlockTemplate, StratumError> {
+        let params =
+            json!({ "wallet_address": self.wallet_address, "reserve_size": self.reserve_size });
+        let result = self
+            .call("get_block_template", params)?
+            .map_err(|err| StratumError::Daemon(format!("no block template: {}", err)))?;
+        serde_json::from_value(result)
+            .map_err(|err| StratumError::Daemon(format!("invalid block template: {}", err)))
+    }
+
+    /// Submits the block, Err with the reason of the daemon if it was not accepted
+    pub fn submit_block(&self, blob: &str) -> Result<Result<(), String>, StratumError> {
+        Ok(self.call("submit_block", json!([blob]))?.map(|_| ()))
+    }
+
+    /// Whether the daemon answers and is synchronized with the network
+    pub fn ready(&self) -> bool {
+        match self.call("get_info", json!({})) {
+            Ok(Ok(info)) => info["synchronized"].as_bool().unwrap_or(true),
+            _ => false,
+        }
+    }
+}
+
+/// The job target of the difficulty, 8 bytes as the 4 bytes of a pool do not reach the
+/// network difficulty
+pub fn job_target(difficulty: u64) -> String {
+    difficulty::format_target(difficulty::difficulty_target(difficulty))
+}
+
+/// False if the hash meets the 64 bit job target, but not the network difficulty on all
+/// 256 bits, the daemon would reject the block
+pub fn meets_network_difficulty(hash: &str, difficulty: u64) -> bool {
+    match byte_string::decode_array(hash) {
+        Ok(hash) => difficulty::meets_difficulty(&hash, difficulty),
+        Err(_) => false,
+    }
+}
+
+/// The block of the template with the nonce of the share. The nonce is at the same place
+/// in the block header as in the hashing blob.
+pub fn block_blob(template: &BlockTemplate, nonce: &str) -> String {
+    worker_pool::with_nonce(&template.blocktemplate_blob, nonce)
+}
+
+/// Subscribes to the new blocks of the daemon at `address`, also to the new transactions
+/// with `mempool`. The receiver gets one message per notification. None without an
+/// address.
+fn subscribe(address: &str, mempool: bool) -> io::Result<Option<(Subscriber, Receiver<()>)>> {
+    if address.is_empty() {
+        return Ok(None);
+    }
+    let topics: &[&str] = if mempool {
+        &[zmq::CHAIN_MAIN_TOPIC, zmq::TXPOOL_ADD_TOPIC]
+    } else {
+        &[zmq::CHAIN_MAIN_TOPIC]
+    };
+    let mut subscriber = Subscriber::connect(address, topics)?;
+    let handle = subscriber.try_clone()?;
+    let (sender, receiver) = unbounded();
+    thread::Builder::new()
+        .name("zmq thread".to_string())
+        .spawn(move || loop {
+            match subscriber.recv() {
+                Ok(message)
+                    if topics
+                        .iter()
+                        .any(|topic| message.starts_with(topic.as_bytes())) =>
+                {
+                    if sender.send(()).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                //also the shutdown of the client
+                Err(err) => {
+                    info!("zmq connection ended: {}", err);
+                    break;
+                }
+            }
+        })?;
+    info!("subscribed to the new blocks of {}", address);
+    Ok(Some((handle, receiver)))
+}
+
+/// Mines the templates of a monerod instead of the jobs of a pool
+pub struct SoloClient {
+    command_sender: Sender<StratumCmd>,
+    thread: thread::JoinHandle<()>,
+    /// the connection to the ZMQ publisher of the daemon, None without one
+    subscriber: Option<Subscriber>,
+}
+
+impl SoloClient {
+    /// Fails if the daemon has no template for the wallet
+    pub fn login(
+        pool_conf: PoolConfig,
+        err_sender: Sender<StratumError>,
+        action_sender: Sender<StratumAction>,
+    ) -> Result<SoloClient, StratumError> {
+        info!(
+            event = "pool_connect", pool = pool_conf.pool_address.as_str();
+            "mining solo on daemon {}", pool_conf.pool_address
+        );
+        let daemon = Daemon::new(&pool_conf);
+        let template = daemon.block_template()?;
+        let (subscriber, notifications) =
+            match subscribe(&pool_conf.zmq, pool_conf.solo.mempool_refresh) {
+                Ok(Some((subscriber, notifications))) => (Some(subscriber), Some(notifications)),
+                Ok(None) => (None, None),
+                Err(err) => {
+                    warn!(
+                        "no block notifications from {}, polling the daemon: {}",
+                        pool_conf.zmq, err
+                    );
+                    (None, None)
+                }
+            };
+        let (command_sender, command_receiver) = unbounded();
+        let algorithm = pool_conf.algorithm.clone();
+        let conf = pool_conf.solo.clone();
+        let thread = thread::Builder::new()
+            .name("solo thread".to_string())
+            .spawn(move || {
+                let mut solo = Solo {
+                    daemon,
+                    conf,
+                    algorithm,
+                    action_sender,
+                    templates: VecDeque::new(),
+                    renewed: Instant::now(),
+                    jobs: 0,
+                };
+                if let Err(err) = solo.run(template, &command_receiver, notifications) {
+                    let _ = err_sender.send(err);
+                }
+                info!("solo thread ended");
+            })?;
+        Ok(SoloClient {
+            command_sender,
+            thread,
+            subscriber,
+        })
+    }
+
+    pub fn new_cmd_channel(&self) -> Sender<StratumCmd> {
+        self.command_sender.clone()
+    }
+
+    pub fn stop(self) -> Result<(), StratumError> {
+        info!("stopping solo client");
+        let _ = self.command_sender.send(StratumCmd::Shutdown {});
+        if let Some(subscriber) = &self.subscriber {
+            subscriber.shutdown();
+        }
+        self.thread
+            .join()
+            .map_err(|_| StratumError::ThreadPanicked("solo"))
+    }
+}
+
+struct Solo {
+    daemon: Daemon,
+    conf: SoloConfig,
+    algorithm: String,
+    action_sender: Sender<StratumAction>,
+    /// by job id, the newest last
+    templates: VecDeque<(String, BlockTemplate)>,
+    /// when the newest template was mined first
+    renewed: Instant,
+    jobs: u64,
+}
+
+impl Solo {
+    fn run(
+        &mut self,
+        template: BlockTemplate,
+        commands: &Receiver<StratumCmd>,
+        notifications: Option<Receiver<()>>,
+    ) -> Result<(), StratumError> {
+        self.new_job(template)?;
+        let interval = Duration::from_secs(self.conf.template_refresh_seconds);
+        let (mut notifications, mut refresh) = match notifications {
+            Some(notifications) => (notifications, tick(interval.max(NOTIFIED_REFRESH_INTERVAL))),
+            None => (never(), tick(interval)),
+        };
+        loop {
+            select! {
+                recv(commands) -> cmd => match cmd {
+                    Ok(StratumCmd::SubmitShare { share }) => self.submit(share)?,
+                    Ok(StratumCmd::Shutdown {}) | Err(_) => return Ok(()),
+                    //the daemon has no login, keep alive or coordinator
+                    Ok(_) => {}
+                },
+                recv(notifications) -> notification => match notification {
+                    Ok(()) => {
+                        //a burst of transactions needs one template
+                        notifications.try_iter().for_each(drop);
+                        self.refresh()?
+                    }
+                    Err(_) => {
+                        warn!("block notifications of the daemon lost, polling it");
+                        notifications = never();
+                        refresh = tick(interval);
+                    }
+                },
+                recv(refresh) -> _ => self.refresh()?,
+            }
+        }
+    }
+
+    /// A new job if the chain moved on, the mempool changed with `mempool_refresh` or the
+    /// template is older than `template_max_age_seconds`
+    fn refresh(&mut self) -> Result<(), StratumError> {
+        let template = self.daemon.block_template()?;
+        let max_age = self.conf.template_max_age_seconds;
+        let renew = match self.templates.back().map(|(_, current)| current) {
+            None => true,
+            Some(current) if current.prev_hash != template.prev_hash => {
+                info!("new block at height {}", template.height);
+                true
+            }
+            Some(current)
+                if self.conf.mempool_refresh
+                    && current.expected_reward != template.expected_reward =>
+            {
+                info!(
+                    "new transactions, block reward {}",
+                    template.expected_reward
+                );
+                true
+            }
+            Some(_) if max_age != 0 && self.renewed.elapsed() >= Duration::from_secs(max_age) => {
+                info!("block template older than {} seconds renewed", max_age);
+                true
+            }
+            Some(_) => false,
+        };
+        if renew {
+            self.new_job(template)?;
+        }
+        Ok(())
+    }
+
+    fn new_job(&mut self, template: BlockTemplate) -> Result<(), StratumError> {
+        self.jobs += 1;
+        let job_id = format!("{}-{}", template.height, self.jobs);
+        let job = StratumAction::Job {
+            miner_id: MINER_ID.to_string(),
+            seed_hash: template.seed_hash.clone(),
+            height: template.height,
+            blob: template.blockhashing_blob.clone(),
+            job_id: job_id.clone(),
+            target: job_target(template.difficulty),
+            algo: self.algorithm.clone(),
+        };
+        if self.templates.len() == RECENT_TEMPLATES {
+            self.templates.pop_front();
+        }
+        self.templates.push_back((job_id, template));
+        self.renewed = Instant::now();
+        self.action_sender
+            .send(job)
+            .map_err(|_| StratumError::ChannelClosed("action"))
+    }
+
+    #[instrument(
+        name = "share_submit",
+        skip_all,
+        fields(job_id = share.job_id.as_str(), nonce = share.nonce.as_str())
+    )]
+    fn submit(&mut self, share: Share) -> Result<(), StratumError> {
+        let submitted = Instant::now();
+        let template = self
+            .templates
+            .iter()
+            .find(|(job_id, _)| *job_id == share.job_id)
+            .map(|(_, template)| template.clone());
+        let result = match &template {
+            Some(template) if !meets_network_difficulty(&share.hash, template.difficulty) => {
+                Err(format!(
+                    "low difficulty, below {} on all 256 bits",
+                    template.difficulty
+                ))
+            }
+            Some(template) => self
+                .daemon
+                .submit_block(&block_blob(template, &share.nonce))?,
+            None => Err(format!("unknown job {}", share.job_id)),
+        };
+        let latency = submitted.elapsed();
+        let height = template.map_or(0, |template| template.height);
+        let action = match result {
+            Ok(()) => {
+                info!(event = "block_found", height = height; "block found at height {}", height);
+                StratumAction::ShareAccepted { share, latency }
+            }
+            Err(err) => StratumAction::ShareRejected {
+                share,
+                err,
+                latency,
+            },
+        };
+        self.action_sender
+            .send(action)
+            .map_err(|_| StratumError::ChannelClosed("action"))?;
+        //the block is on the chain, mine on top of it
+        self.refresh()
+    }
+}
@@ -1,65 +1,223 @@
 extern crate serde;
 extern crate serde_json;
 
-/// For checking the method in the json content and parsing further
+use crate::byte_string::{self, HexError};
+use crate::difficulty;
+use crate::randomx::wipe::wipe_string;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Any line of a pool. Which of the fields are set decides the kind of message, see
+/// `PoolMessage::parse`. Fields of extensions are ignored.
 #[derive(Deserialize, Debug)]
-pub struct Method {
-    pub method: String,
+pub struct Envelope {
+    /// the id of the request a response answers, None for a notification
+    #[serde(default)]
+    pub id: Option<u32>,
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default)]
+    pub error: Option<ErrorDetails>,
+    #[serde(default)]
+    pub result: Option<ResponseResult>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct ErrorDetails {
     pub code: i64,
     pub message: String,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct ErrorResult {
-    pub error: ErrorDetails,
+/// The result of a response, a login result has the miner id and the first job
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponseResult {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub status: String,
+    #[serde(default)]
+    pub job: Option<Job>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Job {
+    /// empty for algorithms without a seed, see `JobHints`
+    #[serde(default)]
     pub seed_hash: String,
+    /// the block height, 0 if the pool does not send it
+    #[serde(default)]
+    pub height: u64,
     pub blob: String,
     pub job_id: String,
     pub target: String,
+    /// the algorithm of the job, empty if the pool does not send it, the configured
+    /// one is mined then
+    #[serde(default)]
+    pub algo: String,
 }
 
-#[derive(Deserialize)]
-pub struct LoginResult {
-    pub id: String,
-    pub job: Job,
-    pub status: String,
+/// The blob has to hold the nonce (bytes 39 to 42)
+const MIN_BLOB_BYTES: usize = 43;
+/// the largest blob of the supported coins, like xmrig
+const MAX_BLOB_BYTES: usize = 408;
+/// far below the minimum of any pool, an easier target floods the pool with shares
+pub const MIN_JOB_DIFFICULTY: u64 = 100;
+
+/// Why a job of a pool is not mined
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum JobError {
+    #[error("no job id")]
+    MissingJobId,
+    #[error("invalid blob: {0}")]
+    Blob(HexError),
+    #[error("blob of {0} bytes, expected {MIN_BLOB_BYTES} to {MAX_BLOB_BYTES}")]
+    BlobLength(usize),
+    #[error("invalid target: {0}")]
+    Target(HexError),
+    #[error("target of {0} bytes, expected 4 or 8")]
+    TargetLength(usize),
+    /// no hash meets it
+    #[error("target 0")]
+    ZeroTarget,
+    #[error("target of difficulty {0}, expected at least {MIN_JOB_DIFFICULTY}")]
+    EasyTarget(u64),
+    #[error("invalid seed hash: {0}")]
+    SeedHash(HexError),
 }
 
-#[derive(Deserialize)]
-pub struct LoginResponse {
-    pub id: u32,
-    pub result: LoginResult,
+impl Job {
+    /// Checks what the workers rely on, a pool may send garbage during an upgrade
+    pub fn validate(&self) -> Result<(), JobError> {
+        if self.job_id.is_empty() {
+            return Err(JobError::MissingJobId);
+        }
+        let blob = byte_string::decode(&self.blob).map_err(JobError::Blob)?;
+        if !(MIN_BLOB_BYTES..=MAX_BLOB_BYTES).contains(&blob.len()) {
+            return Err(JobError::BlobLength(blob.len()));
+        }
+        let target = byte_string::decode(&self.target).map_err(JobError::Target)?;
+        if target.len() != 4 && target.len() != 8 {
+            return Err(JobError::TargetLength(target.len()));
+        }
+        if target.iter().all(|b| *b == 0) {
+            return Err(JobError::ZeroTarget);
+        }
+        let difficulty = difficulty::job_difficulty(&self.target);
+        if difficulty < MIN_JOB_DIFFICULTY {
+            return Err(JobError::EasyTarget(difficulty));
+        }
+        if !self.seed_hash.is_empty() {
+            byte_string::decode_array::<32>(&self.seed_hash).map_err(JobError::SeedHash)?;
+        }
+        Ok(())
+    }
 }
 
-#[derive(Deserialize, Clone)]
-pub struct OkResult {
-    pub id: Option<String>,
-    pub status: String,
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct FleetConfigParams {
+    pub config: String,
 }
 
-#[derive(Deserialize, Clone)]
-pub struct OkResponse {
-    pub id: u32,
-    pub result: OkResult,
+/// A message of a pool
+#[derive(Debug, Clone, PartialEq)]
+pub enum PoolMessage {
+    /// the answer to the login
+    Login {
+        id: Option<u32>,
+        miner_id: String,
+        status: String,
+        job: Job,
+    },
+    /// the answer to a submit or keep alive
+    Status {
+        id: Option<u32>,
+        status: String,
+    },
+    Error {
+        id: Option<u32>,
+        error: ErrorDetails,
+    },
+    Job(Job),
+    Control(ClusterControl),
+    FleetConfig(FleetConfigParams),
+    /// a method mithril does not know, with its name
+    UnknownMethod(String),
 }
 
-#[derive(Deserialize)]
-pub struct JobResponse {
-    pub params: Job,
+#[derive(Debug, Error)]
+pub enum MessageError {
+    #[error("invalid message: {0}")]
+    Json(#[source] serde_json::Error),
+    #[error("invalid {0}: {1}")]
+    Params(&'static str, #[source] serde_json::Error),
+    #[error("neither a method, a result nor an error")]
+    Empty,
+}
+
+impl PoolMessage {
+    pub fn parse(line: &str) -> Result<PoolMessage, MessageError> {
+        let envelope: Envelope = serde_json::from_str(line).map_err(MessageError::Json)?;
+        if let Some(error) = envelope.error {
+            return Ok(PoolMessage::Error {
+                id: envelope.id,
+                error,
+            });
+        }
+        if let Some(method) = envelope.method {
+            let params = envelope.params;
+            return Ok(match method.as_str() {
+                "job" => PoolMessage::Job(from_params("job", params)?),
+                "control" => PoolMessage::Control(from_params("control command", params)?),
+                "config" => PoolMessage::FleetConfig(from_params("fleet config", params)?),
+                _ => PoolMessage::UnknownMethod(method),
+            });
+        }
+        match envelope.result {
+            Some(ResponseResult {
+                id: Some(miner_id),
+                status,
+                job: Some(job),
+            }) => Ok(PoolMessage::Login {
+                id: envelope.id,
+                miner_id,
+                status,
+                job,
+            }),
+            Some(ResponseResult { status, .. }) => Ok(PoolMessage::Status {
+                id: envelope.id,
+                status,
+            }),
+            None => Err(MessageError::Empty),
+        }
+    }
+}
+
+fn from_params<T: DeserializeOwned>(
+    method: &'static str,
+    params: Value,
+) -> Result<T, MessageError> {
+    serde_json::from_value(params).map_err(|err| MessageError::Params(method, err))
 }
 
 #[derive(Serialize)]
 pub struct LoginParams {
     pub login: String,
     pub pass: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub rigid: String,
+    /// the supported algorithms, algorithm switching pools send jobs for one of them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub algo: Vec<String>,
+    /// the benchmarked H/s of the algorithms (MoneroOcean extension), the pool picks the
+    /// most profitable one with them
+    #[serde(rename = "algo-perf", skip_serializing_if = "BTreeMap::is_empty")]
+    pub algo_perf: BTreeMap<String, f64>,
+    /// the extensions of mithril proxies the miner asks for
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -81,7 +239,7 @@ pub struct KeepAliveRequest {
     pub params: KeepAliveParams,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct SubmitParams {
     pub id: String,
     pub job_id: String,
@@ -96,17 +254,141 @@ pub struct SubmitRequest {
     pub params: SubmitParams,
 }
 
-#[derive(Debug)]
+/// The state of a cluster agent, sent to the coordinator every 30 seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HashrateReport {
+    /// H/s since the last report
+    pub hashrate: f64,
+    pub threads: u64,
+    pub paused: bool,
+    /// highest CPU package temperature, None if the rig cannot read it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// errors of the pool connections since the agent started
+    #[serde(default)]
+    pub stratum_errors: u64,
+    /// corrupted dataset items the dataset checks found since the agent started
+    #[serde(default)]
+    pub dataset_errors: u64,
+}
+
+/// A notification without an id, the coordinator does not answer it
+#[derive(Serialize)]
+pub struct ReportRequest {
+    pub method: String,
+    pub params: HashrateReport,
+}
+
+/// A command of the coordinator to its agents
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum ClusterControl {
+    /// stop the workers, the connection is kept
+    Pause,
+    Resume,
+    Reconnect,
+    Retune,
+    Threads {
+        threads: u64,
+    },
+    Profile {
+        profile: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Share {
     pub miner_id: String,
     pub job_id: String,
     pub nonce: String,
     pub hash: String,
+    /// pool difficulty of the job the share was found for
+    pub difficulty: u64,
 }
 
-#[derive(Debug, Clone)]
+/// Algorithm of the pools without an `algorithm`
+pub const DEFAULT_ALGORITHM: &str = "rx/0";
+
+/// Connection settings of one pool, the primary and each backup pool have their own
+#[derive(Debug, Clone, PartialEq)]
 pub struct PoolConfig {
     pub pool_address: String,
     pub wallet_address: String,
     pub pool_password: String,
+    /// sent as `rigid` on login, empty to omit it
+    pub rig_id: String,
+    /// send keepalived requests every 60 seconds
+    pub keepalive: bool,
+    /// the pool reserves the last nonce byte (NiceHash and some proxies)
+    pub nicehash: bool,
+    pub tls: bool,
+    /// host:port of a SOCKS5 proxy, empty for a direct connection
+    pub proxy: String,
+    pub algorithm: String,
+    /// `pool_address` is the JSON-RPC of a monerod, the blocks are mined solo for the
+    /// wallet
+    pub daemon: bool,
+    /// host:port of the ZMQ publisher of the daemon, empty to only poll it
+    pub zmq: String,
+    /// the block templates of the daemon
+    pub solo: SoloConfig,
+    /// login extensions of mithril proxies (`binary`, `fleet`), set for the agents of a
+    /// cluster
+    pub extensions: Vec<String>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            pool_address: String::new(),
+            wallet_address: String::new(),
+            pool_password: String::new(),
+            rig_id: String::new(),
+            keepalive: true,
+            nicehash: false,
+            tls: false,
+            proxy: String::new(),
+            algorithm: DEFAULT_ALGORITHM.to_string(),
+            daemon: false,
+            zmq: String::new(),
+            solo: SoloConfig::default(),
+            extensions: Vec::new(),
+        }
+    }
+}
+
+/// Zeroes the credentials of every copy, i.e. of the configs replaced by a failover or
+/// a reload
+impl Drop for PoolConfig {
+    fn drop(&mut self) {
+        wipe_string(&mut self.wallet_address);
+        wipe_string(&mut self.pool_password);
+    }
+}
+
+/// How a solo pool (`daemon = true`) refreshes the block templates of the daemon
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoloConfig {
+    /// how often the daemon is asked for a new template, at most every 60 seconds if
+    /// its ZMQ notifications arrive
+    pub template_refresh_seconds: u64,
+    /// bytes of the coinbase extra the daemon reserves, at most 255
+    pub reserve_size: u64,
+    /// a template with new transactions of the mempool is mined at once, not only one
+    /// of a new block
+    pub mempool_refresh: bool,
+    /// a template is renewed after this many seconds even if it did not change, 0 for
+    /// never
+    pub template_max_age_seconds: u64,
+}
+
+impl Default for SoloConfig {
+    fn default() -> Self {
+        SoloConfig {
+            template_refresh_seconds: 10,
+            reserve_size: 8,
+            mempool_refresh: false,
+            template_max_age_seconds: 0,
+        }
+    }
 }
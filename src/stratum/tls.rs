@@ -0,0 +1,135 @@
+//! TLS of the stratum connection (`tls = true`) with rustls, the pool certificate is
+//! checked against the Mozilla root certificates. The receive and the send thread of the
+//! client each get a handle of the same connection.
+
+extern crate rustls;
+extern crate webpki_roots;
+
+use self::rustls::{ClientConfig, ClientConnection, OwnedTrustAnchor, RootCertStore, ServerName};
+use super::lock;
+use std::io;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// How long the server may take for the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// a TLS record with its header
+const RECORD_BYTES: usize = 16 * 1024 + 256;
+
+pub struct TlsStream {
+    tcp: TcpStream,
+    conn: Arc<Mutex<ClientConnection>>,
+    records: Vec<u8>,
+}
+
+/// The client config with the Mozilla root certificates
+pub fn client_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let mut roots = RootCertStore::empty();
+            roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    anchor.subject,
+                    anchor.spki,
+                    anchor.name_constraints,
+                )
+            }));
+            Arc::new(
+                ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_root_certificates(roots)
+                    .with_no_client_auth(),
+            )
+        })
+        .clone()
+}
+
+/// Runs the handshake over `tcp`, the certificate of the server has to be valid for the
+/// host of `address` (host:port)
+pub fn connect(tcp: TcpStream, address: &str, config: Arc<ClientConfig>) -> io::Result<TlsStream> {
+    let host = host(address);
+    let server_name = ServerName::try_from(host).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is no valid host name for TLS", host),
+        )
+    })?;
+    let mut conn = ClientConnection::new(config, server_name).map_err(io::Error::other)?;
+
+    let read_timeout = tcp.read_timeout()?;
+    tcp.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    while conn.is_handshaking() {
+        conn.complete_io(&mut &tcp)?;
+    }
+    tcp.set_read_timeout(read_timeout)?;
+
+    Ok(TlsStream {
+        tcp,
+        conn: Arc::new(Mutex::new(conn)),
+        records: vec![0; RECORD_BYTES],
+    })
+}
+
+impl TlsStream {
+    /// Another handle of the connection, i.e. for a second thread
+    pub fn try_clone(&self) -> io::Result<TlsStream> {
+        Ok(TlsStream {
+            tcp: self.tcp.try_clone()?,
+            conn: self.conn.clone(),
+            records: vec![0; RECORD_BYTES],
+        })
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match lock(&self.conn).reader().read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                result => return result,
+            }
+
+            //waits without the lock, so that the send thread can write meanwhile
+            let n = (&self.tcp).read(&mut self.records)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut conn = lock(&self.conn);
+            let mut records = &self.records[..n];
+            while !records.is_empty() {
+                conn.read_tls(&mut records)?;
+                conn.process_new_packets()
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            }
+            //alerts and key updates of the connection
+            while conn.wants_write() {
+                conn.write_tls(&mut &self.tcp)?;
+            }
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut conn = lock(&self.conn);
+        let n = conn.writer().write(buf)?;
+        while conn.wants_write() {
+            conn.write_tls(&mut &self.tcp)?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.tcp).flush()
+    }
+}
+
+/// The host of host:port, without the brackets of an IPv6 address
+fn host(address: &str) -> &str {
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
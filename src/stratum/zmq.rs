@@ -0,0 +1,174 @@
+//! A ZeroMQ subscriber for the notifications of a monerod (`--zmq-pub`). It speaks just
+//! enough of ZMTP 3.0 for a SUB socket with the NULL mechanism: the greeting, the READY
+//! command, the subscription and single or multi frame messages.
+
+use std::io;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// The notification of monerod for a new block on the main chain
+pub const CHAIN_MAIN_TOPIC: &str = "json-minimal-chain_main";
+/// The notification of monerod for new transactions in the mempool
+pub const TXPOOL_ADD_TOPIC: &str = "json-minimal-txpool_add";
+const GREETING_BYTES: usize = 64;
+/// Larger messages are a broken or foreign peer
+const MAX_MESSAGE_BYTES: u64 = 1024 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+/// A frame of the connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub more: bool,
+    pub command: bool,
+    pub body: Vec<u8>,
+}
+
+/// The greeting of ZMTP 3.0 with the NULL mechanism
+pub fn greeting(as_server: bool) -> [u8; GREETING_BYTES] {
+    let mut greeting = [0; GREETING_BYTES];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[11] = 0;
+    greeting[12..16].copy_from_slice(b"NULL");
+    greeting[32] = as_server as u8;
+    greeting
+}
+
+/// The READY command with the socket type
+pub fn ready(socket_type: &str) -> Frame {
+    let mut body = vec![5];
+    body.extend_from_slice(b"READY");
+    body.push(11);
+    body.extend_from_slice(b"Socket-Type");
+    body.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+    body.extend_from_slice(socket_type.as_bytes());
+    Frame {
+        more: false,
+        command: true,
+        body,
+    }
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, frame: &Frame) -> io::Result<()> {
+    let mut flags = 0;
+    if frame.more {
+        flags |= FLAG_MORE;
+    }
+    if frame.command {
+        flags |= FLAG_COMMAND;
+    }
+    if frame.body.len() > u8::MAX as usize {
+        writer.write_all(&[flags | FLAG_LONG])?;
+        writer.write_all(&(frame.body.len() as u64).to_be_bytes())?;
+    } else {
+        writer.write_all(&[flags, frame.body.len() as u8])?;
+    }
+    writer.write_all(&frame.body)?;
+    writer.flush()
+}
+
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Frame> {
+    let mut flags = [0];
+    reader.read_exact(&mut flags)?;
+    let len = if flags[0] & FLAG_LONG != 0 {
+        let mut len = [0; 8];
+        reader.read_exact(&mut len)?;
+        u64::from_be_bytes(len)
+    } else {
+        let mut len = [0];
+        reader.read_exact(&mut len)?;
+        u64::from(len[0])
+    };
+    if len > MAX_MESSAGE_BYTES {
+        return Err(invalid_data(&format!("frame of {} bytes", len)));
+    }
+    let mut body = vec![0; len as usize];
+    reader.read_exact(&mut body)?;
+    Ok(Frame {
+        more: flags[0] & FLAG_MORE != 0,
+        command: flags[0] & FLAG_COMMAND != 0,
+        body,
+    })
+}
+
+/// A SUB socket connected to one publisher
+pub struct Subscriber {
+    stream: TcpStream,
+}
+
+impl Subscriber {
+    /// Connects to the publisher at `address` (host:port) and subscribes to the topics
+    pub fn connect(address: &str, topics: &[&str]) -> io::Result<Subscriber> {
+        let addr = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| invalid_data(&format!("{} has no address", address)))?;
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+        stream.write_all(&greeting(false))?;
+        let mut peer = [0; GREETING_BYTES];
+        stream.read_exact(&mut peer)?;
+        if peer[0] != 0xff || peer[9] != 0x7f || peer[10] < 3 {
+            return Err(invalid_data("not a ZMTP 3 publisher"));
+        }
+        if &peer[12..16] != b"NULL" {
+            return Err(invalid_data("the publisher needs a security mechanism"));
+        }
+        write_frame(&mut stream, &ready("SUB"))?;
+        let peer_ready = read_frame(&mut stream)?;
+        if !peer_ready.command || !peer_ready.body.starts_with(b"\x05READY") {
+            return Err(invalid_data("no READY from the publisher"));
+        }
+        for topic in topics {
+            let mut subscribe = vec![1];
+            subscribe.extend_from_slice(topic.as_bytes());
+            write_frame(
+                &mut stream,
+                &Frame {
+                    more: false,
+                    command: false,
+                    body: subscribe,
+                },
+            )?;
+        }
+        //notifications come when blocks are found
+        stream.set_read_timeout(None)?;
+        Ok(Subscriber { stream })
+    }
+
+    /// The next message with its frames joined, commands like PING are skipped
+    pub fn recv(&mut self) -> io::Result<Vec<u8>> {
+        let mut message = Vec::new();
+        loop {
+            let frame = read_frame(&mut self.stream)?;
+            if frame.command {
+                continue;
+            }
+            message.extend_from_slice(&frame.body);
+            if !frame.more {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// A second handle of the connection, its `shutdown` ends a blocked `recv`
+    pub fn try_clone(&self) -> io::Result<Subscriber> {
+        Ok(Subscriber {
+            stream: self.stream.try_clone()?,
+        })
+    }
+
+    pub fn shutdown(&self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,261 @@
+extern crate crossbeam_channel;
+
+use self::crossbeam_channel::{unbounded, Sender};
+use crate::bench::{cpu_info, nonce_input, verify_test_vector, CpuInfo, SEED};
+use crate::cgroup;
+use crate::console::format_duration;
+use crate::randomx::memory::VmMemory;
+use crate::randomx::vm::new_vm;
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq)]
+pub struct StressConfig {
+    pub threads: u64,
+    pub duration: Duration,
+    /// every interval the hashrate is sampled and one hash per thread is verified
+    pub interval: Duration,
+}
+
+/// A hash of the full dataset VM that differs from the light mode VM
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub thread: u64,
+    pub nonce: u64,
+    /// time since the start of the hashing
+    pub after: Duration,
+    /// the hash of the light mode VM
+    pub expected: String,
+    pub actual: String,
+}
+
+#[derive(Debug)]
+pub struct StressReport {
+    pub cpu: CpuInfo,
+    pub threads: u64,
+    pub init_time: Duration,
+    pub duration: Duration,
+    /// the test vector hash was correct
+    pub verified: bool,
+    /// hashrate of each interval
+    pub hashrates: Vec<f64>,
+    pub checked: u64,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl StressReport {
+    pub fn passed(&self) -> bool {
+        self.verified && self.mismatches.is_empty()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Stability {
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    /// standard deviation relative to the mean
+    pub variation: f64,
+}
+
+/// A hash of a worker thread, sent to the checker
+struct Sample {
+    thread: u64,
+    nonce: u64,
+    hash: String,
+}
+
+/// Hashes with the full dataset for the configured duration. Every interval the last
+/// hash of each thread is recomputed with a light mode VM, which computes the dataset
+/// items on the fly and so does not depend on the dataset in RAM. A progress line is
+/// printed after each interval.
+pub fn run(conf: &StressConfig) -> StressReport {
+    let init_start = Instant::now();
+    let memory = Arc::new(VmMemory::full(SEED));
+    memory.init_dataset(cgroup::available_cpus());
+    let init_time = init_start.elapsed();
+    let verified = verify_test_vector(memory.clone());
+
+    let mut checker = new_vm(Arc::new(VmMemory::light(SEED)));
+    let hashes: Vec<AtomicU64> = (0..conf.threads).map(|_| AtomicU64::new(0)).collect();
+    let check_round = AtomicU64::new(0);
+    let stop = AtomicBool::new(false);
+    let (sndr, rcvr) = unbounded();
+
+    let start = Instant::now();
+    let deadline = start + conf.duration;
+    let mut hashrates = Vec::new();
+    let mut checked = 0;
+    let mut mismatches = Vec::new();
+
+    thread::scope(|scope| {
+        for thread in 0..conf.threads {
+            let worker = Worker {
+                memory: memory.clone(),
+                thread,
+                threads: conf.threads,
+                hashes: &hashes[thread as usize],
+                check_round: &check_round,
+                stop: &stop,
+                sndr: sndr.clone(),
+            };
+            scope.spawn(move || worker.hash());
+        }
+
+        let mut last_total = 0;
+        let mut last_sample = start;
+        while last_sample < deadline {
+            thread::sleep(conf.interval.min(deadline - last_sample));
+            let now = Instant::now();
+            let total: u64 = hashes.iter().map(|h| h.load(Ordering::Relaxed)).sum();
+            hashrates.push((total - last_total) as f64 / (now - last_sample).as_secs_f64());
+
+            check_round.fetch_add(1, Ordering::Relaxed);
+            for _ in 0..conf.threads {
+                let sample = rcvr.recv().expect("stress sample");
+                let expected = checker.calculate_hash(&nonce_input(sample.nonce)).to_hex();
+                checked += 1;
+                if expected.as_str() != sample.hash {
+                    mismatches.push(Mismatch {
+                        thread: sample.thread,
+                        nonce: sample.nonce,
+                        after: now - start,
+                        expected: expected.to_string(),
+                        actual: sample.hash,
+                    });
+                }
+            }
+            println!(
+                "{} {:.1} H/s, {} hashes checked, {} mismatches",
+                format_duration(now - start),
+                hashrates[hashrates.len() - 1],
+                checked,
+                mismatches.len()
+            );
+            //the checks slow down the workers, the next interval starts after them
+            last_total = hashes.iter().map(|h| h.load(Ordering::Relaxed)).sum();
+            last_sample = Instant::now();
+        }
+        stop.store(true, Ordering::Relaxed);
+    });
+
+    StressReport {
+        cpu: cpu_info(),
+        threads: conf.threads,
+        init_time,
+        duration: start.elapsed(),
+        verified,
+        hashrates,
+        checked,
+        mismatches,
+    }
+}
+
+struct Worker<'a> {
+    memory: Arc<VmMemory>,
+    thread: u64,
+    threads: u64,
+    hashes: &'a AtomicU64,
+    check_round: &'a AtomicU64,
+    stop: &'a AtomicBool,
+    sndr: Sender<Sample>,
+}
+
+impl Worker<'_> {
+    /// Hashes the nonces `thread`, `thread + threads`, ... and sends the last hash to
+    /// the checker whenever a new check round starts
+    fn hash(self) {
+        let mut vm = new_vm(self.memory.clone());
+        let mut nonce = self.thread;
+        let mut round = 0;
+        while !self.stop.load(Ordering::Relaxed) {
+            let hash = vm.calculate_hash(&nonce_input(nonce));
+            self.hashes.fetch_add(1, Ordering::Relaxed);
+
+            let current_round = self.check_round.load(Ordering::Relaxed);
+            if current_round != round {
+                round = current_round;
+                let sample = Sample {
+                    thread: self.thread,
+                    nonce,
+                    hash: hash.to_hex().to_string(),
+                };
+                if self.sndr.send(sample).is_err() {
+                    return;
+                }
+            }
+            nonce += self.threads;
+        }
+    }
+}
+
+/// None if there are no hashrate samples
+pub fn stability(hashrates: &[f64]) -> Option<Stability> {
+    if hashrates.is_empty() {
+        return None;
+    }
+    let n = hashrates.len() as f64;
+    let mean = hashrates.iter().sum::<f64>() / n;
+    let variance = hashrates.iter().map(|h| (h - mean).powi(2)).sum::<f64>() / n;
+    Some(Stability {
+        mean,
+        min: hashrates.iter().cloned().fold(f64::INFINITY, f64::min),
+        max: hashrates.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        variation: if mean > 0.0 {
+            variance.sqrt() / mean
+        } else {
+            0.0
+        },
+    })
+}
+
+pub fn format_report(report: &StressReport) -> String {
+    let cpu = &report.cpu;
+    let mut lines = vec![
+        format!("mithril {} stress test", env!("CARGO_PKG_VERSION")),
+        format!(
+            "cpu          {} ({} cores, {} threads)",
+            cpu.model, cpu.physical_cores, cpu.logical_cores
+        ),
+        format!("init time    {:.1}s", report.init_time.as_secs_f64()),
+        format!("threads      {}", report.threads),
+        format!("duration     {}", format_duration(report.duration)),
+    ];
+    lines.push(match stability(&report.hashrates) {
+        Some(s) => format!(
+            "hashrate     {:.1} H/s (min {:.1}, max {:.1}, variation {:.2}%)",
+            s.mean,
+            s.min,
+            s.max,
+            s.variation * 100.0
+        ),
+        None => "hashrate     n/a".to_string(),
+    });
+    lines.push(format!(
+        "checked      {} hashes, {} mismatches",
+        report.checked,
+        report.mismatches.len()
+    ));
+    for m in &report.mismatches {
+        lines.push(format!(
+            "  MISMATCH after {} thread {} nonce {}: expected {}, got {}",
+            format_duration(m.after),
+            m.thread,
+            m.nonce,
+            m.expected,
+            m.actual
+        ));
+    }
+    if !report.verified {
+        lines.push("verification FAILED, the test vector hash is wrong".to_string());
+    }
+    lines.push(if report.passed() {
+        "PASSED".to_string()
+    } else {
+        "FAILED, hashing is unreliable on this machine (RAM timings, overclock?)".to_string()
+    });
+    lines.join("\n")
+}
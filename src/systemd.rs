@@ -0,0 +1,117 @@
+use std::env;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Sends state changes to systemd (`sd_notify`), does nothing if mithril was not
+/// started by a `Type=notify` unit
+pub struct Notifier {
+    /// `NOTIFY_SOCKET`, abstract sockets start with `@`
+    socket: Option<String>,
+    watchdog_interval: Option<Duration>,
+    ready: AtomicBool,
+}
+
+impl Notifier {
+    pub fn new(socket: Option<String>, watchdog_interval: Option<Duration>) -> Notifier {
+        Notifier {
+            socket,
+            watchdog_interval,
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    pub fn from_env() -> Notifier {
+        let watchdog_interval = watchdog_interval(
+            env::var("WATCHDOG_USEC").ok().as_deref(),
+            env::var("WATCHDOG_PID").ok().as_deref(),
+            std::process::id(),
+        );
+        Notifier::new(
+            env::var("NOTIFY_SOCKET").ok().filter(|s| !s.is_empty()),
+            watchdog_interval,
+        )
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    /// How often the watchdog has to be notified, None if the watchdog is off
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.socket.as_ref().and(self.watchdog_interval)
+    }
+
+    /// Tells systemd that mithril is up, only the first call sends `READY=1`
+    pub fn ready(&self, status: &str) {
+        if self.ready.swap(true, Ordering::SeqCst) {
+            self.status(status);
+        } else {
+            self.notify(&format!("READY=1\nSTATUS={}", status));
+        }
+    }
+
+    /// The status line that `systemctl status` shows
+    pub fn status(&self, status: &str) {
+        self.notify(&format!("STATUS={}", status));
+    }
+
+    pub fn watchdog(&self) {
+        self.notify("WATCHDOG=1");
+    }
+
+    /// Tells systemd that mithril is shutting down
+    pub fn stopping(&self) {
+        self.notify("STOPPING=1");
+    }
+
+    fn notify(&self, state: &str) {
+        if let Some(socket) = &self.socket {
+            if let Err(err) = send(socket, state) {
+                warn!("could not notify systemd on {}: {}", socket, err);
+            }
+        }
+    }
+}
+
+/// Half of `WATCHDOG_USEC` as recommended by `sd_watchdog_enabled`. None if the
+/// watchdog is off or meant for another process (`WATCHDOG_PID`).
+pub fn watchdog_interval(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok()? != own_pid {
+            return None;
+        }
+    }
+    let usec = usec?.parse::<u64>().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec) / 2)
+}
+
+#[cfg(target_os = "linux")]
+fn send(socket: &str, state: &str) -> io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let sock = UnixDatagram::unbound()?;
+    match socket.strip_prefix('@') {
+        Some(name) => {
+            let addr = SocketAddr::from_abstract_name(name)?;
+            sock.send_to_addr(state.as_bytes(), &addr)?
+        }
+        None => sock.send_to(state.as_bytes(), socket)?,
+    };
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn send(socket: &str, state: &str) -> io::Result<()> {
+    std::os::unix::net::UnixDatagram::unbound()?.send_to(state.as_bytes(), socket)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &str, _state: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "systemd notifications need unix sockets",
+    ))
+}
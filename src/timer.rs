@@ -29,7 +29,7 @@ pub fn interval_mod_setup(
         60 * worker_conf.auto_tune_interval_minutes
     } else {
         info!("auto_tune disabled");
-        std::u64::MAX
+        u64::MAX
     };
     let donation_mod = if donation_conf.percentage >= DONATION_THRESHOLD {
         if donation_conf.percentage >= 100.0 {
@@ -76,7 +76,10 @@ pub fn setup(worker_conf: &WorkerConfig, donation_conf: &DonationConfig) -> Rece
                     reg_interval
                 };
 
-                clock_sndr.send(action).expect("sending clock signal");
+                //the miner replaced the clock after a config change
+                if clock_sndr.send(action).is_err() {
+                    break;
+                }
                 arm_changes += 1;
             }
         })
@@ -0,0 +1,209 @@
+//! Timed spans of the job, dataset init, hashing and share submit pipeline. The spans are
+//! `tracing` spans, the `SpanLayer` times them and logs the duration when a span closes,
+//! as a debug record of the target `mithril::span` with the event `span`. The JSON log
+//! gets the span name, the parent span, `duration_us` and the fields, enabled with
+//! `levels = { span = "debug" }`. With an export sink the finished spans are also sent
+//! there, for the OTLP exporter.
+
+extern crate crossbeam_channel;
+extern crate tracing;
+extern crate tracing_subscriber;
+
+use self::crossbeam_channel::Sender;
+use self::tracing::field::Visit;
+use self::tracing::span::{Attributes, Id, Record as SpanValues};
+use self::tracing::Subscriber;
+use self::tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use self::tracing_subscriber::registry::{LookupSpan, Registry};
+use log::kv::{ToValue, Value};
+use log::{Level, Record};
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
+
+const TARGET: &str = "mithril::span";
+
+static SINK: OnceLock<Sender<SpanRecord>> = OnceLock::new();
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A finished span for the export. The spans that start without a parent span begin a
+/// trace, the spans within it share the trace id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanRecord {
+    pub name: &'static str,
+    pub trace_id: u128,
+    pub span_id: u64,
+    pub parent_id: Option<u64>,
+    pub start: SystemTime,
+    pub duration: Duration,
+    pub fields: Vec<(&'static str, Field)>,
+}
+
+/// Sends all spans that finish from now on to the sink, only the first call counts
+pub fn export_to(sink: Sender<SpanRecord>) {
+    let _ = SINK.set(sink);
+}
+
+/// Installs the `SpanLayer` as the global subscriber, only the first call counts
+pub fn init() {
+    let _ = tracing::subscriber::set_global_default(Registry::default().with(SpanLayer::new(None)));
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Str(String),
+    U64(u64),
+}
+
+impl ToValue for Field {
+    fn to_value(&self) -> Value<'_> {
+        match self {
+            Field::Str(value) => Value::from(value.as_str()),
+            Field::U64(value) => Value::from(*value),
+        }
+    }
+}
+
+/// Times the spans, see the module documentation
+pub struct SpanLayer {
+    sink: Option<Sender<SpanRecord>>,
+}
+
+impl SpanLayer {
+    /// None sends the finished spans to the sink of `export_to`
+    pub fn new(sink: Option<Sender<SpanRecord>>) -> SpanLayer {
+        SpanLayer { sink }
+    }
+}
+
+/// Kept in the extensions of an open span
+struct Timing {
+    parent: Option<(&'static str, u64)>,
+    trace_id: u128,
+    span_id: u64,
+    fields: Vec<(&'static str, Field)>,
+    start: Instant,
+    start_time: SystemTime,
+}
+
+impl<S> Layer<S> for SpanLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        let parent = span.parent().and_then(|parent| {
+            let extensions = parent.extensions();
+            let timing = extensions.get::<Timing>()?;
+            Some((parent.name(), timing.span_id, timing.trace_id))
+        });
+        let mut fields = Vec::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        span.extensions_mut().insert(Timing {
+            parent: parent.map(|(name, span_id, _)| (name, span_id)),
+            trace_id: match parent {
+                Some((_, _, trace_id)) => trace_id,
+                None => (random_id() as u128) << 64 | random_id() as u128,
+            },
+            span_id: random_id(),
+            fields,
+            start: Instant::now(),
+            start_time: SystemTime::now(),
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &SpanValues<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<Timing>() {
+                values.record(&mut FieldVisitor(&mut timing.fields));
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(&id) {
+            Some(span) => span,
+            None => return,
+        };
+        let timing = match span.extensions_mut().remove::<Timing>() {
+            Some(timing) => timing,
+            None => return,
+        };
+        let name = span.name();
+        let duration = timing.start.elapsed();
+        if let Some(sink) = self.sink.as_ref().or(SINK.get()) {
+            //the exporter may be gone, the span is dropped then
+            let _ = sink.send(SpanRecord {
+                name,
+                trace_id: timing.trace_id,
+                span_id: timing.span_id,
+                parent_id: timing.parent.map(|(_, span_id)| span_id),
+                start: timing.start_time,
+                duration,
+                fields: timing.fields.clone(),
+            });
+        }
+        if !log_enabled!(target: TARGET, Level::Debug) {
+            return;
+        }
+        let duration_us = duration.as_micros() as u64;
+        let mut kvs: Vec<(&str, Value)> = vec![
+            ("event", Value::from("span")),
+            ("span", Value::from(name)),
+            ("duration_us", Value::from(duration_us)),
+        ];
+        if let Some((parent, _)) = timing.parent {
+            kvs.push(("parent", Value::from(parent)));
+        }
+        kvs.extend(timing.fields.iter().map(|(k, v)| (*k, v.to_value())));
+        let kvs: &[(&str, Value)] = &kvs;
+        log::logger().log(
+            &Record::builder()
+                .args(format_args!("{} took {:?}", name, duration))
+                .level(Level::Debug)
+                .target(TARGET)
+                .module_path_static(Some(module_path!()))
+                .key_values(&kvs)
+                .build(),
+        );
+    }
+}
+
+/// Sets the fields of a span, a field that is recorded again is replaced
+struct FieldVisitor<'a>(&'a mut Vec<(&'static str, Field)>);
+
+impl FieldVisitor<'_> {
+    fn set(&mut self, key: &'static str, value: Field) {
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some(field) => field.1 = value,
+            None => self.0.push((key, value)),
+        }
+    }
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.set(field.name(), Field::U64(value));
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.set(field.name(), Field::Str(value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+        self.set(field.name(), Field::Str(format!("{:?}", value)));
+    }
+}
+
+/// A non-zero id that does not repeat within the process
+fn random_id() -> u64 {
+    let n = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    RandomState::new().hash_one(n).max(1)
+}
@@ -0,0 +1,273 @@
+extern crate crossbeam_channel;
+extern crate ratatui;
+
+use self::crossbeam_channel::Sender;
+use self::ratatui::crossterm::event;
+use self::ratatui::crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use self::ratatui::layout::{Constraint, Layout};
+use self::ratatui::style::{Color, Style};
+use self::ratatui::text::Line;
+use self::ratatui::widgets::{Block, List, ListItem, Paragraph, Row, Sparkline, Table};
+use self::ratatui::{DefaultTerminal, Frame};
+use crate::console::{
+    control_cmd, format_duration, format_hashrate, format_latency, format_percentage, parse_key,
+    ConsoleCmd,
+};
+use crate::control::ControlCmd;
+use crate::metric::history::{HashrateHistory, ThreadHistory};
+use crate::metric::stats::{MinerStats, ShareOutcome};
+use crate::signals;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of per second hashrate samples shown in the sparkline
+const SPARKLINE_SAMPLES: usize = 300;
+const THREAD_HASHRATE_WINDOW: Duration = Duration::from_secs(10);
+
+static STOPPED: Mutex<bool> = Mutex::new(false);
+
+/// Starts the full-screen dashboard. Key presses are handled like the console hotkeys,
+/// `q` shuts the miner down.
+pub fn start(stats: Arc<MinerStats>, auto_tune: bool, sndr: Sender<ControlCmd>) {
+    thread::Builder::new()
+        .name("tui thread".to_string())
+        .spawn(move || {
+            let terminal = ratatui::init();
+            run(terminal, &stats, &sndr, auto_tune);
+            stop();
+            if !signals::shutdown_requested() {
+                signals::request_shutdown(&sndr);
+            }
+        })
+        .expect("tui thread handle");
+}
+
+/// Restores the terminal, the dashboard is not drawn anymore afterwards
+pub fn stop() {
+    if let Ok(mut stopped) = STOPPED.lock() {
+        if !*stopped {
+            *stopped = true;
+            ratatui::restore();
+        }
+    }
+}
+
+struct Dashboard {
+    hashrates: VecDeque<u64>,
+    history: HashrateHistory,
+    threads: ThreadHistory,
+    status: String,
+}
+
+fn run(
+    mut terminal: DefaultTerminal,
+    stats: &MinerStats,
+    sndr: &Sender<ControlCmd>,
+    auto_tune: bool,
+) {
+    let mut dashboard = Dashboard {
+        hashrates: VecDeque::with_capacity(SPARKLINE_SAMPLES),
+        history: HashrateHistory::new(Duration::from_secs(15 * 60)),
+        threads: ThreadHistory::new(THREAD_HASHRATE_WINDOW),
+        status: String::new(),
+    };
+    let mut last_sample: Option<Instant> = None;
+
+    loop {
+        if last_sample.is_none_or(|at| at.elapsed() >= REFRESH_INTERVAL) {
+            dashboard.sample(stats);
+            last_sample = Some(Instant::now());
+        }
+        {
+            //do not draw over the restored terminal
+            let stopped = STOPPED.lock().expect("tui stopped lock");
+            if *stopped {
+                return;
+            }
+            if let Err(err) = terminal.draw(|frame| draw(frame, stats, &dashboard)) {
+                error!("drawing the tui failed: {}", err);
+                return;
+            }
+        }
+
+        if !event::poll(REFRESH_INTERVAL).unwrap_or(false) {
+            continue;
+        }
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        let ch = match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return,
+            KeyCode::Char('q') | KeyCode::Esc => return,
+            KeyCode::Char(ch) if ch.is_ascii() => ch as u8,
+            _ => continue,
+        };
+        let cmd = match parse_key(ch) {
+            Some(cmd) => cmd,
+            None => continue,
+        };
+        dashboard.status = match cmd {
+            ConsoleCmd::TogglePause if stats.paused() => "resuming",
+            ConsoleCmd::TogglePause => "paused",
+            ConsoleCmd::Reconnect => "reconnecting",
+            ConsoleCmd::Retune if !auto_tune => {
+                dashboard.status = "auto_tune is disabled".to_string();
+                continue;
+            }
+            ConsoleCmd::Retune => "drawing new auto tuning arm",
+            ConsoleCmd::NextProfile => "switching to the next profile",
+            _ => continue, //everything is on screen already
+        }
+        .to_string();
+        if let Some(control) = control_cmd(cmd) {
+            let _ = sndr.send(control);
+        }
+    }
+}
+
+impl Dashboard {
+    fn sample(&mut self, stats: &MinerStats) {
+        let now = Instant::now();
+        self.history.record(now, stats.snapshot().total_hashes);
+        let hashrate = self.history.hashrate(REFRESH_INTERVAL).unwrap_or(0.0);
+        if self.hashrates.len() == SPARKLINE_SAMPLES {
+            self.hashrates.pop_front();
+        }
+        self.hashrates.push_back(hashrate.round() as u64);
+        self.threads.record(now, stats.thread_hashes());
+    }
+}
+
+fn draw(frame: &mut Frame, stats: &MinerStats, dashboard: &Dashboard) {
+    let [header_area, sparkline_area, tables_area, footer_area] = Layout::vertical([
+        Constraint::Length(4),
+        Constraint::Length(8),
+        Constraint::Min(6),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [threads_area, shares_area] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .areas(tables_area);
+
+    frame.render_widget(header(stats, dashboard), header_area);
+
+    let width = sparkline_area.width.saturating_sub(2) as usize;
+    let skip = dashboard.hashrates.len().saturating_sub(width);
+    let data: Vec<u64> = dashboard.hashrates.iter().skip(skip).cloned().collect();
+    let max = data.iter().cloned().max().unwrap_or(0);
+    frame.render_widget(
+        Sparkline::default()
+            .block(Block::bordered().title(format!("hashrate (max {} H/s)", max)))
+            .data(&data)
+            .style(Style::default().fg(Color::Green)),
+        sparkline_area,
+    );
+
+    let thread_rows: Vec<Row> = dashboard
+        .threads
+        .hashrates()
+        .iter()
+        .enumerate()
+        .map(|(ix, (hashes, rate))| {
+            Row::new(vec![
+                format!("{}", ix),
+                format_hashrate(*rate),
+                format!("{}", hashes),
+            ])
+        })
+        .collect();
+    frame.render_widget(
+        Table::new(
+            thread_rows,
+            [
+                Constraint::Length(6),
+                Constraint::Length(14),
+                Constraint::Min(10),
+            ],
+        )
+        .header(
+            Row::new(vec!["thread", "hashrate", "hashes"])
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .block(Block::bordered().title("threads")),
+        threads_area,
+    );
+
+    let share_items: Vec<ListItem> = stats
+        .recent_shares()
+        .iter()
+        .map(|share| {
+            let ago = SystemTime::now()
+                .duration_since(share.time)
+                .unwrap_or(Duration::ZERO);
+            let (text, color) = match &share.outcome {
+                ShareOutcome::Accepted { effort } => (
+                    format!(
+                        "{} ago  accepted  diff {}  effort {}",
+                        format_duration(ago),
+                        share.difficulty,
+                        effort
+                            .map(|e| format!("{:.1}%", e * 100.0))
+                            .unwrap_or_else(|| "n/a".to_string())
+                    ),
+                    Color::Green,
+                ),
+                ShareOutcome::Rejected { err } => (
+                    format!("{} ago  rejected  {}", format_duration(ago), err),
+                    Color::Red,
+                ),
+            };
+            ListItem::new(text).style(Style::default().fg(color))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(share_items).block(Block::bordered().title("recent shares")),
+        shares_area,
+    );
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            " q quit  p pause/resume  r reconnect  t re-tune  c profile   {}",
+            dashboard.status
+        )),
+        footer_area,
+    );
+}
+
+fn header<'a>(stats: &MinerStats, dashboard: &Dashboard) -> Paragraph<'a> {
+    let snapshot = stats.snapshot();
+    let connection = match stats.connection() {
+        Some((pool, connected_for)) => {
+            format!("{} (connected {})", pool, format_duration(connected_for))
+        }
+        None if stats.paused() => "paused".to_string(),
+        None => "not connected".to_string(),
+    };
+    let hashrates = format!(
+        "hashrate 10s {}  60s {}  15m {}",
+        format_hashrate(dashboard.history.hashrate(Duration::from_secs(10))),
+        format_hashrate(dashboard.history.hashrate(Duration::from_secs(60))),
+        format_hashrate(dashboard.history.hashrate(Duration::from_secs(15 * 60)))
+    );
+    let shares = format!(
+        "shares {} accepted / {} rejected  difficulty {}  effort {}  luck {}  latency p50 {} p99 {}",
+        snapshot.shares_accepted,
+        snapshot.shares_rejected,
+        snapshot.difficulty,
+        format_percentage(snapshot.current_effort()),
+        format_percentage(snapshot.luck()),
+        format_latency(snapshot.share_latency_p50),
+        format_latency(snapshot.share_latency_p99)
+    );
+    Paragraph::new(vec![
+        Line::from(format!("pool {}", connection)),
+        Line::from(hashrates),
+    ])
+    .block(Block::bordered().title(" mithril ").title_bottom(shares))
+}
@@ -0,0 +1,220 @@
+//! `mithril tune`: the offline counterpart of the auto tuning. Benchmarks every thread
+//! count with and without thread affinity and with the scratchpads on huge pages or on
+//! normal pages, each for a fixed time, and ranks them. The dataset is initialised once
+//! and shared by all candidates, like in the benchmark.
+
+use crate::bench::{self, SEED};
+use crate::cgroup;
+use crate::config_migration;
+use crate::mithril_config::ConfigFormat;
+use crate::platform;
+use crate::randomx::large_alloc::{self, Allocators, Malloc};
+use crate::randomx::memory::VmMemory;
+use crate::randomx::vm::new_vm;
+use crate::worker::worker_pool::affinity_core;
+use serde_json::json;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuneConfig {
+    /// the thread counts that are tried
+    pub threads: Vec<u64>,
+    /// hashing time of each candidate
+    pub duration: Duration,
+}
+
+/// One combination of the settings
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub threads: u64,
+    pub affinity: bool,
+    /// the scratchpads are allocated from reserved huge pages (`memory.allocator =
+    /// "default"`), else from the heap (`"malloc"`)
+    pub huge_pages: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TuneResult {
+    #[serde(flatten)]
+    pub candidate: Candidate,
+    pub hashrate: f64,
+    /// all scratchpads got huge pages, false if none are reserved
+    pub large_pages: bool,
+}
+
+/// Half the physical cores, the physical cores and all logical cores, the most common
+/// optima of RandomX, limited by the cgroup
+pub fn default_thread_counts(physical_cores: usize, logical_cores: usize) -> Vec<u64> {
+    let mut counts: Vec<u64> = [physical_cores / 2, physical_cores, logical_cores]
+        .iter()
+        .map(|count| (*count).clamp(1, logical_cores.max(1)) as u64)
+        .collect();
+    counts.sort_unstable();
+    counts.dedup();
+    counts
+}
+
+/// Every combination of the thread counts, the affinity and the huge pages
+pub fn candidates(threads: &[u64]) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    for &threads in threads {
+        for affinity in [false, true] {
+            for huge_pages in [true, false] {
+                candidates.push(Candidate {
+                    threads,
+                    affinity,
+                    huge_pages,
+                });
+            }
+        }
+    }
+    candidates
+}
+
+/// Benchmarks the candidates one after the other, `progress` is called with each result.
+/// Returns the results ranked by hashrate.
+pub fn run(conf: &TuneConfig, mut progress: impl FnMut(&TuneResult)) -> Vec<TuneResult> {
+    let memory = Arc::new(VmMemory::full(SEED));
+    memory.init_dataset(cgroup::available_cpus());
+    let mut results = Vec::new();
+    for candidate in candidates(&conf.threads) {
+        let result = run_candidate(&memory, candidate, conf.duration);
+        progress(&result);
+        results.push(result);
+    }
+    large_alloc::set_allocators(Allocators::default());
+    rank(&mut results);
+    results
+}
+
+fn run_candidate(memory: &Arc<VmMemory>, candidate: Candidate, duration: Duration) -> TuneResult {
+    //the scratchpads are allocated by the VMs of the threads
+    large_alloc::set_allocators(if candidate.huge_pages {
+        Allocators::default()
+    } else {
+        Allocators::all(Arc::new(Malloc))
+    });
+    let cores = num_cpus::get();
+    let deadline = Instant::now() + duration;
+    let start = Instant::now();
+    let threads: Vec<(u64, bool)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..candidate.threads)
+            .map(|thread| {
+                let memory = memory.clone();
+                scope.spawn(move || {
+                    if candidate.affinity {
+                        let core = affinity_core(thread as usize, cores);
+                        if let Err(err) = platform::set_thread_affinity(core) {
+                            warn!(
+                                "pinning tune thread {} to core {} failed: {}",
+                                thread, core, err
+                            );
+                        }
+                    }
+                    let mut vm = new_vm(memory);
+                    let large_pages = vm.scratchpad.large_pages();
+                    let mut nonce = thread;
+                    let mut hashes = 0;
+                    while Instant::now() < deadline {
+                        vm.calculate_hash(&bench::nonce_input(nonce));
+                        nonce += candidate.threads;
+                        hashes += 1;
+                    }
+                    (hashes, large_pages)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("tune thread"))
+            .collect()
+    });
+    let hashes: u64 = threads.iter().map(|(hashes, _)| hashes).sum();
+    TuneResult {
+        candidate,
+        hashrate: hashes as f64 / start.elapsed().as_secs_f64(),
+        large_pages: threads.iter().all(|(_, large_pages)| *large_pages),
+    }
+}
+
+/// Sorts by hashrate, the fastest first. Of equally fast candidates the one with fewer
+/// threads comes first.
+pub fn rank(results: &mut [TuneResult]) {
+    results.sort_by(|a, b| {
+        b.hashrate
+            .total_cmp(&a.hashrate)
+            .then(a.candidate.threads.cmp(&b.candidate.threads))
+    });
+}
+
+/// The line of a candidate, also printed while tuning
+pub fn format_result(result: &TuneResult) -> String {
+    let candidate = &result.candidate;
+    let huge_pages = match (candidate.huge_pages, result.large_pages) {
+        (true, true) => "yes",
+        (true, false) => "unavailable",
+        (false, _) => "no",
+    };
+    format!(
+        "{:>7}  {:<8}  {:<11}  {:>10.1} H/s",
+        candidate.threads,
+        if candidate.affinity { "pinned" } else { "any" },
+        huge_pages,
+        result.hashrate
+    )
+}
+
+/// The ranked results as a table
+pub fn format_table(results: &[TuneResult]) -> String {
+    let mut lines = vec![format!(
+        "{:>4}  {:>7}  {:<8}  {:<11}  {:>14}",
+        "rank", "threads", "affinity", "huge pages", "hashrate"
+    )];
+    for (ix, result) in results.iter().enumerate() {
+        lines.push(format!("{:>4}  {}", ix + 1, format_result(result)));
+    }
+    lines.join("\n")
+}
+
+/// The ranked results of `tune --json`, `written` is the config file the fastest settings
+/// were written to
+pub fn results_json(results: &[TuneResult], written: Option<&Path>) -> serde_json::Value {
+    json!({
+        "results": results,
+        "written": written.map(|path| path.display().to_string()),
+    })
+}
+
+/// The config keys of the candidate, the auto tuning is disabled to keep its thread count
+pub fn config_values(
+    candidate: &Candidate,
+) -> Vec<(&'static str, &'static str, serde_json::Value)> {
+    vec![
+        ("worker", "num_threads", candidate.threads.into()),
+        ("worker", "auto_tune", false.into()),
+        ("worker", "affinity", candidate.affinity.into()),
+        (
+            "memory",
+            "allocator",
+            if candidate.huge_pages {
+                "default"
+            } else {
+                "malloc"
+            }
+            .into(),
+        ),
+    ]
+}
+
+/// Writes the settings of the candidate into the config file, its comments are kept
+pub fn write_config(
+    path: &Path,
+    format: ConfigFormat,
+    candidate: &Candidate,
+) -> Result<(), String> {
+    config_migration::write_keys(path, format, &config_values(candidate))
+}
@@ -0,0 +1,121 @@
+//! Opt-in check for a newer release. The latest release is fetched over HTTPS at startup
+//! and then every `interval_hours`, a newer version than the running one is logged once.
+
+extern crate ureq;
+
+use crate::signals;
+use serde_json::Value;
+
+use std::thread;
+use std::time::Duration;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_URL: &str = "https://api.github.com/repos/Ragnaroek/mithril/releases/latest";
+/// the version of the running binary
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateCheckConfig {
+    pub enabled: bool,
+    /// returns the latest release as JSON with `tag_name` and `html_url`, like the
+    /// releases API of GitHub
+    pub url: String,
+    pub interval_hours: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Release {
+    /// without a leading `v`
+    pub version: String,
+    /// release notes, empty if the response has none
+    pub url: String,
+}
+
+/// Starts the update check thread. Does nothing if the check is disabled.
+pub fn start(conf: UpdateCheckConfig) {
+    if !conf.enabled {
+        return;
+    }
+    info!("checking {} for new releases", conf.url);
+    thread::Builder::new()
+        .name("update check thread".to_string())
+        .spawn(move || {
+            let agent = ureq::AgentBuilder::new()
+                .timeout(HTTP_TIMEOUT)
+                .user_agent(&format!("mithril/{}", CURRENT_VERSION))
+                .build();
+            let mut notified = None;
+            while !signals::shutdown_requested() {
+                match fetch_release(&agent, &conf.url) {
+                    Ok(release) => {
+                        if is_newer(&release.version, CURRENT_VERSION)
+                            && notified.as_ref() != Some(&release.version)
+                        {
+                            warn!("{}", format_notice(&release));
+                            notified = Some(release.version);
+                        }
+                    }
+                    Err(err) => debug!("update check: {}", err),
+                }
+                thread::sleep(Duration::from_secs(conf.interval_hours * 60 * 60));
+            }
+        })
+        .expect("update check thread handle");
+}
+
+fn fetch_release(agent: &ureq::Agent, url: &str) -> Result<Release, String> {
+    let body = agent
+        .get(url)
+        .call()
+        .map_err(|err| format!("fetching {} failed: {}", url, err))?
+        .into_string()
+        .map_err(|err| format!("reading {} failed: {}", url, err))?;
+    parse_release(&body)
+}
+
+/// The `tag_name` and `html_url` of a release of the GitHub API
+pub fn parse_release(body: &str) -> Result<Release, String> {
+    let json: Value =
+        serde_json::from_str(body).map_err(|err| format!("invalid release json: {}", err))?;
+    let tag = json["tag_name"]
+        .as_str()
+        .ok_or_else(|| "the release has no tag_name".to_string())?;
+    Ok(Release {
+        version: tag.trim_start_matches('v').to_string(),
+        url: json["html_url"].as_str().unwrap_or("").to_string(),
+    })
+}
+
+/// `major.minor.patch`, a missing minor or patch is 0. Pre-release and build suffixes
+/// (`-rc1`, `+build`) are ignored.
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((major, minor, patch))
+}
+
+/// False if either version cannot be parsed
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+pub fn format_notice(release: &Release) -> String {
+    let mut notice = format!(
+        "mithril {} is available, this is {}",
+        release.version, CURRENT_VERSION
+    );
+    if !release.url.is_empty() {
+        notice.push_str(&format!(", see {}", release.url));
+    }
+    notice
+}
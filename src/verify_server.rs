@@ -0,0 +1,324 @@
+extern crate crossbeam_channel;
+
+use self::crossbeam_channel::unbounded;
+use crate::api::{error_response, read_request, write_response, Request, Response};
+use crate::byte_string;
+use crate::difficulty::{hash_difficulty, hash_value, target_value};
+use crate::randomx::{RandomXCache, RandomXVm};
+use crate::worker::worker_pool::with_nonce;
+use serde_json::{json, Value};
+
+use std::collections::HashMap;
+use std::io;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// The nonce is bytes 39 to 42 of the blob
+pub const MIN_BLOB_HEX_LEN: usize = 86;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyServerConfig {
+    /// address the HTTP server listens on, e.g. `127.0.0.1:9000`
+    pub listen: String,
+    /// number of requests that are hashed in parallel, and the threads of a batch
+    pub threads: u64,
+    /// number of seed hashes whose 256 MB cache is kept
+    pub seeds: usize,
+}
+
+/// A share as the pool received it, all fields hex encoded like in stratum
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ShareCandidate {
+    /// the hashing blob of the job
+    pub blob: String,
+    /// the 4 byte nonce of the share, as submitted
+    pub nonce: String,
+    pub seed_hash: String,
+    /// the job target, 4 or 8 bytes little endian
+    pub target: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub hash: String,
+    /// the hash meets the target
+    pub valid: bool,
+    /// difficulty the hash would have satisfied
+    pub difficulty: u64,
+}
+
+/// The verification of a share, or why it could not be hashed (i.e. invalid hex)
+pub type VerifyResult = Result<Verification, String>;
+
+/// Serves `POST /verify` until the process is stopped, the requests are hashed by
+/// `threads` threads
+pub fn run(conf: &VerifyServerConfig) -> io::Result<()> {
+    let listener = TcpListener::bind(&conf.listen)?;
+    info!(
+        "verify server listening on {}, {} threads",
+        conf.listen, conf.threads
+    );
+
+    let server = Arc::new(VerifyServer::new(conf.seeds, conf.threads as usize));
+    let (sndr, rcvr) = unbounded::<TcpStream>();
+    for i in 0..conf.threads {
+        let server = server.clone();
+        let rcvr = rcvr.clone();
+        thread::Builder::new()
+            .name(format!("verify thread {}", i))
+            .spawn(move || {
+                for stream in rcvr.iter() {
+                    if let Err(err) = server.serve(stream) {
+                        warn!("verify request failed: {}", err);
+                    }
+                }
+            })?;
+    }
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let _ = sndr.send(stream);
+            }
+            Err(err) => warn!("verify server accept failed: {}", err),
+        }
+    }
+    Ok(())
+}
+
+pub struct VerifyServer {
+    caches: SeedCaches,
+    /// threads of `verify_batch`
+    threads: usize,
+}
+
+impl VerifyServer {
+    pub fn new(seeds: usize, threads: usize) -> VerifyServer {
+        VerifyServer {
+            caches: SeedCaches::new(seeds),
+            threads: threads.max(1),
+        }
+    }
+
+    fn serve(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        let response = match read_request(&mut BufReader::new(&stream)) {
+            Ok(request) => self.handle(&request),
+            Err(err) => error_response(400, &err.to_string()),
+        };
+        write_response(&mut &stream, &response)
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/verify") => match serde_json::from_slice::<ShareCandidate>(&request.body) {
+                Ok(share) => match self.verify(&share) {
+                    Ok(verification) => Response {
+                        status: 200,
+                        body: verification_json(&verification),
+                    },
+                    Err(err) => error_response(400, &err),
+                },
+                Err(err) => error_response(400, &err.to_string()),
+            },
+            ("POST", "/verify_batch") => {
+                match serde_json::from_slice::<Vec<ShareCandidate>>(&request.body) {
+                    Ok(shares) => Response {
+                        status: 200,
+                        body: self
+                            .verify_batch(&shares)
+                            .iter()
+                            .map(|result| match result {
+                                Ok(verification) => verification_json(verification),
+                                Err(err) => json!({ "error": err }),
+                            })
+                            .collect(),
+                    },
+                    Err(err) => error_response(400, &err.to_string()),
+                }
+            }
+            ("GET", "/healthz") => Response {
+                status: 200,
+                body: json!({ "status": "ok", "seeds": self.caches.seeds() }),
+            },
+            (_, "/verify") | (_, "/verify_batch") | (_, "/healthz") => {
+                error_response(405, "method not allowed")
+            }
+            _ => error_response(404, "not found"),
+        }
+    }
+
+    /// Hashes the blob with the nonce, the cache of a new seed hash is computed first
+    pub fn verify(&self, share: &ShareCandidate) -> VerifyResult {
+        check_share(share)?;
+        let seed_hash = share.seed_hash.to_lowercase();
+        verify_with(&self.caches.get(&seed_hash), &seed_hash, share)
+    }
+
+    /// Verifies the shares with all threads, the results are in the order of the shares.
+    /// The shares are hashed grouped by seed hash and the batch holds the cache of every
+    /// seed until it is done, so every cache is computed once even if the batch has more
+    /// seeds than are kept. The caches of the seeds that do not fit are dropped after the
+    /// batch.
+    pub fn verify_batch(&self, shares: &[ShareCandidate]) -> Vec<VerifyResult> {
+        let mut order: Vec<usize> = (0..shares.len()).collect();
+        order.sort_by_key(|ix| shares[*ix].seed_hash.to_lowercase());
+        let mut pinned = HashMap::new();
+        for ix in &order {
+            let share = &shares[*ix];
+            if check_share(share).is_ok() {
+                pinned
+                    .entry(share.seed_hash.to_lowercase())
+                    .or_insert_with_key(|seed_hash| self.caches.get(seed_hash));
+            }
+        }
+
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(vec![None; shares.len()]);
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(shares.len()) {
+                scope.spawn(|| {
+                    while let Some(ix) = order.get(next.fetch_add(1, Ordering::SeqCst)) {
+                        let share = &shares[*ix];
+                        let result = check_share(share).and_then(|_| {
+                            let seed_hash = share.seed_hash.to_lowercase();
+                            verify_with(&pinned[&seed_hash], &seed_hash, share)
+                        });
+                        results.lock().unwrap_or_else(|p| p.into_inner())[*ix] = Some(result);
+                    }
+                });
+            }
+        });
+        results
+            .into_inner()
+            .unwrap_or_else(|p| p.into_inner())
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err("not hashed".to_string())))
+            .collect()
+    }
+
+    /// The seed hashes with a cache, the most recently used first
+    pub fn seeds(&self) -> Vec<String> {
+        self.caches.seeds()
+    }
+}
+
+fn verify_with(seed: &SeedCache, seed_hash: &str, share: &ShareCandidate) -> VerifyResult {
+    let input = byte_string::string_to_u8_array(&with_nonce(&share.blob, &share.nonce));
+    let hash = byte_string::u8_array_to_string(&seed.hash(seed_hash, &input));
+    let valid = hash_value(&hash) < target_value(&share.target);
+    Ok(Verification {
+        difficulty: hash_difficulty(&hash),
+        valid,
+        hash,
+    })
+}
+
+/// The caches of the last used seed hashes, a new seed evicts the least recently
+/// used. VMs are kept with their cache and reused by the next request.
+struct SeedCaches {
+    max_seeds: usize,
+    seeds: Mutex<Vec<(String, Arc<SeedCache>)>>,
+}
+
+#[derive(Default)]
+struct SeedCache {
+    /// computed by the first request of the seed, the others wait for it
+    cache: OnceLock<RandomXCache>,
+    idle_vms: Mutex<Vec<RandomXVm>>,
+}
+
+impl SeedCaches {
+    fn new(max_seeds: usize) -> SeedCaches {
+        SeedCaches {
+            max_seeds: max_seeds.max(1),
+            seeds: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The cache stays alive as long as the returned one is held, even if it is evicted
+    fn get(&self, seed_hash: &str) -> Arc<SeedCache> {
+        let mut seeds = self.seeds.lock().unwrap_or_else(|p| p.into_inner());
+        let seed = match seeds.iter().position(|(seed, _)| seed == seed_hash) {
+            Some(ix) => seeds.remove(ix).1,
+            None => Arc::new(SeedCache::default()),
+        };
+        seeds.insert(0, (seed_hash.to_string(), seed.clone()));
+        seeds.truncate(self.max_seeds);
+        seed
+    }
+
+    fn seeds(&self) -> Vec<String> {
+        let seeds = self.seeds.lock().unwrap_or_else(|p| p.into_inner());
+        seeds.iter().map(|(seed, _)| seed.clone()).collect()
+    }
+}
+
+impl SeedCache {
+    fn hash(&self, seed_hash: &str, input: &[u8]) -> [u8; 32] {
+        let cache = self.cache.get_or_init(|| {
+            info!("computing the cache of seed {}", seed_hash);
+            RandomXCache::new(&byte_string::string_to_u8_array(seed_hash))
+        });
+        let idle = self.idle_vms.lock().ok().and_then(|mut vms| vms.pop());
+        let mut vm = idle.unwrap_or_else(|| {
+            RandomXVm::builder()
+                .cache(cache)
+                .build()
+                .expect("light mode vm")
+        });
+        let hash = vm.hash(input);
+        if let Ok(mut vms) = self.idle_vms.lock() {
+            vms.push(vm);
+        }
+        hash
+    }
+}
+
+/// The hex fields are checked before they are decoded, the decoding functions panic on
+/// invalid hex
+pub fn check_share(share: &ShareCandidate) -> Result<(), String> {
+    check_hex("blob", &share.blob)?;
+    if share.blob.len() < MIN_BLOB_HEX_LEN {
+        return Err(format!(
+            "blob has to be at least {} bytes",
+            MIN_BLOB_HEX_LEN / 2
+        ));
+    }
+    check_hex("nonce", &share.nonce)?;
+    if share.nonce.len() != 8 {
+        return Err("nonce has to be 4 bytes".to_string());
+    }
+    check_hex("seed_hash", &share.seed_hash)?;
+    if share.seed_hash.len() != 64 {
+        return Err("seed_hash has to be 32 bytes".to_string());
+    }
+    check_hex("target", &share.target)?;
+    if share.target.len() != 8 && share.target.len() != 16 {
+        return Err("target has to be 4 or 8 bytes".to_string());
+    }
+    if target_value(&share.target) == 0 {
+        return Err("target must not be 0".to_string());
+    }
+    Ok(())
+}
+
+fn verification_json(verification: &Verification) -> Value {
+    json!({
+        "hash": verification.hash,
+        "valid": verification.valid,
+        "difficulty": verification.difficulty,
+    })
+}
+
+pub fn check_hex(field: &str, value: &str) -> Result<(), String> {
+    if !value.len().is_multiple_of(2) || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("{} is not hex", field));
+    }
+    Ok(())
+}
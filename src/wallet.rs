@@ -0,0 +1,242 @@
+extern crate tiny_keccak;
+
+use self::tiny_keccak::{Hasher, Keccak};
+use std::fmt;
+use strum::Display;
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+/// Monero base58 encodes blocks of 8 bytes, this is the encoded size by block size
+const ENCODED_BLOCK_SIZES: [usize; 9] = [0, 2, 3, 5, 6, 7, 9, 10, 11];
+const FULL_BLOCK_SIZE: usize = 8;
+const FULL_ENCODED_BLOCK_SIZE: usize = 11;
+
+const KEY_SIZE: usize = 32;
+const PAYMENT_ID_SIZE: usize = 8;
+const CHECKSUM_SIZE: usize = 4;
+/// length of standard addresses and subaddresses
+pub const ADDRESS_LENGTH: usize = 95;
+pub const INTEGRATED_ADDRESS_LENGTH: usize = 106;
+
+/// The network prefix (a varint) that starts the address data
+const PREFIXES: [(u64, Network, AddressKind); 9] = [
+    (18, Network::Mainnet, AddressKind::Standard),
+    (19, Network::Mainnet, AddressKind::Integrated),
+    (42, Network::Mainnet, AddressKind::Subaddress),
+    (53, Network::Testnet, AddressKind::Standard),
+    (54, Network::Testnet, AddressKind::Integrated),
+    (63, Network::Testnet, AddressKind::Subaddress),
+    (24, Network::Stagenet, AddressKind::Standard),
+    (25, Network::Stagenet, AddressKind::Integrated),
+    (36, Network::Stagenet, AddressKind::Subaddress),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Stagenet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum AddressKind {
+    Standard,
+    Subaddress,
+    Integrated,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Address {
+    pub network: Network,
+    pub kind: AddressKind,
+    pub spend_key: [u8; KEY_SIZE],
+    pub view_key: [u8; KEY_SIZE],
+    /// only integrated addresses have one
+    pub payment_id: Option<[u8; PAYMENT_ID_SIZE]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddressError {
+    Empty,
+    InvalidCharacter(char),
+    InvalidLength(usize),
+    /// not a valid Monero base58 string, e.g. a block that overflows
+    InvalidEncoding,
+    UnknownPrefix(u64),
+    /// the length does not fit the kind of address the prefix names
+    WrongLength {
+        kind: AddressKind,
+        length: usize,
+    },
+    /// mistyped or truncated address
+    WrongChecksum,
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressError::Empty => write!(f, "is empty, set your Monero address"),
+            AddressError::InvalidCharacter(c) => write!(
+                f,
+                "contains '{}', Monero addresses only use base58 characters",
+                c
+            ),
+            AddressError::InvalidLength(length) => write!(
+                f,
+                "has {} characters, Monero addresses have {} ({} for integrated addresses)",
+                length, ADDRESS_LENGTH, INTEGRATED_ADDRESS_LENGTH
+            ),
+            AddressError::InvalidEncoding => write!(f, "is not a valid base58 encoding"),
+            AddressError::UnknownPrefix(prefix) => write!(
+                f,
+                "has the unknown network prefix {}, it is no Monero address",
+                prefix
+            ),
+            AddressError::WrongLength { kind, length } => write!(
+                f,
+                "starts like a {} address but has {} characters",
+                kind, length
+            ),
+            AddressError::WrongChecksum => {
+                write!(f, "has a wrong checksum, it is mistyped or incomplete")
+            }
+        }
+    }
+}
+
+/// The address without the worker name or fixed difficulty that pools accept after
+/// it (`address.worker`, `address+difficulty`)
+pub fn address_part(wallet: &str) -> &str {
+    wallet.split(['.', '+']).next().unwrap_or(wallet)
+}
+
+/// Decodes and verifies a Monero address: base58, network prefix, length and the
+/// Keccak checksum
+pub fn decode(address: &str) -> Result<Address, AddressError> {
+    if address.is_empty() {
+        return Err(AddressError::Empty);
+    }
+    if let Some(c) = address
+        .chars()
+        .find(|c| !c.is_ascii() || !BASE58_ALPHABET.contains(&(*c as u8)))
+    {
+        return Err(AddressError::InvalidCharacter(c));
+    }
+    if address.len() != ADDRESS_LENGTH && address.len() != INTEGRATED_ADDRESS_LENGTH {
+        return Err(AddressError::InvalidLength(address.len()));
+    }
+
+    let data = base58_decode(address).ok_or(AddressError::InvalidEncoding)?;
+    let (prefix, prefix_len) = read_varint(&data).ok_or(AddressError::InvalidEncoding)?;
+    let (_, network, kind) = *PREFIXES
+        .iter()
+        .find(|(p, _, _)| *p == prefix)
+        .ok_or(AddressError::UnknownPrefix(prefix))?;
+
+    let payment_id_size = match kind {
+        AddressKind::Integrated => PAYMENT_ID_SIZE,
+        _ => 0,
+    };
+    if data.len() != prefix_len + 2 * KEY_SIZE + payment_id_size + CHECKSUM_SIZE {
+        return Err(AddressError::WrongLength {
+            kind,
+            length: address.len(),
+        });
+    }
+
+    let (payload, checksum) = data.split_at(data.len() - CHECKSUM_SIZE);
+    if keccak256(payload)[..CHECKSUM_SIZE] != *checksum {
+        return Err(AddressError::WrongChecksum);
+    }
+
+    let keys = &payload[prefix_len..];
+    Ok(Address {
+        network,
+        kind,
+        spend_key: keys[..KEY_SIZE].try_into().expect("spend key"),
+        view_key: keys[KEY_SIZE..2 * KEY_SIZE].try_into().expect("view key"),
+        payment_id: match kind {
+            AddressKind::Integrated => Some(keys[2 * KEY_SIZE..].try_into().expect("payment id")),
+            _ => None,
+        },
+    })
+}
+
+/// Monero varint, 7 bits per byte, least significant group first. Returns the value
+/// and the number of bytes read.
+fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in data.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut keccak = Keccak::v256();
+    keccak.update(data);
+    let mut hash = [0u8; 32];
+    keccak.finalize(&mut hash);
+    hash
+}
+
+/// Monero base58: blocks of 11 characters decode to 8 bytes (big endian), the last
+/// block can be shorter. None if the string is no valid encoding.
+pub fn base58_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(encoded.len() * FULL_BLOCK_SIZE / FULL_ENCODED_BLOCK_SIZE);
+    for block in encoded.as_bytes().chunks(FULL_ENCODED_BLOCK_SIZE) {
+        let size = ENCODED_BLOCK_SIZES.iter().position(|s| *s == block.len())?;
+        let mut num: u64 = 0;
+        for c in block {
+            let digit = BASE58_ALPHABET.iter().position(|a| a == c)? as u64;
+            num = num.checked_mul(58)?.checked_add(digit)?;
+        }
+        if size < FULL_BLOCK_SIZE && num >> (8 * size) != 0 {
+            return None;
+        }
+        data.extend_from_slice(&num.to_be_bytes()[FULL_BLOCK_SIZE - size..]);
+    }
+    Some(data)
+}
+
+pub fn base58_encode(data: &[u8]) -> String {
+    let mut encoded =
+        String::with_capacity(data.len() * FULL_ENCODED_BLOCK_SIZE / FULL_BLOCK_SIZE + 1);
+    for block in data.chunks(FULL_BLOCK_SIZE) {
+        let mut num = block.iter().fold(0u64, |n, b| n << 8 | u64::from(*b));
+        let mut chars = vec![BASE58_ALPHABET[0]; ENCODED_BLOCK_SIZES[block.len()]];
+        for c in chars.iter_mut().rev() {
+            *c = BASE58_ALPHABET[(num % 58) as usize];
+            num /= 58;
+        }
+        encoded.extend(chars.iter().map(|c| *c as char));
+    }
+    encoded
+}
+
+/// Encodes an address, e.g. for tests
+pub fn encode(address: &Address) -> String {
+    let (prefix, _, _) = PREFIXES
+        .iter()
+        .find(|(_, n, k)| *n == address.network && *k == address.kind)
+        .expect("prefix of every network and kind");
+    let mut data = Vec::new();
+    let mut rest = *prefix;
+    while rest >= 0x80 {
+        data.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    data.push(rest as u8);
+    data.extend_from_slice(&address.spend_key);
+    data.extend_from_slice(&address.view_key);
+    if let Some(payment_id) = address.payment_id {
+        data.extend_from_slice(&payment_id);
+    }
+    let checksum = keccak256(&data);
+    data.extend_from_slice(&checksum[..CHECKSUM_SIZE]);
+    base58_encode(&data)
+}
@@ -0,0 +1,470 @@
+extern crate crossbeam_channel;
+extern crate ureq;
+
+use self::crossbeam_channel::{unbounded, Receiver, Sender};
+use crate::difficulty;
+use crate::metric::push::hashrate;
+use crate::metric::stats::{MinerStats, StatsSnapshot};
+use crate::stratum::stratum_data::Share;
+
+use std::collections::{HashSet, VecDeque};
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use strum::{Display, EnumString};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The monitor evaluates the statistics once per check interval, the windows in the
+/// config are counted in these intervals.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub const DEFAULT_TEMPLATE: &str = r#"{"event":"{{event}}","rig":"{{rig}}","message":"{{message}}","hashrate":{{hashrate}},"timestamp":{{timestamp}}}"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum AlertEvent {
+    /// the connection to the pool was lost or the login failed
+    PoolDisconnect,
+    /// no hashes were computed for `zero_hashrate_minutes`
+    ZeroHashrate,
+    /// the hashrate over `window_minutes` is below `min_hashrate`
+    LowHashrate,
+    /// the share reject rate over `window_minutes` is above `max_reject_percent`
+    HighRejectRate,
+    /// a block was mined solo or an accepted share reached `block_difficulty`
+    BlockFound,
+}
+
+pub const ALL_EVENTS: [AlertEvent; 5] = [
+    AlertEvent::PoolDisconnect,
+    AlertEvent::ZeroHashrate,
+    AlertEvent::LowHashrate,
+    AlertEvent::HighRejectRate,
+    AlertEvent::BlockFound,
+];
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub enabled: bool,
+    pub url: String,
+    /// the events that are posted to the webhook
+    pub events: Vec<AlertEvent>,
+    /// JSON body with `{{placeholder}}`s, see `render`
+    pub template: String,
+    /// name of this miner in the payload
+    pub rig_name: String,
+    /// 0 disables the zero hashrate alert
+    pub zero_hashrate_minutes: u64,
+    /// in H/s, 0 disables the low hashrate alert
+    pub min_hashrate: f64,
+    /// 0 disables the reject rate alert
+    pub max_reject_percent: f64,
+    /// window for the low hashrate and reject rate alerts
+    pub window_minutes: u64,
+    /// share difficulty that counts as found block, 0 disables the alert
+    pub block_difficulty: u64,
+    /// shell command run for each found block, also without a webhook. Empty for none.
+    pub block_command: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub event: AlertEvent,
+    pub message: String,
+    /// hashrate over the last check interval, 0 for alerts not raised by the monitor
+    pub hashrate: f64,
+}
+
+/// A block found by the miner, i.e. an accepted share of solo mining or one that
+/// reached `block_difficulty`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundBlock {
+    /// of the job the share was found for
+    pub height: u64,
+    /// of the hash
+    pub difficulty: u64,
+    pub job_id: String,
+    pub nonce: String,
+    pub hash: String,
+    pub pool: String,
+}
+
+impl FoundBlock {
+    pub fn message(&self) -> String {
+        format!(
+            "block found at height {} on {} (difficulty {}, job {}, nonce {})",
+            self.height, self.pool, self.difficulty, self.job_id, self.nonce
+        )
+    }
+}
+
+/// The block command, run by `sh -c` with the details of the block in the environment:
+/// `MITHRIL_EVENT`, `MITHRIL_HEIGHT`, `MITHRIL_DIFFICULTY`, `MITHRIL_JOB_ID`,
+/// `MITHRIL_NONCE`, `MITHRIL_HASH`, `MITHRIL_POOL`, `MITHRIL_RIG` and `MITHRIL_MESSAGE`
+pub fn block_command(command: &str, block: &FoundBlock, rig_name: &str) -> Command {
+    let mut cmd = shell(command);
+    cmd.env("MITHRIL_EVENT", AlertEvent::BlockFound.to_string())
+        .env("MITHRIL_HEIGHT", block.height.to_string())
+        .env("MITHRIL_DIFFICULTY", block.difficulty.to_string())
+        .env("MITHRIL_JOB_ID", &block.job_id)
+        .env("MITHRIL_NONCE", &block.nonce)
+        .env("MITHRIL_HASH", &block.hash)
+        .env("MITHRIL_POOL", &block.pool)
+        .env("MITHRIL_RIG", rig_name)
+        .env("MITHRIL_MESSAGE", block.message());
+    cmd
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+/// Handle for firing alerts from the main loop. Alerts are posted asynchronously by
+/// the webhook thread, events that are not configured are dropped.
+#[derive(Clone)]
+pub struct Alerter {
+    sndr: Option<Sender<Alert>>,
+    events: Vec<AlertEvent>,
+    block_difficulty: u64,
+    block_command: String,
+    rig_name: String,
+}
+
+impl Alerter {
+    pub fn disabled() -> Alerter {
+        Alerter {
+            sndr: None,
+            events: Vec::new(),
+            block_difficulty: 0,
+            block_command: String::new(),
+            rig_name: String::new(),
+        }
+    }
+
+    /// An alerter that runs the block command but posts nothing, see `start`
+    pub fn new(conf: &WebhookConfig) -> Alerter {
+        Alerter {
+            sndr: None,
+            events: conf.events.clone(),
+            block_difficulty: conf.block_difficulty,
+            block_command: conf.block_command.clone(),
+            rig_name: conf.rig_name.clone(),
+        }
+    }
+
+    pub fn fire(&self, event: AlertEvent, message: String) {
+        self.send(Alert {
+            event,
+            message,
+            hashrate: 0.0,
+        });
+    }
+
+    /// The block of the share if it is one. Each accepted share of a `solo` daemon is a
+    /// block, the share of a pool if its hash reaches the block difficulty.
+    pub fn found_block(
+        &self,
+        share: &Share,
+        height: u64,
+        pool: &str,
+        solo: bool,
+    ) -> Option<FoundBlock> {
+        let difficulty = difficulty::hash_difficulty(&share.hash);
+        let reached = self.block_difficulty != 0 && difficulty >= self.block_difficulty;
+        (solo || reached).then(|| FoundBlock {
+            height,
+            difficulty,
+            job_id: share.job_id.clone(),
+            nonce: share.nonce.clone(),
+            hash: share.hash.clone(),
+            pool: pool.to_string(),
+        })
+    }
+
+    /// Fires the block found alert and runs the block command if the share is a block
+    pub fn share_accepted(&self, share: &Share, height: u64, pool: &str, solo: bool) {
+        if let Some(block) = self.found_block(share, height, pool, solo) {
+            self.fire(AlertEvent::BlockFound, block.message());
+            self.run_block_command(&block);
+        }
+    }
+
+    fn run_block_command(&self, block: &FoundBlock) {
+        if self.block_command.is_empty() {
+            return;
+        }
+        let mut cmd = block_command(&self.block_command, block, &self.rig_name);
+        let spawned = thread::Builder::new()
+            .name("block command thread".to_string())
+            .spawn(move || match cmd.status() {
+                Ok(status) if status.success() => {}
+                Ok(status) => warn!("block command failed: {}", status),
+                Err(err) => error!("block command could not be run: {}", err),
+            });
+        if let Err(err) = spawned {
+            error!("block command thread not started: {}", err);
+        }
+    }
+
+    fn send(&self, alert: Alert) {
+        if let Some(sndr) = &self.sndr {
+            if self.events.contains(&alert.event) && sndr.send(alert).is_err() {
+                error!("webhook thread is gone, alert dropped");
+            }
+        }
+    }
+}
+
+/// Starts the webhook and monitor threads. Returns an `Alerter` that only runs the
+/// block command if webhooks are not enabled.
+pub fn start(conf: WebhookConfig, stats: Arc<MinerStats>) -> Alerter {
+    if !conf.block_command.is_empty() {
+        info!("running \"{}\" for found blocks", conf.block_command);
+    }
+    if !conf.enabled {
+        return Alerter::new(&conf);
+    }
+    if !conf.url.starts_with("http://") && !conf.url.starts_with("https://") {
+        error!("webhook disabled: url {} is not http(s)", conf.url);
+        return Alerter::new(&conf);
+    }
+
+    info!("posting {:?} alerts to webhook", conf.events);
+
+    let (sndr, rcvr) = unbounded();
+    let alerter = Alerter {
+        sndr: Some(sndr.clone()),
+        ..Alerter::new(&conf)
+    };
+
+    let post_conf = conf.clone();
+    thread::Builder::new()
+        .name("webhook thread".to_string())
+        .spawn(move || post_alerts(&post_conf, &rcvr))
+        .expect("webhook thread handle");
+
+    let monitor_alerter = alerter.clone();
+    thread::Builder::new()
+        .name("alert monitor thread".to_string())
+        .spawn(move || {
+            let mut monitor = AlertMonitor::new(&conf);
+            loop {
+                thread::sleep(CHECK_INTERVAL);
+                for alert in monitor.check(stats.snapshot()) {
+                    monitor_alerter.send(alert);
+                }
+            }
+        })
+        .expect("alert monitor thread handle");
+
+    alerter
+}
+
+fn post_alerts(conf: &WebhookConfig, rcvr: &Receiver<Alert>) {
+    let agent = ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build();
+    for alert in rcvr.iter() {
+        let body = render(&conf.template, &alert, &conf.rig_name, unix_time());
+        info!(
+            event = "webhook_alert", alert = alert.event.to_string().as_str();
+            "webhook alert {}: {}", alert.event, alert.message
+        );
+        let result = agent
+            .post(&conf.url)
+            .set("Content-Type", "application/json")
+            .send_string(&body);
+        if let Err(err) = result {
+            error!("posting webhook alert failed: {}", err);
+        }
+    }
+}
+
+/// Replaces the placeholders `{{event}}`, `{{message}}`, `{{rig}}`, `{{hashrate}}` and
+/// `{{timestamp}}` in the template. String values are JSON escaped, but not quoted.
+/// The template is scanned once, placeholders inside the substituted values (e.g. in a
+/// pool error message) are not expanded. Unknown placeholders are kept as they are.
+pub fn render(template: &str, alert: &Alert, rig_name: &str, timestamp: u64) -> String {
+    let mut body = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        body.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let end = match placeholder.find("}}") {
+            Some(end) => end + 2,
+            None => {
+                rest = placeholder;
+                break;
+            }
+        };
+        match &placeholder[2..end - 2] {
+            "event" => body.push_str(&alert.event.to_string()),
+            "message" => body.push_str(&escape_json(&alert.message)),
+            "rig" => body.push_str(&escape_json(rig_name)),
+            "hashrate" => body.push_str(&format!("{:.2}", alert.hashrate)),
+            "timestamp" => body.push_str(&timestamp.to_string()),
+            _ => body.push_str(&placeholder[..end]),
+        }
+        rest = &placeholder[end..];
+    }
+    body.push_str(rest);
+    body
+}
+
+fn escape_json(s: &str) -> String {
+    let quoted = serde_json::to_string(s).unwrap_or_else(|_| "\"\"".to_string());
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Evaluates the hashrate and reject rate conditions on snapshots taken once per check
+/// interval. An alert fires once when its condition starts to hold and again only
+/// after the condition cleared in between.
+pub struct AlertMonitor {
+    zero_hashrate_minutes: u64,
+    min_hashrate: f64,
+    max_reject_percent: f64,
+    window_minutes: u64,
+    snapshots: VecDeque<StatsSnapshot>,
+    active: HashSet<AlertEvent>,
+}
+
+impl AlertMonitor {
+    pub fn new(conf: &WebhookConfig) -> AlertMonitor {
+        AlertMonitor {
+            zero_hashrate_minutes: conf.zero_hashrate_minutes,
+            min_hashrate: conf.min_hashrate,
+            max_reject_percent: conf.max_reject_percent,
+            window_minutes: conf.window_minutes,
+            snapshots: VecDeque::new(),
+            active: HashSet::new(),
+        }
+    }
+
+    /// Must be called once per check interval (one minute)
+    pub fn check(&mut self, snapshot: StatsSnapshot) -> Vec<Alert> {
+        self.snapshots.push_back(snapshot);
+        let max_len = self.zero_hashrate_minutes.max(self.window_minutes) as usize + 1;
+        while self.snapshots.len() > max_len {
+            self.snapshots.pop_front();
+        }
+
+        let current_hashrate = match self.minutes_ago(1) {
+            Some(prev) => hashrate(prev, self.latest(), CHECK_INTERVAL.as_secs_f64()),
+            None => 0.0,
+        };
+
+        let mut alerts = Vec::new();
+
+        let zero_message = match self.minutes_ago(self.zero_hashrate_minutes) {
+            Some(prev) if prev.total_hashes == self.latest().total_hashes => Some(format!(
+                "no hashes in the last {} minutes",
+                self.zero_hashrate_minutes
+            )),
+            _ => None,
+        };
+        self.transition(
+            AlertEvent::ZeroHashrate,
+            zero_message,
+            current_hashrate,
+            &mut alerts,
+        );
+
+        let mut low_message = None;
+        let mut reject_message = None;
+        if let Some(prev) = self.minutes_ago(self.window_minutes) {
+            let latest = self.latest();
+            let window_hashrate = hashrate(prev, latest, (self.window_minutes * 60) as f64);
+            if self.min_hashrate > 0.0
+                && window_hashrate > 0.0
+                && window_hashrate < self.min_hashrate
+            {
+                low_message = Some(format!(
+                    "hashrate {:.2} H/s over the last {} minutes is below {:.2} H/s",
+                    window_hashrate, self.window_minutes, self.min_hashrate
+                ));
+            }
+
+            let accepted = latest.shares_accepted.saturating_sub(prev.shares_accepted);
+            let rejected = latest.shares_rejected.saturating_sub(prev.shares_rejected);
+            if self.max_reject_percent > 0.0 && rejected > 0 {
+                let reject_percent = rejected as f64 * 100.0 / (accepted + rejected) as f64;
+                if reject_percent > self.max_reject_percent {
+                    reject_message = Some(format!(
+                        "{} of {} shares ({:.1}%) rejected in the last {} minutes",
+                        rejected,
+                        accepted + rejected,
+                        reject_percent,
+                        self.window_minutes
+                    ));
+                }
+            }
+        }
+        self.transition(
+            AlertEvent::LowHashrate,
+            low_message,
+            current_hashrate,
+            &mut alerts,
+        );
+        self.transition(
+            AlertEvent::HighRejectRate,
+            reject_message,
+            current_hashrate,
+            &mut alerts,
+        );
+
+        alerts
+    }
+
+    /// `message` is set while the condition for the event holds
+    fn transition(
+        &mut self,
+        event: AlertEvent,
+        message: Option<String>,
+        hashrate: f64,
+        alerts: &mut Vec<Alert>,
+    ) {
+        match message {
+            Some(message) => {
+                if self.active.insert(event) {
+                    alerts.push(Alert {
+                        event,
+                        message,
+                        hashrate,
+                    });
+                }
+            }
+            None => {
+                self.active.remove(&event);
+            }
+        }
+    }
+
+    fn latest(&self) -> &StatsSnapshot {
+        self.snapshots.back().expect("at least one snapshot")
+    }
+
+    fn minutes_ago(&self, minutes: u64) -> Option<&StatsSnapshot> {
+        let minutes = minutes as usize;
+        if minutes == 0 || self.snapshots.len() <= minutes {
+            return None;
+        }
+        self.snapshots.get(self.snapshots.len() - 1 - minutes)
+    }
+}
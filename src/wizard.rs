@@ -0,0 +1,288 @@
+extern crate num_cpus;
+
+use crate::bench::{cpu_info, CpuInfo};
+use crate::cgroup::{Limits, FULL_MEMORY_BYTES};
+use crate::config_validation::{pool_address_problem, wallet_address_problem};
+use crate::mithril_config::DEFAULT_CONFIG;
+use crate::pow::randomx::MemoryMode;
+
+use std::io;
+use std::io::{BufRead, Write};
+
+/// RandomX needs 2 MB of L3 cache per thread for the scratchpad
+pub(crate) const SCRATCHPAD_CACHE_BYTES: u64 = 2 * 1024 * 1024;
+const DEFAULT_POOL: &str = "xmrpool.eu:3333";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hardware {
+    pub cpu: CpuInfo,
+    /// None if unknown
+    pub l3_cache_bytes: Option<u64>,
+    pub memory_bytes: Option<u64>,
+    /// cgroup limits of a container, they win over the cores and memory of the host
+    pub limits: Limits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RigKind {
+    /// only used for mining, all cores are used
+    Dedicated,
+    /// half of the cores are left for other work
+    Desktop,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answers {
+    pub pool: String,
+    pub wallet: String,
+    pub rig: RigKind,
+}
+
+#[cfg(target_os = "macos")]
+pub fn probe_hardware() -> Hardware {
+    use crate::platform::sysctl_u64;
+    Hardware {
+        cpu: cpu_info(),
+        //Apple Silicon has no L3 cache
+        l3_cache_bytes: sysctl_u64("hw.l3cachesize").filter(|size| *size > 0),
+        memory_bytes: sysctl_u64("hw.memsize"),
+        limits: Limits::default(),
+    }
+}
+
+/// FreeBSD has no sysctl for the L3 cache size
+#[cfg(target_os = "freebsd")]
+pub fn probe_hardware() -> Hardware {
+    Hardware {
+        cpu: cpu_info(),
+        l3_cache_bytes: None,
+        memory_bytes: crate::platform::sysctl_u64("hw.physmem"),
+        limits: Limits::default(),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "freebsd")))]
+pub fn probe_hardware() -> Hardware {
+    Hardware {
+        cpu: cpu_info(),
+        l3_cache_bytes: std::fs::read_to_string("/sys/devices/system/cpu/cpu0/cache/index3/size")
+            .ok()
+            .and_then(|size| parse_cache_size(&size)),
+        memory_bytes: std::fs::read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|meminfo| meminfo_total(&meminfo)),
+        limits: crate::cgroup::limits(),
+    }
+}
+
+/// Cache sizes in sysfs look like `32768K`
+pub fn parse_cache_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let (number, unit) = match size.strip_suffix(['K', 'M']) {
+        Some(number) => (number, &size[number.len()..]),
+        None => (size, ""),
+    };
+    let number = number.parse::<u64>().ok()?;
+    Some(match unit {
+        "K" => number * 1024,
+        "M" => number * 1024 * 1024,
+        _ => number,
+    })
+}
+
+/// `MemTotal` of `/proc/meminfo` in bytes
+pub fn meminfo_total(meminfo: &str) -> Option<u64> {
+    let line = meminfo.lines().find(|l| l.starts_with("MemTotal:"))?;
+    let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kb * 1024)
+}
+
+/// One thread per logical core the CPU quota allows, but not more than the L3 cache
+/// holds scratchpads. Desktops keep half of them free.
+pub fn recommended_threads(hw: &Hardware, rig: RigKind) -> u64 {
+    let cores = hw.limits.available_cpus(hw.cpu.logical_cores) as u64;
+    let threads = match hw.l3_cache_bytes {
+        Some(l3) => cores.min(l3 / SCRATCHPAD_CACHE_BYTES),
+        None => cores,
+    };
+    let threads = match rig {
+        RigKind::Dedicated => threads,
+        RigKind::Desktop => threads / 2,
+    };
+    threads.max(1)
+}
+
+/// The light mode if the memory or the container limit cannot hold the 2 GB dataset,
+/// otherwise the check of the cgroup limit at startup decides
+pub fn recommended_memory_mode(hw: &Hardware) -> MemoryMode {
+    match usable_memory(hw) {
+        Some(memory) if memory < FULL_MEMORY_BYTES => MemoryMode::Light,
+        _ => MemoryMode::Auto,
+    }
+}
+
+/// Dedicated rigs pin each worker to its own core, desktops leave the threads to the
+/// scheduler. macOS only takes affinity hints.
+pub fn recommended_affinity(rig: RigKind) -> bool {
+    rig == RigKind::Dedicated && !cfg!(target_os = "macos")
+}
+
+/// The memory of the host or the container limit, whichever is lower
+fn usable_memory(hw: &Hardware) -> Option<u64> {
+    match (hw.memory_bytes, hw.limits.memory_bytes) {
+        (Some(memory), Some(limit)) => Some(memory.min(limit)),
+        (memory, limit) => memory.or(limit),
+    }
+}
+
+pub fn hardware_summary(hw: &Hardware) -> String {
+    let mut lines = vec![format!(
+        "cpu: {} ({} cores, {} threads)",
+        hw.cpu.model, hw.cpu.physical_cores, hw.cpu.logical_cores
+    )];
+    if let Some(l3) = hw.l3_cache_bytes {
+        lines.push(format!("L3 cache: {} MB", l3 / 1024 / 1024));
+    }
+    if let Some(memory) = hw.memory_bytes {
+        lines.push(format!("memory: {} MB", memory / 1024 / 1024));
+    }
+    if let Some(cpus) = hw.limits.cpus {
+        lines.push(format!("container cpu limit: {} cores", cpus));
+    }
+    if let Some(limit) = hw.limits.memory_bytes {
+        lines.push(format!(
+            "container memory limit: {} MB",
+            limit / 1024 / 1024
+        ));
+    }
+    if usable_memory(hw).is_some_and(|memory| memory < FULL_MEMORY_BYTES) {
+        lines.push(
+            "warning: less than the 2.3 GB that the RandomX dataset needs, hashing will be slow"
+                .to_string(),
+        );
+    }
+    lines.join("\n")
+}
+
+/// Asks for the pool, wallet and kind of rig until valid answers are given
+pub fn ask<R: BufRead, W: Write>(input: &mut R, output: &mut W) -> io::Result<Answers> {
+    let pool = prompt(
+        input,
+        output,
+        &format!("pool address (host:port) [{}]: ", DEFAULT_POOL),
+        |answer| {
+            let pool = if answer.is_empty() {
+                DEFAULT_POOL
+            } else {
+                answer
+            };
+            match pool_address_problem(pool) {
+                Some(problem) => Err(format!("the pool address {}", problem)),
+                None => Ok(pool.to_string()),
+            }
+        },
+    )?;
+    let wallet =
+        prompt(
+            input,
+            output,
+            "Monero wallet address: ",
+            |answer| match wallet_address_problem(answer) {
+                Some(problem) => Err(format!("the wallet address {}", problem)),
+                None => Ok(answer.to_string()),
+            },
+        )?;
+    let rig = prompt(
+        input,
+        output,
+        "is this a dedicated mining rig or a desktop that is used while mining? (rig/desktop) [rig]: ",
+        |answer| match answer.to_ascii_lowercase().as_str() {
+            "" | "r" | "rig" => Ok(RigKind::Dedicated),
+            "d" | "desktop" => Ok(RigKind::Desktop),
+            _ => Err("please answer rig or desktop".to_string()),
+        },
+    )?;
+    Ok(Answers { pool, wallet, rig })
+}
+
+fn prompt<R, W, T, F>(input: &mut R, output: &mut W, question: &str, parse: F) -> io::Result<T>
+where
+    R: BufRead,
+    W: Write,
+    F: Fn(&str) -> Result<T, String>,
+{
+    loop {
+        write!(output, "{}", question)?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "input closed"));
+        }
+        match parse(line.trim()) {
+            Ok(answer) => return Ok(answer),
+            Err(problem) => writeln!(output, "{}", problem)?,
+        }
+    }
+}
+
+/// `default_config.toml` with the answers and the recommended thread count, affinity and
+/// memory mode. Dedicated rigs keep auto tuning on, desktops use a fixed thread count.
+pub fn render_config(answers: &Answers, hw: &Hardware) -> String {
+    let threads = recommended_threads(hw, answers.rig);
+    let auto_tune = answers.rig == RigKind::Dedicated;
+    let affinity = recommended_affinity(answers.rig);
+    let memory_mode = recommended_memory_mode(hw).to_string();
+    let mut config = DEFAULT_CONFIG.to_string();
+    config = set_value(&config, "pool", "pool_address", &quoted(&answers.pool));
+    config = set_value(&config, "pool", "wallet_address", &quoted(&answers.wallet));
+    config = set_value(&config, "worker", "num_threads", &threads.to_string());
+    config = set_value(&config, "worker", "auto_tune", &auto_tune.to_string());
+    config = set_value(&config, "worker", "affinity", &affinity.to_string());
+    set_value(&config, "memory", "mode", &quoted(&memory_mode))
+}
+
+/// The answers of `--pool` and `--wallet` for a first start without a config file, None
+/// if they are missing or invalid, the config check reports the problem then
+pub fn answers_from_cli(pool: Option<&str>, wallet: Option<&str>) -> Option<Answers> {
+    let (pool, wallet) = (pool?, wallet?);
+    if pool_address_problem(pool).is_some() || wallet_address_problem(wallet).is_some() {
+        return None;
+    }
+    Some(Answers {
+        pool: pool.to_string(),
+        wallet: wallet.to_string(),
+        rig: RigKind::Dedicated,
+    })
+}
+
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Replaces the value of `key` in the TOML `section`, comments after the value are kept
+pub fn set_value(content: &str, section: &str, key: &str, value: &str) -> String {
+    let header = format!("[{}]", section);
+    let mut in_section = false;
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_section = trimmed == header;
+        }
+        let is_key = in_section
+            && trimmed
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='));
+        if !is_key {
+            lines.push(line.to_string());
+            continue;
+        }
+        let comment = line.find(" #").map(|ix| &line[ix..]).unwrap_or("");
+        lines.push(format!("{} = {}{}", key, value, comment));
+    }
+    let mut result = lines.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
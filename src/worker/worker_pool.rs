@@ -1,20 +1,40 @@
 extern crate crossbeam_channel;
+extern crate num_cpus;
+extern crate tracing;
 
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
 use self::crossbeam_channel::{unbounded, Receiver, Sender};
+use self::tracing::span::EnteredSpan;
+use self::tracing::{field, span, Level};
 use super::super::byte_string;
-use super::super::randomx::memory::{VmMemory, VmMemoryAllocator};
-use super::super::randomx::vm::new_vm;
+use super::super::difficulty::{hash_bytes_value, target_difficulty, target_value};
+use super::super::error::MithrilError;
+use super::super::platform;
+use super::super::pow::{self, PowAlgorithm, PowMemory};
+use super::super::profile::Priority;
+use super::super::randomx::memory::{DatasetError, VmMemoryAllocator};
 use super::super::stratum;
 use super::super::stratum::stratum_data;
 
+/// The bytes of the nonce in a hashing blob
+const NONCE_START: usize = 39;
+const NONCE_END: usize = 43;
+
 pub struct WorkerPool {
     thread_chan: Vec<Sender<WorkerCmd>>,
     thread_hnd: Vec<thread::JoinHandle<()>>,
-    pub vm_memory_allocator: VmMemoryAllocator,
+    /// prepared for every job, the threads get its memory
+    pub algorithm: Box<dyn PowAlgorithm>,
+    /// for the algorithms the pool switches to
+    allocator: fn() -> VmMemoryAllocator,
+    /// the errors of the hashers, see `errors`
+    errors: Receiver<DatasetError>,
+    /// keeps the error channel open while the threads are stopped
+    _error_sndr: Sender<DatasetError>,
 }
 
 #[derive(Clone)]
@@ -23,16 +43,25 @@ pub struct WorkerConfig {
     pub auto_tune: bool,
     pub auto_tune_interval_minutes: u64,
     pub auto_tune_log: String,
+    /// the selected profile, empty if none
+    pub profile: String,
+    pub priority: Priority,
+    /// share of the time the workers sleep between hashes
+    pub throttle_percent: u64,
+    /// pin each worker thread to its own logical core
+    pub affinity: bool,
 }
 
 pub struct JobData {
     pub miner_id: String,
     pub seed_hash: String,
-    pub memory: Arc<VmMemory>,
+    pub memory: Arc<dyn PowMemory>,
     pub blob: String,
     pub job_id: String,
     pub target: String,
     pub nonce: Arc<AtomicU32>,
+    /// keep the nonce byte that the pool reserved
+    pub nicehash: bool,
 }
 
 pub enum WorkerCmd {
@@ -44,30 +73,60 @@ enum WorkerExit {
     NonceSpaceExhausted,
     NewJob { job_data: JobData },
     Stopped,
+    Failed(DatasetError),
 }
 
 pub fn start(
     num_threads: u64,
+    worker_conf: &WorkerConfig,
     share_sndr: &Sender<stratum::StratumCmd>,
     metric_resolution: u64,
-    metric_sndr: &Sender<u64>,
-    vm_memory_allocator: VmMemoryAllocator,
+    metric_sndr: &Sender<(usize, u64)>,
+    algorithm: Box<dyn PowAlgorithm>,
+    allocator: fn() -> VmMemoryAllocator,
 ) -> WorkerPool {
+    if let Err(err) = platform::set_process_priority(worker_conf.priority) {
+        warn!(
+            "setting the {} process priority failed: {}",
+            worker_conf.priority, err
+        );
+    }
+    let cores = num_cpus::get();
+    let (error_sndr, errors) = unbounded();
     let mut thread_chan: Vec<Sender<WorkerCmd>> = Vec::with_capacity(num_threads as usize);
     let mut thread_hnd: Vec<thread::JoinHandle<()>> = Vec::with_capacity(num_threads as usize);
     for i in 0..num_threads {
         let (sndr, rcvr) = unbounded();
         let share_sndr_thread = share_sndr.clone();
         let metric_sndr_thread = metric_sndr.clone();
+        let error_sndr_thread = error_sndr.clone();
+        let priority = worker_conf.priority;
+        let throttle_percent = worker_conf.throttle_percent;
+        let affinity = worker_conf.affinity;
 
         let hnd = thread::Builder::new()
             .name(format!("worker thread {}", i))
             .spawn(move || {
+                if let Err(err) = platform::set_thread_priority(priority) {
+                    warn!("setting the {} priority failed: {}", priority, err);
+                }
+                if affinity {
+                    let core = affinity_core(i as usize, cores);
+                    if let Err(err) = platform::set_thread_affinity(core) {
+                        warn!(
+                            "pinning worker thread {} to core {} failed: {}",
+                            i, core, err
+                        );
+                    }
+                }
                 work(
+                    i as usize,
                     &rcvr,
                     &share_sndr_thread,
                     metric_resolution,
                     &metric_sndr_thread,
+                    &error_sndr_thread,
+                    throttle_percent,
                 )
             })
             .expect("worker thread handle");
@@ -77,7 +136,10 @@ pub fn start(
     WorkerPool {
         thread_chan,
         thread_hnd,
-        vm_memory_allocator,
+        algorithm,
+        allocator,
+        errors,
+        _error_sndr: error_sndr,
     }
 }
 
@@ -85,29 +147,101 @@ impl WorkerPool {
     pub fn job_change(
         &mut self,
         miner_id: &str,
-        seed_hash: &str,
-        blob: &str,
-        job_id: &str,
-        target: &str,
-    ) {
-        info!("job change, blob {}", blob);
-        self.vm_memory_allocator.reallocate(seed_hash.to_string());
+        job: &stratum_data::Job,
+        nicehash: bool,
+    ) -> Result<(), MithrilError> {
+        let stratum_data::Job {
+            seed_hash,
+            height,
+            blob,
+            job_id,
+            target,
+            algo,
+        } = job;
+        let _span = span!(
+            Level::DEBUG,
+            "job",
+            job_id = job_id.as_str(),
+            height = *height
+        )
+        .entered();
+        info!(
+            event = "job", job_id = job_id, seed_hash = seed_hash;
+            "job change, blob {}", blob
+        );
+        if !algo.is_empty() && algo != self.algorithm.name() {
+            match pow::new_algorithm(algo, self.allocator) {
+                Some(algorithm) => {
+                    info!(
+                        "the pool switched from {} to {}",
+                        self.algorithm.name(),
+                        algo
+                    );
+                    self.algorithm = algorithm;
+                }
+                None => {
+                    warn!(
+                        "job {} is for the unsupported algorithm {}, the job is ignored",
+                        job_id, algo
+                    );
+                    return Ok(());
+                }
+            }
+        }
+        let hints = self.algorithm.job_hints();
+        if hints.seed_hash && seed_hash.is_empty() {
+            warn!(
+                "job {} has no seed_hash, {} needs one, the job is ignored",
+                job_id,
+                self.algorithm.name()
+            );
+            return Ok(());
+        }
+        if hints.height && *height == 0 {
+            warn!(
+                "job {} has no height, {} needs one, the job is ignored",
+                job_id,
+                self.algorithm.name()
+            );
+            return Ok(());
+        }
+        let prepared = {
+            let _span =
+                span!(Level::DEBUG, "dataset_init", seed_hash = seed_hash.as_str()).entered();
+            self.algorithm.prepare(seed_hash, *height)?
+        };
+        if let Some(init_time) = prepared {
+            info!(
+                "memory init took {}ms with seed_hash: {}",
+                init_time.as_millis(),
+                seed_hash
+            );
+        }
+        let memory = self.algorithm.memory();
         let nonce = Arc::new(AtomicU32::new(0));
 
-        for (_, tx) in self.thread_chan.iter().enumerate() {
+        for tx in self.thread_chan.iter() {
             tx.send(WorkerCmd::NewJob {
                 job_data: JobData {
                     miner_id: miner_id.to_string(),
-                    seed_hash: seed_hash.to_string(),
-                    memory: self.vm_memory_allocator.vm_memory.clone(),
-                    blob: blob.to_string(),
-                    job_id: job_id.to_string(),
-                    target: target.to_string(),
+                    seed_hash: seed_hash.clone(),
+                    memory: memory.clone(),
+                    blob: blob.clone(),
+                    job_id: job_id.clone(),
+                    target: target.clone(),
                     nonce: nonce.clone(),
+                    nicehash,
                 },
             })
-            .expect("sending new job command");
+            .map_err(|_| MithrilError::ChannelClosed("worker"))?;
         }
+        Ok(())
+    }
+
+    /// The errors that stopped a worker thread from hashing its job, the thread waits
+    /// for the next job
+    pub fn errors(&self) -> &Receiver<DatasetError> {
+        &self.errors
     }
 
     pub fn stop(&self) {
@@ -130,10 +264,13 @@ impl WorkerPool {
 }
 
 fn work(
+    thread: usize,
     rcv: &Receiver<WorkerCmd>,
     share_tx: &Sender<stratum::StratumCmd>,
     metric_resolution: u64,
-    metric_tx: &Sender<u64>,
+    metric_tx: &Sender<(usize, u64)>,
+    error_tx: &Sender<DatasetError>,
+    throttle_percent: u64,
 ) {
     let first_job = rcv.recv();
     if first_job.is_err() {
@@ -149,12 +286,31 @@ fn work(
     };
 
     loop {
-        let exit_reason = work_job(&job, rcv, share_tx, metric_resolution, metric_tx);
-        //if work_job returns the nonce space was exhausted or a new job was received.
-        //In case the nonce space was exhausted, we have to wait blocking for a new job and "idle".
+        let exit_reason = work_job(
+            thread,
+            &job,
+            rcv,
+            share_tx,
+            metric_resolution,
+            metric_tx,
+            throttle_percent,
+        );
+        //if work_job returns the nonce space was exhausted, the hasher failed or a new job was received.
+        //In case the nonce space was exhausted or the hasher failed, we have to wait blocking for a new job and "idle".
         match exit_reason {
-            WorkerExit::NonceSpaceExhausted => {
-                warn!("nonce space exhausted, thread idle");
+            WorkerExit::NonceSpaceExhausted => warn!("nonce space exhausted, thread idle"),
+            WorkerExit::Failed(ref err) => {
+                error!(
+                    "worker thread {} stopped hashing job {}: {}",
+                    thread, job.job_id, err
+                );
+                //the main loop decides how to go on
+                let _ = error_tx.send(err.clone());
+            }
+            _ => {}
+        }
+        match exit_reason {
+            WorkerExit::NonceSpaceExhausted | WorkerExit::Failed(_) => {
                 let job_blocking = rcv.recv();
                 if job_blocking.is_err() {
                     error!("job channel was dropped");
@@ -176,32 +332,56 @@ fn work(
 }
 
 fn work_job<'a>(
+    thread: usize,
     job: &'a JobData,
     rcv: &'a Receiver<WorkerCmd>,
     share_tx: &Sender<stratum::StratumCmd>,
     metric_resolution: u64,
-    metric_tx: &Sender<u64>,
+    metric_tx: &Sender<(usize, u64)>,
+    throttle_percent: u64,
 ) -> WorkerExit {
-    let num_target = job_target_value(&job.target);
+    let num_target = target_value(&job.target);
+    let difficulty = target_difficulty(num_target);
     let mut nonce = job.nonce.fetch_add(1, Ordering::SeqCst);
 
     let mut hash_count: u64 = 0;
-    let mut vm = new_vm(job.memory.clone());
+    let mut hasher = job.memory.clone().hasher();
+    let mut batch = hash_batch(thread, job);
+    let mut bytes_in = match byte_string::decode(&job.blob) {
+        Ok(bytes) if bytes.len() >= NONCE_END => bytes,
+        Ok(bytes) => {
+            error!("job blob of {} bytes has no nonce", bytes.len());
+            return WorkerExit::NonceSpaceExhausted;
+        }
+        Err(err) => {
+            error!("invalid job blob: {}", err);
+            return WorkerExit::NonceSpaceExhausted;
+        }
+    };
+    let mut hash_hex = [0; 64];
 
     while nonce <= 65535 {
-        let nonce_hex = nonce_hex(nonce);
-        let hash_in = with_nonce(&job.blob, &nonce_hex);
-        let bytes_in = byte_string::string_to_u8_array(&hash_in);
+        let hash_start = Instant::now();
+        set_nonce(&mut bytes_in, nonce, job.nicehash);
 
-        let hash_result = vm.calculate_hash(&bytes_in).to_hex();
-        let hash_val = hash_target_value(&hash_result);
+        let hash = match hasher.try_hash(&bytes_in) {
+            Ok(hash) => hash,
+            Err(err) => return WorkerExit::Failed(err),
+        };
 
-        if hash_val < num_target {
+        //only a share is encoded
+        if hash_bytes_value(&hash) < num_target {
+            let mut nonce_hex = [0; 8];
             let share = stratum_data::Share {
                 miner_id: job.miner_id.clone(),
                 job_id: job.job_id.clone(),
-                nonce: nonce_hex,
-                hash: hash_result.to_string(),
+                nonce: byte_string::encode_into(&bytes_in[NONCE_START..NONCE_END], &mut nonce_hex)
+                    .expect("nonce buffer")
+                    .to_string(),
+                hash: byte_string::encode_into(&hash, &mut hash_hex)
+                    .expect("hash buffer")
+                    .to_string(),
+                difficulty,
             };
 
             let submit_result = stratum::submit_share(share_tx, share);
@@ -210,20 +390,28 @@ fn work_job<'a>(
             }
         }
 
+        if throttle_percent > 0 {
+            thread::sleep(throttle_pause(hash_start.elapsed(), throttle_percent));
+        }
+
         hash_count += 1;
-        if hash_count % metric_resolution == 0 {
-            let send_result = metric_tx.send(hash_count);
+        if hash_count.is_multiple_of(metric_resolution) {
+            batch.record("hashes", hash_count);
+            let send_result = metric_tx.send((thread, hash_count));
             if send_result.is_err() {
                 error!("metric submit failed {:?}", send_result);
             }
             hash_count = 0;
+            drop(batch);
+            batch = hash_batch(thread, job);
         }
 
         let cmd = check_command_available(rcv);
         if let Some(cmd_value) = cmd {
             match cmd_value {
                 WorkerCmd::NewJob { job_data } => {
-                    let send_result = metric_tx.send(hash_count);
+                    batch.record("hashes", hash_count);
+                    let send_result = metric_tx.send((thread, hash_count));
                     if send_result.is_err() {
                         //flush hash_count
                         error!("metric submit failed {:?}", send_result);
@@ -239,29 +427,56 @@ fn work_job<'a>(
     WorkerExit::NonceSpaceExhausted
 }
 
+/// The span of the hashes between two metric updates, `hashes` is set when it ends
+fn hash_batch(thread: usize, job: &JobData) -> EnteredSpan {
+    span!(
+        Level::DEBUG,
+        "hash_batch",
+        thread,
+        job_id = job.job_id.as_str(),
+        hashes = field::Empty
+    )
+    .entered()
+}
+
+/// Sleep after a hash that took `hash_time`, so that the thread sleeps `throttle_percent`
+/// of the time
+pub fn throttle_pause(hash_time: Duration, throttle_percent: u64) -> Duration {
+    hash_time * throttle_percent as u32 / (100 - throttle_percent) as u32
+}
+
 pub fn nonce_hex(nonce: u32) -> String {
     format!("{:08x}", nonce)
 }
 
-pub fn with_nonce(blob: &str, nonce: &str) -> String {
-    let (a, _) = blob.split_at(78);
-    let (_, b) = blob.split_at(86);
-    return format!("{}{}{}", a, nonce, b);
+/// Like `nonce_hex`, but the last nonce byte is taken from the blob. NiceHash reserves
+/// it to split the nonce space between miners.
+pub fn nicehash_nonce_hex(nonce: u32, blob: &str) -> String {
+    format!("{:06x}{}", nonce, &blob[84..86])
 }
 
-fn check_command_available(rcv: &Receiver<WorkerCmd>) -> Option<WorkerCmd> {
-    let try_result = rcv.try_recv();
-    match try_result {
-        Ok(cmd) => Some(cmd),
-        _ => None,
+/// The core a worker thread is pinned to with `worker.affinity`
+pub fn affinity_core(thread: usize, cores: usize) -> usize {
+    thread % cores.max(1)
+}
+
+/// Writes the nonce into the hashing blob bytes like `with_nonce` does with `nonce_hex`,
+/// or with `nicehash_nonce_hex` if `nicehash` is set
+pub fn set_nonce(blob: &mut [u8], nonce: u32, nicehash: bool) {
+    let bytes = nonce.to_be_bytes();
+    if nicehash {
+        blob[NONCE_START..NONCE_END - 1].copy_from_slice(&bytes[1..]);
+    } else {
+        blob[NONCE_START..NONCE_END].copy_from_slice(&bytes);
     }
 }
 
-pub fn job_target_value(hex_str: &str) -> u64 {
-    let t = byte_string::hex2_u32_le(hex_str);
-    u64::max_value() / (u64::from(u32::max_value()) / u64::from(t))
+pub fn with_nonce(blob: &str, nonce: &str) -> String {
+    let (a, _) = blob.split_at(78);
+    let (_, b) = blob.split_at(86);
+    format!("{}{}{}", a, nonce, b)
 }
 
-pub fn hash_target_value(hex_str: &str) -> u64 {
-    byte_string::hex2_u64_le(&hex_str[48..])
+fn check_command_available(rcv: &Receiver<WorkerCmd>) -> Option<WorkerCmd> {
+    rcv.try_recv().ok()
 }
@@ -0,0 +1,219 @@
+extern crate mithril;
+
+use mithril::analyze::{self, Analysis, CpuTimes, Culprit, Evidence, Expectation};
+use mithril::bench::CpuInfo;
+use serde_json::json;
+
+#[test]
+fn test_cpu_generation() {
+    let generation = |model| analyze::cpu_generation(model).map(|(name, _)| name);
+    assert_eq!(
+        generation("AMD Ryzen 9 5950X 16-Core Processor"),
+        Some("Zen 3")
+    );
+    assert_eq!(
+        generation("AMD Ryzen Threadripper 3970X 32-Core Processor"),
+        Some("Zen 2")
+    );
+    assert_eq!(
+        generation("AMD Ryzen 9 7950X 16-Core Processor"),
+        Some("Zen 4")
+    );
+    assert_eq!(generation("AMD EPYC 7763 64-Core Processor"), Some("Zen 3"));
+    assert_eq!(generation("AMD EPYC 9654 96-Core Processor"), Some("Zen 4"));
+    assert_eq!(
+        generation("Intel(R) Core(TM) i7-8700K CPU @ 3.70GHz"),
+        Some("Core (Skylake to Rocket Lake)")
+    );
+    assert_eq!(
+        generation("12th Gen Intel(R) Core(TM) i9-12900K"),
+        Some("Core (Alder Lake to Raptor Lake)")
+    );
+    assert_eq!(
+        generation("Intel(R) Core(TM) Ultra 7 155H"),
+        Some("Core Ultra")
+    );
+    assert_eq!(
+        generation("Intel(R) Xeon(R) CPU E5-2680 v4 @ 2.40GHz"),
+        None
+    );
+    assert_eq!(generation("unknown cpu"), None);
+}
+
+#[test]
+fn test_expected_hashrate() {
+    let ryzen = cpu("AMD Ryzen 9 5950X 16-Core Processor", 16, 32);
+    let expected = analyze::expected_hashrate(&ryzen, 32).unwrap();
+    assert_eq!(expected.generation, "Zen 3");
+    //the SMT threads add nothing
+    assert_eq!(expected.hashrate, 16.0 * 1100.0);
+    assert_eq!(
+        analyze::expected_hashrate(&ryzen, 4).unwrap().hashrate,
+        4.0 * 1100.0
+    );
+    assert_eq!(
+        analyze::expected_hashrate(&cpu("unknown cpu", 4, 8), 4),
+        None
+    );
+}
+
+#[test]
+fn test_analyze_healthy() {
+    let analysis = analyze::analyze(&evidence());
+    assert!(analysis.findings.is_empty());
+    assert_eq!(
+        analyze::format_analysis(&analysis),
+        "hashrate     8500.0 H/s with 8 threads
+expected     about 8800 H/s for a Zen 3 CPU, 97% reached
+
+no culprit found"
+    );
+}
+
+#[test]
+fn test_analyze_culprits() {
+    let evidence = Evidence {
+        threads: 16,
+        hashrate: 900.0,
+        light_mode: Some("memory.mode is light".to_string()),
+        large_page_threads: 4,
+        reserved_huge_pages: Some(4),
+        clock_mhz: Some((4500.0, 3600.0)),
+        max_temp_celsius: Some(95.0),
+        background_load: Some(0.1),
+        ..evidence()
+    };
+    let analysis = analyze::analyze(&evidence);
+    let culprits: Vec<Culprit> = analysis.findings.iter().map(|f| f.culprit).collect();
+    assert_eq!(
+        culprits,
+        vec![
+            Culprit::LightMode,
+            Culprit::SmtOversubscription,
+            Culprit::NoHugePages,
+            Culprit::ThermalThrottling,
+            Culprit::BackgroundLoad,
+        ]
+    );
+    let smt = &analysis.findings[1];
+    assert_eq!(
+        smt.evidence,
+        "16 threads on 8 physical cores, their scratchpads need 32 MB of L3 cache, the first core has 16 MB"
+    );
+    let pages = &analysis.findings[2];
+    assert_eq!(
+        pages.evidence,
+        "12 of 16 scratchpads are on 4 KB pages, 4 huge pages are reserved"
+    );
+    assert!(pages.fix.as_ref().unwrap().contains("vm.nr_hugepages=16"));
+    assert_eq!(
+        analysis.findings[3].evidence,
+        "the average clock fell from 4500 MHz to 3600 MHz while hashing, the package reached 95 °C"
+    );
+
+    let report = analyze::format_analysis(&analysis);
+    assert!(report.contains("10% reached"), "{}", report);
+    assert!(report.contains("\nlikely culprits:\n  1. light mode: every dataset item"));
+    assert!(report.contains("  5. background load: 10% of the CPU time was busy"));
+}
+
+#[test]
+fn test_analysis_json() {
+    let evidence = Evidence {
+        light_mode: Some("memory.mode is light".to_string()),
+        ..evidence()
+    };
+    let json = serde_json::to_value(analyze::analyze(&evidence)).unwrap();
+    assert_eq!(json["threads"], 8);
+    assert_eq!(json["hashrate"], 8500.0);
+    assert_eq!(
+        json["expected"],
+        json!({"generation": "Zen 3", "hashrate": 8800.0})
+    );
+    assert_eq!(json["findings"][0]["culprit"], "light_mode");
+    assert_eq!(json["findings"][0]["loss"], 0.8);
+}
+
+#[test]
+fn test_analyze_thresholds() {
+    //small clock dips and load are noise
+    let evidence = Evidence {
+        clock_mhz: Some((4500.0, 4200.0)),
+        background_load: Some(0.02),
+        ..evidence()
+    };
+    assert!(analyze::analyze(&evidence).findings.is_empty());
+}
+
+#[test]
+fn test_format_without_culprit() {
+    let analysis = Analysis {
+        threads: 4,
+        hashrate: 1000.0,
+        expected: Some(Expectation {
+            generation: "Zen 2",
+            hashrate: 3600.0,
+        }),
+        findings: Vec::new(),
+    };
+    assert!(analyze::format_analysis(&analysis).ends_with(
+        "no known culprit found, single channel memory or missing MSR tuning also lower the hashrate"
+    ));
+    let analysis = Analysis {
+        expected: None,
+        ..analysis
+    };
+    assert!(analyze::format_analysis(&analysis).contains("expected     unknown for this CPU"));
+}
+
+#[test]
+fn test_cpu_times() {
+    let stat = "cpu  100 5 50 800 20 3 2 0 0 0
+cpu0 50 2 25 400 10 1 1 0 0 0
+intr 12345";
+    assert_eq!(
+        analyze::parse_cpu_times(stat),
+        Some(CpuTimes {
+            busy: 160,
+            total: 980
+        })
+    );
+    assert_eq!(analyze::parse_cpu_times("intr 1"), None);
+    assert_eq!(analyze::parse_cpu_times("cpu  1 x 3 4"), None);
+
+    let start = CpuTimes {
+        busy: 100,
+        total: 1000,
+    };
+    let end = CpuTimes {
+        busy: 150,
+        total: 1200,
+    };
+    assert_eq!(analyze::busy_share(start, end), Some(0.25));
+    assert_eq!(analyze::busy_share(start, start), None);
+}
+
+//helper
+
+fn cpu(model: &str, physical_cores: usize, logical_cores: usize) -> CpuInfo {
+    CpuInfo {
+        model: model.to_string(),
+        physical_cores,
+        logical_cores,
+    }
+}
+
+fn evidence() -> Evidence {
+    Evidence {
+        cpu: cpu("AMD Ryzen 7 5800X 8-Core Processor", 8, 16),
+        l3_cache_bytes: Some(16 * 1024 * 1024),
+        threads: 8,
+        hashrate: 8500.0,
+        light_mode: None,
+        large_page_threads: 8,
+        reserved_huge_pages: Some(8),
+        clock_mhz: Some((4500.0, 4450.0)),
+        max_temp_celsius: Some(70.0),
+        background_load: Some(0.01),
+    }
+}
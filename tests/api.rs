@@ -0,0 +1,469 @@
+extern crate crossbeam_channel;
+extern crate mithril;
+extern crate serde_json;
+
+use crossbeam_channel::{unbounded, Receiver};
+use mithril::api::{
+    health_problems, memory_json, query_param, read_request, write_response, Api, HealthConfig,
+    Request, Response,
+};
+use mithril::control::ControlCmd;
+use mithril::hardware;
+use mithril::metric::history::{HashrateHistory, HashrateLog};
+use mithril::metric::stats::MinerStats;
+use mithril::pow;
+use mithril::randomx::memory::VmMemoryAllocator;
+use mithril::stratum::stratum_data::PoolConfig;
+use serde_json::json;
+
+use std::io::BufReader;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const TOKEN: &str = "secret";
+
+#[test]
+fn test_read_request() {
+    let raw = "POST /threads?x=1 HTTP/1.1\r\nHost: rig\r\nAuthorization: Bearer secret\r\ncontent-length: 17\r\n\r\n{\"num_threads\":4}";
+    let request = read_request(&mut BufReader::new(raw.as_bytes())).unwrap();
+    assert_eq!(
+        request,
+        Request {
+            method: "POST".to_string(),
+            path: "/threads".to_string(),
+            query: "x=1".to_string(),
+            authorization: Some("Bearer secret".to_string()),
+            body: b"{\"num_threads\":4}".to_vec(),
+        }
+    );
+}
+
+#[test]
+fn test_read_request_rejects_malformed_input() {
+    for raw in [
+        "GET\r\n\r\n",
+        "GET /stats HTTP/1.1\r\nno header\r\n\r\n",
+        "GET /stats HTTP/1.1\r\n", //connection closed before the headers ended
+        "POST /pool HTTP/1.1\r\nContent-Length: 1000000\r\n\r\n",
+        "POST /pool HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}",
+    ] {
+        assert!(
+            read_request(&mut BufReader::new(raw.as_bytes())).is_err(),
+            "{}",
+            raw
+        );
+    }
+}
+
+#[test]
+fn test_write_response() {
+    let mut out = Vec::new();
+    write_response(
+        &mut out,
+        &Response {
+            status: 202,
+            body: json!({ "status": "accepted" }),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "HTTP/1.1 202 Accepted\r\nContent-Type: application/json\r\nContent-Length: 21\r\nConnection: close\r\n\r\n{\"status\":\"accepted\"}"
+    );
+}
+
+#[test]
+fn test_requires_token() {
+    let (api, rcvr) = new_api();
+    let mut request = request("POST", "/pause", "");
+    request.authorization = None;
+    assert_eq!(api.handle(&request).status, 401);
+
+    request.authorization = Some("Bearer wrong!".to_string());
+    assert_eq!(api.handle(&request).status, 401);
+
+    request.authorization = Some("secret".to_string());
+    assert_eq!(api.handle(&request).status, 401);
+
+    assert!(rcvr.try_recv().is_err());
+}
+
+#[test]
+fn test_empty_token_never_authorizes() {
+    let (sndr, _rcvr) = unbounded();
+    let api = Api::new(
+        "".to_string(),
+        health_config(),
+        Arc::new(MinerStats::new()),
+        Arc::new(Mutex::new(HashrateHistory::new(Duration::from_secs(60)))),
+        hashrate_log(),
+        sndr,
+    );
+    let mut request = request("GET", "/stats", "");
+    request.authorization = Some("Bearer ".to_string());
+    assert_eq!(api.handle(&request).status, 401);
+}
+
+#[test]
+fn test_commands() {
+    let (api, rcvr) = new_api();
+
+    assert_eq!(api.handle(&request("POST", "/pause", "")).status, 202);
+    assert_eq!(rcvr.try_recv(), Ok(ControlCmd::Pause));
+
+    assert_eq!(api.handle(&request("POST", "/resume", "")).status, 202);
+    assert_eq!(rcvr.try_recv(), Ok(ControlCmd::Resume));
+
+    assert_eq!(api.handle(&request("POST", "/reload", "")).status, 202);
+    assert_eq!(rcvr.try_recv(), Ok(ControlCmd::ReloadConfig));
+
+    let response = api.handle(&request("POST", "/threads", r#"{"num_threads": 6}"#));
+    assert_eq!(response.status, 202);
+    assert_eq!(response.body, json!({ "status": "accepted" }));
+    assert_eq!(rcvr.try_recv(), Ok(ControlCmd::SetThreads(6)));
+
+    let response = api.handle(&request("POST", "/throttle", r#"{"percent": 30}"#));
+    assert_eq!(response.status, 202);
+    assert_eq!(rcvr.try_recv(), Ok(ControlCmd::SetThrottle(30)));
+
+    let response = api.handle(&request("POST", "/profile", r#"{"name": "eco"}"#));
+    assert_eq!(response.status, 202);
+    assert_eq!(
+        rcvr.try_recv(),
+        Ok(ControlCmd::SetProfile("eco".to_string()))
+    );
+
+    let body = r#"{"pool_address": "pool:4444", "wallet_address": "wallet"}"#;
+    assert_eq!(api.handle(&request("POST", "/pool", body)).status, 202);
+    let mut pool_conf = PoolConfig::default();
+    pool_conf.pool_address = "pool:4444".to_string();
+    pool_conf.wallet_address = "wallet".to_string();
+    assert_eq!(
+        rcvr.try_recv(),
+        Ok(ControlCmd::SwitchPool(Box::new(pool_conf)))
+    );
+}
+
+#[test]
+fn test_persisted_commands() {
+    let (api, rcvr) = new_api();
+
+    let body = r#"{"num_threads": 6, "persist": true}"#;
+    assert_eq!(api.handle(&request("POST", "/threads", body)).status, 202);
+    assert_eq!(
+        rcvr.try_recv(),
+        Ok(ControlCmd::Persist(Box::new(ControlCmd::SetThreads(6))))
+    );
+
+    let body = r#"{"percent": 50, "persist": true}"#;
+    assert_eq!(api.handle(&request("POST", "/throttle", body)).status, 202);
+    assert_eq!(
+        rcvr.try_recv(),
+        Ok(ControlCmd::Persist(Box::new(ControlCmd::SetThrottle(50))))
+    );
+
+    let body = r#"{"name": "eco", "persist": true}"#;
+    assert_eq!(api.handle(&request("POST", "/profile", body)).status, 202);
+    assert_eq!(
+        rcvr.try_recv(),
+        Ok(ControlCmd::Persist(Box::new(ControlCmd::SetProfile(
+            "eco".to_string()
+        ))))
+    );
+
+    let body = r#"{"pool_address": "pool:4444", "wallet_address": "wallet", "persist": false}"#;
+    assert_eq!(api.handle(&request("POST", "/pool", body)).status, 202);
+    assert!(matches!(rcvr.try_recv(), Ok(ControlCmd::SwitchPool(_))));
+}
+
+#[test]
+fn test_invalid_commands() {
+    let (api, rcvr) = new_api();
+    assert_eq!(
+        api.handle(&request("POST", "/threads", r#"{"num_threads": 0}"#))
+            .status,
+        400
+    );
+    assert_eq!(
+        api.handle(&request("POST", "/threads", r#"{"threads": 2}"#))
+            .status,
+        400
+    );
+    assert_eq!(
+        api.handle(&request(
+            "POST",
+            "/pool",
+            r#"{"pool_address": "", "wallet_address": "w"}"#
+        ))
+        .status,
+        400
+    );
+    assert_eq!(
+        api.handle(&request("POST", "/throttle", r#"{"percent": 95}"#))
+            .status,
+        400
+    );
+    assert_eq!(
+        api.handle(&request("POST", "/profile", r#"{"name": ""}"#))
+            .status,
+        400
+    );
+    assert_eq!(api.handle(&request("GET", "/pause", "")).status, 405);
+    assert_eq!(api.handle(&request("GET", "/profile", "")).status, 405);
+    assert_eq!(api.handle(&request("GET", "/unknown", "")).status, 404);
+    assert!(rcvr.try_recv().is_err());
+}
+
+#[test]
+fn test_command_without_main_loop() {
+    let (api, rcvr) = new_api();
+    drop(rcvr);
+    assert_eq!(api.handle(&request("POST", "/pause", "")).status, 503);
+}
+
+#[test]
+fn test_stats() {
+    let stats = Arc::new(MinerStats::new());
+    stats.set_thread_count(2);
+    stats.add_thread_hashes(0, 600);
+    stats.add_thread_hashes(1, 400);
+    stats.set_difficulty(1000);
+    stats.share_accepted(1000);
+    stats.share_rejected();
+    stats.connected("pool:3333");
+
+    let mut history = HashrateHistory::new(Duration::from_secs(15 * 60));
+    let start = Instant::now();
+    history.record(start, 0);
+    history.record(start + Duration::from_secs(10), 1000);
+
+    let (sndr, _rcvr) = unbounded();
+    let api = Api::new(
+        TOKEN.to_string(),
+        health_config(),
+        stats.clone(),
+        Arc::new(Mutex::new(history)),
+        hashrate_log(),
+        sndr,
+    );
+    let response = api.handle(&request("GET", "/stats", ""));
+    assert_eq!(response.status, 200);
+
+    let body = response.body;
+    assert_eq!(body["paused"], json!(false));
+    assert_eq!(body["pool"], json!("pool:3333"));
+    assert_eq!(body["reconnects"], json!(0));
+    assert_eq!(body["hashrate"]["10s"], json!(100.0));
+    assert_eq!(body["hashrate"]["60s"], json!(null));
+    assert_eq!(body["total_hashes"], json!(1000));
+    assert_eq!(body["thread_hashes"], json!([600, 400]));
+    assert_eq!(body["shares_accepted"], json!(1));
+    assert_eq!(body["shares_rejected"], json!(1));
+    assert_eq!(body["difficulty"], json!(1000));
+    assert_eq!(body["average_effort"], json!(1.0));
+    assert_eq!(body["share_latency_p50_ms"], json!(null));
+    assert_eq!(body["lifetime"], json!(null));
+    //no job yet
+    assert_eq!(body["memory"], json!(null));
+}
+
+#[test]
+fn test_hardware() {
+    let stats = Arc::new(MinerStats::new());
+    let (sndr, _rcvr) = unbounded();
+    let api = Api::new(
+        TOKEN.to_string(),
+        health_config(),
+        stats.clone(),
+        Arc::new(Mutex::new(HashrateHistory::new(Duration::from_secs(60)))),
+        hashrate_log(),
+        sndr,
+    );
+    assert_eq!(api.handle(&request("GET", "/hardware", "")).status, 503);
+
+    stats.set_hardware(hardware::probe("full", 1, true));
+    let response = api.handle(&request("GET", "/hardware", ""));
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body["memory_mode"], json!("full"));
+    assert_eq!(response.body["thread_cores"], json!([0]));
+    assert!(response.body["cpu_model"].is_string());
+    assert_eq!(api.handle(&request("POST", "/hardware", "")).status, 405);
+}
+
+#[test]
+fn test_memory_json() {
+    let mut algorithm = pow::new_algorithm("cn-pico", VmMemoryAllocator::initial).unwrap();
+    algorithm.prepare("", 0).unwrap();
+    let memory = algorithm.memory();
+    let hasher = memory.clone().hasher();
+
+    let value = memory_json(memory.as_ref());
+    assert_eq!(value["mode"], json!("scratchpad"));
+    assert_eq!(value["cache_bytes"], json!(0));
+    assert_eq!(value["dataset_pages"], json!(null));
+    assert_eq!(value["scratchpads"], json!(1));
+    assert_eq!(value["scratchpad_bytes"], json!(256 * 1024));
+
+    drop(hasher);
+    assert_eq!(memory_json(memory.as_ref())["scratchpads"], json!(0));
+}
+
+#[test]
+fn test_healthz_without_token() {
+    let (api, _rcvr) = new_api();
+    let mut request = request("GET", "/healthz", "");
+    request.authorization = None;
+    let response = api.handle(&request);
+    //just started, not connected yet
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, json!({ "status": "ok", "problems": [] }));
+
+    request.method = "POST".to_string();
+    assert_eq!(api.handle(&request).status, 405);
+}
+
+#[test]
+fn test_health_problems() {
+    let conf = health_config();
+    assert!(health_problems(&conf, false, Some(100.0), None).is_empty());
+    //running shorter than the stall window
+    assert!(health_problems(&conf, false, None, Some(Duration::from_secs(10))).is_empty());
+    assert_eq!(
+        health_problems(&conf, false, Some(0.0), None),
+        vec!["no hashes in the last 120 seconds"]
+    );
+    assert_eq!(
+        health_problems(&conf, false, Some(0.0), Some(Duration::from_secs(61))),
+        vec![
+            "no hashes in the last 120 seconds",
+            "pool not connected for 61 seconds"
+        ]
+    );
+    assert!(health_problems(&conf, false, Some(10.0), Some(Duration::from_secs(59))).is_empty());
+    assert!(health_problems(&conf, true, Some(0.0), Some(Duration::from_secs(600))).is_empty());
+}
+
+#[test]
+fn test_healthz_unhealthy_if_hashing_stalled() {
+    let stats = Arc::new(MinerStats::new());
+    stats.connected("pool:3333");
+    let mut history = HashrateHistory::new(Duration::from_secs(15 * 60));
+    let start = Instant::now();
+    history.record(start, 1000);
+    history.record(start + Duration::from_secs(120), 1000);
+
+    let (sndr, _rcvr) = unbounded();
+    let api = Api::new(
+        TOKEN.to_string(),
+        health_config(),
+        stats.clone(),
+        Arc::new(Mutex::new(history)),
+        hashrate_log(),
+        sndr,
+    );
+    let response = api.handle(&request("GET", "/healthz", ""));
+    assert_eq!(response.status, 503);
+    assert_eq!(
+        response.body,
+        json!({ "status": "unhealthy", "problems": ["no hashes in the last 120 seconds"] })
+    );
+
+    stats.set_paused(true);
+    let response = api.handle(&request("GET", "/healthz", ""));
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body["status"], json!("paused"));
+}
+
+#[test]
+fn test_query_param() {
+    assert_eq!(query_param("since=10&step=60", "step"), Some("60"));
+    assert_eq!(query_param("since=10&step=60", "since"), Some("10"));
+    assert_eq!(query_param("since=10", "step"), None);
+    assert_eq!(query_param("", "step"), None);
+}
+
+#[test]
+fn test_hashrate_history() {
+    let log = hashrate_log();
+    {
+        let mut log = log.lock().unwrap();
+        let start = Instant::now();
+        for i in 0..=6 {
+            log.record(
+                start + Duration::from_secs(i * 10),
+                1000 + i * 10,
+                i * i * 1000,
+            );
+        }
+    }
+    let (sndr, _rcvr) = unbounded();
+    let api = Api::new(
+        TOKEN.to_string(),
+        health_config(),
+        Arc::new(MinerStats::new()),
+        Arc::new(Mutex::new(HashrateHistory::new(Duration::from_secs(60)))),
+        log,
+        sndr,
+    );
+
+    let response = api.handle(&request("GET", "/hashrate/history", ""));
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body["resolution_seconds"], 10);
+    assert_eq!(response.body["samples"].as_array().unwrap().len(), 6);
+    assert_eq!(response.body["samples"][0], json!([1010, 100.0]));
+
+    let mut query = request("GET", "/hashrate/history", "");
+    query.query = "since=1040&step=20".to_string();
+    let response = api.handle(&query);
+    assert_eq!(response.body["step_seconds"], 20);
+    assert_eq!(
+        response.body["samples"],
+        json!([[1040, 800.0], [1060, 1100.0]])
+    );
+
+    query.query = "step=soon".to_string();
+    assert_eq!(api.handle(&query).status, 400);
+    assert_eq!(
+        api.handle(&request("POST", "/hashrate/history", "")).status,
+        405
+    );
+}
+
+//helper
+
+fn health_config() -> HealthConfig {
+    HealthConfig {
+        hash_stall_seconds: 120,
+        disconnect_seconds: 60,
+    }
+}
+
+fn hashrate_log() -> Arc<Mutex<HashrateLog>> {
+    Arc::new(Mutex::new(HashrateLog::new(
+        Duration::from_secs(10),
+        Duration::from_secs(60 * 60),
+    )))
+}
+
+fn new_api() -> (Api, Receiver<ControlCmd>) {
+    let (sndr, rcvr) = unbounded();
+    let api = Api::new(
+        TOKEN.to_string(),
+        health_config(),
+        Arc::new(MinerStats::new()),
+        Arc::new(Mutex::new(HashrateHistory::new(Duration::from_secs(60)))),
+        hashrate_log(),
+        sndr,
+    );
+    (api, rcvr)
+}
+
+fn request(method: &str, path: &str, body: &str) -> Request {
+    Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: String::new(),
+        authorization: Some(format!("Bearer {}", TOKEN)),
+        body: body.as_bytes().to_vec(),
+    }
+}
@@ -0,0 +1,196 @@
+extern crate mithril;
+
+use mithril::bench::{
+    algorithm_reports_json, cpu_model, format_algorithm_reports, format_report, report_json,
+    run_algorithm, AlgorithmReport, BenchConfig, BenchReport, BenchResults, CpuInfo,
+};
+use mithril::pow::cryptonight::{self as cn, CryptoNight};
+
+use serde_json::json;
+use std::time::Duration;
+
+#[test]
+fn test_cpu_model() {
+    let cpuinfo = "processor\t: 0\nvendor_id\t: AuthenticAMD\nmodel name\t: AMD Ryzen 9 5950X 16-Core Processor\nflags\t\t: fpu\n";
+    assert_eq!(
+        cpu_model(cpuinfo),
+        Some("AMD Ryzen 9 5950X 16-Core Processor".to_string())
+    );
+    assert_eq!(cpu_model("processor\t: 0\n"), None);
+}
+
+#[test]
+fn test_format_report() {
+    let report = BenchReport {
+        cpu: CpuInfo {
+            model: "Test CPU".to_string(),
+            physical_cores: 2,
+            logical_cores: 4,
+        },
+        memory_mode: "full",
+        init_time: Duration::from_millis(12_340),
+        duration: Duration::from_secs(10),
+        thread_hashes: vec![1000, 2000],
+        verified: true,
+        energy_joules: Some(600.0),
+    };
+    assert_eq!(report.hashrate(), 300.0);
+
+    let text = format_report(&report);
+    assert!(text.contains("cpu          Test CPU (2 cores, 4 threads)"));
+    assert!(text.contains("memory mode  full"));
+    assert!(text.contains("init time    12.3s"));
+    assert!(text.contains("threads      2"));
+    assert!(text.contains("hashrate     300.0 H/s"));
+    assert!(text.contains("thread 0    100.0 H/s"));
+    assert!(text.contains("thread 1    200.0 H/s"));
+    assert!(text.contains("efficiency   600.0 J, 0.2000 J/hash, 5.00 H/s/W"));
+    assert!(text.ends_with("verification ok"));
+
+    let unmeasured = BenchReport {
+        energy_joules: None,
+        verified: true,
+        cpu: report.cpu.clone(),
+        thread_hashes: report.thread_hashes.clone(),
+        ..report
+    };
+    assert_eq!(unmeasured.hashrate_per_watt(), None);
+    assert!(format_report(&unmeasured).contains("efficiency   n/a"));
+
+    let failed = BenchReport {
+        verified: false,
+        ..report
+    };
+    assert!(format_report(&failed).contains("verification FAILED"));
+}
+
+#[test]
+fn test_report_json() {
+    let report = BenchReport {
+        cpu: test_cpu(),
+        memory_mode: "full",
+        init_time: Duration::from_millis(12_500),
+        duration: Duration::from_secs(10),
+        thread_hashes: vec![1000, 2000],
+        verified: true,
+        energy_joules: None,
+    };
+    let json = report_json(&report);
+    assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+        json["cpu"],
+        json!({"model": "Test CPU", "physical_cores": 2, "logical_cores": 4})
+    );
+    assert_eq!(json["memory_mode"], "full");
+    assert_eq!(json["init_seconds"], 12.5);
+    assert_eq!(json["duration_seconds"], 10.0);
+    assert_eq!(json["thread_hashes"], json!([1000, 2000]));
+    assert_eq!(json["hashrate"], 300.0);
+    assert_eq!(json["verified"], true);
+    assert_eq!(json["energy_joules"], json!(null));
+    assert_eq!(json["hashrate_per_watt"], json!(null));
+}
+
+#[test]
+fn test_run_algorithm() {
+    let conf = BenchConfig {
+        threads: 2,
+        duration: Duration::from_millis(200),
+        algorithms: vec!["cn-pico".to_string()],
+    };
+    let report = run_algorithm(Box::new(CryptoNight::new(cn::Variant::CnPico)), &conf).unwrap();
+    assert_eq!(report.algorithm, "cn-pico");
+    assert_eq!(report.memory_mode, "scratchpad");
+    assert_eq!(report.thread_hashes.len(), 2);
+    assert!(report.hashrate() > 0.0);
+}
+
+#[test]
+fn test_bench_results() {
+    let path = std::env::temp_dir().join("mithril_test_bench_results.json");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(BenchResults::load(&path).unwrap(), BenchResults::default());
+
+    let mut results = BenchResults::default();
+    results.add(&algorithm_report("rx/0", 1000));
+    results.add(&algorithm_report("cn/r", 50));
+    //the last benchmark replaces the earlier one
+    results.add(&algorithm_report("rx/0", 2000));
+    assert_eq!(results.hashrate("rx/0"), Some(200.0));
+    assert_eq!(results.hashrate("cn/r"), Some(5.0));
+    assert_eq!(results.hashrate("cn-pico"), None);
+    assert_eq!(results.algorithms["rx/0"].threads, 1);
+
+    results.save(&path).unwrap();
+    assert_eq!(BenchResults::load(&path).unwrap(), results);
+
+    std::fs::write(&path, "not json").unwrap();
+    assert!(BenchResults::load(&path).is_err());
+}
+
+#[test]
+fn test_format_algorithm_reports() {
+    let cpu = CpuInfo {
+        model: "Test CPU".to_string(),
+        physical_cores: 2,
+        logical_cores: 4,
+    };
+    let text = format_algorithm_reports(
+        &cpu,
+        &[
+            algorithm_report("rx/0", 1000),
+            AlgorithmReport {
+                energy_joules: None,
+                ..algorithm_report("cn/r", 50)
+            },
+        ],
+    );
+    assert!(text.contains("cpu          Test CPU (2 cores, 4 threads)"));
+    assert!(
+        text.contains("rx/0             full       init    1.5s        100.0 H/s       0.50 H/s/W")
+    );
+    assert!(
+        text.contains("cn/r             full       init    1.5s          5.0 H/s        n/a H/s/W")
+    );
+}
+
+#[test]
+fn test_algorithm_reports_json() {
+    let json = algorithm_reports_json(&test_cpu(), &[algorithm_report("rx/0", 1000)]);
+    assert_eq!(json["cpu"]["model"], "Test CPU");
+    assert_eq!(
+        json["algorithms"],
+        json!([{
+            "algorithm": "rx/0",
+            "memory_mode": "full",
+            "init_seconds": 1.5,
+            "duration_seconds": 10.0,
+            "thread_hashes": [1000],
+            "hashrate": 100.0,
+            "energy_joules": 2000.0,
+            "joules_per_hash": 2.0,
+            "hashrate_per_watt": 0.5,
+        }])
+    );
+}
+
+//helper
+
+fn test_cpu() -> CpuInfo {
+    CpuInfo {
+        model: "Test CPU".to_string(),
+        physical_cores: 2,
+        logical_cores: 4,
+    }
+}
+
+fn algorithm_report(algorithm: &'static str, hashes: u64) -> AlgorithmReport {
+    AlgorithmReport {
+        algorithm,
+        memory_mode: "full",
+        init_time: Duration::from_millis(1500),
+        duration: Duration::from_secs(10),
+        thread_hashes: vec![hashes],
+        energy_joules: Some(2000.0),
+    }
+}
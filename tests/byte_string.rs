@@ -3,7 +3,10 @@
 
 extern crate mithril;
 
-use mithril::byte_string::{hex2_u32_le, hex2_u64_be, string_to_u8_array, u8_array_to_string};
+use mithril::byte_string::{
+    ct_eq, decode, decode_array, decode_u32_le, decode_u64_le, encode_into, hex2_u32_le,
+    hex2_u64_be, string_to_u8_array, u8_array_to_string, HexError,
+};
 
 #[test]
 fn test_hex2_u32_le() {
@@ -26,3 +29,130 @@ fn test_conv_back_and_forth() {
     let str_out = u8_array_to_string(&a);
     assert_eq!(str_in, str_out);
 }
+
+#[test]
+fn test_decode() {
+    assert_eq!(decode(""), Ok(vec![]));
+    assert_eq!(decode("00ff7A"), Ok(vec![0x00, 0xff, 0x7a]));
+    assert_eq!(decode("abc"), Err(HexError::OddLength(3)));
+    assert_eq!(
+        decode("00fg"),
+        Err(HexError::InvalidChar {
+            position: 3,
+            found: 'g'
+        })
+    );
+    assert_eq!(
+        decode("0ü0"),
+        Err(HexError::InvalidChar {
+            position: 1,
+            found: 'ü'
+        })
+    );
+    assert_eq!(
+        decode("00fg").unwrap_err().to_string(),
+        "invalid hex char 'g' at position 3"
+    );
+}
+
+#[test]
+fn test_string_to_u8_array_stops_at_invalid_char() {
+    assert_eq!(string_to_u8_array("0102zz03"), vec![1, 2]);
+    assert_eq!(string_to_u8_array("010"), vec![1]);
+}
+
+#[test]
+fn test_decode_array() {
+    assert_eq!(decode_array::<2>("beef"), Ok([0xbe, 0xef]));
+    assert_eq!(
+        decode_array::<4>("beef"),
+        Err(HexError::Length {
+            expected: 4,
+            actual: 2
+        })
+    );
+    assert_eq!(
+        decode_array::<1>("x0"),
+        Err(HexError::InvalidChar {
+            position: 0,
+            found: 'x'
+        })
+    );
+}
+
+#[test]
+fn test_decode_le() {
+    assert_eq!(decode_u32_le("169f0200ffff"), Ok(171798));
+    assert!(matches!(
+        decode_u32_le("169f"),
+        Err(HexError::Length { .. })
+    ));
+    assert_eq!(decode_u64_le("ffffffffffffff00"), Ok(0x00ffffffffffffff));
+    assert_eq!(decode_u64_le("0100"), Ok(1));
+    assert!(matches!(
+        decode_u64_le(&"00".repeat(9)),
+        Err(HexError::Length { .. })
+    ));
+    assert!(matches!(
+        decode_u64_le("-1"),
+        Err(HexError::InvalidChar { position: 0, .. })
+    ));
+}
+
+#[test]
+fn test_encode_into() {
+    let mut buf = [0; 8];
+    assert_eq!(encode_into(&[0x00, 0xab, 0x7f], &mut buf), Ok("00ab7f"));
+    assert_eq!(encode_into(&[], &mut buf), Ok(""));
+    assert_eq!(
+        encode_into(&[1; 5], &mut buf),
+        Err(HexError::Length {
+            expected: 10,
+            actual: 8
+        })
+    );
+}
+
+#[test]
+fn test_encode_decode_property() {
+    let mut numbers = Numbers::new(7);
+    for len in 0..100 {
+        let bytes: Vec<u8> = (0..len).map(|_| numbers.next().unwrap() as u8).collect();
+        let mut buf = vec![0; 2 * len];
+        let hex = encode_into(&bytes, &mut buf).unwrap().to_string();
+        assert_eq!(hex, u8_array_to_string(&bytes));
+        assert_eq!(decode(&hex), Ok(bytes.clone()));
+        assert_eq!(decode(&hex.to_uppercase()), Ok(bytes));
+    }
+}
+
+#[test]
+fn test_ct_eq() {
+    assert!(ct_eq(b"", b""));
+    assert!(ct_eq(&[1, 2, 3], &[1, 2, 3]));
+    assert!(!ct_eq(&[1, 2, 3], &[1, 2, 4]));
+    assert!(!ct_eq(&[1, 2, 3], &[1, 2]));
+    assert!(!ct_eq(&[0x80], &[0x00]));
+}
+
+//helper
+
+/// xorshift64, the same numbers in every run
+struct Numbers(u64);
+
+impl Numbers {
+    fn new(seed: u64) -> Numbers {
+        Numbers(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+    }
+}
+
+impl Iterator for Numbers {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        Some(self.0)
+    }
+}
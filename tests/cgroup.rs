@@ -0,0 +1,135 @@
+extern crate mithril;
+
+use mithril::cgroup::{parse_cfs_quota, parse_cpu_max, parse_memory_max, Limits};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn test_parse_cpu_max() {
+    assert_eq!(parse_cpu_max("200000 100000"), Some(2.0));
+    assert_eq!(parse_cpu_max("50000 100000\n"), Some(0.5));
+    assert_eq!(parse_cpu_max("max 100000"), None);
+    assert_eq!(parse_cpu_max(""), None);
+}
+
+#[test]
+fn test_parse_cfs_quota() {
+    assert_eq!(parse_cfs_quota("150000\n", "100000\n"), Some(1.5));
+    assert_eq!(parse_cfs_quota("-1", "100000"), None);
+    assert_eq!(parse_cfs_quota("100000", "0"), None);
+}
+
+#[test]
+fn test_parse_memory_max() {
+    assert_eq!(parse_memory_max("1073741824\n"), Some(1024 * 1024 * 1024));
+    assert_eq!(parse_memory_max("max"), None);
+    assert_eq!(parse_memory_max("9223372036854771712"), None);
+}
+
+#[test]
+fn test_read_v2() {
+    let root = cgroup_root("v2");
+    write(&root, "system.slice/memory.max", "4294967296\n");
+    write(
+        &root,
+        "system.slice/mithril.service/cpu.max",
+        "300000 100000\n",
+    );
+    write(&root, "system.slice/mithril.service/memory.max", "max\n");
+
+    let limits = Limits::read(&root, "0::/system.slice/mithril.service\n");
+
+    assert_eq!(
+        limits,
+        Limits {
+            cpus: Some(3.0),
+            memory_bytes: Some(4 * 1024 * 1024 * 1024),
+        }
+    );
+}
+
+#[test]
+fn test_read_v2_namespace() {
+    //inside a container the own cgroup is mounted at the root
+    let root = cgroup_root("v2_namespace");
+    write(&root, "cpu.max", "max 100000\n");
+    write(&root, "memory.max", "536870912\n");
+
+    let limits = Limits::read(&root, "0::/\n");
+
+    assert_eq!(limits.cpus, None);
+    assert_eq!(limits.memory_bytes, Some(512 * 1024 * 1024));
+    assert!(!limits.fits_full_memory());
+}
+
+#[test]
+fn test_read_v1() {
+    let root = cgroup_root("v1");
+    write(&root, "cpu,cpuacct/docker/abc/cpu.cfs_quota_us", "150000\n");
+    write(
+        &root,
+        "cpu,cpuacct/docker/abc/cpu.cfs_period_us",
+        "100000\n",
+    );
+    write(
+        &root,
+        "memory/memory.limit_in_bytes",
+        "9223372036854771712\n",
+    );
+    write(
+        &root,
+        "memory/docker/abc/memory.limit_in_bytes",
+        "8589934592\n",
+    );
+    let proc_cgroup =
+        "12:memory:/docker/abc\n4:cpu,cpuacct:/docker/abc\n1:name=systemd:/docker/abc\n";
+
+    let limits = Limits::read(&root, proc_cgroup);
+
+    assert_eq!(limits.cpus, Some(1.5));
+    assert_eq!(limits.memory_bytes, Some(8 * 1024 * 1024 * 1024));
+    assert!(limits.fits_full_memory());
+}
+
+#[test]
+fn test_read_unlimited() {
+    let root = cgroup_root("unlimited");
+
+    let limits = Limits::read(&root, "0::/user.slice\n");
+
+    assert_eq!(limits, Limits::default());
+    assert!(!limits.is_limited());
+    assert!(limits.fits_full_memory());
+}
+
+#[test]
+fn test_available_cpus() {
+    let limits = Limits {
+        cpus: Some(1.5),
+        memory_bytes: None,
+    };
+    assert_eq!(limits.available_cpus(8), 2);
+    assert_eq!(limits.available_cpus(1), 1);
+    let limits = Limits {
+        cpus: Some(0.2),
+        memory_bytes: None,
+    };
+    assert_eq!(limits.available_cpus(8), 1);
+    assert_eq!(Limits::default().available_cpus(8), 8);
+}
+
+//helper
+
+fn cgroup_root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("mithril_test_cgroup_{}", name));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+fn write(root: &Path, file: &str, content: &str) {
+    let path = root.join(file);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
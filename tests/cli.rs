@@ -0,0 +1,438 @@
+extern crate clap;
+extern crate log;
+extern crate mithril;
+
+use clap::Parser;
+use log::LevelFilter;
+use mithril::cli::{Cli, Command};
+use mithril::completions::Shell;
+use mithril::mithril_config;
+use mithril::mithril_config::ConfigFormat;
+use mithril::pow;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[test]
+fn test_defaults() {
+    let cli = parse(&[]);
+    assert!(cli.command.is_none());
+    assert_eq!(cli.config, PathBuf::from("config.toml"));
+    assert_eq!(cli.pool, None);
+    assert_eq!(cli.threads, None);
+    assert!(!cli.tui);
+    assert!(!cli.has_pool());
+}
+
+#[test]
+fn test_overrides() {
+    let cli = parse(&[
+        "--config",
+        "/etc/mithril.toml",
+        "--pool",
+        "pool.example.com:3333",
+        "--wallet",
+        "4wallet",
+        "--password",
+        "rig1",
+        "--threads",
+        "3",
+        "--log-level",
+        "debug",
+        "--tui",
+    ]);
+    assert_eq!(cli.config, PathBuf::from("/etc/mithril.toml"));
+    assert!(cli.has_pool());
+    assert!(cli.tui);
+
+    let mut config = mithril_config::default_config().unwrap();
+    cli.apply(&mut config);
+    assert_eq!(config.pool_conf.pool_address, "pool.example.com:3333");
+    assert_eq!(config.pool_conf.wallet_address, "4wallet");
+    assert_eq!(config.pool_conf.pool_password, "rig1");
+    assert_eq!(config.worker_conf.num_threads, 3);
+    assert!(!config.worker_conf.auto_tune);
+    assert_eq!(config.log_conf.console_level, Some(LevelFilter::Debug));
+}
+
+#[test]
+fn test_short_pool_options() {
+    let cli = parse(&["-o", "pool.example.com:3333", "-u", "4wallet", "-p", "rig1"]);
+    assert!(cli.has_pool());
+    assert_eq!(cli.pool.as_deref(), Some("pool.example.com:3333"));
+    assert_eq!(cli.wallet.as_deref(), Some("4wallet"));
+    assert_eq!(cli.password.as_deref(), Some("rig1"));
+}
+
+#[test]
+fn test_tls() {
+    let cli = parse(&["--tls"]);
+    assert_eq!(cli.overridden_keys(), vec!["pool.tls"]);
+    let mut config = mithril_config::default_config().unwrap();
+    assert!(!config.pool_conf.tls);
+    cli.apply(&mut config);
+    assert!(config.pool_conf.tls);
+}
+
+#[test]
+fn test_profile() {
+    let mut config = mithril_config::default_config().unwrap();
+    let cli = parse(&["--profile", "eco"]);
+    assert_eq!(cli.overridden_keys(), vec!["worker.profile"]);
+    cli.apply(&mut config);
+    assert_eq!(config.worker_conf.profile, "eco");
+    assert!(!config.worker_conf.auto_tune);
+    assert_eq!(config.worker_conf.throttle_percent, 50);
+
+    let mut config = mithril_config::default_config().unwrap();
+    parse(&["--profile", "max", "--threads", "2"]).apply(&mut config);
+    assert_eq!(config.worker_conf.profile, "max");
+    assert_eq!(config.worker_conf.num_threads, 2);
+}
+
+#[test]
+fn test_console_flags() {
+    let cli = parse(&["--no-color", "--quiet"]);
+    assert_eq!(
+        cli.overridden_keys(),
+        vec!["console.color", "console.quiet"]
+    );
+    let mut config = mithril_config::default_config().unwrap();
+    cli.apply(&mut config);
+    assert!(!config.console_conf.color);
+    assert!(config.console_conf.quiet);
+
+    let mut config = mithril_config::default_config().unwrap();
+    parse(&[]).apply(&mut config);
+    assert!(config.console_conf.color);
+    assert!(!config.console_conf.quiet);
+}
+
+#[test]
+fn test_no_overrides_keep_config() {
+    let mut config = mithril_config::default_config().unwrap();
+    parse(&[]).apply(&mut config);
+    assert_eq!(config.pool_conf.pool_address, "xmrpool.eu:3333");
+    assert_eq!(config.worker_conf.num_threads, 8);
+    assert!(config.worker_conf.auto_tune);
+    assert_eq!(config.log_conf.console_level, None);
+}
+
+#[test]
+fn test_config_format() {
+    assert_eq!(parse(&[]).config_format(), ConfigFormat::Toml);
+    assert_eq!(
+        parse(&["--config", "mithril.json"]).config_format(),
+        ConfigFormat::Json
+    );
+    assert_eq!(
+        parse(&["--config", "mithril.conf", "--config-format", "json"]).config_format(),
+        ConfigFormat::Json
+    );
+    assert!(try_parse(&["--config-format", "yaml"]).is_err());
+}
+
+#[test]
+fn test_invalid_args() {
+    assert!(try_parse(&["--threads", "0"]).is_err());
+    assert!(try_parse(&["--threads", "many"]).is_err());
+    assert!(try_parse(&["--log-level", "loud"]).is_err());
+    assert!(try_parse(&["--unknown"]).is_err());
+    assert!(try_parse(&["bench", "--duration", "0"]).is_err());
+    assert!(try_parse(&["stress", "--pool", "x"]).is_err());
+    assert!(try_parse(&["--daemon", "--tui"]).is_err());
+}
+
+#[test]
+fn test_daemon() {
+    let cli = parse(&[]);
+    assert!(!cli.daemon);
+    assert_eq!(cli.pidfile, None);
+    assert_eq!(cli.log_file, PathBuf::from("mithril.log"));
+
+    let cli = parse(&[
+        "--daemon",
+        "--pidfile",
+        "/run/mithril.pid",
+        "--log-file",
+        "/var/log/mithril.log",
+    ]);
+    assert!(cli.daemon);
+    assert_eq!(cli.pidfile, Some(PathBuf::from("/run/mithril.pid")));
+    assert_eq!(cli.log_file, PathBuf::from("/var/log/mithril.log"));
+}
+
+#[test]
+fn test_bench() {
+    let cli = parse(&["bench", "--threads", "4", "--duration", "10"]);
+    match cli.command {
+        Some(Command::Bench(args)) => {
+            let conf = args.config();
+            assert_eq!(conf.threads, 4);
+            assert_eq!(conf.duration, Duration::from_secs(10));
+        }
+        other => panic!("expected bench, got {:?}", other),
+    }
+
+    match parse(&["bench"]).command {
+        Some(Command::Bench(args)) => {
+            assert!(args.threads > 0);
+            assert_eq!(args.config().duration, Duration::from_secs(60));
+            assert!(args.config().algorithms.is_empty());
+        }
+        other => panic!("expected bench, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_bench_algorithms() {
+    match parse(&["bench", "--algo", "cn/r,rx/0", "--algo", "cn/r"]).command {
+        Some(Command::Bench(args)) => assert_eq!(args.config().algorithms, vec!["cn/r", "rx/0"]),
+        other => panic!("expected bench, got {:?}", other),
+    }
+    match parse(&["bench", "--algo", "all"]).command {
+        Some(Command::Bench(args)) => {
+            assert_eq!(args.config().algorithms, pow::ALGORITHMS.to_vec())
+        }
+        other => panic!("expected bench, got {:?}", other),
+    }
+    assert!(try_parse(&["bench", "--algo", "rx/wow"]).is_err());
+}
+
+#[test]
+fn test_stress() {
+    let cli = parse(&[
+        "stress",
+        "--threads",
+        "2",
+        "--duration",
+        "600",
+        "--interval",
+        "30",
+    ]);
+    match cli.command {
+        Some(Command::Stress(args)) => {
+            let conf = args.config();
+            assert_eq!(conf.threads, 2);
+            assert_eq!(conf.duration, Duration::from_secs(600));
+            assert_eq!(conf.interval, Duration::from_secs(30));
+        }
+        other => panic!("expected stress, got {:?}", other),
+    }
+
+    match parse(&["stress"]).command {
+        Some(Command::Stress(args)) => {
+            let conf = args.config();
+            assert_eq!(conf.duration, Duration::from_secs(60 * 60));
+            assert_eq!(conf.interval, Duration::from_secs(60));
+        }
+        other => panic!("expected stress, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_tune() {
+    let cli = parse(&[
+        "--config",
+        "rig.toml",
+        "tune",
+        "--threads",
+        "4,8,8",
+        "--duration",
+        "10",
+        "--write",
+    ]);
+    match cli.command {
+        Some(Command::Tune(args)) => {
+            let conf = args.config();
+            assert_eq!(conf.threads, vec![4, 8]);
+            assert_eq!(conf.duration, Duration::from_secs(10));
+            assert!(args.write);
+        }
+        other => panic!("expected tune, got {:?}", other),
+    }
+
+    match parse(&["tune"]).command {
+        Some(Command::Tune(args)) => {
+            let conf = args.config();
+            assert!(!conf.threads.is_empty());
+            assert_eq!(conf.duration, Duration::from_secs(30));
+            assert!(!args.write);
+        }
+        other => panic!("expected tune, got {:?}", other),
+    }
+    assert!(try_parse(&["tune", "--threads", "0"]).is_err());
+}
+
+#[test]
+fn test_doctor() {
+    let cli = parse(&["--config", "rig.toml", "doctor"]);
+    match &cli.command {
+        Some(Command::Doctor(args)) => {
+            assert!(!args.analyze);
+            assert_eq!(args.duration, 20);
+        }
+        other => panic!("expected doctor, got {:?}", other),
+    }
+    assert_eq!(cli.config, PathBuf::from("rig.toml"));
+
+    let cli = parse(&["doctor", "--analyze", "--duration", "5"]);
+    match &cli.command {
+        Some(Command::Doctor(args)) => {
+            assert!(args.analyze);
+            assert_eq!(args.duration, 5);
+        }
+        other => panic!("expected doctor, got {:?}", other),
+    }
+    assert!(try_parse(&["doctor", "--duration", "5"]).is_err());
+}
+
+#[test]
+fn test_json() {
+    match parse(&["bench", "--json"]).command {
+        Some(Command::Bench(args)) => assert!(args.json),
+        other => panic!("expected bench, got {:?}", other),
+    }
+    match parse(&["tune", "--json"]).command {
+        Some(Command::Tune(args)) => assert!(args.json),
+        other => panic!("expected tune, got {:?}", other),
+    }
+    match parse(&["doctor", "--analyze", "--json"]).command {
+        Some(Command::Doctor(args)) => assert!(args.json && args.analyze),
+        other => panic!("expected doctor, got {:?}", other),
+    }
+    match parse(&["doctor"]).command {
+        Some(Command::Doctor(args)) => assert!(!args.json),
+        other => panic!("expected doctor, got {:?}", other),
+    }
+    match parse(&["hash", "00", "--json"]).command {
+        Some(Command::Hash(args)) => assert!(args.json),
+        other => panic!("expected hash, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_hash() {
+    let cli = parse(&[
+        "hash",
+        "0c0c",
+        "--algo",
+        "cn/r",
+        "--height",
+        "1000",
+        "--nonce",
+        "01020304",
+        "--target",
+        "ffffff7f",
+    ]);
+    match &cli.command {
+        Some(Command::Hash(args)) => {
+            let conf = args.config();
+            assert_eq!(conf.algorithm, "cn/r");
+            assert_eq!(conf.blob, "0c0c");
+            assert_eq!(conf.height, 1000);
+            assert_eq!(conf.nonce.as_deref(), Some("01020304"));
+            assert_eq!(conf.target.as_deref(), Some("ffffff7f"));
+            assert_eq!(conf.seed_hash, "");
+        }
+        other => panic!("expected hash, got {:?}", other),
+    }
+    match parse(&["hash", "00"]).command {
+        Some(Command::Hash(args)) => assert_eq!(args.config().algorithm, "rx/0"),
+        other => panic!("expected hash, got {:?}", other),
+    }
+    assert!(try_parse(&["hash"]).is_err());
+    assert!(try_parse(&["hash", "00", "--algo", "gr"]).is_err());
+}
+
+#[test]
+fn test_completions() {
+    let cli = parse(&["completions", "zsh"]);
+    match &cli.command {
+        Some(Command::Completions(args)) => assert_eq!(args.shell, Shell::Zsh),
+        other => panic!("expected completions, got {:?}", other),
+    }
+    assert!(try_parse(&["completions", "tcsh"]).is_err());
+    assert!(try_parse(&["completions"]).is_err());
+
+    let cli = parse(&["manpage"]);
+    assert!(matches!(cli.command, Some(Command::Manpage)));
+}
+
+#[test]
+fn test_verify_server() {
+    let cli = parse(&[
+        "verify-server",
+        "--listen",
+        "0.0.0.0:9000",
+        "--threads",
+        "4",
+    ]);
+    match cli.command {
+        Some(Command::VerifyServer(args)) => {
+            let conf = args.config();
+            assert_eq!(conf.listen, "0.0.0.0:9000");
+            assert_eq!(conf.threads, 4);
+            assert_eq!(conf.seeds, 2);
+        }
+        other => panic!("unexpected command {:?}", other),
+    }
+    assert!(try_parse(&["verify-server", "--seeds", "0"]).is_err());
+}
+
+#[test]
+fn test_proxy() {
+    let cli = parse(&["--pool", "pool:3333", "proxy", "--stats-interval", "10"]);
+    let mut config =
+        mithril_config::read_config_content(mithril_config::DEFAULT_CONFIG, ConfigFormat::Toml)
+            .unwrap();
+    cli.apply(&mut config);
+    match cli.command {
+        Some(Command::Proxy(args)) => {
+            let conf = args.config(&config);
+            assert_eq!(conf.listen, "0.0.0.0:3333");
+            assert_eq!(conf.pool.pool_address, "pool:3333");
+            assert_eq!(conf.stats_interval, Duration::from_secs(10));
+        }
+        other => panic!("unexpected command {:?}", other),
+    }
+}
+
+#[test]
+fn test_coordinator() {
+    let cli = parse(&[
+        "--pool",
+        "pool:3333",
+        "coordinator",
+        "--api",
+        "0.0.0.0:8080",
+        "--fleet-config",
+        "fleet.toml",
+    ]);
+    let mut config =
+        mithril_config::read_config_content(mithril_config::DEFAULT_CONFIG, ConfigFormat::Toml)
+            .unwrap();
+    config.api_conf.token = "secret".to_string();
+    cli.apply(&mut config);
+    match cli.command {
+        Some(Command::Coordinator(args)) => {
+            let conf = args.config(&config);
+            assert_eq!(conf.proxy.listen, "0.0.0.0:3333");
+            assert_eq!(conf.proxy.pool.pool_address, "pool:3333");
+            assert_eq!(conf.api, "0.0.0.0:8080");
+            assert_eq!(conf.token, "secret");
+            assert_eq!(conf.fleet_config, Some(PathBuf::from("fleet.toml")));
+        }
+        other => panic!("unexpected command {:?}", other),
+    }
+}
+
+//helper
+
+fn try_parse(args: &[&str]) -> Result<Cli, clap::Error> {
+    Cli::try_parse_from(std::iter::once("mithril").chain(args.iter().cloned()))
+}
+
+fn parse(args: &[&str]) -> Cli {
+    try_parse(args).unwrap()
+}
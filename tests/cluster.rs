@@ -0,0 +1,239 @@
+extern crate crossbeam_channel;
+extern crate mithril;
+extern crate serde_json;
+
+use self::crossbeam_channel::{unbounded, Receiver};
+use mithril::api::Request;
+use mithril::cluster;
+use mithril::stratum::proxy::Proxy;
+use mithril::stratum::stratum_data::PoolConfig;
+use mithril::stratum::StratumAction;
+use serde_json::Value;
+
+const TOKEN: &str = "secret";
+
+#[test]
+fn test_api_needs_the_token() {
+    let proxy = Proxy::new();
+    let mut request = get("/agents");
+    assert_eq!(cluster::handle(&proxy, TOKEN, &request).status, 200);
+    request.authorization = Some("Bearer wrong".to_string());
+    assert_eq!(cluster::handle(&proxy, TOKEN, &request).status, 401);
+    request.authorization = None;
+    assert_eq!(cluster::handle(&proxy, TOKEN, &request).status, 401);
+    //an empty token refuses everything
+    assert_eq!(cluster::handle(&proxy, "", &get("/agents")).status, 401);
+
+    assert_eq!(cluster::handle(&proxy, TOKEN, &get("/control")).status, 405);
+    assert_eq!(cluster::handle(&proxy, TOKEN, &get("/nothing")).status, 404);
+}
+
+#[test]
+fn test_agents_and_control() {
+    let proxy = connected_proxy();
+    let (first, first_lines) = unbounded();
+    let (second, second_lines) = unbounded();
+    let (mut first_slot, mut second_slot) = (None, None);
+    proxy.request(&mut first_slot, "10.0.0.1:1000", &first, &login("rig1"));
+    proxy.request(&mut second_slot, "10.0.0.2:1000", &second, &login("rig2"));
+    first_lines.try_recv().unwrap();
+    second_lines.try_recv().unwrap();
+    proxy.request(
+        &mut first_slot,
+        "",
+        &first,
+        r#"{"method":"report","params":{"hashrate":1500.5,"threads":4,"paused":false}}"#,
+    );
+
+    let agents = cluster::handle(&proxy, TOKEN, &get("/agents")).body;
+    assert_eq!(agents[0]["name"], "rig1");
+    assert_eq!(agents[0]["hashrate"], 1500.5);
+    assert_eq!(agents[0]["threads"], 4);
+    assert_eq!(agents[1]["slot"], 1);
+    assert_eq!(agents[1]["hashrate"], Value::Null);
+
+    let response = cluster::handle(&proxy, TOKEN, &post(r#"{"command":"pause"}"#));
+    assert_eq!(response.body["agents"], 2);
+    assert_eq!(next_line(&first_lines)["params"]["command"], "pause");
+    assert_eq!(next_line(&second_lines)["params"]["command"], "pause");
+
+    let response = cluster::handle(
+        &proxy,
+        TOKEN,
+        &post(r#"{"slot":1,"command":"threads","threads":2}"#),
+    );
+    assert_eq!(response.body["agents"], 1);
+    assert!(first_lines.try_recv().is_err());
+    let control = next_line(&second_lines);
+    assert_eq!(control["method"], "control");
+    assert_eq!(control["params"]["threads"], 2);
+
+    let response = cluster::handle(&proxy, TOKEN, &post(r#"{"slot":5,"command":"resume"}"#));
+    assert_eq!(response.status, 404);
+    let response = cluster::handle(&proxy, TOKEN, &post(r#"{"command":"explode"}"#));
+    assert_eq!(response.status, 400);
+}
+
+#[test]
+fn test_summary() {
+    let proxy = connected_proxy();
+    let (first, first_lines) = unbounded();
+    let (second, second_lines) = unbounded();
+    let (third, _third_lines) = unbounded();
+    let (mut first_slot, mut second_slot, mut third_slot) = (None, None, None);
+    proxy.request(&mut first_slot, "10.0.0.1:1000", &first, &login("rig1"));
+    proxy.request(&mut second_slot, "10.0.0.2:1000", &second, &login("rig2"));
+    proxy.request(&mut third_slot, "10.0.0.3:1000", &third, &login("rig3"));
+    first_lines.try_recv().unwrap();
+    second_lines.try_recv().unwrap();
+    proxy.request(
+        &mut first_slot,
+        "",
+        &first,
+        r#"{"method":"report","params":{"hashrate":1500.5,"threads":4,"paused":false,"temperature":71.0,"stratum_errors":2}}"#,
+    );
+    proxy.request(
+        &mut second_slot,
+        "",
+        &second,
+        r#"{"method":"report","params":{"hashrate":500.0,"threads":2,"paused":true,"temperature":83.5,"dataset_errors":1}}"#,
+    );
+
+    let summary = cluster::handle(&proxy, TOKEN, &get("/summary")).body;
+    assert_eq!(summary["agents"], 3);
+    assert_eq!(summary["reporting"], 2);
+    assert_eq!(summary["paused"], 1);
+    assert_eq!(summary["hashrate"], 2000.5);
+    assert_eq!(summary["threads"], 6);
+    assert_eq!(summary["max_temperature"], 83.5);
+    assert_eq!(summary["hottest"], "rig2");
+    assert_eq!(summary["stratum_errors"], 2);
+    assert_eq!(summary["dataset_errors"], 1);
+
+    let agents = cluster::handle(&proxy, TOKEN, &get("/agents")).body;
+    assert_eq!(agents[0]["temperature"], 71.0);
+    assert_eq!(agents[2]["temperature"], Value::Null);
+
+    let empty = cluster::Summary::of(&[], std::time::Instant::now());
+    assert_eq!(empty.agents, 0);
+    assert_eq!(empty.max_temperature, None);
+}
+
+#[test]
+fn test_fleet_config() {
+    let proxy = connected_proxy();
+    let (agent, agent_lines) = unbounded();
+    let (miner, miner_lines) = unbounded();
+    let (mut agent_slot, mut miner_slot) = (None, None);
+    proxy.request(
+        &mut agent_slot,
+        "10.0.0.1:1000",
+        &agent,
+        &fleet_login("rig1"),
+    );
+    proxy.request(&mut miner_slot, "10.0.0.2:1000", &miner, &login("rig2"));
+    let reply = next_line(&agent_lines);
+    assert_eq!(reply["result"]["extensions"][2], "fleet");
+    //no fleet config yet
+    assert!(agent_lines.try_recv().is_err());
+    next_line(&miner_lines);
+
+    proxy.set_fleet_config("[worker]\nnum_threads = 2\n");
+    let config = next_line(&agent_lines);
+    assert_eq!(config["method"], "config");
+    assert_eq!(config["params"]["config"], "[worker]\nnum_threads = 2\n");
+    //only the agents that asked for it
+    assert!(miner_lines.try_recv().is_err());
+
+    //a later login gets it after the reply
+    let (late, late_lines) = unbounded();
+    let mut late_slot = None;
+    proxy.request(&mut late_slot, "10.0.0.3:1000", &late, &fleet_login("rig3"));
+    assert_eq!(next_line(&late_lines)["result"]["status"], "OK");
+    assert_eq!(next_line(&late_lines)["method"], "config");
+}
+
+#[test]
+fn test_read_fleet_config() {
+    let path = std::env::temp_dir().join(format!("mithril-fleet-{}.toml", std::process::id()));
+    std::fs::write(&path, "[worker]\nnum_threads = 2\n").unwrap();
+    assert_eq!(
+        cluster::read_fleet_config(&path).unwrap(),
+        "[worker]\nnum_threads = 2\n"
+    );
+    std::fs::write(&path, "[cluster]\ncoordinator = \"other:3333\"\n").unwrap();
+    assert!(cluster::read_fleet_config(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(cluster::read_fleet_config(&path).is_err());
+}
+
+#[test]
+fn test_coordinator_pool() {
+    let mut pool = PoolConfig::default();
+    pool.pool_address = "pool:3333".to_string();
+    pool.wallet_address = "wallet".to_string();
+    pool.pool_password = "x".to_string();
+    pool.rig_id = "rig1".to_string();
+    let coordinator = cluster::coordinator_pool("coordinator:3333", &pool);
+    assert_eq!(coordinator.pool_address, "coordinator:3333");
+    assert_eq!(coordinator.wallet_address, "wallet");
+    assert_eq!(coordinator.rig_id, "rig1");
+    assert!(coordinator.nicehash);
+    assert_eq!(coordinator.extensions, vec!["binary", "fleet"]);
+}
+
+//helper
+
+fn connected_proxy() -> Proxy {
+    let proxy = Proxy::new();
+    let (upstream, _upstream_rcvr) = unbounded();
+    proxy.set_upstream(Some(upstream));
+    proxy.upstream_action(StratumAction::Job {
+        miner_id: "pool-miner-id".to_string(),
+        seed_hash: "ab".repeat(32),
+        height: 3000000,
+        blob: "07".repeat(76),
+        job_id: "job1".to_string(),
+        target: "f3220000".to_string(),
+        algo: "rx/0".to_string(),
+    });
+    proxy
+}
+
+fn login(rig_id: &str) -> String {
+    format!(
+        "{{\"id\":1,\"method\":\"login\",\"params\":{{\"login\":\"wallet\",\"pass\":\"x\",\"rigid\":\"{}\"}}}}",
+        rig_id
+    )
+}
+
+fn fleet_login(rig_id: &str) -> String {
+    format!(
+        "{{\"id\":1,\"method\":\"login\",\"params\":{{\"login\":\"wallet\",\"pass\":\"x\",\"rigid\":\"{}\",\"extensions\":[\"fleet\"]}}}}",
+        rig_id
+    )
+}
+
+fn get(path: &str) -> Request {
+    Request {
+        method: "GET".to_string(),
+        path: path.to_string(),
+        query: String::new(),
+        authorization: Some(format!("Bearer {}", TOKEN)),
+        body: Vec::new(),
+    }
+}
+
+fn post(body: &str) -> Request {
+    Request {
+        method: "POST".to_string(),
+        path: "/control".to_string(),
+        query: String::new(),
+        authorization: Some(format!("Bearer {}", TOKEN)),
+        body: body.as_bytes().to_vec(),
+    }
+}
+
+fn next_line(lines: &Receiver<String>) -> Value {
+    serde_json::from_str(&lines.try_recv().unwrap()).unwrap()
+}
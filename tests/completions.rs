@@ -0,0 +1,47 @@
+extern crate mithril;
+
+use mithril::completions::{self, Shell};
+
+#[test]
+fn test_bash() {
+    let script = completions::completion_script(Shell::Bash);
+    assert!(script.starts_with("_mithril() {\n"));
+    assert!(script.contains("complete -F _mithril"));
+    assert!(script.contains("mithril,doctor)"));
+    assert!(script.contains("compgen -W \"rx/0 rx/graft"));
+    //the shells are the positional argument of completions
+    assert!(script.contains("bash elvish fish powershell zsh"));
+}
+
+#[test]
+fn test_zsh() {
+    let script = completions::completion_script(Shell::Zsh);
+    assert!(script.starts_with("#compdef mithril\n"));
+    assert!(script.contains("'--pool=[pool address (host\\:port)]:POOL:_default'"));
+    assert!(script.contains("'--config=[config file, if it does not exist"));
+    assert!(script.contains("]:CONFIG:_files'"));
+}
+
+#[test]
+fn test_fish() {
+    let script = completions::completion_script(Shell::Fish);
+    assert!(script.contains(
+        "-l config -d 'config file, if it does not exist a config for the hardware is written on the first start' -r -F\n"
+    ));
+    assert!(script.contains("-f -a \"tune\" -d 'Benchmarks"));
+    assert!(script.contains("__fish_mithril_using_subcommand doctor\" -l analyze -d"));
+}
+
+#[test]
+fn test_manpage() {
+    let page = completions::manpage();
+    assert!(page.contains(&format!(
+        ".TH mithril 1  \"mithril {}\"",
+        env!("CARGO_PKG_VERSION")
+    )));
+    assert!(page.contains("\n.SH NAME\nmithril \\- Monero CPU miner"));
+    assert!(page.contains("\n.SH OPTIONS\n.TP\n\\fB\\-\\-config\\fR \\fI<CONFIG>\\fR"));
+    assert!(page.contains("[default: config.toml]"));
+    assert!(page.contains("\n.SH SUBCOMMANDS\n"));
+    assert!(page.contains("mithril\\-doctor(1)"));
+}
@@ -0,0 +1,250 @@
+extern crate mithril;
+extern crate serde_json;
+
+use mithril::config_migration::{
+    backup_path, migrate, save, set_key, version, write_keys, CONFIG_VERSION,
+};
+use mithril::mithril_config;
+use mithril::mithril_config::ConfigFormat;
+
+use std::fs;
+use std::path::Path;
+
+const V0_TOML: &str = "[pool]
+# your pool
+pool_address = \"pool:3333\"
+wallet_address = \"\"
+
+[hardware]
+has_aes = false # disable on old CPUs
+
+[worker]
+num_threads = 4
+";
+
+#[test]
+fn test_version() {
+    assert_eq!(version(V0_TOML, ConfigFormat::Toml), Ok(Some(0)));
+    assert_eq!(
+        version(mithril_config::DEFAULT_CONFIG, ConfigFormat::Toml),
+        Ok(Some(CONFIG_VERSION))
+    );
+    assert_eq!(version("[pool", ConfigFormat::Toml), Ok(None));
+    assert!(version("config_version = \"one\"", ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_migrate_toml() {
+    let migration = migrate(V0_TOML, ConfigFormat::Toml).unwrap().unwrap();
+
+    assert_eq!(migration.from, 0);
+    assert_eq!(
+        migration.content,
+        "config_version = 1
+[pool]
+# your pool
+pool_address = \"pool:3333\"
+wallet_address = \"\"
+
+[worker]
+num_threads = 4
+"
+    );
+    assert_eq!(
+        migration.notes,
+        vec!["removed the [hardware] section, AES support is detected automatically"]
+    );
+}
+
+#[test]
+fn test_migrate_current_config() {
+    assert_eq!(
+        migrate(mithril_config::DEFAULT_CONFIG, ConfigFormat::Toml),
+        Ok(None)
+    );
+}
+
+#[test]
+fn test_migrate_newer_config() {
+    let err = migrate("config_version = 99\n[pool]\n", ConfigFormat::Toml).unwrap_err();
+    assert_eq!(
+        err,
+        "config_version is 99, this mithril only knows version 1, please update mithril"
+    );
+}
+
+#[test]
+fn test_migrate_json() {
+    let content = r#"{"pool": {"pool_address": "pool:3333"}, "hardware": {"has_aes": true}}"#;
+    let migration = migrate(content, ConfigFormat::Json).unwrap().unwrap();
+
+    let migrated: serde_json::Value = serde_json::from_str(&migration.content).unwrap();
+    assert_eq!(
+        migrated,
+        serde_json::json!({"config_version": 1, "pool": {"pool_address": "pool:3333"}})
+    );
+    assert_eq!(migration.notes.len(), 1);
+}
+
+#[test]
+fn test_migrate_without_changes() {
+    let migration = migrate("[pool]\npool_address = \"pool:3333\"\n", ConfigFormat::Toml)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        migration.content,
+        "config_version = 1\n[pool]\npool_address = \"pool:3333\"\n"
+    );
+    assert!(migration.notes.is_empty());
+}
+
+#[test]
+fn test_backup_path() {
+    assert_eq!(
+        backup_path(Path::new("/etc/mithril/config.toml"), 0),
+        Path::new("/etc/mithril/config.toml.v0.bak")
+    );
+}
+
+#[test]
+fn test_save() {
+    let dir = std::env::temp_dir().join("mithril_test_config_migration");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    fs::write(&path, V0_TOML).unwrap();
+
+    let migration = migrate(V0_TOML, ConfigFormat::Toml).unwrap().unwrap();
+    let backup = save(&path, V0_TOML, &migration).unwrap();
+
+    assert_eq!(backup, dir.join("config.toml.v0.bak"));
+    assert_eq!(fs::read_to_string(&backup).unwrap(), V0_TOML);
+    assert_eq!(fs::read_to_string(&path).unwrap(), migration.content);
+    assert_eq!(
+        migrate(&migration.content, ConfigFormat::Toml),
+        Ok(None),
+        "migrated config is current"
+    );
+}
+
+#[test]
+fn test_save_fails() {
+    let path = std::env::temp_dir().join("mithril_test_config_migration_missing/config.toml");
+    let migration = migrate(V0_TOML, ConfigFormat::Toml).unwrap().unwrap();
+    assert!(save(&path, V0_TOML, &migration).is_err());
+}
+
+#[test]
+fn test_set_key_toml() {
+    let content = set_key(
+        V0_TOML,
+        ConfigFormat::Toml,
+        "worker",
+        "num_threads",
+        &6.into(),
+    )
+    .unwrap();
+    let content = set_key(
+        &content,
+        ConfigFormat::Toml,
+        "hardware",
+        "has_aes",
+        &true.into(),
+    )
+    .unwrap();
+    let content = set_key(
+        &content,
+        ConfigFormat::Toml,
+        "worker",
+        "affinity",
+        &true.into(),
+    )
+    .unwrap();
+    let content = set_key(
+        &content,
+        ConfigFormat::Toml,
+        "memory",
+        "allocator",
+        &"malloc".into(),
+    )
+    .unwrap();
+    assert_eq!(
+        content,
+        "[pool]
+# your pool
+pool_address = \"pool:3333\"
+wallet_address = \"\"
+
+[hardware]
+has_aes = true # disable on old CPUs
+
+[worker]
+affinity = true
+num_threads = 6
+
+[memory]
+allocator = \"malloc\"
+"
+    );
+    //a # in a string is not a comment
+    let content = set_key(
+        "[pool]\npool_address = \"a#b\"",
+        ConfigFormat::Toml,
+        "pool",
+        "pool_address",
+        &"c".into(),
+    )
+    .unwrap();
+    assert_eq!(content, "[pool]\npool_address = \"c\"\n");
+}
+
+#[test]
+fn test_set_key_json() {
+    let content = set_key(
+        "{\"worker\": {\"num_threads\": 4}}",
+        ConfigFormat::Json,
+        "worker",
+        "num_threads",
+        &6.into(),
+    )
+    .unwrap();
+    let content = set_key(
+        &content,
+        ConfigFormat::Json,
+        "memory",
+        "allocator",
+        &"malloc".into(),
+    )
+    .unwrap();
+    let json: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(json["worker"]["num_threads"], 6);
+    assert_eq!(json["memory"]["allocator"], "malloc");
+    assert!(set_key(
+        "{\"worker\": 1}",
+        ConfigFormat::Json,
+        "worker",
+        "affinity",
+        &true.into()
+    )
+    .is_err());
+}
+
+#[test]
+fn test_write_keys() {
+    let dir = std::env::temp_dir().join("mithril_test_config_write_keys");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    fs::write(&path, "[worker]\nnum_threads = 4 # the cores\n").unwrap();
+
+    let values = [
+        ("worker", "num_threads", 6.into()),
+        ("worker", "throttle_percent", 20.into()),
+    ];
+    write_keys(&path, ConfigFormat::Toml, &values).unwrap();
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "[worker]\nthrottle_percent = 20\nnum_threads = 6 # the cores\n"
+    );
+    assert!(write_keys(&dir.join("missing.toml"), ConfigFormat::Toml, &values).is_err());
+}
@@ -0,0 +1,516 @@
+extern crate mithril;
+
+use mithril::config_validation::{
+    check_keys, has_errors, pool_address_problem, validate, wallet_address_problem, ConfigIssue,
+    ConfigSource, Location, Severity,
+};
+use mithril::mithril_config;
+use mithril::mithril_config::{ConfigFormat, DEFAULT_CONFIG};
+use mithril::wallet;
+use mithril::wallet::{AddressKind, Network};
+
+#[test]
+fn test_default_config_keys_known() {
+    assert_eq!(check_keys(&toml_source(DEFAULT_CONFIG)), Vec::new());
+}
+
+#[test]
+fn test_default_config_needs_wallet() {
+    let config = mithril_config::default_config().unwrap();
+    let issues = validate(&config, &toml_source(DEFAULT_CONFIG), &[]);
+    assert!(has_errors(&issues));
+    assert_eq!(
+        issues[0],
+        ConfigIssue {
+            severity: Severity::Error,
+            key: "pool.wallet_address".to_string(),
+            message: "is empty, set your Monero address".to_string(),
+            location: Some(Location {
+                file: "config.toml".to_string(),
+                line: 5,
+                text: "wallet_address = \"\"".to_string(),
+            }),
+        }
+    );
+}
+
+#[test]
+fn test_valid_config() {
+    let mut config = mithril_config::default_config().unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+    assert_eq!(
+        validate(&config, &toml_source(DEFAULT_CONFIG), &[]),
+        Vec::new()
+    );
+}
+
+#[test]
+fn test_unknown_keys() {
+    let content = DEFAULT_CONFIG
+        .replace("auto_tune_log =", "auto_tune_logfile =")
+        .replace("[share_db]", "[sharedb]")
+        + "\n[gpu]\nenabled = true\n";
+    let issues = check_keys(&toml_source(&content));
+    let messages: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+    assert_eq!(issues.len(), 3);
+    assert!(messages[0].starts_with("error: gpu is an unknown section\n"));
+    assert!(
+        messages[1].starts_with("error: sharedb is an unknown section, did you mean share_db?\n")
+    );
+    assert!(messages[2].starts_with(
+        "error: worker.auto_tune_logfile is an unknown key, did you mean auto_tune_log?\n"
+    ));
+    assert_eq!(issues[1].location.as_ref().unwrap().text, "[sharedb]");
+    assert_eq!(
+        issues[2].location.as_ref().unwrap().text,
+        "auto_tune_logfile = \"./bandit.log\""
+    );
+}
+
+#[test]
+fn test_unknown_keys_json() {
+    let content = r#"{
+    "pool": {"pool_address": "pool:3333", "wallet": "4abc"},
+    "worker": {
+        "num_threds": 4
+    }
+}"#;
+    let issues = check_keys(&ConfigSource {
+        name: "config.json",
+        content,
+        format: ConfigFormat::Json,
+    });
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0].key, "pool.wallet");
+    assert_eq!(issues[0].location.as_ref().unwrap().line, 2);
+    assert_eq!(issues[1].key, "worker.num_threds");
+    assert_eq!(
+        issues[1].message,
+        "is an unknown key, did you mean num_threads?"
+    );
+    assert_eq!(issues[1].location.as_ref().unwrap().line, 4);
+}
+
+#[test]
+fn test_overridden_keys_have_no_location() {
+    let mut config = mithril_config::default_config().unwrap();
+    config.pool_conf.pool_address = "pool".to_string();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    let issues = validate(
+        &config,
+        &toml_source(DEFAULT_CONFIG),
+        &["pool.pool_address"],
+    );
+    assert_eq!(issues.len(), 1);
+    assert_eq!(
+        issues[0].to_string(),
+        "error: pool.pool_address has to be host:port, was \"pool\""
+    );
+}
+
+#[test]
+fn test_value_issues() {
+    let mut config = mithril_config::default_config().unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 10_000;
+    config.donation_conf.percentage = 101.0;
+    config.api_conf.enabled = true;
+
+    let issues = validate(&config, &toml_source(DEFAULT_CONFIG), &[]);
+    let keys: Vec<(&str, Severity)> = issues
+        .iter()
+        .map(|i| (i.key.as_str(), i.severity))
+        .collect();
+    assert_eq!(
+        keys,
+        vec![
+            ("worker.num_threads", Severity::Warning),
+            ("donation.percentage", Severity::Error),
+            ("api.token", Severity::Warning),
+        ]
+    );
+    assert_eq!(issues[1].location.as_ref().unwrap().line, 179);
+}
+
+#[test]
+fn test_backup_pool_issues() {
+    let content = DEFAULT_CONFIG.to_string()
+        + "\n[[backup_pool]]\npool_address = \"backup:3333\"\ntls = true\n"
+        + "\n[[backup_pool]]\npool_address = \"backup2:3333\"\nrigid = \"rig1\"\nproxy = \"localhost\"\n";
+    let source = toml_source(&content);
+    let lines: Vec<&str> = content.lines().collect();
+
+    let issues = check_keys(&source);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].key, "backup_pool[1].rigid");
+    assert_eq!(issues[0].message, "is an unknown key, did you mean rig_id?");
+    assert_eq!(issues[0].location.as_ref().unwrap().line, lines.len() - 1);
+
+    let path = std::env::temp_dir().join("mithril_test_backup_pool_issues.toml");
+    std::fs::write(&path, &content).unwrap();
+    let mut config = mithril_config::read_config(&path, path.to_str().unwrap()).unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.backup_pools[0].wallet_address = config.pool_conf.wallet_address.clone();
+    config.backup_pools[1].wallet_address = config.pool_conf.wallet_address.clone();
+
+    assert!(config.backup_pools[0].tls);
+    let issues = validate(&config, &source, &[]);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].key, "backup_pool[1].proxy");
+    assert_eq!(issues[0].location.as_ref().unwrap().line, lines.len());
+}
+
+#[test]
+fn test_tls_daemon() {
+    let content = DEFAULT_CONFIG.to_string()
+        + "\n[[backup_pool]]\npool_address = \"node.example:18089\"\ndaemon = true\ntls = true\n";
+    let mut config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.backup_pools[0].wallet_address = config.pool_conf.wallet_address.clone();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+    let source = toml_source(&content);
+    assert_eq!(check_keys(&source), Vec::new());
+    assert_eq!(validate(&config, &source, &[]), Vec::new());
+}
+
+#[test]
+fn test_unknown_profile() {
+    let content = DEFAULT_CONFIG.replace("profile = \"\"", "profile = \"turbo\"")
+        + "\n[[profile]]\nname = \"night\"\nthreads_percent = 50\n";
+    assert_eq!(check_keys(&toml_source(&content)), Vec::new());
+    let path = std::env::temp_dir().join("mithril_test_unknown_profile.toml");
+    std::fs::write(&path, &content).unwrap();
+    let mut config = mithril_config::read_config(&path, path.to_str().unwrap()).unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+
+    let issues = validate(&config, &toml_source(&content), &[]);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].key, "worker.profile");
+    assert_eq!(
+        issues[0].message,
+        "is \"turbo\", has to be one of eco, balanced, max, night"
+    );
+    assert!(issues[0]
+        .location
+        .as_ref()
+        .unwrap()
+        .text
+        .starts_with("profile = \"turbo\""));
+}
+
+#[test]
+fn test_unsupported_algorithm() {
+    let mut config = mithril_config::default_config().unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+    config.pool_conf.algorithm = "cn/2".to_string();
+    let issues = validate(&config, &toml_source(DEFAULT_CONFIG), &[]);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(
+        issues[0].to_string(),
+        "error: pool.algorithm has to be one of rx/0, rx/graft, rx/keva, cn/r, cn-heavy/0, cn-heavy/xhv, cn-pico, \
+         argon2/chukwa, argon2/chukwav2, was \"cn/2\"\n  \
+         --> config.toml:12: algorithm = \"rx/0\"  # rx/0 (Monero), the others are listed in \
+         the README"
+    );
+}
+
+#[test]
+fn test_custom_randomx_algorithm() {
+    let content = DEFAULT_CONFIG.replace("algorithm = \"rx/0\"", "algorithm = \"rx/custom\"")
+        + "\n[[randomx]]\nname = \"rx/custom\"\nbase = \"rx/graft\"\nprogram_iterations = 1024\n";
+    let mut config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+    let source = toml_source(&content);
+    assert_eq!(check_keys(&source), Vec::new());
+    assert!(validate(&config, &source, &[]).is_empty());
+}
+
+#[test]
+fn test_dual_pool_problems() {
+    let content = DEFAULT_CONFIG.to_string()
+        + "\n[dual_pool]\npool_address = \"keva:3333\"\nalgorithm = \"rx/wow\"\nnum_threads = 64\n";
+    let mut config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    if let Some(dual) = config.dual_pool.as_mut() {
+        dual.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    }
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+    let source = toml_source(&content);
+    assert_eq!(check_keys(&source), Vec::new());
+    let issues = validate(&config, &source, &[]);
+    assert_eq!(issues[0].key, "dual_pool.algorithm");
+    assert_eq!(issues[0].severity, Severity::Error);
+    assert_eq!(issues[0].location.as_ref().unwrap().line, 184);
+    assert_eq!(issues[1].key, "dual_pool.num_threads");
+    assert_eq!(issues[1].severity, Severity::Warning);
+}
+
+#[test]
+fn test_keva_dual_pool() {
+    let content = DEFAULT_CONFIG.to_string()
+        + "\n[dual_pool]\npool_address = \"keva.pool.example:3333\"\n\
+           wallet_address = \"VE4h5C6YYFZ3w6PsuNPqSqT5rx9c65EQTa\"\n\
+           algorithm = \"rx/keva\"\nnum_threads = 2\n";
+    let mut config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+    let source = toml_source(&content);
+    assert_eq!(check_keys(&source), Vec::new());
+    let issues = validate(&config, &source, &[]);
+    assert!(!has_errors(&issues), "{:?}", issues);
+    assert!(issues
+        .iter()
+        .all(|i| i.key == "dual_pool.num_threads" || i.key == "dual_pool.algorithm"));
+}
+
+#[test]
+fn test_coin_problems() {
+    let content = DEFAULT_CONFIG
+        .replace(
+            "[profit_switch]\nenabled = false",
+            "[profit_switch]\nenabled = true",
+        )
+        .replace("url = \"\" # returns", "url = \"http://api\" # returns")
+        + "\n[[coin]]\nname = \"monero\"\nhashrate = -1.0\npool_address = \"xmr:3333\"\n";
+    let mut config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.coins[0].pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+    let source = toml_source(&content);
+    assert_eq!(check_keys(&source), Vec::new());
+    let issues = validate(&config, &source, &[]);
+    assert_eq!(issues[0].key, "coin[0].hashrate");
+    assert_eq!(issues[0].severity, Severity::Error);
+    assert_eq!(issues[1].key, "profit_switch.enabled");
+    assert_eq!(issues[1].severity, Severity::Warning);
+}
+
+#[test]
+fn test_multi_coin_with_keva() {
+    let content = DEFAULT_CONFIG
+        .replace(
+            "[profit_switch]\nenabled = false",
+            "[profit_switch]\nenabled = true",
+        )
+        .replace("url = \"\" # returns", "url = \"http://api\" # returns")
+        + "\n[[coin]]\nname = \"monero\"\nhashrate = 5000.0\npool_address = \"xmrpool.eu:3333\"\n\
+           \n[[coin]]\nname = \"keva\"\npool_address = \"keva.pool.example:3333\"\n\
+           wallet_address = \"VE4h5C6YYFZ3w6PsuNPqSqT5rx9c65EQTa\"\nalgorithm = \"rx/keva\"\n";
+    let mut config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.coins[0].pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+    let source = toml_source(&content);
+    assert_eq!(check_keys(&source), Vec::new());
+    assert_eq!(config.coins[1].pool_conf.algorithm, "rx/keva");
+    let issues = validate(&config, &source, &[]);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].key, "coin[1].algorithm");
+    assert_eq!(issues[0].severity, Severity::Warning);
+
+    //a Monero coin is still checked
+    config.coins[0].pool_conf.wallet_address = "VE4h5C6YYFZ3w6PsuNPqSqT5rx9c65EQTa".to_string();
+    let issues = validate(&config, &source, &[]);
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0].key, "coin[0].wallet_address");
+}
+
+#[test]
+fn test_testnet_wallet_warning() {
+    let mut address = wallet::decode(&mithril_config::donation_conf().wallet_address).unwrap();
+    address.network = Network::Testnet;
+    let mut config = mithril_config::default_config().unwrap();
+    config.pool_conf.wallet_address = wallet::encode(&address);
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+
+    let issues = validate(&config, &toml_source(DEFAULT_CONFIG), &[]);
+    assert!(!has_errors(&issues));
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].key, "pool.wallet_address");
+    assert_eq!(
+        issues[0].message,
+        "is a testnet address, pools on the main network cannot pay to it"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_non_monero_wallet_on_non_monero_algorithm() {
+    let haven = "hvxxxGQ4vDc1N81jHCqDSeG9BPEQJVTyYWvCYktKG8FY2i33gfYAatnHV6RBndpqRgYG7aiGebVhg43x5e3sVxLv53GWTGyzPg";
+    let mut config = mithril_config::default_config().unwrap();
+    config.pool_conf.wallet_address = haven.to_string();
+    config.pool_conf.algorithm = "cn-heavy/xhv".to_string();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+    assert_eq!(
+        validate(&config, &toml_source(DEFAULT_CONFIG), &[]),
+        Vec::new()
+    );
+
+    config.pool_conf.wallet_address = "".to_string();
+    let issues = validate(&config, &toml_source(DEFAULT_CONFIG), &[]);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].key, "pool.wallet_address");
+    assert_eq!(issues[0].message, "is empty, set your wallet address");
+
+    config.pool_conf.wallet_address = haven.to_string();
+    config.pool_conf.algorithm = "rx/0".to_string();
+    let issues = validate(&config, &toml_source(DEFAULT_CONFIG), &[]);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].key, "pool.wallet_address");
+    assert_eq!(issues[0].severity, Severity::Error);
+}
+
+#[test]
+fn test_cryptonight_heavy_and_pico_wallets() {
+    let haven = "hvxyLDFHqQCQqFm34G1nFziJ9CT4bMb7nGmmZJkHVtPraEezikbMNQx8iHN5S16zMvSBAnzSbM9i8je4EQSvgxZv4suUKEdTgF";
+    assert_valid_pool("cn-heavy/0", haven);
+    assert_valid_pool("cn-heavy/xhv", haven);
+    assert_valid_pool("cn-pico", TURTLECOIN_ADDRESS);
+}
+
+#[test]
+fn test_chukwa_wallets() {
+    assert_valid_pool("argon2/chukwa", TURTLECOIN_ADDRESS);
+    assert_valid_pool("argon2/chukwav2", TURTLECOIN_ADDRESS);
+    assert_valid_pool("argon2/chukwav2", &format!("{}.rig1", TURTLECOIN_ADDRESS));
+}
+
+#[test]
+fn test_randomx_derivative_wallets() {
+    //the wallets are accepted, only the unverified algorithm is warned about
+    for (algorithm, wallet_address) in [
+        (
+            "rx/graft",
+            "G4JcfXW4sEa7imFBoULzQiESLQxj91qfQM9uajeoggv6TsUkWaUMYAnab3vHW92PRUhJd64KEg5hC52wBH2ogFWS8HNn9Nu",
+        ),
+        ("rx/keva", "VE4h5C6YYFZ3w6PsuNPqSqT5rx9c65EQTa"),
+    ] {
+        let issues = pool_issues(algorithm, wallet_address);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert_eq!(issues[0].key, "pool.algorithm");
+        assert_eq!(issues[0].severity, Severity::Warning);
+        assert_eq!(
+            issues[0].message,
+            format!(
+                "{} is not verified against a reference miner yet, the pool may reject the shares",
+                algorithm
+            )
+        );
+    }
+}
+
+#[test]
+fn test_privilege_issues() {
+    let mut config = mithril_config::default_config().unwrap();
+    config.pool_conf.wallet_address = mithril_config::donation_conf().wallet_address.clone();
+    config.privilege_conf.group = "root".to_string();
+
+    let issues = validate(&config, &toml_source(DEFAULT_CONFIG), &[]);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].key, "privileges.group");
+    assert_eq!(issues[0].message, "needs privileges.user");
+
+    config.privilege_conf.user = "mithril-no-such-user".to_string();
+    config.privilege_conf.group = String::new();
+    let issues = validate(&config, &toml_source(DEFAULT_CONFIG), &[]);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].severity, Severity::Error);
+    assert_eq!(issues[0].message, "unknown user mithril-no-such-user");
+}
+
+#[test]
+fn test_pool_address_problem() {
+    assert_eq!(pool_address_problem("xmrpool.eu:3333"), None);
+    assert_eq!(pool_address_problem("127.0.0.1:1"), None);
+    assert!(pool_address_problem("stratum+tcp://xmrpool.eu:3333").is_some());
+    assert!(pool_address_problem("xmrpool.eu").is_some());
+    assert!(pool_address_problem("xmrpool.eu:").is_some());
+    assert!(pool_address_problem("xmrpool.eu:0").is_some());
+    assert!(pool_address_problem("xmrpool.eu:70000").is_some());
+    assert!(pool_address_problem(":3333").is_some());
+}
+
+#[test]
+fn test_wallet_address_problem() {
+    let address = mithril_config::donation_conf().wallet_address.clone();
+    assert_eq!(wallet_address_problem(&address), None);
+    assert_eq!(wallet_address_problem(&format!("{}.rig1", address)), None);
+    assert_eq!(wallet_address_problem(&format!("{}+50000", address)), None);
+    let mut integrated = wallet::decode(&address).unwrap();
+    integrated.kind = AddressKind::Integrated;
+    integrated.payment_id = Some([7; 8]);
+    assert_eq!(wallet_address_problem(&wallet::encode(&integrated)), None);
+
+    assert_eq!(
+        wallet_address_problem(""),
+        Some("is empty, set your Monero address".to_string())
+    );
+    assert_eq!(
+        wallet_address_problem(&address.replace('y', "0")),
+        Some("contains '0', Monero addresses only use base58 characters".to_string())
+    );
+    assert_eq!(
+        wallet_address_problem(&address[..90]),
+        Some(
+            "has 90 characters, Monero addresses have 95 (106 for integrated addresses)"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        wallet_address_problem(&format!("{}{}", address, "1".repeat(11))),
+        Some("starts like a standard address but has 106 characters".to_string())
+    );
+    assert_eq!(
+        wallet_address_problem(&address.replacen('y', "z", 1)),
+        Some("has a wrong checksum, it is mistyped or incomplete".to_string())
+    );
+}
+
+//helper
+
+const TURTLECOIN_ADDRESS: &str = "TRTLv2AaXxPRdtYTkvK6uRDYhSMARcgHR1dAomcndndyYMTiEgaycgBLGGc864oYFm8LjyYRumokPf52sWj5ZyQFSyoaRBsrpy3";
+
+/// The default config with the wallet of the pool in the native format of the algorithm
+/// has no issues
+fn assert_valid_pool(algorithm: &str, wallet_address: &str) {
+    assert_eq!(
+        pool_issues(algorithm, wallet_address),
+        Vec::new(),
+        "{} with {}",
+        algorithm,
+        wallet_address
+    );
+}
+
+fn pool_issues(algorithm: &str, wallet_address: &str) -> Vec<ConfigIssue> {
+    let mut config = mithril_config::default_config().unwrap();
+    config.pool_conf.algorithm = algorithm.to_string();
+    config.pool_conf.wallet_address = wallet_address.to_string();
+    config.worker_conf.auto_tune = false;
+    config.worker_conf.num_threads = 1;
+    validate(&config, &toml_source(DEFAULT_CONFIG), &[])
+}
+
+fn toml_source(content: &str) -> ConfigSource<'_> {
+    ConfigSource {
+        name: "config.toml",
+        content,
+        format: ConfigFormat::Toml,
+    }
+}
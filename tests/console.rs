@@ -0,0 +1,154 @@
+extern crate mithril;
+
+use mithril::console::{
+    control_cmd, format_duration, hashrate_report, parse_key, stats_dump, stats_report, ConsoleCmd,
+    HashrateFormat,
+};
+use mithril::control::ControlCmd;
+use mithril::earnings::NetworkStats;
+use mithril::metric::history::{HashrateHistory, ThreadHistory};
+use mithril::metric::lifetime::LifetimeStats;
+use mithril::metric::stats::MinerStats;
+
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_parse_key() {
+    assert_eq!(parse_key(b'h'), Some(ConsoleCmd::HashrateReport));
+    assert_eq!(parse_key(b's'), Some(ConsoleCmd::ShareStats));
+    assert_eq!(parse_key(b'p'), Some(ConsoleCmd::TogglePause));
+    assert_eq!(parse_key(b'r'), Some(ConsoleCmd::Reconnect));
+    assert_eq!(parse_key(b'T'), Some(ConsoleCmd::Retune));
+    assert_eq!(parse_key(b'c'), Some(ConsoleCmd::NextProfile));
+    assert_eq!(parse_key(b'x'), None);
+    assert_eq!(parse_key(b'\n'), None);
+}
+
+#[test]
+fn test_control_cmd() {
+    assert_eq!(
+        control_cmd(ConsoleCmd::TogglePause),
+        Some(ControlCmd::TogglePause)
+    );
+    assert_eq!(
+        control_cmd(ConsoleCmd::Reconnect),
+        Some(ControlCmd::Reconnect)
+    );
+    assert_eq!(control_cmd(ConsoleCmd::Retune), Some(ControlCmd::Retune));
+    assert_eq!(
+        control_cmd(ConsoleCmd::NextProfile),
+        Some(ControlCmd::NextProfile)
+    );
+    assert_eq!(control_cmd(ConsoleCmd::HashrateReport), None);
+    assert_eq!(control_cmd(ConsoleCmd::ShareStats), None);
+}
+
+#[test]
+fn test_hashrate_report() {
+    let mut history = HashrateHistory::new(Duration::from_secs(15 * 60));
+    let start = Instant::now();
+    for i in 0..=60 {
+        history.record(start + Duration::from_secs(i), i * 100);
+    }
+    assert_eq!(
+        hashrate_report(&history, HashrateFormat::Verbose),
+        "hashrate 10s 100.0 H/s, 60s 100.0 H/s, 15m n/a"
+    );
+    assert_eq!(
+        hashrate_report(&history, HashrateFormat::Compact),
+        "hashrate 100.0/100.0/n/a H/s"
+    );
+}
+
+#[test]
+fn test_stats_report_not_connected() {
+    let stats = MinerStats::new();
+    assert_eq!(
+        stats_report(&stats, None),
+        "not connected, 0 reconnects\nshares 0 accepted, 0 rejected (0.0%), difficulty 0, effort n/a, average effort n/a\nlatency p50 n/a, p99 n/a"
+    );
+}
+
+#[test]
+fn test_stats_report_connected() {
+    let stats = MinerStats::new();
+    stats.connected("pool:3333");
+    stats.disconnected();
+    stats.connected("pool:3333");
+    stats.set_difficulty(1000);
+    stats.add_hashes(3000);
+    stats.share_accepted(1000);
+    stats.share_accepted(1000);
+    stats.share_accepted(1000);
+    stats.share_rejected();
+    assert_eq!(
+        stats_report(&stats, None),
+        "connected to pool:3333 for 0h 00m 00s, 1 reconnects\nshares 3 accepted, 1 rejected (25.0%), difficulty 1000, effort 0.0%, average effort 100.0%\nlatency p50 n/a, p99 n/a"
+    );
+}
+
+#[test]
+fn test_stats_report_with_earnings() {
+    let stats = MinerStats::new();
+    stats.set_difficulty(100_000);
+    let report = stats_report(&stats, Some(1000.0));
+    assert_eq!(report.lines().last(), Some("estimated 864.0 shares/day"));
+
+    stats.set_network_stats(
+        NetworkStats {
+            difficulty: 300e9,
+            reward: 0.6,
+        },
+        1.0,
+    );
+    let report = stats_report(&stats, Some(1000.0));
+    assert_eq!(
+        report.lines().last(),
+        Some("estimated 0.000171 XMR/day, 864.0 shares/day")
+    );
+}
+
+#[test]
+fn test_format_duration() {
+    assert_eq!(format_duration(Duration::from_secs(3723)), "1h 02m 03s");
+    assert_eq!(format_duration(Duration::from_secs(90000)), "25h 00m 00s");
+}
+
+#[test]
+fn test_stats_report_with_lifetime() {
+    let stats = MinerStats::new();
+    stats.set_lifetime_base(LifetimeStats {
+        hashes: 1000,
+        shares_accepted: 3,
+        shares_rejected: 1,
+        uptime_seconds: 7200,
+        best_share_difficulty: 90000,
+        since: 0,
+    });
+    let report = stats_report(&stats, None);
+    assert_eq!(
+        report.lines().last(),
+        Some("lifetime 1000 hashes, shares 3 accepted, 1 rejected, best share 90000, uptime 2h 00m 00s")
+    );
+}
+
+#[test]
+fn test_stats_dump() {
+    let stats = MinerStats::new();
+    let mut history = HashrateHistory::new(Duration::from_secs(15 * 60));
+    let mut threads = ThreadHistory::new(Duration::from_secs(10));
+    let start = Instant::now();
+    history.record(start, 0);
+    history.record(start + Duration::from_secs(10), 1000);
+    threads.record(start, vec![0, 0]);
+    threads.record(start + Duration::from_secs(10), vec![600, 400]);
+
+    let dump = stats_dump(&stats, &history, &threads);
+    let lines: Vec<&str> = dump.lines().collect();
+    assert_eq!(lines[0], "statistics dump");
+    assert_eq!(lines[1], "hashrate 10s 100.0 H/s, 60s n/a, 15m n/a");
+    assert_eq!(lines[2], "thread 0 60.0 H/s, 600 hashes");
+    assert_eq!(lines[3], "thread 1 40.0 H/s, 400 hashes");
+    assert_eq!(lines[4], "not connected, 0 reconnects");
+    assert_eq!(lines.last(), Some(&"memory not allocated yet"));
+}
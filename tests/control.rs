@@ -0,0 +1,38 @@
+extern crate mithril;
+extern crate serde_json;
+
+use mithril::control::{config_values, ControlCmd};
+use mithril::stratum::stratum_data::PoolConfig;
+use serde_json::json;
+
+#[test]
+fn test_config_values() {
+    assert_eq!(
+        config_values(&ControlCmd::SetThreads(6)),
+        vec![
+            ("worker", "num_threads", json!(6)),
+            ("worker", "auto_tune", json!(false)),
+        ]
+    );
+    assert_eq!(
+        config_values(&ControlCmd::Persist(Box::new(ControlCmd::SetThrottle(30)))),
+        vec![("worker", "throttle_percent", json!(30))]
+    );
+    assert_eq!(
+        config_values(&ControlCmd::SetProfile("eco".to_string())),
+        vec![("worker", "profile", json!("eco"))]
+    );
+    let mut pool_conf = PoolConfig::default();
+    pool_conf.pool_address = "pool:4444".to_string();
+    pool_conf.wallet_address = "wallet".to_string();
+    pool_conf.pool_password = "x".to_string();
+    assert_eq!(
+        config_values(&ControlCmd::SwitchPool(Box::new(pool_conf))),
+        vec![
+            ("pool", "pool_address", json!("pool:4444")),
+            ("pool", "wallet_address", json!("wallet")),
+            ("pool", "pool_password", json!("x")),
+        ]
+    );
+    assert!(config_values(&ControlCmd::ReloadConfig).is_empty());
+}
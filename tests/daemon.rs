@@ -0,0 +1,67 @@
+extern crate mithril;
+
+use mithril::daemon;
+use mithril::daemon::Pidfile;
+
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn test_pidfile_written_and_removed() {
+    let path = pidfile_path("written");
+    {
+        let pidfile = Pidfile::create(&path).unwrap();
+        assert_eq!(pidfile.path(), path.as_path());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+        assert_eq!(daemon::running_pid(&path), Some(std::process::id()));
+    }
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_pidfile_stale_overwritten() {
+    let path = pidfile_path("stale");
+    fs::write(&path, "2147483647\n").unwrap();
+    assert_eq!(daemon::running_pid(&path), None);
+
+    let _pidfile = Pidfile::create(&path).unwrap();
+
+    assert_eq!(daemon::running_pid(&path), Some(std::process::id()));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pidfile_of_running_process() {
+    let path = pidfile_path("running");
+    //init is always alive
+    fs::write(&path, "1\n").unwrap();
+
+    let err = Pidfile::create(&path).err().unwrap();
+
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "1\n");
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_pidfile_not_removed_if_taken_over() {
+    let path = pidfile_path("taken_over");
+    let pidfile = Pidfile::create(&path).unwrap();
+    fs::write(&path, "2147483647\n").unwrap();
+
+    drop(pidfile);
+
+    assert!(path.exists());
+    fs::remove_file(&path).unwrap();
+}
+
+//helper
+
+fn pidfile_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("mithril_test_{}.pid", name));
+    let _ = fs::remove_file(&path);
+    path
+}
@@ -0,0 +1,142 @@
+extern crate mithril;
+
+use mithril::byte_string;
+use mithril::dataset_server::{self, DatasetReply, DatasetRequest, DatasetServer};
+use mithril::pow::randomx::{self as rx, MemoryConfig, RandomX};
+use mithril::pow::{PowAlgorithm, PowMemory};
+use mithril::randomx::memory::{SeedMemory, VmMemory, VmMemoryAllocator};
+use mithril::randomx::pages::NumaPolicy;
+use mithril::randomx::RandomXConfig;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const SEED_HASH: &str = "c3f2c6e0b1d4a5968778695a4b3c2d1e0f9e8d7c6b5a49382716051423324150";
+const OTHER_SEED_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+
+#[test]
+fn test_mapped_dataset_hashes_like_a_computed_one() {
+    let variant = register_test_variant();
+    let dir = test_dir("mapped");
+    let server = DatasetServer::new(dir.clone(), 2, 2);
+
+    let files = match server.reply(&request_line(SEED_HASH)) {
+        DatasetReply::Ready(files) => files,
+        DatasetReply::Error(err) => panic!("{}", err),
+    };
+    assert!(files.cache.starts_with(&dir));
+    assert!(files.dataset.exists());
+
+    let mapped = Arc::new(dataset_server::map_files(&files, variant, SEED_HASH).unwrap());
+    assert!(mapped.seed_memory.blocks.shared());
+    assert_eq!(mapped.dataset_fill(), 1.0);
+    assert!(mapped.verify_checksum());
+    let key = byte_string::string_to_u8_array(SEED_HASH);
+    let seed_memory = SeedMemory::try_new_initialised_with(&key, Arc::new(variant.config()));
+    let computed = Arc::new(VmMemory::with_dataset(Arc::new(seed_memory.unwrap())));
+    let input = byte_string::string_to_u8_array(&"ab".repeat(76));
+    assert_eq!(
+        mapped.clone().hasher().hash(&input),
+        computed.hasher().hash(&input)
+    );
+
+    //a second request gets the same files
+    assert_eq!(
+        server.reply(&request_line(SEED_HASH)),
+        DatasetReply::Ready(files.clone())
+    );
+    let mut old_version = files.clone();
+    old_version.version = "0.1.0".to_string();
+    assert!(dataset_server::map_files(&old_version, variant, SEED_HASH).is_err());
+}
+
+#[test]
+fn test_dataset_server_keeps_the_latest_seeds() {
+    register_test_variant();
+    let dir = test_dir("seeds");
+    let server = DatasetServer::new(dir, 1, 1);
+
+    let first = match server.reply(&request_line(SEED_HASH)) {
+        DatasetReply::Ready(files) => files,
+        DatasetReply::Error(err) => panic!("{}", err),
+    };
+    assert!(matches!(
+        server.reply(&request_line(OTHER_SEED_HASH)),
+        DatasetReply::Ready(_)
+    ));
+    assert!(!first.cache.exists());
+    assert!(!first.dataset.exists());
+
+    let invalid = request_line("../../etc/passwd");
+    assert!(matches!(server.reply(&invalid), DatasetReply::Error(_)));
+    let unknown = serde_json::to_string(&DatasetRequest {
+        algorithm: "cn/r".to_string(),
+        seed_hash: SEED_HASH.to_string(),
+    })
+    .unwrap();
+    assert!(matches!(server.reply(&unknown), DatasetReply::Error(_)));
+    assert!(matches!(server.reply("{"), DatasetReply::Error(_)));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_miner_maps_the_dataset_of_the_server() {
+    register_test_variant();
+    let dir = test_dir("socket");
+    let socket = dir.join("dataset.sock");
+    let conf = dataset_server::DatasetServerConfig {
+        socket: socket.clone(),
+        dir: dir.clone(),
+        threads: 2,
+        seeds: 2,
+    };
+    std::thread::spawn(move || dataset_server::run(&conf));
+    while !socket.exists() {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    rx::set_memory(MemoryConfig {
+        numa: NumaPolicy::Local,
+        allocator: None,
+        dataset_server: Some(socket),
+        ..MemoryConfig::default()
+    });
+    let variant = rx::Variant::from_name("rx/dataset-test").unwrap();
+    let mut algorithm = RandomX::with_variant(variant, VmMemoryAllocator::with_mode(true));
+    assert!(algorithm.prepare(SEED_HASH, 0).unwrap().is_some());
+    rx::set_memory(MemoryConfig::default());
+    let usage = algorithm.memory().usage();
+    assert_eq!(usage.dataset_filled_bytes, usage.dataset_bytes);
+    assert!(usage.dataset_pages.unwrap().contains("mapped"));
+}
+
+//helper
+
+fn register_test_variant() -> rx::Variant {
+    rx::register_custom(&[rx::CustomVariant {
+        name: "rx/dataset-test".to_string(),
+        config: RandomXConfig {
+            argon_memory_kib: 1024,
+            argon_iterations: 1,
+            dataset_base_bytes: 1 << 20,
+            dataset_extra_bytes: 0,
+            ..RandomXConfig::monero()
+        },
+    }]);
+    rx::Variant::from_name("rx/dataset-test").unwrap()
+}
+
+fn request_line(seed_hash: &str) -> String {
+    serde_json::to_string(&DatasetRequest {
+        algorithm: "rx/dataset-test".to_string(),
+        seed_hash: seed_hash.to_string(),
+    })
+    .unwrap()
+}
+
+/// A directory per test, the server removes the files of a previous run
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mithril_test_dataset_server_{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
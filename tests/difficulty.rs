@@ -0,0 +1,177 @@
+#![allow(unknown_lints)]
+#![allow(clippy::unreadable_literal)]
+
+extern crate mithril;
+
+use mithril::difficulty::{
+    compact_target, difficulty_target, format_target, hash_bytes_value, hash_difficulty,
+    hash_value, job_difficulty, max_difficulty, meets_difficulty, target_difficulty, target_value,
+};
+
+#[test]
+fn test_hash_value() {
+    assert_eq!(
+        hash_value("c5c49db95a9da3f0802a34c6f97c364e7455fca7e41f72254fd4624dd2f91578"),
+        0x7815f9d24d62d44f
+    );
+    let mut hash = [0; 32];
+    hash[24..].copy_from_slice(&[0x4f, 0xd4, 0x62, 0x4d, 0xd2, 0xf9, 0x15, 0x78]);
+    assert_eq!(hash_bytes_value(&hash), 0x7815f9d24d62d44f);
+}
+
+#[test]
+fn test_target_value() {
+    assert_eq!(target_value("8b4f0100"), 368934881474191);
+    assert_eq!(target_value("ffffffffffffff00"), 0x00ffffffffffffff);
+    assert_eq!(target_value("ffffff7f"), u64::MAX / 2);
+    assert_eq!(target_value("ffffffffffffff7f"), i64::MAX as u64);
+    assert_eq!(target_value("0000000000000001"), 1 << 56);
+    assert_eq!(target_value("00000000"), 0);
+    assert_eq!(target_value("0000000000000000"), 0);
+}
+
+#[test]
+fn test_job_difficulty() {
+    assert_eq!(job_difficulty("8b4f0100"), 50000);
+    assert_eq!(job_difficulty("b88d0600"), 10000);
+    assert_eq!(job_difficulty("e4a50000"), 101134);
+    assert_eq!(job_difficulty("ffffffff"), 1);
+    //solo, above the 4 byte targets
+    assert_eq!(job_difficulty("fa3faa0300000000"), 300000004451);
+    //no hash meets the target 0
+    assert_eq!(job_difficulty("00000000"), u64::MAX);
+}
+
+#[test]
+fn test_hash_difficulty() {
+    assert_eq!(
+        hash_difficulty("c5c49db95a9da3f0802a34c6f97c364e7455fca7e41f72254fd4624dd2f91578"),
+        2
+    );
+    assert_eq!(
+        hash_difficulty("c5c49db95a9da3f0802a34c6f97c364e7455fca7e41f72250000000000000000"),
+        u64::MAX
+    );
+    assert_eq!(hash_difficulty(&"ff".repeat(32)), 1);
+    assert_eq!(hash_difficulty(&"00".repeat(32)), u64::MAX);
+    //not a hash
+    assert_eq!(hash_difficulty("c5c4"), 0);
+    assert_eq!(hash_difficulty(&"zz".repeat(32)), 0);
+}
+
+#[test]
+fn test_format_target() {
+    assert_eq!(format_target(u64::MAX), "ffffffffffffffff");
+    assert_eq!(
+        format_target(difficulty_target(300000000000)),
+        "fa3faa0300000000"
+    );
+    assert_eq!(difficulty_target(0), u64::MAX);
+    assert_eq!(target_difficulty(0), u64::MAX);
+}
+
+#[test]
+fn test_meets_difficulty() {
+    let mut hash = [0; 32];
+    assert!(meets_difficulty(&hash, u64::MAX));
+    //2^255 fits in 256 bits once
+    hash[31] = 0x80;
+    assert!(meets_difficulty(&hash, 1));
+    assert!(!meets_difficulty(&hash, 2));
+    //at the boundary the low bits decide, the top 64 bits alone do not
+    let third = u64::MAX / 3;
+    let mut hash = [0; 32];
+    hash[24..].copy_from_slice(&3u64.to_le_bytes());
+    hash[16] = 1;
+    assert!(meets_difficulty(&hash, third));
+    hash[16] = 4;
+    assert!(!meets_difficulty(&hash, third));
+    assert!(meets_difficulty(&hash, third - 1));
+    assert_eq!(max_difficulty(&hash), third - 1);
+}
+
+#[test]
+fn test_compact_target_property() {
+    for target in Numbers::new(1)
+        .take(10000)
+        .map(|n| n as u32)
+        .filter(|t| *t > 0)
+    {
+        let difficulty = target_difficulty(compact_target(target));
+        //the difficulty of the pool, as the reference miners compute it
+        assert_eq!(
+            difficulty,
+            u64::from(u32::MAX) / u64::from(target),
+            "target {:08x}",
+            target
+        );
+    }
+}
+
+#[test]
+fn test_difficulty_target_property() {
+    for difficulty in Numbers::new(2).take(10000).filter(|d| *d > 0) {
+        let target = difficulty_target(difficulty);
+        assert!(
+            target_difficulty(target) >= difficulty,
+            "difficulty {}",
+            difficulty
+        );
+        //a hash just below the target meets the difficulty on the top 64 bits
+        assert!(u128::from(target) * u128::from(difficulty) <= u128::from(u64::MAX));
+    }
+}
+
+#[test]
+fn test_max_difficulty_property() {
+    let mut numbers = Numbers::new(3);
+    for _ in 0..2000 {
+        let mut hash = [0; 32];
+        for limb in hash.chunks_mut(8) {
+            limb.copy_from_slice(&numbers.next().unwrap().to_le_bytes());
+        }
+        //some hashes with a high difficulty
+        let shift = numbers.next().unwrap() % 64;
+        let top = u64::from_le_bytes(hash[24..].try_into().unwrap()) >> shift;
+        hash[24..].copy_from_slice(&top.to_le_bytes());
+
+        let max = max_difficulty(&hash);
+        assert!(meets_difficulty(&hash, max));
+        assert!(max == u64::MAX || !meets_difficulty(&hash, max + 1));
+        //the 64 bit estimate of the top bits brackets it
+        match u64::MAX.checked_div(top) {
+            Some(estimate) => {
+                assert!(max <= estimate);
+                assert!(max >= u64::MAX / top.saturating_add(1));
+            }
+            None => assert_eq!(max, u64::MAX),
+        }
+
+        //(2^256 - 1) / (top * 2^192) is u64::MAX / top
+        let mut aligned = [0; 32];
+        aligned[24..].copy_from_slice(&top.max(1).to_le_bytes());
+        assert_eq!(max_difficulty(&aligned), u64::MAX / top.max(1));
+    }
+}
+
+//helper
+
+/// xorshift64, the same numbers in every run
+struct Numbers(u64);
+
+impl Numbers {
+    fn new(seed: u64) -> Numbers {
+        Numbers(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+    }
+}
+
+impl Iterator for Numbers {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        Some(self.0)
+    }
+}
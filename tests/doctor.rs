@@ -0,0 +1,162 @@
+extern crate mithril;
+
+use mithril::cgroup::FULL_MEMORY_BYTES;
+use mithril::doctor;
+use mithril::doctor::{Check, Status, Ulimits};
+
+use serde_json::json;
+use std::io;
+use std::time::Duration;
+
+#[test]
+fn test_check_cpu_features() {
+    let checks = doctor::check_cpu_features(true, true);
+    assert!(checks.iter().all(|c| c.status == Status::Pass));
+
+    let checks = doctor::check_cpu_features(false, false);
+    assert_eq!(statuses(&checks), vec![Status::Fail, Status::Warn]);
+    assert!(checks[0].fix.is_some());
+}
+
+#[test]
+fn test_check_huge_pages() {
+    let check = doctor::check_huge_pages(8, 8, Some("madvise"));
+    assert_eq!(check.status, Status::Pass);
+    assert_eq!(check.fix, None);
+
+    let check = doctor::check_huge_pages(2, 8, Some("always"));
+    assert_eq!(check.status, Status::Warn);
+    assert!(check
+        .fix
+        .unwrap()
+        .starts_with("sudo sysctl -w vm.nr_hugepages=8"));
+
+    let check = doctor::check_huge_pages(8, 8, Some("never"));
+    assert_eq!(check.status, Status::Warn);
+    assert_eq!(
+        check.fix.unwrap(),
+        "echo madvise | sudo tee /sys/kernel/mm/transparent_hugepage/enabled"
+    );
+}
+
+#[test]
+fn test_check_memory() {
+    let gb = 1024 * 1024 * 1024;
+    assert_eq!(
+        doctor::check_memory(Some(8 * gb), None).status,
+        Status::Pass
+    );
+    assert_eq!(doctor::check_memory(None, None).status, Status::Pass);
+
+    let low = doctor::check_memory(Some(gb), None);
+    assert_eq!(low.status, Status::Warn);
+    assert!(low.fix.unwrap().starts_with("close other programs"));
+
+    let limited = doctor::check_memory(Some(8 * gb), Some(gb));
+    assert_eq!(limited.status, Status::Warn);
+    assert!(limited.fix.unwrap().contains("docker run --memory"));
+}
+
+#[test]
+fn test_check_msr_and_numa() {
+    assert_eq!(doctor::check_msr(true, true).status, Status::Pass);
+    assert_eq!(
+        doctor::check_msr(false, false).fix,
+        Some("sudo modprobe msr".to_string())
+    );
+    assert_eq!(doctor::check_msr(true, false).status, Status::Warn);
+
+    assert_eq!(doctor::check_numa(1).status, Status::Pass);
+    let check = doctor::check_numa(2);
+    assert_eq!(check.status, Status::Warn);
+    assert_eq!(
+        check.fix,
+        Some("set numa = \"interleave\" in the [memory] section".to_string())
+    );
+}
+
+#[test]
+fn test_check_ulimits() {
+    let unlimited = Ulimits {
+        virtual_bytes: None,
+        data_bytes: Some(FULL_MEMORY_BYTES * 2),
+    };
+    assert_eq!(doctor::check_ulimits(&unlimited).status, Status::Pass);
+
+    let limited = Ulimits {
+        virtual_bytes: Some(1024 * 1024 * 1024),
+        data_bytes: None,
+    };
+    let check = doctor::check_ulimits(&limited);
+    assert_eq!(check.status, Status::Fail);
+    assert!(check.fix.unwrap().starts_with("ulimit -v unlimited ("));
+}
+
+#[test]
+fn test_check_pool() {
+    let check = doctor::check_pool("pool.example:3333", Ok(Duration::from_millis(42)));
+    assert_eq!(check.status, Status::Pass);
+    assert_eq!(check.message, "pool.example:3333 reachable in 42 ms");
+
+    let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+    let check = doctor::check_pool("pool.example:3333", Err(refused));
+    assert_eq!(check.status, Status::Fail);
+    assert!(check.fix.unwrap().ends_with("nc -vz pool.example 3333"));
+}
+
+#[test]
+fn test_meminfo_field() {
+    let meminfo =
+        "MemAvailable:    5320512 kB\nHugePages_Total:      16\nHugePages_Free:        4\n";
+    assert_eq!(
+        doctor::meminfo_field(meminfo, "MemAvailable"),
+        Some(5320512)
+    );
+    assert_eq!(doctor::meminfo_field(meminfo, "HugePages_Total"), Some(16));
+    assert_eq!(doctor::meminfo_field(meminfo, "HugePages"), None);
+}
+
+#[test]
+fn test_format_report() {
+    let checks = vec![
+        doctor::check_numa(1),
+        doctor::check_msr(false, false),
+        doctor::check_pool("pool.example:3333", Err(io::Error::other("refused"))),
+    ];
+    let report = doctor::format_report(&checks);
+    assert!(report.starts_with("PASS  NUMA: single node\nWARN  MSR: "));
+    assert!(report.contains("\n      fix: sudo modprobe msr\n"));
+    assert!(report.ends_with("\n\n2 problems found"));
+    assert!(doctor::has_failures(&checks));
+
+    let passed = vec![doctor::check_numa(1)];
+    assert!(doctor::format_report(&passed).ends_with("all checks passed"));
+    assert!(!doctor::has_failures(&passed));
+}
+
+#[test]
+fn test_report_json() {
+    let checks = vec![doctor::check_numa(1), doctor::check_msr(true, false)];
+    assert_eq!(
+        doctor::report_json(&checks),
+        json!({
+            "checks": [
+                {"name": "NUMA", "status": "pass", "message": "single node", "fix": null},
+                {
+                    "name": "MSR",
+                    "status": "warn",
+                    "message": "/dev/cpu/0/msr is not writable",
+                    "fix": "run the MSR tuning as root"
+                }
+            ],
+            "problems": 1,
+            "failed": false,
+        })
+    );
+}
+
+//helper
+
+fn statuses(checks: &[Check]) -> Vec<Status> {
+    checks.iter().map(|c| c.status).collect()
+}
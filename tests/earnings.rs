@@ -0,0 +1,89 @@
+extern crate mithril;
+
+use mithril::earnings::{
+    estimate, format_estimate, parse_daemon_difficulty, parse_daemon_reward, parse_network_stats,
+    Estimate, NetworkStats,
+};
+
+#[test]
+fn test_estimate() {
+    let network = NetworkStats {
+        difficulty: 86400.0 * 1000.0,
+        reward: 0.5,
+    };
+    //1000 H/s find one block per day
+    let full = estimate(1000.0, 10_000, Some(&network), 0.0);
+    assert_eq!(full.shares_per_day, Some(8640.0));
+    assert_eq!(full.xmr_per_day, Some(0.5));
+
+    let with_fee = estimate(1000.0, 10_000, Some(&network), 10.0);
+    assert_eq!(with_fee.xmr_per_day, Some(0.45));
+}
+
+#[test]
+fn test_estimate_without_data() {
+    assert_eq!(
+        estimate(1000.0, 0, None, 0.0),
+        Estimate {
+            shares_per_day: None,
+            xmr_per_day: None,
+        }
+    );
+}
+
+#[test]
+fn test_format_estimate() {
+    assert_eq!(
+        format_estimate(&Estimate {
+            shares_per_day: Some(8640.0),
+            xmr_per_day: Some(0.000123456),
+        }),
+        "estimated 0.000123 XMR/day, 8640.0 shares/day"
+    );
+    assert_eq!(
+        format_estimate(&Estimate {
+            shares_per_day: None,
+            xmr_per_day: Some(0.5),
+        }),
+        "estimated 0.500000 XMR/day, n/a shares/day"
+    );
+    assert_eq!(
+        format_estimate(&Estimate {
+            shares_per_day: Some(12.34),
+            xmr_per_day: None,
+        }),
+        "estimated 12.3 shares/day"
+    );
+}
+
+#[test]
+fn test_parse_network_stats() {
+    assert_eq!(
+        parse_network_stats(r#"{"difficulty": 3.2e11, "reward": 0.6}"#),
+        Ok(NetworkStats {
+            difficulty: 3.2e11,
+            reward: 0.6,
+        })
+    );
+    assert!(parse_network_stats(r#"{"difficulty": 3.2e11}"#).is_err());
+    assert!(parse_network_stats("not json").is_err());
+}
+
+#[test]
+fn test_parse_daemon_responses() {
+    let info = r#"{"id": "0", "jsonrpc": "2.0", "result": {"difficulty": 320000000000, "height": 3000000, "status": "OK"}}"#;
+    assert_eq!(parse_daemon_difficulty(info), Ok(320000000000.0));
+    let header = r#"{"id": "0", "jsonrpc": "2.0", "result": {"block_header": {"height": 2999999, "reward": 600000000000}, "status": "OK"}}"#;
+    assert_eq!(parse_daemon_reward(header), Ok(0.6));
+}
+
+#[test]
+fn test_parse_daemon_errors() {
+    let error = r#"{"id": "0", "jsonrpc": "2.0", "error": {"code": -32601, "message": "Method not found"}}"#;
+    assert_eq!(
+        parse_daemon_difficulty(error),
+        Err("daemon error: Method not found".to_string())
+    );
+    assert!(parse_daemon_reward(r#"{"result": {}}"#).is_err());
+    assert!(parse_daemon_difficulty("").is_err());
+}
@@ -0,0 +1,60 @@
+extern crate mithril;
+
+use mithril::error::{MithrilError, Recovery};
+use mithril::randomx::memory::{DatasetError, MemoryError};
+use mithril::stratum::StratumError;
+use std::io;
+
+#[test]
+fn test_recovery() {
+    let disconnected = MithrilError::from(StratumError::from(io::Error::other("reset")));
+    assert_eq!(disconnected.recovery(true), Recovery::Retry);
+    assert_eq!(
+        MithrilError::ChannelClosed("timer").recovery(false),
+        Recovery::Retry
+    );
+    assert_eq!(
+        MithrilError::Config("error: no pool".to_string()).recovery(true),
+        Recovery::Exit
+    );
+
+    assert_eq!(memory_error().recovery(true), Recovery::LightMode);
+    assert_eq!(memory_error().recovery(false), Recovery::Exit);
+
+    let dataset_error = MithrilError::from(DatasetError::ItemOutOfRange {
+        item: 40000000,
+        items: 34078719,
+    });
+    assert_eq!(dataset_error.recovery(true), Recovery::LightMode);
+    assert_eq!(dataset_error.recovery(false), Recovery::Exit);
+    assert_eq!(
+        dataset_error.to_string(),
+        "hashing failed: dataset item 40000000 is out of range, the dataset has 34078719 items"
+    );
+
+    let stalled = MithrilError::from(DatasetError::InitStalled { seconds: 120 });
+    assert_eq!(stalled.recovery(true), Recovery::LightMode);
+    assert_eq!(stalled.recovery(false), Recovery::Exit);
+}
+
+#[test]
+fn test_display() {
+    assert_eq!(
+        MithrilError::from(StratumError::ChannelClosed("command")).to_string(),
+        "pool connection failed: the command channel is closed"
+    );
+    assert_eq!(
+        memory_error().to_string(),
+        "memory init failed: Argon2 failed: out of memory"
+    );
+    assert_eq!(
+        MithrilError::Config("error: no pool".to_string()).to_string(),
+        "error: no pool"
+    );
+}
+
+//helper
+
+fn memory_error() -> MithrilError {
+    MemoryError::Argon2("out of memory".to_string()).into()
+}
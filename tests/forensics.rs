@@ -0,0 +1,166 @@
+extern crate mithril;
+extern crate serde_json;
+
+use mithril::byte_string;
+use mithril::forensics::{self, classify, ForensicsConfig, JobContext, RejectCause};
+use mithril::pow;
+use mithril::randomx::memory::VmMemoryAllocator;
+use mithril::stratum::stratum_data::Share;
+use mithril::worker::worker_pool;
+use serde_json::Value;
+
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+#[test]
+fn test_classify() {
+    assert_eq!(
+        classify("Low difficulty share", true, 5000, 1000, Some(false)),
+        RejectCause::HashMismatch
+    );
+    assert_eq!(
+        classify("Low difficulty share", true, 500, 1000, Some(true)),
+        RejectCause::LowDifficulty
+    );
+    //the pool raised the difficulty meanwhile
+    assert_eq!(
+        classify("Low difficulty share", true, 5000, 1000, Some(true)),
+        RejectCause::LowDifficulty
+    );
+    assert_eq!(
+        classify("Block expired", true, 5000, 1000, None),
+        RejectCause::Stale
+    );
+    assert_eq!(
+        classify("Invalid share", false, 5000, 1000, Some(true)),
+        RejectCause::Stale
+    );
+    assert_eq!(
+        classify("Duplicate share", true, 5000, 1000, Some(true)),
+        RejectCause::Duplicate
+    );
+    assert_eq!(
+        classify("IP banned", true, 5000, 1000, None),
+        RejectCause::Unknown
+    );
+}
+
+#[test]
+fn test_disabled_forensics_ignores_rejects() {
+    let forensics = forensics::start(conf(""));
+    forensics.share_rejected(
+        &share("j1", "00000000", "00"),
+        "stale",
+        Duration::ZERO,
+        "pool",
+    );
+}
+
+#[test]
+fn test_capture_rejects() {
+    let dir = std::env::temp_dir().join("mithril_test_forensics");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("rejects.jsonl");
+    let forensics = forensics::start(conf(path.to_str().unwrap()));
+
+    let mut algorithm = pow::new_algorithm("cn-pico", VmMemoryAllocator::initial).unwrap();
+    algorithm.prepare("", 0).unwrap();
+    let memory = algorithm.memory();
+    let blob = "07".repeat(76);
+    let nonce = "2a000000";
+    let input = byte_string::string_to_u8_array(&worker_pool::with_nonce(&blob, nonce));
+    let hash = byte_string::u8_array_to_string(&memory.clone().hasher().hash(&input));
+    forensics.job(JobContext {
+        job_id: "j1".to_string(),
+        blob: blob.clone(),
+        target: "ffffffff".to_string(),
+        seed_hash: String::new(),
+        height: 7,
+        received: SystemTime::now(),
+        memory: Arc::downgrade(&memory),
+    });
+
+    forensics.share_rejected(
+        &share("j1", nonce, &hash),
+        "Low difficulty share",
+        Duration::from_millis(40),
+        "pool:3333",
+    );
+    forensics.share_rejected(
+        &share("j1", nonce, &"ab".repeat(32)),
+        "Low difficulty share",
+        Duration::from_millis(40),
+        "pool:3333",
+    );
+    forensics.share_rejected(
+        &share("j0", nonce, &hash),
+        "Block expired",
+        Duration::ZERO,
+        "pool:3333",
+    );
+
+    let lines = wait_for_lines(&path, 3);
+    assert_eq!(lines[0]["cause"], "low_difficulty");
+    assert_eq!(lines[0]["job_id"], "j1");
+    assert_eq!(lines[0]["blob"], blob.as_str());
+    assert_eq!(lines[0]["target"], "ffffffff");
+    assert_eq!(lines[0]["height"], 7);
+    assert_eq!(lines[0]["nonce"], nonce);
+    assert_eq!(lines[0]["hash"], hash.as_str());
+    assert_eq!(lines[0]["recomputed_hash"], hash.as_str());
+    assert_eq!(lines[0]["pool"], "pool:3333");
+    assert_eq!(lines[0]["error"], "Low difficulty share");
+    assert_eq!(lines[0]["latency_ms"], 40);
+    assert_eq!(lines[0]["current_job"], true);
+    assert!(lines[0]["submitted"].is_string());
+    assert!(lines[0]["job_received"].is_string());
+
+    assert_eq!(lines[1]["cause"], "hash_mismatch");
+    assert_eq!(lines[1]["recomputed_hash"], hash.as_str());
+
+    //a job that is not known any more
+    assert_eq!(lines[2]["cause"], "stale");
+    assert_eq!(lines[2]["blob"], Value::Null);
+    assert_eq!(lines[2]["recomputed_hash"], Value::Null);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+//helper
+
+fn conf(file: &str) -> ForensicsConfig {
+    ForensicsConfig {
+        file: file.to_string(),
+        max_size_mb: 1,
+        keep_files: 1,
+        verify: true,
+    }
+}
+
+fn share(job_id: &str, nonce: &str, hash: &str) -> Share {
+    Share {
+        miner_id: "miner".to_string(),
+        job_id: job_id.to_string(),
+        nonce: nonce.to_string(),
+        hash: hash.to_string(),
+        difficulty: 1,
+    }
+}
+
+fn wait_for_lines(path: &std::path::Path, count: usize) -> Vec<Value> {
+    let start = Instant::now();
+    loop {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        let lines: Vec<Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        if lines.len() >= count || start.elapsed() > Duration::from_secs(10) {
+            return lines;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
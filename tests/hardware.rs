@@ -0,0 +1,74 @@
+extern crate mithril;
+
+use mithril::hardware::{self, cache_name, format_summary, thread_cores, HardwareSummary};
+
+#[test]
+fn test_cache_name() {
+    assert_eq!(cache_name("1\n", "Data\n"), "L1d");
+    assert_eq!(cache_name("1", "Instruction"), "L1i");
+    assert_eq!(cache_name("3", "Unified"), "L3");
+}
+
+#[test]
+fn test_thread_cores() {
+    assert_eq!(
+        thread_cores(5, true, 4),
+        vec![Some(0), Some(1), Some(2), Some(3), Some(0)]
+    );
+    assert_eq!(thread_cores(2, false, 4), vec![None, None]);
+    assert!(thread_cores(0, true, 4).is_empty());
+}
+
+#[test]
+fn test_format_summary() {
+    let summary = HardwareSummary {
+        cpu_model: "AMD Ryzen 7 3700X".to_string(),
+        physical_cores: 8,
+        logical_cores: 16,
+        caches: vec![
+            ("L1d".to_string(), 32 * 1024),
+            ("L3".to_string(), 16 * 1024 * 1024),
+        ],
+        numa_nodes: 1,
+        huge_pages: Some((1168, 1152)),
+        transparent_huge_pages: Some("madvise".to_string()),
+        cpu_features: vec!["aes", "avx2"],
+        memory_mode: "full".to_string(),
+        thread_cores: vec![Some(0), Some(1)],
+    };
+    assert_eq!(
+        format_summary(&summary),
+        vec![
+            "cpu: AMD Ryzen 7 3700X (8 cores, 16 threads)",
+            "caches: L1d 32 KB, L3 16 MB",
+            "numa nodes: 1",
+            "huge pages: 1168 reserved, 1152 free, transparent huge pages madvise",
+            "cpu features: aes, avx2",
+            "memory mode: full",
+            "thread affinity: 0->0 1->1",
+        ]
+    );
+
+    let summary = HardwareSummary {
+        caches: Vec::new(),
+        huge_pages: None,
+        transparent_huge_pages: None,
+        cpu_features: Vec::new(),
+        thread_cores: vec![None],
+        ..summary
+    };
+    let lines = format_summary(&summary);
+    assert_eq!(lines[1], "numa nodes: 1");
+    assert_eq!(lines[2], "huge pages: unknown");
+    assert_eq!(lines[3], "cpu features: none");
+    assert_eq!(lines[5], "thread affinity: 0->any");
+}
+
+#[test]
+fn test_probe() {
+    let summary = hardware::probe("light", 2, false);
+    assert!(summary.logical_cores >= 1);
+    assert!(summary.numa_nodes >= 1);
+    assert_eq!(summary.memory_mode, "light");
+    assert_eq!(summary.thread_cores, vec![None, None]);
+}
@@ -0,0 +1,102 @@
+extern crate mithril;
+extern crate serde_json;
+
+use mithril::hash::{self, HashConfig};
+use serde_json::json;
+
+const CN_BLOB: &str = "0305a0dbd6bf05cf16e503f3a66f78007cbf34144332ecbfc22ed95c8700383b309ace1923a0964b00000008ba939a62724c0d7581fce5761e9d8a0e6a1c3f924fdd8493d1115649c05eb601";
+const SEED_HASH: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+
+#[test]
+fn test_hash_randomx() {
+    let mut conf = config(
+        "rx/0",
+        &format!("0c0c{}00000000{}05", "ab".repeat(37), "cd".repeat(33)),
+    );
+    conf.seed_hash = SEED_HASH.to_string();
+    conf.nonce = Some("01020304".to_string());
+    conf.target = Some("ffffff7f".to_string());
+    let report = hash::run(&conf).unwrap();
+    assert_eq!(report.algorithm, "rx/0");
+    assert_eq!(
+        report.hash,
+        "b27f25eeecc8f1bb8c8b67800b9ccf51670bcb0570ba364a3c745ca5fc721d44"
+    );
+    assert_eq!(report.difficulty, 3);
+    assert_eq!(report.valid, Some(true));
+}
+
+#[test]
+fn test_hash_chukwa() {
+    let mut conf = config("argon2/chukwa", CN_BLOB);
+    let report = hash::run(&conf).unwrap();
+    assert_eq!(
+        report.hash,
+        "c158a105ae75c7561cfd029083a47a87653d51f914128e21c1971d8b10c49034"
+    );
+    assert_eq!(report.valid, None);
+    assert!(!hash::format_report(&report).contains("target"));
+
+    conf.target = Some("00000001".to_string());
+    let report = hash::run(&conf).unwrap();
+    assert_eq!(report.valid, Some(false));
+    assert!(hash::format_report(&report).contains("target      NOT met"));
+}
+
+#[test]
+fn test_hash_invalid_config() {
+    assert_eq!(
+        hash::run(&config("cn/0", CN_BLOB)),
+        Err("unknown algorithm cn/0".to_string())
+    );
+    assert_eq!(
+        hash::run(&config("rx/0", CN_BLOB)),
+        Err("rx/0 needs a seed_hash of 32 bytes".to_string())
+    );
+    assert_eq!(
+        hash::run(&config("cn-pico", "0g")),
+        Err("blob is not hex".to_string())
+    );
+
+    let mut conf = config("cn-pico", "00");
+    conf.nonce = Some("01020304".to_string());
+    assert_eq!(
+        hash::run(&conf),
+        Err("blob has to be at least 43 bytes for a nonce".to_string())
+    );
+    conf.nonce = Some("0102".to_string());
+    assert_eq!(hash::run(&conf), Err("nonce has to be 4 bytes".to_string()));
+
+    let mut conf = config("cn-pico", CN_BLOB);
+    conf.target = Some("ff".to_string());
+    assert_eq!(
+        hash::run(&conf),
+        Err("target has to be 4 or 8 bytes".to_string())
+    );
+}
+
+#[test]
+fn test_report_json() {
+    let report = hash::run(&config("argon2/chukwa", CN_BLOB)).unwrap();
+    let json = hash::report_json(&report);
+    assert_eq!(json["version"], json!(env!("CARGO_PKG_VERSION")));
+    assert_eq!(json["algorithm"], json!("argon2/chukwa"));
+    assert_eq!(json["hash"], json!(report.hash));
+    assert_eq!(json["difficulty"], json!(4));
+    assert_eq!(json["valid"], serde_json::Value::Null);
+    assert!(json["init_seconds"].is_f64());
+    assert!(json["hash_seconds"].is_f64());
+}
+
+//helper
+
+fn config(algorithm: &str, blob: &str) -> HashConfig {
+    HashConfig {
+        algorithm: algorithm.to_string(),
+        blob: blob.to_string(),
+        nonce: None,
+        seed_hash: String::new(),
+        height: 0,
+        target: None,
+    }
+}
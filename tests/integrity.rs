@@ -0,0 +1,77 @@
+extern crate mithril;
+
+use mithril::integrity::{check_memory, IntegrityConfig};
+use mithril::metric::stats::MinerStats;
+use mithril::pow::{self, PowMemory};
+use mithril::randomx::config::RandomXConfig;
+use mithril::randomx::memory::{SeedMemory, VmMemory, VmMemoryAllocator};
+use std::sync::Arc;
+
+#[test]
+fn test_check_memory() {
+    let memory = small_dataset();
+    memory.init_dataset(2);
+    let stats = MinerStats::new();
+    let conf = conf(false);
+    assert_eq!(check_memory(memory.as_ref(), &conf, &stats, 42), 0);
+
+    memory.dataset_memory.write().unwrap()[7].as_mut().unwrap()[0] ^= 1;
+    let check = memory.verify(conf.samples, 42).unwrap();
+    assert!(!check.checksum_matches);
+    assert_eq!(check.samples, 64);
+    //the checksum finds it even if no sample hits the item
+    assert!(check.errors() >= 1);
+    assert_eq!(
+        check_memory(memory.as_ref(), &conf, &stats, 42),
+        check.errors()
+    );
+    assert_eq!(stats.dataset_errors(), check.errors());
+    //without rebuild the corruption stays
+    assert!(!memory.verify_checksum());
+}
+
+#[test]
+fn test_check_memory_rebuild() {
+    let memory = small_dataset();
+    memory.init_dataset(2);
+    memory.dataset_memory.write().unwrap()[3].as_mut().unwrap()[6] ^= 1 << 40;
+    let stats = MinerStats::new();
+    assert!(check_memory(memory.as_ref(), &conf(true), &stats, 1) >= 1);
+    assert!(memory.verify_checksum());
+    assert_eq!(check_memory(memory.as_ref(), &conf(true), &stats, 1), 0);
+}
+
+#[test]
+fn test_check_memory_without_dataset() {
+    let mut algorithm = pow::new_algorithm("cn-pico", VmMemoryAllocator::initial).unwrap();
+    algorithm.prepare("", 0).unwrap();
+    let stats = MinerStats::new();
+    assert_eq!(
+        check_memory(algorithm.memory().as_ref(), &conf(true), &stats, 1),
+        0
+    );
+    assert!(VmMemory::no_memory().verify(64, 1).is_none());
+}
+
+//helper
+
+fn conf(rebuild: bool) -> IntegrityConfig {
+    IntegrityConfig {
+        enabled: true,
+        interval_minutes: 30,
+        samples: 64,
+        rebuild,
+    }
+}
+
+fn small_dataset() -> Arc<VmMemory> {
+    let config = RandomXConfig {
+        argon_memory_kib: 1024,
+        argon_iterations: 1,
+        dataset_base_bytes: 1 << 20,
+        dataset_extra_bytes: 0,
+        ..RandomXConfig::monero()
+    };
+    let seed_memory = SeedMemory::try_new_initialised_with(b"integrity", Arc::new(config));
+    Arc::new(VmMemory::with_dataset(Arc::new(seed_memory.unwrap())))
+}
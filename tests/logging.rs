@@ -0,0 +1,173 @@
+extern crate log;
+extern crate mithril;
+
+use log::kv::Value;
+use log::{Level, LevelFilter, Record};
+use mithril::logging::{format_timestamp, json_line, module_level, quiet_allows, RotatingFile};
+
+use std::fs;
+use std::time::Duration;
+
+#[test]
+fn test_module_level() {
+    let modules = vec![
+        ("randomx".to_string(), LevelFilter::Warn),
+        ("stratum".to_string(), LevelFilter::Debug),
+        ("stratum::solo".to_string(), LevelFilter::Trace),
+    ];
+    assert_eq!(
+        module_level(&modules, "mithril::stratum"),
+        Some(LevelFilter::Debug)
+    );
+    assert_eq!(
+        module_level(&modules, "mithril::stratum::proxy"),
+        Some(LevelFilter::Debug)
+    );
+    //the longest module
+    assert_eq!(
+        module_level(&modules, "mithril::stratum::solo"),
+        Some(LevelFilter::Trace)
+    );
+    assert_eq!(
+        module_level(&modules, "mithril_randomx::vm"),
+        Some(LevelFilter::Warn)
+    );
+    assert_eq!(module_level(&modules, "mithril::stratum_data"), None);
+    assert_eq!(module_level(&modules, "mithril"), None);
+    assert_eq!(module_level(&[], "mithril::stratum"), None);
+}
+
+#[test]
+fn test_quiet_allows() {
+    let record = |level: Level, event: &'static str| {
+        let kvs: [(&str, Value); 1] = [("event", Value::from(event))];
+        quiet_allows(&Record::builder().level(level).key_values(&kvs).build())
+    };
+    assert!(record(Level::Info, "share_accepted"));
+    assert!(record(Level::Warn, "share_rejected"));
+    assert!(record(Level::Info, "block_found"));
+    assert!(record(Level::Error, "stratum_error"));
+    assert!(!record(Level::Info, "job"));
+    assert!(!record(Level::Warn, "pool_disconnect"));
+    assert!(!quiet_allows(
+        &Record::builder()
+            .args(format_args!("stopping workers"))
+            .level(Level::Info)
+            .build()
+    ));
+}
+
+#[test]
+fn test_format_timestamp() {
+    assert_eq!(format_timestamp(Duration::ZERO), "1970-01-01T00:00:00.000Z");
+    assert_eq!(
+        format_timestamp(Duration::from_millis(1588766645123)),
+        "2020-05-06T12:04:05.123Z"
+    );
+    assert_eq!(
+        format_timestamp(Duration::from_secs(951782400)),
+        "2000-02-29T00:00:00.000Z"
+    );
+}
+
+#[test]
+fn test_json_line_without_key_values() {
+    let line = json_line(
+        &Record::builder()
+            .args(format_args!("stopping workers"))
+            .level(Level::Info)
+            .target("mithril::worker")
+            .build(),
+        Duration::ZERO,
+    );
+    let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(json["timestamp"], "1970-01-01T00:00:00.000Z");
+    assert_eq!(json["level"], "INFO");
+    assert_eq!(json["target"], "mithril::worker");
+    assert_eq!(json["event"], "log");
+    assert_eq!(json["message"], "stopping workers");
+}
+
+#[test]
+fn test_json_line_with_key_values() {
+    let kvs: [(&str, Value); 4] = [
+        ("event", Value::from("share_accepted")),
+        ("difficulty", Value::from(50000u64)),
+        ("effort", Value::from(0.5f64)),
+        ("job_id", Value::from("job \"1\"")),
+    ];
+    let line = json_line(
+        &Record::builder()
+            .args(format_args!("Share accepted"))
+            .level(Level::Warn)
+            .key_values(&kvs)
+            .build(),
+        Duration::ZERO,
+    );
+    let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(json["level"], "WARN");
+    assert_eq!(json["event"], "share_accepted");
+    assert_eq!(json["difficulty"], 50000);
+    assert_eq!(json["effort"], 0.5);
+    assert_eq!(json["job_id"], "job \"1\"");
+    assert!(!line.contains('\n'));
+}
+
+#[test]
+fn test_rotate_by_size() {
+    let dir = test_dir("mithril_test_log_rotate_size");
+    let path = dir.join("mithril.json");
+
+    let mut file = RotatingFile::open(&path, 20, None, 2).unwrap();
+    for line in ["line 1 ....", "line 2 ....", "line 3 ....", "line 4 ...."].iter() {
+        file.write_line(line).unwrap();
+    }
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "line 4 ....\n");
+    assert_eq!(
+        fs::read_to_string(dir.join("mithril.json.1")).unwrap(),
+        "line 3 ....\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("mithril.json.2")).unwrap(),
+        "line 2 ....\n"
+    );
+    assert!(!dir.join("mithril.json.3").exists());
+}
+
+#[test]
+fn test_rotate_by_time() {
+    let dir = test_dir("mithril_test_log_rotate_time");
+    let path = dir.join("mithril.json");
+
+    let mut file = RotatingFile::open(&path, 1024, Some(Duration::ZERO), 1).unwrap();
+    file.write_line("a").unwrap();
+    file.write_line("b").unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "b\n");
+    assert_eq!(
+        fs::read_to_string(dir.join("mithril.json.1")).unwrap(),
+        "a\n"
+    );
+}
+
+#[test]
+fn test_appends_to_existing_file() {
+    let dir = test_dir("mithril_test_log_append");
+    let path = dir.join("mithril.json");
+    fs::write(&path, "old\n").unwrap();
+
+    let mut file = RotatingFile::open(&path, 1024, None, 1).unwrap();
+    file.write_line("new").unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "old\nnew\n");
+}
+
+//helper
+
+fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,115 @@
+extern crate mithril;
+
+use mithril::metric::history::{HashrateHistory, HashrateLog, ThreadHistory};
+
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_hashrate_over_window() {
+    let mut history = HashrateHistory::new(Duration::from_secs(60));
+    let start = Instant::now();
+    history.record(start, 0);
+    history.record(start + Duration::from_secs(10), 1000);
+    history.record(start + Duration::from_secs(20), 3000);
+
+    assert_eq!(history.hashrate(Duration::from_secs(10)), Some(200.0));
+    assert_eq!(history.hashrate(Duration::from_secs(20)), Some(150.0));
+}
+
+#[test]
+fn test_hashrate_not_covered_by_history() {
+    let mut history = HashrateHistory::new(Duration::from_secs(60));
+    assert_eq!(history.hashrate(Duration::from_secs(10)), None);
+
+    let start = Instant::now();
+    history.record(start, 0);
+    history.record(start + Duration::from_secs(5), 500);
+    assert_eq!(history.hashrate(Duration::from_secs(10)), None);
+}
+
+#[test]
+fn test_old_samples_are_dropped() {
+    let mut history = HashrateHistory::new(Duration::from_secs(20));
+    let start = Instant::now();
+    for i in 0..10 {
+        history.record(start + Duration::from_secs(i * 10), i * 100);
+    }
+    assert_eq!(history.hashrate(Duration::from_secs(20)), Some(10.0));
+    assert_eq!(history.hashrate(Duration::from_secs(30)), None);
+}
+
+#[test]
+fn test_thread_hashrates() {
+    let mut threads = ThreadHistory::new(Duration::from_secs(10));
+    assert_eq!(threads.hashrates(), vec![]);
+
+    let start = Instant::now();
+    threads.record(start, vec![0, 0]);
+    assert_eq!(threads.hashrates(), vec![(0, None), (0, None)]);
+
+    threads.record(start + Duration::from_secs(5), vec![500, 250]);
+    threads.record(start + Duration::from_secs(10), vec![1000, 500]);
+    assert_eq!(
+        threads.hashrates(),
+        vec![(1000, Some(100.0)), (500, Some(50.0))]
+    );
+
+    //the oldest sample is out of the window now
+    threads.record(start + Duration::from_secs(15), vec![2000, 500]);
+    assert_eq!(
+        threads.hashrates(),
+        vec![(2000, Some(150.0)), (500, Some(25.0))]
+    );
+}
+
+#[test]
+fn test_thread_hashrates_after_worker_restart() {
+    let mut threads = ThreadHistory::new(Duration::from_secs(10));
+    let start = Instant::now();
+    threads.record(start, vec![5000]);
+    threads.record(start + Duration::from_secs(5), vec![100, 100]);
+    assert_eq!(threads.hashrates(), vec![(100, None), (100, None)]);
+}
+
+#[test]
+fn test_hashrate_log_ring() {
+    let mut log = HashrateLog::new(Duration::from_secs(10), Duration::from_secs(30));
+    let start = Instant::now();
+    //the first count is the base
+    log.record(start, 100, 0);
+    assert!(log.query(0, 10).is_empty());
+    for i in 1..=5 {
+        log.record(start + Duration::from_secs(i * 10), 100 + i * 10, i * 1000);
+    }
+    //the capacity is 3 samples
+    assert_eq!(
+        log.query(0, 10),
+        vec![(130, 100.0), (140, 100.0), (150, 100.0)]
+    );
+    assert_eq!(log.query(140, 10), vec![(140, 100.0), (150, 100.0)]);
+}
+
+#[test]
+fn test_hashrate_log_steps() {
+    let mut log = HashrateLog::new(Duration::from_secs(10), Duration::from_secs(60 * 60));
+    let start = Instant::now();
+    let mut hashes = 0;
+    log.record(start, 0, hashes);
+    for (i, rate) in [100, 300, 200, 400, 600].iter().enumerate() {
+        hashes += rate * 10;
+        let i = i as u64 + 1;
+        log.record(start + Duration::from_secs(i * 10), i * 10, hashes);
+    }
+    assert_eq!(log.query(0, 30), vec![(0, 200.0), (30, 400.0)]);
+    //steps below the resolution return the samples
+    assert_eq!(log.query(0, 1).len(), 5);
+}
+
+#[test]
+fn test_hashrate_log_disabled() {
+    let mut log = HashrateLog::new(Duration::from_secs(10), Duration::ZERO);
+    let start = Instant::now();
+    log.record(start, 0, 0);
+    log.record(start + Duration::from_secs(10), 10, 1000);
+    assert!(log.query(0, 10).is_empty());
+}
@@ -0,0 +1,67 @@
+extern crate mithril;
+
+use mithril::metric::latency::LatencyHistogram;
+use mithril::metric::stats::MinerStats;
+
+use std::time::Duration;
+
+#[test]
+fn test_empty_histogram() {
+    let histogram = LatencyHistogram::new();
+    assert_eq!(histogram.count(), 0);
+    assert_eq!(histogram.percentile(0.5), None);
+    assert!(histogram.buckets().is_empty());
+}
+
+#[test]
+fn test_percentiles_within_bucket_error() {
+    let mut histogram = LatencyHistogram::new();
+    for ms in 1..=1000 {
+        histogram.record(Duration::from_millis(ms));
+    }
+    assert_eq!(histogram.count(), 1000);
+    assert_close(histogram.percentile(0.5).unwrap(), 500.0);
+    assert_close(histogram.percentile(0.99).unwrap(), 990.0);
+    assert!(histogram.percentile(0.0).unwrap() <= Duration::from_millis(1));
+}
+
+#[test]
+fn test_single_sample() {
+    let mut histogram = LatencyHistogram::new();
+    histogram.record(Duration::from_millis(120));
+    assert_close(histogram.percentile(0.5).unwrap(), 120.0);
+    assert_close(histogram.percentile(0.99).unwrap(), 120.0);
+    assert_eq!(histogram.buckets().len(), 1);
+    assert_eq!(histogram.buckets()[0].1, 1);
+}
+
+#[test]
+fn test_overflow_bucket() {
+    let mut histogram = LatencyHistogram::new();
+    histogram.record(Duration::from_secs(600));
+    assert!(histogram.percentile(0.5).unwrap() >= Duration::from_secs(50));
+    assert_eq!(histogram.buckets(), vec![(f64::INFINITY, 1)]);
+}
+
+#[test]
+fn test_latency_in_snapshot() {
+    let stats = MinerStats::new();
+    assert_eq!(stats.snapshot().share_latency_p50, None);
+    stats.share_latency(Duration::from_millis(80));
+    stats.share_latency(Duration::from_millis(80));
+    let snapshot = stats.snapshot();
+    assert_close(snapshot.share_latency_p50.unwrap(), 80.0);
+    assert_close(snapshot.share_latency_p99.unwrap(), 80.0);
+}
+
+//helper
+
+fn assert_close(latency: Duration, expected_ms: f64) {
+    let ms = latency.as_secs_f64() * 1000.0;
+    assert!(
+        (ms - expected_ms).abs() <= expected_ms * 0.25,
+        "{}ms is not close to {}ms",
+        ms,
+        expected_ms
+    );
+}
@@ -0,0 +1,87 @@
+extern crate mithril;
+
+use mithril::metric::lifetime::LifetimeStats;
+use mithril::metric::stats::{MinerStats, StatsSnapshot};
+
+use std::fs;
+use std::time::Duration;
+
+#[test]
+fn test_add_run() {
+    let base = LifetimeStats {
+        hashes: 1000,
+        shares_accepted: 10,
+        shares_rejected: 1,
+        uptime_seconds: 3600,
+        best_share_difficulty: 5000,
+        since: 1588000000,
+    };
+    let run = StatsSnapshot {
+        total_hashes: 500,
+        shares_accepted: 2,
+        shares_rejected: 1,
+        best_share_difficulty: 8000,
+        ..StatsSnapshot::default()
+    };
+    assert_eq!(
+        base.add(&run, Duration::from_secs(60)),
+        LifetimeStats {
+            hashes: 1500,
+            shares_accepted: 12,
+            shares_rejected: 2,
+            uptime_seconds: 3660,
+            best_share_difficulty: 8000,
+            since: 1588000000,
+        }
+    );
+}
+
+#[test]
+fn test_save_and_load() {
+    let path = std::env::temp_dir().join("mithril_test_lifetime_stats.json");
+    let stats = LifetimeStats {
+        hashes: 1,
+        shares_accepted: 2,
+        shares_rejected: 3,
+        uptime_seconds: 4,
+        best_share_difficulty: 5,
+        since: 6,
+    };
+    stats.save(&path).unwrap();
+    assert_eq!(LifetimeStats::load(&path).unwrap(), stats);
+}
+
+#[test]
+fn test_load_missing_file_starts_now() {
+    let path = std::env::temp_dir().join("mithril_test_lifetime_stats_missing.json");
+    let _ = fs::remove_file(&path);
+    let stats = LifetimeStats::load(&path).unwrap();
+    assert_eq!(stats.hashes, 0);
+    assert!(stats.since > 0);
+}
+
+#[test]
+fn test_load_broken_file_fails() {
+    let path = std::env::temp_dir().join("mithril_test_lifetime_stats_broken.json");
+    fs::write(&path, "{ not json").unwrap();
+    assert!(LifetimeStats::load(&path).is_err());
+}
+
+#[test]
+fn test_miner_stats_lifetime() {
+    let stats = MinerStats::new();
+    assert_eq!(stats.lifetime(), None);
+
+    stats.set_lifetime_base(LifetimeStats {
+        hashes: 100,
+        best_share_difficulty: 10,
+        ..LifetimeStats::default()
+    });
+    stats.add_hashes(50);
+    stats.best_share(20);
+    stats.best_share(15);
+
+    let lifetime = stats.lifetime().unwrap();
+    assert_eq!(lifetime.hashes, 150);
+    assert_eq!(lifetime.best_share_difficulty, 20);
+}
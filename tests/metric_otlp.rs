@@ -0,0 +1,116 @@
+extern crate mithril;
+extern crate serde_json;
+
+use mithril::metric::otlp::{metrics_json, traces_json, OtlpConfig};
+use mithril::metric::stats::StatsSnapshot;
+use mithril::metric::telemetry::Telemetry;
+use mithril::trace::{Field, SpanRecord};
+
+use serde_json::Value;
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn test_metrics_json() {
+    let snapshot = StatsSnapshot {
+        total_hashes: 1000,
+        shares_accepted: 3,
+        shares_rejected: 1,
+        ..StatsSnapshot::default()
+    };
+    let body = metrics_json(
+        &conf("rig01"),
+        &snapshot,
+        123.5,
+        &Telemetry::default(),
+        UNIX_EPOCH + Duration::from_secs(1),
+        UNIX_EPOCH + Duration::from_secs(2),
+    );
+    let json: Value = serde_json::from_str(&body).unwrap();
+    let resource = &json["resourceMetrics"][0]["resource"]["attributes"];
+    assert_eq!(resource[0]["key"], "service.name");
+    assert_eq!(resource[0]["value"]["stringValue"], "mithril");
+    assert_eq!(resource[1]["key"], "host.name");
+    assert_eq!(resource[1]["value"]["stringValue"], "rig01");
+
+    let metrics = &json["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+    assert_eq!(metrics[0]["name"], "mithril.hashrate");
+    assert_eq!(metrics[0]["unit"], "H/s");
+    let point = &metrics[0]["gauge"]["dataPoints"][0];
+    assert_eq!(point["asDouble"], 123.5);
+    assert_eq!(point["timeUnixNano"], "2000000000");
+
+    assert_eq!(metrics[2]["name"], "mithril.hashes");
+    let sum = &metrics[2]["sum"];
+    assert_eq!(sum["isMonotonic"], true);
+    assert_eq!(sum["aggregationTemporality"], 2);
+    assert_eq!(sum["dataPoints"][0]["asInt"], "1000");
+    assert_eq!(sum["dataPoints"][0]["startTimeUnixNano"], "1000000000");
+    assert_eq!(metrics[4]["name"], "mithril.shares_rejected");
+}
+
+#[test]
+fn test_metrics_json_without_host() {
+    let body = metrics_json(
+        &conf(""),
+        &StatsSnapshot::default(),
+        0.0,
+        &Telemetry::default(),
+        UNIX_EPOCH,
+        UNIX_EPOCH,
+    );
+    let json: Value = serde_json::from_str(&body).unwrap();
+    let resource = json["resourceMetrics"][0]["resource"]["attributes"]
+        .as_array()
+        .unwrap()
+        .clone();
+    assert_eq!(resource.len(), 1);
+}
+
+#[test]
+fn test_traces_json() {
+    let spans = vec![
+        SpanRecord {
+            name: "job",
+            trace_id: 0xab,
+            span_id: 1,
+            parent_id: None,
+            start: UNIX_EPOCH + Duration::from_secs(5),
+            duration: Duration::from_millis(3),
+            fields: vec![("job_id", Field::Str("j1".to_string()))],
+        },
+        SpanRecord {
+            name: "dataset_init",
+            trace_id: 0xab,
+            span_id: 2,
+            parent_id: Some(1),
+            start: UNIX_EPOCH + Duration::from_secs(5),
+            duration: Duration::from_millis(2),
+            fields: vec![("height", Field::U64(3000000))],
+        },
+    ];
+    let body = traces_json(&conf(""), &spans);
+    let json: Value = serde_json::from_str(&body).unwrap();
+    let spans = &json["resourceSpans"][0]["scopeSpans"][0]["spans"];
+    assert_eq!(spans[0]["traceId"], "000000000000000000000000000000ab");
+    assert_eq!(spans[0]["spanId"], "0000000000000001");
+    assert_eq!(spans[0]["name"], "job");
+    assert_eq!(spans[0]["startTimeUnixNano"], "5000000000");
+    assert_eq!(spans[0]["endTimeUnixNano"], "5003000000");
+    assert!(spans[0].get("parentSpanId").is_none());
+    assert_eq!(spans[0]["attributes"][0]["key"], "job_id");
+    assert_eq!(spans[0]["attributes"][0]["value"]["stringValue"], "j1");
+
+    assert_eq!(spans[1]["parentSpanId"], "0000000000000001");
+    assert_eq!(spans[1]["attributes"][0]["value"]["intValue"], "3000000");
+}
+
+fn conf(host: &str) -> OtlpConfig {
+    OtlpConfig {
+        enabled: true,
+        endpoint: "http://127.0.0.1:4318".to_string(),
+        interval_seconds: 10,
+        service_name: "mithril".to_string(),
+        host: host.to_string(),
+        traces: true,
+    }
+}
@@ -0,0 +1,259 @@
+extern crate mithril;
+
+use mithril::metric::push::{
+    hashrate, influx_lines, parse_address, send, statsd_lines, PushConfig, PushFormat, PushTarget,
+};
+use mithril::metric::stats::StatsSnapshot;
+use mithril::metric::telemetry::Telemetry;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_parse_address() {
+    assert_eq!(
+        parse_address("udp://127.0.0.1:8089"),
+        Ok(PushTarget::Udp {
+            addr: "127.0.0.1:8089".to_string()
+        })
+    );
+    assert_eq!(
+        parse_address("http://influx:8086/write?db=mithril"),
+        Ok(PushTarget::Http {
+            addr: "influx:8086".to_string(),
+            path: "/write?db=mithril".to_string()
+        })
+    );
+    assert_eq!(
+        parse_address("http://influx:8086"),
+        Ok(PushTarget::Http {
+            addr: "influx:8086".to_string(),
+            path: "/".to_string()
+        })
+    );
+    assert!(parse_address("https://influx:8086").is_err());
+}
+
+#[test]
+fn test_influx_line() {
+    let line = influx_lines(
+        &conf("rig 01"),
+        &snapshot(1000, 3, 1),
+        123.456,
+        &Telemetry::default(),
+        Duration::from_secs(2),
+    );
+    assert_eq!(
+        line,
+        "mithril,host=rig\\ 01 hashrate=123.46,hashes=1000i,shares_accepted=3i,shares_rejected=1i,difficulty=0i,luck=0.0000 2000000000"
+    );
+}
+
+#[test]
+fn test_influx_line_without_host() {
+    let line = influx_lines(
+        &conf(""),
+        &snapshot(0, 0, 0),
+        0.0,
+        &Telemetry::default(),
+        Duration::from_secs(1),
+    );
+    assert_eq!(
+        line,
+        "mithril hashrate=0.00,hashes=0i,shares_accepted=0i,shares_rejected=0i,difficulty=0i 1000000000"
+    );
+}
+
+#[test]
+fn test_influx_line_with_effort() {
+    let snapshot = StatsSnapshot {
+        total_hashes: 3000,
+        shares_accepted: 1,
+        shares_rejected: 0,
+        accepted_difficulty: 2000,
+        last_share_hashes: 2000,
+        difficulty: 2000,
+        ..StatsSnapshot::default()
+    };
+    let line = influx_lines(
+        &conf(""),
+        &snapshot,
+        10.0,
+        &Telemetry::default(),
+        Duration::from_secs(1),
+    );
+    assert_eq!(
+        line,
+        "mithril hashrate=10.00,hashes=3000i,shares_accepted=1i,shares_rejected=0i,difficulty=2000i,effort=0.5000,average_effort=1.0000,luck=0.6667 1000000000"
+    );
+}
+
+#[test]
+fn test_influx_lines_with_telemetry() {
+    let telemetry = Telemetry {
+        core_freq_mhz: vec![Some(3000.0), None, Some(4000.0)],
+        package_temp_celsius: Some(71.5),
+        power_watts: Some(88.25),
+    };
+    let lines = influx_lines(
+        &conf("rig"),
+        &snapshot(0, 0, 0),
+        0.0,
+        &telemetry,
+        Duration::from_secs(1),
+    );
+    assert_eq!(
+        lines,
+        "mithril,host=rig hashrate=0.00,hashes=0i,shares_accepted=0i,shares_rejected=0i,difficulty=0i,\
+         cpu_freq_min_mhz=3000.0000,cpu_freq_avg_mhz=3500.0000,cpu_freq_max_mhz=4000.0000,\
+         package_temp_celsius=71.5000,power_watts=88.2500 1000000000\n\
+         mithril_cpu,host=rig,core=0 freq_mhz=3000.0 1000000000\n\
+         mithril_cpu,host=rig,core=2 freq_mhz=4000.0 1000000000"
+    );
+}
+
+#[test]
+fn test_influx_line_with_dataset_stats() {
+    let snapshot = StatsSnapshot {
+        dataset_hit_rate: Some(0.75),
+        dataset_read_p50: Some(Duration::from_nanos(128)),
+        dataset_read_p99: Some(Duration::from_nanos(4096)),
+        ..StatsSnapshot::default()
+    };
+    let line = influx_lines(
+        &conf(""),
+        &snapshot,
+        0.0,
+        &Telemetry::default(),
+        Duration::from_secs(1),
+    );
+    assert_eq!(
+        line,
+        "mithril hashrate=0.00,hashes=0i,shares_accepted=0i,shares_rejected=0i,difficulty=0i,\
+         dataset_hit_rate=0.7500,dataset_read_p50_ns=128.0000,dataset_read_p99_ns=4096.0000 1000000000"
+    );
+}
+
+#[test]
+fn test_statsd_lines_are_deltas() {
+    let lines = statsd_lines(
+        &conf(""),
+        &snapshot(100, 1, 0),
+        &snapshot(350, 3, 1),
+        25.0,
+        &Telemetry::default(),
+    );
+    assert_eq!(
+        lines,
+        "mithril.hashrate:25.00|g\nmithril.hashes:250|c\nmithril.shares_accepted:2|c\nmithril.shares_rejected:1|c\nmithril.luck:0.0000|g"
+    );
+}
+
+#[test]
+fn test_hashrate() {
+    assert_eq!(
+        hashrate(&snapshot(100, 0, 0), &snapshot(700, 0, 0), 10.0),
+        60.0
+    );
+    assert_eq!(
+        hashrate(&snapshot(100, 0, 0), &snapshot(700, 0, 0), 0.0),
+        0.0
+    );
+}
+
+#[test]
+fn test_send_udp() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let target = PushTarget::Udp {
+        addr: socket.local_addr().unwrap().to_string(),
+    };
+
+    send(&target, "mithril.hashrate:1.00|g").unwrap();
+
+    let mut buf = [0; 64];
+    let n = socket.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"mithril.hashrate:1.00|g");
+}
+
+#[test]
+fn test_send_http() {
+    let (target, server) = http_server("HTTP/1.1 204 No Content");
+
+    send(&target, "mithril hashrate=1.00 1").unwrap();
+
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST /write?db=mithril HTTP/1.1"));
+    assert!(request.ends_with("mithril hashrate=1.00 1"));
+}
+
+#[test]
+fn test_send_http_error_status() {
+    let (target, server) = http_server("HTTP/1.1 404 Not Found");
+
+    assert!(send(&target, "mithril hashrate=1.00 1").is_err());
+    server.join().unwrap();
+}
+
+//helper
+
+fn conf(host: &str) -> PushConfig {
+    PushConfig {
+        enabled: true,
+        format: PushFormat::Influx,
+        address: "udp://127.0.0.1:8089".to_string(),
+        interval_seconds: 10,
+        prefix: "mithril".to_string(),
+        host: host.to_string(),
+    }
+}
+
+/// Answers one request with the status line and returns the received request
+fn http_server(status_line: &'static str) -> (PushTarget, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let target = PushTarget::Http {
+        addr: listener.local_addr().unwrap().to_string(),
+        path: "/write?db=mithril".to_string(),
+    };
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(len) = line.to_lowercase().strip_prefix("content-length:") {
+                content_length = len.trim().parse().unwrap();
+            }
+            request.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        request.push_str(&String::from_utf8(body).unwrap());
+        write!(
+            reader.get_mut(),
+            "{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status_line
+        )
+        .unwrap();
+        request
+    });
+    (target, server)
+}
+
+fn snapshot(total_hashes: u64, shares_accepted: u64, shares_rejected: u64) -> StatsSnapshot {
+    StatsSnapshot {
+        total_hashes,
+        shares_accepted,
+        shares_rejected,
+        ..Default::default()
+    }
+}
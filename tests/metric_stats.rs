@@ -0,0 +1,167 @@
+extern crate mithril;
+
+use mithril::metric::report_line;
+use mithril::metric::stats::{DatasetWindow, MinerStats, ShareOutcome, ShareRecord, StatsSnapshot};
+use mithril::randomx::dataset_stats::DatasetStats;
+
+use std::time::{Duration, Instant, SystemTime};
+
+#[test]
+fn test_miner_stats_snapshot() {
+    let stats = MinerStats::new();
+    stats.add_hashes(10);
+    stats.add_hashes(5);
+    stats.share_accepted(0);
+    stats.share_rejected();
+    stats.share_accepted(0);
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.total_hashes, 15);
+    assert_eq!(snapshot.shares_accepted, 2);
+    assert_eq!(snapshot.shares_rejected, 1);
+}
+
+#[test]
+fn test_effort_of_accepted_share() {
+    let stats = MinerStats::new();
+    stats.set_difficulty(1000);
+    stats.add_hashes(1500);
+    assert_eq!(stats.snapshot().current_effort(), Some(1.5));
+
+    assert_eq!(stats.share_accepted(1000), Some(1.5));
+    assert_eq!(stats.snapshot().current_effort(), Some(0.0));
+
+    stats.add_hashes(500);
+    assert_eq!(stats.share_accepted(1000), Some(0.5));
+
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.average_effort(), Some(1.0));
+    assert_eq!(snapshot.luck(), Some(1.0));
+}
+
+#[test]
+fn test_luck_includes_current_round() {
+    let stats = MinerStats::new();
+    stats.set_difficulty(1000);
+    stats.add_hashes(1000);
+    stats.share_accepted(1000);
+    stats.add_hashes(3000);
+
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.average_effort(), Some(1.0));
+    assert_eq!(snapshot.current_effort(), Some(3.0));
+    assert_eq!(snapshot.luck(), Some(0.25));
+}
+
+#[test]
+fn test_effort_unknown_without_difficulty_and_shares() {
+    let snapshot = StatsSnapshot::default();
+    assert_eq!(snapshot.current_effort(), None);
+    assert_eq!(snapshot.average_effort(), None);
+    assert_eq!(snapshot.luck(), None);
+}
+
+#[test]
+fn test_paused() {
+    let stats = MinerStats::new();
+    assert!(!stats.paused());
+    stats.set_paused(true);
+    assert!(stats.paused());
+}
+
+#[test]
+fn test_stratum_errors() {
+    let stats = MinerStats::new();
+    assert_eq!(stats.stratum_errors(), 0);
+    stats.stratum_error();
+    stats.stratum_error();
+    assert_eq!(stats.stratum_errors(), 2);
+}
+
+#[test]
+fn test_disconnected_at() {
+    let stats = MinerStats::new();
+    assert!(stats.disconnected_at().is_none());
+    stats.connected("pool:3333");
+    assert!(stats.disconnected_at().is_none());
+    stats.disconnected();
+    assert!(stats.connection().is_none());
+    assert!(stats.disconnected_at().is_some());
+}
+
+#[test]
+fn test_thread_hashes() {
+    let stats = MinerStats::new();
+    stats.set_thread_count(2);
+    stats.add_thread_hashes(0, 10);
+    stats.add_thread_hashes(1, 5);
+    stats.add_thread_hashes(0, 10);
+    assert_eq!(stats.thread_hashes(), vec![20, 5]);
+    assert_eq!(stats.snapshot().total_hashes, 25);
+
+    stats.set_thread_count(3);
+    assert_eq!(stats.thread_hashes(), vec![0, 0, 0]);
+    assert_eq!(stats.snapshot().total_hashes, 25);
+}
+
+#[test]
+fn test_recent_shares_newest_first_and_bounded() {
+    let stats = MinerStats::new();
+    for i in 0..25 {
+        stats.record_share(ShareRecord {
+            time: SystemTime::now(),
+            job_id: format!("{}", i),
+            difficulty: 1000,
+            outcome: ShareOutcome::Accepted { effort: None },
+        });
+    }
+    let shares = stats.recent_shares();
+    assert_eq!(shares.len(), 20);
+    assert_eq!(shares[0].job_id, "24");
+    assert_eq!(shares[19].job_id, "5");
+}
+
+#[test]
+fn test_dataset_hit_rate_of_last_window() {
+    let mut window = DatasetWindow::default();
+    let start = Instant::now();
+    let length = Duration::from_secs(60);
+    assert_eq!(window.update(start, &reads(10, 90), length), None);
+    assert_eq!(
+        window.update(start + Duration::from_secs(30), &reads(50, 100), length),
+        None
+    );
+    //the first window has 40 hits and 20 misses
+    assert_eq!(
+        window.update(start + Duration::from_secs(60), &reads(50, 110), length),
+        Some(40.0 / 60.0)
+    );
+    assert_eq!(
+        window.update(start + Duration::from_secs(90), &reads(100, 110), length),
+        Some(40.0 / 60.0)
+    );
+    assert_eq!(
+        window.update(start + Duration::from_secs(120), &reads(150, 110), length),
+        Some(1.0)
+    );
+    //no reads, i.e. paused
+    assert_eq!(
+        window.update(start + Duration::from_secs(180), &reads(150, 110), length),
+        None
+    );
+}
+
+#[test]
+fn test_report_line() {
+    assert_eq!(report_line(1000, 42, None), "1000;42;");
+    assert_eq!(report_line(1000, 42, Some(0.875)), "1000;42;0.8750");
+}
+
+//helper
+
+fn reads(hits: u64, misses: u64) -> DatasetStats {
+    DatasetStats {
+        hits,
+        misses,
+        ..DatasetStats::default()
+    }
+}
@@ -0,0 +1,152 @@
+extern crate mithril;
+
+use mithril::metric::telemetry::{self, EnergyCounter, Telemetry, TelemetrySampler};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn test_sample_intel_sysfs() {
+    let root = fake_sys("intel");
+    write(
+        &root,
+        "devices/system/cpu/cpu0/cpufreq/scaling_cur_freq",
+        "3400000",
+    );
+    write(
+        &root,
+        "devices/system/cpu/cpu1/cpufreq/scaling_cur_freq",
+        "2200000",
+    );
+    write(
+        &root,
+        "devices/system/cpu/cpufreq/policy0/scaling_cur_freq",
+        "1",
+    );
+    write(&root, "class/hwmon/hwmon2/name", "coretemp");
+    write(&root, "class/hwmon/hwmon2/temp1_label", "Package id 0");
+    write(&root, "class/hwmon/hwmon2/temp1_input", "64000");
+    write(&root, "class/hwmon/hwmon2/temp2_label", "Core 0");
+    write(&root, "class/hwmon/hwmon2/temp2_input", "99000");
+    write(&root, "class/powercap/intel-rapl:0/energy_uj", "1000000");
+    write(
+        &root,
+        "class/powercap/intel-rapl:0:0/energy_uj",
+        "999999999",
+    );
+
+    let mut sampler = TelemetrySampler::with_sys_root(&root);
+    let telemetry = sampler.sample();
+
+    assert_eq!(telemetry.core_freq_mhz, vec![Some(3400.0), Some(2200.0)]);
+    assert_eq!(telemetry.package_temp_celsius, Some(64.0));
+    assert_eq!(telemetry.power_watts, None); //needs two samples
+
+    write(&root, "class/powercap/intel-rapl:0/energy_uj", "5000000");
+    let power = sampler.sample().power_watts.expect("power");
+    assert!(power > 0.0);
+}
+
+#[test]
+fn test_sample_amd_temp_and_thermal_zone_fallback() {
+    let root = fake_sys("amd");
+    write(&root, "class/hwmon/hwmon0/name", "k10temp");
+    write(&root, "class/hwmon/hwmon0/temp1_label", "Tctl");
+    write(&root, "class/hwmon/hwmon0/temp1_input", "70250");
+    assert_eq!(
+        TelemetrySampler::with_sys_root(&root)
+            .sample()
+            .package_temp_celsius,
+        Some(70.25)
+    );
+
+    let root = fake_sys("thermal_zone");
+    write(&root, "class/thermal/thermal_zone0/type", "acpitz");
+    write(&root, "class/thermal/thermal_zone0/temp", "20000");
+    write(&root, "class/thermal/thermal_zone1/type", "x86_pkg_temp");
+    write(&root, "class/thermal/thermal_zone1/temp", "55000");
+    assert_eq!(
+        TelemetrySampler::with_sys_root(&root)
+            .sample()
+            .package_temp_celsius,
+        Some(55.0)
+    );
+}
+
+#[test]
+fn test_sample_nothing_available() {
+    let root = fake_sys("empty");
+    assert_eq!(
+        TelemetrySampler::with_sys_root(&root).sample(),
+        Telemetry::default()
+    );
+}
+
+#[test]
+fn test_freq_min_avg_max() {
+    let telemetry = Telemetry {
+        core_freq_mhz: vec![Some(1000.0), None, Some(3000.0), Some(2000.0)],
+        ..Default::default()
+    };
+    assert_eq!(
+        telemetry.freq_min_avg_max_mhz(),
+        Some((1000.0, 2000.0, 3000.0))
+    );
+    assert_eq!(Telemetry::default().freq_min_avg_max_mhz(), None);
+}
+
+#[test]
+fn test_energy_counters() {
+    let root = fake_sys("energy");
+    assert_eq!(
+        TelemetrySampler::with_sys_root(&root).energy_counters(),
+        None
+    );
+    write(&root, "class/powercap/intel-rapl:1/energy_uj", "20");
+    write(&root, "class/powercap/intel-rapl:0/energy_uj", "10");
+    write(
+        &root,
+        "class/powercap/intel-rapl:0/max_energy_range_uj",
+        "1000",
+    );
+    write(&root, "class/powercap/intel-rapl:0:0/energy_uj", "5");
+    assert_eq!(
+        TelemetrySampler::with_sys_root(&root).energy_counters(),
+        Some(vec![counter(10, Some(1000)), counter(20, None)])
+    );
+}
+
+#[test]
+fn test_energy_joules() {
+    let start = [counter(900_000, Some(1_000_000)), counter(0, None)];
+    //the first counter wrapped
+    let end = [counter(100_000, Some(1_000_000)), counter(500_000, None)];
+    assert_eq!(telemetry::energy_joules(&start, &end), Some(0.7));
+    //the range of a wrapped counter is needed
+    assert_eq!(telemetry::energy_joules(&end, &start[..1]), None);
+    let wrapped = [counter(10, None), counter(500_000, None)];
+    assert_eq!(telemetry::energy_joules(&end, &wrapped), None);
+    assert_eq!(telemetry::energy_joules(&[], &[]), None);
+}
+
+//helper
+
+fn counter(energy_uj: u64, max_energy_range_uj: Option<u64>) -> EnergyCounter {
+    EnergyCounter {
+        energy_uj,
+        max_energy_range_uj,
+    }
+}
+
+fn fake_sys(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("mithril_test_sys_{}", name));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+fn write(root: &Path, file: &str, content: &str) {
+    let path = root.join(file);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
@@ -1,8 +1,23 @@
+extern crate log;
 extern crate mithril;
+extern crate num_cpus;
 
+use log::LevelFilter;
+use mithril::console::{ConsoleConfig, HashrateFormat, TimestampFormat};
+use mithril::metric::push::PushFormat;
 use mithril::mithril_config;
+use mithril::mithril_config::ConfigFormat;
+use mithril::payout::PayoutWallet;
+use mithril::pow::randomx::MemoryMode;
+use mithril::profile::Priority;
+use mithril::randomx::large_alloc::AllocKind;
+use mithril::randomx::pages::NumaPolicy;
+use mithril::randomx::RandomXConfig;
+use mithril::stratum::stratum_data::SoloConfig;
+use mithril::update_check;
+use mithril::webhook::{ALL_EVENTS, DEFAULT_TEMPLATE};
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 #[test]
@@ -14,18 +29,75 @@ fn test_read_default_config() {
     assert_eq!(config.pool_conf.pool_password, "");
 
     assert_eq!(config.worker_conf.num_threads, 8);
-    assert_eq!(config.worker_conf.auto_tune, true);
+    assert!(config.worker_conf.auto_tune);
     assert_eq!(config.worker_conf.auto_tune_interval_minutes, 15);
     assert_eq!(config.worker_conf.auto_tune_log, "./bandit.log");
+    assert_eq!(config.worker_conf.profile, "");
+    assert!(config.profiles.is_empty());
 
-    assert_eq!(config.metric_conf.enabled, false);
-    assert_eq!(config.metric_conf.resolution, std::u32::MAX as u64);
-    assert_eq!(
-        config.metric_conf.sample_interval_seconds,
-        std::u32::MAX as u64
-    );
+    assert!(!config.metric_conf.enabled);
+    assert_eq!(config.metric_conf.resolution, u32::MAX as u64);
+    assert_eq!(config.metric_conf.sample_interval_seconds, u32::MAX as u64);
     assert_eq!(config.metric_conf.report_file, "/dev/null");
 
+    assert!(!config.push_conf.enabled);
+    assert_eq!(config.push_conf.format, PushFormat::Influx);
+    assert_eq!(config.push_conf.address, "udp://127.0.0.1:8089");
+    assert_eq!(config.push_conf.interval_seconds, 10);
+    assert_eq!(config.push_conf.prefix, "mithril");
+    assert_eq!(config.push_conf.host, "");
+    assert!(!config.otlp_conf.enabled);
+    assert_eq!(config.otlp_conf.endpoint, "http://127.0.0.1:4318");
+    assert_eq!(config.otlp_conf.interval_seconds, 10);
+    assert_eq!(config.otlp_conf.service_name, "mithril");
+    assert!(config.otlp_conf.traces);
+
+    assert!(!config.webhook_conf.enabled);
+    assert_eq!(config.webhook_conf.url, "");
+    assert_eq!(config.webhook_conf.events, ALL_EVENTS.to_vec());
+    assert_eq!(config.webhook_conf.template, DEFAULT_TEMPLATE);
+    assert_eq!(config.webhook_conf.zero_hashrate_minutes, 10);
+    assert_eq!(config.webhook_conf.min_hashrate, 0.0);
+    assert_eq!(config.webhook_conf.max_reject_percent, 10.0);
+    assert_eq!(config.webhook_conf.window_minutes, 15);
+    assert_eq!(config.webhook_conf.block_difficulty, 0);
+    assert_eq!(config.webhook_conf.block_command, "");
+
+    assert_eq!(config.log_conf.json_file, "");
+    assert_eq!(config.log_conf.json_level, LevelFilter::Info);
+    assert_eq!(config.log_conf.console_level, None);
+    assert_eq!(config.log_conf.max_size_mb, 10);
+    assert_eq!(config.log_conf.rotate_hours, 24);
+    assert_eq!(config.log_conf.keep_files, 5);
+    assert!(config.log_conf.levels.is_empty());
+
+    assert!(!config.api_conf.enabled);
+    assert_eq!(config.api_conf.address, "127.0.0.1:3380");
+    assert_eq!(config.api_conf.token, "");
+    assert_eq!(config.api_conf.health.hash_stall_seconds, 300);
+    assert_eq!(config.api_conf.health.disconnect_seconds, 300);
+
+    assert!(!config.share_db_conf.enabled);
+    assert_eq!(config.share_db_conf.path, "./mithril_history.db");
+    assert_eq!(config.share_db_conf.retention_days, 30);
+    assert_eq!(config.forensics_conf.file, "");
+    assert_eq!(config.forensics_conf.max_size_mb, 10);
+    assert_eq!(config.forensics_conf.keep_files, 3);
+    assert!(config.forensics_conf.verify);
+
+    assert!(!config.earnings_conf.enabled);
+    assert_eq!(config.earnings_conf.url, "");
+    assert_eq!(config.earnings_conf.daemon, "");
+    assert_eq!(config.earnings_conf.interval_minutes, 10);
+    assert_eq!(config.earnings_conf.pool_fee_percent, 0.0);
+
+    assert_eq!(config.privilege_conf.user, "");
+    assert_eq!(config.privilege_conf.group, "");
+
+    assert!(!config.update_check_conf.enabled);
+    assert_eq!(config.update_check_conf.url, update_check::DEFAULT_URL);
+    assert_eq!(config.update_check_conf.interval_hours, 24);
+
     assert_eq!(config.donation_conf.percentage, 2.5);
 }
 
@@ -38,9 +110,602 @@ fn test_disabled_metric_value_should_be_addable_to_now() {
     //Ok if it doesn't panic
 }
 
+#[test]
+fn test_embedded_default_config() {
+    let config = mithril_config::default_config().unwrap();
+    let file_config = read_default_config();
+
+    assert_eq!(config.pool_conf, file_config.pool_conf);
+    assert_eq!(
+        config.worker_conf.num_threads,
+        file_config.worker_conf.num_threads
+    );
+    assert_eq!(config.donation_conf.percentage, 2.5);
+}
+
+#[test]
+fn test_console_level() {
+    let path = std::env::temp_dir().join("mithril_test_console_level_config.toml");
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace("console_level = \"\"", "console_level = \"debug\"");
+    std::fs::write(&path, content).unwrap();
+
+    let config = mithril_config::read_config(&path, path.to_str().unwrap()).unwrap();
+    assert_eq!(config.log_conf.console_level, Some(LevelFilter::Debug));
+}
+
+#[test]
+fn test_module_levels() {
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace(
+            "levels = {}",
+            "levels = { stratum = \"debug\", randomx = \"warn\" }",
+        );
+    let config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    assert_eq!(
+        config.log_conf.levels,
+        vec![
+            ("randomx".to_string(), LevelFilter::Warn),
+            ("stratum".to_string(), LevelFilter::Debug),
+        ]
+    );
+
+    let invalid = content.replace("\"warn\"", "\"loud\"");
+    assert!(mithril_config::read_config_content(&invalid, ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_otlp_endpoint() {
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace("\"http://127.0.0.1:4318\"", "\"collector:4318\"");
+    assert!(mithril_config::read_config_content(&content, ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_console_config() {
+    let config = read_default_config();
+    assert_eq!(config.console_conf, ConsoleConfig::default());
+
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace("timestamps = \"seconds\"", "timestamps = \"millis\"")
+        .replace("hashrate = \"verbose\"", "hashrate = \"compact\"")
+        .replace("quiet = false", "quiet = true");
+    let config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    assert_eq!(config.console_conf.timestamps, TimestampFormat::Millis);
+    assert_eq!(config.console_conf.hashrate, HashrateFormat::Compact);
+    assert!(config.console_conf.quiet);
+    assert!(config.console_conf.color);
+
+    let invalid = content.replace("\"compact\"", "\"short\"");
+    assert!(mithril_config::read_config_content(&invalid, ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_detect_format() {
+    assert_eq!(
+        ConfigFormat::detect(Path::new("config.toml")),
+        ConfigFormat::Toml
+    );
+    assert_eq!(
+        ConfigFormat::detect(Path::new("/etc/mithril/config.JSON")),
+        ConfigFormat::Json
+    );
+    assert_eq!(
+        ConfigFormat::detect(Path::new("config")),
+        ConfigFormat::Toml
+    );
+    assert_eq!("json".parse::<ConfigFormat>().unwrap(), ConfigFormat::Json);
+}
+
+#[test]
+fn test_read_json_config() {
+    let path = std::env::temp_dir().join("mithril_test_config.json");
+    std::fs::write(&path, JSON_CONFIG).unwrap();
+
+    let config = mithril_config::read_config(&path, path.to_str().unwrap()).unwrap();
+    assert_eq!(config.pool_conf.pool_address, "pool.example.com:3333");
+    assert_eq!(config.pool_conf.wallet_address, "4wallet");
+    assert_eq!(config.worker_conf.num_threads, 4);
+    assert!(!config.worker_conf.auto_tune);
+    assert!(!config.metric_conf.enabled);
+    assert!(config.api_conf.enabled);
+    assert_eq!(config.api_conf.token, "secret");
+    assert_eq!(config.donation_conf.percentage, 1.0);
+}
+
+#[test]
+fn test_read_json_config_explicit_format() {
+    let path = std::env::temp_dir().join("mithril_test_json_config.conf");
+    std::fs::write(&path, JSON_CONFIG).unwrap();
+
+    assert!(mithril_config::read_config(&path, path.to_str().unwrap()).is_err());
+    let config =
+        mithril_config::read_config_format(&path, path.to_str().unwrap(), ConfigFormat::Json)
+            .unwrap();
+    assert_eq!(config.worker_conf.num_threads, 4);
+}
+
 //helper
 
+const JSON_CONFIG: &str = r#"{
+    "pool": {"pool_address": "pool.example.com:3333", "wallet_address": "4wallet", "pool_password": "x"},
+    "worker": {"num_threads": 4, "auto_tune": false, "auto_tune_interval_minutes": 15, "auto_tune_log": "./bandit.log"},
+    "metric": {"enabled": false, "resolution": 100, "sample_interval_seconds": 60, "report_file": "/dev/null"},
+    "api": {"enabled": true, "token": "secret"},
+    "donation": {"percentage": 1}
+}"#;
+
 fn read_default_config() -> mithril_config::MithrilConfig {
     let path = &format!("{}{}", "./", "default_config.toml");
-    return mithril_config::read_config(Path::new(path), "default_config.toml").unwrap();
+    mithril_config::read_config(Path::new(path), "default_config.toml").unwrap()
+}
+
+#[test]
+fn test_pool_defaults() {
+    let config = read_default_config();
+    assert_eq!(config.pool_conf.rig_id, "");
+    assert!(config.pool_conf.keepalive);
+    assert!(!config.pool_conf.nicehash);
+    assert!(!config.pool_conf.tls);
+    assert_eq!(config.pool_conf.proxy, "");
+    assert_eq!(config.pool_conf.algorithm, "rx/0");
+    assert!(config.backup_pools.is_empty());
+    assert_eq!(config.pools(), vec![&config.pool_conf]);
+}
+
+#[test]
+fn test_backup_pools() {
+    let path = std::env::temp_dir().join("mithril_test_backup_pools_config.toml");
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace("wallet_address = \"\"", "wallet_address = \"4wallet\"")
+        + "\n[[backup_pool]]\npool_address = \"backup:3333\"\nrig_id = \"rig1\"\nnicehash = true\n"
+        + "\n[[backup_pool]]\npool_address = \"backup2:3333\"\nwallet_address = \"4other\"\n"
+        + "keepalive = false\nproxy = \"127.0.0.1:9050\"\n";
+    std::fs::write(&path, content).unwrap();
+
+    let config = mithril_config::read_config(&path, path.to_str().unwrap()).unwrap();
+    assert_eq!(config.backup_pools.len(), 2);
+    let backup = &config.backup_pools[0];
+    assert_eq!(backup.pool_address, "backup:3333");
+    assert_eq!(backup.wallet_address, "4wallet");
+    assert_eq!(backup.rig_id, "rig1");
+    assert!(backup.nicehash);
+    assert!(backup.keepalive);
+    let backup = &config.backup_pools[1];
+    assert_eq!(backup.wallet_address, "4other");
+    assert!(!backup.keepalive);
+    assert!(!backup.nicehash);
+    assert_eq!(backup.proxy, "127.0.0.1:9050");
+    assert_eq!(
+        config
+            .pools()
+            .iter()
+            .map(|p| p.pool_address.as_str())
+            .collect::<Vec<_>>(),
+        vec!["xmrpool.eu:3333", "backup:3333", "backup2:3333"]
+    );
+}
+
+#[test]
+fn test_dual_pool() {
+    assert_eq!(read_default_config().dual_pool, None);
+
+    let path = std::env::temp_dir().join("mithril_test_dual_pool_config.toml");
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace("wallet_address = \"\"", "wallet_address = \"4wallet\"")
+        + "\n[dual_pool]\npool_address = \"keva:3333\"\nalgorithm = \"rx/keva\"\nnum_threads = 2\n";
+    std::fs::write(&path, content).unwrap();
+
+    let config = mithril_config::read_config(&path, path.to_str().unwrap()).unwrap();
+    let dual = config.dual_pool.clone().unwrap();
+    assert_eq!(dual.num_threads, 2);
+    assert_eq!(dual.pool_conf.pool_address, "keva:3333");
+    assert_eq!(dual.pool_conf.algorithm, "rx/keva");
+    assert_eq!(dual.pool_conf.wallet_address, "4wallet");
+    //not a backup of the primary pool
+    assert_eq!(config.pools().len(), 1);
+}
+
+#[test]
+fn test_profit_switch() {
+    let config = read_default_config();
+    assert!(!config.profit_conf.enabled);
+    assert_eq!(config.profit_conf.interval_minutes, 10);
+    assert!(config.coins.is_empty());
+
+    let path = std::env::temp_dir().join("mithril_test_profit_switch_config.toml");
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace("wallet_address = \"\"", "wallet_address = \"4wallet\"")
+        + "\n[[coin]]\nname = \"monero\"\nhashrate = 5000\npool_address = \"xmr:3333\"\n"
+        + "\n[[coin]]\nname = \"keva\"\nhashrate = 9000.5\npool_address = \"keva:3333\"\nalgorithm = \"rx/keva\"\n";
+    std::fs::write(&path, content).unwrap();
+
+    let config = mithril_config::read_config(&path, path.to_str().unwrap()).unwrap();
+    assert_eq!(config.coins.len(), 2);
+    assert_eq!(config.coins[0].name, "monero");
+    assert_eq!(config.coins[0].hashrate, 5000.0);
+    assert_eq!(config.coins[0].pool_conf.wallet_address, "4wallet");
+    assert_eq!(config.coins[1].hashrate, 9000.5);
+    assert_eq!(config.coins[1].pool_conf.algorithm, "rx/keva");
+}
+
+#[test]
+fn test_profit_switch_without_url() {
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace(
+            "[profit_switch]\nenabled = false",
+            "[profit_switch]\nenabled = true",
+        );
+    let result = mithril_config::read_config_content(&content, ConfigFormat::Toml);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_randomx_variants() {
+    assert!(read_default_config().randomx_variants.is_empty());
+
+    let content = std::fs::read_to_string("default_config.toml").unwrap()
+        + "\n[[randomx]]\nname = \"rx/custom\"\nbase = \"rx/keva\"\nargon_salt = \"RandomX-Custom\"\nprogram_iterations = 1024\n"
+        + "\n[[randomx]]\nname = \"rx/other\"\n";
+    let config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    assert_eq!(config.randomx_variants.len(), 2);
+    let custom = &config.randomx_variants[0];
+    assert_eq!(custom.name, "rx/custom");
+    assert_eq!(custom.config.argon_salt, b"RandomX-Custom".to_vec());
+    assert_eq!(custom.config.program_iterations, 1024);
+    //the other parameters are the ones of the base
+    assert_eq!(custom.config.scratchpad_l3_bytes, 1024 * 1024);
+    assert_eq!(config.randomx_variants[1].config, RandomXConfig::monero());
+}
+
+#[test]
+fn test_invalid_randomx_variants() {
+    let default = std::fs::read_to_string("default_config.toml").unwrap();
+    for variant in [
+        "name = \"rx/0\"",
+        "name = \"\"",
+        "name = \"rx/custom\"\nbase = \"rx/wow\"",
+        "name = \"rx/custom\"\ndataset_base_bytes = 1000",
+        "name = \"rx/custom\"\nfreq_iror_r = 12",
+    ] {
+        let content = format!("{default}\n[[randomx]]\n{variant}\n");
+        let result = mithril_config::read_config_content(&content, ConfigFormat::Toml);
+        assert!(result.is_err(), "{variant}");
+    }
+    let content =
+        format!("{default}\n[[randomx]]\nname = \"rx/a\"\n\n[[randomx]]\nname = \"rx/a\"\n");
+    assert!(mithril_config::read_config_content(&content, ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_dataset_init() {
+    let config = read_default_config();
+    assert!(config.dataset_init_conf.background);
+    assert_eq!(config.dataset_init_conf.threads, 0);
+    assert_eq!(config.dataset_init_conf.priority, Priority::Idle);
+    assert_eq!(config.dataset_init_conf.mb_per_second, 0);
+    assert_eq!(config.dataset_init_conf.stall_seconds, 120);
+    assert_eq!(config.dataset_init_conf.stall_retries, 1);
+
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace("mb_per_second = 0", "mb_per_second = 20")
+        .replace("priority = \"idle\"", "priority = \"low\"");
+    let config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    assert_eq!(config.dataset_init_conf.priority, Priority::Low);
+    assert_eq!(config.dataset_init_conf.mb_per_second, 20);
+
+    let content = content.replace("priority = \"low\"", "priority = \"lowest\"");
+    assert!(mithril_config::read_config_content(&content, ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_memory_numa() {
+    let config = read_default_config();
+    assert_eq!(config.memory_conf.numa, NumaPolicy::Local);
+
+    let content = std::fs::read_to_string("default_config.toml").unwrap();
+    let interleave = content.replace("numa = \"local\"", "numa = \"interleave\"");
+    let config = mithril_config::read_config_content(&interleave, ConfigFormat::Toml).unwrap();
+    assert_eq!(config.memory_conf.numa, NumaPolicy::Interleave);
+
+    let replicate = content.replace("numa = \"local\"", "numa = \"replicate\"");
+    assert!(mithril_config::read_config_content(&replicate, ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_memory_mode() {
+    let config = read_default_config();
+    assert_eq!(config.memory_conf.mode, MemoryMode::Auto);
+
+    let content = std::fs::read_to_string("default_config.toml").unwrap();
+    let light = content.replace("mode = \"auto\"", "mode = \"light\"");
+    let config = mithril_config::read_config_content(&light, ConfigFormat::Toml).unwrap();
+    assert_eq!(config.memory_conf.mode, MemoryMode::Light);
+    assert!(!config.memory_conf.mode.full_memory(true));
+    assert!(MemoryMode::Fast.full_memory(false));
+    assert!(!MemoryMode::Auto.full_memory(false));
+
+    let full = content.replace("mode = \"auto\"", "mode = \"full\"");
+    assert!(mithril_config::read_config_content(&full, ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_memory_allocator() {
+    let config = read_default_config();
+    assert_eq!(config.memory_conf.allocator, None);
+    assert_eq!(config.memory_conf.dataset_server, None);
+
+    let content = std::fs::read_to_string("default_config.toml").unwrap();
+    let shm = content.replace("allocator = \"default\"", "allocator = \"shm\"");
+    let config = mithril_config::read_config_content(&shm, ConfigFormat::Toml).unwrap();
+    assert_eq!(config.memory_conf.allocator, Some(AllocKind::Shm));
+
+    let server = content.replace(
+        "dataset_server = \"\"",
+        "dataset_server = \"/run/mithril/dataset.sock\"",
+    );
+    let config = mithril_config::read_config_content(&server, ConfigFormat::Toml).unwrap();
+    assert_eq!(
+        config.memory_conf.dataset_server,
+        Some(PathBuf::from("/run/mithril/dataset.sock"))
+    );
+
+    let jemalloc = content.replace("allocator = \"default\"", "allocator = \"jemalloc\"");
+    assert!(mithril_config::read_config_content(&jemalloc, ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_cluster_agent() {
+    let config = read_default_config();
+    assert_eq!(config.cluster_conf.coordinator, None);
+    assert_eq!(config.pools().len(), 1 + config.backup_pools.len());
+
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace("coordinator = \"\"", "coordinator = \"10.0.0.1:3333\"");
+    let config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    let coordinator = config.cluster_conf.coordinator.clone().unwrap();
+    assert_eq!(coordinator.pool_address, "10.0.0.1:3333");
+    assert_eq!(coordinator.wallet_address, config.pool_conf.wallet_address);
+    assert!(coordinator.nicehash);
+    assert_eq!(config.pools(), vec![&coordinator]);
+}
+
+#[test]
+fn test_solo_daemon() {
+    let config = read_default_config();
+    assert!(!config.pool_conf.daemon);
+
+    let content = std::fs::read_to_string("default_config.toml").unwrap()
+        + "\n[[backup_pool]]\npool_address = \"127.0.0.1:18081\"\ndaemon = true\n";
+    let config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    assert!(config.backup_pools[0].daemon);
+    assert_eq!(
+        config.backup_pools[0].wallet_address,
+        config.pool_conf.wallet_address
+    );
+
+    let proxied = content.replace("daemon = true", "daemon = true\nproxy = \"127.0.0.1:9050\"");
+    assert!(mithril_config::read_config_content(&proxied, ConfigFormat::Toml).is_err());
+    let dual = std::fs::read_to_string("default_config.toml").unwrap()
+        + "\n[dual_pool]\npool_address = \"127.0.0.1:18081\"\ndaemon = true\n";
+    assert!(mithril_config::read_config_content(&dual, ConfigFormat::Toml).is_err());
+
+    let notified = content.replace("daemon = true", "daemon = true\nzmq = \"127.0.0.1:18083\"");
+    let config = mithril_config::read_config_content(&notified, ConfigFormat::Toml).unwrap();
+    assert_eq!(config.backup_pools[0].zmq, "127.0.0.1:18083");
+    assert_eq!(config.pool_conf.zmq, "");
+    let pool_zmq = notified.replace("daemon = true", "daemon = false");
+    assert!(mithril_config::read_config_content(&pool_zmq, ConfigFormat::Toml).is_err());
+
+    assert_eq!(config.backup_pools[0].solo, SoloConfig::default());
+    let tuned = content.replace(
+        "daemon = true",
+        "daemon = true\ntemplate_refresh_seconds = 5\nreserve_size = 60\nmempool_refresh = true\ntemplate_max_age_seconds = 120",
+    );
+    let config = mithril_config::read_config_content(&tuned, ConfigFormat::Toml).unwrap();
+    assert_eq!(
+        config.backup_pools[0].solo,
+        SoloConfig {
+            template_refresh_seconds: 5,
+            reserve_size: 60,
+            mempool_refresh: true,
+            template_max_age_seconds: 120,
+        }
+    );
+    let reserve = tuned.replace("reserve_size = 60", "reserve_size = 256");
+    assert!(mithril_config::read_config_content(&reserve, ConfigFormat::Toml).is_err());
+    let refresh = tuned.replace(
+        "template_refresh_seconds = 5",
+        "template_refresh_seconds = 0",
+    );
+    assert!(mithril_config::read_config_content(&refresh, ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_fleet_config() {
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace("coordinator = \"\"", "coordinator = \"10.0.0.1:3333\"")
+        .replace("local = []", "local = [\"worker.num_threads\"]");
+    let fleet = "[worker]\nnum_threads = 2\nauto_tune = false\n[donation]\npercentage = 2.5\n";
+    let config =
+        mithril_config::read_config_with_fleet(&content, ConfigFormat::Toml, fleet).unwrap();
+    //local
+    assert_eq!(config.worker_conf.num_threads, 8);
+    assert!(!config.worker_conf.auto_tune);
+    assert_eq!(config.donation_conf.percentage, 2.5);
+    assert_eq!(config.cluster_conf.local, vec!["worker.num_threads"]);
+    assert_eq!(config.cluster_conf.fleet_config.as_deref(), Some(fleet));
+    assert_eq!(
+        config.cluster_conf.coordinator.unwrap().pool_address,
+        "10.0.0.1:3333"
+    );
+
+    assert!(mithril_config::check_fleet_config(fleet).is_ok());
+    assert!(mithril_config::check_fleet_config("[cluster]\ncoordinator = \"\"").is_err());
+    assert!(mithril_config::check_fleet_config("[worker]\nnum_threads = \"many\"").is_err());
+    assert!(mithril_config::check_fleet_config("[worker").is_err());
+}
+
+#[test]
+fn test_dataset_check() {
+    let config = read_default_config();
+    assert!(!config.integrity_conf.enabled);
+    assert_eq!(config.integrity_conf.interval_minutes, 30);
+    assert_eq!(config.integrity_conf.samples, 1024);
+
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace(
+            "[dataset_check]\nenabled = false",
+            "[dataset_check]\nenabled = true",
+        )
+        .replace("rebuild = false", "rebuild = true")
+        .replace("samples = 1024", "samples = 0");
+    assert!(mithril_config::read_config_content(&content, ConfigFormat::Toml).is_err());
+    let content = content.replace("samples = 0", "samples = 64");
+    let config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    assert!(config.integrity_conf.enabled);
+    assert!(config.integrity_conf.rebuild);
+    assert_eq!(config.integrity_conf.samples, 64);
+}
+
+#[test]
+fn test_profiles() {
+    let path = std::env::temp_dir().join("mithril_test_profiles_config.toml");
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace("profile = \"\"", "profile = \"night\"")
+        + "\n[[profile]]\nname = \"night\"\nthreads_percent = 100\npriority = \"low\"\nthrottle_percent = 20\n"
+        + "\n[[profile]]\nname = \"eco\"\n";
+    std::fs::write(&path, content).unwrap();
+
+    let config = mithril_config::read_config(&path, path.to_str().unwrap()).unwrap();
+    assert_eq!(config.profiles.len(), 2);
+    assert_eq!(config.worker_conf.profile, "night");
+    assert_eq!(config.worker_conf.num_threads, num_cpus::get() as u64);
+    assert!(!config.worker_conf.auto_tune);
+    assert_eq!(config.worker_conf.priority, Priority::Low);
+    assert_eq!(config.worker_conf.throttle_percent, 20);
+    // the user profile replaces the built-in eco profile, missing keys use the defaults
+    let eco = config.profile("eco").unwrap();
+    assert_eq!(eco.threads_percent, 100);
+    assert_eq!(eco.priority, Priority::Normal);
+    assert_eq!(eco.throttle_percent, 0);
+}
+
+#[test]
+fn test_invalid_profile() {
+    let path = std::env::temp_dir().join("mithril_test_invalid_profile_config.toml");
+    let content = std::fs::read_to_string("default_config.toml").unwrap()
+        + "\n[[profile]]\nname = \"night\"\nthrottle_percent = 95\n";
+    std::fs::write(&path, content).unwrap();
+
+    let err = mithril_config::read_config(&path, path.to_str().unwrap())
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "profile[0].throttle_percent has to be <= 90"
+    );
+}
+
+#[test]
+fn test_worker_throttle() {
+    assert_eq!(read_default_config().worker_conf.throttle_percent, 0);
+
+    let content = std::fs::read_to_string("default_config.toml").unwrap();
+    let throttled = content.replace("throttle_percent = 0 #", "throttle_percent = 25 #");
+    let config = mithril_config::read_config_content(&throttled, ConfigFormat::Toml).unwrap();
+    assert_eq!(config.worker_conf.throttle_percent, 25);
+
+    let too_high = content.replace("throttle_percent = 0 #", "throttle_percent = 95 #");
+    let err = mithril_config::read_config_content(&too_high, ConfigFormat::Toml)
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "throttle_percent has to be <= 90");
+}
+
+#[test]
+fn test_update_check_needs_https() {
+    let content = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .replace(
+            "https://api.github.com/repos/Ragnaroek",
+            "http://api.github.com/repos/Ragnaroek",
+        );
+    let err = mithril_config::read_config_content(&content, ConfigFormat::Toml)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "update_check.url has to be an https:// URL, was http://api.github.com/repos/Ragnaroek/mithril/releases/latest"
+    );
+}
+
+#[test]
+fn test_payout_wallets() {
+    assert!(read_default_config().payout_wallets.is_empty());
+
+    let content = std::fs::read_to_string("default_config.toml").unwrap()
+        + "\n[[payout]]\nwallet_address = \"alice\"\npercentage = 60.0\n\n[[payout]]\nwallet_address = \"bob\"\npercentage = 40.0\n";
+    let config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    assert_eq!(
+        config.payout_wallets,
+        vec![
+            PayoutWallet {
+                wallet_address: "alice".to_string(),
+                percentage: 60.0
+            },
+            PayoutWallet {
+                wallet_address: "bob".to_string(),
+                percentage: 40.0
+            },
+        ]
+    );
+
+    let uneven = content.replace("percentage = 40.0", "percentage = 30.0");
+    let err = mithril_config::read_config_content(&uneven, ConfigFormat::Toml)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "the payout percentages have to add up to 100, were 90"
+    );
+    let negative = content
+        .replace("percentage = 60.0", "percentage = 110.0")
+        .replace("percentage = 40.0", "percentage = -10.0");
+    assert!(mithril_config::read_config_content(&negative, ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_optional_sections_default_if_missing() {
+    let path = std::env::temp_dir().join("mithril_test_minimal_config.toml");
+    let minimal = std::fs::read_to_string("default_config.toml")
+        .unwrap()
+        .split("[metric_push]")
+        .next()
+        .unwrap()
+        .to_string()
+        + "[donation]\npercentage = 0.0\n";
+    std::fs::write(&path, minimal).unwrap();
+
+    let config = mithril_config::read_config(&path, path.to_str().unwrap()).unwrap();
+    assert!(!config.push_conf.enabled);
+    assert_eq!(config.push_conf.interval_seconds, 10);
+    assert!(!config.webhook_conf.enabled);
+    assert_eq!(config.webhook_conf.events, ALL_EVENTS.to_vec());
+    assert_eq!(config.log_conf.json_file, "");
+    assert!(!config.api_conf.enabled);
+    assert!(!config.share_db_conf.enabled);
 }
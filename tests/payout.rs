@@ -0,0 +1,50 @@
+extern crate mithril;
+
+use mithril::payout::{PayoutSplit, PayoutWallet};
+
+#[test]
+fn test_no_wallets() {
+    assert!(PayoutSplit::new(Vec::new()).is_none());
+}
+
+#[test]
+fn test_rotate() {
+    let split = PayoutSplit::new(wallets(&[60.0, 40.0])).unwrap();
+    assert_eq!(split.current(), "wallet0");
+    //nothing mined yet
+    assert!(!split.rotate());
+
+    split.share_accepted(1000);
+    assert!(split.rotate());
+    assert_eq!(split.current(), "wallet1");
+    split.share_accepted(1000);
+    assert!(split.rotate());
+    assert_eq!(split.current(), "wallet0");
+    assert_eq!(split.split(), vec![50.0, 50.0]);
+}
+
+#[test]
+fn test_split_converges() {
+    let split = PayoutSplit::new(wallets(&[70.0, 20.0, 10.0])).unwrap();
+    for _ in 0..1000 {
+        split.share_accepted(5000);
+        split.rotate();
+    }
+    let percentages = split.split();
+    for (percentage, expected) in percentages.iter().zip([70.0, 20.0, 10.0]) {
+        assert!((percentage - expected).abs() < 0.5, "{:?}", percentages);
+    }
+}
+
+//helper
+
+fn wallets(percentages: &[f64]) -> Vec<PayoutWallet> {
+    percentages
+        .iter()
+        .enumerate()
+        .map(|(i, percentage)| PayoutWallet {
+            wallet_address: format!("wallet{}", i),
+            percentage: *percentage,
+        })
+        .collect()
+}
@@ -0,0 +1,28 @@
+extern crate mithril;
+
+use mithril::platform;
+use mithril::profile::Priority;
+
+#[test]
+fn test_set_thread_priority_normal() {
+    assert!(platform::set_thread_priority(Priority::Normal).is_ok());
+    assert!(platform::set_process_priority(Priority::Normal).is_ok());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_set_thread_affinity() {
+    let result = std::thread::spawn(|| platform::set_thread_affinity(0))
+        .join()
+        .unwrap();
+    assert!(result.is_ok());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_set_thread_affinity_unknown_core() {
+    let result = std::thread::spawn(|| platform::set_thread_affinity(1023))
+        .join()
+        .unwrap();
+    assert!(result.is_err());
+}
@@ -0,0 +1,389 @@
+extern crate mithril;
+
+use mithril::byte_string;
+use mithril::pow::chukwa::{self, Chukwa};
+use mithril::pow::cryptonight::random_math::{self, Instr, Opcode};
+use mithril::pow::cryptonight::{CryptoNight, Variant};
+use mithril::pow::randomx::{self as rx, RandomX, LIGHT_MEMORY_BYTES, SCRATCHPAD_BYTES};
+use mithril::pow::{self, JobHints, PowAlgorithm};
+use mithril::randomx::memory::{SeedMemory, VmMemory, VmMemoryAllocator};
+use mithril::randomx::RandomXConfig;
+use mithril::worker::worker_pool::with_nonce;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+//a block header, the input of the test vectors of other miners
+const CN_BLOB: &str = "0305a0dbd6bf05cf16e503f3a66f78007cbf34144332ecbfc22ed95c8700383b309ace1923a0964b00000008ba939a62724c0d7581fce5761e9d8a0e6a1c3f924fdd8493d1115649c05eb601";
+const SEED_HASH: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+
+#[test]
+fn test_randomx() {
+    let mut randomx = RandomX::new(VmMemoryAllocator::with_mode(true));
+    assert_eq!(randomx.name(), "rx/0");
+    assert_eq!(
+        randomx.job_hints(),
+        JobHints {
+            seed_hash: true,
+            height: false
+        }
+    );
+    assert!(randomx.full_memory());
+    assert_eq!(randomx.memory_requirements().thread_bytes, SCRATCHPAD_BYTES);
+
+    randomx.light_mode();
+    assert!(!randomx.full_memory());
+    let requirements = randomx.memory_requirements();
+    assert_eq!(requirements.shared_bytes, LIGHT_MEMORY_BYTES);
+    assert_eq!(
+        requirements.total_bytes(4),
+        LIGHT_MEMORY_BYTES + 4 * SCRATCHPAD_BYTES
+    );
+}
+
+#[test]
+fn test_randomx_hash() {
+    let mut randomx = RandomX::new(VmMemoryAllocator::with_mode(false));
+    assert!(randomx.prepare(SEED_HASH, 0).unwrap().is_some());
+    assert!(randomx.prepare(SEED_HASH, 0).unwrap().is_none());
+
+    let memory = randomx.memory();
+    assert_eq!(memory.mode(), "light");
+    assert_eq!(memory.fill(), 0.0);
+    assert!(!memory.initialising());
+
+    let blob = format!("0c0c{}00000000{}05", "ab".repeat(37), "cd".repeat(33));
+    let input = byte_string::string_to_u8_array(&with_nonce(&blob, "01020304"));
+    let mut hasher = memory.hasher();
+    assert_eq!(
+        byte_string::u8_array_to_string(&hasher.hash(&input)),
+        "b27f25eeecc8f1bb8c8b67800b9ccf51670bcb0570ba364a3c745ca5fc721d44"
+    );
+}
+
+#[test]
+fn test_randomx_variants() {
+    let mut keva = pow::new_algorithm("rx/keva", || VmMemoryAllocator::with_mode(false)).unwrap();
+    assert_eq!(keva.name(), "rx/keva");
+    assert!(keva.job_hints().seed_hash);
+    assert_eq!(keva.memory_requirements().thread_bytes, 1024 * 1024);
+    assert!(keva.prepare(SEED_HASH, 0).unwrap().is_some());
+    assert_eq!(keva.memory().mode(), "light");
+
+    let blob = format!("0c0c{}00000000{}05", "ab".repeat(37), "cd".repeat(33));
+    let input = byte_string::string_to_u8_array(&with_nonce(&blob, "01020304"));
+    //computed with this implementation for the published parameter sets, not yet
+    //confirmed with xmrig, RandomGRAFT or RandomKEVA
+    assert_eq!(
+        byte_string::u8_array_to_string(&keva.memory().hasher().hash(&input)),
+        "3149d0a07ab950042aa100b3e5f4979e37faf9e84ce89781b020ac8e5028361a"
+    );
+
+    let mut graft = RandomX::with_variant(rx::Variant::Graft, VmMemoryAllocator::with_mode(true));
+    assert_eq!(graft.name(), "rx/graft");
+    assert_eq!(graft.memory_requirements().thread_bytes, SCRATCHPAD_BYTES);
+    graft.light_mode();
+    graft.prepare(SEED_HASH, 0).unwrap();
+    assert_eq!(
+        byte_string::u8_array_to_string(&graft.memory().hasher().hash(&input)),
+        "44c722230ac45fddacd8cc93fcb2cf97bda8d630426ee406072fd46a3202a3fd"
+    );
+    assert_eq!(rx::Variant::from_name("rx/graft"), Some(rx::Variant::Graft));
+    assert_eq!(rx::Variant::from_name("rx/wow"), None);
+}
+
+#[test]
+fn test_custom_randomx_variant() {
+    let custom = rx::CustomVariant {
+        name: "rx/custom".to_string(),
+        config: RandomXConfig {
+            argon_salt: b"RandomX-Custom\x01".to_vec(),
+            argon_memory_kib: 1024,
+            argon_iterations: 1,
+            dataset_base_bytes: 1 << 20,
+            dataset_extra_bytes: 0,
+            ..RandomXConfig::monero()
+        },
+    };
+    assert!(pow::new_algorithm("rx/custom", || VmMemoryAllocator::with_mode(false)).is_none());
+
+    rx::register_custom(std::slice::from_ref(&custom));
+    assert_eq!(rx::custom_names(), vec!["rx/custom"]);
+    assert!(pow::algorithm_names().contains(&"rx/custom"));
+    let mut algorithm =
+        pow::new_algorithm("rx/custom", || VmMemoryAllocator::with_mode(false)).unwrap();
+    assert_eq!(algorithm.name(), "rx/custom");
+    assert_eq!(
+        algorithm.memory_requirements().shared_bytes,
+        custom.config.cache_bytes()
+    );
+    algorithm.prepare(SEED_HASH, 0).unwrap();
+    let input = byte_string::string_to_u8_array(&with_nonce(&"ab".repeat(76), "01020304"));
+    let custom_hash = algorithm.memory().hasher().hash(&input);
+    let mut rx0 = RandomX::with_variant(rx::Variant::Rx0, VmMemoryAllocator::with_mode(false));
+    rx0.prepare(SEED_HASH, 0).unwrap();
+    assert_ne!(custom_hash, rx0.memory().hasher().hash(&input));
+
+    //a reload replaces the registered variants
+    rx::register_custom(&[]);
+    assert!(rx::custom_names().is_empty());
+    assert_eq!(rx::Variant::from_name("rx/custom"), None);
+}
+
+#[test]
+fn test_randomx_shared_memory() {
+    let seed_hash = "2222222222222222222222222222222222222222222222222222222222222222";
+    let mut primary = RandomX::new(VmMemoryAllocator::with_mode(false));
+    let mut dual = RandomX::new(VmMemoryAllocator::with_mode(false));
+    let mut keva = RandomX::with_variant(rx::Variant::Keva, VmMemoryAllocator::with_mode(false));
+    assert!(primary.prepare(seed_hash, 0).unwrap().is_some());
+    assert_eq!(dual.prepare(seed_hash, 0).unwrap(), Some(Duration::ZERO));
+    assert!(Arc::ptr_eq(&primary.memory(), &dual.memory()));
+    keva.prepare(seed_hash, 0).unwrap();
+    assert!(!Arc::ptr_eq(&primary.memory(), &keva.memory()));
+}
+
+#[test]
+fn test_watch_dataset_init() {
+    let memory = small_dataset(b"watched");
+    let outcome = rx::watch_dataset_init(
+        &memory,
+        2,
+        0,
+        || {},
+        Duration::from_secs(60),
+        Duration::from_millis(10),
+    );
+    assert_eq!(outcome, rx::InitOutcome::Finished);
+    assert_eq!(memory.dataset_fill(), 1.0);
+    assert!(memory.verify_checksum());
+}
+
+#[test]
+fn test_watch_dataset_init_stalled() {
+    let memory = small_dataset(b"stalled");
+    //the init thread is stuck until released
+    let released = Arc::new(AtomicBool::new(false));
+    let stuck = released.clone();
+    let outcome = rx::watch_dataset_init(
+        &memory,
+        1,
+        0,
+        move || {
+            while !stuck.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+            }
+        },
+        Duration::from_millis(100),
+        Duration::from_millis(10),
+    );
+    assert_eq!(outcome, rx::InitOutcome::Stalled);
+    assert_eq!(memory.dataset_fill(), 0.0);
+    let report = rx::stall_report(
+        &memory,
+        1,
+        Duration::from_secs(120),
+        Duration::from_secs(150),
+    );
+    assert!(report.starts_with(
+        "the dataset init made no progress for 120s, 0.0% of the 16384 items computed in 150s with 1 threads"
+    ));
+
+    //the aborted init ends once released, the retry computes the dataset
+    released.store(true, Ordering::Relaxed);
+    let outcome = rx::watch_dataset_init(
+        &memory,
+        1,
+        0,
+        || {},
+        Duration::from_secs(60),
+        Duration::from_millis(10),
+    );
+    assert_eq!(outcome, rx::InitOutcome::Finished);
+    assert_eq!(memory.dataset_fill(), 1.0);
+    assert!(memory.verify_checksum());
+}
+
+#[test]
+fn test_cryptonight() {
+    let mut cn = CryptoNight::new(Variant::CnR);
+    assert_eq!(cn.name(), "cn/r");
+    assert_eq!(
+        cn.job_hints(),
+        JobHints {
+            seed_hash: false,
+            height: true
+        }
+    );
+    assert!(!cn.full_memory());
+    assert_eq!(cn.memory_requirements().total_bytes(2), 4 * 1024 * 1024);
+    assert!(cn.prepare("", 1806260).unwrap().is_some());
+    assert!(cn.prepare("", 1806260).unwrap().is_none());
+    assert!(cn.prepare("", 1806261).unwrap().is_some());
+    assert_eq!(cn.memory().mode(), "scratchpad");
+
+    let cn0 = CryptoNight::new(Variant::Cn0);
+    assert_eq!(cn0.name(), "cn/0");
+    assert!(!cn0.job_hints().height);
+}
+
+#[test]
+fn test_cryptonight_hash() {
+    //the test vectors of the reference implementation
+    assert_eq!(
+        cn_hash(Variant::Cn0, 0, b""),
+        "eb14e8a833fac6fe9a43b57b336789c46ffe93f2868452240720607b14387e11"
+    );
+    assert_eq!(
+        cn_hash(Variant::Cn0, 0, b"This is a test"),
+        "a084f01d1437a09c6985401b60d43554ae105802c5f5d8a9b3253649c0be6605"
+    );
+    assert_eq!(
+        cn_hash(Variant::Cn0, 0, b"de omnibus dubitandum"),
+        "2f8e3df40bd11f9ac90c743ca8e32bb391da4fb98612aa3b6cdc639ee00b31f5"
+    );
+    assert_eq!(
+        cn_hash(
+            Variant::CnR,
+            1806260,
+            b"This is a test This is a test This is a test"
+        ),
+        "f759588ad57e758467295443a9bd71490abff8e9dad1b95b6bf2f5d0d78387bc"
+    );
+    assert_eq!(
+        cn_hash(
+            Variant::CnR,
+            1806261,
+            b"Lorem ipsum dolor sit amet, consectetur adipiscing"
+        ),
+        "5bb833deca2bdd7252a9ccd7b4ce0b6a4854515794b56c207262f7a5b9bdb566"
+    );
+}
+
+#[test]
+fn test_random_math() {
+    for height in 1806260..1806300 {
+        let code = random_math::generate(height);
+        assert!((60..=70).contains(&code.len()), "height {}", height);
+        assert!(code.iter().any(|instr| instr.src == 8));
+        assert!(code.iter().all(|instr| instr.dst < 4));
+    }
+    assert_eq!(random_math::generate(1), random_math::generate(1));
+    assert_ne!(random_math::generate(1), random_math::generate(2));
+
+    let mut r = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+    let code = [
+        Instr {
+            op: Opcode::Mul,
+            dst: 0,
+            src: 8,
+            c: 0,
+        },
+        Instr {
+            op: Opcode::Add,
+            dst: 1,
+            src: 0,
+            c: u32::MAX,
+        },
+        Instr {
+            op: Opcode::Ror,
+            dst: 2,
+            src: 4,
+            c: 0,
+        },
+        Instr {
+            op: Opcode::Xor,
+            dst: 3,
+            src: 8,
+            c: 0,
+        },
+    ];
+    random_math::execute(&code, &mut r);
+    assert_eq!(r[..4], [9, 10, 3u32.rotate_right(5), 4 ^ 9]);
+}
+
+#[test]
+fn test_cryptonight_variants() {
+    let blob = byte_string::string_to_u8_array(CN_BLOB);
+    assert_eq!(
+        cn_hash(Variant::Cn0, 0, &blob),
+        "1a3ffbee909b420d91f7be6e5fb56db71b3110d886011e877ee5786afd080100"
+    );
+    assert_eq!(
+        cn_hash(Variant::CnHeavy0, 0, &blob),
+        "9983f21bdf2010a8d707bb2f14d78664bbe1187f55014b39e5f3d69328e48fc2"
+    );
+    assert_eq!(
+        cn_hash(Variant::CnHeavyXhv, 0, &blob),
+        "5ac3f785c490c58550ec95d2726563577e7c1c212d0cde591273201e44fdd5b6"
+    );
+    assert_eq!(
+        cn_hash(Variant::CnPico, 0, &blob),
+        "08f421d7833117300eda66e98f4a2569093df300500173944efc401e9a4a17af"
+    );
+
+    assert_eq!(
+        Variant::from_name("cn-heavy/xhv"),
+        Some(Variant::CnHeavyXhv)
+    );
+    assert_eq!(Variant::from_name("cn-lite"), None);
+    assert_eq!(Variant::CnHeavy0.scratchpad_bytes(), 4 * 1024 * 1024);
+    assert_eq!(Variant::CnPico.scratchpad_bytes(), 256 * 1024);
+
+    let pico = pow::new_algorithm("cn-pico", || panic!("no RandomX memory")).unwrap();
+    assert_eq!(pico.name(), "cn-pico");
+    assert_eq!(pico.memory_requirements().thread_bytes, 256 * 1024);
+    assert!(!pico.job_hints().height);
+    assert!(pow::new_algorithm("cn/0", || panic!("no RandomX memory")).is_none());
+}
+
+#[test]
+fn test_chukwa() {
+    let blob = byte_string::string_to_u8_array(CN_BLOB);
+    let chukwa = pow::new_algorithm("argon2/chukwa", || panic!("no RandomX memory")).unwrap();
+    assert_eq!(chukwa.name(), "argon2/chukwa");
+    assert_eq!(chukwa.memory_requirements().total_bytes(2), 1024 * 1024);
+    let mut hasher = chukwa.memory().hasher();
+    assert_eq!(
+        byte_string::u8_array_to_string(&hasher.hash(&blob)),
+        "c158a105ae75c7561cfd029083a47a87653d51f914128e21c1971d8b10c49034"
+    );
+    //the memory is reused
+    assert_eq!(
+        byte_string::u8_array_to_string(&hasher.hash(&blob)),
+        "c158a105ae75c7561cfd029083a47a87653d51f914128e21c1971d8b10c49034"
+    );
+    assert_eq!(hasher.hash(b"short"), [0xff; 32]);
+
+    let mut hasher = Chukwa::new(chukwa::Variant::ChukwaV2).memory().hasher();
+    assert_eq!(
+        byte_string::u8_array_to_string(&hasher.hash(&blob)),
+        "77cf6958b3536e1f9f0d1ea165f22811ca7bc487ea9f52030b5050c17fcdd8f5"
+    );
+    assert_eq!(
+        chukwa::Variant::from_name("argon2/chukwav2"),
+        Some(chukwa::Variant::ChukwaV2)
+    );
+}
+
+//helper
+
+fn small_dataset(key: &[u8]) -> Arc<VmMemory> {
+    let config = RandomXConfig {
+        argon_memory_kib: 1024,
+        argon_iterations: 1,
+        dataset_base_bytes: 1 << 20,
+        dataset_extra_bytes: 0,
+        ..RandomXConfig::monero()
+    };
+    let seed_memory = SeedMemory::try_new_initialised_with(key, Arc::new(config));
+    Arc::new(VmMemory::with_dataset(Arc::new(seed_memory.unwrap())))
+}
+
+fn cn_hash(variant: Variant, height: u64, input: &[u8]) -> String {
+    let mut cn = CryptoNight::new(variant);
+    cn.prepare("", height).unwrap();
+    let mut hasher = cn.memory().hasher();
+    byte_string::u8_array_to_string(&hasher.hash(input))
+}
@@ -0,0 +1,35 @@
+#![cfg(unix)]
+
+extern crate mithril;
+
+use mithril::privileges;
+use mithril::privileges::PrivilegeConfig;
+
+#[test]
+fn test_lookup_user() {
+    let account = privileges::lookup_user("root").unwrap();
+    assert_eq!(account.user, "root");
+    assert_eq!(account.uid, 0);
+    assert_eq!(account.gid, 0);
+    assert!(!account.home.is_empty());
+
+    let err = privileges::lookup_user("mithril-no-such-user").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    assert!(privileges::lookup_user("bad\0name").is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_lookup_group() {
+    assert_eq!(privileges::lookup_group("root").unwrap(), 0);
+    assert!(privileges::lookup_group("mithril-no-such-group").is_err());
+}
+
+#[test]
+fn test_no_user_keeps_privileges() {
+    let conf = PrivilegeConfig {
+        user: String::new(),
+        group: String::new(),
+    };
+    assert_eq!(privileges::drop_privileges(&conf).unwrap(), None);
+}
@@ -0,0 +1,80 @@
+extern crate mithril;
+
+use mithril::profile;
+use mithril::profile::{Priority, Profile};
+use mithril::worker::worker_pool::WorkerConfig;
+
+#[test]
+fn test_builtin_profiles() {
+    let names: Vec<String> = profile::builtin_profiles()
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    assert_eq!(names, vec!["eco", "balanced", "max"]);
+
+    let eco = profile::find("eco", &[]).unwrap();
+    assert_eq!(eco.num_threads(8), 2);
+    assert_eq!(eco.priority, Priority::Idle);
+    assert_eq!(eco.throttle_percent, 50);
+    assert_eq!(profile::find("balanced", &[]).unwrap().num_threads(8), 4);
+    assert_eq!(profile::find("max", &[]).unwrap().num_threads(8), 8);
+    assert_eq!(profile::find("turbo", &[]), None);
+}
+
+#[test]
+fn test_num_threads_at_least_one() {
+    assert_eq!(profile::find("eco", &[]).unwrap().num_threads(2), 1);
+}
+
+#[test]
+fn test_user_profile_replaces_builtin() {
+    let user = vec![profile("eco", 10), profile("night", 75)];
+    let names: Vec<String> = profile::all_profiles(&user)
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    assert_eq!(names, vec!["balanced", "max", "eco", "night"]);
+    assert_eq!(profile::find("eco", &user).unwrap().threads_percent, 10);
+}
+
+#[test]
+fn test_next() {
+    let user = vec![profile("night", 75)];
+    assert_eq!(profile::next("", &user).name, "eco");
+    assert_eq!(profile::next("eco", &user).name, "balanced");
+    assert_eq!(profile::next("max", &user).name, "night");
+    assert_eq!(profile::next("night", &user).name, "eco");
+}
+
+#[test]
+fn test_apply() {
+    let mut worker_conf = WorkerConfig {
+        num_threads: 8,
+        auto_tune: true,
+        auto_tune_interval_minutes: 15,
+        auto_tune_log: "./bandit.log".to_string(),
+        profile: "".to_string(),
+        priority: Priority::Normal,
+        throttle_percent: 0,
+        affinity: false,
+    };
+    profile::find("balanced", &[])
+        .unwrap()
+        .apply(&mut worker_conf, 6);
+    assert_eq!(worker_conf.profile, "balanced");
+    assert_eq!(worker_conf.num_threads, 3);
+    assert!(!worker_conf.auto_tune);
+    assert_eq!(worker_conf.priority, Priority::Low);
+    assert_eq!(worker_conf.throttle_percent, 0);
+}
+
+//helper
+
+fn profile(name: &str, threads_percent: u64) -> Profile {
+    Profile {
+        name: name.to_string(),
+        threads_percent,
+        priority: Priority::Normal,
+        throttle_percent: 0,
+    }
+}
@@ -0,0 +1,100 @@
+extern crate mithril;
+
+use mithril::bench::{AlgorithmResult, BenchResults};
+use mithril::profit::{
+    daily_revenue, parse_markets, revenues, select_coin, with_bench_hashrates, CoinConfig,
+    CoinMarket,
+};
+use mithril::stratum::stratum_data::PoolConfig;
+
+#[test]
+fn test_daily_revenue() {
+    let market = CoinMarket {
+        price: 100.0,
+        difficulty: 86400.0 * 1000.0,
+        reward: 0.5,
+    };
+    //1000 H/s find one block per day
+    assert_eq!(daily_revenue(1000.0, &market), 50.0);
+    assert_eq!(daily_revenue(2000.0, &market), 100.0);
+    assert_eq!(
+        daily_revenue(
+            1000.0,
+            &CoinMarket {
+                difficulty: 0.0,
+                ..market
+            }
+        ),
+        0.0
+    );
+}
+
+#[test]
+fn test_select_coin_hysteresis() {
+    let revenues = vec![("monero".to_string(), 10.0), ("keva".to_string(), 10.4)];
+    //4% more is within the 5% hysteresis
+    assert_eq!(select_coin(Some("monero"), &revenues, 5.0), None);
+    assert_eq!(select_coin(Some("monero"), &revenues, 3.0), Some("keva"));
+    assert_eq!(select_coin(Some("keva"), &revenues, 0.0), None);
+    //without a current coin the best one is always selected
+    assert_eq!(select_coin(None, &revenues, 5.0), Some("keva"));
+}
+
+#[test]
+fn test_select_coin_without_revenue() {
+    let revenues = vec![("monero".to_string(), 0.0), ("keva".to_string(), 0.0)];
+    assert_eq!(select_coin(None, &revenues, 5.0), None);
+    assert_eq!(select_coin(Some("monero"), &[], 5.0), None);
+}
+
+#[test]
+fn test_parse_markets() {
+    let markets = parse_markets(
+        r#"{"monero": {"price": 160.0, "difficulty": 3.2e11, "reward": 0.6},
+            "keva": {"price": 0.01, "difficulty": 1e8, "reward": 10}}"#,
+    )
+    .unwrap();
+    assert_eq!(markets.len(), 2);
+    assert_eq!(markets["keva"].reward, 10.0);
+
+    let revenues = revenues(&[coin("monero", 5000.0), coin("wownero", 5000.0)], &markets);
+    assert!(revenues[0].1 > 0.0);
+    assert_eq!(revenues[1], ("wownero".to_string(), 0.0));
+
+    assert!(parse_markets(r#"{"monero": {"price": 160.0}}"#).is_err());
+    assert!(parse_markets("not json").is_err());
+}
+
+#[test]
+fn test_with_bench_hashrates() {
+    let mut results = BenchResults::default();
+    results.algorithms.insert(
+        "rx/0".to_string(),
+        AlgorithmResult {
+            hashrate: 4000.0,
+            threads: 8,
+            time: 0,
+        },
+    );
+    let mut keva = coin("keva", 0.0);
+    keva.pool_conf.algorithm = "rx/keva".to_string();
+    let coins = with_bench_hashrates(
+        vec![coin("monero", 0.0), coin("wownero", 3000.0), keva],
+        &results,
+    );
+    assert_eq!(coins[0].hashrate, 4000.0);
+    //a configured hashrate is kept
+    assert_eq!(coins[1].hashrate, 3000.0);
+    //not benchmarked
+    assert_eq!(coins[2].hashrate, 0.0);
+}
+
+//helper
+
+fn coin(name: &str, hashrate: f64) -> CoinConfig {
+    CoinConfig {
+        name: name.to_string(),
+        hashrate,
+        pool_conf: PoolConfig::default(),
+    }
+}
@@ -0,0 +1,294 @@
+extern crate crossbeam_channel;
+extern crate mithril;
+extern crate serde_json;
+
+use self::crossbeam_channel::{unbounded, Receiver, Sender};
+use mithril::stratum::binary;
+use mithril::stratum::proxy::{self, Proxy, ProxyConfig};
+use mithril::stratum::stratum_data::{PoolConfig, Share};
+use mithril::stratum::{self, StratumAction, StratumClient, StratumCmd};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TARGET: &str = "f3220000";
+
+#[test]
+fn test_miners_get_their_own_nonce_byte() {
+    let (proxy, _upstream) = connected_proxy();
+    let (first, first_lines) = unbounded();
+    let (second, second_lines) = unbounded();
+    let (mut first_slot, mut second_slot) = (None, None);
+    proxy.request(&mut first_slot, "10.0.0.1:1000", &first, &login(1, "rig1"));
+    proxy.request(&mut second_slot, "10.0.0.2:1000", &second, &login(1, ""));
+    assert_eq!((first_slot, second_slot), (Some(0), Some(1)));
+
+    let reply = next_line(&first_lines);
+    assert_eq!(reply["result"]["status"], "OK");
+    assert_eq!(blob_slot(&reply["result"]["job"]), "00");
+    assert_eq!(blob_slot(&next_line(&second_lines)["result"]["job"]), "01");
+
+    proxy.upstream_action(job("job2"));
+    assert_eq!(blob_slot(&next_line(&first_lines)["params"]), "00");
+    let job = next_line(&second_lines);
+    assert_eq!(job["method"], "job");
+    assert_eq!(job["params"]["job_id"], "job2");
+    assert_eq!(blob_slot(&job["params"]), "01");
+
+    let stats = proxy.stats();
+    assert_eq!(stats[0].name, "rig1");
+    assert_eq!(stats[1].name, "wallet");
+    proxy.disconnect(0);
+    let (third, _third_lines) = unbounded();
+    let mut third_slot = None;
+    proxy.request(&mut third_slot, "10.0.0.3:1000", &third, &login(1, ""));
+    assert_eq!(third_slot, Some(0));
+}
+
+#[test]
+fn test_shares_are_forwarded_and_answered() {
+    let (proxy, upstream) = connected_proxy();
+    let (lines, lines_rcvr) = unbounded();
+    let mut slot = None;
+    proxy.request(&mut slot, "10.0.0.1:1000", &lines, &login(1, "rig1"));
+    proxy.request(&mut slot, "10.0.0.1:1000", &lines, &login(1, "rig1"));
+    next_line(&lines_rcvr);
+    assert!(next_line(&lines_rcvr)["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("already"));
+
+    proxy.request(&mut slot, "", &lines, &submit(7, "job1", "12345600"));
+    let share = match upstream.try_recv().unwrap() {
+        StratumCmd::SubmitShare { share } => share,
+        cmd => panic!("{:?}", cmd),
+    };
+    assert_eq!(share.miner_id, "pool-miner-id");
+    assert_eq!(share.nonce, "12345600");
+    assert!(lines_rcvr.try_recv().is_err());
+    proxy.request(&mut slot, "", &lines, &submit(8, "job1", "12345600"));
+    assert!(next_line(&lines_rcvr)["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("duplicate"));
+
+    proxy.upstream_action(StratumAction::ShareAccepted {
+        share: share.clone(),
+        latency: Duration::from_millis(10),
+    });
+    let reply = next_line(&lines_rcvr);
+    assert_eq!(reply["id"], 7);
+    assert_eq!(reply["result"]["status"], "OK");
+
+    //another miner's nonce byte, a job the pool did not send and a lost connection
+    proxy.request(&mut slot, "", &lines, &submit(9, "job1", "12345601"));
+    assert!(next_line(&lines_rcvr)["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("nicehash"));
+    proxy.request(&mut slot, "", &lines, &submit(10, "job0", "12345700"));
+    assert!(next_line(&lines_rcvr)["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("unknown job"));
+    proxy.request(&mut slot, "", &lines, &submit(11, "job1", "12345800"));
+    upstream.try_recv().unwrap();
+    proxy.set_upstream(None);
+    assert_eq!(next_line(&lines_rcvr)["id"], 11);
+
+    let stats = &proxy.stats()[0];
+    assert_eq!(stats.accepted, 1);
+    assert_eq!(stats.rejected, 4);
+    assert_eq!(stats.hashes, share.difficulty);
+    assert!(stats.hashrate(stats.connected + Duration::from_secs(10)) > 0.0);
+}
+
+#[test]
+fn test_login_accepts_the_binary_protocol() {
+    let (proxy, _upstream) = connected_proxy();
+    let (lines, lines_rcvr) = unbounded();
+    let (mut first, mut second) = (None, None);
+    proxy.request(&mut first, "10.0.0.1:1000", &lines, &login(1, "rig1"));
+    let reply = next_line(&lines_rcvr);
+    assert_eq!(
+        reply["result"]["extensions"],
+        json!(["nicehash", "keepalive"])
+    );
+
+    let binary_login = login(1, "rig2").replace("}}", ",\"extensions\":[\"binary\"]}}");
+    proxy.request(&mut second, "10.0.0.2:1000", &lines, &binary_login);
+    let reply = lines_rcvr.try_recv().unwrap();
+    assert!(binary::switches_to_binary(&reply));
+}
+
+#[test]
+fn test_login_needs_a_job() {
+    let proxy = Proxy::new();
+    let (lines, lines_rcvr) = unbounded();
+    let mut slot = None;
+    proxy.request(&mut slot, "10.0.0.1:1000", &lines, &login(1, ""));
+    assert_eq!(slot, None);
+    assert!(next_line(&lines_rcvr)["error"].is_object());
+    proxy.request(&mut slot, "", &lines, "{");
+    assert!(next_line(&lines_rcvr)["error"].is_object());
+    proxy.request(&mut slot, "", &lines, &submit(2, "job1", "00000000"));
+    assert_eq!(next_line(&lines_rcvr)["error"]["message"], "not logged in");
+    proxy.request(
+        &mut slot,
+        "",
+        &lines,
+        "{\"id\":3,\"method\":\"keepalived\",\"params\":{}}",
+    );
+    assert_eq!(next_line(&lines_rcvr)["result"]["status"], "KEEPALIVED");
+}
+
+#[test]
+fn test_mithril_mines_through_the_proxy() {
+    mine_through_proxy(false);
+}
+
+#[test]
+fn test_mithril_mines_through_the_proxy_with_the_binary_protocol() {
+    mine_through_proxy(true);
+}
+
+//helper
+
+fn mine_through_proxy(binary: bool) {
+    let pool = TcpListener::bind("127.0.0.1:0").unwrap();
+    let pool_address = pool.local_addr().unwrap().to_string();
+    let (submits, submits_rcvr) = unbounded();
+    thread::spawn(move || fake_pool(pool, submits));
+
+    let listen = free_address();
+    let mut pool_conf = PoolConfig::default();
+    pool_conf.pool_address = pool_address;
+    pool_conf.wallet_address = "wallet".to_string();
+    let conf = ProxyConfig {
+        listen: listen.clone(),
+        pool: pool_conf,
+        stats_interval: Duration::from_secs(60),
+    };
+    thread::spawn(move || proxy::run(&conf));
+
+    //the proxy answers logins when the pool sent a job
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let (client, actions, blob) = loop {
+        assert!(Instant::now() < deadline, "no job from the proxy");
+        let (err_sndr, _err_rcvr) = unbounded();
+        let (action_sndr, actions) = unbounded();
+        let mut miner = PoolConfig::default();
+        miner.pool_address = listen.clone();
+        miner.wallet_address = "miner".to_string();
+        miner.nicehash = true;
+        if binary {
+            miner.extensions = vec![binary::EXTENSION.to_string()];
+        }
+        if let Ok(client) = StratumClient::login(miner, err_sndr, action_sndr) {
+            match actions.recv_timeout(Duration::from_secs(5)) {
+                Ok(StratumAction::Job { blob, .. }) => break (client, actions, blob),
+                _ => client.stop().unwrap(),
+            }
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    assert_eq!(&blob[84..86], "00");
+
+    let share = Share {
+        miner_id: "0".to_string(),
+        job_id: "job1".to_string(),
+        nonce: "abcdef00".to_string(),
+        hash: "00".repeat(32),
+        difficulty: 1,
+    };
+    stratum::submit_share(&client.new_cmd_channel(), share).unwrap();
+    assert_eq!(
+        submits_rcvr.recv_timeout(Duration::from_secs(5)).unwrap(),
+        "abcdef00"
+    );
+    assert!(matches!(
+        actions.recv_timeout(Duration::from_secs(5)),
+        Ok(StratumAction::ShareAccepted { .. })
+    ));
+    client.stop().unwrap();
+}
+
+fn connected_proxy() -> (Proxy, Receiver<StratumCmd>) {
+    let proxy = Proxy::new();
+    let (upstream, upstream_rcvr) = unbounded();
+    proxy.set_upstream(Some(upstream));
+    proxy.upstream_action(job("job1"));
+    (proxy, upstream_rcvr)
+}
+
+fn job(job_id: &str) -> StratumAction {
+    StratumAction::Job {
+        miner_id: "pool-miner-id".to_string(),
+        seed_hash: "ab".repeat(32),
+        height: 3000000,
+        blob: "07".repeat(76),
+        job_id: job_id.to_string(),
+        target: TARGET.to_string(),
+        algo: "rx/0".to_string(),
+    }
+}
+
+fn login(id: u32, rig_id: &str) -> String {
+    format!(
+        "{{\"id\":{},\"method\":\"login\",\"params\":{{\"login\":\"wallet\",\"pass\":\"x\",\"rigid\":\"{}\"}}}}",
+        id, rig_id
+    )
+}
+
+fn submit(id: u32, job_id: &str, nonce: &str) -> String {
+    format!(
+        "{{\"id\":{},\"method\":\"submit\",\"params\":{{\"id\":\"0\",\"job_id\":\"{}\",\"nonce\":\"{}\",\"result\":\"{}\"}}}}",
+        id,
+        job_id,
+        nonce,
+        "00".repeat(32)
+    )
+}
+
+fn next_line(lines: &Receiver<String>) -> Value {
+    serde_json::from_str(&lines.try_recv().unwrap()).unwrap()
+}
+
+fn blob_slot(job: &Value) -> String {
+    job["blob"].as_str().unwrap()[84..86].to_string()
+}
+
+fn free_address() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+/// Answers the login with a job and accepts all shares, the nonces are sent to `submits`
+fn fake_pool(pool: TcpListener, submits: Sender<String>) {
+    let (stream, _) = pool.accept().unwrap();
+    let mut writer = stream.try_clone().unwrap();
+    for line in BufReader::new(stream).lines() {
+        let request: Value = serde_json::from_str(&line.unwrap()).unwrap();
+        let reply = match request["method"].as_str().unwrap() {
+            "login" => format!(
+                "{{\"id\":1,\"jsonrpc\":\"2.0\",\"error\":null,\"result\":{{\"id\":\"pool-miner-id\",\"job\":{{\"blob\":\"{}\",\"job_id\":\"job1\",\"target\":\"{}\",\"seed_hash\":\"{}\"}},\"status\":\"OK\"}}}}",
+                "07".repeat(76),
+                TARGET,
+                "ab".repeat(32)
+            ),
+            "submit" => {
+                submits
+                    .send(request["params"]["nonce"].as_str().unwrap().to_string())
+                    .unwrap();
+                format!(
+                    "{{\"id\":{},\"jsonrpc\":\"2.0\",\"error\":null,\"result\":{{\"status\":\"OK\"}}}}",
+                    request["id"]
+                )
+            }
+            _ => continue,
+        };
+        writeln!(writer, "{}", reply).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+extern crate mithril;
+
+extern crate crossbeam_channel;
+
+use crossbeam_channel::unbounded;
+use mithril::pow::cryptonight::{self as cn, CryptoNight};
+use mithril::profile::Priority;
+use mithril::randomx::memory::VmMemoryAllocator;
+use mithril::replay::{self, JobRecord, JobRecorder, ReplaySummary};
+use mithril::stratum::stratum_data::Job;
+use mithril::worker::worker_pool;
+use mithril::worker::worker_pool::WorkerConfig;
+
+use std::time::Duration;
+
+#[test]
+fn test_record_and_read() {
+    let path = std::env::temp_dir().join("mithril_test_record.log");
+    let recorder = JobRecorder::create(&path).unwrap();
+    recorder.record("miner", &job("1", "aa"), false);
+    recorder.record("miner", &job("2", "bb"), true);
+
+    let records = replay::read_records(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].job, job("1", "aa"));
+    assert_eq!(records[0].miner_id, "miner");
+    assert!(!records[0].nicehash);
+    assert_eq!(records[1].job, job("2", "bb"));
+    assert!(records[1].nicehash);
+    assert!(records[0].ms <= records[1].ms);
+}
+
+#[test]
+fn test_parse_records() {
+    let log = r#"{"ms":0,"miner_id":"m","nicehash":false,"job":{"seed_hash":"aa","height":3,"blob":"00","job_id":"1","target":"ffffffff","algo":"rx/0"}}
+
+{"ms":20,"miner_id":"m","nicehash":false,"job":{"blob":"00","job_id":"2","target":"ffffffff"}}
+"#;
+    let records = replay::parse_records(log.as_bytes()).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].job.height, 3);
+    assert_eq!(records[1].ms, 20);
+    assert_eq!(records[1].job.seed_hash, "");
+
+    let err = replay::parse_records("\n{\"ms\":0}\n".as_bytes()).unwrap_err();
+    assert!(err.starts_with("line 2: invalid job record"), "{}", err);
+    assert!(replay::read_records(std::path::Path::new("/nonexistent/jobs.log")).is_err());
+}
+
+#[test]
+fn test_replay_jobs() {
+    let (share_sndr, share_rcvr) = unbounded();
+    let (metric_sndr, _metric_rcvr) = unbounded();
+    let mut pool = worker_pool::start(
+        1,
+        &worker_conf(),
+        &share_sndr,
+        1,
+        &metric_sndr,
+        Box::new(CryptoNight::new(cn::Variant::CnPico)),
+        VmMemoryAllocator::initial,
+    );
+    let records = vec![
+        record(0, job("1", "")),
+        record(50, job("2", "")),
+        record(100, job("3", "01")),
+    ];
+
+    let summary =
+        replay::replay(&records, &mut pool, &share_rcvr, Duration::from_millis(300)).unwrap();
+    pool.stop();
+    pool.join();
+    assert_eq!(summary.jobs, 3);
+    assert_eq!(summary.seed_changes, 1);
+    //every hash meets the target
+    assert!(summary.shares > 0);
+    assert!(summary.stale_shares <= summary.shares);
+    assert!(summary.duration >= Duration::from_millis(400));
+}
+
+#[test]
+fn test_format_summary() {
+    let summary = ReplaySummary {
+        jobs: 4,
+        seed_changes: 1,
+        shares: 10,
+        stale_shares: 2,
+        duration: Duration::from_millis(1500),
+    };
+    assert_eq!(
+        replay::format_summary(&summary),
+        "replayed 4 jobs with 1 seed changes in 1.5s, 10 shares, 2 of them stale"
+    );
+}
+
+//helper
+
+fn job(job_id: &str, seed_hash: &str) -> Job {
+    Job {
+        seed_hash: seed_hash.to_string(),
+        height: 0,
+        blob: "00".repeat(76),
+        job_id: job_id.to_string(),
+        target: "ffffffff".to_string(),
+        algo: "cn-pico".to_string(),
+    }
+}
+
+fn record(ms: u64, job: Job) -> JobRecord {
+    JobRecord {
+        ms,
+        miner_id: "miner".to_string(),
+        nicehash: false,
+        job,
+    }
+}
+
+fn worker_conf() -> WorkerConfig {
+    WorkerConfig {
+        num_threads: 1,
+        auto_tune: false,
+        auto_tune_interval_minutes: 15,
+        auto_tune_log: String::new(),
+        profile: String::new(),
+        priority: Priority::Normal,
+        throttle_percent: 0,
+        affinity: false,
+    }
+}
@@ -0,0 +1,181 @@
+extern crate mithril;
+
+use mithril::share_db;
+#[cfg(feature = "sqlite")]
+use mithril::share_db::Database;
+use mithril::share_db::{ConnectionEvent, HistoryEvent, ShareDbConfig};
+
+#[cfg(feature = "sqlite")]
+use std::time::Duration;
+use std::time::SystemTime;
+
+#[test]
+fn test_disabled_share_db_ignores_events() {
+    let share_db = share_db::start(ShareDbConfig {
+        enabled: false,
+        path: "/nonexistent/history.db".to_string(),
+        retention_days: 30,
+    });
+    share_db.record(connected(SystemTime::now(), "pool:3333"));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_insert_events() {
+    let (mut db, path) = open_db("insert");
+    let now = SystemTime::now();
+    db.insert(&connected(now, "pool:3333")).unwrap();
+    db.insert(&HistoryEvent::Job {
+        time: now,
+        job_id: "job1".to_string(),
+        difficulty: 50000,
+    })
+    .unwrap();
+    db.insert(&share(now, 120000, None)).unwrap();
+    db.insert(&share(
+        now,
+        u64::MAX,
+        Some("Low difficulty share".to_string()),
+    ))
+    .unwrap();
+    db.insert(&HistoryEvent::Connection {
+        time: now,
+        event: ConnectionEvent::Disconnected,
+        pool: "pool:3333".to_string(),
+        detail: "connection reset".to_string(),
+    })
+    .unwrap();
+
+    let conn = db.connection();
+    let shares: Vec<ShareRow> = conn
+        .prepare("SELECT pool, job_id, difficulty, hash_difficulty, latency_ms, accepted, error FROM shares ORDER BY accepted DESC")
+        .unwrap()
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+            ))
+        })
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(
+        shares,
+        vec![
+            (
+                "pool:3333".to_string(),
+                "job1".to_string(),
+                50000,
+                120000,
+                150,
+                true,
+                None
+            ),
+            (
+                "pool:3333".to_string(),
+                "job1".to_string(),
+                50000,
+                i64::MAX,
+                150,
+                false,
+                Some("Low difficulty share".to_string())
+            ),
+        ]
+    );
+
+    let job_pool: String = conn
+        .query_row("SELECT pool FROM jobs", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(job_pool, "pool:3333");
+
+    let events: Vec<String> = conn
+        .prepare("SELECT event FROM connections ORDER BY rowid")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(events, vec!["connected", "disconnected"]);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_prune_deletes_old_rows() {
+    let (mut db, path) = open_db("prune");
+    let now = SystemTime::now();
+    let old = now - Duration::from_secs(40 * 24 * 60 * 60);
+    db.insert(&connected(old, "pool:3333")).unwrap();
+    db.insert(&share(old, 1000, None)).unwrap();
+    db.insert(&share(now, 1000, None)).unwrap();
+
+    let deleted = db
+        .prune(now - Duration::from_secs(30 * 24 * 60 * 60))
+        .unwrap();
+    assert_eq!(deleted, 2);
+    let shares: i64 = db
+        .connection()
+        .query_row("SELECT COUNT(*) FROM shares", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(shares, 1);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_reopen_keeps_rows() {
+    let (mut db, path) = open_db("reopen");
+    db.insert(&share(SystemTime::now(), 1000, None)).unwrap();
+    drop(db);
+
+    let db = Database::open(&path).unwrap();
+    let shares: i64 = db
+        .connection()
+        .query_row("SELECT COUNT(*) FROM shares", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(shares, 1);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+//helper
+
+/// pool, job_id, difficulty, hash_difficulty, latency_ms, accepted, error
+#[cfg(feature = "sqlite")]
+type ShareRow = (String, String, i64, i64, i64, bool, Option<String>);
+
+#[cfg(feature = "sqlite")]
+fn open_db(name: &str) -> (Database, std::path::PathBuf) {
+    let path = std::env::temp_dir().join(format!("mithril_test_share_db_{}.db", name));
+    let _ = std::fs::remove_file(&path);
+    (Database::open(&path).unwrap(), path)
+}
+
+fn connected(time: SystemTime, pool: &str) -> HistoryEvent {
+    HistoryEvent::Connection {
+        time,
+        event: ConnectionEvent::Connected,
+        pool: pool.to_string(),
+        detail: String::new(),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn share(submitted: SystemTime, hash_difficulty: u64, error: Option<String>) -> HistoryEvent {
+    HistoryEvent::Share {
+        submitted,
+        job_id: "job1".to_string(),
+        nonce: "0000029a".to_string(),
+        difficulty: 50000,
+        hash_difficulty,
+        latency: Duration::from_millis(150),
+        error,
+    }
+}
@@ -0,0 +1,56 @@
+#![cfg(unix)]
+
+extern crate crossbeam_channel;
+extern crate libc;
+extern crate mithril;
+
+use crossbeam_channel::unbounded;
+use mithril::control::ControlCmd;
+use mithril::metric::stats::MinerStats;
+use mithril::signals;
+use mithril::signals::SignalAction;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn test_action() {
+    assert_eq!(signals::action(libc::SIGTERM), Some(SignalAction::Shutdown));
+    assert_eq!(signals::action(libc::SIGINT), Some(SignalAction::Shutdown));
+    assert_eq!(
+        signals::action(libc::SIGHUP),
+        Some(SignalAction::Control(ControlCmd::ReloadConfig))
+    );
+    assert_eq!(
+        signals::action(libc::SIGUSR1),
+        Some(SignalAction::StatsDump)
+    );
+    assert_eq!(
+        signals::action(libc::SIGUSR2),
+        Some(SignalAction::Control(ControlCmd::TogglePause))
+    );
+    assert_eq!(signals::action(libc::SIGPIPE), None);
+}
+
+#[test]
+fn test_signals_sent_to_main_loop() {
+    let (sndr, rcvr) = unbounded();
+    signals::start(Arc::new(MinerStats::new()), sndr);
+
+    unsafe {
+        libc::raise(libc::SIGUSR2);
+    }
+    assert_eq!(
+        rcvr.recv_timeout(Duration::from_secs(5)),
+        Ok(ControlCmd::TogglePause)
+    );
+
+    unsafe {
+        libc::raise(libc::SIGHUP);
+    }
+    assert_eq!(
+        rcvr.recv_timeout(Duration::from_secs(5)),
+        Ok(ControlCmd::ReloadConfig)
+    );
+    assert!(!signals::shutdown_requested());
+}
@@ -1,15 +1,24 @@
 extern crate crossbeam_channel;
 extern crate mithril;
+extern crate rustls;
 extern crate serde;
 extern crate serde_json;
 
 use self::crossbeam_channel::unbounded;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use mithril::bench::{AlgorithmResult, BenchResults};
+use mithril::byte_string;
+use mithril::pow;
 use mithril::stratum;
+use mithril::stratum::socks;
 use mithril::stratum::stratum_data;
+use mithril::stratum::tls;
 
 #[test]
 fn test_ser_submit_json() {
@@ -35,6 +44,10 @@ fn test_ser_login_json() {
         params: stratum_data::LoginParams {
             login: "foo".to_string(),
             pass: "bar".to_string(),
+            rigid: "".to_string(),
+            algo: Vec::new(),
+            algo_perf: BTreeMap::new(),
+            extensions: Vec::new(),
         },
     };
 
@@ -45,17 +58,202 @@ fn test_ser_login_json() {
 }
 
 #[test]
-fn test_parse_method_with_method_field() {
-    let method: stratum_data::Method =
-        serde_json::from_str("{\"jsonrpc\":\"2.0\",\"method\":\"job\",\"params\":{}}").unwrap();
-    assert_eq!(method.method, "job".to_string());
+fn test_ser_login_json_with_rig_id() {
+    let login_req = stratum_data::LoginRequest {
+        id: 1,
+        method: "login".to_string(),
+        params: stratum_data::LoginParams {
+            login: "foo".to_string(),
+            pass: "bar".to_string(),
+            rigid: "rig1".to_string(),
+            algo: Vec::new(),
+            algo_perf: BTreeMap::new(),
+            extensions: Vec::new(),
+        },
+    };
+
+    assert_eq!(
+        serde_json::to_string(&login_req).unwrap(),
+        "{\"id\":1,\"method\":\"login\",\"params\":{\"login\":\"foo\",\"pass\":\"bar\",\"rigid\":\"rig1\"}}"
+    );
+}
+
+#[test]
+fn test_ser_login_json_with_algo() {
+    let login_req = stratum_data::LoginRequest {
+        id: 1,
+        method: "login".to_string(),
+        params: stratum_data::LoginParams {
+            login: "foo".to_string(),
+            pass: "bar".to_string(),
+            rigid: "".to_string(),
+            algo: vec!["rx/0".to_string(), "cn/r".to_string()],
+            algo_perf: BTreeMap::from([("rx/0".to_string(), 5000.0)]),
+            extensions: Vec::new(),
+        },
+    };
+
+    assert_eq!(
+        serde_json::to_string(&login_req).unwrap(),
+        "{\"id\":1,\"method\":\"login\",\"params\":{\"login\":\"foo\",\"pass\":\"bar\",\"algo\":[\"rx/0\",\"cn/r\"],\"algo-perf\":{\"rx/0\":5000.0}}}"
+    );
+}
+
+#[test]
+fn test_login_algo_perf() {
+    let mut results = BenchResults::default();
+    for (name, hashrate) in [("rx/0", 5000.0), ("rx/wow", 4000.0)] {
+        results.algorithms.insert(
+            name.to_string(),
+            AlgorithmResult {
+                hashrate,
+                threads: 8,
+                time: 0,
+            },
+        );
+    }
+    //algorithms of older versions are not sent
+    assert_eq!(
+        stratum::login_algo_perf(&results),
+        BTreeMap::from([("rx/0".to_string(), 5000.0)])
+    );
+}
+
+#[test]
+fn test_login_algorithms() {
+    let algorithms = stratum::login_algorithms("cn/r");
+    assert_eq!(algorithms[0], "cn/r");
+    assert_eq!(algorithms.len(), pow::ALGORITHMS.len());
+    assert!(algorithms.contains(&"rx/0".to_string()));
+    assert_eq!(algorithms.iter().filter(|name| *name == "cn/r").count(), 1);
+}
+
+#[test]
+fn test_parse_message_with_method_field() {
+    let message = stratum_data::PoolMessage::parse(
+        r#"{"jsonrpc":"2.0","method":"job","params":{"blob":"0606","job_id":"1","target":"169f0200"}}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        message,
+        stratum_data::PoolMessage::Job(stratum_data::Job {
+            seed_hash: String::new(),
+            height: 0,
+            blob: "0606".to_string(),
+            job_id: "1".to_string(),
+            target: "169f0200".to_string(),
+            algo: String::new(),
+        })
+    );
+    assert_eq!(
+        stratum_data::PoolMessage::parse(
+            r#"{"jsonrpc":"2.0","method":"mining.notify","params":{}}"#
+        )
+        .unwrap(),
+        stratum_data::PoolMessage::UnknownMethod("mining.notify".to_string())
+    );
+}
+
+#[test]
+fn test_parse_message_without_method_field() {
+    assert!(matches!(
+        stratum_data::PoolMessage::parse(r#"{"jsonrpc":"2.0","params":{}}"#),
+        Err(stratum_data::MessageError::Empty)
+    ));
+    assert!(matches!(
+        stratum_data::PoolMessage::parse("not json"),
+        Err(stratum_data::MessageError::Json(_))
+    ));
+}
+
+#[test]
+fn test_parse_message_ignores_unknown_fields() {
+    let line = r#"{"id":7,"jsonrpc":"2.0","error":null,"result":{"status":"OK","extensions":["algo"]},"motd":"hi"}"#;
+    assert_eq!(
+        stratum_data::PoolMessage::parse(line).unwrap(),
+        stratum_data::PoolMessage::Status {
+            id: Some(7),
+            status: "OK".to_string()
+        }
+    );
+
+    let line = r#"{"jsonrpc":"2.0","method":"job","params":{"blob":"0606","job_id":"1","target":"169f0200","variant":"rx","cn_heavy":false}}"#;
+    assert!(matches!(
+        stratum_data::PoolMessage::parse(line).unwrap(),
+        stratum_data::PoolMessage::Job(_)
+    ));
 }
 
 #[test]
-fn test_parse_method_without_method_field() {
-    let result: Result<stratum_data::Method, serde_json::Error> =
-        serde_json::from_str("{\"jsonrpc\":\"2.0\",\"params\":{}}");
-    assert_eq!(result.is_err(), true);
+fn test_parse_message_malformed() {
+    //a job without blob
+    let err = stratum_data::PoolMessage::parse(
+        r#"{"jsonrpc":"2.0","method":"job","params":{"job_id":"1","target":"169f0200"}}"#,
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("invalid job: missing field `blob`"));
+
+    //a height that is not a number
+    assert!(matches!(
+        stratum_data::PoolMessage::parse(
+            r#"{"jsonrpc":"2.0","method":"job","params":{"blob":"06","job_id":"1","target":"ff","height":"x"}}"#
+        ),
+        Err(stratum_data::MessageError::Params("job", _))
+    ));
+
+    //an error without message
+    assert!(matches!(
+        stratum_data::PoolMessage::parse(r#"{"id":1,"error":{"code":-1}}"#),
+        Err(stratum_data::MessageError::Json(_))
+    ));
+}
+
+#[test]
+fn test_parse_message_error_and_login() {
+    assert_eq!(
+        stratum_data::PoolMessage::parse(
+            r#"{"id":3,"jsonrpc":"2.0","error":{"code":-1,"message":"Unauthenticated"},"result":null}"#
+        )
+        .unwrap(),
+        stratum_data::PoolMessage::Error {
+            id: Some(3),
+            error: stratum_data::ErrorDetails {
+                code: -1,
+                message: "Unauthenticated".to_string()
+            }
+        }
+    );
+
+    let line = r#"{"id":1,"jsonrpc":"2.0","error":null,"result":{"id":"42","status":"OK","job":{"blob":"0606","job_id":"1","target":"169f0200"}}}"#;
+    match stratum_data::PoolMessage::parse(line).unwrap() {
+        stratum_data::PoolMessage::Login {
+            id,
+            miner_id,
+            status,
+            job,
+        } => {
+            assert_eq!(id, Some(1));
+            assert_eq!(miner_id, "42");
+            assert_eq!(status, "OK");
+            assert_eq!(job.job_id, "1");
+        }
+        message => panic!("Wrong message returned: {:?}", message),
+    }
+}
+
+#[test]
+fn test_parse_line_dispatch_unexpected_status() {
+    let (tx, rx) = unbounded();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::None));
+
+    let line = r#"{"id":1,"jsonrpc":"2.0","error":null,"result":{"status":"BUSY"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+    assert!(matches!(
+        rx.recv().unwrap(),
+        stratum::StratumAction::Error { .. }
+    ));
 }
 
 #[test]
@@ -74,7 +272,7 @@ fn test_parse_line_dispatch_result_error() {
 
     match result {
         stratum::StratumAction::Error{err} => assert_eq!(err, "error received: Low difficulty share (code -1, raw json {\"id\":1,\"jsonrpc\":\"2.0\",\"error\":{\"code\":-1,\"message\":\"Low difficulty share\"}})".to_string()),
-        _ => assert!(false, "Wrong result returned: {:?}", result)
+        _ => panic!("Wrong result returned: {:?}", result)
     }
 }
 
@@ -115,6 +313,7 @@ fn test_parse_line_dispatch_result_initial_job() {
             blob,
             job_id,
             target,
+            ..
         } => {
             assert_eq!(miner_id, "930717205908149");
             assert_eq!(
@@ -125,7 +324,7 @@ fn test_parse_line_dispatch_result_initial_job() {
             assert_eq!(job_id, "738478949642740");
             assert_eq!(target, "169f0200");
         }
-        _ => assert!(false, "Wrong result returned: {:?}", result),
+        _ => panic!("Wrong result returned: {:?}", result),
     }
 }
 
@@ -155,8 +354,8 @@ fn test_parse_line_dispatch_result_initial_job_with_non_ok_result() {
     let result = rx.recv().unwrap();
 
     match result {
-        stratum::StratumAction::Error { .. } => assert!(true),
-        _ => assert!(false, "Wrong result returned: {:?}", result),
+        stratum::StratumAction::Error { .. } => {}
+        _ => panic!("Wrong result returned: {:?}", result),
     }
 }
 
@@ -179,8 +378,8 @@ fn test_parse_line_dispatch_unknown_method() {
     let result = rx.recv().unwrap();
 
     match result {
-        stratum::StratumAction::Error { .. } => assert!(true),
-        _ => assert!(false, "Wrong result returned: {:?}", result),
+        stratum::StratumAction::Error { .. } => {}
+        _ => panic!("Wrong result returned: {:?}", result),
     }
 }
 
@@ -211,6 +410,7 @@ fn test_parse_line_dispatch_job_method() {
             blob,
             job_id,
             target,
+            ..
         } => {
             assert_eq!(miner_id, "test_miner_id");
             assert_eq!(blob, "0606fcb29bcf051b9c7bfc60c98885de404ef48f721f09b8f51d37faf280470880bd120d4e9e0500000000577192c076fed53a24372bc43a3bed1d448a061ad06a262ac5e7f6803a28ccc705");
@@ -221,7 +421,29 @@ fn test_parse_line_dispatch_job_method() {
                 "ae2b3c3b6e013f9c3512a94a4e9f2cf0552f28a3dd0383ba7bac3f54ec06b56f"
             )
         }
-        _ => assert!(false, "Wrong result returned: {:?}", result),
+        _ => panic!("Wrong result returned: {:?}", result),
+    }
+}
+
+#[test]
+fn test_parse_line_dispatch_job_method_with_algo() {
+    let (tx, rx) = unbounded();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+
+    let line = format!(
+        r#"{{"jsonrpc":"2.0","method":"job","params":{{"blob":"{}","job_id":"1","target":"169f0200","height":1000,"algo":"cn/r"}}}}"#,
+        "06".repeat(76)
+    );
+    stratum::parse_line_dispatch_result(&line, &tx, &miner_id_mutex);
+
+    match rx.recv().unwrap() {
+        stratum::StratumAction::Job {
+            algo, seed_hash, ..
+        } => {
+            assert_eq!(algo, "cn/r");
+            assert_eq!(seed_hash, "");
+        }
+        result => panic!("Wrong result returned: {:?}", result),
     }
 }
 
@@ -251,7 +473,7 @@ fn test_parse_line_dispatch_job_method_missing_miner_id() {
             err,
             "miner_id not available for first mining job (login failed previously, this is a bug)"
         ),
-        _ => assert!(false, "Wrong result returned: {:?}", result),
+        _ => panic!("Wrong result returned: {:?}", result),
     }
 }
 
@@ -285,12 +507,373 @@ fn test_parse_line_dispatch_keepalive() {
     assert_eq!(stratum::StratumAction::KeepAliveOk, result);
 }
 
+#[test]
+fn test_parse_line_dispatch_control() {
+    let (tx, rx) = unbounded();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+
+    let line =
+        r#"{"jsonrpc":"2.0","method":"control","params":{"command":"profile","profile":"eco"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+    assert_eq!(
+        rx.recv().unwrap(),
+        stratum::StratumAction::Control {
+            control: stratum_data::ClusterControl::Profile {
+                profile: "eco".to_string()
+            }
+        }
+    );
+
+    let line = r#"{"jsonrpc":"2.0","method":"control","params":{"command":"explode"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+    assert!(matches!(
+        rx.recv().unwrap(),
+        stratum::StratumAction::Error { .. }
+    ));
+}
+
+#[test]
+fn test_parse_line_dispatch_fleet_config() {
+    let (tx, rx) = unbounded();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+
+    let line =
+        r#"{"jsonrpc":"2.0","method":"config","params":{"config":"[worker]\nnum_threads = 2\n"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+    assert_eq!(
+        rx.recv().unwrap(),
+        stratum::StratumAction::FleetConfig {
+            config: "[worker]\nnum_threads = 2\n".to_string()
+        }
+    );
+
+    let line = r#"{"jsonrpc":"2.0","method":"config","params":{}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+    assert!(matches!(
+        rx.recv().unwrap(),
+        stratum::StratumAction::Error { .. }
+    ));
+}
+
+#[test]
+fn test_job_validate() {
+    assert_eq!(job().validate(), Ok(()));
+    let invalid = |change: fn(&mut stratum_data::Job)| {
+        let mut job = job();
+        change(&mut job);
+        job.validate().unwrap_err()
+    };
+    assert_eq!(
+        invalid(|job| job.job_id.clear()),
+        stratum_data::JobError::MissingJobId
+    );
+    assert_eq!(
+        invalid(|job| job.blob = "06".repeat(42)),
+        stratum_data::JobError::BlobLength(42)
+    );
+    assert_eq!(
+        invalid(|job| job.blob = "06".repeat(409)),
+        stratum_data::JobError::BlobLength(409)
+    );
+    assert!(matches!(
+        invalid(|job| job.blob.replace_range(10..11, "x")),
+        stratum_data::JobError::Blob(byte_string::HexError::InvalidChar { position: 10, .. })
+    ));
+    assert_eq!(
+        invalid(|job| job.target = "169f02".to_string()),
+        stratum_data::JobError::TargetLength(3)
+    );
+    assert!(matches!(
+        invalid(|job| job.target = "169f020".to_string()),
+        stratum_data::JobError::Target(byte_string::HexError::OddLength(7))
+    ));
+    assert_eq!(
+        invalid(|job| job.target = "0000000000000000".to_string()),
+        stratum_data::JobError::ZeroTarget
+    );
+    assert_eq!(
+        invalid(|job| job.target = "ffffffff".to_string()),
+        stratum_data::JobError::EasyTarget(1)
+    );
+    assert_eq!(
+        invalid(|job| job.target = "ffffffffffffff02".to_string()),
+        stratum_data::JobError::EasyTarget(85)
+    );
+    assert!(matches!(
+        invalid(|job| job.seed_hash = "ae2b".to_string()),
+        stratum_data::JobError::SeedHash(_)
+    ));
+    //pools of algorithms without a seed
+    let mut job = job();
+    job.seed_hash.clear();
+    job.target = "ffffffffffffff00".to_string();
+    assert_eq!(job.validate(), Ok(()));
+    job.target = "5c8fc2f5285c8f02".to_string();
+    assert_eq!(job.validate(), Ok(()));
+}
+
+#[test]
+fn test_parse_line_dispatch_invalid_job() {
+    let (tx, rx) = unbounded();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+
+    let line = r#"{"jsonrpc":"2.0","method":"job","params":{"blob":"0606zz","job_id":"9","target":"169f0200"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+    match rx.recv().unwrap() {
+        stratum::StratumAction::Error { err } => {
+            assert_eq!(
+                err,
+                "invalid job 9: invalid blob: invalid hex char 'z' at position 4"
+            )
+        }
+        result => panic!("Wrong result returned: {:?}", result),
+    }
+
+    //missing fields
+    let line = r#"{"jsonrpc":"2.0","method":"job","params":{"job_id":"9"}}"#;
+    stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+    assert!(matches!(
+        rx.recv().unwrap(),
+        stratum::StratumAction::Error { .. }
+    ));
+}
+
+#[test]
+fn test_parse_submit_response_accepted() {
+    let pending = pending_with_share(5);
+    let line = r#"{"id":5,"jsonrpc":"2.0","error":null,"result":{"status":"OK"}}"#;
+
+    let result = stratum::parse_submit_response(line, &pending, received(&pending, 5));
+    assert_eq!(
+        result,
+        Some(stratum::StratumAction::ShareAccepted {
+            share: share(),
+            latency: Duration::from_millis(150)
+        })
+    );
+    assert!(pending.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_submit_response_rejected() {
+    let pending = pending_with_share(5);
+    let line = r#"{"id":5,"jsonrpc":"2.0","error":{"code":-1,"message":"Low difficulty share"}}"#;
+
+    let result = stratum::parse_submit_response(line, &pending, received(&pending, 5));
+    assert_eq!(
+        result,
+        Some(stratum::StratumAction::ShareRejected {
+            share: share(),
+            err: "Low difficulty share (code -1)".to_string(),
+            latency: Duration::from_millis(150)
+        })
+    );
+}
+
+#[test]
+fn test_parse_submit_response_unknown_id() {
+    let pending = pending_with_share(5);
+    let line = r#"{"id":1,"jsonrpc":"2.0","error":null,"result":{"status":"KEEPALIVED"}}"#;
+
+    assert_eq!(
+        stratum::parse_submit_response(line, &pending, Instant::now()),
+        None
+    );
+    assert_eq!(pending.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn test_socks_connect() {
+    let proxy = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_address = proxy.local_addr().unwrap().to_string();
+    let proxy_thread = thread::spawn(move || {
+        let (mut conn, _) = proxy.accept().unwrap();
+        let mut greeting = [0u8; 3];
+        conn.read_exact(&mut greeting).unwrap();
+        conn.write_all(&[5, 0]).unwrap();
+        let mut request = vec![
+            0u8;
+            socks::connect_request("pool.example.com:3333")
+                .unwrap()
+                .len()
+        ];
+        conn.read_exact(&mut request).unwrap();
+        conn.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0x0d, 0x05])
+            .unwrap();
+        conn.write_all(b"hello\n").unwrap();
+        (greeting, request)
+    });
+
+    let stream = socks::connect(&proxy_address, "pool.example.com:3333").unwrap();
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    assert_eq!(line, "hello\n");
+
+    let (greeting, request) = proxy_thread.join().unwrap();
+    assert_eq!(greeting, [5, 1, 0]);
+    let mut expected = vec![5, 1, 0, 3, 16];
+    expected.extend_from_slice(b"pool.example.com");
+    expected.extend_from_slice(&[0x0d, 0x05]);
+    assert_eq!(request, expected);
+}
+
+#[test]
+fn test_socks_connect_refused_by_proxy() {
+    let proxy = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_address = proxy.local_addr().unwrap().to_string();
+    let proxy_thread = thread::spawn(move || {
+        let (mut conn, _) = proxy.accept().unwrap();
+        let mut greeting = [0u8; 3];
+        conn.read_exact(&mut greeting).unwrap();
+        conn.write_all(&[5, 0]).unwrap();
+        let mut request = [0u8; 11];
+        conn.read_exact(&mut request).unwrap();
+        conn.write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+    });
+
+    let err = socks::connect(&proxy_address, "pool:3333").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "proxy {} could not connect to pool:3333: connection refused",
+            proxy_address
+        )
+    );
+    proxy_thread.join().unwrap();
+}
+
+#[test]
+fn test_socks_connect_request_invalid_target() {
+    assert!(socks::connect_request("pool").is_err());
+    assert!(socks::connect_request("pool:port").is_err());
+    assert!(socks::connect_request(":3333").is_err());
+}
+
+#[test]
+fn test_tls_connect() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let pool_thread = thread::spawn(move || {
+        let (conn, _) = listener.accept().unwrap();
+        let tls = rustls::ServerConnection::new(tls_server_config()).unwrap();
+        let mut stream = rustls::StreamOwned::new(tls, conn);
+        let mut line = String::new();
+        BufReader::new(&mut stream).read_line(&mut line).unwrap();
+        stream.write_all(b"{\"id\":1,\"result\":{}}\n").unwrap();
+        line
+    });
+
+    let conn = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let stream = tls::connect(conn, &format!("localhost:{}", port), tls_client_config()).unwrap();
+    //the send thread of the client writes through a clone
+    let mut writer = stream.try_clone().unwrap();
+    writer.write_all(b"{\"method\":\"login\"}\n").unwrap();
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    assert_eq!(line, "{\"id\":1,\"result\":{}}\n");
+    assert_eq!(pool_thread.join().unwrap(), "{\"method\":\"login\"}\n");
+}
+
+#[test]
+fn test_tls_connect_unknown_certificate() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let pool_thread = thread::spawn(move || {
+        let (conn, _) = listener.accept().unwrap();
+        let tls = rustls::ServerConnection::new(tls_server_config()).unwrap();
+        let mut stream = rustls::StreamOwned::new(tls, conn);
+        let _ = stream.read(&mut [0u8; 1]);
+    });
+
+    //the self-signed certificate is not in the Mozilla root certificates
+    let conn = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let err = tls::connect(conn, &format!("localhost:{}", port), tls::client_config())
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains("invalid peer certificate"),
+        "{}",
+        err
+    );
+    pool_thread.join().unwrap();
+}
+
 #[test]
 fn test_start_tick_thread_shutdown() {
     let (stop_tx, stop_rx) = unbounded();
-    let (rx, hnd) = stratum::start_tick_thread(Duration::from_secs(60), stop_rx);
+    let (rx, hnd) =
+        stratum::start_tick_thread(Duration::from_secs(60), stop_rx).expect("tick thread");
     stop_tx.send(()).expect("sending stop signal");
     let result = rx.recv().expect("stop signal");
     assert_eq!(stratum::Tick::Stop, result);
     hnd.join().expect("tick thread join");
 }
+
+//helper
+
+fn share() -> stratum_data::Share {
+    stratum_data::Share {
+        miner_id: "miner".to_string(),
+        job_id: "job".to_string(),
+        nonce: "0000029a".to_string(),
+        hash: "hash".to_string(),
+        difficulty: 120000,
+    }
+}
+
+fn job() -> stratum_data::Job {
+    stratum_data::Job {
+        seed_hash: "ae2b3c3b6e013f9c3512a94a4e9f2cf0552f28a3dd0383ba7bac3f54ec06b56f".to_string(),
+        height: 1000,
+        blob: "0606fcb29bcf051b9c7bfc60c98885de404ef48f721f09b8f51d37faf280470880bd120d4e9e0500000000577192c076fed53a24372bc43a3bed1d448a061ad06a262ac5e7f6803a28ccc705".to_string(),
+        job_id: "878440772206522".to_string(),
+        target: "169f0200".to_string(),
+        algo: String::new(),
+    }
+}
+
+fn pending_with_share(id: u32) -> stratum::PendingShares {
+    let mut pending = HashMap::new();
+    pending.insert(
+        id,
+        stratum::PendingShare {
+            share: share(),
+            submitted: Instant::now(),
+        },
+    );
+    Arc::new(Mutex::new(pending))
+}
+
+/// The time the response to the share arrives, 150ms after the submit
+fn received(pending: &stratum::PendingShares, id: u32) -> Instant {
+    pending.lock().unwrap()[&id].submitted + Duration::from_millis(150)
+}
+
+const TLS_CERTIFICATE: &[u8] = include_bytes!("tls/localhost.crt.der");
+const TLS_KEY: &[u8] = include_bytes!("tls/localhost.key.der");
+
+/// A pool with the self-signed certificate of localhost
+fn tls_server_config() -> Arc<rustls::ServerConfig> {
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(TLS_CERTIFICATE.to_vec())],
+            rustls::PrivateKey(TLS_KEY.to_vec()),
+        )
+        .unwrap();
+    Arc::new(config)
+}
+
+/// Trusts the self-signed certificate of localhost
+fn tls_client_config() -> Arc<rustls::ClientConfig> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots
+        .add(&rustls::Certificate(TLS_CERTIFICATE.to_vec()))
+        .unwrap();
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Arc::new(config)
+}
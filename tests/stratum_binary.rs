@@ -0,0 +1,112 @@
+extern crate mithril;
+extern crate serde_json;
+
+use mithril::stratum::binary::{self, Message};
+use std::io::{Cursor, ErrorKind};
+
+#[test]
+fn test_messages_round_trip() {
+    let job = r#"{"jsonrpc":"2.0","method":"job","params":{"blob":"0707ab","job_id":"job1","target":"f3220000","height":3000000,"seed_hash":"abcd","algo":"rx/0"}}"#;
+    let submit = r#"{"id":7,"method":"submit","params":{"id":"3","job_id":"job1","nonce":"12345603","result":"00000000000000000000000000000000000000000000000000000000000000ff"}}"#;
+    let accepted = r#"{"id":7,"jsonrpc":"2.0","error":null,"result":{"status":"OK"}}"#;
+    let rejected = r#"{"id":8,"jsonrpc":"2.0","error":{"code":-1,"message":"duplicate share"}}"#;
+    let keepalive = r#"{"id":1,"jsonrpc":"2.0","error":null,"result":{"status":"KEEPALIVED"}}"#;
+
+    let job_message = Message::from_line(job);
+    assert_eq!(
+        job_message,
+        Message::Job {
+            job_id: "job1".to_string(),
+            blob: vec![7, 7, 0xab],
+            target: vec![0xf3, 0x22, 0, 0],
+            height: 3000000,
+            seed_hash: vec![0xab, 0xcd],
+            algo: "rx/0".to_string(),
+        }
+    );
+    assert!(matches!(
+        Message::from_line(submit),
+        Message::Submit {
+            id: 7,
+            nonce: [0x12, 0x34, 0x56, 0x03],
+            ..
+        }
+    ));
+    assert_eq!(
+        Message::from_line(rejected),
+        Message::Result {
+            id: 8,
+            error: Some("duplicate share".to_string())
+        }
+    );
+    assert_eq!(
+        Message::from_line(keepalive),
+        Message::Json(keepalive.to_string())
+    );
+
+    for line in [job, submit, accepted, rejected, keepalive] {
+        let message = Message::from_line(line);
+        let mut frame = Vec::new();
+        binary::write_message(&mut frame, &message).unwrap();
+        let read = binary::read_message(&mut Cursor::new(&frame)).unwrap();
+        assert_eq!(read, message);
+        assert_eq!(json(&read.to_line()), json(line));
+    }
+}
+
+#[test]
+fn test_frames_are_smaller_than_lines() {
+    let blob = "07".repeat(76);
+    let job = format!(
+        r#"{{"jsonrpc":"2.0","method":"job","params":{{"blob":"{}","job_id":"job1","target":"f3220000","height":3000000,"seed_hash":"{}"}}}}"#,
+        blob,
+        "ab".repeat(32)
+    );
+    let mut frame = Vec::new();
+    binary::write_message(&mut frame, &Message::from_line(&job)).unwrap();
+    assert!(frame.len() * 2 < job.len(), "{} bytes", frame.len());
+}
+
+#[test]
+fn test_lines_without_a_compact_form_stay_json() {
+    //not hex, an odd length and a string id
+    let job =
+        r#"{"jsonrpc":"2.0","method":"job","params":{"blob":"zz","job_id":"job1","target":"f32"}}"#;
+    let submit = r#"{"id":"x","method":"submit","params":{"id":"3","job_id":"job1","nonce":"12345603","result":"00"}}"#;
+    for line in [job, submit, "{", r#"{"method":"report","params":{}}"#] {
+        assert_eq!(Message::from_line(line), Message::Json(line.to_string()));
+    }
+}
+
+#[test]
+fn test_invalid_frames() {
+    let read = |bytes: &[u8]| {
+        binary::read_message(&mut Cursor::new(bytes))
+            .unwrap_err()
+            .kind()
+    };
+    assert_eq!(read(&[0, 0, 0, 0]), ErrorKind::InvalidData);
+    assert_eq!(read(&[0, 0, 0, 1]), ErrorKind::InvalidData);
+    assert_eq!(read(&[1, 0, 0, 0, 9]), ErrorKind::InvalidData);
+    //a result with a truncated id
+    assert_eq!(read(&[3, 0, 0, 0, 3, 1, 0]), ErrorKind::InvalidData);
+    //the connection closed in the middle of a frame
+    assert_eq!(read(&[5, 0, 0, 0, 0, b'{']), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_login_extension() {
+    let login =
+        r#"{"id":1,"method":"login","params":{"login":"w","pass":"x","extensions":["binary"]}}"#;
+    assert!(binary::asks_binary(login));
+    assert!(!binary::asks_binary(&login.replace("binary", "other")));
+    let reply = r#"{"id":1,"result":{"id":"0","status":"OK","extensions":["nicehash","binary"]}}"#;
+    assert!(binary::switches_to_binary(reply));
+    assert!(!binary::switches_to_binary(&reply.replace("OK", "ERROR")));
+}
+
+//helper
+
+fn json(line: &str) -> serde_json::Value {
+    serde_json::from_str(line).unwrap()
+}
@@ -0,0 +1,373 @@
+extern crate crossbeam_channel;
+extern crate mithril;
+extern crate serde_json;
+
+use self::crossbeam_channel::{unbounded, Sender};
+use mithril::api::{read_request, write_response, Response};
+use mithril::difficulty;
+use mithril::stratum;
+use mithril::stratum::solo::{self, BlockTemplate, SoloClient};
+use mithril::stratum::stratum_data::{PoolConfig, Share, SoloConfig};
+use mithril::stratum::zmq::{self, Frame};
+use mithril::stratum::{StratumAction, StratumCmd};
+use serde_json::{json, Value};
+
+use std::io::{BufReader, Read};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_job_target() {
+    assert_eq!(solo::job_target(1), "ffffffffffffffff");
+    assert_eq!(solo::job_target(300000000000), "fa3faa0300000000");
+    assert_eq!(
+        difficulty::job_difficulty(&solo::job_target(300000000000)),
+        300000004451
+    );
+}
+
+#[test]
+fn test_meets_network_difficulty() {
+    assert!(solo::meets_network_difficulty(&"00".repeat(32), 300000000000));
+    assert!(solo::meets_network_difficulty(&"ff".repeat(32), 1));
+    assert!(!solo::meets_network_difficulty(&"ff".repeat(32), 2));
+    assert!(!solo::meets_network_difficulty("00", 1));
+}
+
+#[test]
+fn test_block_blob() {
+    let template = template(10);
+    let blob = solo::block_blob(&template, "deadbeef");
+    assert_eq!(&blob[78..86], "deadbeef");
+    assert_eq!(blob.len(), template.blocktemplate_blob.len());
+    assert_eq!(&blob[..78], &template.blocktemplate_blob[..78]);
+}
+
+#[test]
+fn test_mine_solo() {
+    let (requests, requests_rcvr) = unbounded();
+    let daemon = fake_daemon(requests, Some("high-hash"));
+    let (err_sndr, err_rcvr) = unbounded();
+    let (action_sndr, action_rcvr) = unbounded();
+    let client = SoloClient::login(daemon_pool(&daemon), err_sndr, action_sndr).unwrap();
+
+    let request = requests_rcvr.recv().unwrap();
+    assert_eq!(request["method"], "get_block_template");
+    assert_eq!(request["params"]["wallet_address"], "wallet");
+    assert_eq!(request["params"]["reserve_size"], 8);
+    let job_id = match action_rcvr.recv_timeout(TIMEOUT).unwrap() {
+        StratumAction::Job {
+            job_id,
+            blob,
+            target,
+            height,
+            seed_hash,
+            ..
+        } => {
+            assert_eq!(blob, template(10).blockhashing_blob);
+            assert_eq!(target, solo::job_target(300000000000));
+            assert_eq!(height, 10);
+            assert_eq!(seed_hash, "ab".repeat(32));
+            job_id
+        }
+        other => panic!("unexpected action {:?}", other),
+    };
+
+    let cmd = client.new_cmd_channel();
+    cmd.send(StratumCmd::SubmitShare {
+        share: share(&job_id),
+    })
+    .unwrap();
+    let submit = requests_rcvr.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(submit["method"], "submit_block");
+    assert_eq!(
+        submit["params"][0],
+        solo::block_blob(&template(10), "deadbeef")
+    );
+    assert!(matches!(
+        action_rcvr.recv_timeout(TIMEOUT).unwrap(),
+        StratumAction::ShareRejected { err, .. } if err == "high-hash"
+    ));
+
+    cmd.send(StratumCmd::SubmitShare {
+        share: share("unknown"),
+    })
+    .unwrap();
+    assert!(matches!(
+        action_rcvr.recv_timeout(TIMEOUT).unwrap(),
+        StratumAction::ShareRejected { .. }
+    ));
+
+    client.stop().unwrap();
+    assert!(err_rcvr.try_recv().is_err());
+}
+
+#[test]
+fn test_block_accepted() {
+    let (requests, requests_rcvr) = unbounded();
+    let daemon = fake_daemon(requests, None);
+    let (err_sndr, _err_rcvr) = unbounded();
+    let (action_sndr, action_rcvr) = unbounded();
+    let client = SoloClient::login(daemon_pool(&daemon), err_sndr, action_sndr).unwrap();
+    let job_id = match action_rcvr.recv_timeout(TIMEOUT).unwrap() {
+        StratumAction::Job { job_id, .. } => job_id,
+        other => panic!("unexpected action {:?}", other),
+    };
+    client
+        .new_cmd_channel()
+        .send(StratumCmd::SubmitShare {
+            share: share(&job_id),
+        })
+        .unwrap();
+    assert!(matches!(
+        action_rcvr.recv_timeout(TIMEOUT).unwrap(),
+        StratumAction::ShareAccepted { .. }
+    ));
+    //the template after the block
+    assert!(matches!(
+        action_rcvr.recv_timeout(TIMEOUT).unwrap(),
+        StratumAction::Job { height: 11, .. }
+    ));
+    assert_eq!(requests_rcvr.try_iter().count(), 3);
+    client.stop().unwrap();
+}
+
+#[test]
+fn test_daemon_not_reachable() {
+    let pool = daemon_pool("127.0.0.1:1");
+    let (err_sndr, _err_rcvr) = unbounded();
+    let (action_sndr, _action_rcvr) = unbounded();
+    assert!(SoloClient::login(pool.clone(), err_sndr, action_sndr).is_err());
+    assert!(!stratum::reachable(&pool));
+}
+
+#[test]
+fn test_reachable() {
+    let (requests, _requests_rcvr) = unbounded();
+    let daemon = fake_daemon(requests, None);
+    assert!(stratum::reachable(&daemon_pool(&daemon)));
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut pool = PoolConfig::default();
+    pool.pool_address = listener.local_addr().unwrap().to_string();
+    assert!(stratum::reachable(&pool));
+}
+
+#[test]
+fn test_block_notification() {
+    let (requests, _requests_rcvr) = unbounded();
+    let chain = Arc::new(Chain::new());
+    let daemon = fake_daemon_at(requests, None, chain.clone());
+    let (publisher, subscribed) = fake_publisher();
+    let mut pool = daemon_pool(&daemon);
+    pool.zmq = publisher;
+    let (err_sndr, _err_rcvr) = unbounded();
+    let (action_sndr, action_rcvr) = unbounded();
+    let client = SoloClient::login(pool, err_sndr, action_sndr).unwrap();
+    assert!(matches!(
+        action_rcvr.recv_timeout(TIMEOUT).unwrap(),
+        StratumAction::Job { height: 10, .. }
+    ));
+
+    let mut stream = subscribed.recv_timeout(TIMEOUT).unwrap();
+    chain.height.store(12, Ordering::SeqCst);
+    let notification = format!(
+        "{}:{{\"first_height\":12,\"first_prev_id\":\"{}\",\"ids\":[]}}",
+        zmq::CHAIN_MAIN_TOPIC,
+        "0b".repeat(32)
+    );
+    zmq::write_frame(&mut stream, &frame(notification.as_bytes())).unwrap();
+    //long before the next poll
+    assert!(matches!(
+        action_rcvr.recv_timeout(TIMEOUT).unwrap(),
+        StratumAction::Job { height: 12, .. }
+    ));
+    client.stop().unwrap();
+}
+
+#[test]
+fn test_mempool_refresh() {
+    let (requests, _requests_rcvr) = unbounded();
+    let chain = Arc::new(Chain::new());
+    let daemon = fake_daemon_at(requests, None, chain.clone());
+    let mut pool = daemon_pool(&daemon);
+    pool.solo = SoloConfig {
+        template_refresh_seconds: 1,
+        mempool_refresh: true,
+        ..SoloConfig::default()
+    };
+    let (err_sndr, _err_rcvr) = unbounded();
+    let (action_sndr, action_rcvr) = unbounded();
+    let client = SoloClient::login(pool, err_sndr, action_sndr).unwrap();
+    assert!(matches!(
+        action_rcvr.recv_timeout(TIMEOUT).unwrap(),
+        StratumAction::Job { height: 10, .. }
+    ));
+    //the same template is not mined again
+    assert!(action_rcvr.recv_timeout(Duration::from_millis(1500)).is_err());
+
+    chain.reward.store(700000000001, Ordering::SeqCst);
+    assert!(matches!(
+        action_rcvr.recv_timeout(TIMEOUT).unwrap(),
+        StratumAction::Job { height: 10, .. }
+    ));
+    client.stop().unwrap();
+}
+
+#[test]
+fn test_template_max_age() {
+    let (requests, _requests_rcvr) = unbounded();
+    let daemon = fake_daemon(requests, None);
+    let mut pool = daemon_pool(&daemon);
+    pool.solo = SoloConfig {
+        template_refresh_seconds: 1,
+        template_max_age_seconds: 2,
+        ..SoloConfig::default()
+    };
+    let (err_sndr, _err_rcvr) = unbounded();
+    let (action_sndr, action_rcvr) = unbounded();
+    let client = SoloClient::login(pool, err_sndr, action_sndr).unwrap();
+    let first = match action_rcvr.recv_timeout(TIMEOUT).unwrap() {
+        StratumAction::Job { job_id, .. } => job_id,
+        other => panic!("unexpected action {:?}", other),
+    };
+    //renewed though nothing changed
+    match action_rcvr.recv_timeout(TIMEOUT).unwrap() {
+        StratumAction::Job { job_id, height, .. } => {
+            assert_ne!(job_id, first);
+            assert_eq!(height, 10);
+        }
+        other => panic!("unexpected action {:?}", other),
+    }
+    client.stop().unwrap();
+}
+
+//helper
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn template(height: u64) -> BlockTemplate {
+    BlockTemplate {
+        blocktemplate_blob: format!("{:02x}", height).repeat(100),
+        blockhashing_blob: format!("{:02x}", height).repeat(76),
+        difficulty: 300000000000,
+        height,
+        prev_hash: format!("{:02x}", height).repeat(32),
+        seed_hash: "ab".repeat(32),
+        expected_reward: 700000000000,
+    }
+}
+
+fn share(job_id: &str) -> Share {
+    Share {
+        miner_id: "solo".to_string(),
+        job_id: job_id.to_string(),
+        nonce: "deadbeef".to_string(),
+        hash: "00".repeat(32),
+        difficulty: 300000000000,
+    }
+}
+
+fn daemon_pool(address: &str) -> PoolConfig {
+    let mut pool = PoolConfig::default();
+    pool.pool_address = address.to_string();
+    pool.wallet_address = "wallet".to_string();
+    pool.daemon = true;
+    pool
+}
+
+/// The chain of the fake daemon
+struct Chain {
+    height: AtomicU64,
+    /// changes with the mempool
+    reward: AtomicU64,
+}
+
+impl Chain {
+    fn new() -> Chain {
+        Chain {
+            height: AtomicU64::new(10),
+            reward: AtomicU64::new(700000000000),
+        }
+    }
+}
+
+/// A monerod that answers the RPC requests and sends them to `requests`. A block
+/// moves the chain on, unless `reject` is given as reason.
+fn fake_daemon(requests: Sender<Value>, reject: Option<&'static str>) -> String {
+    fake_daemon_at(requests, reject, Arc::new(Chain::new()))
+}
+
+fn fake_daemon_at(
+    requests: Sender<Value>,
+    reject: Option<&'static str>,
+    chain: Arc<Chain>,
+) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let request = read_request(&mut BufReader::new(&stream)).unwrap();
+            let request: Value = serde_json::from_slice(&request.body).unwrap();
+            let body = match request["method"].as_str().unwrap() {
+                "get_block_template" => {
+                    let template = template(chain.height.load(Ordering::SeqCst));
+                    json!({ "id": "0", "jsonrpc": "2.0", "result": {
+                        "blocktemplate_blob": template.blocktemplate_blob,
+                        "blockhashing_blob": template.blockhashing_blob,
+                        "difficulty": template.difficulty,
+                        "height": template.height,
+                        "prev_hash": template.prev_hash,
+                        "seed_hash": template.seed_hash,
+                        "expected_reward": chain.reward.load(Ordering::SeqCst),
+                        "status": "OK",
+                    }})
+                }
+                "submit_block" => match reject {
+                    Some(reason) => json!({ "id": "0", "jsonrpc": "2.0", "error": { "code": -7, "message": reason } }),
+                    None => {
+                        chain.height.fetch_add(1, Ordering::SeqCst);
+                        json!({ "id": "0", "jsonrpc": "2.0", "result": { "status": "OK" } })
+                    }
+                },
+                _ => json!({ "id": "0", "jsonrpc": "2.0", "result": { "status": "OK", "synchronized": true } }),
+            };
+            let _ = requests.send(request);
+            write_response(&mut &stream, &Response { status: 200, body }).unwrap();
+        }
+    });
+    address
+}
+
+/// A ZMQ publisher, the connection is sent once the subscriber subscribed to the blocks
+fn fake_publisher() -> (String, crossbeam_channel::Receiver<TcpStream>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (subscribed, subscribed_rcvr) = unbounded();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut greeting = [0; 64];
+        stream.read_exact(&mut greeting).unwrap();
+        assert_eq!(greeting, zmq::greeting(false));
+        std::io::Write::write_all(&mut stream, &zmq::greeting(true)).unwrap();
+        assert_eq!(zmq::read_frame(&mut stream).unwrap(), zmq::ready("SUB"));
+        zmq::write_frame(&mut stream, &zmq::ready("PUB")).unwrap();
+        let subscription = zmq::read_frame(&mut stream).unwrap();
+        assert_eq!(subscription.body[0], 1);
+        assert_eq!(&subscription.body[1..], zmq::CHAIN_MAIN_TOPIC.as_bytes());
+        subscribed.send(stream).unwrap();
+    });
+    (address, subscribed_rcvr)
+}
+
+fn frame(body: &[u8]) -> Frame {
+    Frame {
+        more: false,
+        command: false,
+        body: body.to_vec(),
+    }
+}
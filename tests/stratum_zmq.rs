@@ -0,0 +1,49 @@
+extern crate mithril;
+
+use mithril::stratum::zmq::{self, Frame};
+
+use std::io::Cursor;
+
+#[test]
+fn test_greeting() {
+    let greeting = zmq::greeting(true);
+    assert_eq!(greeting.len(), 64);
+    assert_eq!(greeting[0], 0xff);
+    assert_eq!(greeting[9], 0x7f);
+    assert_eq!(&greeting[10..12], &[3, 0]);
+    assert_eq!(&greeting[12..16], b"NULL");
+    assert_eq!(greeting[32], 1);
+    assert_eq!(zmq::greeting(false)[32], 0);
+}
+
+#[test]
+fn test_ready() {
+    let ready = zmq::ready("SUB");
+    assert!(ready.command);
+    assert_eq!(ready.body, b"\x05READY\x0bSocket-Type\x00\x00\x00\x03SUB");
+}
+
+#[test]
+fn test_frame_roundtrip() {
+    for len in [0, 1, 255, 256, 70000] {
+        let frame = Frame {
+            more: len % 2 == 0,
+            command: false,
+            body: vec![7; len],
+        };
+        let mut bytes = Vec::new();
+        zmq::write_frame(&mut bytes, &frame).unwrap();
+        //the long size above 255 bytes
+        assert_eq!(bytes.len(), len + if len > 255 { 9 } else { 2 });
+        assert_eq!(zmq::read_frame(&mut Cursor::new(bytes)).unwrap(), frame);
+    }
+}
+
+#[test]
+fn test_frame_too_large() {
+    let mut bytes = vec![0x02];
+    bytes.extend_from_slice(&(64u64 * 1024 * 1024).to_be_bytes());
+    assert!(zmq::read_frame(&mut Cursor::new(bytes)).is_err());
+    //cut off
+    assert!(zmq::read_frame(&mut Cursor::new(vec![0x00, 5, 1])).is_err());
+}
@@ -0,0 +1,67 @@
+extern crate mithril;
+
+use mithril::bench::CpuInfo;
+use mithril::stress::{format_report, stability, Mismatch, StressReport};
+
+use std::time::Duration;
+
+#[test]
+fn test_stability() {
+    assert_eq!(stability(&[]), None);
+
+    let s = stability(&[90.0, 110.0, 100.0, 100.0]).unwrap();
+    assert_eq!(s.mean, 100.0);
+    assert_eq!(s.min, 90.0);
+    assert_eq!(s.max, 110.0);
+    assert!((s.variation - 0.0707).abs() < 0.0001);
+
+    assert_eq!(stability(&[0.0]).unwrap().variation, 0.0);
+}
+
+#[test]
+fn test_format_report_passed() {
+    let report = new_report(Vec::new());
+    assert!(report.passed());
+
+    let text = format_report(&report);
+    assert!(text.contains("duration     0h 10m 00s"));
+    assert!(text.contains("hashrate     100.0 H/s (min 90.0, max 110.0, variation 7.07%)"));
+    assert!(text.contains("checked      20 hashes, 0 mismatches"));
+    assert!(text.ends_with("PASSED"));
+}
+
+#[test]
+fn test_format_report_mismatch() {
+    let report = new_report(vec![Mismatch {
+        thread: 1,
+        nonce: 7,
+        after: Duration::from_secs(120),
+        expected: "aa".to_string(),
+        actual: "bb".to_string(),
+    }]);
+    assert!(!report.passed());
+
+    let text = format_report(&report);
+    assert!(text.contains("checked      20 hashes, 1 mismatches"));
+    assert!(text.contains("MISMATCH after 0h 02m 00s thread 1 nonce 7: expected aa, got bb"));
+    assert!(text.contains("FAILED"));
+}
+
+//helper
+
+fn new_report(mismatches: Vec<Mismatch>) -> StressReport {
+    StressReport {
+        cpu: CpuInfo {
+            model: "Test CPU".to_string(),
+            physical_cores: 2,
+            logical_cores: 2,
+        },
+        threads: 2,
+        init_time: Duration::from_secs(30),
+        duration: Duration::from_secs(600),
+        verified: true,
+        hashrates: vec![90.0, 110.0, 100.0, 100.0],
+        checked: 20,
+        mismatches,
+    }
+}
@@ -0,0 +1,90 @@
+#![cfg(unix)]
+
+extern crate mithril;
+
+use mithril::systemd::{watchdog_interval, Notifier};
+
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+
+#[test]
+fn test_watchdog_interval() {
+    assert_eq!(
+        watchdog_interval(Some("30000000"), None, 42),
+        Some(Duration::from_secs(15))
+    );
+    assert_eq!(
+        watchdog_interval(Some("30000000"), Some("42"), 42),
+        Some(Duration::from_secs(15))
+    );
+    assert_eq!(watchdog_interval(Some("30000000"), Some("7"), 42), None);
+    assert_eq!(watchdog_interval(Some("0"), None, 42), None);
+    assert_eq!(watchdog_interval(Some("soon"), None, 42), None);
+    assert_eq!(watchdog_interval(None, None, 42), None);
+}
+
+#[test]
+fn test_notifier_disabled() {
+    let notifier = Notifier::new(None, Some(Duration::from_secs(15)));
+    assert!(!notifier.enabled());
+    assert_eq!(notifier.watchdog_interval(), None);
+    notifier.ready("mining");
+    notifier.watchdog();
+}
+
+#[test]
+fn test_notifier_sends_ready_once() {
+    let (socket, path) = notify_socket("ready");
+    let notifier = Notifier::new(
+        Some(path.to_string_lossy().to_string()),
+        Some(Duration::from_secs(15)),
+    );
+
+    assert_eq!(notifier.watchdog_interval(), Some(Duration::from_secs(15)));
+    notifier.ready("mining on pool:3333");
+    notifier.watchdog();
+    notifier.ready("mining on backup:3333");
+    notifier.stopping();
+
+    assert_eq!(receive(&socket), "READY=1\nSTATUS=mining on pool:3333");
+    assert_eq!(receive(&socket), "WATCHDOG=1");
+    assert_eq!(receive(&socket), "STATUS=mining on backup:3333");
+    assert_eq!(receive(&socket), "STOPPING=1");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_notifier_abstract_socket() {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+
+    let name = format!("mithril_test_notify_{}", std::process::id());
+    let addr = SocketAddr::from_abstract_name(&name).unwrap();
+    let socket = UnixDatagram::bind_addr(&addr).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let notifier = Notifier::new(Some(format!("@{}", name)), None);
+
+    notifier.status("paused");
+
+    assert_eq!(receive(&socket), "STATUS=paused");
+}
+
+//helper
+
+fn notify_socket(name: &str) -> (UnixDatagram, std::path::PathBuf) {
+    let path = std::env::temp_dir().join(format!("mithril_test_notify_{}.sock", name));
+    let _ = std::fs::remove_file(&path);
+    let socket = UnixDatagram::bind(&path).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    (socket, path)
+}
+
+fn receive(socket: &UnixDatagram) -> String {
+    let mut buf = [0u8; 256];
+    let len = socket.recv(&mut buf).unwrap();
+    String::from_utf8_lossy(&buf[..len]).to_string()
+}
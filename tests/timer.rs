@@ -1,6 +1,7 @@
 extern crate mithril;
 
 use mithril::mithril_config::DonationConfig;
+use mithril::profile::Priority;
 use mithril::timer;
 use mithril::worker::worker_pool::WorkerConfig;
 
@@ -11,6 +12,10 @@ fn test_interval_mod_setup_donation_disabled_auto_tune_enabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        profile: "".to_string(),
+        priority: Priority::Normal,
+        throttle_percent: 0,
+        affinity: false,
     };
     let donation_conf = DonationConfig { percentage: 0.0 };
 
@@ -26,9 +31,13 @@ fn test_interval_mod_setup_donation_below_threshold_auto_tune_enabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        profile: "".to_string(),
+        priority: Priority::Normal,
+        throttle_percent: 0,
+        affinity: false,
     };
     let donation_conf = DonationConfig {
-        percentage: 1.0 / 10.0 - std::f64::EPSILON,
+        percentage: 1.0 / 10.0 - f64::EPSILON,
     };
 
     let (interval, donation_mod) = timer::interval_mod_setup(&worker_conf, &donation_conf);
@@ -43,11 +52,15 @@ fn test_interval_mod_setup_donation_disabled_auto_tune_disabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        profile: "".to_string(),
+        priority: Priority::Normal,
+        throttle_percent: 0,
+        affinity: false,
     };
     let donation_conf = DonationConfig { percentage: 0.0 };
 
     let (interval, donation_mod) = timer::interval_mod_setup(&worker_conf, &donation_conf);
-    assert_eq!(interval, std::u64::MAX);
+    assert_eq!(interval, u64::MAX);
     assert_eq!(donation_mod, None);
 }
 
@@ -58,6 +71,10 @@ fn test_interval_mod_setup_donation_enabled_auto_tune_disabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        profile: "".to_string(),
+        priority: Priority::Normal,
+        throttle_percent: 0,
+        affinity: false,
     };
     let donation_conf = DonationConfig { percentage: 2.5 };
 
@@ -73,6 +90,10 @@ fn test_interval_mod_setup_donation_enabled_auto_tune_enabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        profile: "".to_string(),
+        priority: Priority::Normal,
+        throttle_percent: 0,
+        affinity: false,
     };
     let donation_conf = DonationConfig { percentage: 2.5 };
 
@@ -88,6 +109,10 @@ fn test_interval_mod_setup_donation_100_percent_enabled_auto_tune_enabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        profile: "".to_string(),
+        priority: Priority::Normal,
+        throttle_percent: 0,
+        affinity: false,
     };
     let donation_conf = DonationConfig { percentage: 100.0 };
 
@@ -0,0 +1,86 @@
+extern crate crossbeam_channel;
+extern crate mithril;
+extern crate tracing;
+extern crate tracing_subscriber;
+
+use crossbeam_channel::{unbounded, Receiver};
+use mithril::trace::{Field, SpanLayer, SpanRecord};
+use tracing::{field, span, Dispatch, Level};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::Registry;
+
+#[test]
+fn test_nested_spans() {
+    let (dispatch, spans) = dispatch();
+    tracing::dispatcher::with_default(&dispatch, || {
+        let job = span!(Level::DEBUG, "job").entered();
+        {
+            let _init = span!(Level::DEBUG, "dataset_init").entered();
+        }
+        drop(job);
+    });
+    let init = spans.recv().unwrap();
+    let job = spans.recv().unwrap();
+    assert_eq!(init.name, "dataset_init");
+    assert_eq!(job.name, "job");
+    assert_eq!(init.parent_id, Some(job.span_id));
+    assert_eq!(init.trace_id, job.trace_id);
+    assert_eq!(job.parent_id, None);
+    assert!(init.duration <= job.duration);
+}
+
+#[test]
+fn test_spans_per_thread() {
+    let (dispatch, spans) = dispatch();
+    tracing::dispatcher::with_default(&dispatch, || {
+        let _job = span!(Level::DEBUG, "job").entered();
+        let thread_dispatch = dispatch.clone();
+        std::thread::spawn(move || {
+            tracing::dispatcher::with_default(&thread_dispatch, || {
+                let _batch = span!(Level::DEBUG, "hash_batch").entered();
+            })
+        })
+        .join()
+        .unwrap();
+    });
+    let batch = spans.recv().unwrap();
+    let job = spans.recv().unwrap();
+    assert_eq!(batch.name, "hash_batch");
+    assert_eq!(batch.parent_id, None);
+    assert_ne!(batch.trace_id, job.trace_id);
+}
+
+#[test]
+fn test_fields() {
+    let (dispatch, spans) = dispatch();
+    tracing::dispatcher::with_default(&dispatch, || {
+        let batch = span!(
+            Level::DEBUG,
+            "hash_batch",
+            thread = 3usize,
+            job_id = "job1",
+            hashes = field::Empty
+        )
+        .entered();
+        batch.record("hashes", 100u64);
+        batch.record("hashes", 200u64);
+    });
+    let batch = spans.recv().unwrap();
+    assert_eq!(batch.name, "hash_batch");
+    assert_eq!(
+        batch.fields,
+        vec![
+            ("thread", Field::U64(3)),
+            ("job_id", Field::Str("job1".to_string())),
+            ("hashes", Field::U64(200)),
+        ]
+    );
+}
+
+//helper
+
+fn dispatch() -> (Dispatch, Receiver<SpanRecord>) {
+    let (sndr, rcvr) = unbounded();
+    let subscriber = Registry::default().with(SpanLayer::new(Some(sndr)));
+    (Dispatch::new(subscriber), rcvr)
+}
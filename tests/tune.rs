@@ -0,0 +1,105 @@
+extern crate mithril;
+
+use mithril::mithril_config::{self, ConfigFormat};
+use mithril::tune::{self, Candidate, TuneResult};
+
+use serde_json::json;
+use std::fs;
+
+#[test]
+fn test_default_thread_counts() {
+    assert_eq!(tune::default_thread_counts(8, 16), vec![4, 8, 16]);
+    assert_eq!(tune::default_thread_counts(4, 4), vec![2, 4]);
+    assert_eq!(tune::default_thread_counts(1, 1), vec![1]);
+    //a cgroup limit below the physical cores
+    assert_eq!(tune::default_thread_counts(8, 2), vec![2]);
+}
+
+#[test]
+fn test_candidates() {
+    let candidates = tune::candidates(&[2, 4]);
+    assert_eq!(candidates.len(), 8);
+    assert_eq!(candidates[0], candidate(2, false, true));
+    assert_eq!(candidates[3], candidate(2, true, false));
+    assert_eq!(candidates[7], candidate(4, true, false));
+}
+
+#[test]
+fn test_rank_and_format() {
+    let mut results = vec![
+        result(candidate(2, false, true), 1000.0, true),
+        result(candidate(8, true, true), 2500.0, false),
+        result(candidate(4, true, false), 2500.0, false),
+    ];
+    tune::rank(&mut results);
+    assert_eq!(results[0].candidate, candidate(4, true, false));
+    assert_eq!(results[1].candidate, candidate(8, true, true));
+    assert_eq!(results[2].candidate, candidate(2, false, true));
+
+    assert_eq!(
+        tune::format_table(&results),
+        "rank  threads  affinity  huge pages         hashrate
+   1        4  pinned    no               2500.0 H/s
+   2        8  pinned    unavailable      2500.0 H/s
+   3        2  any       yes              1000.0 H/s"
+    );
+}
+
+#[test]
+fn test_results_json() {
+    let results = vec![result(candidate(4, true, false), 2500.0, false)];
+    let path = std::path::Path::new("rig.toml");
+    assert_eq!(
+        tune::results_json(&results, Some(path)),
+        json!({
+            "results": [{
+                "threads": 4,
+                "affinity": true,
+                "huge_pages": false,
+                "hashrate": 2500.0,
+                "large_pages": false
+            }],
+            "written": "rig.toml",
+        })
+    );
+    assert_eq!(
+        tune::results_json(&[], None),
+        json!({"results": [], "written": null})
+    );
+}
+
+#[test]
+fn test_write_config() {
+    let path = std::env::temp_dir().join("mithril_test_tune_config.toml");
+    fs::write(&path, mithril_config::DEFAULT_CONFIG).unwrap();
+    tune::write_config(&path, ConfigFormat::Toml, &candidate(6, true, false)).unwrap();
+    let content = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    assert_eq!(config.worker_conf.num_threads, 6);
+    assert!(!config.worker_conf.auto_tune);
+    assert!(config.worker_conf.affinity);
+    assert!(content.contains("allocator = \"malloc\" # malloc, mmap, hugetlb or shm"));
+
+    let missing = std::env::temp_dir().join("mithril_test_tune_missing/config.toml");
+    assert!(tune::write_config(&missing, ConfigFormat::Toml, &candidate(1, false, true)).is_err());
+}
+
+//helper
+
+fn candidate(threads: u64, affinity: bool, huge_pages: bool) -> Candidate {
+    Candidate {
+        threads,
+        affinity,
+        huge_pages,
+    }
+}
+
+fn result(candidate: Candidate, hashrate: f64, large_pages: bool) -> TuneResult {
+    TuneResult {
+        candidate,
+        hashrate,
+        large_pages,
+    }
+}
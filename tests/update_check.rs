@@ -0,0 +1,68 @@
+extern crate mithril;
+
+use mithril::update_check::{
+    format_notice, is_newer, parse_release, parse_version, Release, CURRENT_VERSION,
+};
+
+#[test]
+fn test_parse_version() {
+    assert_eq!(parse_version("0.20.0"), Some((0, 20, 0)));
+    assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+    assert_eq!(parse_version("2.1"), Some((2, 1, 0)));
+    assert_eq!(parse_version("3"), Some((3, 0, 0)));
+    assert_eq!(parse_version("1.2.3-rc1"), Some((1, 2, 3)));
+    assert_eq!(parse_version("1.2.3+build5"), Some((1, 2, 3)));
+    assert_eq!(parse_version("1.2.3.4"), None);
+    assert_eq!(parse_version("latest"), None);
+    assert_eq!(parse_version(""), None);
+    assert!(parse_version(CURRENT_VERSION).is_some());
+}
+
+#[test]
+fn test_is_newer() {
+    assert!(is_newer("0.21.0", "0.20.0"));
+    assert!(is_newer("v0.20.1", "0.20.0"));
+    assert!(is_newer("1.0.0", "0.99.99"));
+    assert!(is_newer("0.10.0", "0.9.0"));
+    assert!(!is_newer("0.20.0", "0.20.0"));
+    assert!(!is_newer("0.19.5", "0.20.0"));
+    assert!(!is_newer("nightly", "0.20.0"));
+}
+
+#[test]
+fn test_parse_release() {
+    let body = r#"{"tag_name": "v0.21.0", "html_url": "https://github.com/Ragnaroek/mithril/releases/tag/v0.21.0", "name": "0.21.0"}"#;
+    assert_eq!(
+        parse_release(body),
+        Ok(Release {
+            version: "0.21.0".to_string(),
+            url: "https://github.com/Ragnaroek/mithril/releases/tag/v0.21.0".to_string(),
+        })
+    );
+    assert_eq!(parse_release(r#"{"tag_name": "0.21.0"}"#).unwrap().url, "");
+    assert!(parse_release(r#"{"message": "Not Found"}"#).is_err());
+    assert!(parse_release("<html>").is_err());
+}
+
+#[test]
+fn test_format_notice() {
+    let release = Release {
+        version: "99.0.0".to_string(),
+        url: "https://example.com/release".to_string(),
+    };
+    assert_eq!(
+        format_notice(&release),
+        format!(
+            "mithril 99.0.0 is available, this is {}, see https://example.com/release",
+            CURRENT_VERSION
+        )
+    );
+    let without_url = Release {
+        url: String::new(),
+        ..release
+    };
+    assert_eq!(
+        format_notice(&without_url),
+        format!("mithril 99.0.0 is available, this is {}", CURRENT_VERSION)
+    );
+}
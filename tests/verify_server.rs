@@ -0,0 +1,168 @@
+extern crate mithril;
+extern crate serde_json;
+
+use mithril::api::Request;
+use mithril::verify_server::{check_share, ShareCandidate, VerifyServer};
+use serde_json::json;
+
+const SEED_HASH: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+const HASH: &str = "b27f25eeecc8f1bb8c8b67800b9ccf51670bcb0570ba364a3c745ca5fc721d44";
+
+#[test]
+fn test_verify() {
+    let server = VerifyServer::new(2, 2);
+    let response = server.handle(&post("/verify", &share_json("ffffff7f")));
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.body,
+        json!({ "hash": HASH, "valid": true, "difficulty": 3 })
+    );
+
+    //the cache of the seed is reused, difficulty 3 does not meet a target of 1000
+    let verification = server.verify(&share("e4a63d00")).unwrap();
+    assert_eq!(verification.hash, HASH);
+    assert!(!verification.valid);
+    assert_eq!(server.seeds(), vec![SEED_HASH.to_string()]);
+}
+
+#[test]
+fn test_verify_batch() {
+    let server = VerifyServer::new(2, 3);
+    let mut invalid = share("ffffff7f");
+    invalid.nonce = "xx".to_string();
+    let shares = [share("ffffff7f"), invalid, share("e4a63d00")];
+    let results = server.verify_batch(&shares);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().map(|v| v.valid), Ok(true));
+    assert_eq!(results[1], Err("nonce is not hex".to_string()));
+    assert_eq!(results[2].as_ref().map(|v| v.valid), Ok(false));
+    assert_eq!(results[2].as_ref().unwrap().hash, HASH);
+    assert!(server.verify_batch(&[]).is_empty());
+
+    let body = format!(
+        "[{},{{\"blob\":\"00\",\"nonce\":\"00\",\"seed_hash\":\"{}\",\"target\":\"ff\"}}]",
+        share_json("ffffff7f"),
+        SEED_HASH
+    );
+    let response = server.handle(&post("/verify_batch", &body));
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.body,
+        json!([
+            { "hash": HASH, "valid": true, "difficulty": 3 },
+            { "error": "blob has to be at least 43 bytes" },
+        ])
+    );
+    assert_eq!(server.handle(&post("/verify_batch", "{}")).status, 400);
+}
+
+#[test]
+fn test_verify_batch_with_more_seeds_than_kept() {
+    let server = VerifyServer::new(1, 2);
+    let mut other_seed = share("ffffff7f");
+    other_seed.seed_hash = "22".repeat(32);
+    let shares = [share("ffffff7f"), other_seed, share("e4a63d00")];
+    let results = server.verify_batch(&shares);
+    assert_eq!(results[0].as_ref().unwrap().hash, HASH);
+    assert_ne!(results[1].as_ref().unwrap().hash, HASH);
+    assert_eq!(results[2].as_ref().unwrap().hash, HASH);
+    assert_eq!(server.seeds(), vec!["22".repeat(32)]);
+}
+
+#[test]
+fn test_check_share() {
+    assert_eq!(check_share(&share("ffffff7f")), Ok(()));
+    assert_eq!(check_share(&share("ffffffffffffff7f")), Ok(()));
+
+    let mut candidate = share("ffffff7f");
+    candidate.blob = candidate.blob[..84].to_string();
+    assert_eq!(
+        check_share(&candidate),
+        Err("blob has to be at least 43 bytes".to_string())
+    );
+
+    let mut candidate = share("ffffff7f");
+    candidate.nonce = "0102030g".to_string();
+    assert_eq!(check_share(&candidate), Err("nonce is not hex".to_string()));
+
+    let mut candidate = share("ffffff7f");
+    candidate.nonce = "010203".to_string();
+    assert_eq!(
+        check_share(&candidate),
+        Err("nonce has to be 4 bytes".to_string())
+    );
+
+    let mut candidate = share("ffffff7f");
+    candidate.seed_hash = "11".to_string();
+    assert_eq!(
+        check_share(&candidate),
+        Err("seed_hash has to be 32 bytes".to_string())
+    );
+
+    assert_eq!(
+        check_share(&share("fff")),
+        Err("target is not hex".to_string())
+    );
+    assert_eq!(
+        check_share(&share("ffff")),
+        Err("target has to be 4 or 8 bytes".to_string())
+    );
+    assert_eq!(
+        check_share(&share("00000000")),
+        Err("target must not be 0".to_string())
+    );
+}
+
+#[test]
+fn test_handle_errors() {
+    let server = VerifyServer::new(2, 2);
+
+    let response = server.handle(&post("/verify", "{\"blob\":\"00\"}"));
+    assert_eq!(response.status, 400);
+
+    let response = server.handle(&post("/verify", &share_json("0")));
+    assert_eq!(response.status, 400);
+    assert_eq!(response.body, json!({ "error": "target is not hex" }));
+
+    let mut get = post("/verify", "");
+    get.method = "GET".to_string();
+    assert_eq!(server.handle(&get).status, 405);
+    assert_eq!(server.handle(&post("/stats", "")).status, 404);
+    assert!(server.seeds().is_empty());
+}
+
+#[test]
+fn test_healthz() {
+    let server = VerifyServer::new(2, 2);
+    let mut request = post("/healthz", "");
+    request.method = "GET".to_string();
+    let response = server.handle(&request);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, json!({ "status": "ok", "seeds": [] }));
+}
+
+//helper
+
+fn share(target: &str) -> ShareCandidate {
+    serde_json::from_str(&share_json(target)).unwrap()
+}
+
+fn share_json(target: &str) -> String {
+    json!({
+        "blob": format!("0c0c{}00000000{}05", "ab".repeat(37), "cd".repeat(33)),
+        "nonce": "01020304",
+        "seed_hash": SEED_HASH,
+        "target": target,
+    })
+    .to_string()
+}
+
+fn post(path: &str, body: &str) -> Request {
+    Request {
+        method: "POST".to_string(),
+        path: path.to_string(),
+        query: String::new(),
+        authorization: None,
+        body: body.as_bytes().to_vec(),
+    }
+}
@@ -0,0 +1,130 @@
+extern crate mithril;
+
+use mithril::mithril_config;
+use mithril::wallet;
+use mithril::wallet::{Address, AddressError, AddressKind, Network};
+
+#[test]
+fn test_decode_standard_address() {
+    let address = wallet::decode(&donation_address()).unwrap();
+    assert_eq!(address.network, Network::Mainnet);
+    assert_eq!(address.kind, AddressKind::Standard);
+    assert_eq!(address.payment_id, None);
+    assert_eq!(wallet::encode(&address), donation_address());
+}
+
+#[test]
+fn test_encode_decode_all_kinds() {
+    for network in [Network::Mainnet, Network::Testnet, Network::Stagenet] {
+        for kind in [
+            AddressKind::Standard,
+            AddressKind::Subaddress,
+            AddressKind::Integrated,
+        ] {
+            let address = address(network, kind);
+            let encoded = wallet::encode(&address);
+            let expected_len = match kind {
+                AddressKind::Integrated => wallet::INTEGRATED_ADDRESS_LENGTH,
+                _ => wallet::ADDRESS_LENGTH,
+            };
+            assert_eq!(encoded.len(), expected_len);
+            assert_eq!(wallet::decode(&encoded), Ok(address));
+        }
+    }
+}
+
+#[test]
+fn test_address_prefix_characters() {
+    let first_char = |network, kind| wallet::encode(&address(network, kind)).remove(0);
+    assert_eq!(first_char(Network::Mainnet, AddressKind::Standard), '4');
+    assert_eq!(first_char(Network::Mainnet, AddressKind::Subaddress), '8');
+    assert_eq!(first_char(Network::Mainnet, AddressKind::Integrated), '4');
+    assert_eq!(first_char(Network::Testnet, AddressKind::Standard), '9');
+    assert_eq!(first_char(Network::Stagenet, AddressKind::Standard), '5');
+}
+
+#[test]
+fn test_decode_errors() {
+    let address = donation_address();
+    assert_eq!(wallet::decode(""), Err(AddressError::Empty));
+    assert_eq!(
+        wallet::decode(&address.replace('y', "l")),
+        Err(AddressError::InvalidCharacter('l'))
+    );
+    assert_eq!(
+        wallet::decode(&address[..94]),
+        Err(AddressError::InvalidLength(94))
+    );
+    assert_eq!(
+        wallet::decode(&address.replacen('8', "9", 1)),
+        Err(AddressError::WrongChecksum)
+    );
+    assert_eq!(
+        wallet::decode(&format!("{}{}", &address[..88], "zzzzzzz")),
+        Err(AddressError::InvalidEncoding)
+    );
+    assert_eq!(
+        wallet::decode(&format!("{}{}", address, "1".repeat(11))),
+        Err(AddressError::WrongLength {
+            kind: AddressKind::Standard,
+            length: 106
+        })
+    );
+    let unknown_prefix = format!("1{}", &address[1..]);
+    assert!(matches!(
+        wallet::decode(&unknown_prefix),
+        Err(AddressError::UnknownPrefix(_))
+    ));
+}
+
+#[test]
+fn test_address_part() {
+    assert_eq!(wallet::address_part("4abc.rig1"), "4abc");
+    assert_eq!(wallet::address_part("4abc+50000"), "4abc");
+    assert_eq!(wallet::address_part("4abc"), "4abc");
+}
+
+#[test]
+fn test_base58() {
+    assert_eq!(wallet::base58_encode(&[0x00]), "11");
+    assert_eq!(wallet::base58_encode(&[0xff]), "5Q");
+    assert_eq!(wallet::base58_encode(&[0xff; 8]), "jpXCZedGfVQ");
+    assert_eq!(wallet::base58_decode("jpXCZedGfVQ"), Some(vec![0xff; 8]));
+    assert_eq!(wallet::base58_decode("5Q"), Some(vec![0xff]));
+
+    let data: Vec<u8> = (0..69).collect();
+    assert_eq!(
+        wallet::base58_decode(&wallet::base58_encode(&data)),
+        Some(data)
+    );
+}
+
+#[test]
+fn test_base58_invalid() {
+    // overflows the byte of a one byte block
+    assert_eq!(wallet::base58_decode("zz"), None);
+    // overflows 64 bits
+    assert_eq!(wallet::base58_decode("zzzzzzzzzzz"), None);
+    // no block has 4 characters
+    assert_eq!(wallet::base58_decode("1111"), None);
+    assert_eq!(wallet::base58_decode("0O"), None);
+}
+
+//helper
+
+fn donation_address() -> String {
+    mithril_config::donation_conf().wallet_address.clone()
+}
+
+fn address(network: Network, kind: AddressKind) -> Address {
+    Address {
+        network,
+        kind,
+        spend_key: [1; 32],
+        view_key: [2; 32],
+        payment_id: match kind {
+            AddressKind::Integrated => Some([3; 8]),
+            _ => None,
+        },
+    }
+}
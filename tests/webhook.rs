@@ -0,0 +1,220 @@
+extern crate mithril;
+
+use mithril::metric::stats::StatsSnapshot;
+use mithril::stratum::stratum_data::Share;
+use mithril::webhook::{
+    self, render, Alert, AlertEvent, AlertMonitor, Alerter, FoundBlock, WebhookConfig, ALL_EVENTS,
+    DEFAULT_TEMPLATE,
+};
+
+use std::ffi::OsStr;
+
+#[test]
+fn test_render_default_template() {
+    let alert = Alert {
+        event: AlertEvent::LowHashrate,
+        message: "hashrate is low".to_string(),
+        hashrate: 1234.5,
+    };
+    let body = render(DEFAULT_TEMPLATE, &alert, "rig01", 1588000000);
+    assert_eq!(
+        body,
+        r#"{"event":"low_hashrate","rig":"rig01","message":"hashrate is low","hashrate":1234.50,"timestamp":1588000000}"#
+    );
+}
+
+#[test]
+fn test_render_escapes_strings() {
+    let alert = Alert {
+        event: AlertEvent::PoolDisconnect,
+        message: "error \"timeout\"\nretrying".to_string(),
+        hashrate: 0.0,
+    };
+    let body = render(
+        r#"{"content":"{{rig}}: {{message}}"}"#,
+        &alert,
+        "rig \"1\"",
+        0,
+    );
+    assert_eq!(
+        body,
+        r#"{"content":"rig \"1\": error \"timeout\"\nretrying"}"#
+    );
+    let parsed: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(parsed["content"], "rig \"1\": error \"timeout\"\nretrying");
+}
+
+#[test]
+fn test_render_does_not_expand_placeholders_in_values() {
+    let alert = Alert {
+        event: AlertEvent::PoolDisconnect,
+        message: "bad worker name {{rig}} {{timestamp}}".to_string(),
+        hashrate: 0.0,
+    };
+    let body = render(
+        "{{message}} on {{rig}} {{unknown}} {{rig",
+        &alert,
+        "rig01",
+        7,
+    );
+    assert_eq!(
+        body,
+        "bad worker name {{rig}} {{timestamp}} on rig01 {{unknown}} {{rig"
+    );
+}
+
+#[test]
+fn test_zero_hashrate_fires_once() {
+    let mut monitor = AlertMonitor::new(&config());
+
+    assert_eq!(monitor.check(snapshot(0, 0, 0)), vec![]);
+    assert_eq!(monitor.check(snapshot(0, 0, 0)), vec![]);
+    let alerts = monitor.check(snapshot(0, 0, 0));
+    assert_eq!(events(&alerts), vec![AlertEvent::ZeroHashrate]);
+    assert_eq!(monitor.check(snapshot(0, 0, 0)), vec![]);
+
+    //recovers and fires again after the next outage
+    monitor.check(snapshot(6000, 0, 0));
+    monitor.check(snapshot(12000, 0, 0));
+    monitor.check(snapshot(12000, 0, 0));
+    let alerts = monitor.check(snapshot(12000, 0, 0));
+    assert_eq!(events(&alerts), vec![AlertEvent::ZeroHashrate]);
+}
+
+#[test]
+fn test_low_hashrate() {
+    let mut monitor = AlertMonitor::new(&config());
+
+    //100 H/s is expected, 60 hashes per minute is 1 H/s
+    let mut alerts = Vec::new();
+    for i in 0..4 {
+        alerts.extend(monitor.check(snapshot(i * 60, 0, 0)));
+    }
+    assert_eq!(events(&alerts), vec![AlertEvent::LowHashrate]);
+    assert_eq!(alerts[0].hashrate, 1.0);
+}
+
+#[test]
+fn test_hashrate_above_threshold_does_not_fire() {
+    let mut monitor = AlertMonitor::new(&config());
+    for i in 0..10 {
+        assert_eq!(monitor.check(snapshot(i * 60 * 200, 0, 0)), vec![]);
+    }
+}
+
+#[test]
+fn test_high_reject_rate() {
+    let mut monitor = AlertMonitor::new(&config());
+    let hashes_per_minute = 60 * 200;
+
+    monitor.check(snapshot(0, 0, 0));
+    monitor.check(snapshot(hashes_per_minute, 5, 0));
+    monitor.check(snapshot(2 * hashes_per_minute, 9, 0));
+    //1 of 10 shares rejected in the window is not above 10%
+    assert_eq!(monitor.check(snapshot(3 * hashes_per_minute, 9, 1)), vec![]);
+    let alerts = monitor.check(snapshot(4 * hashes_per_minute, 9, 3));
+    assert_eq!(events(&alerts), vec![AlertEvent::HighRejectRate]);
+    assert_eq!(
+        alerts[0].message,
+        "3 of 7 shares (42.9%) rejected in the last 3 minutes"
+    );
+}
+
+#[test]
+fn test_all_events_parse() {
+    for event in ALL_EVENTS.iter() {
+        assert_eq!(event.to_string().parse::<AlertEvent>(), Ok(*event));
+    }
+}
+
+#[test]
+fn test_found_block() {
+    let share = Share {
+        miner_id: "solo".to_string(),
+        job_id: "10-1".to_string(),
+        nonce: "deadbeef".to_string(),
+        //difficulty 255
+        hash: format!("{}{}", "00".repeat(31), "01"),
+        difficulty: 100,
+    };
+    let alerter = Alerter::new(&config());
+    assert_eq!(alerter.found_block(&share, 10, "pool", false), None);
+    let block = alerter
+        .found_block(&share, 10, "127.0.0.1:18081", true)
+        .unwrap();
+    assert_eq!(block.height, 10);
+    assert_eq!(block.difficulty, 255);
+    assert_eq!(block.pool, "127.0.0.1:18081");
+    assert_eq!(
+        block.message(),
+        "block found at height 10 on 127.0.0.1:18081 (difficulty 255, job 10-1, nonce deadbeef)"
+    );
+
+    let alerter = Alerter::new(&WebhookConfig {
+        block_difficulty: 200,
+        ..config()
+    });
+    assert!(alerter.found_block(&share, 10, "pool", false).is_some());
+    let alerter = Alerter::new(&WebhookConfig {
+        block_difficulty: 300,
+        ..config()
+    });
+    assert!(alerter.found_block(&share, 10, "pool", false).is_none());
+}
+
+#[test]
+fn test_block_command() {
+    let block = FoundBlock {
+        height: 10,
+        difficulty: 256,
+        job_id: "10-1".to_string(),
+        nonce: "deadbeef".to_string(),
+        hash: "00".repeat(32),
+        pool: "127.0.0.1:18081".to_string(),
+    };
+    let cmd = webhook::block_command("notify.sh", &block, "rig01");
+    let env = |name: &str| {
+        cmd.get_envs()
+            .find(|(key, _)| *key == OsStr::new(name))
+            .and_then(|(_, value)| value)
+            .map(|value| value.to_str().unwrap().to_string())
+    };
+    assert_eq!(env("MITHRIL_EVENT").unwrap(), "block_found");
+    assert_eq!(env("MITHRIL_HEIGHT").unwrap(), "10");
+    assert_eq!(env("MITHRIL_DIFFICULTY").unwrap(), "256");
+    assert_eq!(env("MITHRIL_NONCE").unwrap(), "deadbeef");
+    assert_eq!(env("MITHRIL_RIG").unwrap(), "rig01");
+    assert_eq!(env("MITHRIL_MESSAGE").unwrap(), block.message());
+    assert!(cmd.get_args().any(|arg| arg == "notify.sh"));
+}
+
+//helper
+
+fn config() -> WebhookConfig {
+    WebhookConfig {
+        enabled: true,
+        url: "http://localhost/hook".to_string(),
+        events: ALL_EVENTS.to_vec(),
+        template: DEFAULT_TEMPLATE.to_string(),
+        rig_name: "".to_string(),
+        zero_hashrate_minutes: 2,
+        min_hashrate: 100.0,
+        max_reject_percent: 10.0,
+        window_minutes: 3,
+        block_difficulty: 0,
+        block_command: "".to_string(),
+    }
+}
+
+fn snapshot(total_hashes: u64, shares_accepted: u64, shares_rejected: u64) -> StatsSnapshot {
+    StatsSnapshot {
+        total_hashes,
+        shares_accepted,
+        shares_rejected,
+        ..StatsSnapshot::default()
+    }
+}
+
+fn events(alerts: &[Alert]) -> Vec<AlertEvent> {
+    alerts.iter().map(|a| a.event).collect()
+}
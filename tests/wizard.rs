@@ -0,0 +1,237 @@
+extern crate mithril;
+
+use mithril::bench::CpuInfo;
+use mithril::cgroup::Limits;
+use mithril::config_validation::{validate, ConfigSource};
+use mithril::mithril_config;
+use mithril::mithril_config::ConfigFormat;
+use mithril::pow::randomx::MemoryMode;
+use mithril::wizard::{
+    answers_from_cli, ask, hardware_summary, meminfo_total, parse_cache_size, recommended_affinity,
+    recommended_memory_mode, recommended_threads, render_config, set_value, Answers, Hardware,
+    RigKind,
+};
+
+use std::io::Cursor;
+
+#[test]
+fn test_parse_cache_size() {
+    assert_eq!(parse_cache_size("32768K\n"), Some(32 * 1024 * 1024));
+    assert_eq!(parse_cache_size("8M"), Some(8 * 1024 * 1024));
+    assert_eq!(parse_cache_size("1024"), Some(1024));
+    assert_eq!(parse_cache_size("big"), None);
+}
+
+#[test]
+fn test_meminfo_total() {
+    let meminfo = "MemTotal:       16303580 kB\nMemFree:         1234 kB\n";
+    assert_eq!(meminfo_total(meminfo), Some(16303580 * 1024));
+    assert_eq!(meminfo_total("MemFree: 1 kB\n"), None);
+}
+
+#[test]
+fn test_recommended_threads() {
+    let hw = new_hardware(16, Some(32 * 1024 * 1024));
+    assert_eq!(recommended_threads(&hw, RigKind::Dedicated), 16);
+    assert_eq!(recommended_threads(&hw, RigKind::Desktop), 8);
+
+    let small_cache = new_hardware(16, Some(8 * 1024 * 1024));
+    assert_eq!(recommended_threads(&small_cache, RigKind::Dedicated), 4);
+
+    let unknown_cache = new_hardware(6, None);
+    assert_eq!(recommended_threads(&unknown_cache, RigKind::Dedicated), 6);
+
+    let single = new_hardware(1, None);
+    assert_eq!(recommended_threads(&single, RigKind::Desktop), 1);
+
+    let mut container = new_hardware(16, Some(32 * 1024 * 1024));
+    container.limits.cpus = Some(2.5);
+    assert_eq!(recommended_threads(&container, RigKind::Dedicated), 3);
+}
+
+#[test]
+fn test_recommended_memory_mode() {
+    let hw = new_hardware(8, None);
+    assert_eq!(recommended_memory_mode(&hw), MemoryMode::Auto);
+
+    let small = Hardware {
+        memory_bytes: Some(1024 * 1024 * 1024),
+        ..hw.clone()
+    };
+    assert_eq!(recommended_memory_mode(&small), MemoryMode::Light);
+
+    let container = Hardware {
+        limits: Limits {
+            memory_bytes: Some(512 * 1024 * 1024),
+            ..Limits::default()
+        },
+        ..hw.clone()
+    };
+    assert_eq!(recommended_memory_mode(&container), MemoryMode::Light);
+
+    let unknown = Hardware {
+        memory_bytes: None,
+        ..hw
+    };
+    assert_eq!(recommended_memory_mode(&unknown), MemoryMode::Auto);
+}
+
+#[test]
+fn test_recommended_affinity() {
+    assert_eq!(
+        recommended_affinity(RigKind::Dedicated),
+        !cfg!(target_os = "macos")
+    );
+    assert!(!recommended_affinity(RigKind::Desktop));
+}
+
+#[test]
+fn test_answers_from_cli() {
+    let wallet = mithril_config::donation_conf().wallet_address.clone();
+    assert_eq!(
+        answers_from_cli(Some("pool.example.com:3333"), Some(&wallet)),
+        Some(Answers {
+            pool: "pool.example.com:3333".to_string(),
+            wallet: wallet.clone(),
+            rig: RigKind::Dedicated,
+        })
+    );
+    assert_eq!(answers_from_cli(Some("pool.example.com:3333"), None), None);
+    assert_eq!(
+        answers_from_cli(Some("pool.example.com"), Some(&wallet)),
+        None
+    );
+    assert_eq!(
+        answers_from_cli(Some("pool.example.com:3333"), Some("4wallet")),
+        None
+    );
+}
+
+#[test]
+fn test_hardware_summary() {
+    let mut hw = new_hardware(8, Some(16 * 1024 * 1024));
+    assert_eq!(
+        hardware_summary(&hw),
+        "cpu: Test CPU (4 cores, 8 threads)\nL3 cache: 16 MB\nmemory: 8192 MB"
+    );
+    hw.memory_bytes = Some(2 * 1024 * 1024 * 1024);
+    assert!(hardware_summary(&hw).contains("warning: less than the 2.3 GB"));
+
+    let mut container = new_hardware(8, None);
+    container.limits = Limits {
+        cpus: Some(2.0),
+        memory_bytes: Some(1024 * 1024 * 1024),
+    };
+    assert_eq!(
+        hardware_summary(&container),
+        "cpu: Test CPU (4 cores, 8 threads)\nmemory: 8192 MB\ncontainer cpu limit: 2 cores\n\
+         container memory limit: 1024 MB\n\
+         warning: less than the 2.3 GB that the RandomX dataset needs, hashing will be slow"
+    );
+}
+
+#[test]
+fn test_ask() {
+    let wallet = mithril_config::donation_conf().wallet_address.clone();
+    let mut input = Cursor::new(format!(
+        "stratum+tcp://pool:3333\n\nnot a wallet\n{}\nmaybe\nd\n",
+        wallet
+    ));
+    let mut output = Vec::new();
+
+    let answers = ask(&mut input, &mut output).unwrap();
+    assert_eq!(
+        answers,
+        Answers {
+            pool: "xmrpool.eu:3333".to_string(),
+            wallet,
+            rig: RigKind::Desktop,
+        }
+    );
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.matches("pool address (host:port)").count(), 2);
+    assert_eq!(output.matches("Monero wallet address").count(), 2);
+    assert!(output.contains("please answer rig or desktop"));
+}
+
+#[test]
+fn test_ask_input_closed() {
+    let mut input = Cursor::new("pool.example.com:3333\n");
+    assert!(ask(&mut input, &mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_render_config() {
+    let answers = Answers {
+        pool: "pool.example.com:3333".to_string(),
+        wallet: mithril_config::donation_conf().wallet_address.clone(),
+        rig: RigKind::Desktop,
+    };
+    let hw = new_hardware(1, None);
+    let content = render_config(&answers, &hw);
+
+    let path = std::env::temp_dir().join("mithril_test_wizard_config.toml");
+    std::fs::write(&path, &content).unwrap();
+    let config = mithril_config::read_config(&path, path.to_str().unwrap()).unwrap();
+    assert_eq!(config.pool_conf.pool_address, "pool.example.com:3333");
+    assert_eq!(config.pool_conf.wallet_address, answers.wallet);
+    assert_eq!(config.worker_conf.num_threads, 1);
+    assert!(!config.worker_conf.auto_tune);
+    assert!(!config.worker_conf.affinity);
+    assert_eq!(config.memory_conf.mode, MemoryMode::Auto);
+
+    let source = ConfigSource {
+        name: "config.toml",
+        content: &content,
+        format: ConfigFormat::Toml,
+    };
+    assert_eq!(validate(&config, &source, &[]), Vec::new());
+}
+
+#[test]
+fn test_render_config_for_small_dedicated_rig() {
+    let answers = Answers {
+        pool: "pool.example.com:3333".to_string(),
+        wallet: mithril_config::donation_conf().wallet_address.clone(),
+        rig: RigKind::Dedicated,
+    };
+    let hw = Hardware {
+        memory_bytes: Some(1024 * 1024 * 1024),
+        ..new_hardware(4, None)
+    };
+    let content = render_config(&answers, &hw);
+    let config = mithril_config::read_config_content(&content, ConfigFormat::Toml).unwrap();
+    assert_eq!(config.worker_conf.num_threads, 4);
+    assert!(config.worker_conf.auto_tune);
+    assert_eq!(config.worker_conf.affinity, !cfg!(target_os = "macos"));
+    assert_eq!(config.memory_conf.mode, MemoryMode::Light);
+}
+
+#[test]
+fn test_set_value() {
+    let content = "[a]\nkey = 1 # comment\nkey_long = 2\n\n[b]\nkey = 3\n";
+    assert_eq!(
+        set_value(content, "a", "key", "10"),
+        "[a]\nkey = 10 # comment\nkey_long = 2\n\n[b]\nkey = 3\n"
+    );
+    assert_eq!(
+        set_value(content, "b", "key", "\"x\""),
+        "[a]\nkey = 1 # comment\nkey_long = 2\n\n[b]\nkey = \"x\"\n"
+    );
+    assert_eq!(set_value(content, "c", "key", "0"), content);
+}
+
+//helper
+
+fn new_hardware(logical_cores: usize, l3_cache_bytes: Option<u64>) -> Hardware {
+    Hardware {
+        cpu: CpuInfo {
+            model: "Test CPU".to_string(),
+            physical_cores: logical_cores.div_ceil(2),
+            logical_cores,
+        },
+        l3_cache_bytes,
+        memory_bytes: Some(8 * 1024 * 1024 * 1024),
+        limits: Limits::default(),
+    }
+}
@@ -3,7 +3,18 @@
 
 extern crate mithril;
 
+extern crate crossbeam_channel;
+
+use crossbeam_channel::unbounded;
+use mithril::byte_string;
+use mithril::pow::cryptonight::{self as cn, CryptoNight};
+use mithril::profile::Priority;
+use mithril::randomx::memory::VmMemoryAllocator;
+use mithril::stratum::stratum_data::Job;
 use mithril::worker::worker_pool;
+use mithril::worker::worker_pool::WorkerConfig;
+
+use std::time::Duration;
 
 #[test]
 fn test_with_nonce() {
@@ -14,21 +25,92 @@ fn test_with_nonce() {
 }
 
 #[test]
-fn test_hash_target_value() {
+fn test_nonce_hex() {
+    assert_eq!(worker_pool::nonce_hex(666), "0000029a");
+}
+
+#[test]
+fn test_throttle_pause() {
+    let hash_time = Duration::from_millis(10);
+    assert_eq!(worker_pool::throttle_pause(hash_time, 0), Duration::ZERO);
+    assert_eq!(worker_pool::throttle_pause(hash_time, 50), hash_time);
     assert_eq!(
-        worker_pool::hash_target_value(
-            "c5c49db95a9da3f0802a34c6f97c364e7455fca7e41f72254fd4624dd2f91578"
-        ),
-        0x7815f9d24d62d44f
+        worker_pool::throttle_pause(hash_time, 90),
+        Duration::from_millis(90)
     );
 }
 
 #[test]
-fn test_job_target_value() {
-    assert_eq!(worker_pool::job_target_value("8b4f0100"), 368934881474191);
+fn test_nicehash_nonce_hex() {
+    let blob = "0606cbe692d005ecfebc7d2249d2b43535c237c02359e888b8b05d2e980c1405779241ac3ab485000000a7e62a06e71559c98a37e7b6743465f4f72e42784c5719411c935dc002e347826b05";
+    assert_eq!(worker_pool::nicehash_nonce_hex(666, blob), "00029aa7");
 }
 
 #[test]
-fn test_nonce_hex() {
-    assert_eq!(worker_pool::nonce_hex(666), "0000029a");
+fn test_set_nonce() {
+    let blob = "0606cbe692d005ecfebc7d2249d2b43535c237c02359e888b8b05d2e980c1405779241ac3ab485000000a7e62a06e71559c98a37e7b6743465f4f72e42784c5719411c935dc002e347826b05";
+    let mut bytes = byte_string::decode(blob).unwrap();
+    worker_pool::set_nonce(&mut bytes, 666, false);
+    assert_eq!(
+        byte_string::u8_array_to_string(&bytes),
+        worker_pool::with_nonce(blob, &worker_pool::nonce_hex(666))
+    );
+    let mut bytes = byte_string::decode(blob).unwrap();
+    worker_pool::set_nonce(&mut bytes, 0xfffe, true);
+    assert_eq!(
+        byte_string::u8_array_to_string(&bytes),
+        worker_pool::with_nonce(blob, &worker_pool::nicehash_nonce_hex(0xfffe, blob))
+    );
+}
+
+#[test]
+fn test_job_algorithm_switch() {
+    let (share_sndr, _share_rcvr) = unbounded();
+    let (metric_sndr, _metric_rcvr) = unbounded();
+    let mut pool = worker_pool::start(
+        0,
+        &worker_conf(),
+        &share_sndr,
+        1,
+        &metric_sndr,
+        Box::new(CryptoNight::new(cn::Variant::CnPico)),
+        VmMemoryAllocator::initial,
+    );
+
+    pool.job_change("miner", &job("cn-heavy/0"), false).unwrap();
+    assert_eq!(pool.algorithm.name(), "cn-heavy/0");
+    //the job of an unsupported algorithm is ignored
+    pool.job_change("miner", &job("rx/wow"), false).unwrap();
+    assert_eq!(pool.algorithm.name(), "cn-heavy/0");
+    //jobs without algo keep the current algorithm
+    pool.job_change("miner", &job(""), false).unwrap();
+    assert_eq!(pool.algorithm.name(), "cn-heavy/0");
+    pool.stop();
+    pool.join();
+}
+
+//helper
+
+fn job(algo: &str) -> Job {
+    Job {
+        seed_hash: String::new(),
+        height: 0,
+        blob: "00".repeat(76),
+        job_id: "1".to_string(),
+        target: "169f0200".to_string(),
+        algo: algo.to_string(),
+    }
+}
+
+fn worker_conf() -> WorkerConfig {
+    WorkerConfig {
+        num_threads: 0,
+        auto_tune: false,
+        auto_tune_interval_minutes: 15,
+        auto_tune_log: String::new(),
+        profile: String::new(),
+        priority: Priority::Normal,
+        throttle_percent: 0,
+        affinity: false,
+    }
 }